@@ -0,0 +1,74 @@
+//! ARM Data Watchpoint and Trace unit
+//!
+//! Only the cycle counter is used, to time short sequences of code precisely.
+//! It is not implemented on the Cortex-M0 and M0+.
+//!
+//! <http://infocenter.arm.com/help/index.jsp?topic=/com.arm.doc.ddi0439b/BABJFFGJ.html>
+
+use kernel::common::registers::{register_bitfields, register_structs, ReadWrite};
+use kernel::common::StaticRef;
+
+register_structs! {
+    DwtRegisters {
+        /// Control Register
+        (0x00 => ctrl: ReadWrite<u32, Control::Register>),
+
+        /// Cycle Count Register
+        (0x04 => cyccnt: ReadWrite<u32>),
+
+        (0x08 => @END),
+    }
+}
+
+register_structs! {
+    DebugRegisters {
+        /// Debug Exception and Monitor Control Register
+        (0x00 => demcr: ReadWrite<u32, DebugExceptionAndMonitorControl::Register>),
+
+        (0x04 => @END),
+    }
+}
+
+register_bitfields![u32,
+    Control [
+        /// Number of comparators implemented. Reads as zero if there are
+        /// none.
+        NUMCOMP         OFFSET(28)  NUMBITS(4),
+
+        /// Reads as one if the cycle counter is not implemented.
+        NOCYCCNT        OFFSET(25)  NUMBITS(1),
+
+        /// Enables the cycle counter.
+        CYCCNTENA       OFFSET(0)   NUMBITS(1)
+    ],
+
+    DebugExceptionAndMonitorControl [
+        /// Global enable for the DWT and ITM.
+        TRCENA          OFFSET(24)  NUMBITS(1)
+    ]
+];
+
+const DWT: StaticRef<DwtRegisters> = unsafe { StaticRef::new(0xE0001000 as *const DwtRegisters) };
+
+const DEBUG: StaticRef<DebugRegisters> =
+    unsafe { StaticRef::new(0xE000EDFC as *const DebugRegisters) };
+
+/// Start the cycle counter, from zero. Returns false if the processor has no
+/// cycle counter.
+pub unsafe fn enable_cycle_counter() -> bool {
+    DEBUG
+        .demcr
+        .modify(DebugExceptionAndMonitorControl::TRCENA::SET);
+    if DWT.ctrl.is_set(Control::NOCYCCNT) {
+        return false;
+    }
+    DWT.cyccnt.set(0);
+    DWT.ctrl.modify(Control::CYCCNTENA::SET);
+    true
+}
+
+/// The number of processor cycles since the counter was enabled, modulo
+/// 2^32.
+pub fn cycle_count() -> u32 {
+    DWT.cyccnt.get()
+}
@@ -7,6 +7,7 @@
 
 use core::fmt::Write;
 
+pub mod dwt;
 pub mod nvic;
 pub mod scb;
 pub mod support;
@@ -1,5 +1,6 @@
 //! ARM Cortex-M SysTick peripheral.
 
+use core::cmp;
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
 use kernel::common::StaticRef;
 
@@ -59,6 +60,9 @@ const BASE_ADDR: *const SystickRegisters = 0xE000E010 as *const SystickRegisters
 const SYSTICK_BASE: StaticRef<SystickRegisters> =
     unsafe { StaticRef::new(BASE_ADDR as *const SystickRegisters) };
 
+/// The reload value is 24 bits wide.
+const MAX_RELOAD: u64 = 0x00FF_FFFF;
+
 impl SysTick {
     /// Initialize the `SysTick` with default values
     ///
@@ -106,7 +110,7 @@ impl kernel::SysTick for SysTick {
             let us = us as u64;
             let hertz = self.hertz() as u64;
 
-            hertz * us / 1_000_000
+            cmp::min(hertz * us / 1_000_000, MAX_RELOAD)
         };
 
         // n.b.: 4.4.5 'hints and tips' suggests setting reload before value
@@ -116,6 +120,14 @@ impl kernel::SysTick for SysTick {
         SYSTICK_BASE.syst_cvr.set(0);
     }
 
+    fn max_us(&self) -> u32 {
+        let hertz = self.hertz() as u64;
+        if hertz == 0 {
+            return u32::max_value();
+        }
+        cmp::min(MAX_RELOAD * 1_000_000 / hertz, u32::max_value() as u64) as u32
+    }
+
     fn greater_than(&self, us: u32) -> bool {
         let tics = {
             // We need to convert from microseconds to native tics, which could overflow in 32-bit
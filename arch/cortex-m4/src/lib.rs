@@ -11,6 +11,7 @@ pub mod mpu;
 // valid on cortex-m4.
 pub use cortexm::support;
 
+pub use cortexm::dwt;
 pub use cortexm::nvic;
 pub use cortexm::print_cortexm_state as print_cortexm4_state;
 pub use cortexm::scb;
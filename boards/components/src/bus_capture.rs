@@ -0,0 +1,57 @@
+//! Component for recording SPI and I2C transactions.
+//!
+//! The second argument of the helper is the number of transactions kept.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let bus_capture = components::bus_capture::BusCaptureComponent::new(&nrf52::rtc::RTC)
+//!     .finalize(components::bus_capture_component_helper!(nrf52::rtc::Rtc, 32));
+//! mux_spi.set_capture(bus_capture);
+//! pconsole.set_bus_capture(bus_capture);
+//! ```
+
+use core::mem::MaybeUninit;
+
+use capsules::bus_capture::{BusCapture, Transaction};
+use kernel::component::Component;
+use kernel::hil::time;
+use kernel::static_init_half;
+
+// Setup static space for the objects.
+#[macro_export]
+macro_rules! bus_capture_component_helper {
+    ($T:ty, $N:expr) => {{
+        use capsules::bus_capture::{BusCapture, Transaction};
+        use core::mem::MaybeUninit;
+        static mut BUF: MaybeUninit<BusCapture<'static, $T>> = MaybeUninit::uninit();
+        static mut TRANSACTIONS: [Transaction; $N] = [Transaction::EMPTY; $N];
+        (&mut BUF, &mut TRANSACTIONS)
+    };};
+}
+
+pub struct BusCaptureComponent<T: 'static + time::Time> {
+    time: &'static T,
+}
+
+impl<T: 'static + time::Time> BusCaptureComponent<T> {
+    pub fn new(time: &'static T) -> BusCaptureComponent<T> {
+        BusCaptureComponent { time }
+    }
+}
+
+impl<T: 'static + time::Time> Component for BusCaptureComponent<T> {
+    type StaticInput = (
+        &'static mut MaybeUninit<BusCapture<'static, T>>,
+        &'static mut [Transaction],
+    );
+    type Output = &'static BusCapture<'static, T>;
+
+    unsafe fn finalize(self, static_buffer: Self::StaticInput) -> Self::Output {
+        static_init_half!(
+            static_buffer.0,
+            BusCapture<'static, T>,
+            BusCapture::new(self.time, static_buffer.1)
+        )
+    }
+}
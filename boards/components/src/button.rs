@@ -64,7 +64,12 @@ macro_rules! button_component_buf {
     };};
 }
 
-pub struct ButtonComponent<IP: 'static + gpio::InterruptPin> {
+/// The button driver produced by `ButtonComponent`. `IP` can be a trait
+/// object (`dyn kernel::hil::gpio::InterruptPin`) to mix buttons backed by
+/// different pin types.
+pub type ButtonComponentType<IP> = Button<'static, IP>;
+
+pub struct ButtonComponent<IP: 'static + gpio::InterruptPin + ?Sized> {
     board_kernel: &'static kernel::Kernel,
     button_pins: &'static [(
         &'static gpio::InterruptValueWrapper<'static, IP>,
@@ -73,7 +78,7 @@ pub struct ButtonComponent<IP: 'static + gpio::InterruptPin> {
     )],
 }
 
-impl<IP: 'static + gpio::InterruptPin> ButtonComponent<IP> {
+impl<IP: 'static + gpio::InterruptPin + ?Sized> ButtonComponent<IP> {
     pub fn new(
         board_kernel: &'static kernel::Kernel,
         button_pins: &'static [(
@@ -89,7 +94,7 @@ impl<IP: 'static + gpio::InterruptPin> ButtonComponent<IP> {
     }
 }
 
-impl<IP: 'static + gpio::InterruptPin> Component for ButtonComponent<IP> {
+impl<IP: 'static + gpio::InterruptPin + ?Sized> Component for ButtonComponent<IP> {
     type StaticInput = &'static mut MaybeUninit<Button<'static, IP>>;
     type Output = &'static Button<'static, IP>;
 
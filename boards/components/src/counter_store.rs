@@ -0,0 +1,106 @@
+//! Component for persisting event counters in nonvolatile storage.
+//!
+//! The second argument of the helper is the size of the record buffer, which
+//! must be at least 4 bytes plus 4 bytes per counter and per counter entry.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let counters = static_init!(
+//!     [&'static kernel::metrics::Counter<'static>; 1],
+//!     [flash_erase]
+//! );
+//! let counter_store = components::counter_store::CounterStoreComponent::new(
+//!     mux_alarm,
+//!     nonvolatile_storage,
+//!     counters,
+//!     0x5f000,
+//!     3600,
+//! )
+//! .finalize(components::counter_store_component_helper!(nrf52::rtc::Rtc, 520));
+//! ```
+
+use core::mem::MaybeUninit;
+
+use capsules::counter_store::CounterStore;
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use kernel::component::Component;
+use kernel::hil::nonvolatile_storage::NonvolatileStorage;
+use kernel::hil::time;
+use kernel::metrics::Counter;
+use kernel::static_init_half;
+
+// Setup static space for the objects.
+#[macro_export]
+macro_rules! counter_store_component_helper {
+    ($A:ty, $N:expr) => {{
+        use capsules::counter_store::CounterStore;
+        use capsules::virtual_alarm::VirtualMuxAlarm;
+        use core::mem::MaybeUninit;
+        static mut BUF1: MaybeUninit<VirtualMuxAlarm<'static, $A>> = MaybeUninit::uninit();
+        static mut BUF2: MaybeUninit<CounterStore<'static, VirtualMuxAlarm<'static, $A>>> =
+            MaybeUninit::uninit();
+        static mut BUFFER: [u8; $N] = [0; $N];
+        (&mut BUF1, &mut BUF2, &mut BUFFER)
+    };};
+}
+
+pub struct CounterStoreComponent<A: 'static + time::Alarm<'static>> {
+    alarm_mux: &'static MuxAlarm<'static, A>,
+    storage: &'static dyn NonvolatileStorage<'static>,
+    counters: &'static [&'static Counter<'static>],
+    address: usize,
+    period_s: u32,
+}
+
+impl<A: 'static + time::Alarm<'static>> CounterStoreComponent<A> {
+    pub fn new(
+        alarm_mux: &'static MuxAlarm<'static, A>,
+        storage: &'static dyn NonvolatileStorage<'static>,
+        counters: &'static [&'static Counter<'static>],
+        address: usize,
+        period_s: u32,
+    ) -> CounterStoreComponent<A> {
+        CounterStoreComponent {
+            alarm_mux,
+            storage,
+            counters,
+            address,
+            period_s,
+        }
+    }
+}
+
+impl<A: 'static + time::Alarm<'static>> Component for CounterStoreComponent<A> {
+    type StaticInput = (
+        &'static mut MaybeUninit<VirtualMuxAlarm<'static, A>>,
+        &'static mut MaybeUninit<CounterStore<'static, VirtualMuxAlarm<'static, A>>>,
+        &'static mut [u8],
+    );
+    type Output = &'static CounterStore<'static, VirtualMuxAlarm<'static, A>>;
+
+    unsafe fn finalize(self, static_buffer: Self::StaticInput) -> Self::Output {
+        let virtual_alarm = static_init_half!(
+            static_buffer.0,
+            VirtualMuxAlarm<'static, A>,
+            VirtualMuxAlarm::new(self.alarm_mux)
+        );
+        let counter_store = static_init_half!(
+            static_buffer.1,
+            CounterStore<'static, VirtualMuxAlarm<'static, A>>,
+            CounterStore::new(
+                self.storage,
+                virtual_alarm,
+                self.counters,
+                self.address,
+                self.period_s,
+                static_buffer.2
+            )
+        );
+
+        time::Alarm::set_client(virtual_alarm, counter_store);
+        self.storage.set_client(counter_store);
+        counter_store.restore();
+        counter_store
+    }
+}
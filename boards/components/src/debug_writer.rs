@@ -8,6 +8,14 @@
 //! ```rust
 //! DebugWriterComponent::new(uart_mux).finalize(());
 //! ```
+//!
+//! The `DebugWriter` is returned, for boards that frame the console output:
+//!
+//! ```rust
+//! let debug_writer = DebugWriterComponent::new(uart_mux).finalize(());
+//! debug_writer.set_framing(true);
+//! console.set_framing(true);
+//! ```
 
 // Author: Brad Campbell <bradjc@virginia.edu>
 // Last modified: 11/07/2019
@@ -34,7 +42,7 @@ unsafe impl capabilities::ProcessManagementCapability for Capability {}
 
 impl Component for DebugWriterComponent {
     type StaticInput = ();
-    type Output = ();
+    type Output = &'static kernel::debug::DebugWriter;
 
     unsafe fn finalize(self, _s: Self::StaticInput) -> Self::Output {
         // The sum of the output_buf and internal_buf is set to 1024 bytes in order to avoid excessive
@@ -60,5 +68,7 @@ impl Component for DebugWriterComponent {
             kernel::debug::DebugWriterWrapper::new(debugger)
         );
         kernel::debug::set_debug_writer_wrapper(debug_wrapper);
+
+        debugger
     }
 }
@@ -0,0 +1,114 @@
+//! Component for the entropy pool.
+//!
+//! This provides one Component, EntropyPoolComponent, which mixes a TRNG
+//! with the noise of an ADC channel and the time of the samples, and
+//! provides the output of its generator as an `Entropy32`. The ADC HIL
+//! has no `set_client()`, so the board sets the pool as the client of the
+//! ADC, before starting it.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let entropy_pool = components::entropy_pool::EntropyPoolComponent::new(
+//!     &nrf52::trng::TRNG,
+//!     &nrf52::adc::ADC,
+//!     &nrf52::adc::AdcChannel::VDD,
+//!     mux_alarm,
+//!     dynamic_deferred_caller,
+//! )
+//! .finalize(components::entropy_pool_component_helper!(
+//!     nrf52::rtc::Rtc,
+//!     nrf52::adc::Adc
+//! ));
+//! nrf52::adc::ADC.set_client(entropy_pool);
+//! entropy_pool.start();
+//! let mux_rng = components::rng::RngMuxComponent::new(entropy_pool).finalize(());
+//! ```
+
+use capsules::entropy_pool::EntropyPool;
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use core::mem::MaybeUninit;
+use kernel::common::dynamic_deferred_call::DynamicDeferredCall;
+use kernel::component::Component;
+use kernel::hil::adc;
+use kernel::hil::entropy::Entropy32;
+use kernel::hil::time::{self, Alarm};
+use kernel::static_init_half;
+
+// Setup static space for the objects.
+#[macro_export]
+macro_rules! entropy_pool_component_helper {
+    ($A:ty, $D:ty) => {{
+        use capsules::entropy_pool::EntropyPool;
+        use capsules::virtual_alarm::VirtualMuxAlarm;
+        use core::mem::MaybeUninit;
+        static mut BUF1: MaybeUninit<VirtualMuxAlarm<'static, $A>> = MaybeUninit::uninit();
+        static mut BUF2: MaybeUninit<EntropyPool<'static, VirtualMuxAlarm<'static, $A>, $D>> =
+            MaybeUninit::uninit();
+        (&mut BUF1, &mut BUF2)
+    };};
+}
+
+pub struct EntropyPoolComponent<A: 'static + time::Alarm<'static>, D: 'static + adc::Adc> {
+    trng: &'static dyn Entropy32<'static>,
+    adc: &'static D,
+    adc_channel: &'static D::Channel,
+    alarm_mux: &'static MuxAlarm<'static, A>,
+    deferred_caller: &'static DynamicDeferredCall,
+}
+
+impl<A: 'static + time::Alarm<'static>, D: 'static + adc::Adc> EntropyPoolComponent<A, D> {
+    pub fn new(
+        trng: &'static dyn Entropy32<'static>,
+        adc: &'static D,
+        adc_channel: &'static D::Channel,
+        alarm_mux: &'static MuxAlarm<'static, A>,
+        deferred_caller: &'static DynamicDeferredCall,
+    ) -> Self {
+        EntropyPoolComponent {
+            trng,
+            adc,
+            adc_channel,
+            alarm_mux,
+            deferred_caller,
+        }
+    }
+}
+
+impl<A: 'static + time::Alarm<'static>, D: 'static + adc::Adc> Component
+    for EntropyPoolComponent<A, D>
+{
+    type StaticInput = (
+        &'static mut MaybeUninit<VirtualMuxAlarm<'static, A>>,
+        &'static mut MaybeUninit<EntropyPool<'static, VirtualMuxAlarm<'static, A>, D>>,
+    );
+    type Output = &'static EntropyPool<'static, VirtualMuxAlarm<'static, A>, D>;
+
+    unsafe fn finalize(self, static_buffer: Self::StaticInput) -> Self::Output {
+        let alarm = static_init_half!(
+            static_buffer.0,
+            VirtualMuxAlarm<'static, A>,
+            VirtualMuxAlarm::new(self.alarm_mux)
+        );
+        let entropy_pool = static_init_half!(
+            static_buffer.1,
+            EntropyPool<'static, VirtualMuxAlarm<'static, A>, D>,
+            EntropyPool::new(
+                self.trng,
+                self.adc,
+                self.adc_channel,
+                alarm,
+                self.deferred_caller
+            )
+        );
+        entropy_pool.initialize_callback_handle(
+            self.deferred_caller
+                .register(entropy_pool)
+                .expect("no deferred call slot available for the entropy pool"),
+        );
+        self.trng.set_client(entropy_pool);
+        alarm.set_client(entropy_pool);
+
+        entropy_pool
+    }
+}
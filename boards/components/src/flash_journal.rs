@@ -0,0 +1,90 @@
+//! Component for keeping the error journal in nonvolatile storage.
+//!
+//! The second argument of the helper is the size of the journal region in
+//! bytes, which should be a multiple of the 16 byte record size.
+//!
+//! The returned journal still has to be handed to the kernel with
+//! `kernel::journal::set_journal()`.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let journal = components::flash_journal::FlashJournalComponent::new(
+//!     mux_alarm,
+//!     journal_storage,
+//!     0x5e000,
+//! )
+//! .finalize(components::flash_journal_component_helper!(nrf52::rtc::Rtc, 1024));
+//! kernel::journal::set_journal(journal);
+//! ```
+
+use core::mem::MaybeUninit;
+
+use capsules::flash_journal::FlashJournal;
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use kernel::component::Component;
+use kernel::hil::nonvolatile_storage::NonvolatileStorage;
+use kernel::hil::time;
+use kernel::static_init_half;
+
+// Setup static space for the objects.
+#[macro_export]
+macro_rules! flash_journal_component_helper {
+    ($A:ty, $N:expr) => {{
+        use capsules::flash_journal::FlashJournal;
+        use capsules::virtual_alarm::VirtualMuxAlarm;
+        use core::mem::MaybeUninit;
+        static mut BUF1: MaybeUninit<VirtualMuxAlarm<'static, $A>> = MaybeUninit::uninit();
+        static mut BUF2: MaybeUninit<FlashJournal<'static, VirtualMuxAlarm<'static, $A>>> =
+            MaybeUninit::uninit();
+        static mut BUFFER: [u8; $N] = [0xff; $N];
+        (&mut BUF1, &mut BUF2, &mut BUFFER)
+    };};
+}
+
+pub struct FlashJournalComponent<A: 'static + time::Alarm<'static>> {
+    alarm_mux: &'static MuxAlarm<'static, A>,
+    storage: &'static dyn NonvolatileStorage<'static>,
+    address: usize,
+}
+
+impl<A: 'static + time::Alarm<'static>> FlashJournalComponent<A> {
+    pub fn new(
+        alarm_mux: &'static MuxAlarm<'static, A>,
+        storage: &'static dyn NonvolatileStorage<'static>,
+        address: usize,
+    ) -> FlashJournalComponent<A> {
+        FlashJournalComponent {
+            alarm_mux,
+            storage,
+            address,
+        }
+    }
+}
+
+impl<A: 'static + time::Alarm<'static>> Component for FlashJournalComponent<A> {
+    type StaticInput = (
+        &'static mut MaybeUninit<VirtualMuxAlarm<'static, A>>,
+        &'static mut MaybeUninit<FlashJournal<'static, VirtualMuxAlarm<'static, A>>>,
+        &'static mut [u8],
+    );
+    type Output = &'static FlashJournal<'static, VirtualMuxAlarm<'static, A>>;
+
+    unsafe fn finalize(self, static_buffer: Self::StaticInput) -> Self::Output {
+        let virtual_alarm = static_init_half!(
+            static_buffer.0,
+            VirtualMuxAlarm<'static, A>,
+            VirtualMuxAlarm::new(self.alarm_mux)
+        );
+        let journal = static_init_half!(
+            static_buffer.1,
+            FlashJournal<'static, VirtualMuxAlarm<'static, A>>,
+            FlashJournal::new(self.storage, virtual_alarm, self.address, static_buffer.2)
+        );
+
+        time::Alarm::set_client(virtual_alarm, journal);
+        self.storage.set_client(journal);
+        journal.restore();
+        journal
+    }
+}
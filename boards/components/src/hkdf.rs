@@ -0,0 +1,110 @@
+//! Component for HKDF key derivation on top of an HMAC engine, with its
+//! userspace driver.
+//!
+//! Usage
+//! -----
+//! ```rust
+//!    let hkdf = components::hkdf::HkdfComponent::new(
+//!        board_kernel,
+//!        &mux_hmac,
+//!        static_init!([u8; 128], [0; 128]),
+//!        static_init!([u8; 32], [0; 32]),
+//!        static_init!([u8; 64], [0; 64]),
+//!    )
+//!    .finalize(components::hkdf_component_helper!(lowrisc::hmac::Hmac));
+//! ```
+
+use capsules::hkdf::{Hkdf, HkdfDriver, HASH_LEN};
+use capsules::virtual_hmac::MuxHmac;
+use capsules::virtual_hmac::VirtualMuxHmac;
+use core::mem::MaybeUninit;
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::create_capability;
+use kernel::hil::digest;
+use kernel::static_init_half;
+
+// Setup static space for the objects.
+#[macro_export]
+macro_rules! hkdf_component_helper {
+    ($A:ty) => {{
+        use capsules::hkdf::{Hkdf, HkdfDriver};
+        use capsules::virtual_hmac::VirtualMuxHmac;
+        use core::mem::MaybeUninit;
+        static mut BUF1: MaybeUninit<VirtualMuxHmac<'static, $A, [u8; 32]>> = MaybeUninit::uninit();
+        static mut BUF2: MaybeUninit<Hkdf<'static, VirtualMuxHmac<'static, $A, [u8; 32]>>> =
+            MaybeUninit::uninit();
+        static mut BUF3: MaybeUninit<HkdfDriver<'static, VirtualMuxHmac<'static, $A, [u8; 32]>>> =
+            MaybeUninit::uninit();
+        (&mut BUF1, &mut BUF2, &mut BUF3)
+    };};
+}
+
+pub struct HkdfComponent<A: 'static + digest::Digest<'static, [u8; HASH_LEN]>> {
+    board_kernel: &'static kernel::Kernel,
+    mux_hmac: &'static MuxHmac<'static, A, [u8; HASH_LEN]>,
+    buffer: &'static mut [u8],
+    digest_buffer: &'static mut [u8; HASH_LEN],
+    okm_buffer: &'static mut [u8],
+}
+
+impl<A: 'static + digest::Digest<'static, [u8; HASH_LEN]>> HkdfComponent<A> {
+    pub fn new(
+        board_kernel: &'static kernel::Kernel,
+        mux_hmac: &'static MuxHmac<'static, A, [u8; HASH_LEN]>,
+        buffer: &'static mut [u8],
+        digest_buffer: &'static mut [u8; HASH_LEN],
+        okm_buffer: &'static mut [u8],
+    ) -> HkdfComponent<A> {
+        HkdfComponent {
+            board_kernel,
+            mux_hmac,
+            buffer,
+            digest_buffer,
+            okm_buffer,
+        }
+    }
+}
+
+impl<A: 'static + digest::Digest<'static, [u8; HASH_LEN]> + digest::HMACSha256> Component
+    for HkdfComponent<A>
+{
+    type StaticInput = (
+        &'static mut MaybeUninit<VirtualMuxHmac<'static, A, [u8; HASH_LEN]>>,
+        &'static mut MaybeUninit<Hkdf<'static, VirtualMuxHmac<'static, A, [u8; HASH_LEN]>>>,
+        &'static mut MaybeUninit<HkdfDriver<'static, VirtualMuxHmac<'static, A, [u8; HASH_LEN]>>>,
+    );
+
+    type Output = &'static HkdfDriver<'static, VirtualMuxHmac<'static, A, [u8; HASH_LEN]>>;
+
+    unsafe fn finalize(self, s: Self::StaticInput) -> Self::Output {
+        let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+        let virtual_hmac_user = static_init_half!(
+            s.0,
+            VirtualMuxHmac<'static, A, [u8; HASH_LEN]>,
+            VirtualMuxHmac::new(self.mux_hmac)
+        );
+        virtual_hmac_user.setup();
+
+        let hkdf = static_init_half!(
+            s.1,
+            Hkdf<'static, VirtualMuxHmac<'static, A, [u8; HASH_LEN]>>,
+            Hkdf::new(virtual_hmac_user, self.buffer, self.digest_buffer)
+        );
+        digest::Digest::set_client(virtual_hmac_user, hkdf);
+
+        let hkdf_driver = static_init_half!(
+            s.2,
+            HkdfDriver<'static, VirtualMuxHmac<'static, A, [u8; HASH_LEN]>>,
+            HkdfDriver::new(
+                hkdf,
+                self.okm_buffer,
+                self.board_kernel.create_grant(&grant_cap)
+            )
+        );
+        hkdf.set_client(hkdf_driver);
+
+        hkdf_driver
+    }
+}
@@ -66,14 +66,18 @@ impl<A: 'static + digest::Digest<'static, T>, T: 'static + digest::DigestType>
     }
 }
 
-impl<A: 'static + digest::Digest<'static, T>, T: 'static + digest::DigestType> Component
-    for HmacMuxComponent<A, T>
+impl<
+        A: 'static + digest::Digest<'static, T> + digest::DigestVerify<'static, T>,
+        T: 'static + digest::DigestType,
+    > Component for HmacMuxComponent<A, T>
 {
     type StaticInput = &'static mut MaybeUninit<MuxHmac<'static, A, T>>;
     type Output = &'static MuxHmac<'static, A, T>;
 
     unsafe fn finalize(self, s: Self::StaticInput) -> Self::Output {
         let mux_hmac = static_init_half!(s, MuxHmac<'static, A, T>, MuxHmac::new(self.hmac));
+        digest::Digest::set_client(self.hmac, mux_hmac);
+        digest::DigestVerify::set_verify_client(self.hmac, mux_hmac);
 
         mux_hmac
     }
@@ -120,7 +124,11 @@ impl<A: 'static + digest::Digest<'static, T>, T: 'static + digest::DigestType> H
 }
 
 impl<
-        A: kernel::hil::digest::HMACSha256 + 'static + digest::Digest<'static, T>,
+        A: kernel::hil::digest::HMACSha256
+            + kernel::hil::digest::Sha256
+            + 'static
+            + digest::Digest<'static, T>
+            + digest::DigestVerify<'static, T>,
         T: 'static + digest::DigestType,
     > Component for HmacComponent<A, T>
 {
@@ -139,6 +147,7 @@ impl<
             VirtualMuxHmac<'static, A, T>,
             VirtualMuxHmac::new(self.mux_hmac)
         );
+        virtual_hmac_user.setup();
 
         let hmac = static_init_half!(
             s.1,
@@ -152,6 +161,7 @@ impl<
         );
 
         digest::Digest::set_client(virtual_hmac_user, hmac);
+        digest::DigestVerify::set_verify_client(virtual_hmac_user, hmac);
 
         hmac
     }
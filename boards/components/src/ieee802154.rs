@@ -27,7 +27,7 @@ use core::mem::MaybeUninit;
 use kernel::capabilities;
 use kernel::component::Component;
 use kernel::hil::radio;
-use kernel::hil::symmetric_encryption::{self, AES128Ctr, AES128, AES128CBC, AES128CCM};
+use kernel::hil::symmetric_encryption::{self, AES128Ctr, ClearKeys, AES128, AES128CBC, AES128CCM};
 use kernel::{create_capability, static_init, static_init_half};
 
 // Setup static space for the objects.
@@ -54,7 +54,7 @@ macro_rules! ieee802154_component_helper {
 
 pub struct Ieee802154Component<
     R: 'static + kernel::hil::radio::Radio,
-    A: 'static + AES128<'static> + AES128Ctr + AES128CBC,
+    A: 'static + AES128<'static> + AES128Ctr + AES128CBC + ClearKeys,
 > {
     board_kernel: &'static kernel::Kernel,
     radio: &'static R,
@@ -65,7 +65,7 @@ pub struct Ieee802154Component<
 
 impl<
         R: 'static + kernel::hil::radio::Radio,
-        A: 'static + AES128<'static> + AES128Ctr + AES128CBC,
+        A: 'static + AES128<'static> + AES128Ctr + AES128CBC + ClearKeys,
     > Ieee802154Component<R, A>
 {
     pub fn new(
@@ -97,7 +97,7 @@ static mut CRYPT_BUF: [u8; CRYPT_SIZE] = [0x00; CRYPT_SIZE];
 
 impl<
         R: 'static + kernel::hil::radio::Radio,
-        A: 'static + AES128<'static> + AES128Ctr + AES128CBC,
+        A: 'static + AES128<'static> + AES128Ctr + AES128CBC + ClearKeys,
     > Component for Ieee802154Component<R, A>
 {
     type StaticInput = (
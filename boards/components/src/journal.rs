@@ -0,0 +1,46 @@
+//! Component for the error journal syscall driver.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let journal_driver =
+//!     components::journal::JournalComponent::new(board_kernel, journal).finalize(());
+//! ```
+
+use capsules::journal::JournalDriver;
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::create_capability;
+use kernel::journal::Journal;
+use kernel::static_init;
+
+pub struct JournalComponent {
+    board_kernel: &'static kernel::Kernel,
+    journal: &'static dyn Journal,
+}
+
+impl JournalComponent {
+    pub fn new(
+        board_kernel: &'static kernel::Kernel,
+        journal: &'static dyn Journal,
+    ) -> JournalComponent {
+        JournalComponent {
+            board_kernel: board_kernel,
+            journal: journal,
+        }
+    }
+}
+
+impl Component for JournalComponent {
+    type StaticInput = ();
+    type Output = &'static JournalDriver<'static>;
+
+    unsafe fn finalize(self, _static_buffer: Self::StaticInput) -> Self::Output {
+        let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+        static_init!(
+            JournalDriver<'static>,
+            JournalDriver::new(self.journal, self.board_kernel.create_grant(&grant_cap))
+        )
+    }
+}
@@ -55,17 +55,21 @@ macro_rules! led_component_buf {
     };};
 }
 
-pub struct LedsComponent<P: 'static + kernel::hil::gpio::Pin> {
+/// The LED driver produced by `LedsComponent`. `P` can be a trait object
+/// (`dyn kernel::hil::gpio::Pin`) to mix LEDs backed by different pin types.
+pub type LedsComponentType<P> = LED<'static, P>;
+
+pub struct LedsComponent<P: 'static + kernel::hil::gpio::Pin + ?Sized> {
     pins: &'static [(&'static P, kernel::hil::gpio::ActivationMode)],
 }
 
-impl<P: 'static + kernel::hil::gpio::Pin> LedsComponent<P> {
+impl<P: 'static + kernel::hil::gpio::Pin + ?Sized> LedsComponent<P> {
     pub fn new(pins: &'static [(&'static P, kernel::hil::gpio::ActivationMode)]) -> Self {
         Self { pins }
     }
 }
 
-impl<P: 'static + kernel::hil::gpio::Pin> Component for LedsComponent<P> {
+impl<P: 'static + kernel::hil::gpio::Pin + ?Sized> Component for LedsComponent<P> {
     type StaticInput = &'static mut MaybeUninit<LED<'static, P>>;
     type Output = &'static LED<'static, P>;
 
@@ -2,23 +2,32 @@
 
 pub mod alarm;
 pub mod analog_comparator;
+pub mod bus_capture;
 pub mod button;
 pub mod console;
+pub mod counter_store;
 pub mod crc;
 pub mod debug_queue;
 pub mod debug_writer;
+pub mod entropy_pool;
+pub mod flash_journal;
 pub mod gpio;
 pub mod hd44780;
+pub mod hkdf;
 pub mod hmac;
 pub mod i2c;
 pub mod ieee802154;
 pub mod isl29035;
+pub mod journal;
 pub mod l3gd20;
 pub mod led;
 pub mod lldb;
 pub mod lsm303dlhc;
+pub mod metrics;
 pub mod mx25r6435f;
 pub mod ninedof;
+pub mod nonce;
+pub mod nonvolatile_region;
 pub mod nonvolatile_storage;
 pub mod nrf51822;
 pub mod panic_button;
@@ -26,5 +35,9 @@ pub mod process_console;
 pub mod rng;
 pub mod segger_rtt;
 pub mod si7021;
+pub mod software_aes;
+pub mod software_sha256;
+pub mod software_sha512;
 pub mod spi;
+pub mod system_events;
 pub mod temperature;
@@ -0,0 +1,82 @@
+//! Components for kernel event counters and the metrics syscall driver.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! // A counter keyed by index, e.g. by IRQ number.
+//! let interrupts = components::counter_component_helper!("irq", 48);
+//! board_kernel.register_counter(interrupts);
+//!
+//! // A counter with explicit keys, e.g. driver numbers.
+//! let syscalls = components::counter_component_helper!(
+//!     "syscall",
+//!     keys: capsules::console::DRIVER_NUM, capsules::led::DRIVER_NUM
+//! );
+//! board_kernel.set_syscall_counter(syscalls);
+//!
+//! let metrics = components::metrics::MetricsComponent::new(board_kernel).finalize(());
+//! ```
+
+use capsules::metrics;
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::create_capability;
+use kernel::static_init;
+
+#[macro_export]
+macro_rules! counter_component_helper {
+    ($name:expr, keys: $($key:expr),+ ) => {{
+        use core::cell::Cell;
+        use kernel::count_expressions;
+        use kernel::metrics::Counter;
+        use kernel::static_init;
+        const NUM_KEYS: usize = count_expressions!($($key),+);
+
+        let keys = static_init!([usize; NUM_KEYS], [$($key,)*]);
+        // An all-zero `Cell<u32>` is `Cell::new(0)`.
+        let counts = static_init!([Cell<u32>; NUM_KEYS], core::mem::zeroed());
+        static_init!(Counter<'static>, Counter::new_with_keys($name, counts, keys))
+    };};
+    ($name:expr, $N:expr) => {{
+        use core::cell::Cell;
+        use kernel::metrics::Counter;
+        use kernel::static_init;
+
+        // An all-zero `Cell<u32>` is `Cell::new(0)`.
+        let counts = static_init!([Cell<u32>; $N], core::mem::zeroed());
+        static_init!(Counter<'static>, Counter::new($name, counts))
+    };};
+}
+
+pub struct Capability;
+unsafe impl capabilities::ProcessManagementCapability for Capability {}
+
+pub struct MetricsComponent {
+    board_kernel: &'static kernel::Kernel,
+}
+
+impl MetricsComponent {
+    pub fn new(board_kernel: &'static kernel::Kernel) -> MetricsComponent {
+        MetricsComponent {
+            board_kernel: board_kernel,
+        }
+    }
+}
+
+impl Component for MetricsComponent {
+    type StaticInput = ();
+    type Output = &'static metrics::Metrics<Capability>;
+
+    unsafe fn finalize(self, _static_buffer: Self::StaticInput) -> Self::Output {
+        let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+        static_init!(
+            metrics::Metrics<Capability>,
+            metrics::Metrics::new(
+                self.board_kernel,
+                self.board_kernel.create_grant(&grant_cap),
+                Capability
+            )
+        )
+    }
+}
@@ -1,5 +1,11 @@
 //! Component for the MX25R6435F flash chip.
 //!
+//! The component reads the ID of the chip (RDID) before returning it, and
+//! returns `None` if the chip does not answer with the ID of the MX25R6435F,
+//! e.g. on a board where it is not populated. The chip is polled until it
+//! answers, so `chip` must already handle the interrupts of the SPI
+//! controller and of the alarm.
+//!
 //! Usage
 //! -----
 //! ```rust
@@ -9,12 +15,16 @@
 //!     &gpio_port[driver.chip_select] as &dyn kernel::hil::gpio::Pin,
 //!     mux_alarm,
 //!     mux_spi,
+//!     chip,
 //! )
 //! .finalize(components::mx25r6435f_component_helper!(
 //!     nrf52::spi::SPIM,
 //!     nrf52::gpio::GPIOPin,
 //!     nrf52::rtc::Rtc
 //! ));
+//! if mx25r6435f.is_none() {
+//!     debug!("No external flash");
+//! }
 //! ```
 use capsules::mx25r6435f::MX25R6435F;
 use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
@@ -23,6 +33,11 @@ use core::mem::MaybeUninit;
 use kernel::component::Component;
 use kernel::hil;
 use kernel::static_init_half;
+use kernel::{Chip, ReturnCode};
+
+/// Number of times the pending interrupts are checked while waiting for the
+/// ID, about a second at 64 MHz.
+const PROBE_POLLS: usize = 10_000_000;
 
 // Setup static space for the objects.
 #[macro_export]
@@ -50,19 +65,22 @@ pub struct Mx25r6435fComponent<
     S: 'static + hil::spi::SpiMaster,
     P: 'static + hil::gpio::Pin,
     A: 'static + hil::time::Alarm<'static>,
+    C: 'static + Chip,
 > {
     write_protect_pin: &'static P,
     hold_pin: &'static P,
     chip_select: S::ChipSelect,
     mux_alarm: &'static MuxAlarm<'static, A>,
     mux_spi: &'static MuxSpiMaster<'static, S>,
+    chip: &'static C,
 }
 
 impl<
         S: 'static + hil::spi::SpiMaster,
         P: 'static + hil::gpio::Pin,
         A: 'static + hil::time::Alarm<'static>,
-    > Mx25r6435fComponent<S, P, A>
+        C: 'static + Chip,
+    > Mx25r6435fComponent<S, P, A, C>
 {
     pub fn new(
         write_protect_pin: &'static P,
@@ -70,14 +88,40 @@ impl<
         chip_select: S::ChipSelect,
         mux_alarm: &'static MuxAlarm<'static, A>,
         mux_spi: &'static MuxSpiMaster<'static, S>,
-    ) -> Mx25r6435fComponent<S, P, A> {
+        chip: &'static C,
+    ) -> Mx25r6435fComponent<S, P, A, C> {
         Mx25r6435fComponent {
             write_protect_pin,
             hold_pin,
             chip_select,
             mux_alarm,
             mux_spi,
+            chip,
+        }
+    }
+
+    /// Read the ID of the chip, handling interrupts until it is read.
+    fn probe(
+        &self,
+        mx25r6435f: &MX25R6435F<
+            'static,
+            VirtualSpiMasterDevice<'static, S>,
+            P,
+            VirtualMuxAlarm<'static, A>,
+        >,
+    ) -> Option<[u8; 3]> {
+        if mx25r6435f.read_identification() != ReturnCode::SUCCESS {
+            return None;
+        }
+        for _ in 0..PROBE_POLLS {
+            if mx25r6435f.identification().is_some() {
+                break;
+            }
+            if self.chip.has_pending_interrupts() {
+                self.chip.service_pending_interrupts();
+            }
         }
+        mx25r6435f.identification()
     }
 }
 
@@ -85,7 +129,8 @@ impl<
         S: 'static + hil::spi::SpiMaster,
         P: 'static + hil::gpio::Pin,
         A: 'static + hil::time::Alarm<'static>,
-    > Component for Mx25r6435fComponent<S, P, A>
+        C: 'static + Chip,
+    > Component for Mx25r6435fComponent<S, P, A, C>
 {
     type StaticInput = (
         &'static mut MaybeUninit<VirtualSpiMasterDevice<'static, S>>,
@@ -94,11 +139,13 @@ impl<
             MX25R6435F<'static, VirtualSpiMasterDevice<'static, S>, P, VirtualMuxAlarm<'static, A>>,
         >,
     );
-    type Output = &'static MX25R6435F<
-        'static,
-        VirtualSpiMasterDevice<'static, S>,
-        P,
-        VirtualMuxAlarm<'static, A>,
+    type Output = Option<
+        &'static MX25R6435F<
+            'static,
+            VirtualSpiMasterDevice<'static, S>,
+            P,
+            VirtualMuxAlarm<'static, A>,
+        >,
     >;
 
     unsafe fn finalize(self, static_buffer: Self::StaticInput) -> Self::Output {
@@ -133,6 +180,11 @@ impl<
         );
         mx25r6435f_spi.set_client(mx25r6435f);
         hil::time::Alarm::set_client(mx25r6435f_virtual_alarm, mx25r6435f);
-        mx25r6435f
+
+        if self.probe(mx25r6435f) == Some(capsules::mx25r6435f::ID) {
+            Some(mx25r6435f)
+        } else {
+            None
+        }
     }
 }
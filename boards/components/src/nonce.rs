@@ -0,0 +1,78 @@
+//! Component for the nonce service.
+//!
+//! The service shares the random number generator through a `MuxRngMaster`
+//! and keeps its boot count in `storage` at `address`. It starts issuing
+//! nonces once the boot count has been updated.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let nonce = components::nonce::NonceComponent::new(
+//!     board_kernel,
+//!     mux_rng,
+//!     nonce_storage,
+//!     0x5d000,
+//! )
+//! .finalize(());
+//! ```
+
+use capsules::nonce::{NonceService, RECORD_LEN};
+use capsules::virtual_rng::{MuxRngMaster, VirtualRngMasterDevice};
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::create_capability;
+use kernel::hil::nonvolatile_storage::NonvolatileStorage;
+use kernel::hil::rng::Rng;
+use kernel::static_init;
+
+pub struct NonceComponent {
+    board_kernel: &'static kernel::Kernel,
+    mux_rng: &'static MuxRngMaster<'static>,
+    storage: &'static dyn NonvolatileStorage<'static>,
+    address: usize,
+}
+
+impl NonceComponent {
+    pub fn new(
+        board_kernel: &'static kernel::Kernel,
+        mux_rng: &'static MuxRngMaster<'static>,
+        storage: &'static dyn NonvolatileStorage<'static>,
+        address: usize,
+    ) -> NonceComponent {
+        NonceComponent {
+            board_kernel,
+            mux_rng,
+            storage,
+            address,
+        }
+    }
+}
+
+impl Component for NonceComponent {
+    type StaticInput = ();
+    type Output = &'static NonceService<'static>;
+
+    unsafe fn finalize(self, _static_buffer: Self::StaticInput) -> Self::Output {
+        let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+        let virtual_rng = static_init!(
+            VirtualRngMasterDevice<'static>,
+            VirtualRngMasterDevice::new(self.mux_rng)
+        );
+        let buffer = static_init!([u8; RECORD_LEN], [0; RECORD_LEN]);
+        let nonce = static_init!(
+            NonceService<'static>,
+            NonceService::new(
+                virtual_rng,
+                self.storage,
+                self.address,
+                buffer,
+                self.board_kernel.create_grant(&grant_cap)
+            )
+        );
+        virtual_rng.set_client(nonce);
+        self.storage.set_client(nonce);
+        nonce.restore();
+        nonce
+    }
+}
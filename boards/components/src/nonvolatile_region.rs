@@ -0,0 +1,51 @@
+//! Component for confining a kernel user of nonvolatile storage to a region.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let journal_region = components::nonvolatile_region::NonvolatileRegionComponent::new(
+//!     journal_storage,
+//!     "journal",
+//!     JOURNAL_REGION,
+//! )
+//! .finalize(());
+//! ```
+
+use capsules::nonvolatile_region::NonvolatileRegion;
+use kernel::component::Component;
+use kernel::hil::nonvolatile_storage::NonvolatileStorage;
+use kernel::static_init;
+
+pub struct NonvolatileRegionComponent {
+    storage: &'static dyn NonvolatileStorage<'static>,
+    name: &'static str,
+    region: (usize, usize),
+}
+
+impl NonvolatileRegionComponent {
+    pub fn new(
+        storage: &'static dyn NonvolatileStorage<'static>,
+        name: &'static str,
+        region: (usize, usize),
+    ) -> NonvolatileRegionComponent {
+        NonvolatileRegionComponent {
+            storage,
+            name,
+            region,
+        }
+    }
+}
+
+impl Component for NonvolatileRegionComponent {
+    type StaticInput = ();
+    type Output = &'static NonvolatileRegion<'static>;
+
+    unsafe fn finalize(self, _static_buffer: Self::StaticInput) -> Self::Output {
+        let region = static_init!(
+            NonvolatileRegion<'static>,
+            NonvolatileRegion::new(self.storage, self.name, self.region)
+        );
+        self.storage.set_client(region);
+        region
+    }
+}
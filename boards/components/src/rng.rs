@@ -1,18 +1,26 @@
 //! Component for random number generator using `Entropy32ToRandom`.
 //!
-//! This provides one Component, RngComponent, which implements a
-//! userspace syscall interface to the RNG peripheral (TRNG).
+//! This provides three Components: RngComponent, which implements a
+//! userspace syscall interface to the RNG peripheral (TRNG), and
+//! RngMuxComponent and RngDriverComponent, which do the same on a virtualized
+//! RNG so that kernel services can share the peripheral.
 //!
 //! Usage
 //! -----
 //! ```rust
 //! let rng = components::rng::RngComponent::new(board_kernel, &sam4l::trng::TRNG).finalize(());
 //! ```
+//!
+//! ```rust
+//! let mux_rng = components::rng::RngMuxComponent::new(&nrf52::trng::TRNG).finalize(());
+//! let rng = components::rng::RngDriverComponent::new(board_kernel, mux_rng).finalize(());
+//! ```
 
 // Author: Hudson Ayers <hayers@cs.stanford.edu>
 // Last modified: 07/12/2019
 
 use capsules::rng;
+use capsules::virtual_rng::{MuxRngMaster, VirtualRngMasterDevice};
 use kernel::capabilities;
 use kernel::component::Component;
 use kernel::create_capability;
@@ -61,3 +69,68 @@ impl Component for RngComponent {
         rng
     }
 }
+
+pub struct RngMuxComponent {
+    trng: &'static dyn Entropy32<'static>,
+}
+
+impl RngMuxComponent {
+    pub fn new(trng: &'static dyn Entropy32<'static>) -> RngMuxComponent {
+        RngMuxComponent { trng: trng }
+    }
+}
+
+impl Component for RngMuxComponent {
+    type StaticInput = ();
+    type Output = &'static MuxRngMaster<'static>;
+
+    unsafe fn finalize(self, _static_buffer: Self::StaticInput) -> Self::Output {
+        let entropy_to_random = static_init!(
+            rng::Entropy32ToRandom<'static>,
+            rng::Entropy32ToRandom::new(self.trng)
+        );
+        let mux_rng = static_init!(MuxRngMaster<'static>, MuxRngMaster::new(entropy_to_random));
+        self.trng.set_client(entropy_to_random);
+        entropy_to_random.set_client(mux_rng);
+
+        mux_rng
+    }
+}
+
+pub struct RngDriverComponent {
+    board_kernel: &'static kernel::Kernel,
+    mux_rng: &'static MuxRngMaster<'static>,
+}
+
+impl RngDriverComponent {
+    pub fn new(
+        board_kernel: &'static kernel::Kernel,
+        mux_rng: &'static MuxRngMaster<'static>,
+    ) -> RngDriverComponent {
+        RngDriverComponent {
+            board_kernel: board_kernel,
+            mux_rng: mux_rng,
+        }
+    }
+}
+
+impl Component for RngDriverComponent {
+    type StaticInput = ();
+    type Output = &'static rng::RngDriver<'static>;
+
+    unsafe fn finalize(self, _static_buffer: Self::StaticInput) -> Self::Output {
+        let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+        let virtual_rng = static_init!(
+            VirtualRngMasterDevice<'static>,
+            VirtualRngMasterDevice::new(self.mux_rng)
+        );
+        let rng = static_init!(
+            rng::RngDriver<'static>,
+            rng::RngDriver::new(virtual_rng, self.board_kernel.create_grant(&grant_cap))
+        );
+        virtual_rng.set_client(rng);
+
+        rng
+    }
+}
@@ -0,0 +1,41 @@
+//! Component for the software AES-128 implementation, for boards whose chip
+//! has no AES engine.
+//!
+//! The board must give the deferred caller a client slot for it.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let aes = components::software_aes::SoftwareAesComponent::new(dynamic_deferred_caller)
+//!     .finalize(());
+//! ```
+
+use capsules::software_aes::SoftwareAes;
+use kernel::common::dynamic_deferred_call::DynamicDeferredCall;
+use kernel::component::Component;
+use kernel::static_init;
+
+pub struct SoftwareAesComponent {
+    deferred_caller: &'static DynamicDeferredCall,
+}
+
+impl SoftwareAesComponent {
+    pub fn new(deferred_caller: &'static DynamicDeferredCall) -> SoftwareAesComponent {
+        SoftwareAesComponent { deferred_caller }
+    }
+}
+
+impl Component for SoftwareAesComponent {
+    type StaticInput = ();
+    type Output = &'static SoftwareAes<'static>;
+
+    unsafe fn finalize(self, _static_buffer: Self::StaticInput) -> Self::Output {
+        let aes = static_init!(SoftwareAes<'static>, SoftwareAes::new(self.deferred_caller));
+        aes.initialize_callback_handle(
+            self.deferred_caller
+                .register(aes)
+                .expect("no deferred call slot available for software AES"),
+        );
+        aes
+    }
+}
@@ -0,0 +1,44 @@
+//! Component for the software SHA-256 and HMAC-SHA-256 implementation, for
+//! boards whose chip has no hash engine.
+//!
+//! The board must give the deferred caller a client slot for it.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let sha256 = components::software_sha256::SoftwareSha256Component::new(dynamic_deferred_caller)
+//!     .finalize(());
+//! ```
+
+use capsules::sha256::SoftwareSha256;
+use kernel::common::dynamic_deferred_call::DynamicDeferredCall;
+use kernel::component::Component;
+use kernel::static_init;
+
+pub struct SoftwareSha256Component {
+    deferred_caller: &'static DynamicDeferredCall,
+}
+
+impl SoftwareSha256Component {
+    pub fn new(deferred_caller: &'static DynamicDeferredCall) -> SoftwareSha256Component {
+        SoftwareSha256Component { deferred_caller }
+    }
+}
+
+impl Component for SoftwareSha256Component {
+    type StaticInput = ();
+    type Output = &'static SoftwareSha256<'static>;
+
+    unsafe fn finalize(self, _static_buffer: Self::StaticInput) -> Self::Output {
+        let sha256 = static_init!(
+            SoftwareSha256<'static>,
+            SoftwareSha256::new(self.deferred_caller)
+        );
+        sha256.initialize_callback_handle(
+            self.deferred_caller
+                .register(sha256)
+                .expect("no deferred call slot available for software SHA-256"),
+        );
+        sha256
+    }
+}
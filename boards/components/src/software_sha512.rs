@@ -0,0 +1,64 @@
+//! Component for the software SHA-512 and SHA-384 implementation, for boards
+//! whose hash engine does not compute these digests.
+//!
+//! The board must give the deferred caller a client slot for it.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let sha512 = components::software_sha512::SoftwareSha512Component::new(
+//!     dynamic_deferred_caller,
+//! )
+//! .finalize(components::software_sha512_component_helper!(Sha512Digest));
+//! ```
+
+use capsules::sha512::{Sha512Variant, SoftwareSha512};
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use kernel::common::dynamic_deferred_call::DynamicDeferredCall;
+use kernel::component::Component;
+use kernel::static_init_half;
+
+// Setup static space for the objects.
+#[macro_export]
+macro_rules! software_sha512_component_helper {
+    ($T:ty) => {{
+        use capsules::sha512::SoftwareSha512;
+        use core::mem::MaybeUninit;
+        static mut BUF: MaybeUninit<SoftwareSha512<'static, $T>> = MaybeUninit::uninit();
+        &mut BUF
+    };};
+}
+
+pub struct SoftwareSha512Component<T: 'static + Sha512Variant> {
+    deferred_caller: &'static DynamicDeferredCall,
+    phantom: PhantomData<&'static T>,
+}
+
+impl<T: 'static + Sha512Variant> SoftwareSha512Component<T> {
+    pub fn new(deferred_caller: &'static DynamicDeferredCall) -> SoftwareSha512Component<T> {
+        SoftwareSha512Component {
+            deferred_caller,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: 'static + Sha512Variant> Component for SoftwareSha512Component<T> {
+    type StaticInput = &'static mut MaybeUninit<SoftwareSha512<'static, T>>;
+    type Output = &'static SoftwareSha512<'static, T>;
+
+    unsafe fn finalize(self, s: Self::StaticInput) -> Self::Output {
+        let sha512 = static_init_half!(
+            s,
+            SoftwareSha512<'static, T>,
+            SoftwareSha512::new(self.deferred_caller)
+        );
+        sha512.initialize_callback_handle(
+            self.deferred_caller
+                .register(sha512)
+                .expect("no deferred call slot available for software SHA-512"),
+        );
+        sha512
+    }
+}
@@ -0,0 +1,45 @@
+//! Component for the system events syscall driver.
+//!
+//! The component also sets the driver as the listener of
+//! `kernel::system_events`.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let system_events =
+//!     components::system_events::SystemEventsComponent::new(board_kernel).finalize(());
+//! ```
+
+use capsules::system_events::SystemEvents;
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::create_capability;
+use kernel::static_init;
+
+pub struct SystemEventsComponent {
+    board_kernel: &'static kernel::Kernel,
+}
+
+impl SystemEventsComponent {
+    pub fn new(board_kernel: &'static kernel::Kernel) -> SystemEventsComponent {
+        SystemEventsComponent {
+            board_kernel: board_kernel,
+        }
+    }
+}
+
+impl Component for SystemEventsComponent {
+    type StaticInput = ();
+    type Output = &'static SystemEvents;
+
+    unsafe fn finalize(self, _static_buffer: Self::StaticInput) -> Self::Output {
+        let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+        let system_events = static_init!(
+            SystemEvents,
+            SystemEvents::new(self.board_kernel.create_grant(&grant_cap))
+        );
+        kernel::system_events::set_listener(system_events);
+        system_events
+    }
+}
@@ -50,6 +50,9 @@ impl IoWrite for Writer {
 #[panic_handler]
 /// Panic handler
 pub unsafe extern "C" fn panic_fmt(pi: &PanicInfo) -> ! {
+    // Count the reset that ends this panic towards booting in safe mode.
+    nrf52840::boot_mode::BOOT_MODE.record_panic();
+
     // The nRF52840 Dongle LEDs (see back of board)
     const LED1_PIN: Pin = Pin::P0_06;
     let led = &mut led::LedLow::new(&mut nrf52840::gpio::PORT[LED1_PIN]);
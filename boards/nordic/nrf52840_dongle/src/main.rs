@@ -140,6 +140,12 @@ pub unsafe fn reset_handler() {
     let chip = static_init!(nrf52840::chip::Chip, nrf52840::chip::new());
     CHIP = Some(chip);
 
+    // Blinks the number of a failed boot check.
+    let status_led = static_init!(
+        kernel::hil::led::LedLow<'static, nrf52840::gpio::GPIOPin>,
+        kernel::hil::led::LedLow::new(&mut nrf52840::gpio::PORT[LED2_R_PIN])
+    );
+
     nrf52dk_base::setup_board(
         board_kernel,
         BUTTON_RST_PIN,
@@ -149,6 +155,7 @@ pub unsafe fn reset_handler() {
         LED2_G_PIN,
         LED2_B_PIN,
         led,
+        status_led,
         UartChannel::Pins(UartPins::new(UART_RTS, UART_TXD, UART_CTS, UART_RXD)),
         &SpiPins::new(SPI_MOSI, SPI_MISO, SPI_CLK),
         &None,
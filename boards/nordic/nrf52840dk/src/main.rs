@@ -113,6 +113,12 @@ const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultRespons
 // Number of concurrent processes this platform supports.
 const NUM_PROCS: usize = 8;
 
+// How long a process may run before being pre-empted. Compute-heavy
+// processes, e.g. doing cryptography in userspace, may need a longer
+// timeslice; the 'timeslice' counter of the process console 'metrics'
+// command shows how much of it processes use.
+const TIMESLICE_US: u32 = 10000;
+
 #[link_section = ".app_memory"]
 static mut APP_MEMORY: [u8; 0x3C000] = [0; 0x3C000];
 
@@ -149,6 +155,7 @@ pub unsafe fn reset_handler() {
     };
 
     let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    board_kernel.set_timeslice_us(TIMESLICE_US);
 
     let gpio = components::gpio::GpioComponent::new(
         board_kernel,
@@ -226,6 +233,12 @@ pub unsafe fn reset_handler() {
     let chip = static_init!(nrf52840::chip::Chip, nrf52840::chip::new());
     CHIP = Some(chip);
 
+    // Blinks the number of a failed boot check.
+    let status_led = static_init!(
+        kernel::hil::led::LedLow<'static, nrf52840::gpio::GPIOPin>,
+        kernel::hil::led::LedLow::new(&mut nrf52840::gpio::PORT[LED4_PIN])
+    );
+
     nrf52dk_base::setup_board(
         board_kernel,
         BUTTON_RST_PIN,
@@ -235,6 +248,7 @@ pub unsafe fn reset_handler() {
         LED2_PIN,
         LED3_PIN,
         led,
+        status_led,
         uart_channel,
         &SpiPins::new(SPI_MOSI, SPI_MISO, SPI_CLK),
         &Some(SpiMX25R6435FPins::new(
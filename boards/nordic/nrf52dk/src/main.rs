@@ -207,6 +207,12 @@ pub unsafe fn reset_handler() {
     let chip = static_init!(nrf52832::chip::Chip, nrf52832::chip::new());
     CHIP = Some(chip);
 
+    // Blinks the number of a failed boot check.
+    let status_led = static_init!(
+        kernel::hil::led::LedLow<'static, nrf52832::gpio::GPIOPin>,
+        kernel::hil::led::LedLow::new(&mut nrf52832::gpio::PORT[LED4_PIN])
+    );
+
     nrf52dk_base::setup_board(
         board_kernel,
         BUTTON_RST_PIN,
@@ -216,6 +222,7 @@ pub unsafe fn reset_handler() {
         LED2_PIN,
         LED3_PIN,
         led,
+        status_led,
         UartChannel::Pins(UartPins::new(UART_RTS, UART_TXD, UART_CTS, UART_RXD)),
         &SpiPins::new(SPI_MOSI, SPI_MISO, SPI_CLK),
         &None,
@@ -0,0 +1,122 @@
+//! Hardware checks run at boot, before processes are loaded.
+//!
+//! A failed check does not stop the boot: the kernel runs with the hardware
+//! that works, using fallbacks where there are some, e.g. the RC oscillator
+//! instead of the 32.768 kHz crystal. Some checks run before the console
+//! exists, so the failures are recorded and reported together once it does:
+//! each one is described on the console, and the status LED blinks as many
+//! times as the number of the lowest failed check, then pauses, until the
+//! next reset.
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::debug;
+use kernel::hil::led::Led;
+use kernel::hil::time::{Alarm, AlarmClient, Frequency};
+
+/// Number of times the pending interrupts are checked while waiting for the
+/// hardware, about a second at 64 MHz.
+pub const TIMEOUT_POLLS: usize = 10_000_000;
+
+const BLINK_MS: u32 = 200;
+const PAUSE_MS: u32 = 1000;
+
+/// The checks, numbered by the number of blinks that report them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Check {
+    LowFrequencyClock = 1,
+    HighFrequencyClock = 2,
+    Uicr = 3,
+}
+
+impl Check {
+    fn description(self) -> &'static str {
+        match self {
+            Check::LowFrequencyClock => {
+                "the 32.768 kHz crystal did not start, using the RC oscillator"
+            }
+            Check::HighFrequencyClock => {
+                "the 64 MHz crystal did not start, using the internal oscillator"
+            }
+            Check::Uicr => "the UICR does not hold the configuration written to it",
+        }
+    }
+}
+
+const CHECKS: [Check; 3] = [
+    Check::LowFrequencyClock,
+    Check::HighFrequencyClock,
+    Check::Uicr,
+];
+
+pub struct Diagnostics<'a, A: Alarm<'a>> {
+    /// The failed checks, bit `n` for check `n`.
+    failed: Cell<u8>,
+    led: TakeCell<'a, dyn Led>,
+    alarm: OptionalCell<&'a A>,
+    /// Position in the blink pattern: the LED is on at even steps, and the
+    /// pattern pauses after the last one.
+    step: Cell<usize>,
+}
+
+impl<'a, A: Alarm<'a>> Diagnostics<'a, A> {
+    pub fn new() -> Diagnostics<'a, A> {
+        Diagnostics {
+            failed: Cell::new(0),
+            led: TakeCell::empty(),
+            alarm: OptionalCell::empty(),
+            step: Cell::new(0),
+        }
+    }
+
+    pub fn fail(&self, check: Check) {
+        self.failed.set(self.failed.get() | 1 << check as u8);
+    }
+
+    pub fn failed(&self) -> bool {
+        self.failed.get() != 0
+    }
+
+    /// Print the failed checks, and start blinking `led` with `alarm` if
+    /// there are any. Must be called once the debug writer is set.
+    pub fn report(&'a self, led: &'a mut dyn Led, alarm: &'a A) {
+        if !self.failed() {
+            return;
+        }
+        for &check in CHECKS.iter() {
+            if self.failed.get() & 1 << check as u8 != 0 {
+                debug!("Boot check {} failed: {}", check as u8, check.description());
+            }
+        }
+        led.init();
+        self.led.replace(led);
+        alarm.set_client(self);
+        self.alarm.set(alarm);
+        self.fired();
+    }
+}
+
+impl<'a, A: Alarm<'a>> AlarmClient for Diagnostics<'a, A> {
+    fn fired(&self) {
+        let blinks = self.failed.get().trailing_zeros() as usize;
+        let step = self.step.get();
+        let delay_ms = if step < 2 * blinks {
+            self.led.map(|led| {
+                if step % 2 == 0 {
+                    led.on();
+                } else {
+                    led.off();
+                }
+            });
+            self.step.set(step + 1);
+            BLINK_MS
+        } else {
+            self.step.set(0);
+            PAUSE_MS
+        };
+        self.alarm.map(|alarm| {
+            let delay = delay_ms * <A::Frequency>::frequency() / 1000;
+            alarm.set_alarm(alarm.now().wrapping_add(delay));
+        });
+    }
+}
@@ -0,0 +1,32 @@
+//! The optional capsules of the board, enabled with the Cargo features of
+//! this crate, as listed by the `features` command of the process console.
+//!
+//! The flash sizes are rough figures for release builds, meant to show what
+//! is worth turning off when an image no longer fits, not exact costs.
+//! `make size` with and without a feature gives the exact figure for a
+//! given build.
+
+use capsules::process_console::Feature;
+
+pub static FEATURES: [Feature; 4] = [
+    Feature {
+        name: "ieee802154",
+        enabled: cfg!(feature = "ieee802154"),
+        flash_size: 20 * 1024,
+    },
+    Feature {
+        name: "bus_capture",
+        enabled: cfg!(feature = "bus_capture"),
+        flash_size: 3 * 1024,
+    },
+    Feature {
+        name: "journal",
+        enabled: cfg!(feature = "journal"),
+        flash_size: 6 * 1024,
+    },
+    Feature {
+        name: "nonce",
+        enabled: cfg!(feature = "nonce"),
+        flash_size: 3 * 1024,
+    },
+];
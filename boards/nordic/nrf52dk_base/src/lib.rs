@@ -7,19 +7,122 @@ use kernel::{create_capability, debug, debug_gpio, debug_verbose, static_init};
 
 use capsules::virtual_alarm::VirtualMuxAlarm;
 use kernel::capabilities;
+use kernel::common::cells::OptionalCell;
 use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
 use kernel::component::Component;
+use kernel::hil::boot_mode::{BootMode, BootModeControl};
 use nrf52::gpio::Pin;
 use nrf52::rtc::Rtc;
 use nrf52::uicr::Regulator0Output;
 
+pub mod diagnostics;
+pub mod features;
+pub mod memory_map;
 pub mod nrf52_components;
+use memory_map::{APP_STORAGE, COUNTER_REGION, JOURNAL_REGION, KERNEL_STORAGE, NONCE_REGION};
 use nrf52_components::ble::BLEComponent;
 
 // Constants related to the configuration of the 15.4 network stack
 const SRC_MAC: u16 = 0xf00f;
 const PAN_ID: u16 = 0xABCD;
 
+/// Number of NVIC interrupt lines on the nRF52 family.
+const NUM_IRQS: usize = 48;
+/// Number of internal flash pages, enough for the 1 MB of the nRF52840.
+const NUM_FLASH_PAGES: usize = 256;
+
+/// Supply voltage of the development kits, in millivolts.
+const ENERGY_VOLTAGE_MV: u32 = 3000;
+/// Coarse currents drawn by the CPU, radio, AES engine, UARTE and SPIM when
+/// active, in microamps, from the product specifications with the LDO
+/// regulator.
+const ENERGY_CURRENTS_UA: [u32; kernel::energy::SUBSYSTEMS] = [7400, 11000, 2400, 600, 1600];
+
+type BootDiagnostics = diagnostics::Diagnostics<'static, VirtualMuxAlarm<'static, Rtc<'static>>>;
+
+/// Allows selecting the mode of the next boot, from the process console and
+/// from processes through the boot mode driver. The driver also needs to
+/// look up process names to check which processes may select it.
+struct BootModeCap;
+unsafe impl capabilities::BootModeCapability for BootModeCap {}
+unsafe impl capabilities::ProcessManagementCapability for BootModeCap {}
+
+/// Soft resets for the `reset` and `bootloader` commands of the process
+/// console.
+struct ConsoleReset;
+
+impl capsules::process_console::Reset for ConsoleReset {
+    fn reset(&self) -> kernel::ReturnCode {
+        unsafe {
+            cortexm4::scb::reset();
+        }
+        kernel::ReturnCode::FAIL
+    }
+
+    fn reset_to_bootloader(&self) -> kernel::ReturnCode {
+        unsafe {
+            nrf52::boot_mode::BOOT_MODE.set_next_boot_mode(BootMode::Dfu, &BootModeCap);
+        }
+        self.reset()
+    }
+}
+
+/// Runs the interrupt latency test of the chip for the `latency` command of
+/// the process console.
+struct ConsoleLatencyTest;
+
+impl capsules::process_console::LatencyTest for ConsoleLatencyTest {
+    fn start(&self, samples: usize) -> kernel::ReturnCode {
+        unsafe { nrf52::latency::LATENCY.start(samples) }
+    }
+}
+
+type Uptime = capsules::uptime::Uptime<'static, VirtualMuxAlarm<'static, Rtc<'static>>>;
+
+/// The uptime and the clocks for the `uptime` command of the process console.
+struct ConsoleClocks {
+    uptime: &'static Uptime,
+}
+
+impl capsules::process_console::Clocks for ConsoleClocks {
+    fn uptime_us(&self) -> u64 {
+        self.uptime.now_us()
+    }
+
+    fn low_frequency_clock(&self) -> capsules::process_console::ClockStatus {
+        let clock = unsafe { &nrf52::clock::CLOCK };
+        let (source, calibrated) = match clock.low_source() {
+            nrf52::clock::LowClockSource::XTAL => ("32.768 kHz crystal", None),
+            nrf52::clock::LowClockSource::SYNTH => ("synthesized from HFCLK", None),
+            // The RC oscillator is never calibrated against the HFXO, so it
+            // is only accurate to about 500 ppm
+            _ => ("RC oscillator", Some(false)),
+        };
+        capsules::process_console::ClockStatus {
+            source,
+            running: clock.low_running(),
+            calibrated,
+        }
+    }
+
+    fn high_frequency_clock(&self) -> capsules::process_console::ClockStatus {
+        let clock = unsafe { &nrf52::clock::CLOCK };
+        let source = match clock.high_source() {
+            nrf52::clock::HighClockSource::XTAL => "64 MHz crystal",
+            nrf52::clock::HighClockSource::RC => "internal oscillator",
+        };
+        capsules::process_console::ClockStatus {
+            source,
+            running: clock.high_running(),
+            calibrated: None,
+        }
+    }
+
+    fn rtc_drift_ppm(&self) -> Option<i32> {
+        self.uptime.drift_ppm()
+    }
+}
+
 /// Pins for SPI for the flash chip MX25R6435F
 #[derive(Debug)]
 pub struct SpiMX25R6435FPins {
@@ -72,6 +175,12 @@ pub enum UartChannel<'a> {
     Rtt(components::segger_rtt::SeggerRttMemoryRefs<'a>),
 }
 
+/// LED driver type used by the nRF52 boards.
+pub type Led = components::led::LedsComponentType<nrf52::gpio::GPIOPin>;
+
+/// Button driver type used by the nRF52 boards.
+pub type Button = components::button::ButtonComponentType<nrf52::gpio::GPIOPin>;
+
 /// Supported drivers by the platform
 pub struct Platform {
     ble_radio: &'static capsules::ble_advertising_driver::BLE<
@@ -80,21 +189,32 @@ pub struct Platform {
         VirtualMuxAlarm<'static, Rtc<'static>>,
     >,
     ieee802154_radio: Option<&'static capsules::ieee802154::RadioDriver<'static>>,
-    button: &'static capsules::button::Button<'static, nrf52::gpio::GPIOPin>,
+    button: &'static Button,
     pconsole: &'static capsules::process_console::ProcessConsole<
         'static,
         components::process_console::Capability,
     >,
     console: &'static capsules::console::Console<'static>,
     gpio: &'static capsules::gpio::GPIO<'static, nrf52::gpio::GPIOPin>,
-    led: &'static capsules::led::LED<'static, nrf52::gpio::GPIOPin>,
+    led: &'static Led,
     rng: &'static capsules::rng::RngDriver<'static>,
+    aes: &'static capsules::aes::AesDriver<
+        'static,
+        capsules::virtual_aes_block::VirtualAES128Block<
+            'static,
+            capsules::crypto_registry::CryptoRegistry<'static>,
+        >,
+    >,
     temp: &'static capsules::temperature::TemperatureSensor<'static>,
     ipc: kernel::ipc::IPC,
+    metrics: &'static capsules::metrics::Metrics<components::metrics::Capability>,
+    system_events: &'static capsules::system_events::SystemEvents,
+    boot_mode: &'static capsules::boot_mode::BootModeDriver<'static, BootModeCap>,
     analog_comparator: &'static capsules::analog_comparator::AnalogComparator<
         'static,
         nrf52::acomp::Comparator<'static>,
     >,
+    adc_monitor: &'static capsules::adc_monitor::AdcMonitor<'static, nrf52::adc::Adc>,
     alarm: &'static capsules::alarm::AlarmDriver<
         'static,
         capsules::virtual_alarm::VirtualMuxAlarm<'static, nrf52::rtc::Rtc<'static>>,
@@ -102,6 +222,10 @@ pub struct Platform {
     // The nRF52dk does not have the flash chip on it, so we make this optional.
     nonvolatile_storage:
         Option<&'static capsules::nonvolatile_storage_driver::NonvolatileStorage<'static>>,
+    // The error journal is kept on the flash chip as well.
+    journal: Option<&'static capsules::journal::JournalDriver<'static>>,
+    // So is the boot count of the nonce service.
+    nonce: Option<&'static capsules::nonce::NonceService<'static>>,
 }
 
 impl kernel::Platform for Platform {
@@ -116,6 +240,7 @@ impl kernel::Platform for Platform {
             capsules::led::DRIVER_NUM => f(Some(self.led)),
             capsules::button::DRIVER_NUM => f(Some(self.button)),
             capsules::rng::DRIVER_NUM => f(Some(self.rng)),
+            capsules::aes::DRIVER_NUM => f(Some(self.aes)),
             capsules::ble_advertising_driver::DRIVER_NUM => f(Some(self.ble_radio)),
             capsules::ieee802154::DRIVER_NUM => match self.ieee802154_radio {
                 Some(radio) => f(Some(radio)),
@@ -123,10 +248,16 @@ impl kernel::Platform for Platform {
             },
             capsules::temperature::DRIVER_NUM => f(Some(self.temp)),
             capsules::analog_comparator::DRIVER_NUM => f(Some(self.analog_comparator)),
+            capsules::adc_monitor::DRIVER_NUM => f(Some(self.adc_monitor)),
             capsules::nonvolatile_storage_driver::DRIVER_NUM => {
                 f(self.nonvolatile_storage.map_or(None, |nv| Some(nv)))
             }
             kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            capsules::metrics::DRIVER_NUM => f(Some(self.metrics)),
+            capsules::system_events::DRIVER_NUM => f(Some(self.system_events)),
+            capsules::boot_mode::DRIVER_NUM => f(Some(self.boot_mode)),
+            capsules::journal::DRIVER_NUM => f(self.journal.map_or(None, |j| Some(j))),
+            capsules::nonce::DRIVER_NUM => f(self.nonce.map_or(None, |n| Some(n))),
             _ => f(None),
         }
     }
@@ -142,11 +273,12 @@ pub unsafe fn setup_board<I: nrf52::interrupt_service::InterruptService>(
     debug_pin1_index: Pin,
     debug_pin2_index: Pin,
     debug_pin3_index: Pin,
-    led: &'static capsules::led::LED<'static, nrf52::gpio::GPIOPin>,
+    led: &'static Led,
+    status_led: &'static mut dyn kernel::hil::led::Led,
     uart_channel: UartChannel<'static>,
     spi_pins: &SpiPins,
     mx25r6435f: &Option<SpiMX25R6435FPins>,
-    button: &'static capsules::button::Button<'static, nrf52::gpio::GPIOPin>,
+    button: &'static Button,
     ieee802154: bool,
     app_memory: &mut [u8],
     process_pointers: &'static mut [Option<&'static dyn kernel::procs::ProcessType>],
@@ -155,8 +287,14 @@ pub unsafe fn setup_board<I: nrf52::interrupt_service::InterruptService>(
     nfc_as_gpios: bool,
     chip: &'static nrf52::chip::NRF52<I>,
 ) {
-    nrf52_components::startup::NrfStartupComponent::new(nfc_as_gpios, button_rst_pin, reg_vout)
-        .finalize(());
+    let diagnostics = static_init!(BootDiagnostics, diagnostics::Diagnostics::new());
+    nrf52_components::startup::NrfStartupComponent::new(
+        nfc_as_gpios,
+        button_rst_pin,
+        reg_vout,
+        diagnostics,
+    )
+    .finalize(());
 
     // Create capabilities that the board needs to call certain protected kernel
     // functions.
@@ -176,6 +314,13 @@ pub unsafe fn setup_board<I: nrf52::interrupt_service::InterruptService>(
     rtc.start();
     let mux_alarm = components::alarm::AlarmMuxComponent::new(rtc)
         .finalize(components::alarm_mux_component_helper!(nrf52::rtc::Rtc));
+    let uptime_alarm = static_init!(
+        VirtualMuxAlarm<'static, Rtc<'static>>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let uptime = static_init!(Uptime, capsules::uptime::Uptime::new(uptime_alarm));
+    kernel::hil::time::Alarm::set_client(uptime_alarm, uptime);
+    uptime.start();
     let alarm = components::alarm::AlarmDriverComponent::new(board_kernel, mux_alarm)
         .finalize(components::alarm_component_helper!(nrf52::rtc::Rtc));
 
@@ -197,7 +342,7 @@ pub unsafe fn setup_board<I: nrf52::interrupt_service::InterruptService>(
     };
 
     let dynamic_deferred_call_clients =
-        static_init!([DynamicDeferredCallClientState; 2], Default::default());
+        static_init!([DynamicDeferredCallClientState; 3], Default::default());
     let dynamic_deferred_caller = static_init!(
         DynamicDeferredCall,
         DynamicDeferredCall::new(dynamic_deferred_call_clients)
@@ -213,6 +358,82 @@ pub unsafe fn setup_board<I: nrf52::interrupt_service::InterruptService>(
         components::process_console::ProcessConsoleComponent::new(board_kernel, uart_mux)
             .finalize(());
 
+    // Event counters, readable from the process console and userspace.
+    let syscall_counter = components::counter_component_helper!(
+        "syscall",
+        keys: capsules::console::DRIVER_NUM,
+        capsules::gpio::DRIVER_NUM,
+        capsules::alarm::DRIVER_NUM,
+        capsules::led::DRIVER_NUM,
+        capsules::button::DRIVER_NUM,
+        capsules::rng::DRIVER_NUM,
+        capsules::aes::DRIVER_NUM,
+        capsules::ble_advertising_driver::DRIVER_NUM,
+        capsules::ieee802154::DRIVER_NUM,
+        capsules::temperature::DRIVER_NUM,
+        capsules::analog_comparator::DRIVER_NUM,
+        capsules::adc_monitor::DRIVER_NUM,
+        capsules::nonvolatile_storage_driver::DRIVER_NUM,
+        kernel::ipc::DRIVER_NUM,
+        capsules::metrics::DRIVER_NUM,
+        capsules::journal::DRIVER_NUM,
+        capsules::system_events::DRIVER_NUM,
+        capsules::nonce::DRIVER_NUM,
+        capsules::boot_mode::DRIVER_NUM
+    );
+    board_kernel.set_syscall_counter(syscall_counter);
+    let timeslice_counter =
+        components::counter_component_helper!("timeslice", kernel::Kernel::TIMESLICE_COUNTER_LEN);
+    board_kernel.set_timeslice_counter(timeslice_counter);
+    let interrupt_counter = components::counter_component_helper!("irq", NUM_IRQS);
+    board_kernel.register_counter(interrupt_counter);
+    chip.set_interrupt_counter(interrupt_counter);
+    let nvmc_erase_counter = components::counter_component_helper!("nvmc_erase", NUM_FLASH_PAGES);
+    board_kernel.register_counter(nvmc_erase_counter);
+    nrf52::nvmc::NVMC.set_erase_counter(nvmc_erase_counter);
+    let aes_counter = components::counter_component_helper!("aes", nrf52::aes::COUNTER_LEN);
+    board_kernel.register_counter(aes_counter);
+    nrf52::aes::AESECB.set_counter(aes_counter);
+    // Energy estimates. The uptime alarm wakes the kernel often enough for
+    // the RTC not to wrap between two updates.
+    let energy_time_counter =
+        components::counter_component_helper!("energy_ms", kernel::energy::SUBSYSTEMS);
+    board_kernel.register_counter(energy_time_counter);
+    let energy_counter =
+        components::counter_component_helper!("energy_mj", kernel::energy::SUBSYSTEMS);
+    board_kernel.register_counter(energy_counter);
+    let energy_meter = static_init!(
+        kernel::energy::EnergyMeter<'static, Rtc<'static>>,
+        kernel::energy::EnergyMeter::new(
+            &nrf52::rtc::RTC,
+            ENERGY_VOLTAGE_MV,
+            ENERGY_CURRENTS_UA,
+            energy_time_counter,
+            energy_counter,
+        )
+    );
+    energy_meter.start();
+    board_kernel.set_activity(energy_meter);
+    nrf52::ble_radio::RADIO.set_activity(energy_meter);
+    nrf52::ieee802154_radio::RADIO.set_activity(energy_meter);
+    nrf52::aes::AESECB.set_activity(energy_meter);
+    nrf52::uart::UARTE0.set_activity(energy_meter);
+    nrf52::spi::SPIM0.set_activity(energy_meter);
+    let metrics = components::metrics::MetricsComponent::new(board_kernel).finalize(());
+    let system_events =
+        components::system_events::SystemEventsComponent::new(board_kernel).finalize(());
+    let boot_mode = static_init!(
+        capsules::boot_mode::BootModeDriver<'static, BootModeCap>,
+        // Processes can read the boot mode, but only the process console can
+        // select it. Name the processes that may select it here.
+        capsules::boot_mode::BootModeDriver::new(
+            board_kernel,
+            &nrf52::boot_mode::BOOT_MODE,
+            &[],
+            BootModeCap
+        )
+    );
+
     // Setup the console.
     let console = components::console::ConsoleComponent::new(board_kernel, uart_mux).finalize(());
     // Create the debugger object that handles calls to `debug!()`.
@@ -221,17 +442,26 @@ pub unsafe fn setup_board<I: nrf52::interrupt_service::InterruptService>(
     let ble_radio =
         BLEComponent::new(board_kernel, &nrf52::ble_radio::RADIO, mux_alarm).finalize(());
 
-    let ieee802154_radio = if ieee802154 {
+    // AES-128, for 802.15.4 and userspace, runs on the ECB peripheral. Bind
+    // it to `capsules::software_aes::SoftwareAes` instead to leave the
+    // peripheral to the Bluetooth stack.
+    let crypto = static_init!(
+        capsules::crypto_registry::CryptoRegistry<'static>,
+        capsules::crypto_registry::CryptoRegistry::new()
+    );
+    crypto.bind_aes128(&nrf52::aes::AESECB);
+
+    let ieee802154_radio = if cfg!(feature = "ieee802154") && ieee802154 {
         let (radio, _mux_mac) = components::ieee802154::Ieee802154Component::new(
             board_kernel,
             &nrf52::ieee802154_radio::RADIO,
-            &nrf52::aes::AESECB,
+            crypto,
             PAN_ID,
             SRC_MAC,
         )
         .finalize(components::ieee802154_component_helper!(
             nrf52::ieee802154_radio::Radio,
-            nrf52::aes::AesECB<'static>
+            capsules::crypto_registry::CryptoRegistry<'static>
         ));
         Some(radio)
     } else {
@@ -242,54 +472,264 @@ pub unsafe fn setup_board<I: nrf52::interrupt_service::InterruptService>(
         components::temperature::TemperatureComponent::new(board_kernel, &nrf52::temperature::TEMP)
             .finalize(());
 
-    let rng = components::rng::RngComponent::new(board_kernel, &nrf52::trng::TRNG).finalize(());
+    // Mix the TRNG with the noise of the supply voltage and of the radio,
+    // and share the result between the userspace driver and the nonce
+    // service.
+    let entropy_pool = components::entropy_pool::EntropyPoolComponent::new(
+        &nrf52::trng::TRNG,
+        &nrf52::adc::ADC,
+        &nrf52::adc::AdcChannel::VDD,
+        mux_alarm,
+        dynamic_deferred_caller,
+    )
+    .finalize(components::entropy_pool_component_helper!(
+        nrf52::rtc::Rtc,
+        nrf52::adc::Adc
+    ));
+    nrf52::adc::ADC.set_client(entropy_pool);
+    nrf52::ieee802154_radio::RADIO.set_entropy_accumulator(entropy_pool);
+    entropy_pool.start();
+    let mux_rng = components::rng::RngMuxComponent::new(entropy_pool).finalize(());
+    let rng = components::rng::RngDriverComponent::new(board_kernel, mux_rng).finalize(());
+
+    // Userspace AES only uses single blocks of the ECB peripheral, so it does
+    // not get in the way of the 802.15.4 stack. Other kernel users of single
+    // blocks get their own user of the mux.
+    let mux_aes = static_init!(
+        capsules::virtual_aes_block::MuxAES128Block<
+            'static,
+            capsules::crypto_registry::CryptoRegistry<'static>,
+        >,
+        capsules::virtual_aes_block::MuxAES128Block::new(crypto)
+    );
+    kernel::hil::symmetric_encryption::AES128Block::set_block_client(crypto, mux_aes);
+    let virtual_aes = static_init!(
+        capsules::virtual_aes_block::VirtualAES128Block<
+            'static,
+            capsules::crypto_registry::CryptoRegistry<'static>,
+        >,
+        capsules::virtual_aes_block::VirtualAES128Block::new(mux_aes)
+    );
+    virtual_aes.setup();
+    let aes_queue = static_init!(
+        [OptionalCell<(kernel::AppId, usize)>; 4],
+        [
+            OptionalCell::empty(),
+            OptionalCell::empty(),
+            OptionalCell::empty(),
+            OptionalCell::empty()
+        ]
+    );
+    let aes = static_init!(
+        capsules::aes::AesDriver<
+            'static,
+            capsules::virtual_aes_block::VirtualAES128Block<
+                'static,
+                capsules::crypto_registry::CryptoRegistry<'static>,
+            >,
+        >,
+        capsules::aes::AesDriver::new(
+            virtual_aes,
+            aes_queue,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    kernel::hil::symmetric_encryption::AES128Block::set_block_client(virtual_aes, aes);
 
     // SPI
     let mux_spi = components::spi::SpiMuxComponent::new(&nrf52::spi::SPIM0)
         .finalize(components::spi_mux_component_helper!(nrf52::spi::SPIM));
 
+    // Record SPI transactions for the 'bus' command of the process console.
+    if cfg!(feature = "bus_capture") {
+        let bus_capture =
+            components::bus_capture::BusCaptureComponent::new(&nrf52::rtc::RTC).finalize(
+                components::bus_capture_component_helper!(nrf52::rtc::Rtc, 32),
+            );
+        mux_spi.set_capture(bus_capture);
+        pconsole.set_bus_capture(bus_capture);
+    }
+    pconsole.set_reset(static_init!(ConsoleReset, ConsoleReset));
+    pconsole.set_features(&features::FEATURES);
+    pconsole.set_clocks(static_init!(ConsoleClocks, ConsoleClocks { uptime }));
+    pconsole.set_latency_test(&ConsoleLatencyTest);
+
     nrf52::spi::SPIM0.configure(
         nrf52::pinmux::Pinmux::new(spi_pins.mosi as u32),
         nrf52::pinmux::Pinmux::new(spi_pins.miso as u32),
         nrf52::pinmux::Pinmux::new(spi_pins.clk as u32),
     );
 
-    let nonvolatile_storage: Option<
-        &'static capsules::nonvolatile_storage_driver::NonvolatileStorage<'static>,
-    > = if let Some(driver) = mx25r6435f {
-        let mx25r6435f = components::mx25r6435f::Mx25r6435fComponent::new(
+    // The footprint of the flash chip may not be populated, in which case
+    // the drivers that use it are left out.
+    let external_flash = mx25r6435f.as_ref().and_then(|driver| {
+        components::mx25r6435f::Mx25r6435fComponent::new(
             &gpio_port[driver.write_protect_pin],
             &gpio_port[driver.hold_pin],
             &gpio_port[driver.chip_select] as &dyn kernel::hil::gpio::Pin,
             mux_alarm,
             mux_spi,
+            chip,
         )
         .finalize(components::mx25r6435f_component_helper!(
             nrf52::spi::SPIM,
             nrf52::gpio::GPIOPin,
             nrf52::rtc::Rtc
-        ));
+        ))
+    });
+
+    let (nonvolatile_storage, journal, nonce): (
+        Option<&'static capsules::nonvolatile_storage_driver::NonvolatileStorage<'static>>,
+        Option<&'static capsules::journal::JournalDriver<'static>>,
+        Option<&'static capsules::nonce::NonceService<'static>>,
+    ) = if let Some(mx25r6435f) = external_flash {
+        // Track erases per 64 kB block.
+        let mx25r6435f_erase_counter =
+            components::counter_component_helper!("mx25r6435f_erase", 128);
+        board_kernel.register_counter(mx25r6435f_erase_counter);
+        mx25r6435f.set_erase_counter(mx25r6435f_erase_counter);
+
+        // Share the flash chip between the storage driver, the journal and
+        // the nonce service.
+        type Mx25r6435f = capsules::mx25r6435f::MX25R6435F<
+            'static,
+            capsules::virtual_spi::VirtualSpiMasterDevice<'static, nrf52::spi::SPIM>,
+            nrf52::gpio::GPIOPin,
+            VirtualMuxAlarm<'static, nrf52::rtc::Rtc<'static>>,
+        >;
+        let mux_flash = static_init!(
+            capsules::virtual_flash::MuxFlash<'static, Mx25r6435f>,
+            capsules::virtual_flash::MuxFlash::new(mx25r6435f)
+        );
+        kernel::hil::flash::HasClient::set_client(mx25r6435f, mux_flash);
+        let storage_flash = static_init!(
+            capsules::virtual_flash::FlashUser<'static, Mx25r6435f>,
+            capsules::virtual_flash::FlashUser::new(mux_flash)
+        );
+        let journal_flash = static_init!(
+            capsules::virtual_flash::FlashUser<'static, Mx25r6435f>,
+            capsules::virtual_flash::FlashUser::new(mux_flash)
+        );
+        let nonce_flash = static_init!(
+            capsules::virtual_flash::FlashUser<'static, Mx25r6435f>,
+            capsules::virtual_flash::FlashUser::new(mux_flash)
+        );
 
         let nonvolatile_storage =
             components::nonvolatile_storage::NonvolatileStorageComponent::new(
                 board_kernel,
-                mx25r6435f,
-                0x60000, // Start address for userspace accessible region
-                0x20000, // Length of userspace accessible region
-                0,       // Start address of kernel region
-                0x60000, // Length of kernel region
+                storage_flash,
+                APP_STORAGE.0,
+                APP_STORAGE.1,
+                KERNEL_STORAGE.0,
+                KERNEL_STORAGE.1,
             )
             .finalize(components::nv_storage_component_helper!(
-                capsules::mx25r6435f::MX25R6435F<
+                capsules::virtual_flash::FlashUser<'static, Mx25r6435f>
+            ));
+
+        // Keep the erase counts in the last sector of the kernel region.
+        let counter_region = components::nonvolatile_region::NonvolatileRegionComponent::new(
+            nonvolatile_storage,
+            "counters",
+            COUNTER_REGION,
+        )
+        .finalize(());
+        let erase_counters = static_init!(
+            [&'static kernel::metrics::Counter<'static>; 2],
+            [nvmc_erase_counter, mx25r6435f_erase_counter]
+        );
+        components::counter_store::CounterStoreComponent::new(
+            mux_alarm,
+            counter_region,
+            erase_counters,
+            0,    // Address of the erase counts in their region
+            3600, // Write the erase counts at most once per hour
+        )
+        .finalize(components::counter_store_component_helper!(
+            nrf52::rtc::Rtc,
+            4 + 4 * (2 + NUM_FLASH_PAGES + 128)
+        ));
+
+        let journal = if cfg!(feature = "journal") {
+            // Keep the error journal in the sector before the erase counts.
+            let journal_pagebuffer = static_init!(
+                capsules::mx25r6435f::Mx25r6435fSector,
+                capsules::mx25r6435f::Mx25r6435fSector::default()
+            );
+            let journal_storage = static_init!(
+                capsules::nonvolatile_to_pages::NonvolatileToPages<
                     'static,
-                    capsules::virtual_spi::VirtualSpiMasterDevice<'static, nrf52::spi::SPIM>,
-                    nrf52::gpio::GPIOPin,
-                    VirtualMuxAlarm<'static, nrf52::rtc::Rtc>,
-                >
+                    capsules::virtual_flash::FlashUser<'static, Mx25r6435f>,
+                >,
+                capsules::nonvolatile_to_pages::NonvolatileToPages::new(
+                    journal_flash,
+                    journal_pagebuffer
+                )
+            );
+            kernel::hil::flash::HasClient::set_client(journal_flash, journal_storage);
+            let journal_region = components::nonvolatile_region::NonvolatileRegionComponent::new(
+                journal_storage,
+                "journal",
+                JOURNAL_REGION,
+            )
+            .finalize(());
+            let flash_journal = components::flash_journal::FlashJournalComponent::new(
+                mux_alarm,
+                journal_region,
+                0, // Start of the journal in its region
+            )
+            .finalize(components::flash_journal_component_helper!(
+                nrf52::rtc::Rtc,
+                1024
             ));
-        Some(nonvolatile_storage)
+            kernel::journal::set_journal(flash_journal);
+            let journal = components::journal::JournalComponent::new(board_kernel, flash_journal)
+                .finalize(());
+            Some(journal)
+        } else {
+            None
+        };
+
+        let nonce = if cfg!(feature = "nonce") {
+            // Keep the boot count of the nonce service in the sector before the
+            // journal.
+            let nonce_pagebuffer = static_init!(
+                capsules::mx25r6435f::Mx25r6435fSector,
+                capsules::mx25r6435f::Mx25r6435fSector::default()
+            );
+            let nonce_storage = static_init!(
+                capsules::nonvolatile_to_pages::NonvolatileToPages<
+                    'static,
+                    capsules::virtual_flash::FlashUser<'static, Mx25r6435f>,
+                >,
+                capsules::nonvolatile_to_pages::NonvolatileToPages::new(
+                    nonce_flash,
+                    nonce_pagebuffer
+                )
+            );
+            kernel::hil::flash::HasClient::set_client(nonce_flash, nonce_storage);
+            let nonce_region = components::nonvolatile_region::NonvolatileRegionComponent::new(
+                nonce_storage,
+                "nonce",
+                NONCE_REGION,
+            )
+            .finalize(());
+            let nonce = components::nonce::NonceComponent::new(
+                board_kernel,
+                mux_rng,
+                nonce_region,
+                0, // Address of the boot count in its region
+            )
+            .finalize(());
+            Some(nonce)
+        } else {
+            None
+        };
+
+        (Some(nonvolatile_storage), journal, nonce)
     } else {
-        None
+        (None, None, None)
     };
 
     // Initialize AC using AIN5 (P0.29) as VIN+ and VIN- as AIN0 (P0.02)
@@ -300,7 +740,32 @@ pub unsafe fn setup_board<I: nrf52::interrupt_service::InterruptService>(
     )
     .finalize(components::acomp_component_buf!(nrf52::acomp::Comparator));
 
-    nrf52_components::NrfClockComponent::new().finalize(());
+    // Threshold monitoring of the analog inputs and of the supply voltage.
+    let adc_channels = static_init!(
+        [&'static nrf52::adc::AdcChannel; 9],
+        [
+            &nrf52::adc::AdcChannel::AnalogInput0,
+            &nrf52::adc::AdcChannel::AnalogInput1,
+            &nrf52::adc::AdcChannel::AnalogInput2,
+            &nrf52::adc::AdcChannel::AnalogInput3,
+            &nrf52::adc::AdcChannel::AnalogInput4,
+            &nrf52::adc::AdcChannel::AnalogInput5,
+            &nrf52::adc::AdcChannel::AnalogInput6,
+            &nrf52::adc::AdcChannel::AnalogInput7,
+            &nrf52::adc::AdcChannel::VDD,
+        ]
+    );
+    let adc_monitor = static_init!(
+        capsules::adc_monitor::AdcMonitor<'static, nrf52::adc::Adc>,
+        capsules::adc_monitor::AdcMonitor::new(
+            &nrf52::adc::ADC,
+            adc_channels,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    nrf52::adc::ADC.set_limit_client(adc_monitor);
+
+    nrf52_components::NrfClockComponent::new(diagnostics).finalize(());
 
     let platform = Platform {
         button,
@@ -311,16 +776,34 @@ pub unsafe fn setup_board<I: nrf52::interrupt_service::InterruptService>(
         led,
         gpio,
         rng,
+        aes,
         temp,
         alarm,
         analog_comparator,
+        adc_monitor,
         nonvolatile_storage,
+        journal,
+        nonce,
         ipc: kernel::ipc::IPC::new(board_kernel, &memory_allocation_capability),
+        metrics,
+        system_events,
+        boot_mode,
     };
 
     platform.pconsole.start();
     debug!("Initialization complete. Entering main loop\r");
     debug!("{}", &nrf52::ficr::FICR_INSTANCE);
+    if mx25r6435f.is_some() && external_flash.is_none() {
+        debug!("MX25R6435F not found, running without external storage");
+    }
+    memory_map::print(app_memory, external_flash.is_some());
+    if diagnostics.failed() {
+        let diagnostics_alarm = static_init!(
+            VirtualMuxAlarm<'static, Rtc<'static>>,
+            VirtualMuxAlarm::new(mux_alarm)
+        );
+        diagnostics.report(status_led, diagnostics_alarm);
+    }
 
     extern "C" {
         /// Beginning of the ROM region containing app images.
@@ -331,22 +814,30 @@ pub unsafe fn setup_board<I: nrf52::interrupt_service::InterruptService>(
         /// This symbol is defined in the linker script.
         static _eapps: u8;
     }
-    kernel::procs::load_processes(
-        board_kernel,
-        chip,
-        core::slice::from_raw_parts(
-            &_sapps as *const u8,
-            &_eapps as *const u8 as usize - &_sapps as *const u8 as usize,
-        ),
-        app_memory,
-        process_pointers,
-        app_fault_response,
-        &process_management_capability,
-    )
-    .unwrap_or_else(|err| {
-        debug!("Error loading processes!");
-        debug!("{:?}", err);
-    });
+    if nrf52::boot_mode::BOOT_MODE.boot_mode() == BootMode::SafeMode {
+        debug!("Safe mode, not starting processes.");
+        let crash_count = nrf52::boot_mode::BOOT_MODE.crash_count();
+        if crash_count > 0 {
+            debug!("{} consecutive crashes before this boot.", crash_count);
+        }
+    } else {
+        kernel::procs::load_processes(
+            board_kernel,
+            chip,
+            core::slice::from_raw_parts(
+                &_sapps as *const u8,
+                &_eapps as *const u8 as usize - &_sapps as *const u8 as usize,
+            ),
+            app_memory,
+            process_pointers,
+            app_fault_response,
+            &process_management_capability,
+        )
+        .unwrap_or_else(|err| {
+            debug!("Error loading processes!");
+            debug!("{:?}", err);
+        });
+    }
 
     board_kernel.kernel_loop(&platform, chip, Some(&platform.ipc), &main_loop_capability);
 }
@@ -0,0 +1,91 @@
+//! Memory map of the nRF52 development kits.
+//!
+//! The internal flash and RAM are laid out by the chip linker scripts
+//! (`nrf52832_chip_layout.ld` and `nrf52840_chip_layout.ld`), so their
+//! boundaries are read from the linker symbols. The partitions of the
+//! external MX25R6435F flash are declared here, and the build fails if they
+//! overlap or do not fit in the chip.
+//!
+//! `print()` writes the whole map to the debug output at boot.
+
+use kernel::debug;
+
+/// Size of the MX25R6435F, 64 Mbit.
+pub const MX25R6435F_SIZE: usize = 0x800000;
+
+// Partitions of the MX25R6435F.
+capsules::nonvolatile_layout! {
+    MX25R6435F_SIZE;
+    pub KERNEL_STORAGE = (0x0, 0x60000),
+    pub APP_STORAGE = (0x60000, 0x20000),
+}
+
+// Regions of the kernel partition, each used by one kernel service.
+capsules::nonvolatile_layout! {
+    KERNEL_STORAGE.1;
+    pub NONCE_REGION = (0x5d000, 0x1000),
+    pub JOURNAL_REGION = (0x5e000, 0x1000),
+    pub COUNTER_REGION = (0x5f000, 0x1000),
+}
+
+/// Partitions and regions of the MX25R6435F, with the regions of the kernel
+/// partition given as absolute addresses.
+const EXTERNAL_FLASH: [(&str, (usize, usize)); 5] = [
+    ("nonce", (KERNEL_STORAGE.0 + NONCE_REGION.0, NONCE_REGION.1)),
+    (
+        "journal",
+        (KERNEL_STORAGE.0 + JOURNAL_REGION.0, JOURNAL_REGION.1),
+    ),
+    (
+        "counters",
+        (KERNEL_STORAGE.0 + COUNTER_REGION.0, COUNTER_REGION.1),
+    ),
+    ("kernel storage", KERNEL_STORAGE),
+    ("app storage", APP_STORAGE),
+];
+
+extern "C" {
+    /// Beginning of the kernel code.
+    static _stext: u8;
+    /// Beginning of the on-chip kernel storage.
+    static _sstorage: u8;
+    /// End of the on-chip kernel storage.
+    static _estorage: u8;
+    /// Beginning of the ROM region containing app images.
+    static _sapps: u8;
+    /// End of the ROM region containing app images.
+    static _eapps: u8;
+    /// Beginning of the kernel stack, the first kernel data in RAM.
+    static _sstack: u8;
+    /// End of the zeroed kernel data, the last kernel data in RAM.
+    static _ezero: u8;
+}
+
+fn print_region(name: &str, start: usize, end: usize) {
+    debug!("  {:<16}{:#010x}-{:#010x}", name, start, end);
+}
+
+/// Print the memory map. `app_memory` is the RAM given to processes, and
+/// `external_flash` tells whether the board has an MX25R6435F.
+pub unsafe fn print(app_memory: &[u8], external_flash: bool) {
+    let addr = |symbol: &u8| symbol as *const u8 as usize;
+
+    debug!("Internal flash:");
+    print_region("kernel", addr(&_stext), addr(&_sstorage));
+    print_region("kernel storage", addr(&_sstorage), addr(&_estorage));
+    print_region("apps", addr(&_sapps), addr(&_eapps));
+    debug!("RAM:");
+    print_region("kernel", addr(&_sstack), addr(&_ezero));
+    let app_memory_start = app_memory.as_ptr() as usize;
+    print_region(
+        "apps",
+        app_memory_start,
+        app_memory_start + app_memory.len(),
+    );
+    if external_flash {
+        debug!("MX25R6435F:");
+        for (name, (start, length)) in EXTERNAL_FLASH.iter() {
+            print_region(name, *start, start + length);
+        }
+    }
+}
@@ -1,5 +1,7 @@
 //! Component for starting up nrf52 platforms.
 
+use crate::diagnostics::{self, Check};
+use crate::BootDiagnostics;
 use kernel::component::Component;
 use nrf52::gpio::Pin;
 use nrf52::uicr::Regulator0Output;
@@ -8,14 +10,21 @@ pub struct NrfStartupComponent {
     nfc_as_gpios: bool,
     button_rst_pin: Pin,
     reg_vout: Regulator0Output,
+    diagnostics: &'static BootDiagnostics,
 }
 
 impl NrfStartupComponent {
-    pub fn new(nfc_as_gpios: bool, button_rst_pin: Pin, reg_vout: Regulator0Output) -> Self {
+    pub fn new(
+        nfc_as_gpios: bool,
+        button_rst_pin: Pin,
+        reg_vout: Regulator0Output,
+        diagnostics: &'static BootDiagnostics,
+    ) -> Self {
         Self {
             nfc_as_gpios,
             button_rst_pin,
             reg_vout,
+            diagnostics,
         }
     }
 }
@@ -82,6 +91,17 @@ impl Component for NrfStartupComponent {
             needs_soft_reset = true;
         }
 
+        // Resetting would not help if the writes did not go through, and
+        // would then be repeated on every boot.
+        let configured = uicr.get_psel0_reset_pin() == Some(self.button_rst_pin)
+            && uicr.get_psel1_reset_pin() == Some(self.button_rst_pin)
+            && uicr.get_vout() == self.reg_vout
+            && (!self.nfc_as_gpios || uicr.is_nfc_pins_protection_enabled());
+        if !configured {
+            self.diagnostics.fail(Check::Uicr);
+            return;
+        }
+
         // Any modification of UICR needs a soft reset for the changes to be taken into account.
         if needs_soft_reset {
             cortexm4::scb::reset();
@@ -89,14 +109,21 @@ impl Component for NrfStartupComponent {
     }
 }
 
-pub struct NrfClockComponent {}
+pub struct NrfClockComponent {
+    diagnostics: &'static BootDiagnostics,
+}
 
 impl NrfClockComponent {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(diagnostics: &'static BootDiagnostics) -> Self {
+        Self { diagnostics }
     }
 }
 
+/// Poll `started` until it returns true, for at most `TIMEOUT_POLLS` polls.
+fn wait_started(started: impl Fn() -> bool) -> bool {
+    (0..diagnostics::TIMEOUT_POLLS).any(|_| started())
+}
+
 impl Component for NrfClockComponent {
     type StaticInput = ();
     type Output = ();
@@ -109,7 +136,17 @@ impl Component for NrfClockComponent {
         nrf52::clock::CLOCK.low_set_source(nrf52::clock::LowClockSource::XTAL);
         nrf52::clock::CLOCK.low_start();
         nrf52::clock::CLOCK.high_start();
-        while !nrf52::clock::CLOCK.low_started() {}
-        while !nrf52::clock::CLOCK.high_started() {}
+        if !wait_started(|| nrf52::clock::CLOCK.low_started()) {
+            self.diagnostics.fail(Check::LowFrequencyClock);
+            nrf52::clock::CLOCK.low_stop();
+            nrf52::clock::CLOCK.low_set_source(nrf52::clock::LowClockSource::RC);
+            nrf52::clock::CLOCK.low_start();
+            // The RC oscillator is part of the chip and always starts.
+            while !nrf52::clock::CLOCK.low_started() {}
+        }
+        if !wait_started(|| nrf52::clock::CLOCK.high_started()) {
+            self.diagnostics.fail(Check::HighFrequencyClock);
+            nrf52::clock::CLOCK.high_stop();
+        }
     }
 }
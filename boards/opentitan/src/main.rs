@@ -7,6 +7,7 @@
 // https://github.com/rust-lang/rust/issues/62184.
 #![cfg_attr(not(doc), no_main)]
 
+use capsules::crypto_registry::CryptoRegistry;
 use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
 use capsules::virtual_hmac::VirtualMuxHmac;
 use kernel::capabilities;
@@ -61,7 +62,7 @@ struct OpenTitan {
     >,
     hmac: &'static capsules::hmac::HmacDriver<
         'static,
-        VirtualMuxHmac<'static, lowrisc::hmac::Hmac<'static>, [u8; 32]>,
+        VirtualMuxHmac<'static, CryptoRegistry<'static>, [u8; 32]>,
         [u8; 32],
     >,
     lldb: &'static capsules::low_level_debug::LowLevelDebug<
@@ -238,8 +239,14 @@ pub unsafe fn reset_handler() {
     let hmac_data_buffer = static_init!([u8; 64], [0; 64]);
     let hmac_dest_buffer = static_init!([u8; 32], [0; 32]);
 
-    let mux_hmac = components::hmac::HmacMuxComponent::new(&ibex::hmac::HMAC).finalize(
-        components::hmac_mux_component_helper!(lowrisc::hmac::Hmac, [u8; 32]),
+    // SHA-256 and HMAC run on the HMAC engine. Bind them to
+    // `capsules::sha256::SoftwareSha256` instead to keep the engine for
+    // another use.
+    let crypto = static_init!(CryptoRegistry<'static>, CryptoRegistry::new());
+    crypto.bind_sha256(&ibex::hmac::HMAC);
+
+    let mux_hmac = components::hmac::HmacMuxComponent::new(crypto).finalize(
+        components::hmac_mux_component_helper!(CryptoRegistry<'static>, [u8; 32]),
     );
 
     let hmac = components::hmac::HmacComponent::new(
@@ -249,7 +256,7 @@ pub unsafe fn reset_handler() {
         hmac_dest_buffer,
     )
     .finalize(components::hmac_component_helper!(
-        lowrisc::hmac::Hmac,
+        CryptoRegistry<'static>,
         [u8; 32]
     ));
 
@@ -0,0 +1,149 @@
+//! Provides userspace with hardware threshold monitoring of ADC channels.
+//!
+//! A process sets a low and a high limit on a channel, and gets a callback
+//! once a sample falls outside of them. The ADC compares the samples with
+//! the limits itself, so the process, and the kernel, are not woken up for
+//! each sample. This suits battery monitors and analog alarms.
+//!
+//! The ADC monitors one channel at a time, so one process at a time can use
+//! the driver. Monitoring stops after the callback, and the process starts it
+//! again if it wants to know about the next crossing.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let adc_channels = static_init!(
+//!     [&'static nrf52::adc::AdcChannel; 2],
+//!     [
+//!         &nrf52::adc::AdcChannel::AnalogInput0,
+//!         &nrf52::adc::AdcChannel::VDD,
+//!     ]
+//! );
+//! let adc_monitor = static_init!(
+//!     capsules::adc_monitor::AdcMonitor<'static, nrf52::adc::Adc>,
+//!     capsules::adc_monitor::AdcMonitor::new(
+//!         &nrf52::adc::ADC,
+//!         adc_channels,
+//!         board_kernel.create_grant(&memory_allocation_capability)
+//!     )
+//! );
+//! nrf52::adc::ADC.set_limit_client(adc_monitor);
+//! ```
+
+use crate::driver;
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::adc::{AdcLimits, LimitClient};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = driver::NUM::AdcMonitor as usize;
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+}
+
+pub struct AdcMonitor<'a, A: AdcLimits> {
+    adc: &'a A,
+    channels: &'a [&'a A::Channel],
+    apps: Grant<App>,
+    /// The process monitoring a channel, if any.
+    appid: OptionalCell<AppId>,
+    /// Index of the monitored channel in `channels`.
+    channel: Cell<usize>,
+}
+
+impl<'a, A: AdcLimits> AdcMonitor<'a, A> {
+    pub fn new(adc: &'a A, channels: &'a [&'a A::Channel], grant: Grant<App>) -> AdcMonitor<'a, A> {
+        AdcMonitor {
+            adc: adc,
+            channels: channels,
+            apps: grant,
+            appid: OptionalCell::empty(),
+            channel: Cell::new(0),
+        }
+    }
+
+    fn start(&self, channel: usize, low: u16, high: u16, appid: AppId) -> ReturnCode {
+        if channel >= self.channels.len() {
+            return ReturnCode::EINVAL;
+        }
+        if self.appid.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        let res = self.adc.monitor(self.channels[channel], low, high);
+        if res == ReturnCode::SUCCESS {
+            self.appid.set(appid);
+            self.channel.set(channel);
+        }
+        res
+    }
+
+    fn stop(&self, appid: AppId) -> ReturnCode {
+        if self.appid.map_or(true, |owner| *owner != appid) {
+            return ReturnCode::EINVAL;
+        }
+        let res = self.adc.stop_sampling();
+        if res == ReturnCode::SUCCESS {
+            self.appid.clear();
+        }
+        res
+    }
+}
+
+impl<'a, A: AdcLimits> LimitClient for AdcMonitor<'a, A> {
+    fn limit_reached(&self, above: bool) {
+        self.appid.take().map(|appid| {
+            let channel = self.channel.get();
+            let _ = self.apps.enter(appid, |app, _| {
+                app.callback
+                    .map(|mut cb| cb.schedule(channel, above as usize, 0));
+            });
+        });
+    }
+}
+
+impl<'a, A: AdcLimits> Driver for AdcMonitor<'a, A> {
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Called when a sample of the monitored channel is outside of
+    ///        the limits, with the channel and 1 if it is above the high
+    ///        limit, 0 if it is below the low limit.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check, returns the number of channels.
+    /// - `1`: Start monitoring channel `data1`. The low limit is in the low
+    ///        16 bits of `data2`, the high limit in the high 16 bits, both
+    ///        left-justified.
+    /// - `2`: Stop monitoring, without a callback.
+    fn command(&self, command_num: usize, data1: usize, data2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SuccessWithValue {
+                value: self.channels.len(),
+            },
+            1 => self.start(data1, data2 as u16, (data2 >> 16) as u16, appid),
+            2 => self.stop(appid),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
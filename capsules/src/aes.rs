@@ -0,0 +1,457 @@
+//! Provides userspace with access to AES-128 encryption.
+//!
+//! The driver supports ECB and CBC encryption, and CTR mode in both
+//! directions. ECB and CBC messages can be padded with PKCS#7 by the driver,
+//! so that processes can encrypt messages of any length. It only needs the
+//! block cipher of the underlying engine (`AES128Block`), so it can share the
+//! engine with kernel users of the streaming `AES128` interface, such as the
+//! 802.15.4 stack.
+//!
+//! Processes take turns: while one message is being processed, requests of
+//! other processes are queued, one per process, and served in the order they
+//! were made. The board gives the slots of the queue to `new()`, which
+//! bound the number of processes that can wait.
+//!
+//! Usage
+//! -----
+//!
+//! The engine is shared through a `MuxAES128Block`:
+//!
+//! ```rust
+//! let virtual_aes = static_init!(
+//!     VirtualAES128Block<'static, nrf52::aes::AesECB<'static>>,
+//!     VirtualAES128Block::new(mux_aes)
+//! );
+//! virtual_aes.setup();
+//! let aes_queue = static_init!(
+//!     [OptionalCell<(AppId, usize)>; 2],
+//!     [OptionalCell::empty(), OptionalCell::empty()]
+//! );
+//! let aes = static_init!(
+//!     capsules::aes::AesDriver<'static, VirtualAES128Block<'static, nrf52::aes::AesECB<'static>>>,
+//!     capsules::aes::AesDriver::new(
+//!         virtual_aes,
+//!         aes_queue,
+//!         board_kernel.create_grant(&memory_allocation_capability)
+//!     )
+//! );
+//! AES128Block::set_block_client(virtual_aes, aes);
+//! ```
+
+use crate::driver;
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::bounded_queue::BoundedQueue;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::symmetric_encryption::{
+    AES128Block, BlockClient, ClearKeys, AES128_BLOCK_SIZE, AES128_KEY_SIZE,
+};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = driver::NUM::Aes as usize;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Ecb,
+    Cbc,
+    Ctr,
+}
+
+pub struct App {
+    callback: Option<Callback>,
+    key: Option<AppSlice<Shared, u8>>,
+    iv: Option<AppSlice<Shared, u8>>,
+    data: Option<AppSlice<Shared, u8>>,
+    mode: Mode,
+    /// Whether to add PKCS#7 padding to ECB and CBC messages.
+    pad: bool,
+}
+
+impl Default for App {
+    fn default() -> App {
+        App {
+            callback: None,
+            key: None,
+            iv: None,
+            data: None,
+            mode: Mode::Ecb,
+            pad: false,
+        }
+    }
+}
+
+pub struct AesDriver<'a, A: AES128Block<'a> + ClearKeys> {
+    aes: &'a A,
+    apps: Grant<App>,
+    appid: OptionalCell<AppId>,
+    /// The processes waiting for the engine, with the length of their
+    /// message.
+    queue: BoundedQueue<'a, (AppId, usize)>,
+    mode: Cell<Mode>,
+    key: Cell<[u8; AES128_KEY_SIZE]>,
+    /// The CBC chaining value, or the CTR counter block.
+    iv: Cell<[u8; AES128_BLOCK_SIZE]>,
+    /// Offset in the data buffer of the block being processed.
+    offset: Cell<usize>,
+    len: Cell<usize>,
+}
+
+impl<'a, A: AES128Block<'a> + ClearKeys> AesDriver<'a, A> {
+    pub fn new(
+        aes: &'a A,
+        queue: &'a [OptionalCell<(AppId, usize)>],
+        grant: Grant<App>,
+    ) -> AesDriver<'a, A> {
+        AesDriver {
+            aes: aes,
+            apps: grant,
+            appid: OptionalCell::empty(),
+            queue: BoundedQueue::new(queue),
+            mode: Cell::new(Mode::Ecb),
+            key: Cell::new([0; AES128_KEY_SIZE]),
+            iv: Cell::new([0; AES128_BLOCK_SIZE]),
+            offset: Cell::new(0),
+            len: Cell::new(0),
+        }
+    }
+
+    /// Start processing the first `message_len` bytes of the data buffer of
+    /// `appid`, padded if the process asked for it.
+    fn start(&self, appid: AppId, message_len: usize) -> ReturnCode {
+        let mut len = message_len;
+        let res = self
+            .apps
+            .enter(appid, |app, _| {
+                if app.pad {
+                    // At least one byte of padding
+                    len = match (len / AES128_BLOCK_SIZE)
+                        .checked_add(1)
+                        .and_then(|blocks| blocks.checked_mul(AES128_BLOCK_SIZE))
+                    {
+                        Some(len) => len,
+                        None => return ReturnCode::ESIZE,
+                    };
+                } else if len == 0 || (app.mode != Mode::Ctr && len % AES128_BLOCK_SIZE != 0) {
+                    return ReturnCode::EINVAL;
+                }
+                let mut key = [0; AES128_KEY_SIZE];
+                match app.key {
+                    Some(ref slice) if slice.len() == AES128_KEY_SIZE => {
+                        key.copy_from_slice(slice.as_ref())
+                    }
+                    _ => return ReturnCode::EINVAL,
+                }
+                let mut iv = [0; AES128_BLOCK_SIZE];
+                if app.mode != Mode::Ecb {
+                    match app.iv {
+                        Some(ref slice) if slice.len() == AES128_BLOCK_SIZE => {
+                            iv.copy_from_slice(slice.as_ref())
+                        }
+                        _ => return ReturnCode::EINVAL,
+                    }
+                }
+                let padded = app.pad;
+                match app.data {
+                    Some(ref mut data) => match data.as_mut().get_mut(..len) {
+                        Some(buf) => {
+                            if padded {
+                                pad(buf, message_len);
+                            }
+                        }
+                        None => return ReturnCode::ESIZE,
+                    },
+                    None => return ReturnCode::EINVAL,
+                }
+
+                self.key.set(key);
+                self.iv.set(iv);
+                self.mode.set(app.mode);
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into());
+        if res != ReturnCode::SUCCESS {
+            return res;
+        }
+
+        self.appid.set(appid);
+        self.offset.set(0);
+        self.len.set(len);
+        let res = self.next_block();
+        if res != ReturnCode::SUCCESS {
+            self.clear();
+        }
+        res
+    }
+
+    /// Hand the block at the current offset to the engine.
+    fn next_block(&self) -> ReturnCode {
+        let offset = self.offset.get();
+        let n = cmp::min(AES128_BLOCK_SIZE, self.len.get() - offset);
+        self.appid.map_or(ReturnCode::FAIL, |appid| {
+            self.apps
+                .enter(*appid, |app, _| {
+                    let mut input = [0; AES128_BLOCK_SIZE];
+                    let block = app
+                        .data
+                        .as_ref()
+                        .and_then(|data| data.as_ref().get(offset..offset + n));
+                    match self.mode.get() {
+                        Mode::Ecb => block.map(|block| input.copy_from_slice(block)),
+                        Mode::Cbc => block.map(|block| {
+                            input = self.iv.get();
+                            for (i, byte) in block.iter().enumerate() {
+                                input[i] ^= byte;
+                            }
+                        }),
+                        Mode::Ctr => {
+                            input = self.iv.get();
+                            Some(())
+                        }
+                    }
+                    .map_or(ReturnCode::EINVAL, |_| {
+                        self.aes
+                            .ecb_encrypt_block(&self.key.get(), &input)
+                            .map_or_else(|e| e.into(), |()| ReturnCode::SUCCESS)
+                    })
+                })
+                .unwrap_or_else(|err| err.into())
+        })
+    }
+
+    /// Wipe the key material, here and in the engine, and release the
+    /// engine.
+    fn clear(&self) {
+        self.key.set([0; AES128_KEY_SIZE]);
+        self.iv.set([0; AES128_BLOCK_SIZE]);
+        // The last block of the process is done, so this only fails if the
+        // engine is busy with another user, whose key it holds instead
+        let _ = self.aes.clear_keys();
+        self.appid.clear();
+    }
+
+    fn finish(&self, appid: AppId, res: ReturnCode) {
+        let len = self.len.get();
+        let _ = self.apps.enter(appid, |app, _| {
+            app.callback
+                .map(|mut cb| cb.schedule(usize::from(res), len, 0));
+        });
+        self.clear();
+        self.check_queue();
+    }
+
+    /// Start the next queued request, if the engine is free.
+    fn check_queue(&self) {
+        while self.appid.is_none() {
+            let (appid, len) = match self.queue.dequeue() {
+                Some(next) => next,
+                None => return,
+            };
+            let res = self.start(appid, len);
+            if res != ReturnCode::SUCCESS {
+                let _ = self.apps.enter(appid, |app, _| {
+                    app.callback
+                        .map(|mut cb| cb.schedule(usize::from(res), 0, 0));
+                });
+            }
+        }
+    }
+}
+
+/// Fill the end of `buf`, after the first `message_len` bytes, with PKCS#7
+/// padding. `buf` is at most one block longer than the message.
+fn pad(buf: &mut [u8], message_len: usize) {
+    if let Some(padding) = buf.get_mut(message_len..) {
+        let pad_len = padding.len() as u8;
+        for byte in padding.iter_mut() {
+            *byte = pad_len;
+        }
+    }
+}
+
+/// Increment the counter block as a 128-bit big-endian integer.
+fn increment_counter(counter: &mut [u8; AES128_BLOCK_SIZE]) {
+    for byte in counter.iter_mut().rev() {
+        *byte = byte.wrapping_add(1);
+        if *byte != 0 {
+            break;
+        }
+    }
+}
+
+impl<'a, A: AES128Block<'a> + ClearKeys> BlockClient for AesDriver<'a, A> {
+    fn encrypt_block_done(&self, block: Result<&[u8; AES128_BLOCK_SIZE], CryptoError>) {
+        let block = match block {
+            Ok(block) => block,
+            Err(e) => {
+                self.appid
+                    .map(|appid| self.finish(*appid, ReturnCode::from(e)));
+                return;
+            }
+        };
+        let offset = self.offset.get();
+        let n = cmp::min(AES128_BLOCK_SIZE, self.len.get() - offset);
+        self.appid.map(|appid| {
+            let appid = *appid;
+            let written = self.apps.enter(appid, |app, _| {
+                let out = app
+                    .data
+                    .as_mut()
+                    .and_then(|data| data.as_mut().get_mut(offset..offset + n));
+                out.map(|out| match self.mode.get() {
+                    Mode::Ecb => out.copy_from_slice(block),
+                    Mode::Cbc => {
+                        out.copy_from_slice(block);
+                        self.iv.set(*block);
+                    }
+                    Mode::Ctr => {
+                        for (byte, key) in out.iter_mut().zip(block.iter()) {
+                            *byte ^= key;
+                        }
+                        let mut counter = self.iv.get();
+                        increment_counter(&mut counter);
+                        self.iv.set(counter);
+                    }
+                })
+            });
+            match written {
+                Ok(Some(())) => {
+                    self.offset.set(offset + n);
+                    if offset + n == self.len.get() {
+                        self.finish(appid, ReturnCode::SUCCESS);
+                    } else {
+                        let res = self.next_block();
+                        if res != ReturnCode::SUCCESS {
+                            self.finish(appid, res);
+                        }
+                    }
+                }
+                // The process unallowed its buffer in the middle of the
+                // message, or allowed a shorter one.
+                Ok(None) => self.finish(appid, ReturnCode::EINVAL),
+                // The process is gone, there is nobody to report to.
+                Err(_) => {
+                    self.clear();
+                    self.check_queue();
+                }
+            }
+        });
+    }
+}
+
+impl<'a, A: AES128Block<'a> + ClearKeys> Driver for AesDriver<'a, A> {
+    /// Specify memory regions to be used.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The key, 16 bytes.
+    /// - `1`: The IV for CBC, or the initial counter block for CTR, 16 bytes.
+    ///        Not used in ECB mode.
+    /// - `2`: The data, which is encrypted or decrypted in place. It cannot
+    ///        be changed while the message of the process is being processed.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        if allow_num == 2 && self.appid.map_or(false, |owner| *owner == appid) {
+            return ReturnCode::EBUSY;
+        }
+        self.apps
+            .enter(appid, |app, _| {
+                match allow_num {
+                    0 => app.key = slice,
+                    1 => app.iv = slice,
+                    2 => app.data = slice,
+                    _ => return ReturnCode::ENOSUPPORT,
+                }
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    /// Subscribe to AES events.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Subscribe to completion of `crypt`. The callback signature is
+    ///        `fn(result: ReturnCode, len: usize)`
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Select the mode: `data1` is 0 for ECB, 1 for CBC and 2 for
+    ///        CTR. Bit 0 of `data2` is set to encrypt and clear to decrypt,
+    ///        bit 1 adds PKCS#7 padding in ECB and CBC mode. ECB and CBC
+    ///        decryption need the inverse cipher and return `ENOSUPPORT`.
+    /// - `2`: Encrypt or decrypt the first `data1` bytes of the data buffer,
+    ///        a multiple of 16 bytes in ECB and CBC mode without padding.
+    ///        With padding, the data buffer needs room for the padded
+    ///        message. Returns `ESIZE` if the data buffer is too short. If
+    ///        another process is using the engine, the request is queued,
+    ///        or refused with `ENOMEM` if the queue is full.
+    fn command(&self, command_num: usize, data1: usize, data2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+
+            1 => {
+                let encrypting = data2 & 1 != 0;
+                let pad = data2 & 2 != 0;
+                let mode = match (data1, encrypting, pad) {
+                    (0, true, _) => Mode::Ecb,
+                    (1, true, _) => Mode::Cbc,
+                    (2, _, false) => Mode::Ctr,
+                    (0, false, _) | (1, false, _) => return ReturnCode::ENOSUPPORT,
+                    _ => return ReturnCode::EINVAL,
+                };
+                self.apps
+                    .enter(appid, |app, _| {
+                        app.mode = mode;
+                        app.pad = pad;
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or_else(|err| err.into())
+            }
+
+            2 => match self.appid.map(|owner| *owner) {
+                None => self.start(appid, data1),
+                Some(owner) if owner == appid => ReturnCode::EBUSY,
+                Some(_) => {
+                    let mut queued = false;
+                    self.queue.retain(|&(waiting, _)| {
+                        queued |= waiting == appid;
+                        true
+                    });
+                    if queued {
+                        ReturnCode::EBUSY
+                    } else if self.queue.enqueue((appid, data1)) {
+                        ReturnCode::SUCCESS
+                    } else {
+                        ReturnCode::ENOMEM
+                    }
+                }
+            },
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
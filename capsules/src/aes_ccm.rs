@@ -55,11 +55,11 @@ use crate::net::stream::SResult;
 use crate::net::stream::{encode_bytes, encode_u16};
 use core::cell::Cell;
 use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::crypto::CryptoError;
 use kernel::hil::symmetric_encryption;
 use kernel::hil::symmetric_encryption::{
-    AES128Ctr, AES128, AES128CBC, AES128_BLOCK_SIZE, AES128_KEY_SIZE, CCM_NONCE_LENGTH,
+    AES128Ctr, ClearKeys, AES128, AES128CBC, AES128_BLOCK_SIZE, AES128_KEY_SIZE, CCM_NONCE_LENGTH,
 };
-use kernel::ReturnCode;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 enum CCMState {
@@ -68,7 +68,7 @@ enum CCMState {
     Encrypt,
 }
 
-pub struct AES128CCM<'a, A: AES128<'a> + AES128Ctr + AES128CBC> {
+pub struct AES128CCM<'a, A: AES128<'a> + AES128Ctr + AES128CBC + ClearKeys> {
     aes: &'a A,
     crypt_buf: TakeCell<'a, [u8]>,
     crypt_auth_len: Cell<usize>,
@@ -86,7 +86,7 @@ pub struct AES128CCM<'a, A: AES128<'a> + AES128Ctr + AES128CBC> {
     saved_tag: Cell<[u8; AES128_BLOCK_SIZE]>,
 }
 
-impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
+impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC + ClearKeys> AES128CCM<'a, A> {
     pub fn new(aes: &'a A, crypt_buf: &'static mut [u8]) -> AES128CCM<'a, A> {
         AES128CCM {
             aes: aes,
@@ -106,39 +106,40 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
     }
 
     /// Prepares crypt_buf with the input for the CCM* authentication and
-    /// encryption/decryption transformations. Returns ENOMEM if crypt_buf is
-    /// not present or if it is not long enough.
+    /// encryption/decryption transformations. Returns `InvalidArgument` if
+    /// crypt_buf is not present or if it is not long enough.
     fn prepare_ccm_buffer(
         &self,
         nonce: &[u8; CCM_NONCE_LENGTH],
         mic_len: usize,
         a_data: &[u8],
         m_data: &[u8],
-    ) -> ReturnCode {
-        self.crypt_buf.map_or(ReturnCode::ENOMEM, |cbuf| {
-            let (auth_len, enc_len) =
-                match Self::encode_ccm_buffer(cbuf, nonce, mic_len, a_data, m_data) {
-                    SResult::Done(_, out) => out,
-                    SResult::Needed(_) => {
-                        return ReturnCode::ENOMEM;
-                    }
-                    SResult::Error(_) => {
-                        return ReturnCode::FAIL;
-                    }
-                };
-            // debug!("auth: ({})", auth_len);
-            // for i in 0..auth_len {
-            //     debug!("{:02x}", cbuf[i]);
-            // }
-            // debug!("enc: ({})", enc_len);
-            // for i in auth_len..enc_len {
-            //     debug!("{:02x}", cbuf[i]);
-            // }
-
-            self.crypt_auth_len.set(auth_len);
-            self.crypt_enc_len.set(enc_len);
-            ReturnCode::SUCCESS
-        })
+    ) -> Result<(), CryptoError> {
+        self.crypt_buf
+            .map_or(Err(CryptoError::InvalidArgument), |cbuf| {
+                let (auth_len, enc_len) =
+                    match Self::encode_ccm_buffer(cbuf, nonce, mic_len, a_data, m_data) {
+                        SResult::Done(_, out) => out,
+                        SResult::Needed(_) => {
+                            return Err(CryptoError::InvalidArgument);
+                        }
+                        SResult::Error(_) => {
+                            return Err(CryptoError::NotSupported);
+                        }
+                    };
+                // debug!("auth: ({})", auth_len);
+                // for i in 0..auth_len {
+                //     debug!("{:02x}", cbuf[i]);
+                // }
+                // debug!("enc: ({})", enc_len);
+                // for i in auth_len..enc_len {
+                //     debug!("{:02x}", cbuf[i]);
+                // }
+
+                self.crypt_auth_len.set(auth_len);
+                self.crypt_enc_len.set(enc_len);
+                Ok(())
+            })
     }
 
     /// This function encodes AuthData (a_data) and PData/CData (m_data) into a
@@ -218,7 +219,7 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
 
     // Assumes that the state is Idle, which means that crypt_buf must be
     // present. Panics if this is not the case.
-    fn start_ccm_auth(&self) -> ReturnCode {
+    fn start_ccm_auth(&self) -> Result<(), CryptoError> {
         if !(self.state.get() == CCMState::Idle)
             && !(self.state.get() == CCMState::Encrypt && self.reversed())
         {
@@ -226,14 +227,8 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
         }
 
         let iv = [0u8; AES128_BLOCK_SIZE];
-        let res = self.aes.set_iv(&iv);
-        if res != ReturnCode::SUCCESS {
-            return res;
-        }
-        let res = self.aes.set_key(&self.key.get());
-        if res != ReturnCode::SUCCESS {
-            return res;
-        }
+        self.aes.set_iv(&iv)?;
+        self.aes.set_key(&self.key.get())?;
 
         let crypt_buf = match self.crypt_buf.take() {
             None => panic!("Cannot perform CCM* auth because crypt_buf is not present."),
@@ -253,21 +248,21 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
         match self.aes.crypt(None, crypt_buf, 0, auth_end) {
             None => {
                 self.state.set(CCMState::Auth);
-                ReturnCode::SUCCESS
+                Ok(())
             }
-            Some((res, _, crypt_buf)) => {
+            Some((e, _, crypt_buf)) => {
                 // Request failed
                 self.crypt_buf.replace(crypt_buf);
-                res
+                Err(e)
             }
         }
     }
 
-    fn start_ccm_encrypt(&self) -> ReturnCode {
+    fn start_ccm_encrypt(&self) -> Result<(), CryptoError> {
         if !(self.state.get() == CCMState::Auth)
             && !(self.state.get() == CCMState::Idle && self.reversed())
         {
-            return ReturnCode::FAIL;
+            return Err(CryptoError::HardwareFault);
         }
         self.state.set(CCMState::Idle); // default to fail
 
@@ -283,10 +278,10 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
         // Since L = 2, flags = 1.
         iv[0] = 1;
         iv[1..1 + CCM_NONCE_LENGTH].copy_from_slice(&self.nonce.get());
-        let res = self.aes.set_iv(&iv);
-        if res != ReturnCode::SUCCESS {
-            return res;
-        }
+        self.aes.set_iv(&iv)?;
+        // Decryption starts here, after the key was cleared at the end of
+        // the previous message
+        self.aes.set_key(&self.key.get())?;
 
         self.aes.set_mode_aes128ctr(self.encrypting.get());
         self.aes.start_message();
@@ -303,11 +298,11 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
         ) {
             None => {
                 self.state.set(CCMState::Encrypt);
-                ReturnCode::SUCCESS
+                Ok(())
             }
-            Some((res, _, crypt_buf)) => {
+            Some((e, _, crypt_buf)) => {
                 self.crypt_buf.replace(crypt_buf);
-                res
+                Err(e)
             }
         }
     }
@@ -344,9 +339,10 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
         });
 
         self.state.set(CCMState::Idle);
+        self.clear_engine_keys();
         self.crypt_client.map(|client| {
             self.buf.take().map(|buf| {
-                client.crypt_done(buf, ReturnCode::SUCCESS, tag_valid);
+                client.crypt_done(buf, Ok(()), tag_valid);
             });
         });
     }
@@ -380,13 +376,22 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
         });
 
         self.state.set(CCMState::Idle);
+        self.clear_engine_keys();
         self.crypt_client.map(|client| {
             self.buf.take().map(|buf| {
-                client.crypt_done(buf, ReturnCode::SUCCESS, tag_valid);
+                client.crypt_done(buf, Ok(()), tag_valid);
             });
         });
     }
 
+    /// Wipe the key from the engine at the end of a message. The key is set
+    /// again at the start of the next one.
+    fn clear_engine_keys(&self) {
+        // The engine is busy if another client started a message from its
+        // callback, and then holds the key of that client instead
+        let _ = self.aes.clear_keys();
+    }
+
     fn save_tag_block(&self) {
         // Copies [auth_len - AES128_BLOCK_SIZE..auth_len] to saved_tag
         // and zeroes it out
@@ -414,32 +419,32 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
     }
 }
 
-impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> symmetric_encryption::AES128CCM<'a>
+impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC + ClearKeys> symmetric_encryption::AES128CCM<'a>
     for AES128CCM<'a, A>
 {
     fn set_client(&self, client: &'a dyn symmetric_encryption::CCMClient) {
         self.crypt_client.set(client);
     }
 
-    fn set_key(&self, key: &[u8]) -> ReturnCode {
+    fn set_key(&self, key: &[u8]) -> Result<(), CryptoError> {
         if key.len() < AES128_KEY_SIZE {
-            ReturnCode::EINVAL
+            Err(CryptoError::BadKeyLength)
         } else {
             let mut new_key = [0u8; AES128_KEY_SIZE];
             new_key.copy_from_slice(key);
             self.key.set(new_key);
-            ReturnCode::SUCCESS
+            Ok(())
         }
     }
 
-    fn set_nonce(&self, nonce: &[u8]) -> ReturnCode {
+    fn set_nonce(&self, nonce: &[u8]) -> Result<(), CryptoError> {
         if nonce.len() < CCM_NONCE_LENGTH {
-            ReturnCode::EINVAL
+            Err(CryptoError::InvalidArgument)
         } else {
             let mut new_nonce = [0u8; CCM_NONCE_LENGTH];
             new_nonce.copy_from_slice(nonce);
             self.nonce.set(new_nonce);
-            ReturnCode::SUCCESS
+            Ok(())
         }
     }
 
@@ -452,25 +457,24 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> symmetric_encryption::AES128CCM<
         mic_len: usize,
         confidential: bool,
         encrypting: bool,
-    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+    ) -> Result<(), (CryptoError, &'static mut [u8])> {
         if self.state.get() != CCMState::Idle {
-            return (ReturnCode::EBUSY, Some(buf));
+            return Err((CryptoError::EngineBusy, buf));
         }
         if !(a_off <= m_off && m_off + m_len + mic_len <= buf.len()) {
-            return (ReturnCode::EINVAL, Some(buf));
+            return Err((CryptoError::InvalidArgument, buf));
         }
 
         self.confidential.set(confidential);
         self.encrypting.set(encrypting);
 
-        let res = self.prepare_ccm_buffer(
+        if let Err(e) = self.prepare_ccm_buffer(
             &self.nonce.get(),
             mic_len,
             &buf[a_off..m_off],
             &buf[m_off..m_off + m_len],
-        );
-        if res != ReturnCode::SUCCESS {
-            return (res, Some(buf));
+        ) {
+            return Err((e, buf));
         }
 
         let res = if !confidential || encrypting {
@@ -482,21 +486,39 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> symmetric_encryption::AES128CCM<
             self.start_ccm_encrypt()
         };
 
-        if res != ReturnCode::SUCCESS {
-            (res, Some(buf))
-        } else {
-            self.buf.replace(buf);
-            self.pos.set((a_off, m_off, m_len, mic_len));
-            (ReturnCode::SUCCESS, None)
+        match res {
+            Ok(()) => {
+                self.buf.replace(buf);
+                self.pos.set((a_off, m_off, m_len, mic_len));
+                Ok(())
+            }
+            Err(e) => Err((e, buf)),
         }
     }
 }
 
-impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> symmetric_encryption::Client<'a>
+impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC + ClearKeys> symmetric_encryption::Client<'a>
     for AES128CCM<'a, A>
 {
-    fn crypt_done(&self, _: Option<&'a mut [u8]>, crypt_buf: &'a mut [u8]) {
+    fn crypt_done(
+        &self,
+        _: Option<&'a mut [u8]>,
+        crypt_buf: &'a mut [u8],
+        res: Result<(), CryptoError>,
+    ) {
         self.crypt_buf.replace(crypt_buf);
+        if let Err(e) = res {
+            if self.state.get() != CCMState::Idle {
+                self.clear_engine_keys();
+                self.state.set(CCMState::Idle);
+                self.buf.take().map(|buf| {
+                    self.crypt_client.map(move |client| {
+                        client.crypt_done(buf, Err(e), false);
+                    });
+                });
+            }
+            return;
+        }
         match self.state.get() {
             CCMState::Idle => {}
             CCMState::Auth => {
@@ -527,7 +549,8 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> symmetric_encryption::Client<'a>
                     }
 
                     let res = self.start_ccm_encrypt();
-                    if res != ReturnCode::SUCCESS {
+                    if res.is_err() {
+                        self.clear_engine_keys();
                         // Return client buffer to client
                         self.buf.take().map(|buf| {
                             self.crypt_client.map(move |client| {
@@ -560,7 +583,8 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> symmetric_encryption::Client<'a>
                             .for_each(|b| *b = 0);
                     });
                     let res = self.start_ccm_auth();
-                    if res != ReturnCode::SUCCESS {
+                    if res.is_err() {
+                        self.clear_engine_keys();
                         // Return client buffer to client
                         self.buf.take().map(|buf| {
                             self.crypt_client.map(move |client| {
@@ -0,0 +1,312 @@
+//! Implements AES-CMAC (RFC 4493) using an underlying AES-CBC implementation.
+//!
+//! CMAC is a CBC-MAC over the message where the last block is masked with one
+//! of two subkeys derived from the cipher key:
+//!
+//! ```text
+//! L  = AES(K, 0^128)
+//! K1 = dbl(L)
+//! K2 = dbl(K1)
+//! ```
+//!
+//! If the last block of the message is complete it is XORed with K1,
+//! otherwise it is padded with `0x80 0x00 ...` and XORed with K2. A
+//! zero-length message is treated as a single padded block. The tag is the
+//! last output block of AES-CBC (with a zero IV) over the prepared message.
+//!
+//! The subkeys are computed with a single-block CBC pass the first time a tag
+//! is requested after `set_key()`, and then reused.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//! const CRYPT_SIZE: usize = 8 * symmetric_encryption::AES128_BLOCK_SIZE;
+//! static mut CRYPT_BUF: [u8; CRYPT_SIZE] = [0x00; CRYPT_SIZE];
+//!
+//! let aes_cmac = static_init!(
+//!     capsules::aes_cmac::AES128CMAC<'static, sam4l::aes::Aes<'static>>,
+//!     capsules::aes_cmac::AES128CMAC::new(&sam4l::aes::AES, &mut CRYPT_BUF)
+//! );
+//! sam4l::aes::AES.set_client(aes_cmac);
+//! sam4l::aes::AES.enable();
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::symmetric_encryption;
+use kernel::hil::symmetric_encryption::{
+    AES128, AES128CBC, AES128_BLOCK_SIZE, AES128_KEY_SIZE, CMAC_TAG_LENGTH,
+};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum CMACState {
+    Idle,
+    Subkey,
+    Mac,
+}
+
+/// The constant used when doubling a block in GF(2^128).
+const RB: u8 = 0x87;
+
+pub struct AES128CMAC<'a, A: AES128<'a> + AES128CBC> {
+    aes: &'a A,
+    crypt_buf: TakeCell<'a, [u8]>,
+    crypt_len: Cell<usize>,
+    client: OptionalCell<&'a dyn symmetric_encryption::CMACClient>,
+
+    state: Cell<CMACState>,
+    verify: Cell<bool>,
+
+    buf: TakeCell<'static, [u8]>,
+    pos: Cell<(usize, usize, usize)>,
+    key: Cell<[u8; AES128_KEY_SIZE]>,
+    subkeys: OptionalCell<([u8; AES128_BLOCK_SIZE], [u8; AES128_BLOCK_SIZE])>,
+}
+
+impl<'a, A: AES128<'a> + AES128CBC> AES128CMAC<'a, A> {
+    pub fn new(aes: &'a A, crypt_buf: &'static mut [u8]) -> AES128CMAC<'a, A> {
+        AES128CMAC {
+            aes: aes,
+            crypt_buf: TakeCell::new(crypt_buf),
+            crypt_len: Cell::new(0),
+            client: OptionalCell::empty(),
+            state: Cell::new(CMACState::Idle),
+            verify: Cell::new(false),
+            buf: TakeCell::empty(),
+            pos: Cell::new((0, 0, 0)),
+            key: Cell::new(Default::default()),
+            subkeys: OptionalCell::empty(),
+        }
+    }
+
+    /// Multiply a block by x in GF(2^128).
+    fn dbl(block: &[u8; AES128_BLOCK_SIZE]) -> [u8; AES128_BLOCK_SIZE] {
+        let mut out = [0u8; AES128_BLOCK_SIZE];
+        for i in 0..AES128_BLOCK_SIZE {
+            let carry = if i + 1 < AES128_BLOCK_SIZE {
+                block[i + 1] >> 7
+            } else {
+                0
+            };
+            out[i] = (block[i] << 1) | carry;
+        }
+        // Branch-free conditional reduction
+        out[AES128_BLOCK_SIZE - 1] ^= RB & 0u8.wrapping_sub(block[0] >> 7);
+        out
+    }
+
+    /// Run AES-CBC with a zero IV over `crypt_buf[0..len]`.
+    fn start_cbc(&self, len: usize, next: CMACState) -> Result<(), CryptoError> {
+        let iv = [0u8; AES128_BLOCK_SIZE];
+        self.aes.set_iv(&iv)?;
+        self.aes.set_key(&self.key.get())?;
+
+        let crypt_buf = match self.crypt_buf.take() {
+            None => return Err(CryptoError::InvalidArgument),
+            Some(buf) => buf,
+        };
+
+        // We are performing CBC-MAC, so always encrypting.
+        self.aes.set_mode_aes128cbc(true);
+        self.aes.start_message();
+        match self.aes.crypt(None, crypt_buf, 0, len) {
+            None => {
+                self.state.set(next);
+                Ok(())
+            }
+            Some((e, _, crypt_buf)) => {
+                self.crypt_buf.replace(crypt_buf);
+                Err(e)
+            }
+        }
+    }
+
+    fn start_subkey(&self) -> Result<(), CryptoError> {
+        self.crypt_buf
+            .map_or(Err(CryptoError::InvalidArgument), |cbuf| {
+                if cbuf.len() < AES128_BLOCK_SIZE {
+                    return Err(CryptoError::InvalidArgument);
+                }
+                cbuf[..AES128_BLOCK_SIZE].iter_mut().for_each(|b| *b = 0);
+                Ok(())
+            })?;
+        self.start_cbc(AES128_BLOCK_SIZE, CMACState::Subkey)
+    }
+
+    /// Copies the message into `crypt_buf`, padding and masking the last
+    /// block with the appropriate subkey, and starts the CBC pass.
+    fn start_mac(&self) -> Result<(), CryptoError> {
+        let (k1, k2) = match self.subkeys.map(|subkeys| *subkeys) {
+            None => return Err(CryptoError::HardwareFault),
+            Some(subkeys) => subkeys,
+        };
+        let (m_off, m_len, _) = self.pos.get();
+
+        let n_blocks = if m_len == 0 {
+            1
+        } else {
+            (m_len + AES128_BLOCK_SIZE - 1) / AES128_BLOCK_SIZE
+        };
+        let crypt_len = n_blocks * AES128_BLOCK_SIZE;
+        let complete = m_len != 0 && m_len % AES128_BLOCK_SIZE == 0;
+
+        self.crypt_buf
+            .map_or(Err(CryptoError::InvalidArgument), |cbuf| {
+                if cbuf.len() < crypt_len {
+                    return Err(CryptoError::InvalidArgument);
+                }
+                self.buf.map(|buf| {
+                    cbuf[..m_len].copy_from_slice(&buf[m_off..m_off + m_len]);
+                });
+
+                let last = crypt_len - AES128_BLOCK_SIZE;
+                let subkey = if complete {
+                    k1
+                } else {
+                    cbuf[m_len] = 0x80;
+                    cbuf[m_len + 1..crypt_len].iter_mut().for_each(|b| *b = 0);
+                    k2
+                };
+                cbuf[last..crypt_len]
+                    .iter_mut()
+                    .zip(subkey.iter())
+                    .for_each(|(b, k)| *b ^= *k);
+                Ok(())
+            })?;
+
+        self.crypt_len.set(crypt_len);
+        self.start_cbc(crypt_len, CMACState::Mac)
+    }
+
+    fn end_mac(&self) {
+        let (m_off, m_len, tag_len) = self.pos.get();
+        let tag_off = self.crypt_len.get() - AES128_BLOCK_SIZE;
+        let verify = self.verify.get();
+
+        let tag_valid = self.buf.map_or(false, |buf| {
+            self.crypt_buf.map_or(false, |cbuf| {
+                let tag = &cbuf[tag_off..tag_off + tag_len];
+                let dest = &mut buf[m_off + m_len..m_off + m_len + tag_len];
+                if verify {
+                    // Accumulate the differences so the comparison takes the
+                    // same time regardless of where the tags differ.
+                    dest.iter()
+                        .zip(tag.iter())
+                        .fold(0, |acc, (a, b)| acc | (*a ^ *b))
+                        == 0
+                } else {
+                    dest.copy_from_slice(tag);
+                    true
+                }
+            })
+        });
+
+        self.finish(Ok(()), tag_valid);
+    }
+
+    fn finish(&self, res: Result<(), CryptoError>, tag_valid: bool) {
+        self.state.set(CMACState::Idle);
+        self.buf.take().map(|buf| {
+            self.client.map(move |client| {
+                client.mac_done(buf, res, tag_valid);
+            });
+        });
+    }
+}
+
+impl<'a, A: AES128<'a> + AES128CBC> symmetric_encryption::AES128CMAC<'a> for AES128CMAC<'a, A> {
+    fn set_client(&'a self, client: &'a dyn symmetric_encryption::CMACClient) {
+        self.client.set(client);
+    }
+
+    fn set_key(&self, key: &[u8]) -> Result<(), CryptoError> {
+        if self.state.get() != CMACState::Idle {
+            return Err(CryptoError::EngineBusy);
+        }
+        if key.len() != AES128_KEY_SIZE {
+            Err(CryptoError::BadKeyLength)
+        } else {
+            let mut new_key = [0u8; AES128_KEY_SIZE];
+            new_key.copy_from_slice(key);
+            self.key.set(new_key);
+            self.subkeys.clear();
+            Ok(())
+        }
+    }
+
+    fn compute(
+        &self,
+        buf: &'static mut [u8],
+        m_off: usize,
+        m_len: usize,
+        tag_len: usize,
+        verify: bool,
+    ) -> Result<(), (CryptoError, &'static mut [u8])> {
+        if self.state.get() != CMACState::Idle {
+            return Err((CryptoError::EngineBusy, buf));
+        }
+        let end = m_off
+            .checked_add(m_len)
+            .and_then(|end| end.checked_add(tag_len));
+        match end {
+            Some(end) if tag_len > 0 && tag_len <= CMAC_TAG_LENGTH && end <= buf.len() => {}
+            _ => return Err((CryptoError::InvalidArgument, buf)),
+        }
+
+        self.buf.replace(buf);
+        self.pos.set((m_off, m_len, tag_len));
+        self.verify.set(verify);
+
+        let res = if self.subkeys.is_some() {
+            self.start_mac()
+        } else {
+            self.start_subkey()
+        };
+
+        if let Err(e) = res {
+            if let Some(buf) = self.buf.take() {
+                return Err((e, buf));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, A: AES128<'a> + AES128CBC> symmetric_encryption::Client<'a> for AES128CMAC<'a, A> {
+    fn crypt_done(
+        &self,
+        _: Option<&'a mut [u8]>,
+        crypt_buf: &'a mut [u8],
+        res: Result<(), CryptoError>,
+    ) {
+        self.crypt_buf.replace(crypt_buf);
+        if let Err(e) = res {
+            if self.state.get() != CMACState::Idle {
+                self.finish(Err(e), false);
+            }
+            return;
+        }
+        match self.state.get() {
+            CMACState::Idle => {}
+            CMACState::Subkey => {
+                let mut l = [0u8; AES128_BLOCK_SIZE];
+                self.crypt_buf.map(|cbuf| {
+                    l.copy_from_slice(&cbuf[..AES128_BLOCK_SIZE]);
+                    cbuf[..AES128_BLOCK_SIZE].iter_mut().for_each(|b| *b = 0);
+                });
+                let k1 = Self::dbl(&l);
+                let k2 = Self::dbl(&k1);
+                self.subkeys.set((k1, k2));
+
+                if let Err(e) = self.start_mac() {
+                    self.finish(Err(e), false);
+                }
+            }
+            CMACState::Mac => {
+                self.end_mac();
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Provides userspace with access to a battery fuel gauge and, optionally, a
+//! battery charger.
+//!
+//! Userspace Interface
+//! -------------------
+//!
+//! ### `subscribe` System Call
+//!
+//! The `subscribe` system call supports the single `subscribe_number` zero,
+//! which is used to provide a callback that will return back the result of
+//! a fuel gauge reading. The callback is called with:
+//!
+//! * `0` and the state of charge in hundredths of percent,
+//! * `1` and the battery voltage in millivolts, or
+//! * `2` and the `ReturnCode` of the error if the reading failed.
+//!
+//! ### `command` System Call
+//!
+//! The `command` system call support one argument `cmd` which is used to specify the specific
+//! operation, currently the following cmd's are supported:
+//!
+//! * `0`: check whether the driver exist
+//! * `1`: read the state of charge
+//! * `2`: read the battery voltage
+//! * `3`: get the charging status. Returns `0` when discharging, `1` when
+//!   charging and `2` when external power is present but the battery is not
+//!   charging.
+//!
+//! The possible return from the 'command' system call indicates the following:
+//!
+//! * `SUCCESS`:    The operation has been successful.
+//! * `EBUSY`:      The driver is busy.
+//! * `ENODEVICE`:  The board has no charger.
+//! * `ENOSUPPORT`: Invalid `cmd`.
+//! * `ENOMEM`:     No sufficient memory available.
+//!
+//! Usage
+//! -----
+//!
+//! You need a device that provides the `hil::power::BatteryGauge` trait, and
+//! optionally one that provides `hil::power::Charger`.
+//!
+//! ```rust
+//! let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+//!
+//! let battery = static_init!(
+//!     capsules::battery::Battery<'static>,
+//!     capsules::battery::Battery::new(
+//!         max17048,
+//!         Some(bq24075),
+//!         board_kernel.create_grant(&grant_cap)
+//!     )
+//! );
+//! kernel::hil::power::BatteryGauge::set_client(max17048, battery);
+//! ```
+
+use core::cell::Cell;
+use kernel::hil;
+use kernel::system_events;
+use kernel::ReturnCode;
+use kernel::{AppId, Callback, Driver, Grant};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Battery as usize;
+
+/// State of charge, in hundredths of percent, below which processes are
+/// notified with a `BatteryLow` system event.
+const LOW_STATE_OF_CHARGE: usize = 1000;
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    subscribed: bool,
+}
+
+pub struct Battery<'a> {
+    gauge: &'a dyn hil::power::BatteryGauge,
+    charger: Option<&'a dyn hil::power::Charger>,
+    apps: Grant<App>,
+    busy: Cell<bool>,
+    /// Whether the last state of charge was below `LOW_STATE_OF_CHARGE`.
+    low: Cell<bool>,
+}
+
+impl<'a> Battery<'a> {
+    pub fn new(
+        gauge: &'a dyn hil::power::BatteryGauge,
+        charger: Option<&'a dyn hil::power::Charger>,
+        grant: Grant<App>,
+    ) -> Battery<'a> {
+        Battery {
+            gauge: gauge,
+            charger: charger,
+            apps: grant,
+            busy: Cell::new(false),
+            low: Cell::new(false),
+        }
+    }
+
+    fn enqueue_command<F>(&self, appid: AppId, read: F) -> ReturnCode
+    where
+        F: FnOnce() -> ReturnCode,
+    {
+        self.apps
+            .enter(appid, |app, _| {
+                if !self.busy.get() {
+                    let res = read();
+                    if res == ReturnCode::SUCCESS {
+                        app.subscribed = true;
+                        self.busy.set(true);
+                    }
+                    res
+                } else {
+                    ReturnCode::EBUSY
+                }
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    fn configure_callback(&self, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        self.apps
+            .enter(app_id, |app, _| {
+                app.callback = callback;
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    fn reading_done(&self, kind: usize, value: usize) {
+        self.busy.set(false);
+        for cntr in self.apps.iter() {
+            cntr.enter(|app, _| {
+                if app.subscribed {
+                    app.subscribed = false;
+                    app.callback.map(|mut cb| cb.schedule(kind, value, 0));
+                }
+            });
+        }
+    }
+}
+
+impl hil::power::BatteryGaugeClient for Battery<'_> {
+    fn state_of_charge(&self, soc: usize) {
+        let low = soc < LOW_STATE_OF_CHARGE;
+        if low && !self.low.get() {
+            system_events::notify(system_events::Event::BatteryLow, soc);
+        }
+        self.low.set(low);
+        self.reading_done(0, soc);
+    }
+
+    fn voltage(&self, voltage: usize) {
+        self.reading_done(1, voltage);
+    }
+
+    fn reading_failed(&self, error: ReturnCode) {
+        self.reading_done(2, usize::from(error));
+    }
+}
+
+impl Driver for Battery<'_> {
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            // subscribe to fuel gauge readings with callback
+            0 => self.configure_callback(callback, app_id),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            // check whether the driver exists
+            0 => ReturnCode::SUCCESS,
+
+            // read state of charge
+            1 => self.enqueue_command(appid, || self.gauge.read_state_of_charge()),
+
+            // read voltage
+            2 => self.enqueue_command(appid, || self.gauge.read_voltage()),
+
+            // get charging status
+            3 => self.charger.map_or(ReturnCode::ENODEVICE, |charger| {
+                ReturnCode::SuccessWithValue {
+                    value: charger.charging_status() as usize,
+                }
+            }),
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
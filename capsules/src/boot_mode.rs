@@ -0,0 +1,96 @@
+//! Lets processes read the mode the chip booted in, and select the mode of
+//! the next boot (see `kernel::hil::boot_mode`).
+//!
+//! Userspace Interface
+//! -------------------
+//!
+//! ### `command` System Call
+//!
+//! * `0`: check whether the driver exists
+//! * `1`: get the current boot mode, the number of a
+//!   `kernel::hil::boot_mode::BootMode`
+//! * `2`: select the mode of the next boot. `arg1` is the number of the
+//!   mode. Returns `EINVAL` for an unknown mode, and `ENOSUPPORT` for a
+//!   process that is not allowed to select the boot mode.
+//!
+//! Usage
+//! -----
+//!
+//! The driver holds the `BootModeCapability`, so only boards that want
+//! processes to be able to select the boot mode include it. Even then, only
+//! the processes named in `selectors` may select it: entering DFU or safe
+//! mode stops the other processes.
+//!
+//! ```rust
+//! struct BootModeCap;
+//! unsafe impl capabilities::BootModeCapability for BootModeCap {}
+//! unsafe impl capabilities::ProcessManagementCapability for BootModeCap {}
+//!
+//! let boot_mode = static_init!(
+//!     capsules::boot_mode::BootModeDriver<BootModeCap>,
+//!     capsules::boot_mode::BootModeDriver::new(
+//!         board_kernel,
+//!         &nrf52::boot_mode::BOOT_MODE,
+//!         &["updater"],
+//!         BootModeCap
+//!     )
+//! );
+//! ```
+
+use crate::driver;
+use kernel::capabilities::{BootModeCapability, ProcessManagementCapability};
+use kernel::hil::boot_mode::{BootMode, BootModeControl};
+use kernel::introspection::KernelInfo;
+use kernel::{AppId, Driver, ReturnCode};
+
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = driver::NUM::BootMode as usize;
+
+pub struct BootModeDriver<'a, C: BootModeCapability + ProcessManagementCapability> {
+    info: KernelInfo,
+    control: &'a dyn BootModeControl,
+    /// Names of the processes allowed to select the boot mode.
+    selectors: &'a [&'a str],
+    capability: C,
+}
+
+impl<'a, C: BootModeCapability + ProcessManagementCapability> BootModeDriver<'a, C> {
+    pub fn new(
+        kernel: &'static kernel::Kernel,
+        control: &'a dyn BootModeControl,
+        selectors: &'a [&'a str],
+        capability: C,
+    ) -> BootModeDriver<'a, C> {
+        BootModeDriver {
+            info: KernelInfo::new(kernel),
+            control: control,
+            selectors: selectors,
+            capability: capability,
+        }
+    }
+}
+
+impl<C: BootModeCapability + ProcessManagementCapability> Driver for BootModeDriver<'_, C> {
+    fn command(&self, command_num: usize, data1: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: self.control.boot_mode() as usize,
+            },
+            2 => {
+                let name = self.info.process_name(appid, &self.capability);
+                if !self.selectors.contains(&name) {
+                    return ReturnCode::ENOSUPPORT;
+                }
+                match BootMode::from_usize(data1) {
+                    Some(mode) => {
+                        self.control.set_next_boot_mode(mode, &self.capability);
+                        ReturnCode::SUCCESS
+                    }
+                    None => ReturnCode::EINVAL,
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Driver for the status pins of the TI BQ24075 battery charger.
+//!
+//! <https://www.ti.com/product/BQ24075>
+//!
+//! The BQ24075 is a standalone linear Li-Ion charger. It reports its state
+//! through two open-drain, active-low outputs:
+//!
+//! - `PGOOD`: asserted when a valid input power source is present.
+//! - `CHG`: asserted while the battery is being charged.
+//!
+//! This driver configures both pins as inputs with pull-ups and implements
+//! `hil::power::Charger` on top of them.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let charger = static_init!(
+//!     capsules::bq24075::BQ24075<'static>,
+//!     capsules::bq24075::BQ24075::new(
+//!         &nrf52840::gpio::PORT[CHARGER_PGOOD_PIN],
+//!         &nrf52840::gpio::PORT[CHARGER_CHG_PIN]
+//!     )
+//! );
+//! ```
+
+use kernel::hil::gpio;
+use kernel::hil::power;
+
+pub struct BQ24075<'a> {
+    pgood: &'a dyn gpio::Pin,
+    chg: &'a dyn gpio::Pin,
+}
+
+impl<'a> BQ24075<'a> {
+    pub fn new(pgood: &'a dyn gpio::Pin, chg: &'a dyn gpio::Pin) -> BQ24075<'a> {
+        for pin in [pgood, chg].iter() {
+            pin.make_input();
+            pin.set_floating_state(gpio::FloatingState::PullUp);
+        }
+        BQ24075 {
+            pgood: pgood,
+            chg: chg,
+        }
+    }
+}
+
+impl power::Charger for BQ24075<'_> {
+    fn charging_status(&self) -> power::ChargingStatus {
+        let power_good = self.pgood.read_activation(gpio::ActivationMode::ActiveLow)
+            == gpio::ActivationState::Active;
+        let charging = self.chg.read_activation(gpio::ActivationMode::ActiveLow)
+            == gpio::ActivationState::Active;
+
+        match (power_good, charging) {
+            (false, _) => power::ChargingStatus::Discharging,
+            (true, true) => power::ChargingStatus::Charging,
+            (true, false) => power::ChargingStatus::Charged,
+        }
+    }
+}
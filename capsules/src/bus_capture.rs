@@ -0,0 +1,240 @@
+//! Records the last transactions on SPI and I2C buses for debugging.
+//!
+//! `BusCapture` keeps a ring of the most recent transactions issued through
+//! `virtual_spi::MuxSpiMaster` and `virtual_i2c::MuxI2C`: the bus, the device,
+//! the first bytes written, the lengths, the result and how long the
+//! transaction took. This shows what actually went over the wire when a
+//! driver, e.g. for the MX25R6435F flash chip or an external sensor,
+//! misbehaves.
+//!
+//! Capturing starts disabled and costs a single check per transaction until
+//! it is enabled, e.g. with the `bus on` command of the process console.
+//!
+//! SPI devices are identified by their position in the list of devices of the
+//! mux, I2C devices by their address.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let bus_capture = components::bus_capture::BusCaptureComponent::new(&nrf52::rtc::RTC)
+//!     .finalize(components::bus_capture_component_helper!(nrf52::rtc::Rtc, 32));
+//! mux_spi.set_capture(bus_capture);
+//! pconsole.set_bus_capture(bus_capture);
+//! ```
+
+use core::cell::Cell;
+use core::fmt;
+use kernel::common::cells::TakeCell;
+use kernel::hil::i2c;
+use kernel::hil::time::{Frequency, Time};
+
+/// Number of bytes written at the start of a transaction that are recorded.
+pub const COMMAND_LEN: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bus {
+    Spi,
+    I2C,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    /// The transaction has not completed yet.
+    Pending,
+    Complete,
+    /// The I2C transaction failed.
+    I2CError(i2c::Error),
+}
+
+#[derive(Clone, Copy)]
+pub struct Transaction {
+    /// Number of the transaction, counting all captured transactions.
+    pub sequence: u32,
+    pub bus: Bus,
+    pub device: u8,
+    /// The first `command_len` bytes written.
+    pub command: [u8; COMMAND_LEN],
+    pub command_len: u8,
+    pub write_len: u16,
+    pub read_len: u16,
+    pub status: Status,
+    /// Time from issuing the transaction to its completion.
+    pub duration_us: u32,
+    /// Start time in clock tics, only used until completion.
+    start: u32,
+}
+
+impl Transaction {
+    /// An empty entry, to initialize the buffer of a `BusCapture`.
+    pub const EMPTY: Transaction = Transaction {
+        sequence: 0,
+        bus: Bus::Spi,
+        device: 0,
+        command: [0; COMMAND_LEN],
+        command_len: 0,
+        write_len: 0,
+        read_len: 0,
+        status: Status::Pending,
+        duration_us: 0,
+        start: 0,
+    };
+}
+
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.bus {
+            Bus::Spi => write!(f, "{:5} spi dev {}", self.sequence, self.device)?,
+            Bus::I2C => write!(f, "{:5} i2c {:#04x}", self.sequence, self.device)?,
+        }
+        write!(f, " w {:3} r {:3} [", self.write_len, self.read_len)?;
+        for (i, byte) in self.command[..self.command_len as usize].iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, "] ")?;
+        match self.status {
+            Status::Pending => write!(f, "pending"),
+            Status::Complete => write!(f, "ok {}us", self.duration_us),
+            Status::I2CError(error) => write!(f, "{:?} {}us", error, self.duration_us),
+        }
+    }
+}
+
+/// Interface used by the bus muxes to record transactions, and by the process
+/// console to read them.
+pub trait Capture {
+    /// Record the start of a transaction. Returns a handle to pass to
+    /// `complete()`, or `None` if capturing is disabled.
+    fn start(
+        &self,
+        bus: Bus,
+        device: u8,
+        write: &[u8],
+        write_len: usize,
+        read_len: usize,
+    ) -> Option<u32>;
+
+    /// Record the result of the transaction started with `handle`.
+    fn complete(&self, handle: u32, status: Status);
+
+    fn set_enabled(&self, enabled: bool);
+
+    fn is_enabled(&self) -> bool;
+
+    /// Returns the number of recorded transactions.
+    fn len(&self) -> usize;
+
+    /// Returns the transaction at `index`, the oldest being at index `0`.
+    fn get(&self, index: usize) -> Option<Transaction>;
+
+    /// Remove all recorded transactions.
+    fn clear(&self);
+}
+
+pub struct BusCapture<'a, T: Time> {
+    time: &'a T,
+    transactions: TakeCell<'a, [Transaction]>,
+    enabled: Cell<bool>,
+    /// Sequence number of the next transaction.
+    sequence: Cell<u32>,
+    /// Number of recorded transactions.
+    count: Cell<usize>,
+}
+
+impl<'a, T: Time> BusCapture<'a, T> {
+    pub fn new(time: &'a T, transactions: &'a mut [Transaction]) -> BusCapture<'a, T> {
+        BusCapture {
+            time: time,
+            transactions: TakeCell::new(transactions),
+            enabled: Cell::new(false),
+            sequence: Cell::new(0),
+            count: Cell::new(0),
+        }
+    }
+
+    fn tics_to_us(&self, tics: u32) -> u32 {
+        (tics as u64 * 1_000_000 / <T::Frequency>::frequency() as u64) as u32
+    }
+}
+
+impl<'a, T: Time> Capture for BusCapture<'a, T> {
+    fn start(
+        &self,
+        bus: Bus,
+        device: u8,
+        write: &[u8],
+        write_len: usize,
+        read_len: usize,
+    ) -> Option<u32> {
+        if !self.enabled.get() {
+            return None;
+        }
+        let start = self.time.now();
+        self.transactions.map_or(None, |transactions| {
+            if transactions.is_empty() {
+                return None;
+            }
+            let sequence = self.sequence.get();
+            let transaction = &mut transactions[sequence as usize % transactions.len()];
+            *transaction = Transaction::EMPTY;
+            transaction.sequence = sequence;
+            transaction.bus = bus;
+            transaction.device = device;
+            let command_len = core::cmp::min(COMMAND_LEN, core::cmp::min(write_len, write.len()));
+            transaction.command[..command_len].copy_from_slice(&write[..command_len]);
+            transaction.command_len = command_len as u8;
+            transaction.write_len = write_len as u16;
+            transaction.read_len = read_len as u16;
+            transaction.start = start;
+
+            self.sequence.set(sequence.wrapping_add(1));
+            if self.count.get() < transactions.len() {
+                self.count.set(self.count.get() + 1);
+            }
+            Some(sequence)
+        })
+    }
+
+    fn complete(&self, handle: u32, status: Status) {
+        let now = self.time.now();
+        self.transactions.map(|transactions| {
+            let transaction = &mut transactions[handle as usize % transactions.len()];
+            // The slot may have been reused in the meantime.
+            if transaction.sequence != handle || transaction.status != Status::Pending {
+                return;
+            }
+            let elapsed = now.wrapping_sub(transaction.start) & self.time.max_tics();
+            transaction.duration_us = self.tics_to_us(elapsed);
+            transaction.status = status;
+        });
+    }
+
+    fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    fn len(&self) -> usize {
+        self.count.get()
+    }
+
+    fn get(&self, index: usize) -> Option<Transaction> {
+        if index >= self.count.get() {
+            return None;
+        }
+        self.transactions.map_or(None, |transactions| {
+            let first = self.sequence.get() as usize + transactions.len() - self.count.get();
+            Some(transactions[(first + index) % transactions.len()])
+        })
+    }
+
+    fn clear(&self) {
+        self.count.set(0);
+    }
+}
@@ -33,9 +33,22 @@
 //! When the buffer has been written successfully, the buffer is released from
 //! the driver. Successive writes must call `allow` each time a buffer is to be
 //! written.
+//!
+//! Framing
+//! -------
+//!
+//! On boards where processes and the kernel debug output share one UART, the
+//! board can call `set_framing(true)`, and `set_framing()` of the
+//! `DebugWriter`, so that a host can tell the streams apart. Each write is
+//! then sent as one or more COBS frames, whose first byte is the channel of
+//! the process that wrote it, see `doc/ConsoleFraming.md`. Reads are not
+//! framed.
 
+use core::cell::Cell;
 use core::cmp;
+use core::iter;
 use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::cobs;
 use kernel::hil::uart;
 use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
 
@@ -66,6 +79,13 @@ pub struct Console<'a> {
     tx_buffer: TakeCell<'static, [u8]>,
     rx_in_progress: OptionalCell<AppId>,
     rx_buffer: TakeCell<'static, [u8]>,
+    framing: Cell<bool>,
+}
+
+/// Channel of the framed output of a process. Channel 0 is the kernel debug
+/// output.
+fn channel(app_id: AppId) -> u8 {
+    (app_id.id() % 255 + 1) as u8
 }
 
 impl<'a> Console<'a> {
@@ -82,9 +102,16 @@ impl<'a> Console<'a> {
             tx_buffer: TakeCell::new(tx_buffer),
             rx_in_progress: OptionalCell::empty(),
             rx_buffer: TakeCell::new(rx_buffer),
+            framing: Cell::new(false),
         }
     }
 
+    /// Send the output of each process in COBS frames tagged with the
+    /// channel of the process.
+    pub fn set_framing(&self, framing: bool) {
+        self.framing.set(framing);
+    }
+
     /// Internal helper function for setting up a new send transaction
     fn send_new(&self, app_id: AppId, app: &mut App, len: usize) -> ReturnCode {
         match app.write_buffer.take() {
@@ -119,25 +146,29 @@ impl<'a> Console<'a> {
         if self.tx_in_progress.is_none() {
             self.tx_in_progress.set(app_id);
             self.tx_buffer.take().map(|buffer| {
-                let mut transaction_len = app.write_remaining;
-                for (i, c) in slice.as_ref()[slice.len() - app.write_remaining..slice.len()]
-                    .iter()
-                    .enumerate()
-                {
-                    if buffer.len() <= i {
-                        break;
-                    }
-                    buffer[i] = *c;
-                }
+                let start = app.write_len - app.write_remaining;
+                let capacity = if self.framing.get() {
+                    // Leave room for the channel and the COBS overhead
+                    cobs::max_payload_len(buffer.len()).saturating_sub(1)
+                } else {
+                    buffer.len()
+                };
+                let len = cmp::min(app.write_remaining, capacity);
+                let data = &slice.as_ref()[start..start + len];
+
+                let transaction_len = if self.framing.get() {
+                    let framed = iter::once(channel(app_id)).chain(data.iter().cloned());
+                    cobs::encode(framed, buffer)
+                } else {
+                    buffer[..len].copy_from_slice(data);
+                    len
+                };
 
                 // Check if everything we wanted to print
                 // fit in the buffer.
-                if app.write_remaining > buffer.len() {
-                    transaction_len = buffer.len();
-                    app.write_remaining -= buffer.len();
+                app.write_remaining -= len;
+                if app.write_remaining > 0 {
                     app.write_buffer = Some(slice);
-                } else {
-                    app.write_remaining = 0;
                 }
 
                 let (_err, _opt) = self.uart.transmit_buffer(buffer, transaction_len);
@@ -358,23 +389,21 @@ impl uart::ReceiveClient for Console<'_> {
                             // An iterator over the returned buffer yielding only the first `rx_len`
                             // bytes
                             let rx_buffer = buffer.iter().take(rx_len);
-                            match error {
-                                uart::Error::None | uart::Error::Aborted => {
-                                    // Receive some bytes, signal error type and return bytes to process buffer
-                                    if let Some(mut app_buffer) = app.read_buffer.take() {
-                                        for (a, b) in app_buffer.iter_mut().zip(rx_buffer) {
-                                            *a = *b;
-                                        }
-                                        cb.schedule(From::from(rcode), rx_len, 0);
-                                    } else {
-                                        // Oops, no app buffer
-                                        cb.schedule(From::from(ReturnCode::EINVAL), 0, 0);
-                                    }
-                                }
-                                _ => {
-                                    // Some UART error occurred
-                                    cb.schedule(From::from(ReturnCode::FAIL), 0, 0);
+                            let rcode = match error {
+                                uart::Error::None | uart::Error::Aborted => rcode,
+                                // Some UART error occurred, the bytes received
+                                // before it are still returned
+                                _ => ReturnCode::FAIL,
+                            };
+                            // Receive some bytes, signal error type and return bytes to process buffer
+                            if let Some(mut app_buffer) = app.read_buffer.take() {
+                                for (a, b) in app_buffer.iter_mut().zip(rx_buffer) {
+                                    *a = *b;
                                 }
+                                cb.schedule(From::from(rcode), rx_len, 0);
+                            } else {
+                                // Oops, no app buffer
+                                cb.schedule(From::from(ReturnCode::EINVAL), 0, 0);
                             }
                         });
                     })
@@ -0,0 +1,218 @@
+//! Persists event counters in nonvolatile storage.
+//!
+//! `CounterStore` keeps a set of `kernel::metrics::Counter`s across reboots,
+//! e.g. the erase counts of flash sectors. `restore()` reads the stored counts
+//! and adds them to the counters. Afterwards the counts are written back
+//! periodically, but only if they changed since the last write.
+//!
+//! The record is a magic number followed by, for each counter, the number of
+//! entries and the value of each entry, all as little-endian `u32`s. A record
+//! that does not match the counters, e.g. because the board configuration
+//! changed, is ignored.
+//!
+//! Writing the record wears the storage as well, so the period should be long,
+//! e.g. an hour.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let counters = static_init!(
+//!     [&'static kernel::metrics::Counter<'static>; 2],
+//!     [nvmc_erase, flash_erase]
+//! );
+//! let counter_store = components::counter_store::CounterStoreComponent::new(
+//!     mux_alarm,
+//!     nonvolatile_storage,
+//!     counters,
+//!     0x5f000, // Address of the record
+//!     3600,    // Write the counts at most once per hour
+//! )
+//! .finalize(components::counter_store_component_helper!(nrf52::rtc::Rtc, 1548));
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::hil::time::{self, Alarm, Frequency};
+use kernel::metrics::Counter;
+use kernel::ReturnCode;
+
+const MAGIC: u32 = 0x434e_5453;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    Restoring,
+    Saving,
+}
+
+pub struct CounterStore<'a, A: Alarm<'a>> {
+    storage: &'a dyn NonvolatileStorage<'a>,
+    alarm: &'a A,
+    counters: &'a [&'a Counter<'a>],
+    address: usize,
+    period_s: u32,
+    buffer: TakeCell<'a, [u8]>,
+    state: Cell<State>,
+    /// Sum of all counts in the stored record.
+    saved_total: Cell<u64>,
+    /// Sum of all counts in the record being written.
+    pending_total: Cell<u64>,
+}
+
+impl<'a, A: Alarm<'a>> CounterStore<'a, A> {
+    pub fn new(
+        storage: &'a dyn NonvolatileStorage<'a>,
+        alarm: &'a A,
+        counters: &'a [&'a Counter<'a>],
+        address: usize,
+        period_s: u32,
+        buffer: &'a mut [u8],
+    ) -> CounterStore<'a, A> {
+        CounterStore {
+            storage: storage,
+            alarm: alarm,
+            counters: counters,
+            address: address,
+            period_s: period_s,
+            buffer: TakeCell::new(buffer),
+            state: Cell::new(State::Idle),
+            saved_total: Cell::new(0),
+            pending_total: Cell::new(0),
+        }
+    }
+
+    /// Length of the record in bytes.
+    pub fn record_len(&self) -> usize {
+        4 + self
+            .counters
+            .iter()
+            .map(|counter| 4 + 4 * counter.len())
+            .sum::<usize>()
+    }
+
+    fn total(&self) -> u64 {
+        self.counters
+            .iter()
+            .flat_map(|counter| (0..counter.len()).map(move |i| counter.get_index(i)))
+            .map(|count| count.unwrap_or(0) as u64)
+            .sum()
+    }
+
+    fn start_timer(&self) {
+        let interval = self.period_s.saturating_mul(<A::Frequency>::frequency());
+        self.alarm
+            .set_alarm(self.alarm.now().wrapping_add(interval));
+    }
+
+    /// Read the stored counts, add them to the counters and start writing
+    /// them back periodically.
+    pub fn restore(&self) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        let len = self.record_len();
+        self.buffer.take().map_or(ReturnCode::ENOMEM, |buffer| {
+            if buffer.len() < len {
+                self.buffer.replace(buffer);
+                return ReturnCode::ESIZE;
+            }
+            let res = self.storage.read(buffer, self.address, len);
+            if res == ReturnCode::SUCCESS {
+                self.state.set(State::Restoring);
+            } else {
+                self.start_timer();
+            }
+            res
+        })
+    }
+
+    fn save(&self) {
+        let total = self.total();
+        if total == self.saved_total.get() {
+            self.start_timer();
+            return;
+        }
+        let len = self.record_len();
+        let res = self.buffer.take().map_or(ReturnCode::ENOMEM, |buffer| {
+            let mut words = buffer.chunks_mut(4);
+            let mut put = |value: u32| {
+                words
+                    .next()
+                    .map(|word| word.copy_from_slice(&value.to_le_bytes()));
+            };
+            put(MAGIC);
+            for counter in self.counters.iter() {
+                put(counter.len() as u32);
+                for i in 0..counter.len() {
+                    put(counter.get_index(i).unwrap_or(0));
+                }
+            }
+            self.storage.write(buffer, self.address, len)
+        });
+        if res == ReturnCode::SUCCESS {
+            self.pending_total.set(total);
+            self.state.set(State::Saving);
+        } else {
+            self.start_timer();
+        }
+    }
+}
+
+fn word(buffer: &[u8], index: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buffer[4 * index..4 * index + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+impl<'a, A: Alarm<'a>> time::AlarmClient for CounterStore<'a, A> {
+    fn fired(&self) {
+        if self.state.get() == State::Idle {
+            self.save();
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> NonvolatileStorageClient<'a> for CounterStore<'a, A> {
+    fn read_done(&self, buffer: &'a mut [u8], length: usize) {
+        // Check the whole layout before touching any counter.
+        let mut valid = length == self.record_len() && word(buffer, 0) == MAGIC;
+        let mut index = 1;
+        for counter in self.counters.iter() {
+            if !valid {
+                break;
+            }
+            valid = word(buffer, index) as usize == counter.len();
+            index += 1 + counter.len();
+        }
+
+        if valid {
+            let mut index = 1;
+            let mut saved_total = 0;
+            for counter in self.counters.iter() {
+                index += 1;
+                for i in 0..counter.len() {
+                    let count = word(buffer, index);
+                    if let Some(key) = counter.key(i) {
+                        counter.add(key, count);
+                    }
+                    saved_total += count as u64;
+                    index += 1;
+                }
+            }
+            self.saved_total.set(saved_total);
+        }
+
+        self.buffer.replace(buffer);
+        self.state.set(State::Idle);
+        self.start_timer();
+    }
+
+    fn write_done(&self, buffer: &'a mut [u8], _length: usize) {
+        self.saved_total.set(self.pending_total.get());
+        self.buffer.replace(buffer);
+        self.state.set(State::Idle);
+        self.start_timer();
+    }
+}
@@ -0,0 +1,325 @@
+//! Board-level selection of the backend of each cryptographic algorithm.
+//!
+//! A `CryptoRegistry` stands in for the engine of the capsules that use an
+//! algorithm. The board binds each algorithm to a backend once, in
+//! `main.rs`, and the capsules are instantiated with the registry as their
+//! engine. A product can then trade power, speed and key protection, for
+//! example the nRF5x ECB peripheral against `SoftwareAes`, by changing the
+//! binding only: neither the capsules nor their types in the `Platform`
+//! struct change.
+//!
+//! The algorithms that have more than one backend in this tree are bound:
+//!
+//! * `sha256`: SHA-256 and HMAC-SHA-256, with `Digest`, `DigestVerify`,
+//!   `Sha256` and `HMACSha256`. Backends: `lowrisc::hmac::Hmac` and
+//!   `capsules::sha256::SoftwareSha256`.
+//! * `aes128`: AES-128 in ECB, CBC and CTR modes and single blocks, with
+//!   `AES128`, `AES128Ctr`, `AES128CBC`, `AES128Block` and `ClearKeys`.
+//!   Backends: `nrf5x::aes::AesECB` and `capsules::software_aes::SoftwareAes`.
+//!   AES-CCM for 802.15.4 is built on this binding by `capsules::aes_ccm`.
+//!
+//! The operations of an algorithm that is not bound return `NotSupported`.
+//! Each algorithm must be bound before the capsules set themselves as
+//! clients, as setting a client is forwarded to the backend.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let crypto = static_init!(
+//!     capsules::crypto_registry::CryptoRegistry<'static>,
+//!     capsules::crypto_registry::CryptoRegistry::new()
+//! );
+//! crypto.bind_aes128(&nrf52::aes::AESECB);
+//! // or, to keep the peripheral free for the radio:
+//! // crypto.bind_aes128(software_aes);
+//!
+//! let mux_aes = static_init!(
+//!     MuxAES128Block<'static, CryptoRegistry<'static>>,
+//!     MuxAES128Block::new(crypto)
+//! );
+//! AES128Block::set_block_client(crypto, mux_aes);
+//! ```
+
+use kernel::common::cells::OptionalCell;
+use kernel::common::leasable_buffer::{LeasableBuffer, ReadOnlyLeasableBuffer};
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::digest::{self, Cancelled};
+use kernel::hil::symmetric_encryption::{
+    AES128Block, AES128Ctr, BlockClient, ClearKeys, Client, AES128, AES128CBC, AES128_BLOCK_SIZE,
+    AES128_KEY_SIZE,
+};
+
+/// The interfaces a SHA-256 backend provides.
+pub trait Sha256Backend<'a>:
+    digest::Digest<'a, [u8; 32]>
+    + digest::DigestVerify<'a, [u8; 32]>
+    + digest::Sha256
+    + digest::HMACSha256
+{
+}
+
+impl<'a, E> Sha256Backend<'a> for E where
+    E: digest::Digest<'a, [u8; 32]>
+        + digest::DigestVerify<'a, [u8; 32]>
+        + digest::Sha256
+        + digest::HMACSha256
+{
+}
+
+/// The interfaces an AES-128 backend provides.
+pub trait Aes128Backend<'a>:
+    AES128<'a> + AES128Ctr + AES128CBC + AES128Block<'a> + ClearKeys
+{
+}
+
+impl<'a, E> Aes128Backend<'a> for E where
+    E: AES128<'a> + AES128Ctr + AES128CBC + AES128Block<'a> + ClearKeys
+{
+}
+
+pub struct CryptoRegistry<'a> {
+    sha256: OptionalCell<&'a dyn Sha256Backend<'a>>,
+    aes128: OptionalCell<&'a dyn Aes128Backend<'a>>,
+}
+
+impl<'a> CryptoRegistry<'a> {
+    pub const fn new() -> CryptoRegistry<'a> {
+        CryptoRegistry {
+            sha256: OptionalCell::empty(),
+            aes128: OptionalCell::empty(),
+        }
+    }
+
+    /// Use `backend` for SHA-256 and HMAC-SHA-256.
+    pub fn bind_sha256(&self, backend: &'a dyn Sha256Backend<'a>) {
+        self.sha256.set(backend);
+    }
+
+    /// Use `backend` for AES-128.
+    pub fn bind_aes128(&self, backend: &'a dyn Aes128Backend<'a>) {
+        self.aes128.set(backend);
+    }
+
+    fn sha256(&self) -> Option<&'a dyn Sha256Backend<'a>> {
+        self.sha256.map(|backend| *backend)
+    }
+
+    fn aes128(&self) -> Option<&'a dyn Aes128Backend<'a>> {
+        self.aes128.map(|backend| *backend)
+    }
+}
+
+impl<'a> digest::Digest<'a, [u8; 32]> for CryptoRegistry<'a> {
+    fn set_client(&'a self, client: &'a dyn digest::Client<'a, [u8; 32]>) {
+        self.sha256().map(|backend| backend.set_client(client));
+    }
+
+    fn add_data(
+        &self,
+        data: LeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static mut [u8])> {
+        match self.sha256() {
+            Some(backend) => backend.add_data(data),
+            None => Err((CryptoError::NotSupported, data.take())),
+        }
+    }
+
+    fn add_readonly_data(
+        &self,
+        data: ReadOnlyLeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static [u8])> {
+        match self.sha256() {
+            Some(backend) => backend.add_readonly_data(data),
+            None => Err((CryptoError::NotSupported, data.take())),
+        }
+    }
+
+    fn run(
+        &'a self,
+        digest: &'static mut [u8; 32],
+    ) -> Result<(), (CryptoError, &'static mut [u8; 32])> {
+        match self.sha256() {
+            Some(backend) => backend.run(digest),
+            None => Err((CryptoError::NotSupported, digest)),
+        }
+    }
+
+    fn clear_data(&self) {
+        self.sha256().map(|backend| backend.clear_data());
+    }
+
+    fn cancel(&self) -> Cancelled<[u8; 32]> {
+        self.sha256()
+            .map_or(Cancelled::default(), |backend| backend.cancel())
+    }
+}
+
+impl<'a> digest::DigestVerify<'a, [u8; 32]> for CryptoRegistry<'a> {
+    fn set_verify_client(&'a self, client: &'a dyn digest::ClientVerify<'a, [u8; 32]>) {
+        self.sha256()
+            .map(|backend| backend.set_verify_client(client));
+    }
+
+    fn verify(
+        &'a self,
+        compare: &'static mut [u8; 32],
+    ) -> Result<(), (CryptoError, &'static mut [u8; 32])> {
+        match self.sha256() {
+            Some(backend) => backend.verify(compare),
+            None => Err((CryptoError::NotSupported, compare)),
+        }
+    }
+}
+
+impl digest::Sha256 for CryptoRegistry<'_> {
+    fn set_mode_sha256(&self) -> Result<(), CryptoError> {
+        self.sha256()
+            .map_or(Err(CryptoError::NotSupported), |backend| {
+                backend.set_mode_sha256()
+            })
+    }
+}
+
+impl digest::HMACSha256 for CryptoRegistry<'_> {
+    fn set_mode_hmacsha256(&self, key: &[u8]) -> Result<(), CryptoError> {
+        self.sha256()
+            .map_or(Err(CryptoError::NotSupported), |backend| {
+                backend.set_mode_hmacsha256(key)
+            })
+    }
+}
+
+impl<'a> AES128<'a> for CryptoRegistry<'a> {
+    fn enable(&self) {
+        self.aes128().map(|backend| backend.enable());
+    }
+
+    fn disable(&self) {
+        self.aes128().map(|backend| backend.disable());
+    }
+
+    fn set_client(&'a self, client: &'a dyn Client<'a>) {
+        self.aes128()
+            .map(|backend| AES128::set_client(backend, client));
+    }
+
+    fn set_key(&self, key: &[u8]) -> Result<(), CryptoError> {
+        self.aes128()
+            .map_or(Err(CryptoError::NotSupported), |backend| {
+                AES128::set_key(backend, key)
+            })
+    }
+
+    fn set_iv(&self, iv: &[u8]) -> Result<(), CryptoError> {
+        self.aes128()
+            .map_or(Err(CryptoError::NotSupported), |backend| backend.set_iv(iv))
+    }
+
+    fn start_message(&self) {
+        self.aes128().map(|backend| backend.start_message());
+    }
+
+    fn crypt(
+        &'a self,
+        source: Option<&'a mut [u8]>,
+        dest: &'a mut [u8],
+        start_index: usize,
+        stop_index: usize,
+    ) -> Option<(CryptoError, Option<&'a mut [u8]>, &'a mut [u8])> {
+        match self.aes128() {
+            Some(backend) => backend.crypt(source, dest, start_index, stop_index),
+            None => Some((CryptoError::NotSupported, source, dest)),
+        }
+    }
+}
+
+impl AES128Ctr for CryptoRegistry<'_> {
+    fn set_mode_aes128ctr(&self, encrypting: bool) {
+        self.aes128()
+            .map(|backend| backend.set_mode_aes128ctr(encrypting));
+    }
+
+    fn set_ctr_no_increment(&self, no_increment: bool) -> Result<(), CryptoError> {
+        self.aes128()
+            .map_or(Err(CryptoError::NotSupported), |backend| {
+                backend.set_ctr_no_increment(no_increment)
+            })
+    }
+}
+
+impl AES128CBC for CryptoRegistry<'_> {
+    fn set_mode_aes128cbc(&self, encrypting: bool) {
+        self.aes128()
+            .map(|backend| backend.set_mode_aes128cbc(encrypting));
+    }
+}
+
+impl<'a> AES128Block<'a> for CryptoRegistry<'a> {
+    fn set_block_client(&'a self, client: &'a dyn BlockClient) {
+        self.aes128()
+            .map(|backend| backend.set_block_client(client));
+    }
+
+    fn ecb_encrypt_block(
+        &self,
+        key: &[u8; AES128_KEY_SIZE],
+        block: &[u8; AES128_BLOCK_SIZE],
+    ) -> Result<(), CryptoError> {
+        self.aes128()
+            .map_or(Err(CryptoError::NotSupported), |backend| {
+                backend.ecb_encrypt_block(key, block)
+            })
+    }
+}
+
+impl ClearKeys for CryptoRegistry<'_> {
+    fn clear_keys(&self) -> Result<(), CryptoError> {
+        self.aes128()
+            .map_or(Err(CryptoError::NotSupported), |backend| {
+                backend.clear_keys()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha256::SoftwareSha256;
+    use crate::software_aes::SoftwareAes;
+    use kernel::common::dynamic_deferred_call::DynamicDeferredCall;
+    use kernel::hil::digest::{HMACSha256, Sha256};
+
+    #[test]
+    fn unbound() {
+        let crypto = CryptoRegistry::new();
+        assert_eq!(
+            AES128::set_key(&crypto, &[0; AES128_KEY_SIZE]),
+            Err(CryptoError::NotSupported)
+        );
+        assert_eq!(crypto.clear_keys(), Err(CryptoError::NotSupported));
+        assert_eq!(crypto.set_mode_sha256(), Err(CryptoError::NotSupported));
+        assert_eq!(
+            crypto.set_mode_hmacsha256(&[0; 32]),
+            Err(CryptoError::NotSupported)
+        );
+    }
+
+    #[test]
+    fn bound() {
+        let deferred_caller = DynamicDeferredCall::new(&mut []);
+        let aes = SoftwareAes::new(&deferred_caller);
+        let sha = SoftwareSha256::new(&deferred_caller);
+        let crypto = CryptoRegistry::new();
+        crypto.bind_aes128(&aes);
+        crypto.bind_sha256(&sha);
+        assert_eq!(AES128::set_key(&crypto, &[0; AES128_KEY_SIZE]), Ok(()));
+        assert_eq!(
+            AES128::set_key(&crypto, &[0; AES128_KEY_SIZE - 1]),
+            Err(CryptoError::BadKeyLength)
+        );
+        assert_eq!(crypto.clear_keys(), Ok(()));
+        assert_eq!(crypto.set_mode_sha256(), Ok(()));
+        assert_eq!(crypto.set_mode_hmacsha256(&[0; 32]), Ok(()));
+    }
+}
@@ -0,0 +1,225 @@
+//! Add the data of chained buffers to any digest engine.
+//!
+//! Protocol capsules often need the digest of data that is split across
+//! buffers, such as the header, payload and trailer of a packet.
+//! `ChainedDigest` sits between a client and a digest engine. It passes the
+//! `Digest` calls through, and implements `DigestChain` by adding the
+//! buffers of a chain one after the other, with a single callback at the
+//! end, so the data never needs to be copied into one static buffer.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let chained_sha = static_init!(
+//!     capsules::digest_chain::ChainedDigest<'static, SoftwareSha256<'static>, [u8; 32]>,
+//!     capsules::digest_chain::ChainedDigest::new(sha)
+//! );
+//! digest::Digest::set_client(sha, chained_sha);
+//! digest::Digest::set_client(chained_sha, client);
+//! digest::DigestChain::set_chain_client(chained_sha, client);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::leasable_buffer::{LeasableBuffer, ReadOnlyLeasableBuffer};
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::digest::{self, DataChain, DigestType};
+
+pub struct ChainedDigest<'a, D: digest::Digest<'a, T>, T: DigestType> {
+    digest: &'a D,
+    client: OptionalCell<&'a dyn digest::Client<'a, T>>,
+    chain_client: OptionalCell<&'a dyn digest::ClientChain<'a>>,
+    /// The chain being added, if any.
+    chain: TakeCell<'static, DataChain>,
+    /// The slot of the buffer being added.
+    index: Cell<usize>,
+}
+
+impl<'a, D: digest::Digest<'a, T>, T: DigestType> ChainedDigest<'a, D, T> {
+    pub fn new(digest: &'a D) -> ChainedDigest<'a, D, T> {
+        ChainedDigest {
+            digest: digest,
+            client: OptionalCell::empty(),
+            chain_client: OptionalCell::empty(),
+            chain: TakeCell::empty(),
+            index: Cell::new(0),
+        }
+    }
+
+    /// Add the data of the next buffer of the chain, from slot `start`.
+    /// Returns `Ok(false)` when there is no buffer left to add.
+    fn add_next(&self, chain: &mut DataChain, start: usize) -> Result<bool, CryptoError> {
+        let next = chain
+            .iter()
+            .skip(start)
+            .position(|slot| slot.as_ref().map_or(false, |data| data.len() > 0));
+        let index = match next {
+            Some(position) => start + position,
+            None => return Ok(false),
+        };
+        self.index.set(index);
+        match chain[index].take().map(|data| self.digest.add_data(data)) {
+            Some(Err((e, data))) => {
+                chain[index] = Some(LeasableBuffer::new(data));
+                Err(e)
+            }
+            _ => Ok(true),
+        }
+    }
+
+    fn chain_done(&self, result: Result<(), CryptoError>) {
+        self.chain.take().map(|chain| {
+            self.chain_client
+                .map(move |client| client.add_data_chain_done(result, chain));
+        });
+    }
+}
+
+impl<'a, D: digest::Digest<'a, T>, T: DigestType> digest::Digest<'a, T>
+    for ChainedDigest<'a, D, T>
+{
+    fn set_client(&'a self, client: &'a dyn digest::Client<'a, T>) {
+        self.client.set(client);
+    }
+
+    fn add_data(
+        &self,
+        data: LeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static mut [u8])> {
+        if self.chain.is_some() {
+            return Err((CryptoError::EngineBusy, data.take()));
+        }
+        self.digest.add_data(data)
+    }
+
+    fn add_readonly_data(
+        &self,
+        data: ReadOnlyLeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static [u8])> {
+        if self.chain.is_some() {
+            return Err((CryptoError::EngineBusy, data.take()));
+        }
+        self.digest.add_readonly_data(data)
+    }
+
+    fn run(&'a self, digest: &'static mut T) -> Result<(), (CryptoError, &'static mut T)> {
+        if self.chain.is_some() {
+            return Err((CryptoError::EngineBusy, digest));
+        }
+        self.digest.run(digest)
+    }
+
+    fn clear_data(&self) {
+        self.digest.clear_data();
+    }
+
+    /// Stop the chain in progress, if any, and return it with the buffer
+    /// that was being added back in its slot.
+    fn cancel(&self) -> digest::Cancelled<T> {
+        let mut cancelled = self.digest.cancel();
+        if let Some(chain) = self.chain.take() {
+            if let Some(data) = cancelled.data.take() {
+                chain[self.index.get()] = Some(LeasableBuffer::new(data));
+            }
+            cancelled.chain = Some(chain);
+        }
+        cancelled
+    }
+}
+
+impl<'a, D: digest::Digest<'a, T>, T: DigestType> digest::DigestChain<'a>
+    for ChainedDigest<'a, D, T>
+{
+    fn set_chain_client(&'a self, client: &'a dyn digest::ClientChain<'a>) {
+        self.chain_client.set(client);
+    }
+
+    fn add_data_chain(
+        &self,
+        chain: &'static mut DataChain,
+    ) -> Result<(), (CryptoError, &'static mut DataChain)> {
+        if self.chain.is_some() {
+            return Err((CryptoError::EngineBusy, chain));
+        }
+        match self.add_next(chain, 0) {
+            Ok(true) => {
+                self.chain.replace(chain);
+                Ok(())
+            }
+            Ok(false) => Err((CryptoError::InvalidArgument, chain)),
+            Err(e) => Err((e, chain)),
+        }
+    }
+}
+
+impl<'a, D: digest::Digest<'a, T>, T: DigestType> digest::Client<'a, T>
+    for ChainedDigest<'a, D, T>
+{
+    fn add_data_done(&'a self, result: Result<(), CryptoError>, data: &'static mut [u8]) {
+        let chain = match self.chain.take() {
+            Some(chain) => chain,
+            None => {
+                self.client
+                    .map(move |client| client.add_data_done(result, data));
+                return;
+            }
+        };
+        let index = self.index.get();
+        chain[index] = Some(LeasableBuffer::new(data));
+        let next = result.and_then(|()| self.add_next(chain, index + 1));
+        self.chain.replace(chain);
+        match next {
+            Ok(true) => (),
+            Ok(false) => self.chain_done(Ok(())),
+            Err(e) => self.chain_done(Err(e)),
+        }
+    }
+
+    fn add_readonly_data_done(&'a self, result: Result<(), CryptoError>, data: &'static [u8]) {
+        self.client
+            .map(move |client| client.add_readonly_data_done(result, data));
+    }
+
+    fn hash_done(&'a self, result: Result<(), CryptoError>, digest: &'static mut T) {
+        self.client
+            .map(move |client| client.hash_done(result, digest));
+    }
+}
+
+impl<'a, D: digest::Digest<'a, T> + digest::DigestVerify<'a, T>, T: DigestType>
+    digest::DigestVerify<'a, T> for ChainedDigest<'a, D, T>
+{
+    fn set_verify_client(&'a self, client: &'a dyn digest::ClientVerify<'a, T>) {
+        self.digest.set_verify_client(client);
+    }
+
+    fn verify(&'a self, compare: &'static mut T) -> Result<(), (CryptoError, &'static mut T)> {
+        if self.chain.is_some() {
+            return Err((CryptoError::EngineBusy, compare));
+        }
+        self.digest.verify(compare)
+    }
+}
+
+impl<'a, D: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> digest::HMACSha256
+    for ChainedDigest<'a, D, T>
+{
+    fn set_mode_hmacsha256(&self, key: &[u8]) -> Result<(), CryptoError> {
+        if self.chain.is_some() {
+            return Err(CryptoError::EngineBusy);
+        }
+        self.digest.set_mode_hmacsha256(key)
+    }
+}
+
+impl<'a, D: digest::Digest<'a, T> + digest::Sha256, T: DigestType> digest::Sha256
+    for ChainedDigest<'a, D, T>
+{
+    fn set_mode_sha256(&self) -> Result<(), CryptoError> {
+        if self.chain.is_some() {
+            return Err(CryptoError::EngineBusy);
+        }
+        self.digest.set_mode_sha256()
+    }
+}
@@ -1,4 +1,9 @@
 //! Mapping of capsules to their syscall driver number.
+//!
+//! Every capsule that implements `Driver` takes its `DRIVER_NUM` from this
+//! enum, and boards match on those constants in `with_driver()`. Since the
+//! numbers are enum discriminants, assigning the same number to two drivers
+//! is rejected at compile time instead of silently shadowing one of them.
 
 use enum_primitive::cast::FromPrimitive;
 use enum_primitive::enum_from_primitive;
@@ -16,9 +21,16 @@ pub enum NUM {
     Adc                   = 0x00005,
     Dac                   = 0x00006,
     AnalogComparator      = 0x00007,
+    LowLevelDebug         = 0x00008,
+    AdcMonitor            = 0x00009,
 
     // Kernel
     Ipc                   = 0x10000,
+    Metrics               = 0x10001,
+    Journal               = 0x10002,
+    SystemEvents          = 0x10003,
+    Nonce                 = 0x10004,
+    BootMode              = 0x10005,
 
     // HW Buses
     Spi                   = 0x20001,
@@ -32,9 +44,12 @@ pub enum NUM {
     Udp                   = 0x30002,
 
     // Cryptography
+    Aes                   = 0x40000,
     Rng                   = 0x40001,
     Crc                   = 0x40002,
     Hmac                  = 0x40003,
+    Hkdf                  = 0x40004,
+    Measurement           = 0x40005,
 
     // Storage
     AppFlash              = 0x50000,
@@ -64,5 +79,11 @@ pub enum NUM {
 
     // Misc
     Buzzer                = 0x90000,
+    Battery               = 0x90001,
+    IrRemote              = 0x90002,
 }
 }
+
+// IPC is implemented in the kernel crate, which cannot use this enum. Fail
+// the build if the two numbers ever disagree.
+const _: [(); 1] = [(); (NUM::Ipc as usize == kernel::ipc::DRIVER_NUM) as usize];
@@ -0,0 +1,155 @@
+//! Driver for the Maxim DS18B20 1-Wire digital thermometer.
+//!
+//! <https://datasheets.maximintegrated.com/en/ds/DS18B20.pdf>
+//!
+//! The sensor converts the temperature in 750ms at its default 12-bit
+//! resolution, which this driver waits for with an alarm, then reads the
+//! scratchpad. A scratchpad with a wrong CRC is read again a few times, and
+//! the reading is dropped if it stays wrong. The sensor must be powered
+//! from its VDD pin: parasite power needs a strong pull-up during the
+//! conversion, which the bus driver does not provide.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let ds18b20_virtual_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let ds18b20 = static_init!(
+//!     capsules::ds18b20::Ds18b20<
+//!         'static,
+//!         nrf5x::gpio::GPIOPin,
+//!         nrf5x::timer::Timer,
+//!         VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+//!     >,
+//!     capsules::ds18b20::Ds18b20::new(one_wire, ds18b20_virtual_alarm, None)
+//! );
+//! ds18b20_virtual_alarm.set_client(ds18b20);
+//! ```
+
+use crate::one_wire::{crc8, OneWire, Rom};
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::gpio;
+use kernel::hil::sensors::{TemperatureClient, TemperatureDriver};
+use kernel::hil::time::{self, Frequency};
+use kernel::ReturnCode;
+
+/// Function commands of the DS18B20.
+const CONVERT_T: u8 = 0x44;
+const READ_SCRATCHPAD: u8 = 0xbe;
+
+/// Conversion time at 12-bit resolution, in milliseconds.
+const CONVERSION_TIME: u32 = 750;
+const READ_ATTEMPTS: usize = 3;
+
+/// Convert the temperature register, in sixteenths of degrees, to
+/// hundredths of degrees centigrade.
+fn hundredths(scratchpad: &[u8; 9]) -> i32 {
+    let raw = i16::from_le_bytes([scratchpad[0], scratchpad[1]]);
+    raw as i32 * 100 / 16
+}
+
+pub struct Ds18b20<'a, P: gpio::Pin, T: time::Time, A: time::Alarm<'a>> {
+    bus: &'a OneWire<'a, P, T>,
+    alarm: &'a A,
+    /// The ROM code of the sensor, or `None` if it is the only device on
+    /// the bus.
+    rom: Option<Rom>,
+    client: OptionalCell<&'static dyn TemperatureClient>,
+    busy: Cell<bool>,
+}
+
+impl<'a, P: gpio::Pin, T: time::Time, A: time::Alarm<'a>> Ds18b20<'a, P, T, A> {
+    pub fn new(bus: &'a OneWire<'a, P, T>, alarm: &'a A, rom: Option<Rom>) -> Ds18b20<'a, P, T, A> {
+        Ds18b20 {
+            bus: bus,
+            alarm: alarm,
+            rom: rom,
+            client: OptionalCell::empty(),
+            busy: Cell::new(false),
+        }
+    }
+
+    fn read_scratchpad(&self) -> Option<[u8; 9]> {
+        for _ in 0..READ_ATTEMPTS {
+            if self.bus.select(self.rom.as_ref()) != ReturnCode::SUCCESS {
+                return None;
+            }
+            self.bus.write_byte(READ_SCRATCHPAD);
+            let mut scratchpad = [0; 9];
+            self.bus.read(&mut scratchpad);
+            if crc8(&scratchpad[..8]) == scratchpad[8] {
+                return Some(scratchpad);
+            }
+        }
+        None
+    }
+}
+
+impl<'a, P: gpio::Pin, T: time::Time, A: time::Alarm<'a>> time::AlarmClient
+    for Ds18b20<'a, P, T, A>
+{
+    fn fired(&self) {
+        self.busy.set(false);
+        if let Some(scratchpad) = self.read_scratchpad() {
+            let temp = hundredths(&scratchpad);
+            self.client.map(|client| client.callback(temp as usize));
+        }
+    }
+}
+
+impl<'a, P: gpio::Pin, T: time::Time, A: time::Alarm<'a>> TemperatureDriver
+    for Ds18b20<'a, P, T, A>
+{
+    fn set_client(&self, client: &'static dyn TemperatureClient) {
+        self.client.set(client);
+    }
+
+    fn read_temperature(&self) -> ReturnCode {
+        if self.busy.get() {
+            return ReturnCode::EBUSY;
+        }
+        let res = self.bus.select(self.rom.as_ref());
+        if res != ReturnCode::SUCCESS {
+            return res;
+        }
+        self.bus.write_byte(CONVERT_T);
+
+        let interval = CONVERSION_TIME * <A::Frequency>::frequency() / 1000;
+        self.alarm
+            .set_alarm(self.alarm.now().wrapping_add(interval));
+        self.busy.set(true);
+        ReturnCode::SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hundredths;
+    use crate::one_wire::crc8;
+
+    fn scratchpad(raw: u16) -> [u8; 9] {
+        let [lsb, msb] = raw.to_le_bytes();
+        [lsb, msb, 0x4b, 0x46, 0x7f, 0xff, 0x0c, 0x10, 0]
+    }
+
+    #[test]
+    fn conversion() {
+        // From the temperature/data relationship table of the datasheet
+        assert_eq!(hundredths(&scratchpad(0x07d0)), 12500);
+        assert_eq!(hundredths(&scratchpad(0x0191)), 2506);
+        assert_eq!(hundredths(&scratchpad(0x0000)), 0);
+        assert_eq!(hundredths(&scratchpad(0xff5e)), -1012);
+        assert_eq!(hundredths(&scratchpad(0xfc90)), -5500);
+    }
+
+    #[test]
+    fn power_on_scratchpad() {
+        let scratchpad = [0x50, 0x05, 0x4b, 0x46, 0x7f, 0xff, 0x0c, 0x10, 0x1c];
+        assert_eq!(crc8(&scratchpad[..8]), scratchpad[8]);
+        assert_eq!(hundredths(&scratchpad), 8500);
+    }
+}
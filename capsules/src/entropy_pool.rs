@@ -0,0 +1,388 @@
+//! Entropy pool mixing several sources, in the style of Fortuna.
+//!
+//! `EntropyPool` gathers samples from the TRNG, from the least significant
+//! bits of ADC samples, from the time at which samples arrive, and from any
+//! driver that adds samples through the `Accumulator` interface, such as a
+//! radio measuring noise. It mixes them with SHA-256 into pools, and reseeds
+//! its generator from the pools. The generator provides the output, as an
+//! `Entropy32`, so it can replace the TRNG under the random number
+//! generators of a board: they stay secure as long as one of the sources
+//! works, even if the TRNG degrades in the field.
+//!
+//! As in Fortuna (Ferguson and Schneier, Practical Cryptography, chapter
+//! 10), the samples of each source are spread over `NUM_POOLS` pools in
+//! turn, and reseed number `r` uses pool `i` if `2^i` divides `r`. An
+//! attacker who can inject or observe samples of some sources therefore
+//! cannot keep the generator in a known state: the later pools accumulate
+//! enough entropy between the reseeds that use them. Pool 0 must have
+//! received `MIN_POOL_SIZE` bytes for a reseed, and the sources are polled,
+//! and reseeds considered, every `POLL_MS` ms.
+//!
+//! Each source goes through a repetition count test (NIST SP 800-90B,
+//! section 4.4.1): a sample repeated `REPETITION_CUTOFF` times in a row is
+//! rejected, and the source is reported as stuck until it produces another
+//! value. `health()` returns the counts of mixed and rejected samples and of
+//! failed requests of each source.
+//!
+//! The generator hashes its key with a counter to produce output blocks,
+//! and replaces its key after each request, so that the output already
+//! given cannot be recovered from a later state.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let entropy_pool = static_init!(
+//!     capsules::entropy_pool::EntropyPool<'static, VirtualMuxAlarm<'static, Rtc>, nrf52::adc::Adc>,
+//!     capsules::entropy_pool::EntropyPool::new(
+//!         &nrf52::trng::TRNG,
+//!         &nrf52::adc::ADC,
+//!         &nrf52::adc::AdcChannel::VDD,
+//!         entropy_pool_alarm,
+//!         dynamic_deferred_caller
+//!     )
+//! );
+//! entropy_pool.initialize_callback_handle(
+//!     dynamic_deferred_caller
+//!         .register(entropy_pool)
+//!         .expect("no deferred call slot available for the entropy pool"),
+//! );
+//! nrf52::trng::TRNG.set_client(entropy_pool);
+//! nrf52::adc::ADC.set_client(entropy_pool);
+//! entropy_pool_alarm.set_client(entropy_pool);
+//! entropy_pool.start();
+//! ```
+
+use crate::sha256::{Sha256, SHA256_DIGEST_SIZE};
+use core::cell::Cell;
+use kernel::common::cells::{MapCell, OptionalCell};
+use kernel::common::dynamic_deferred_call::{
+    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
+};
+use kernel::debug;
+use kernel::hil::adc;
+use kernel::hil::entropy::{Accumulator, Client32, Continue, Entropy32, Source};
+use kernel::hil::time::{Alarm, AlarmClient, Frequency};
+use kernel::ReturnCode;
+
+pub const NUM_POOLS: usize = 8;
+
+/// Bytes pool 0 must have received for a reseed.
+pub const MIN_POOL_SIZE: u64 = 64;
+
+/// Identical samples in a row after which a source is considered stuck.
+pub const REPETITION_CUTOFF: u32 = 8;
+
+/// Interval between polls once the generator is seeded.
+pub const POLL_MS: u32 = 1000;
+
+/// Interval between polls until the generator is first seeded.
+const SEEDING_POLL_MS: u32 = 100;
+
+/// Words taken from the TRNG at each poll.
+const TRNG_WORDS: usize = 16;
+
+/// Output blocks given to the client per callback, before the key changes.
+const BLOCKS_PER_CALLBACK: usize = 8;
+
+const NUM_SOURCES: usize = 4;
+
+type Block = [u8; SHA256_DIGEST_SIZE];
+
+/// Health accounting of a source.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Health {
+    /// Samples mixed into the pools.
+    pub samples: u32,
+    /// Samples rejected by the repetition count test.
+    pub rejected: u32,
+    /// Requests to the source that failed.
+    pub failures: u32,
+    /// Whether the last sample was rejected.
+    pub stuck: bool,
+}
+
+#[derive(Clone, Copy, Default)]
+struct SourceState {
+    health: Health,
+    last: u32,
+    /// Number of times in a row `last` was sampled.
+    run: u32,
+    /// Pool receiving the next sample.
+    pool: usize,
+}
+
+pub struct EntropyPool<'a, A: Alarm<'a>, D: adc::Adc> {
+    trng: &'a dyn Entropy32<'a>,
+    adc: &'a D,
+    adc_channel: &'a D::Channel,
+    alarm: &'a A,
+    deferred_caller: &'a DynamicDeferredCall,
+    handle: OptionalCell<DeferredCallHandle>,
+    client: OptionalCell<&'a dyn Client32>,
+    pools: MapCell<[Sha256; NUM_POOLS]>,
+    sources: Cell<[SourceState; NUM_SOURCES]>,
+    key: Cell<Block>,
+    counter: Cell<u64>,
+    reseeds: Cell<u32>,
+    /// Whether the client asked for entropy.
+    pending: Cell<bool>,
+}
+
+impl<'a, A: Alarm<'a>, D: adc::Adc> EntropyPool<'a, A, D> {
+    pub fn new(
+        trng: &'a dyn Entropy32<'a>,
+        adc: &'a D,
+        adc_channel: &'a D::Channel,
+        alarm: &'a A,
+        deferred_caller: &'a DynamicDeferredCall,
+    ) -> EntropyPool<'a, A, D> {
+        EntropyPool {
+            trng: trng,
+            adc: adc,
+            adc_channel: adc_channel,
+            alarm: alarm,
+            deferred_caller: deferred_caller,
+            handle: OptionalCell::empty(),
+            client: OptionalCell::empty(),
+            pools: MapCell::new([Sha256::new(); NUM_POOLS]),
+            sources: Cell::new([SourceState::default(); NUM_SOURCES]),
+            key: Cell::new([0; SHA256_DIGEST_SIZE]),
+            counter: Cell::new(0),
+            reseeds: Cell::new(0),
+            pending: Cell::new(false),
+        }
+    }
+
+    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
+        self.handle.replace(handle);
+    }
+
+    /// Start polling the sources.
+    pub fn start(&self) {
+        self.poll();
+    }
+
+    pub fn health(&self, source: Source) -> Health {
+        self.sources.get()[source as usize].health
+    }
+
+    /// Number of times the generator was reseeded.
+    pub fn reseeds(&self) -> u32 {
+        self.reseeds.get()
+    }
+
+    fn seeded(&self) -> bool {
+        self.reseeds.get() != 0
+    }
+
+    fn poll(&self) {
+        self.reseed();
+        if self.trng.get() != ReturnCode::SUCCESS {
+            self.fail(Source::Trng);
+        }
+        // Fails while the ADC is busy with another user.
+        if self.adc.sample(self.adc_channel) != ReturnCode::SUCCESS {
+            self.fail(Source::AdcNoise);
+        }
+
+        let interval_ms = if self.seeded() {
+            POLL_MS
+        } else {
+            SEEDING_POLL_MS
+        };
+        let interval = interval_ms * <A::Frequency>::frequency() / 1000;
+        self.alarm
+            .set_alarm(self.alarm.now().wrapping_add(interval));
+    }
+
+    fn fail(&self, source: Source) {
+        let mut sources = self.sources.get();
+        let health = &mut sources[source as usize].health;
+        health.failures = health.failures.saturating_add(1);
+        self.sources.set(sources);
+    }
+
+    /// Test `sample` and add it to the next pool of `source`.
+    fn mix(&self, source: Source, sample: u32) {
+        let mut sources = self.sources.get();
+        let state = &mut sources[source as usize];
+        if sample == state.last {
+            state.run = state.run.saturating_add(1);
+        } else {
+            state.last = sample;
+            state.run = 1;
+        }
+
+        if state.run >= REPETITION_CUTOFF {
+            if !state.health.stuck {
+                debug!("Entropy source {:?} is stuck", source);
+            }
+            state.health.rejected = state.health.rejected.saturating_add(1);
+            state.health.stuck = true;
+        } else {
+            let pool = state.pool;
+            state.pool = (pool + 1) % NUM_POOLS;
+            state.health.samples = state.health.samples.saturating_add(1);
+            state.health.stuck = false;
+            self.pools.map(|pools| {
+                pools[pool].update(&[source as u8, 4]);
+                pools[pool].update(&sample.to_le_bytes());
+            });
+        }
+        self.sources.set(sources);
+    }
+
+    /// Mix the time of an event of another source.
+    fn mix_time(&self) {
+        self.mix(Source::TimerJitter, self.alarm.now());
+    }
+
+    fn reseed(&self) {
+        let ready = self
+            .pools
+            .map_or(false, |pools| pools[0].len() >= MIN_POOL_SIZE);
+        if !ready {
+            return;
+        }
+
+        let reseeds = self.reseeds.get().wrapping_add(1);
+        self.reseeds.set(reseeds);
+        let mut sha = Sha256::new();
+        sha.update(&self.key.get());
+        self.pools.map(|pools| {
+            for (i, pool) in pools.iter_mut().enumerate() {
+                if i > 0 && reseeds % (1 << i) != 0 {
+                    break;
+                }
+                sha.update(&pool.finish());
+                *pool = Sha256::new();
+            }
+        });
+        self.key.set(sha.finish());
+
+        if self.pending.get() {
+            self.handle.map(|handle| self.deferred_caller.set(*handle));
+        }
+    }
+
+    /// The next output block of the generator.
+    fn block(&self) -> Block {
+        let counter = self.counter.get();
+        self.counter.set(counter.wrapping_add(1));
+        let mut sha = Sha256::new();
+        sha.update(&self.key.get());
+        sha.update(&counter.to_le_bytes());
+        sha.finish()
+    }
+}
+
+/// The output given to the client in one callback.
+struct Output<'b, 'a, A: Alarm<'a>, D: adc::Adc> {
+    pool: &'b EntropyPool<'a, A, D>,
+    block: Block,
+    /// Index of the next word in `block`.
+    index: usize,
+    blocks: usize,
+}
+
+impl<'a, A: Alarm<'a>, D: adc::Adc> Iterator for Output<'_, 'a, A, D> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.index == SHA256_DIGEST_SIZE / 4 {
+            if self.blocks == BLOCKS_PER_CALLBACK {
+                return None;
+            }
+            self.block = self.pool.block();
+            self.blocks += 1;
+            self.index = 0;
+        }
+        let word = &self.block[4 * self.index..4 * self.index + 4];
+        self.index += 1;
+        Some(u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+    }
+}
+
+impl<'a, A: Alarm<'a>, D: adc::Adc> Entropy32<'a> for EntropyPool<'a, A, D> {
+    fn get(&self) -> ReturnCode {
+        self.pending.set(true);
+        if self.seeded() {
+            self.handle.map(|handle| self.deferred_caller.set(*handle));
+        }
+        ReturnCode::SUCCESS
+    }
+
+    fn cancel(&self) -> ReturnCode {
+        self.pending.set(false);
+        ReturnCode::SUCCESS
+    }
+
+    fn set_client(&'a self, client: &'a dyn Client32) {
+        self.client.set(client);
+    }
+}
+
+impl<'a, A: Alarm<'a>, D: adc::Adc> DynamicDeferredCallClient for EntropyPool<'a, A, D> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        if !self.pending.get() {
+            return;
+        }
+        self.pending.set(false);
+
+        let mut output = Output {
+            pool: self,
+            block: [0; SHA256_DIGEST_SIZE],
+            index: SHA256_DIGEST_SIZE / 4,
+            blocks: 0,
+        };
+        let result = self.client.map_or(Continue::Done, |client| {
+            client.entropy_available(&mut output, ReturnCode::SUCCESS)
+        });
+        self.key.set(self.block());
+
+        if result == Continue::More {
+            self.get();
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>, D: adc::Adc> Client32 for EntropyPool<'a, A, D> {
+    fn entropy_available(
+        &self,
+        entropy: &mut dyn Iterator<Item = u32>,
+        error: ReturnCode,
+    ) -> Continue {
+        if error != ReturnCode::SUCCESS {
+            self.fail(Source::Trng);
+            return Continue::Done;
+        }
+        for word in entropy.take(TRNG_WORDS) {
+            self.mix(Source::Trng, word);
+        }
+        self.mix_time();
+        Continue::Done
+    }
+}
+
+impl<'a, A: Alarm<'a>, D: adc::Adc> adc::Client for EntropyPool<'a, A, D> {
+    fn sample_ready(&self, sample: u16) {
+        self.mix(Source::AdcNoise, sample as u32);
+        self.mix_time();
+    }
+}
+
+impl<'a, A: Alarm<'a>, D: adc::Adc> Accumulator for EntropyPool<'a, A, D> {
+    fn add_sample(&self, source: Source, sample: u32) {
+        self.mix(source, sample);
+        if source != Source::TimerJitter {
+            self.mix_time();
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>, D: adc::Adc> AlarmClient for EntropyPool<'a, A, D> {
+    fn fired(&self) {
+        self.poll();
+    }
+}
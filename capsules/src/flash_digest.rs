@@ -0,0 +1,156 @@
+//! Hash a region of flash, such as the image of a process, with a digest
+//! engine.
+//!
+//! The engine reads the region where it is, with `add_readonly_data()`, so
+//! it is never copied into RAM. This is the basis for measuring the kernel
+//! and the processes at boot, and for attesting them later.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let flash_digest = static_init!(
+//!     capsules::flash_digest::FlashDigest<'static, lowrisc::hmac::Hmac>,
+//!     capsules::flash_digest::FlashDigest::new(
+//!         &ibex::hmac::HMAC,
+//!         static_init!([u8; 32], [0; 32])
+//!     )
+//! );
+//! digest::Digest::set_client(&ibex::hmac::HMAC, flash_digest);
+//! flash_digest.set_client(measurements);
+//!
+//! // With a `ProcessManagementCapability`
+//! board_kernel.process_each_capability(&process_mgmt_cap, |process| {
+//!     measurements.add(process.flash_image());
+//! });
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::leasable_buffer::ReadOnlyLeasableBuffer;
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::digest;
+use kernel::ReturnCode;
+
+pub trait Client {
+    /// The SHA-256 of `region`, passed to `hash_region()`, was computed, or
+    /// the engine failed.
+    fn region_hashed(&self, region: &'static [u8], result: Result<&[u8; 32], CryptoError>);
+}
+
+pub struct FlashDigest<'a, D: digest::Digest<'a, [u8; 32]>> {
+    digest: &'a D,
+    client: OptionalCell<&'a dyn Client>,
+    region: OptionalCell<&'static [u8]>,
+    /// Number of bytes of `region` given to the engine.
+    offset: Cell<usize>,
+    digest_buffer: TakeCell<'static, [u8; 32]>,
+}
+
+impl<'a, D: digest::Digest<'a, [u8; 32]>> FlashDigest<'a, D> {
+    pub fn new(digest: &'a D, digest_buffer: &'static mut [u8; 32]) -> FlashDigest<'a, D> {
+        FlashDigest {
+            digest,
+            client: OptionalCell::empty(),
+            region: OptionalCell::empty(),
+            offset: Cell::new(0),
+            digest_buffer: TakeCell::new(digest_buffer),
+        }
+    }
+
+    pub fn set_client(&self, client: &'a dyn Client) {
+        self.client.set(client);
+    }
+
+    /// Compute the SHA-256 of `region`, which `region_hashed()` returns.
+    /// Returns `EBUSY` if a region is being hashed, and `ESIZE` if `region`
+    /// is empty.
+    pub fn hash_region(&self, region: &'static [u8]) -> ReturnCode {
+        if self.region.is_some() || self.digest_buffer.is_none() {
+            return ReturnCode::EBUSY;
+        }
+        if region.is_empty() {
+            return ReturnCode::ESIZE;
+        }
+
+        self.region.set(region);
+        self.offset.set(0);
+        match self.add_next() {
+            Ok(()) => ReturnCode::SUCCESS,
+            Err(e) => {
+                self.digest.clear_data();
+                self.region.clear();
+                e.into()
+            }
+        }
+    }
+
+    /// Give the rest of the region to the engine, which may take only part
+    /// of it.
+    fn add_next(&self) -> Result<(), CryptoError> {
+        self.region
+            .map_or(Err(CryptoError::HardwareFault), |region| {
+                let rest = &region[self.offset.get()..];
+                match self
+                    .digest
+                    .add_readonly_data(ReadOnlyLeasableBuffer::new(rest))
+                {
+                    Ok(len) => {
+                        self.offset.set(self.offset.get() + len);
+                        Ok(())
+                    }
+                    Err((e, _)) => Err(e),
+                }
+            })
+    }
+
+    fn finish(&self, result: Result<(), CryptoError>) {
+        self.digest.clear_data();
+
+        // Copy the digest out, so that the client can hash another region
+        // from the callback
+        let mut digest = [0; 32];
+        self.digest_buffer
+            .map(|buffer| digest.copy_from_slice(buffer));
+
+        self.region.take().map(|region| {
+            self.client.map(|client| {
+                client.region_hashed(region, result.map(|()| &digest));
+            });
+        });
+    }
+}
+
+impl<'a, D: digest::Digest<'a, [u8; 32]>> digest::Client<'a, [u8; 32]> for FlashDigest<'a, D> {
+    fn add_data_done(&'a self, _result: Result<(), CryptoError>, _data: &'static mut [u8]) {
+        // Only read-only data is given to the engine.
+    }
+
+    fn add_readonly_data_done(&'a self, result: Result<(), CryptoError>, _data: &'static [u8]) {
+        if let Err(e) = result {
+            self.finish(Err(e));
+            return;
+        }
+
+        let len = self.region.map_or(0, |region| region.len());
+        let res = if self.offset.get() < len {
+            self.add_next()
+        } else {
+            match self.digest_buffer.take() {
+                Some(buffer) => self.digest.run(buffer).map_err(|(e, buffer)| {
+                    self.digest_buffer.replace(buffer);
+                    e
+                }),
+                None => Err(CryptoError::EngineBusy),
+            }
+        };
+        if let Err(e) = res {
+            self.finish(Err(e));
+        }
+    }
+
+    fn hash_done(&'a self, result: Result<(), CryptoError>, digest: &'static mut [u8; 32]) {
+        self.digest_buffer.replace(digest);
+        self.finish(result);
+    }
+}
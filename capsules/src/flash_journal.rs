@@ -0,0 +1,366 @@
+//! Keeps the error journal (see `kernel::journal`) in nonvolatile storage.
+//!
+//! The journal is a ring of fixed size records in a region of nonvolatile
+//! storage. The region is mirrored in RAM: new records are written to the RAM
+//! copy, and the whole region is written back to storage shortly afterwards.
+//! Writing the region back erases its flash sector, so the records appended
+//! within `MIN_WRITE_INTERVAL_MS` of a write are batched into the next one.
+//! At boot, `restore()` reads the region back and appends a `Boot` record.
+//!
+//! Each record is 16 bytes, all fields little-endian:
+//!
+//! ```text
+//! 0             4             8      10     12            16
+//! +-------------+-------------+------+------+-------------+
+//! | sequence    | timestamp   | event|0xffff| arg         |
+//! +-------------+-------------+------+------+-------------+
+//! ```
+//!
+//! A slot whose sequence number is `0xffffffff`, i.e. erased flash, is empty.
+//! Timestamps are milliseconds since boot, which the journal keeps track of
+//! with an alarm that fires at least every minute.
+//!
+//! Records appended while the RAM copy is being read or written are queued,
+//! and dropped if the queue is full.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let journal = components::flash_journal::FlashJournalComponent::new(
+//!     mux_alarm,
+//!     journal_storage,
+//!     0x5e000, // Start of the journal region
+//! )
+//! .finalize(components::flash_journal_component_helper!(nrf52::rtc::Rtc, 1024));
+//! kernel::journal::set_journal(journal);
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::TakeCell;
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::hil::time::{self, Alarm, Frequency};
+use kernel::journal::{Event, Journal, Record};
+use kernel::ReturnCode;
+
+/// Size of a record in storage.
+pub const RECORD_LEN: usize = 16;
+
+/// Number of records that can be queued while the RAM copy is in use.
+const QUEUE_LEN: usize = 4;
+
+/// Delay between appending a record and writing it to storage, so that
+/// records appended together are written together.
+const WRITE_DELAY_MS: u32 = 1000;
+
+/// Shortest time between two writes to storage, each of which erases the
+/// sector of the journal.
+const MIN_WRITE_INTERVAL_MS: u32 = 10 * 60_000;
+
+/// Longest time between two updates of the uptime. This must be shorter than
+/// the time it takes for the alarm counter to wrap.
+const UPTIME_PERIOD_MS: u32 = 60_000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Restoring,
+    Idle,
+    Writing,
+}
+
+pub struct FlashJournal<'a, A: Alarm<'a>> {
+    storage: &'a dyn NonvolatileStorage<'a>,
+    alarm: &'a A,
+    address: usize,
+    /// RAM copy of the journal region.
+    buffer: TakeCell<'a, [u8]>,
+    slots: usize,
+    state: Cell<State>,
+    /// Slot of the next record.
+    next: Cell<usize>,
+    /// Number of records in the journal.
+    count: Cell<usize>,
+    sequence: Cell<u32>,
+    /// Whether the RAM copy differs from storage.
+    dirty: Cell<bool>,
+    /// Uptime at which the RAM copy is written back, if it is scheduled.
+    write_at: Cell<Option<u32>>,
+    /// Uptime of the last write to storage since boot.
+    last_write_ms: Cell<Option<u32>>,
+    queue: Cell<[(Event, u32, u32); QUEUE_LEN]>,
+    queue_len: Cell<usize>,
+    uptime_ms: Cell<u32>,
+    uptime_tics: Cell<u32>,
+}
+
+impl<'a, A: Alarm<'a>> FlashJournal<'a, A> {
+    /// `buffer` holds the RAM copy of the region, and its length sets the
+    /// size of the region. It should be a multiple of `RECORD_LEN`.
+    pub fn new(
+        storage: &'a dyn NonvolatileStorage<'a>,
+        alarm: &'a A,
+        address: usize,
+        buffer: &'a mut [u8],
+    ) -> FlashJournal<'a, A> {
+        let slots = buffer.len() / RECORD_LEN;
+        FlashJournal {
+            storage: storage,
+            alarm: alarm,
+            address: address,
+            buffer: TakeCell::new(buffer),
+            slots: slots,
+            state: Cell::new(State::Idle),
+            next: Cell::new(0),
+            count: Cell::new(0),
+            sequence: Cell::new(0),
+            dirty: Cell::new(false),
+            write_at: Cell::new(None),
+            last_write_ms: Cell::new(None),
+            queue: Cell::new([(Event::Boot, 0, 0); QUEUE_LEN]),
+            queue_len: Cell::new(0),
+            uptime_ms: Cell::new(0),
+            uptime_tics: Cell::new(0),
+        }
+    }
+
+    /// Read the journal from storage and record a `Boot` event.
+    pub fn restore(&self) -> ReturnCode {
+        self.uptime_tics.set(self.alarm.now());
+
+        let len = self.slots * RECORD_LEN;
+        let res = self.buffer.take().map_or(ReturnCode::EBUSY, |buffer| {
+            self.storage.read(buffer, self.address, len)
+        });
+        if res == ReturnCode::SUCCESS {
+            self.state.set(State::Restoring);
+        }
+        // While restoring, this is queued until the journal has been read.
+        self.append(Event::Boot, 0);
+        res
+    }
+
+    fn now_ms(&self) -> u32 {
+        let now = self.alarm.now();
+        let elapsed = now.wrapping_sub(self.uptime_tics.get()) & self.alarm.max_tics();
+        let elapsed_ms = (elapsed as u64 * 1000 / <A::Frequency>::frequency() as u64) as u32;
+        self.uptime_ms.get().wrapping_add(elapsed_ms)
+    }
+
+    fn update_uptime(&self) {
+        let now = self.alarm.now();
+        self.uptime_ms.set(self.now_ms());
+        self.uptime_tics.set(now);
+    }
+
+    fn set_timer(&self, ms: u32) {
+        let tics = (ms as u64 * <A::Frequency>::frequency() as u64 / 1000) as u32;
+        self.alarm.set_alarm(self.alarm.now().wrapping_add(tics));
+    }
+
+    fn slot(&self, index: usize) -> usize {
+        (self.next.get() + self.slots - self.count.get() + index) % self.slots
+    }
+
+    /// Write a record into the next slot of the RAM copy.
+    fn write_record(&self, buffer: &mut [u8], event: Event, arg: u32, timestamp_ms: u32) {
+        if self.slots == 0 {
+            return;
+        }
+        let offset = self.next.get() * RECORD_LEN;
+        let record = &mut buffer[offset..offset + RECORD_LEN];
+        record[0..4].copy_from_slice(&self.sequence.get().to_le_bytes());
+        record[4..8].copy_from_slice(&timestamp_ms.to_le_bytes());
+        record[8..10].copy_from_slice(&(event as u16).to_le_bytes());
+        record[10..12].copy_from_slice(&[0xff, 0xff]);
+        record[12..16].copy_from_slice(&arg.to_le_bytes());
+
+        self.sequence.set(self.sequence.get().wrapping_add(1));
+        self.next.set((self.next.get() + 1) % self.slots);
+        if self.count.get() < self.slots {
+            self.count.set(self.count.get() + 1);
+        }
+        self.dirty.set(true);
+    }
+
+    /// Move the queued records into the RAM copy.
+    fn drain_queue(&self, buffer: &mut [u8]) {
+        let queue = self.queue.get();
+        for &(event, arg, timestamp_ms) in queue[..self.queue_len.get()].iter() {
+            self.write_record(buffer, event, arg, timestamp_ms);
+        }
+        self.queue_len.set(0);
+    }
+
+    /// Schedule writing the RAM copy to storage, unless it already is. The
+    /// write waits `WRITE_DELAY_MS` for more records, and at least
+    /// `MIN_WRITE_INTERVAL_MS` after the previous write.
+    fn schedule_write(&self) {
+        if self.write_at.get().is_none() {
+            let now = self.now_ms();
+            let since_write = self
+                .last_write_ms
+                .get()
+                .map_or(MIN_WRITE_INTERVAL_MS, |last| now.wrapping_sub(last));
+            let delay = cmp::max(
+                WRITE_DELAY_MS,
+                MIN_WRITE_INTERVAL_MS.saturating_sub(since_write),
+            );
+            self.write_at.set(Some(now.wrapping_add(delay)));
+        }
+        self.arm();
+    }
+
+    /// Set the alarm for the scheduled write, or for the next uptime update
+    /// if it comes first.
+    fn arm(&self) {
+        let delay = self.write_at.get().map_or(UPTIME_PERIOD_MS, |at| {
+            let remaining = at.wrapping_sub(self.now_ms()) as i32;
+            cmp::min(cmp::max(remaining, 1) as u32, UPTIME_PERIOD_MS)
+        });
+        self.set_timer(delay);
+    }
+
+    fn write_due(&self) -> bool {
+        self.write_at
+            .get()
+            .map_or(false, |at| self.now_ms().wrapping_sub(at) as i32 >= 0)
+    }
+
+    /// Write the RAM copy to storage if it changed, otherwise wait for the
+    /// next uptime update.
+    fn write_back(&self) {
+        self.write_at.set(None);
+        if !self.dirty.get() {
+            self.arm();
+            return;
+        }
+        let len = self.slots * RECORD_LEN;
+        let res = self.buffer.take().map_or(ReturnCode::EBUSY, |buffer| {
+            self.storage.write(buffer, self.address, len)
+        });
+        if res == ReturnCode::SUCCESS {
+            self.dirty.set(false);
+            self.last_write_ms.set(Some(self.now_ms()));
+            self.state.set(State::Writing);
+        } else {
+            // Nothing was erased, try again soon
+            self.write_at
+                .set(Some(self.now_ms().wrapping_add(WRITE_DELAY_MS)));
+            self.arm();
+        }
+    }
+
+    /// Schedule a write if the RAM copy changed, otherwise wait for the next
+    /// uptime update.
+    fn rearm(&self) {
+        if self.dirty.get() {
+            self.schedule_write();
+        } else {
+            self.arm();
+        }
+    }
+}
+
+fn word(buffer: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buffer[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+impl<'a, A: Alarm<'a>> Journal for FlashJournal<'a, A> {
+    fn append(&self, event: Event, arg: u32) {
+        let timestamp_ms = self.now_ms();
+        let appended = self.buffer.map(|buffer| {
+            self.write_record(buffer, event, arg, timestamp_ms);
+        });
+        if appended.is_some() {
+            self.schedule_write();
+        } else if self.queue_len.get() < QUEUE_LEN {
+            let mut queue = self.queue.get();
+            queue[self.queue_len.get()] = (event, arg, timestamp_ms);
+            self.queue.set(queue);
+            self.queue_len.set(self.queue_len.get() + 1);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.count.get()
+    }
+
+    fn get(&self, index: usize) -> Option<Record> {
+        if index >= self.count.get() {
+            return None;
+        }
+        let offset = self.slot(index) * RECORD_LEN;
+        self.buffer.map_or(None, |buffer| {
+            Some(Record {
+                sequence: word(buffer, offset),
+                timestamp_ms: word(buffer, offset + 4),
+                event: u16::from_le_bytes([buffer[offset + 8], buffer[offset + 9]]),
+                arg: word(buffer, offset + 12),
+            })
+        })
+    }
+
+    fn clear(&self) -> ReturnCode {
+        self.buffer.map_or(ReturnCode::EBUSY, |buffer| {
+            buffer.iter_mut().for_each(|byte| *byte = 0xff);
+            self.next.set(0);
+            self.count.set(0);
+            self.dirty.set(true);
+            self.schedule_write();
+            ReturnCode::SUCCESS
+        })
+    }
+}
+
+impl<'a, A: Alarm<'a>> time::AlarmClient for FlashJournal<'a, A> {
+    fn fired(&self) {
+        self.update_uptime();
+        // Otherwise the end of the read or write sets the alarm again
+        if self.state.get() == State::Idle {
+            if self.write_due() {
+                self.write_back();
+            } else {
+                self.arm();
+            }
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> NonvolatileStorageClient<'a> for FlashJournal<'a, A> {
+    fn read_done(&self, buffer: &'a mut [u8], _length: usize) {
+        // Find the most recent record. Records are written in order, so the
+        // journal continues after it.
+        let mut count = 0;
+        let mut latest: Option<(usize, u32)> = None;
+        for slot in 0..self.slots {
+            let sequence = word(buffer, slot * RECORD_LEN);
+            if sequence == 0xffffffff {
+                continue;
+            }
+            count += 1;
+            if latest.map_or(true, |(_, latest_sequence)| sequence > latest_sequence) {
+                latest = Some((slot, sequence));
+            }
+        }
+        if let Some((slot, sequence)) = latest {
+            self.next.set((slot + 1) % self.slots);
+            self.sequence.set(sequence.wrapping_add(1));
+        }
+        self.count.set(count);
+
+        self.drain_queue(buffer);
+        self.buffer.replace(buffer);
+        self.state.set(State::Idle);
+        self.rearm();
+    }
+
+    fn write_done(&self, buffer: &'a mut [u8], _length: usize) {
+        self.drain_queue(buffer);
+        self.buffer.replace(buffer);
+        self.state.set(State::Idle);
+        self.rearm();
+    }
+}
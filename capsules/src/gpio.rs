@@ -98,6 +98,19 @@ impl<'a, IP: gpio::InterruptPin> GPIO<'a, IP> {
         }
     }
 
+    fn configure_drive_mode(&self, pin_num: u32, config: usize) -> ReturnCode {
+        if let Some(pin) = self.pins[pin_num as usize] {
+            match config {
+                0 => pin.set_drive_mode(gpio::DriveMode::PushPull),
+                1 => pin.set_drive_mode(gpio::DriveMode::OpenDrain),
+                2 => pin.set_drive_mode(gpio::DriveMode::HighDrive),
+                _ => ReturnCode::ENOSUPPORT,
+            }
+        } else {
+            ReturnCode::ENODEVICE
+        }
+    }
+
     fn configure_interrupt(&self, pin_num: u32, config: usize) -> ReturnCode {
         let pins = self.pins.as_ref();
         let index = pin_num as usize;
@@ -187,6 +200,10 @@ impl<IP: gpio::InterruptPin> Driver for GPIO<'_, IP> {
     ///                   Set to `0` to interrupt on either edge.
     ///                   Set to `1` for rising edge.
     ///                   Set to `2` for falling edge.
+    ///   - `drive_config`: Output drive setting.
+    ///                     Set to `0` for push-pull, the default.
+    ///                     Set to `1` for open-drain.
+    ///                     Set to `2` for high drive.
     ///
     /// ### `command_num`
     ///
@@ -200,6 +217,7 @@ impl<IP: gpio::InterruptPin> Driver for GPIO<'_, IP> {
     /// - `7`: Configure interrupt on `pin` with `irq_config` in 0x00XX00000
     /// - `8`: Disable interrupt on `pin`.
     /// - `9`: Disable `pin`.
+    /// - `10`: Set the output drive of `pin` to `drive_config`.
     fn command(&self, command_num: usize, data1: usize, data2: usize, _: AppId) -> ReturnCode {
         let pins = self.pins.as_ref();
         let pin_index = data1;
@@ -332,6 +350,16 @@ impl<IP: gpio::InterruptPin> Driver for GPIO<'_, IP> {
                 }
             }
 
+            // configure output drive of pin
+            10 => {
+                let drive_config = data2;
+                if pin_index >= pins.len() {
+                    ReturnCode::EINVAL /* impossible pin */
+                } else {
+                    self.configure_drive_mode(pin_index as u32, drive_config)
+                }
+            }
+
             // default
             _ => ReturnCode::ENOSUPPORT,
         }
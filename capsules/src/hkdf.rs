@@ -0,0 +1,475 @@
+//! HKDF-SHA256 key derivation (RFC 5869), on top of the HMAC HIL.
+//!
+//! `Hkdf` derives output key material (OKM) from input key material (IKM),
+//! an optional salt and optional context information: HKDF-Extract computes
+//! a pseudorandom key PRK = HMAC(salt, IKM), and HKDF-Expand computes the
+//! blocks T(i) = HMAC(PRK, T(i-1) | info | i) until enough bytes are output.
+//! The kernel uses it through `derive()` and `Client`. `HkdfDriver` lets
+//! processes derive keys through the same capsule, one process at a time.
+//!
+//! The IKM and the info are copied into the working buffer given to
+//! `Hkdf::new()`, which bounds their combined length: it must hold 32 bytes
+//! for T(i-1), the info, the counter byte and the IKM. The PRK and the
+//! working buffer are cleared once a derivation completes.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let hkdf = static_init!(
+//!     capsules::hkdf::Hkdf<'static, VirtualMuxHmac<'static, lowrisc::hmac::Hmac, [u8; 32]>>,
+//!     capsules::hkdf::Hkdf::new(
+//!         virtual_hmac_user,
+//!         static_init!([u8; 128], [0; 128]),
+//!         static_init!([u8; 32], [0; 32]),
+//!     )
+//! );
+//! digest::Digest::set_client(virtual_hmac_user, hkdf);
+//!
+//! let hkdf_driver = static_init!(
+//!     capsules::hkdf::HkdfDriver<'static, VirtualMuxHmac<'static, lowrisc::hmac::Hmac, [u8; 32]>>,
+//!     capsules::hkdf::HkdfDriver::new(
+//!         hkdf,
+//!         static_init!([u8; 64], [0; 64]),
+//!         board_kernel.create_grant(&memory_allocation_cap),
+//!     )
+//! );
+//! hkdf.set_client(hkdf_driver);
+//! ```
+
+use crate::driver;
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = driver::NUM::Hkdf as usize;
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::leasable_buffer::LeasableBuffer;
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::digest;
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Length of the PRK and of each block T(i).
+pub const HASH_LEN: usize = 32;
+
+/// The longest OKM that HKDF-SHA256 can derive.
+pub const MAX_OKM_LEN: usize = 255 * HASH_LEN;
+
+pub trait Client {
+    /// The derivation started by `derive()` is done. On success, the first
+    /// `len` bytes of `okm` hold the output key material.
+    fn derive_done(&self, result: Result<(), CryptoError>, okm: &'static mut [u8]);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    Extract,
+    /// Computing the block T(counter).
+    Expand(u8),
+}
+
+pub struct Hkdf<'a, H: digest::Digest<'a, [u8; HASH_LEN]> + digest::HMACSha256> {
+    hmac: &'a H,
+    client: OptionalCell<&'a dyn Client>,
+    state: Cell<State>,
+
+    /// Holds T(i-1) in its first `HASH_LEN` bytes, then the info, the
+    /// counter byte and, during HKDF-Extract, the IKM.
+    buffer: TakeCell<'static, [u8]>,
+    info_len: Cell<usize>,
+    digest: TakeCell<'static, [u8; HASH_LEN]>,
+    prk: Cell<[u8; HASH_LEN]>,
+
+    okm: TakeCell<'static, [u8]>,
+    okm_len: Cell<usize>,
+    /// Number of bytes of OKM output so far.
+    okm_done: Cell<usize>,
+}
+
+impl<'a, H: digest::Digest<'a, [u8; HASH_LEN]> + digest::HMACSha256> Hkdf<'a, H> {
+    pub fn new(
+        hmac: &'a H,
+        buffer: &'static mut [u8],
+        digest: &'static mut [u8; HASH_LEN],
+    ) -> Hkdf<'a, H> {
+        Hkdf {
+            hmac,
+            client: OptionalCell::empty(),
+            state: Cell::new(State::Idle),
+            buffer: TakeCell::new(buffer),
+            info_len: Cell::new(0),
+            digest: TakeCell::new(digest),
+            prk: Cell::new([0; HASH_LEN]),
+            okm: TakeCell::empty(),
+            okm_len: Cell::new(0),
+            okm_done: Cell::new(0),
+        }
+    }
+
+    pub fn set_client(&self, client: &'a dyn Client) {
+        self.client.set(client);
+    }
+
+    /// Derive `len` bytes of OKM into `okm` from `ikm`, `salt` and `info`,
+    /// which are copied before this returns. An empty salt stands for a salt
+    /// of `HASH_LEN` zeros, as in RFC 5869.
+    /// Returns `EngineBusy` if a derivation is in progress, and
+    /// `InvalidArgument` if `len` is 0, longer than `okm` or than
+    /// `MAX_OKM_LEN`, or if `ikm` and `info` do not fit in the working
+    /// buffer.
+    pub fn derive(
+        &self,
+        salt: &[u8],
+        ikm: &[u8],
+        info: &[u8],
+        okm: &'static mut [u8],
+        len: usize,
+    ) -> Result<(), (CryptoError, &'static mut [u8])> {
+        if self.state.get() != State::Idle {
+            return Err((CryptoError::EngineBusy, okm));
+        }
+        if len == 0 || len > okm.len() || len > MAX_OKM_LEN {
+            return Err((CryptoError::InvalidArgument, okm));
+        }
+        let ikm_start = HASH_LEN + info.len() + 1;
+        let fits = self
+            .buffer
+            .map_or(false, |buffer| ikm_start + ikm.len() <= buffer.len());
+        if !fits {
+            return Err((CryptoError::InvalidArgument, okm));
+        }
+
+        let zero_salt = [0; HASH_LEN];
+        let key = if salt.is_empty() {
+            &zero_salt[..]
+        } else {
+            salt
+        };
+        if let Err(e) = self.hmac.set_mode_hmacsha256(key) {
+            return Err((e, okm));
+        }
+
+        self.buffer.map(|buffer| {
+            buffer[HASH_LEN..ikm_start - 1].copy_from_slice(info);
+            buffer[ikm_start..ikm_start + ikm.len()].copy_from_slice(ikm);
+        });
+        self.info_len.set(info.len());
+
+        if let Err(e) = self.hash(ikm_start, ikm_start + ikm.len()) {
+            self.clear();
+            return Err((e, okm));
+        }
+        self.okm.replace(okm);
+        self.okm_len.set(len);
+        self.okm_done.set(0);
+        self.state.set(State::Extract);
+        Ok(())
+    }
+
+    /// Compute the HMAC of `buffer[start..end]`, with the key that is set.
+    fn hash(&self, start: usize, end: usize) -> Result<(), CryptoError> {
+        if start == end {
+            // Nothing to add, which happens for an empty IKM
+            return self.run();
+        }
+        let buffer = self.buffer.take().ok_or(CryptoError::EngineBusy)?;
+        let mut lease = LeasableBuffer::new(buffer);
+        lease.slice(start..end);
+        self.hmac
+            .add_data(lease)
+            .map(|_| ())
+            .map_err(|(e, buffer)| {
+                self.buffer.replace(buffer);
+                e
+            })
+    }
+
+    fn run(&self) -> Result<(), CryptoError> {
+        let digest = self.digest.take().ok_or(CryptoError::EngineBusy)?;
+        self.hmac.run(digest).map_err(|(e, digest)| {
+            self.digest.replace(digest);
+            e
+        })
+    }
+
+    /// Start computing the block T(counter).
+    fn expand(&self, counter: u8) -> Result<(), CryptoError> {
+        self.hmac.set_mode_hmacsha256(&self.prk.get())?;
+
+        let counter_index = HASH_LEN + self.info_len.get();
+        self.buffer.map(|buffer| buffer[counter_index] = counter);
+        self.state.set(State::Expand(counter));
+        // T(0) is empty
+        let start = if counter == 1 { HASH_LEN } else { 0 };
+        self.hash(start, counter_index + 1)
+    }
+
+    /// Wipe the keys and the key material.
+    fn clear(&self) {
+        self.hmac.clear_data();
+        self.prk.set([0; HASH_LEN]);
+        self.buffer
+            .map(|buffer| buffer.iter_mut().for_each(|b| *b = 0));
+        self.digest
+            .map(|digest| digest.iter_mut().for_each(|b| *b = 0));
+    }
+
+    fn finish(&self, result: Result<(), CryptoError>) {
+        self.clear();
+        self.state.set(State::Idle);
+        self.okm.take().map(|okm| {
+            if result.is_err() {
+                okm.iter_mut().for_each(|b| *b = 0);
+            }
+            self.client
+                .map(move |client| client.derive_done(result, okm));
+        });
+    }
+}
+
+impl<'a, H: digest::Digest<'a, [u8; HASH_LEN]> + digest::HMACSha256>
+    digest::Client<'a, [u8; HASH_LEN]> for Hkdf<'a, H>
+{
+    fn add_data_done(&'a self, result: Result<(), CryptoError>, data: &'static mut [u8]) {
+        self.buffer.replace(data);
+        if let Err(e) = result.and_then(|()| self.run()) {
+            self.finish(Err(e));
+        }
+    }
+
+    fn add_readonly_data_done(&'a self, _result: Result<(), CryptoError>, _data: &'static [u8]) {
+        // All the data is copied into the working buffer.
+    }
+
+    fn hash_done(&'a self, result: Result<(), CryptoError>, digest: &'static mut [u8; HASH_LEN]) {
+        let block = *digest;
+        self.digest.replace(digest);
+        if let Err(e) = result {
+            self.finish(Err(e));
+            return;
+        }
+
+        let next = match self.state.get() {
+            State::Idle => return,
+            State::Extract => {
+                self.prk.set(block);
+                1
+            }
+            State::Expand(counter) => {
+                let done = self.okm_done.get();
+                let n = core::cmp::min(HASH_LEN, self.okm_len.get() - done);
+                self.okm
+                    .map(|okm| okm[done..done + n].copy_from_slice(&block[..n]));
+                self.okm_done.set(done + n);
+                if done + n == self.okm_len.get() {
+                    self.finish(Ok(()));
+                    return;
+                }
+                self.buffer
+                    .map(|buffer| buffer[..HASH_LEN].copy_from_slice(&block));
+                counter + 1
+            }
+        };
+        if let Err(e) = self.expand(next) {
+            self.finish(Err(e));
+        }
+    }
+}
+
+/// Derive keys for processes, one process at a time.
+pub struct HkdfDriver<'a, H: digest::Digest<'a, [u8; HASH_LEN]> + digest::HMACSha256> {
+    hkdf: &'a Hkdf<'a, H>,
+    apps: Grant<App>,
+    appid: OptionalCell<AppId>,
+    /// The OKM of the running derivation, copied to the process at the end.
+    okm_buffer: TakeCell<'static, [u8]>,
+    okm_len: Cell<usize>,
+}
+
+impl<'a, H: digest::Digest<'a, [u8; HASH_LEN]> + digest::HMACSha256> HkdfDriver<'a, H> {
+    pub fn new(
+        hkdf: &'a Hkdf<'a, H>,
+        okm_buffer: &'static mut [u8],
+        grant: Grant<App>,
+    ) -> HkdfDriver<'a, H> {
+        HkdfDriver {
+            hkdf,
+            apps: grant,
+            appid: OptionalCell::empty(),
+            okm_buffer: TakeCell::new(okm_buffer),
+            okm_len: Cell::new(0),
+        }
+    }
+
+    fn derive(&self, appid: AppId) -> ReturnCode {
+        let mut okm = match self.okm_buffer.take() {
+            None => return ReturnCode::EBUSY,
+            Some(okm) => Some(okm),
+        };
+        let res = self
+            .apps
+            .enter(appid, |app, _| {
+                let len = app.okm.as_ref().map_or(0, |okm| okm.len());
+                let buffer = match okm.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::FAIL,
+                };
+                if len > buffer.len() {
+                    okm = Some(buffer);
+                    return ReturnCode::ESIZE;
+                }
+                let salt = app.salt.as_ref().map_or(&[][..], |salt| salt.as_ref());
+                let ikm = app.ikm.as_ref().map_or(&[][..], |ikm| ikm.as_ref());
+                let info = app.info.as_ref().map_or(&[][..], |info| info.as_ref());
+                self.okm_len.set(len);
+                match self.hkdf.derive(salt, ikm, info, buffer, len) {
+                    Ok(()) => ReturnCode::SUCCESS,
+                    Err((e, buffer)) => {
+                        okm = Some(buffer);
+                        e.into()
+                    }
+                }
+            })
+            .unwrap_or_else(|err| err.into());
+        // The buffer is only kept if the derivation did not start
+        okm.map(|buffer| self.okm_buffer.replace(buffer));
+        if res == ReturnCode::SUCCESS {
+            self.appid.set(appid);
+        }
+        res
+    }
+}
+
+impl<'a, H: digest::Digest<'a, [u8; HASH_LEN]> + digest::HMACSha256> Client for HkdfDriver<'a, H> {
+    fn derive_done(&self, result: Result<(), CryptoError>, okm: &'static mut [u8]) {
+        let len = self.okm_len.get();
+        self.appid.take().map(|appid| {
+            let _ = self.apps.enter(appid, |app, _| {
+                let (res, n) = match result {
+                    // The process may have allowed a shorter buffer since it
+                    // started the derivation
+                    Ok(()) => app.okm.as_mut().map_or((ReturnCode::EINVAL, 0), |dest| {
+                        let n = cmp::min(cmp::min(len, dest.len()), okm.len());
+                        dest.as_mut()[..n].copy_from_slice(&okm[..n]);
+                        if n == len {
+                            (ReturnCode::SUCCESS, n)
+                        } else {
+                            (ReturnCode::ESIZE, n)
+                        }
+                    }),
+                    Err(e) => (ReturnCode::from(e), 0),
+                };
+                app.callback.map(|cb| cb.schedule(usize::from(res), n, 0));
+            });
+        });
+        okm.iter_mut().for_each(|b| *b = 0);
+        self.okm_buffer.replace(okm);
+    }
+}
+
+/// Specify memory regions to be used.
+///
+/// ### `allow_num`
+///
+/// - `0`: The salt, of any length. May be empty or not allowed.
+/// - `1`: The input key material.
+/// - `2`: The context information. May be empty or not allowed.
+/// - `3`: The output key material. The kernel derives as many bytes as this
+///        buffer holds.
+impl<'a, H: digest::Digest<'a, [u8; HASH_LEN]> + digest::HMACSha256> Driver for HkdfDriver<'a, H> {
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        self.apps
+            .enter(appid, |app, _| {
+                match allow_num {
+                    0 => app.salt = slice,
+                    1 => app.ikm = slice,
+                    2 => app.info = slice,
+                    3 => app.okm = slice,
+                    _ => return ReturnCode::ENOSUPPORT,
+                }
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    /// Subscribe to HkdfDriver events.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: The derivation is done. The callback signature is
+    ///        `fn(result: u32, len: u32)`, where `len` is the number of
+    ///        bytes written to the output buffer. `result` is `ESIZE` if a
+    ///        shorter output buffer was allowed during the derivation.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.callback.insert(callback);
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Derive the output key material from the buffers allowed.
+    ///        Returns `EBUSY` if another process is deriving a key, `ESIZE`
+    ///        if the output buffer is longer than the kernel buffer, and
+    ///        `EINVAL` if the lengths are invalid for HKDF, or the IKM and
+    ///        the info are too long.
+    fn command(
+        &self,
+        command_num: usize,
+        _data1: usize,
+        _data2: usize,
+        appid: AppId,
+    ) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => {
+                // A derivation always completes, even if its process dies
+                // in the meantime, so there is no owner to clear here.
+                if self.appid.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                self.derive(appid)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+pub struct App {
+    callback: OptionalCell<Callback>,
+    salt: Option<AppSlice<Shared, u8>>,
+    ikm: Option<AppSlice<Shared, u8>>,
+    info: Option<AppSlice<Shared, u8>>,
+    okm: Option<AppSlice<Shared, u8>>,
+}
+
+impl Default for App {
+    fn default() -> App {
+        App {
+            callback: OptionalCell::empty(),
+            salt: None,
+            ikm: None,
+            info: None,
+            okm: None,
+        }
+    }
+}
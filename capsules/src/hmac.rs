@@ -1,5 +1,21 @@
 //! HMAC (Hash-based Message Authentication Code).
 //!
+//! Before the first operation of a process, the driver checks the engine
+//! with known-answer tests: the SHA-256 of "abc" and test case 2 of RFC
+//! 4231, an HMAC-SHA256. The operations requested while the tests run wait
+//! for them. If the engine gives a wrong answer or reports a fault, the
+//! driver refuses all further operations with `FAIL`, since it cannot be
+//! trusted to authenticate anything. If the tests cannot run, e.g. because
+//! another user of the engine holds it, the waiting operations fail with
+//! that error and the tests run again with the next one. Command `3`
+//! reports the outcome.
+//!
+//! Besides the key in its allowed key buffer, an app can load up to
+//! `KEY_SLOTS` keys into slots held by the kernel in its grant, and pass the
+//! handle of a slot to `run` and `verify` instead of allowing the key every
+//! time. Once loaded, the key no longer needs to be in the memory of the
+//! app, and a key loaded as non-exportable can never be read back.
+//!
 //! Usage
 //! -----
 //!
@@ -13,6 +29,7 @@
 //!     VirtualMuxHmac<'static, lowrisc::hmac::Hmac>,
 //!     VirtualMuxHmac::new(mux_hmac)
 //! );
+//! virtual_hmac_user.setup();
 //! let hmac = static_init!(
 //!     capsules::hmac::HmacDriver<'static, VirtualMuxHmac<'static, lowrisc::hmac::Hmac>>,
 //!     capsules::hmac::HmacDriver::new(
@@ -28,18 +45,77 @@ use crate::driver;
 pub const DRIVER_NUM: usize = driver::NUM::Hmac as usize;
 
 use core::cell::Cell;
-use core::convert::TryInto;
 use core::marker::PhantomData;
 use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::common::leasable_buffer::LeasableBuffer;
+use kernel::hil::crypto::CryptoError;
 use kernel::hil::digest;
 use kernel::hil::digest::DigestType;
 use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
 
+/// The number of key slots of each app.
+pub const KEY_SLOTS: usize = 4;
+/// The longest key a slot holds: one SHA-256 block. Longer keys are
+/// equivalent to their SHA-256 digest, which apps can load instead.
+pub const MAX_KEY_LEN: usize = 64;
+
+/// The handle that selects the allowed key buffer rather than a key slot.
+const ALLOWED_KEY: usize = 0;
+/// Load flag: the key can be exported back to the app.
+const KEY_EXPORTABLE: usize = 1;
+
+/// The known-answer test of plain SHA-256: the digest of "abc".
+pub(crate) const KAT_SHA256_DATA: &[u8] = b"abc";
+pub(crate) const KAT_SHA256_DIGEST: [u8; 32] = [
+    0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae, 0x22, 0x23,
+    0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61, 0xf2, 0x00, 0x15, 0xad,
+];
+
+/// The known-answer test of HMAC-SHA256: test case 2 of RFC 4231.
+pub(crate) const KAT_HMAC_KEY: &[u8] = b"Jefe";
+pub(crate) const KAT_HMAC_DATA: &[u8] = b"what do ya want for nothing?";
+pub(crate) const KAT_HMAC_DIGEST: [u8; 32] = [
+    0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95, 0x75, 0xc7,
+    0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9, 0x64, 0xec, 0x38, 0x43,
+];
+
+/// The state of the known-answer tests of the engine. The values are those
+/// reported by command `3`.
+#[derive(Clone, Copy, PartialEq)]
+enum SelfTest {
+    NotRun = 0,
+    /// Computing the SHA-256 test.
+    Sha256 = 1,
+    /// Computing the HMAC test.
+    Hmac = 2,
+    Passed = 3,
+    Failed = 4,
+}
+
+impl SelfTest {
+    fn running(self) -> bool {
+        self == SelfTest::Sha256 || self == SelfTest::Hmac
+    }
+}
+
+/// The known-answer tests of the engine under a driver, for diagnostics
+/// outside of the syscall interface.
+pub trait EngineSelfTest {
+    /// Start the tests if they have not run yet, and return their state,
+    /// with the values reported by command `3`.
+    fn engine_self_test(&self) -> usize;
+}
+
 pub struct HmacDriver<'a, H: digest::Digest<'a, T>, T: 'static + DigestType> {
     hmac: &'a H,
 
     active: Cell<bool>,
+    self_test: Cell<SelfTest>,
+    /// Whether the running operation compares the HMAC with the digest
+    /// buffer of the app instead of filling it.
+    verify: Cell<bool>,
+    /// The key handle of the running operation.
+    key: Cell<usize>,
 
     apps: Grant<App>,
     appid: OptionalCell<AppId>,
@@ -50,7 +126,11 @@ pub struct HmacDriver<'a, H: digest::Digest<'a, T>, T: 'static + DigestType> {
     dest_buffer: TakeCell<'static, T>,
 }
 
-impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> HmacDriver<'a, H, T>
+impl<
+        'a,
+        H: digest::Digest<'a, T> + digest::DigestVerify<'a, T> + digest::HMACSha256 + digest::Sha256,
+        T: DigestType,
+    > HmacDriver<'a, H, T>
 where
     T: AsMut<[u8]>,
 {
@@ -63,6 +143,9 @@ where
         HmacDriver {
             hmac: hmac,
             active: Cell::new(false),
+            self_test: Cell::new(SelfTest::NotRun),
+            verify: Cell::new(false),
+            key: Cell::new(ALLOWED_KEY),
             apps: grant,
             appid: OptionalCell::empty(),
             phantom: PhantomData,
@@ -76,16 +159,23 @@ where
         self.appid.map_or(ReturnCode::ERESERVE, move |appid| {
             self.apps
                 .enter(*appid, |app, _| {
-                    match app.key.as_ref() {
-                        Some(k) => {
-                            self.hmac
-                                .set_mode_hmacsha256(k.as_ref().try_into().unwrap())
-                                .unwrap();
-                        }
-                        None => {
-                            return ReturnCode::ERESERVE;
-                        }
+                    let res = match self.key.get() {
+                        ALLOWED_KEY => match app.key.as_ref() {
+                            Some(k) => self.hmac.set_mode_hmacsha256(k.as_ref()),
+                            None => {
+                                return ReturnCode::ERESERVE;
+                            }
+                        },
+                        handle => match slot(&app.keys, handle) {
+                            Some(key) => self.hmac.set_mode_hmacsha256(key.as_ref()),
+                            None => {
+                                return ReturnCode::EINVAL;
+                            }
+                        },
                     };
+                    if let Err(e) = res {
+                        return e.into();
+                    }
 
                     match app.data.as_ref() {
                         Some(d) => {
@@ -110,7 +200,7 @@ where
                                 .add_data(LeasableBuffer::new(self.data_buffer.take().unwrap()))
                             {
                                 self.data_buffer.replace(e.1);
-                                return e.0;
+                                return e.0.into();
                             }
                         }
                         None => {
@@ -124,7 +214,112 @@ where
         })
     }
 
+    /// Stop the operation of the owning app and take back the buffers that
+    /// were given to the HMAC.
+    fn cancel(&self) {
+        let cancelled = self.hmac.cancel();
+        cancelled.data.map(|data| self.data_buffer.replace(data));
+        cancelled
+            .digest
+            .map(|digest| self.dest_buffer.replace(digest));
+        self.data_copied.set(0);
+        self.appid.clear();
+    }
+
+    /// Start the known-answer tests, with the SHA-256 test.
+    fn start_self_test(&self) {
+        self.hmac.clear_data();
+        self.self_test.set(SelfTest::Sha256);
+        let res = self
+            .hmac
+            .set_mode_sha256()
+            .and_then(|()| self.add_self_test_data(KAT_SHA256_DATA));
+        if let Err(e) = res {
+            self.self_test_done(Err(e));
+        }
+    }
+
+    fn add_self_test_data(&self, data: &[u8]) -> Result<(), CryptoError> {
+        let buffer = self.data_buffer.take().ok_or(CryptoError::EngineBusy)?;
+        if buffer.len() < data.len() {
+            self.data_buffer.replace(buffer);
+            return Err(CryptoError::InvalidArgument);
+        }
+        buffer[..data.len()].copy_from_slice(data);
+        let mut lease = LeasableBuffer::new(buffer);
+        lease.slice(..data.len());
+        self.hmac
+            .add_data(lease)
+            .map(|_| ())
+            .map_err(|(e, buffer)| {
+                self.data_buffer.replace(buffer);
+                e
+            })
+    }
+
+    /// Check the digest of a known-answer test, and start the next one.
+    fn self_test_digest(&self, result: Result<(), CryptoError>, digest: &T) {
+        let expected = match self.self_test.get() {
+            SelfTest::Sha256 => &KAT_SHA256_DIGEST,
+            _ => &KAT_HMAC_DIGEST,
+        };
+        let res = result.and_then(|()| {
+            if digest.as_ref() != &expected[..] {
+                return Err(CryptoError::HardwareFault);
+            }
+            Ok(())
+        });
+        if res.is_err() || self.self_test.get() == SelfTest::Hmac {
+            self.self_test_done(res);
+            return;
+        }
+
+        self.hmac.clear_data();
+        self.self_test.set(SelfTest::Hmac);
+        let res = self
+            .hmac
+            .set_mode_hmacsha256(KAT_HMAC_KEY)
+            .and_then(|()| self.add_self_test_data(KAT_HMAC_DATA));
+        if let Err(e) = res {
+            self.self_test_done(Err(e));
+        }
+    }
+
+    /// Record the outcome of the known-answer tests, and serve the
+    /// operations that waited for them. Only a wrong digest or a fault of
+    /// the engine fails the tests for good. Other errors, such as the
+    /// engine being busy with another user, fail the waiting operations,
+    /// and the tests run again with the next one.
+    fn self_test_done(&self, result: Result<(), CryptoError>) {
+        self.hmac.clear_data();
+        let error = match result {
+            Ok(()) => {
+                self.self_test.set(SelfTest::Passed);
+                self.check_queue();
+                return;
+            }
+            Err(CryptoError::HardwareFault) => {
+                self.self_test.set(SelfTest::Failed);
+                ReturnCode::FAIL
+            }
+            Err(e) => {
+                self.self_test.set(SelfTest::NotRun);
+                ReturnCode::from(e)
+            }
+        };
+        for appiter in self.apps.iter() {
+            appiter.enter(|app, _| {
+                if app.pending_run_app.take().is_some() {
+                    app.callback.map(|cb| cb.schedule(usize::from(error), 0, 0));
+                }
+            });
+        }
+    }
+
     fn check_queue(&self) {
+        if self.self_test.get() != SelfTest::Passed {
+            return;
+        }
         for appiter in self.apps.iter() {
             let started_command = appiter.enter(|app, _| {
                 // If an app is already running let it complete
@@ -136,6 +331,8 @@ where
                 app.pending_run_app.take().map_or(false, |appid| {
                     // Mark this driver as being in use.
                     self.appid.set(appid);
+                    self.verify.set(app.pending_verify);
+                    self.key.set(app.pending_key);
                     // Actually make the buzz happen.
                     self.run() == ReturnCode::SUCCESS
                 })
@@ -147,10 +344,28 @@ where
     }
 }
 
-impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> digest::Client<'a, T>
-    for HmacDriver<'a, H, T>
+impl<
+        'a,
+        H: digest::Digest<'a, T> + digest::DigestVerify<'a, T> + digest::HMACSha256 + digest::Sha256,
+        T: DigestType,
+    > digest::Client<'a, T> for HmacDriver<'a, H, T>
 {
-    fn add_data_done(&'a self, _result: Result<(), ReturnCode>, data: &'static mut [u8]) {
+    fn add_data_done(&'a self, result: Result<(), CryptoError>, data: &'static mut [u8]) {
+        if self.self_test.get().running() {
+            self.data_buffer.replace(data);
+            let res = result.and_then(|()| {
+                let digest = self.dest_buffer.take().ok_or(CryptoError::EngineBusy)?;
+                self.hmac.run(digest).map_err(|(e, digest)| {
+                    self.dest_buffer.replace(digest);
+                    e
+                })
+            });
+            if let Err(e) = res {
+                self.self_test_done(Err(e));
+            }
+            return;
+        }
+
         self.appid.map(move |id| {
             self.apps
                 .enter(*id, move |app, _| {
@@ -222,13 +437,29 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> digest::C
                     // If we get here we are ready to run the digest, reset the copied data
                     self.data_copied.set(0);
 
-                    if let Err(e) = self.hmac.run(self.dest_buffer.take().unwrap()) {
+                    let dest_buffer = self.dest_buffer.take().unwrap();
+                    let result = if self.verify.get() {
+                        // The digest buffer of the app holds the expected value
+                        match app.dest.as_ref() {
+                            Some(dest) if dest.len() == dest_buffer.as_ref().len() => {
+                                dest_buffer.as_mut().copy_from_slice(dest.as_ref());
+                                self.hmac.verify(dest_buffer)
+                            }
+                            _ => Err((CryptoError::InvalidArgument, dest_buffer)),
+                        }
+                    } else {
+                        self.hmac.run(dest_buffer)
+                    };
+
+                    if let Err((e, dest_buffer)) = result {
+                        self.dest_buffer.replace(dest_buffer);
+
                         // Error, clear the appid and data
                         self.hmac.clear_data();
                         self.appid.clear();
 
                         app.callback.map(|cb| {
-                            cb.schedule(usize::from(e.0), 0, 0);
+                            cb.schedule(usize::from(ReturnCode::from(e)), 0, 0);
                         });
 
                         self.check_queue();
@@ -246,7 +477,19 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> digest::C
         });
     }
 
-    fn hash_done(&'a self, result: Result<(), ReturnCode>, digest: &mut T) {
+    fn add_readonly_data_done(&'a self, _result: Result<(), CryptoError>, _data: &'static [u8]) {
+        // The driver only ever hashes data copied into its own mutable
+        // buffer, so this callback is never expected.
+    }
+
+    fn hash_done(&'a self, result: Result<(), CryptoError>, digest: &'static mut T) {
+        if self.self_test.get().running() {
+            let value = *digest;
+            self.dest_buffer.replace(digest);
+            self.self_test_digest(result, &value);
+            return;
+        }
+
         self.appid.map(|id| {
             self.apps
                 .enter(*id, |app, _| {
@@ -263,7 +506,9 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> digest::C
 
                     app.callback.map(|cb| match result {
                         Ok(_) => cb.schedule(0, pointer as usize, 0),
-                        Err(e) => cb.schedule(usize::from(e), pointer as usize, 0),
+                        Err(e) => {
+                            cb.schedule(usize::from(ReturnCode::from(e)), pointer as usize, 0)
+                        }
                     });
 
                     // Clear the current appid as it has finished running
@@ -279,6 +524,63 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> digest::C
                     }
                 })
         });
+
+        self.dest_buffer.replace(digest);
+    }
+}
+
+impl<
+        'a,
+        H: digest::Digest<'a, T> + digest::DigestVerify<'a, T> + digest::HMACSha256 + digest::Sha256,
+        T: DigestType,
+    > digest::ClientVerify<'a, T> for HmacDriver<'a, H, T>
+{
+    fn verification_done(&'a self, result: Result<bool, CryptoError>, compare: &'static mut T) {
+        // Don't leave the expected value around
+        for byte in compare.as_mut().iter_mut() {
+            *byte = 0;
+        }
+        self.dest_buffer.replace(compare);
+
+        self.appid.map(|id| {
+            self.apps
+                .enter(*id, |app, _| {
+                    self.hmac.clear_data();
+
+                    app.callback.map(|cb| match result {
+                        Ok(matched) => cb.schedule(0, matched as usize, 0),
+                        Err(e) => cb.schedule(usize::from(ReturnCode::from(e)), 0, 0),
+                    });
+
+                    // Clear the current appid as it has finished running
+                    self.appid.clear();
+                    self.check_queue();
+                })
+                .map_err(|err| {
+                    if err == kernel::procs::Error::NoSuchApp
+                        || err == kernel::procs::Error::InactiveApp
+                    {
+                        self.appid.clear();
+                        self.check_queue();
+                    }
+                })
+        });
+    }
+}
+
+impl<
+        'a,
+        H: digest::Digest<'a, T> + digest::DigestVerify<'a, T> + digest::HMACSha256 + digest::Sha256,
+        T: DigestType,
+    > EngineSelfTest for HmacDriver<'a, H, T>
+where
+    T: AsMut<[u8]>,
+{
+    fn engine_self_test(&self) -> usize {
+        if self.self_test.get() == SelfTest::NotRun {
+            self.start_self_test();
+        }
+        self.self_test.get() as usize
     }
 }
 
@@ -286,8 +588,10 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> digest::C
 ///
 /// ### `allow_num`
 ///
-/// - `0`: Allow a buffer for storing the key.
-///        The kernel will read from this when running
+/// - `0`: Allow a buffer for storing the key, of any length.
+///        The kernel will read from this when running with the key handle
+///        `0`, and when loading a key into a slot. Exporting a key writes
+///        it here.
 ///        This should not be changed after running `run` until the HMAC
 ///        has completed
 /// - `1`: Allow a buffer for storing the buffer.
@@ -296,9 +600,13 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> digest::C
 ///        has completed
 /// - `2`: Allow a buffer for storing the digest.
 ///        The kernel will fill this with the HMAC digest before calling
-///        the `hash_done` callback.
-impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> Driver
-    for HmacDriver<'a, H, T>
+///        the `hash_done` callback. For `verify` this holds the expected
+///        digest instead, which the kernel only reads.
+impl<
+        'a,
+        H: digest::Digest<'a, T> + digest::DigestVerify<'a, T> + digest::HMACSha256 + digest::Sha256,
+        T: DigestType,
+    > Driver for HmacDriver<'a, H, T>
 {
     fn allow(
         &self,
@@ -344,7 +652,9 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> Driver
     /// ### `subscribe_num`
     ///
     /// - `0`: Subscribe to interrupts from HMAC events.
-    ///        The callback signature is `fn(result: u32)`
+    ///        The callback signature is `fn(result: u32)`. After `verify`
+    ///        the second argument is 1 if the HMAC matched the expected
+    ///        digest and 0 if it didn't.
     fn subscribe(
         &self,
         subscribe_num: usize,
@@ -385,7 +695,25 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> Driver
     /// ### `command_num`
     ///
     /// - `0`: set_algorithm
-    /// - `1`: run
+    /// - `1`: run, with the key handle in `data1`: `0` for the allowed key
+    ///        buffer, or the handle of a loaded key
+    /// - `2`: verify, like run but the HMAC is compared in constant time
+    ///        with the digest buffer, and only the result is reported
+    /// - `3`: self-test status. Returns `SuccessWithValue` with `0` if the
+    ///        known-answer tests have not run yet, or could not run because
+    ///        the engine was busy, `1` or `2` while they
+    ///        run, `3` if the engine passed them and `4` if it failed them,
+    ///        in which case `run` and `verify` return `FAIL`.
+    /// - `4`: load the allowed key buffer into a free key slot. If bit 0 of
+    ///        `data1` is set the key can be exported later. Returns
+    ///        `SuccessWithValue` with the handle of the key, `ENOMEM` if all
+    ///        the slots are in use and `ESIZE` if the key is longer than
+    ///        `MAX_KEY_LEN` bytes.
+    /// - `5`: export the key with handle `data1` into the allowed key
+    ///        buffer. Returns `SuccessWithValue` with the length of the key,
+    ///        `FAIL` if the key is not exportable and `ESIZE` if the buffer
+    ///        is too short.
+    /// - `6`: erase the key with handle `data1` and free its slot.
     fn command(&self, command_num: usize, data1: usize, _data2: usize, appid: AppId) -> ReturnCode {
         let match_or_empty_or_nonexistant = self.appid.map_or(true, |owning_app| {
             // We have recorded that an app has ownership of the HMAC.
@@ -411,6 +739,15 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> Driver
             }
         });
 
+        // The owning app may have died in the middle of an operation, stop it
+        // and get the buffers back before starting a new one.
+        let owner_gone = self.appid.map_or(false, |owning_app| {
+            owning_app != &appid && self.apps.enter(*owning_app, |_, _| ()).is_err()
+        });
+        if owner_gone {
+            self.cancel();
+        }
+
         match command_num {
             // set_algorithm
             0 => {
@@ -424,10 +761,38 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> Driver
                 }
             }
 
-            // run
-            1 => {
-                if match_or_empty_or_nonexistant {
+            // run, verify
+            1 | 2 => {
+                let verify = command_num == 2;
+                let key = data1;
+                let valid_key = self
+                    .apps
+                    .enter(appid, |app, _| {
+                        key == ALLOWED_KEY || slot(&app.keys, key).is_some()
+                    })
+                    .unwrap_or(false);
+                if !valid_key {
+                    return ReturnCode::EINVAL;
+                }
+
+                match self.self_test.get() {
+                    SelfTest::Failed => return ReturnCode::FAIL,
+                    SelfTest::NotRun => self.start_self_test(),
+                    _ => {}
+                }
+                match self.self_test.get() {
+                    SelfTest::Failed => return ReturnCode::FAIL,
+                    // The tests could not start, e.g. another user of the
+                    // engine is running
+                    SelfTest::NotRun => return ReturnCode::EBUSY,
+                    _ => {}
+                }
+
+                // The request waits for the self-test like for another app
+                if self.self_test.get() == SelfTest::Passed && match_or_empty_or_nonexistant {
                     self.appid.set(appid);
+                    self.verify.set(verify);
+                    self.key.set(key);
                     let ret = self.run();
 
                     if ret != ReturnCode::SUCCESS {
@@ -449,6 +814,8 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> Driver
                             } else {
                                 // We can store this, so lets do it.
                                 app.pending_run_app = Some(appid);
+                                app.pending_verify = verify;
+                                app.pending_key = key;
                                 ReturnCode::SUCCESS
                             }
                         })
@@ -456,18 +823,122 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> Driver
                 }
             }
 
+            // self-test status
+            3 => ReturnCode::SuccessWithValue {
+                value: self.self_test.get() as usize,
+            },
+
+            // load key
+            4 => self
+                .apps
+                .enter(appid, |app, _| {
+                    let len = match app.key.as_ref() {
+                        Some(k) => k.len(),
+                        None => return ReturnCode::ERESERVE,
+                    };
+                    if len > MAX_KEY_LEN {
+                        return ReturnCode::ESIZE;
+                    }
+                    let index = match app.keys.iter().position(|slot| slot.is_none()) {
+                        Some(index) => index,
+                        None => return ReturnCode::ENOMEM,
+                    };
+                    let mut key = Key {
+                        bytes: [0; MAX_KEY_LEN],
+                        len: len,
+                        exportable: data1 & KEY_EXPORTABLE != 0,
+                    };
+                    app.key
+                        .as_ref()
+                        .map(|k| key.bytes[..len].copy_from_slice(k.as_ref()));
+                    app.keys[index] = Some(key);
+                    ReturnCode::SuccessWithValue { value: index + 1 }
+                })
+                .unwrap_or_else(|err| err.into()),
+
+            // export key
+            5 => self
+                .apps
+                .enter(appid, |app, _| {
+                    let app: &mut App = app;
+                    let key = match slot(&app.keys, data1) {
+                        Some(key) => key,
+                        None => return ReturnCode::EINVAL,
+                    };
+                    if !key.exportable {
+                        return ReturnCode::FAIL;
+                    }
+                    let len = key.len;
+                    match app.key.as_mut() {
+                        Some(k) if k.len() >= len => {
+                            k.as_mut()[..len].copy_from_slice(&key.bytes[..len]);
+                            ReturnCode::SuccessWithValue { value: len }
+                        }
+                        Some(_) => ReturnCode::ESIZE,
+                        None => ReturnCode::ERESERVE,
+                    }
+                })
+                .unwrap_or_else(|err| err.into()),
+
+            // erase key
+            6 => self
+                .apps
+                .enter(appid, |app, _| {
+                    if slot(&app.keys, data1).is_none() {
+                        return ReturnCode::EINVAL;
+                    }
+                    // A running or queued operation of the app may use the
+                    // key.
+                    let owner = self.appid.map_or(false, |owner| owner == &appid);
+                    if app.pending_run_app.is_some() || (owner && self.key.get() == data1) {
+                        return ReturnCode::EBUSY;
+                    }
+                    if let Some(key) = app.keys[data1 - 1].as_mut() {
+                        for byte in key.bytes.iter_mut() {
+                            *byte = 0;
+                        }
+                    }
+                    app.keys[data1 - 1] = None;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+
             // default
             _ => ReturnCode::ENOSUPPORT,
         }
     }
 }
 
+/// A key loaded into a slot.
+struct Key {
+    bytes: [u8; MAX_KEY_LEN],
+    len: usize,
+    exportable: bool,
+}
+
+impl AsRef<[u8]> for Key {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
 pub struct App {
     callback: OptionalCell<Callback>,
     pending_run_app: Option<AppId>,
+    pending_verify: bool,
+    pending_key: usize,
     key: Option<AppSlice<Shared, u8>>,
     data: Option<AppSlice<Shared, u8>>,
     dest: Option<AppSlice<Shared, u8>>,
+    keys: [Option<Key>; KEY_SLOTS],
+}
+
+/// The key of `keys` with handle `handle`, if it is loaded.
+fn slot(keys: &[Option<Key>], handle: usize) -> Option<&Key> {
+    handle
+        .checked_sub(1)
+        .and_then(|index| keys.get(index))
+        .and_then(|slot| slot.as_ref())
 }
 
 impl Default for App {
@@ -475,9 +946,12 @@ impl Default for App {
         App {
             callback: OptionalCell::empty(),
             pending_run_app: None,
+            pending_verify: false,
+            pending_key: ALLOWED_KEY,
             key: None,
             data: None,
             dest: None,
+            keys: Default::default(),
         }
     }
 }
@@ -0,0 +1,242 @@
+//! HMAC-SHA256 challenge-response authentication for the process console.
+//!
+//! `HmacChallenge` implements `process_console::Authenticator`. A challenge
+//! is a fresh random value from an `Rng`, and the expected response is
+//! HMAC-SHA256(key, challenge), computed by the digest engine. The key is
+//! provided by the board, typically read from a key store or from
+//! one-time-programmable memory, and never leaves the device. Each challenge
+//! can be answered only once.
+//!
+//! On the host, the response can be computed with e.g.:
+//!
+//! ```text
+//! $ echo -n <challenge> | xxd -r -p | openssl dgst -sha256 -mac HMAC -macopt hexkey:<key>
+//! ```
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let auth = static_init!(
+//!     capsules::hmac_challenge::HmacChallenge<'static, lowrisc::hmac::Hmac>,
+//!     capsules::hmac_challenge::HmacChallenge::new(
+//!         &lowrisc::hmac::HMAC,
+//!         rng,
+//!         CONSOLE_KEY,
+//!         &mut capsules::hmac_challenge::CHALLENGE_BUF,
+//!         &mut capsules::hmac_challenge::DIGEST_BUF,
+//!     )
+//! );
+//! lowrisc::hmac::HMAC.set_client(auth);
+//! rng.set_client(auth);
+//! auth.set_client(pconsole);
+//! pconsole.set_authenticator(auth);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::leasable_buffer::LeasableBuffer;
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::digest;
+use kernel::hil::rng;
+use kernel::ReturnCode;
+
+use crate::process_console::{Authenticator, AuthenticatorClient};
+
+/// Length of a challenge in bytes.
+pub const CHALLENGE_LEN: usize = 16;
+
+pub static mut CHALLENGE_BUF: [u8; CHALLENGE_LEN] = [0; CHALLENGE_LEN];
+pub static mut DIGEST_BUF: [u8; 32] = [0; 32];
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    Challenge,
+    Verify,
+}
+
+pub struct HmacChallenge<'a, D: digest::Digest<'a, [u8; 32]> + digest::HMACSha256> {
+    hmac: &'a D,
+    rng: &'a dyn rng::Rng<'a>,
+    key: [u8; 32],
+    client: OptionalCell<&'a dyn AuthenticatorClient>,
+    state: Cell<State>,
+
+    challenge: TakeCell<'static, [u8]>,
+    challenge_len: Cell<usize>,
+    /// Whether the current challenge can still be answered.
+    challenge_valid: Cell<bool>,
+    digest: TakeCell<'static, [u8; 32]>,
+    response: Cell<[u8; 32]>,
+}
+
+impl<'a, D: digest::Digest<'a, [u8; 32]> + digest::HMACSha256> HmacChallenge<'a, D> {
+    pub fn new(
+        hmac: &'a D,
+        rng: &'a dyn rng::Rng<'a>,
+        key: [u8; 32],
+        challenge: &'static mut [u8],
+        digest: &'static mut [u8; 32],
+    ) -> HmacChallenge<'a, D> {
+        HmacChallenge {
+            hmac: hmac,
+            rng: rng,
+            key: key,
+            client: OptionalCell::empty(),
+            state: Cell::new(State::Idle),
+            challenge: TakeCell::new(challenge),
+            challenge_len: Cell::new(0),
+            challenge_valid: Cell::new(false),
+            digest: TakeCell::new(digest),
+            response: Cell::new([0; 32]),
+        }
+    }
+
+    fn finish(&self, valid: bool) {
+        self.hmac.clear_data();
+        self.state.set(State::Idle);
+        self.client.map(|client| client.verified(valid));
+    }
+}
+
+impl<'a, D: digest::Digest<'a, [u8; 32]> + digest::HMACSha256> Authenticator<'a>
+    for HmacChallenge<'a, D>
+{
+    fn set_client(&self, client: &'a dyn AuthenticatorClient) {
+        self.client.set(client);
+    }
+
+    fn start_challenge(&self) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.challenge_valid.set(false);
+        self.challenge_len.set(0);
+        let res = self.rng.get();
+        if res == ReturnCode::SUCCESS {
+            self.state.set(State::Challenge);
+        }
+        res
+    }
+
+    fn verify(&self, response: &[u8]) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        if !self.challenge_valid.get() {
+            return ReturnCode::EINVAL;
+        }
+        // Whatever the outcome, the challenge must not be reused.
+        self.challenge_valid.set(false);
+        if response.len() != 32 {
+            return ReturnCode::EINVAL;
+        }
+        let mut expected = [0; 32];
+        expected.copy_from_slice(response);
+        self.response.set(expected);
+
+        if let Err(e) = self.hmac.set_mode_hmacsha256(&self.key) {
+            return e.into();
+        }
+        let challenge = match self.challenge.take() {
+            None => return ReturnCode::ENOMEM,
+            Some(challenge) => challenge,
+        };
+        let mut lease = LeasableBuffer::new(challenge);
+        lease.slice(0..CHALLENGE_LEN);
+        match self.hmac.add_data(lease) {
+            Ok(_) => {
+                self.state.set(State::Verify);
+                ReturnCode::SUCCESS
+            }
+            Err((e, challenge)) => {
+                self.challenge.replace(challenge);
+                self.hmac.clear_data();
+                e.into()
+            }
+        }
+    }
+}
+
+impl<'a, D: digest::Digest<'a, [u8; 32]> + digest::HMACSha256> rng::Client
+    for HmacChallenge<'a, D>
+{
+    fn randomness_available(
+        &self,
+        randomness: &mut dyn Iterator<Item = u32>,
+        error: ReturnCode,
+    ) -> rng::Continue {
+        if self.state.get() != State::Challenge {
+            return rng::Continue::Done;
+        }
+        if error != ReturnCode::SUCCESS {
+            self.state.set(State::Idle);
+            return rng::Continue::Done;
+        }
+
+        let done = self.challenge.map_or(true, |challenge| {
+            let mut len = self.challenge_len.get();
+            while len < CHALLENGE_LEN {
+                match randomness.next() {
+                    None => break,
+                    Some(word) => {
+                        let bytes = word.to_le_bytes();
+                        let count = core::cmp::min(4, CHALLENGE_LEN - len);
+                        challenge[len..len + count].copy_from_slice(&bytes[..count]);
+                        len += count;
+                    }
+                }
+            }
+            self.challenge_len.set(len);
+            len == CHALLENGE_LEN
+        });
+        if !done {
+            return rng::Continue::More;
+        }
+
+        self.state.set(State::Idle);
+        self.challenge_valid.set(true);
+        self.challenge.map(|challenge| {
+            self.client
+                .map(|client| client.challenge_ready(&challenge[..CHALLENGE_LEN]));
+        });
+        rng::Continue::Done
+    }
+}
+
+impl<'a, D: digest::Digest<'a, [u8; 32]> + digest::HMACSha256> digest::Client<'a, [u8; 32]>
+    for HmacChallenge<'a, D>
+{
+    fn add_data_done(&'a self, result: Result<(), CryptoError>, data: &'static mut [u8]) {
+        self.challenge.replace(data);
+        if result.is_err() {
+            self.finish(false);
+            return;
+        }
+        match self.digest.take() {
+            None => self.finish(false),
+            Some(digest) => {
+                if let Err((_, digest)) = self.hmac.run(digest) {
+                    self.digest.replace(digest);
+                    self.finish(false);
+                }
+            }
+        }
+    }
+
+    fn add_readonly_data_done(&'a self, _result: Result<(), CryptoError>, _data: &'static [u8]) {}
+
+    fn hash_done(&'a self, result: Result<(), CryptoError>, digest: &'static mut [u8; 32]) {
+        // Accumulate the differences so the comparison takes the same time
+        // regardless of where the values differ.
+        let diff = digest
+            .iter()
+            .zip(self.response.get().iter())
+            .fold(0, |acc, (a, b)| acc | (*a ^ *b));
+        digest.iter_mut().for_each(|b| *b = 0);
+        self.digest.replace(digest);
+        self.response.set([0; 32]);
+        self.finish(result.is_ok() && diff == 0);
+    }
+}
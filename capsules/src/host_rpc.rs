@@ -0,0 +1,373 @@
+//! Binary RPC over a UART for hardware-in-the-loop test automation.
+//!
+//! A host script sends length-prefixed requests and the capsule answers
+//! each with one response, so that tests can run the known-answer tests,
+//! read the kernel event counters, hash memory regions and drive pins
+//! without parsing the text of the process console. Only the functions
+//! below, and those the board registers with `set_functions()`, can be
+//! called. The channel is unauthenticated: it is meant for test builds, on
+//! a UART that is not exposed otherwise.
+//!
+//! Frames
+//! ------
+//!
+//! A request is a 16-bit little-endian length, followed by that many bytes:
+//! the function number and its arguments. The response has the same
+//! layout: the length, then the status as the signed 8-bit value of a
+//! `ReturnCode`, then the result. Requests longer than the receive buffer
+//! are read and dropped, and answered with `ESIZE`. The capsule only
+//! receives the next request once the response is sent.
+//!
+//! Functions
+//! ---------
+//!
+//! - `0x00` ping: the result is the arguments.
+//! - `0x01` known-answer tests of the software SHA-256 and HMAC-SHA256. The
+//!   result has one byte per test, `0` if it passed and `1` if it failed.
+//!   If the board gave the HMAC driver to `set_engine_self_test()`, a third
+//!   byte is the state of the known-answer tests of its hash engine, as
+//!   reported by its command `3`: the first call starts them, and the host
+//!   calls again until the state is `3`, passed, or `4`, failed.
+//! - `0x02` metrics: the argument is the name of a kernel event counter.
+//!   The result is the key and the value of each entry, as 32-bit
+//!   little-endian integers.
+//! - `0x03` hash: the arguments are the index of a region given to `new()`,
+//!   and a 32-bit little-endian offset and length. The result is the
+//!   SHA-256 of those bytes of the region.
+//! - `0x04` pin: the arguments are the index of a pin given to `new()` and
+//!   an operation: `0` clear, `1` set, `2` toggle, `3` read. The result is
+//!   the level of the pin after the operation.
+//! - `0x80` and up: the functions of the board, in the order they were
+//!   registered.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let rpc_uart = static_init!(UartDevice, UartDevice::new(uart_mux, true));
+//! rpc_uart.setup();
+//! let rpc = static_init!(
+//!     capsules::host_rpc::HostRpc<'static, Capability>,
+//!     capsules::host_rpc::HostRpc::new(
+//!         rpc_uart,
+//!         &mut capsules::host_rpc::TX_BUF,
+//!         &mut capsules::host_rpc::RX_BUF,
+//!         board_kernel,
+//!         Capability,
+//!         &[flash_region],
+//!         &[&nrf52::gpio::PORT[LED1_PIN]],
+//!     )
+//! );
+//! hil::uart::Transmit::set_transmit_client(rpc_uart, rpc);
+//! hil::uart::Receive::set_receive_client(rpc_uart, rpc);
+//! rpc.set_engine_self_test(hmac);
+//! rpc.start();
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+
+use crate::hmac::EngineSelfTest;
+use crate::hmac::{KAT_HMAC_DATA, KAT_HMAC_DIGEST, KAT_HMAC_KEY};
+use crate::hmac::{KAT_SHA256_DATA, KAT_SHA256_DIGEST};
+use crate::sha256::{HmacSha256, Sha256};
+use kernel::capabilities::ProcessManagementCapability;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::gpio;
+use kernel::hil::uart;
+use kernel::introspection::KernelInfo;
+use kernel::Kernel;
+use kernel::ReturnCode;
+
+pub static mut TX_BUF: [u8; 256] = [0; 256];
+pub static mut RX_BUF: [u8; 256] = [0; 256];
+
+/// The length field of the frames.
+const HEADER_LEN: usize = 2;
+/// The length field and the status of a response.
+const RESPONSE_HEADER_LEN: usize = 3;
+
+const PING: u8 = 0x00;
+const KAT: u8 = 0x01;
+const METRICS: u8 = 0x02;
+const HASH: u8 = 0x03;
+const PIN: u8 = 0x04;
+/// The number of the first function of the board.
+const BOARD_FUNCTIONS: u8 = 0x80;
+
+/// A diagnostic function of the board, called by the host.
+pub trait Function {
+    /// Run the function with `args`, and write its result into `result`.
+    /// Returns the length of the result.
+    fn call(&self, args: &[u8], result: &mut [u8]) -> Result<usize, ReturnCode>;
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// Waiting for the length of a request.
+    Header,
+    /// Waiting for the body of a request.
+    Body,
+    /// Dropping the rest of a request that does not fit.
+    Discard(usize),
+}
+
+pub struct HostRpc<'a, C: ProcessManagementCapability> {
+    uart: &'a dyn uart::UartData<'a>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    state: Cell<State>,
+    kernel: &'static Kernel,
+    capability: C,
+    regions: &'a [&'a [u8]],
+    pins: &'a [&'a dyn gpio::Pin],
+    functions: OptionalCell<&'a [&'a dyn Function]>,
+    engine: OptionalCell<&'a dyn EngineSelfTest>,
+}
+
+impl<'a, C: ProcessManagementCapability> HostRpc<'a, C> {
+    pub fn new(
+        uart: &'a dyn uart::UartData<'a>,
+        tx_buffer: &'static mut [u8],
+        rx_buffer: &'static mut [u8],
+        kernel: &'static Kernel,
+        capability: C,
+        regions: &'a [&'a [u8]],
+        pins: &'a [&'a dyn gpio::Pin],
+    ) -> HostRpc<'a, C> {
+        HostRpc {
+            uart: uart,
+            tx_buffer: TakeCell::new(tx_buffer),
+            rx_buffer: TakeCell::new(rx_buffer),
+            state: Cell::new(State::Header),
+            kernel: kernel,
+            capability: capability,
+            regions: regions,
+            pins: pins,
+            functions: OptionalCell::empty(),
+            engine: OptionalCell::empty(),
+        }
+    }
+
+    /// Make `functions` callable as functions `0x80` and up.
+    pub fn set_functions(&self, functions: &'a [&'a dyn Function]) {
+        self.functions.set(functions);
+    }
+
+    /// Report the known-answer tests of the hash engine under `engine` with
+    /// those of the software hash.
+    pub fn set_engine_self_test(&self, engine: &'a dyn EngineSelfTest) {
+        self.engine.set(engine);
+    }
+
+    /// Start receiving requests.
+    pub fn start(&self) -> ReturnCode {
+        self.receive(State::Header, HEADER_LEN);
+        ReturnCode::SUCCESS
+    }
+
+    fn receive(&self, state: State, len: usize) {
+        self.state.set(state);
+        self.rx_buffer.take().map(|buffer| {
+            let len = cmp::min(len, buffer.len());
+            if let (_, Some(buffer)) = self.uart.receive_buffer(buffer, len) {
+                self.rx_buffer.replace(buffer);
+            }
+        });
+    }
+
+    /// Send a response with `status` and the first `len` bytes of the
+    /// result, already in the transmit buffer.
+    fn respond(&self, status: ReturnCode, len: usize) {
+        self.tx_buffer.take().map(|buffer| {
+            let frame_len = len + RESPONSE_HEADER_LEN - HEADER_LEN;
+            buffer[0] = frame_len as u8;
+            buffer[1] = (frame_len >> 8) as u8;
+            buffer[2] = isize::from(status) as u8;
+            if let (_, Some(buffer)) = self.uart.transmit_buffer(buffer, len + RESPONSE_HEADER_LEN)
+            {
+                // The response is lost, wait for the next request
+                self.tx_buffer.replace(buffer);
+                self.receive(State::Header, HEADER_LEN);
+            }
+        });
+    }
+
+    /// Run the function of `request`, writing its result into `result`.
+    fn call(&self, request: &[u8], result: &mut [u8]) -> Result<usize, ReturnCode> {
+        let (function, args) = request.split_first().ok_or(ReturnCode::EINVAL)?;
+        match *function {
+            PING => {
+                let len = args.len();
+                result
+                    .get_mut(..len)
+                    .ok_or(ReturnCode::ESIZE)?
+                    .copy_from_slice(args);
+                Ok(len)
+            }
+            KAT => self.known_answer_tests(result),
+            METRICS => self.metrics(args, result),
+            HASH => self.hash(args, result),
+            PIN => self.pin(args, result),
+            function if function >= BOARD_FUNCTIONS => self
+                .functions
+                .map_or(None, |functions| {
+                    functions.get((function - BOARD_FUNCTIONS) as usize)
+                })
+                .ok_or(ReturnCode::ENOSUPPORT)?
+                .call(args, result),
+            _ => Err(ReturnCode::ENOSUPPORT),
+        }
+    }
+
+    fn known_answer_tests(&self, result: &mut [u8]) -> Result<usize, ReturnCode> {
+        if result.len() < 3 {
+            return Err(ReturnCode::ESIZE);
+        }
+        let mut sha = Sha256::new();
+        sha.update(KAT_SHA256_DATA);
+        result[0] = (sha.finish() != KAT_SHA256_DIGEST) as u8;
+        let mut hmac = HmacSha256::new(KAT_HMAC_KEY);
+        hmac.update(KAT_HMAC_DATA);
+        result[1] = (hmac.finish() != KAT_HMAC_DIGEST) as u8;
+        Ok(self.engine.map_or(2, |engine| {
+            result[2] = engine.engine_self_test() as u8;
+            3
+        }))
+    }
+
+    fn metrics(&self, name: &[u8], result: &mut [u8]) -> Result<usize, ReturnCode> {
+        let info = KernelInfo::new(self.kernel);
+        let mut ret = Err(ReturnCode::EINVAL);
+        info.counter_each(&self.capability, |counter| {
+            if counter.name().as_bytes() != name {
+                return;
+            }
+            let mut len = 0;
+            for index in 0..counter.len() {
+                let entry = match result.get_mut(len..len + 8) {
+                    Some(entry) => entry,
+                    None => {
+                        ret = Err(ReturnCode::ESIZE);
+                        return;
+                    }
+                };
+                let key = counter.key(index).unwrap_or(index) as u32;
+                let value = counter.get_index(index).unwrap_or(0);
+                entry[..4].copy_from_slice(&key.to_le_bytes());
+                entry[4..].copy_from_slice(&value.to_le_bytes());
+                len += 8;
+            }
+            ret = Ok(len);
+        });
+        ret
+    }
+
+    fn hash(&self, args: &[u8], result: &mut [u8]) -> Result<usize, ReturnCode> {
+        if args.len() != 9 {
+            return Err(ReturnCode::EINVAL);
+        }
+        let region = self
+            .regions
+            .get(args[0] as usize)
+            .ok_or(ReturnCode::EINVAL)?;
+        let offset = read_u32(&args[1..5]) as usize;
+        let len = read_u32(&args[5..9]) as usize;
+        let data = offset
+            .checked_add(len)
+            .and_then(|end| region.get(offset..end))
+            .ok_or(ReturnCode::EINVAL)?;
+
+        let mut sha = Sha256::new();
+        sha.update(data);
+        let digest = sha.finish();
+        result
+            .get_mut(..digest.len())
+            .ok_or(ReturnCode::ESIZE)?
+            .copy_from_slice(&digest);
+        Ok(digest.len())
+    }
+
+    fn pin(&self, args: &[u8], result: &mut [u8]) -> Result<usize, ReturnCode> {
+        if args.len() != 2 || result.is_empty() {
+            return Err(ReturnCode::EINVAL);
+        }
+        let pin = self.pins.get(args[0] as usize).ok_or(ReturnCode::EINVAL)?;
+        match args[1] {
+            0 => pin.clear(),
+            1 => pin.set(),
+            2 => {
+                pin.toggle();
+            }
+            3 => {}
+            _ => return Err(ReturnCode::EINVAL),
+        }
+        result[0] = pin.read() as u8;
+        Ok(1)
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut word = [0; 4];
+    word.copy_from_slice(bytes);
+    u32::from_le_bytes(word)
+}
+
+impl<'a, C: ProcessManagementCapability> uart::TransmitClient for HostRpc<'a, C> {
+    fn transmitted_buffer(&self, buffer: &'static mut [u8], _tx_len: usize, _rcode: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+        self.receive(State::Header, HEADER_LEN);
+    }
+}
+
+impl<'a, C: ProcessManagementCapability> uart::ReceiveClient for HostRpc<'a, C> {
+    fn received_buffer(
+        &self,
+        buffer: &'static mut [u8],
+        rx_len: usize,
+        _rcode: ReturnCode,
+        error: uart::Error,
+    ) {
+        let capacity = buffer.len();
+        if error != uart::Error::None {
+            // The framing is lost, start over with the next request
+            self.rx_buffer.replace(buffer);
+            self.receive(State::Header, HEADER_LEN);
+            return;
+        }
+
+        match self.state.get() {
+            State::Header => {
+                let len = buffer[0] as usize | (buffer[1] as usize) << 8;
+                self.rx_buffer.replace(buffer);
+                if len == 0 {
+                    self.respond(ReturnCode::EINVAL, 0);
+                } else if len > capacity {
+                    self.receive(State::Discard(len), len);
+                } else {
+                    self.receive(State::Body, len);
+                }
+            }
+            State::Body => {
+                let mut ret = Err(ReturnCode::ENOMEM);
+                self.tx_buffer.map(|tx| {
+                    ret = self.call(&buffer[..rx_len], &mut tx[RESPONSE_HEADER_LEN..]);
+                });
+                self.rx_buffer.replace(buffer);
+                match ret {
+                    Ok(len) => self.respond(ReturnCode::SUCCESS, len),
+                    Err(e) => self.respond(e, 0),
+                }
+            }
+            State::Discard(remaining) => {
+                self.rx_buffer.replace(buffer);
+                let remaining = remaining.saturating_sub(rx_len);
+                if remaining > 0 {
+                    self.receive(State::Discard(remaining), remaining);
+                } else {
+                    self.respond(ReturnCode::ESIZE, 0);
+                }
+            }
+        }
+    }
+}
@@ -78,6 +78,7 @@ use crate::net::stream::SResult;
 use crate::net::stream::{encode_bytes, encode_u32, encode_u8};
 use core::cell::Cell;
 use kernel::common::cells::{MapCell, OptionalCell};
+use kernel::hil::crypto::CryptoError;
 use kernel::hil::radio;
 use kernel::hil::symmetric_encryption::{CCMClient, AES128CCM};
 use kernel::ReturnCode;
@@ -489,12 +490,12 @@ impl<'a, M: Mac, A: AES128CCM<'a>> Framer<'a, M, A> {
                                 let (a_off, m_off) =
                                     (radio::PSDU_OFFSET, radio::PSDU_OFFSET + m_off);
 
-                                if self.aes_ccm.set_key(&key) != ReturnCode::SUCCESS
-                                    || self.aes_ccm.set_nonce(&nonce) != ReturnCode::SUCCESS
+                                if self.aes_ccm.set_key(&key).is_err()
+                                    || self.aes_ccm.set_nonce(&nonce).is_err()
                                 {
                                     (TxState::Idle, (ReturnCode::FAIL, Some(buf)))
                                 } else {
-                                    let (res, opt_buf) = self.aes_ccm.crypt(
+                                    let res = self.aes_ccm.crypt(
                                         buf,
                                         a_off,
                                         m_off,
@@ -504,20 +505,14 @@ impl<'a, M: Mac, A: AES128CCM<'a>> Framer<'a, M, A> {
                                         true,
                                     );
                                     match res {
-                                        ReturnCode::SUCCESS => {
-                                            (TxState::Encrypting(info), (res, None))
+                                        Ok(()) => {
+                                            (TxState::Encrypting(info), (ReturnCode::SUCCESS, None))
                                         }
-                                        ReturnCode::EBUSY => {
-                                            let buf = match opt_buf {
-                                                Some(buf) => buf,
-                                                None => panic!("aes_ccm did not return the buffer"),
-                                            };
-                                            (
-                                                TxState::ReadyToEncrypt(info, buf),
-                                                (ReturnCode::SUCCESS, None),
-                                            )
-                                        }
-                                        _ => (TxState::Idle, (res, opt_buf)),
+                                        Err((CryptoError::EngineBusy, buf)) => (
+                                            TxState::ReadyToEncrypt(info, buf),
+                                            (ReturnCode::SUCCESS, None),
+                                        ),
+                                        Err((e, buf)) => (TxState::Idle, (e.into(), Some(buf))),
                                     }
                                 }
                             }
@@ -571,12 +566,12 @@ impl<'a, M: Mac, A: AES128CCM<'a>> Framer<'a, M, A> {
                             let (m_off, m_len) = info.ccm_encrypt_ranges();
                             let (a_off, m_off) = (radio::PSDU_OFFSET, radio::PSDU_OFFSET + m_off);
 
-                            if self.aes_ccm.set_key(&key) != ReturnCode::SUCCESS
-                                || self.aes_ccm.set_nonce(&nonce) != ReturnCode::SUCCESS
+                            if self.aes_ccm.set_key(&key).is_err()
+                                || self.aes_ccm.set_nonce(&nonce).is_err()
                             {
                                 (RxState::Idle, Some(buf))
                             } else {
-                                let (res, opt_buf) = self.aes_ccm.crypt(
+                                let res = self.aes_ccm.crypt(
                                     buf,
                                     a_off,
                                     m_off,
@@ -586,15 +581,11 @@ impl<'a, M: Mac, A: AES128CCM<'a>> Framer<'a, M, A> {
                                     true,
                                 );
                                 match res {
-                                    ReturnCode::SUCCESS => (RxState::Decrypting(info), None),
-                                    ReturnCode::EBUSY => {
-                                        let buf = match opt_buf {
-                                            Some(buf) => buf,
-                                            None => panic!("aes_ccm did not return the buffer"),
-                                        };
+                                    Ok(()) => (RxState::Decrypting(info), None),
+                                    Err((CryptoError::EngineBusy, buf)) => {
                                         (RxState::ReadyToDecrypt(info, buf), None)
                                     }
-                                    _ => (RxState::Idle, opt_buf),
+                                    Err((_, buf)) => (RxState::Idle, Some(buf)),
                                 }
                             }
                         }
@@ -840,7 +831,7 @@ impl<'a, M: Mac, A: AES128CCM<'a>> radio::ConfigClient for Framer<'a, M, A> {
 }
 
 impl<'a, M: Mac, A: AES128CCM<'a>> CCMClient for Framer<'a, M, A> {
-    fn crypt_done(&self, buf: &'static mut [u8], res: ReturnCode, tag_is_valid: bool) {
+    fn crypt_done(&self, buf: &'static mut [u8], res: Result<(), CryptoError>, tag_is_valid: bool) {
         let mut tx_waiting = false;
         let mut rx_waiting = false;
 
@@ -848,9 +839,9 @@ impl<'a, M: Mac, A: AES128CCM<'a>> CCMClient for Framer<'a, M, A> {
         let opt_buf = if let Some(state) = self.tx_state.take() {
             match state {
                 TxState::Encrypting(info) => {
-                    let (rval, opt_buf) = if res != ReturnCode::SUCCESS {
+                    let (rval, opt_buf) = if let Err(e) = res {
                         self.tx_state.replace(TxState::Idle);
-                        (res, Some(buf))
+                        (e.into(), Some(buf))
                     } else {
                         self.tx_state.replace(TxState::ReadyToTransmit(info, buf));
                         self.step_transmit_state()
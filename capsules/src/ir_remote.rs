@@ -0,0 +1,664 @@
+//! Infrared remote control, with the NEC and RC5 protocols.
+//!
+//! Frames are sent by switching a PWM carrier on and off: the carrier is
+//! 38kHz for NEC and 36kHz for RC5, with a third duty cycle, and the marks
+//! and spaces are timed with an alarm. Frames are received from a
+//! demodulating IR receiver, such as a TSOP38238, whose output is low while
+//! it sees the carrier. Each edge of the output is timestamped with the
+//! alarm counter, and a frame is decoded once the line has been idle for
+//! `FRAME_GAP`. Timestamps are taken when the interrupt is handled, so
+//! receiving is only reliable while the kernel is not busy for long; both
+//! protocols accept pulses within 30% of their nominal length.
+//!
+//! The capsule is half-duplex: edges are ignored while a frame is sent, and
+//! a frame cannot be sent while one is being received.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let virtual_pwm_ir = static_init!(
+//!     capsules::virtual_pwm::PwmPinUser<'static, nrf52::pwm::Pwm>,
+//!     capsules::virtual_pwm::PwmPinUser::new(mux_pwm, nrf5x::pinmux::Pinmux::new(30))
+//! );
+//! virtual_pwm_ir.add_to_mux();
+//!
+//! let virtual_alarm_ir = static_init!(
+//!     capsules::virtual_alarm::VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+//!     capsules::virtual_alarm::VirtualMuxAlarm::new(mux_alarm)
+//! );
+//!
+//! let ir_remote = static_init!(
+//!     capsules::ir_remote::IrRemote<
+//!         'static,
+//!         nrf5x::gpio::GPIOPin,
+//!         capsules::virtual_alarm::VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+//!     >,
+//!     capsules::ir_remote::IrRemote::new(
+//!         virtual_pwm_ir,
+//!         &nrf52840::gpio::PORT[Pin::P0_31],
+//!         virtual_alarm_ir,
+//!         board_kernel.create_grant(&memory_allocation_capability)
+//!     )
+//! );
+//! virtual_alarm_ir.set_client(ir_remote);
+//! nrf52840::gpio::PORT[Pin::P0_31].set_client(ir_remote);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{MapCell, OptionalCell};
+use kernel::hil;
+use kernel::hil::gpio;
+use kernel::hil::time::{self, Frequency};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::IrRemote as usize;
+
+/// The longest frame, a NEC frame: the leader, 32 bits and the stop mark.
+pub const MAX_PULSES: usize = 67;
+
+/// A silence longer than any space within a frame, which ends a frame.
+const FRAME_GAP: u32 = 10000;
+
+const NEC_CARRIER: usize = 38000;
+const NEC_LEADER_MARK: u32 = 9000;
+const NEC_LEADER_SPACE: u32 = 4500;
+const NEC_REPEAT_SPACE: u32 = 2250;
+const NEC_UNIT: u32 = 562;
+
+const RC5_CARRIER: usize = 36000;
+const RC5_HALF_BIT: u32 = 889;
+const RC5_BITS: usize = 14;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protocol {
+    Nec = 0,
+    Rc5 = 1,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub protocol: Protocol,
+    /// 8 bits, or 16 for extended NEC; 5 bits for RC5.
+    pub address: u16,
+    /// 8 bits for NEC; 7 bits for RC5, including the inverted second start
+    /// bit of extended RC5.
+    pub command: u8,
+    /// NEC: the frame is a repeat code, sent while a key is held down.
+    pub repeat: bool,
+    /// RC5: the toggle bit, which changes each time a key is pressed.
+    pub toggle: bool,
+}
+
+/// Whether `duration` is within 30% of `nominal`.
+fn matches(duration: u32, nominal: u32) -> bool {
+    duration * 10 >= nominal * 7 && duration * 10 <= nominal * 13
+}
+
+/// Encode `frame` as the lengths of its marks and spaces in microseconds,
+/// starting with a mark. Returns the number of pulses, or `None` if a field
+/// does not fit the protocol.
+pub fn encode(frame: &Frame, pulses: &mut [u16; MAX_PULSES]) -> Option<usize> {
+    match frame.protocol {
+        Protocol::Nec => {
+            if frame.repeat {
+                pulses[..3].copy_from_slice(&[
+                    NEC_LEADER_MARK as u16,
+                    NEC_REPEAT_SPACE as u16,
+                    NEC_UNIT as u16,
+                ]);
+                return Some(3);
+            }
+            let address = if frame.address > 0xff {
+                frame.address
+            } else {
+                frame.address | (!frame.address & 0xff) << 8
+            };
+            let data =
+                address as u32 | (frame.command as u32) << 16 | (!frame.command as u32) << 24;
+            pulses[0] = NEC_LEADER_MARK as u16;
+            pulses[1] = NEC_LEADER_SPACE as u16;
+            for i in 0..32 {
+                let space = if data & (1 << i) != 0 { 3 } else { 1 };
+                pulses[2 + 2 * i] = NEC_UNIT as u16;
+                pulses[3 + 2 * i] = (space * NEC_UNIT) as u16;
+            }
+            pulses[MAX_PULSES - 1] = NEC_UNIT as u16;
+            Some(MAX_PULSES)
+        }
+        Protocol::Rc5 => {
+            if frame.address > 0x1f || frame.command > 0x7f {
+                return None;
+            }
+            // Start bit, inverted command bit 6, toggle, address, command
+            let data = 1 << 13
+                | ((frame.command & 0x40 == 0) as u16) << 12
+                | (frame.toggle as u16) << 11
+                | frame.address << 6
+                | (frame.command & 0x3f) as u16;
+            // Manchester code: 1 is a space then a mark, 0 the opposite. The
+            // space of the first bit is the idle line.
+            let mut count = 0;
+            let mut mark = true;
+            let mut halves = 1;
+            for i in (0..RC5_BITS - 1).rev() {
+                let bit = data & (1 << i) != 0;
+                for &half in [!bit, bit].iter() {
+                    if half == mark {
+                        halves += 1;
+                    } else {
+                        pulses[count] = (halves * RC5_HALF_BIT) as u16;
+                        count += 1;
+                        mark = half;
+                        halves = 1;
+                    }
+                }
+            }
+            // A trailing space merges with the idle line
+            if mark {
+                pulses[count] = (halves * RC5_HALF_BIT) as u16;
+                count += 1;
+            }
+            Some(count)
+        }
+    }
+}
+
+fn decode_nec(pulses: &[u16]) -> Option<Frame> {
+    if pulses.len() == 3 {
+        if matches(pulses[0] as u32, NEC_LEADER_MARK)
+            && matches(pulses[1] as u32, NEC_REPEAT_SPACE)
+            && matches(pulses[2] as u32, NEC_UNIT)
+        {
+            return Some(Frame {
+                protocol: Protocol::Nec,
+                address: 0,
+                command: 0,
+                repeat: true,
+                toggle: false,
+            });
+        }
+        return None;
+    }
+    if pulses.len() != MAX_PULSES
+        || !matches(pulses[0] as u32, NEC_LEADER_MARK)
+        || !matches(pulses[1] as u32, NEC_LEADER_SPACE)
+        || !matches(pulses[MAX_PULSES - 1] as u32, NEC_UNIT)
+    {
+        return None;
+    }
+    let mut data: u32 = 0;
+    for i in 0..32 {
+        if !matches(pulses[2 + 2 * i] as u32, NEC_UNIT) {
+            return None;
+        }
+        let space = pulses[3 + 2 * i] as u32;
+        if matches(space, 3 * NEC_UNIT) {
+            data |= 1 << i;
+        } else if !matches(space, NEC_UNIT) {
+            return None;
+        }
+    }
+    let [address, address_inverted, command, command_inverted] = data.to_le_bytes();
+    if command != !command_inverted {
+        return None;
+    }
+    let address = if address == !address_inverted {
+        address as u16
+    } else {
+        (data & 0xffff) as u16
+    };
+    Some(Frame {
+        protocol: Protocol::Nec,
+        address,
+        command,
+        repeat: false,
+        toggle: false,
+    })
+}
+
+fn decode_rc5(pulses: &[u16]) -> Option<Frame> {
+    // The levels of the half bits, starting with the space of the first bit
+    let mut halves = [false; 2 * RC5_BITS];
+    let mut count = 1;
+    for (i, pulse) in pulses.iter().enumerate() {
+        let n = if matches(*pulse as u32, RC5_HALF_BIT) {
+            1
+        } else if matches(*pulse as u32, 2 * RC5_HALF_BIT) {
+            2
+        } else {
+            return None;
+        };
+        if count + n > halves.len() {
+            return None;
+        }
+        let mark = i % 2 == 0;
+        halves[count..count + n].iter_mut().for_each(|h| *h = mark);
+        count += n;
+    }
+
+    let mut data: u16 = 0;
+    for bit in 0..RC5_BITS {
+        match (halves[2 * bit], halves[2 * bit + 1]) {
+            (false, true) => data |= 1 << (RC5_BITS - 1 - bit),
+            (true, false) => {}
+            _ => return None,
+        }
+    }
+    let command_high = if data & (1 << 12) == 0 { 0x40 } else { 0 };
+    Some(Frame {
+        protocol: Protocol::Rc5,
+        address: (data >> 6) & 0x1f,
+        command: command_high | (data & 0x3f) as u8,
+        repeat: false,
+        toggle: data & (1 << 11) != 0,
+    })
+}
+
+/// Decode the lengths of the marks and spaces of a frame, in microseconds,
+/// starting with a mark.
+pub fn decode(pulses: &[u16]) -> Option<Frame> {
+    decode_nec(pulses).or_else(|| decode_rc5(pulses))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    Receiving,
+    Transmitting,
+}
+
+#[derive(Default)]
+pub struct App {
+    rx_callback: Option<Callback>,
+    tx_callback: Option<Callback>,
+    receiving: bool,
+}
+
+pub struct IrRemote<'a, P: gpio::InterruptPin, A: time::Alarm<'a>> {
+    pwm_pin: &'a dyn hil::pwm::PwmPin,
+    rx_pin: &'a P,
+    alarm: &'a A,
+    apps: Grant<App>,
+    state: Cell<State>,
+
+    /// The marks and spaces of the frame being sent or received.
+    pulses: MapCell<[u16; MAX_PULSES]>,
+    pulse_count: Cell<usize>,
+    /// Whether the frame received had more pulses than fit.
+    overflow: Cell<bool>,
+    /// The time of the last edge received, or the end of the last pulse
+    /// sent.
+    last_time: Cell<u32>,
+    /// The next pulse to send.
+    tx_index: Cell<usize>,
+    carrier: Cell<usize>,
+    tx_app: OptionalCell<AppId>,
+    /// The last NEC frame received, which repeat codes refer to.
+    last_nec: OptionalCell<Frame>,
+}
+
+impl<'a, P: gpio::InterruptPin, A: time::Alarm<'a>> IrRemote<'a, P, A> {
+    pub fn new(
+        pwm_pin: &'a dyn hil::pwm::PwmPin,
+        rx_pin: &'a P,
+        alarm: &'a A,
+        grant: Grant<App>,
+    ) -> IrRemote<'a, P, A> {
+        IrRemote {
+            pwm_pin: pwm_pin,
+            rx_pin: rx_pin,
+            alarm: alarm,
+            apps: grant,
+            state: Cell::new(State::Idle),
+            pulses: MapCell::new([0; MAX_PULSES]),
+            pulse_count: Cell::new(0),
+            overflow: Cell::new(false),
+            last_time: Cell::new(0),
+            tx_index: Cell::new(0),
+            carrier: Cell::new(0),
+            tx_app: OptionalCell::empty(),
+            last_nec: OptionalCell::empty(),
+        }
+    }
+
+    fn us_to_tics(us: u32) -> u32 {
+        (us as u64 * <A::Frequency>::frequency() as u64 / 1_000_000) as u32
+    }
+
+    fn tics_to_us(tics: u32) -> u32 {
+        (tics as u64 * 1_000_000 / <A::Frequency>::frequency() as u64) as u32
+    }
+
+    /// Listen to the receiver while any app wants frames.
+    fn update_receiving(&self) {
+        let receiving = Cell::new(false);
+        self.apps.each(|app| {
+            if app.receiving {
+                receiving.set(true);
+            }
+        });
+        if receiving.get() {
+            self.rx_pin.make_input();
+            self.rx_pin
+                .enable_interrupts(gpio::InterruptEdge::EitherEdge);
+        } else {
+            self.rx_pin.disable_interrupts();
+            if self.state.get() == State::Receiving {
+                self.state.set(State::Idle);
+                self.alarm.disable();
+            }
+        }
+    }
+
+    fn transmit(&self, frame: &Frame, appid: AppId) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        let count = match self.pulses.map(|pulses| encode(frame, pulses)) {
+            Some(Some(count)) => count,
+            Some(None) => return ReturnCode::EINVAL,
+            None => return ReturnCode::FAIL,
+        };
+        self.carrier.set(match frame.protocol {
+            Protocol::Nec => NEC_CARRIER,
+            Protocol::Rc5 => RC5_CARRIER,
+        });
+        self.pulse_count.set(count);
+        self.tx_index.set(0);
+        self.last_time.set(self.alarm.now());
+        self.state.set(State::Transmitting);
+        self.tx_app.set(appid);
+        self.next_pulse();
+        ReturnCode::SUCCESS
+    }
+
+    /// Start the next mark or space, or end the frame.
+    fn next_pulse(&self) {
+        let index = self.tx_index.get();
+        if index == self.pulse_count.get() {
+            self.pwm_pin.stop();
+            self.state.set(State::Idle);
+            self.tx_app.take().map(|appid| {
+                let _ = self.apps.enter(appid, |app, _| {
+                    app.tx_callback.map(|mut cb| cb.schedule(0, 0, 0));
+                });
+            });
+            return;
+        }
+
+        if index % 2 == 0 {
+            let duty_cycle = self.pwm_pin.get_maximum_duty_cycle() / 3;
+            self.pwm_pin.start(self.carrier.get(), duty_cycle);
+        } else {
+            self.pwm_pin.stop();
+        }
+        let length = self.pulses.map_or(0, |pulses| pulses[index]);
+        // Time each pulse from the end of the previous one, so that the
+        // delays in handling the alarm do not add up.
+        let end = self
+            .last_time
+            .get()
+            .wrapping_add(Self::us_to_tics(length as u32));
+        self.last_time.set(end);
+        self.tx_index.set(index + 1);
+        self.alarm.set_alarm(end);
+    }
+
+    fn frame_received(&self) {
+        self.state.set(State::Idle);
+        if self.overflow.get() {
+            return;
+        }
+        let count = self.pulse_count.get();
+        let frame = match self.pulses.map(|pulses| decode(&pulses[..count])) {
+            Some(Some(frame)) => frame,
+            _ => return,
+        };
+        let frame = match (frame.protocol, frame.repeat) {
+            (Protocol::Nec, false) => {
+                self.last_nec.set(frame);
+                frame
+            }
+            (Protocol::Nec, true) => match self.last_nec.map(|last| *last) {
+                Some(last) => Frame {
+                    repeat: true,
+                    ..last
+                },
+                None => return,
+            },
+            (Protocol::Rc5, _) => frame,
+        };
+
+        let flag = frame.repeat || frame.toggle;
+        self.apps.each(|app| {
+            if app.receiving {
+                app.rx_callback.map(|mut cb| {
+                    cb.schedule(
+                        frame.protocol as usize,
+                        frame.address as usize,
+                        frame.command as usize | (flag as usize) << 8,
+                    )
+                });
+            }
+        });
+    }
+}
+
+impl<'a, P: gpio::InterruptPin, A: time::Alarm<'a>> gpio::Client for IrRemote<'a, P, A> {
+    fn fired(&self) {
+        let now = self.alarm.now();
+        // The receiver output is low during a mark
+        let mark = !self.rx_pin.read();
+        match self.state.get() {
+            State::Transmitting => return,
+            State::Idle => {
+                if !mark {
+                    return;
+                }
+                self.state.set(State::Receiving);
+                self.pulse_count.set(0);
+                self.overflow.set(false);
+            }
+            State::Receiving => {
+                let length = Self::tics_to_us(now.wrapping_sub(self.last_time.get()));
+                let count = self.pulse_count.get();
+                if count < MAX_PULSES {
+                    self.pulses.map(|pulses| {
+                        pulses[count] = core::cmp::min(length, core::u16::MAX as u32) as u16
+                    });
+                    self.pulse_count.set(count + 1);
+                } else {
+                    self.overflow.set(true);
+                }
+            }
+        }
+        self.last_time.set(now);
+        self.alarm
+            .set_alarm(now.wrapping_add(Self::us_to_tics(FRAME_GAP)));
+    }
+}
+
+impl<'a, P: gpio::InterruptPin, A: time::Alarm<'a>> time::AlarmClient for IrRemote<'a, P, A> {
+    fn fired(&self) {
+        match self.state.get() {
+            State::Idle => {}
+            State::Receiving => self.frame_received(),
+            State::Transmitting => self.next_pulse(),
+        }
+    }
+}
+
+impl<'a, P: gpio::InterruptPin, A: time::Alarm<'a>> Driver for IrRemote<'a, P, A> {
+    /// Setup callbacks.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: A frame was received. The callback signature is
+    ///        `fn(protocol: u32, address: u32, command: u32)`, where the
+    ///        protocol is `0` for NEC and `1` for RC5. Bit 8 of `command` is
+    ///        the repeat flag for NEC and the toggle bit for RC5.
+    /// - `1`: The frame passed to command `3` was sent.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        self.apps
+            .enter(app_id, |app, _| {
+                match subscribe_num {
+                    0 => app.rx_callback = callback,
+                    1 => app.tx_callback = callback,
+                    _ => return ReturnCode::ENOSUPPORT,
+                }
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    /// Command interface.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Return SUCCESS if this driver is included on the platform.
+    /// - `1`: Start receiving frames.
+    /// - `2`: Stop receiving frames.
+    /// - `3`: Send a frame. `arg1` is the protocol, `0` for NEC and `1` for
+    ///   RC5. `arg2` holds the address in bits 0 to 15, the command in bits
+    ///   16 to 23, and the NEC repeat flag or the RC5 toggle bit in bit 24.
+    ///   Returns `EBUSY` while a frame is sent or received, and `EINVAL` if
+    ///   the address or the command is too large for the protocol.
+    fn command(&self, command_num: usize, arg1: usize, arg2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+
+            1 | 2 => {
+                let res = self
+                    .apps
+                    .enter(appid, |app, _| {
+                        app.receiving = command_num == 1;
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or_else(|err| err.into());
+                self.update_receiving();
+                res
+            }
+
+            3 => {
+                let protocol = match arg1 {
+                    0 => Protocol::Nec,
+                    1 => Protocol::Rc5,
+                    _ => return ReturnCode::EINVAL,
+                };
+                let flag = arg2 & (1 << 24) != 0;
+                let frame = Frame {
+                    protocol,
+                    address: arg2 as u16,
+                    command: (arg2 >> 16) as u8,
+                    repeat: protocol == Protocol::Nec && flag,
+                    toggle: protocol == Protocol::Rc5 && flag,
+                };
+                self.transmit(&frame, appid)
+            }
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nec(address: u16, command: u8) -> Frame {
+        Frame {
+            protocol: Protocol::Nec,
+            address,
+            command,
+            repeat: false,
+            toggle: false,
+        }
+    }
+
+    fn rc5(address: u16, command: u8, toggle: bool) -> Frame {
+        Frame {
+            protocol: Protocol::Rc5,
+            address,
+            command,
+            repeat: false,
+            toggle,
+        }
+    }
+
+    fn round_trip(frame: Frame) {
+        let mut pulses = [0; MAX_PULSES];
+        let count = encode(&frame, &mut pulses).unwrap();
+        assert_eq!(decode(&pulses[..count]), Some(frame));
+
+        // Receivers stretch marks and shorten spaces
+        for (i, pulse) in pulses[..count].iter_mut().enumerate() {
+            if i % 2 == 0 {
+                *pulse += *pulse / 5;
+            } else {
+                *pulse -= *pulse / 5;
+            }
+        }
+        assert_eq!(decode(&pulses[..count]), Some(frame));
+    }
+
+    #[test]
+    fn nec_frames() {
+        round_trip(nec(0x00, 0x45));
+        round_trip(nec(0x04, 0x08));
+        round_trip(nec(0x1240, 0x12));
+        round_trip(Frame {
+            repeat: true,
+            ..nec(0, 0)
+        });
+    }
+
+    #[test]
+    fn nec_layout() {
+        let mut pulses = [0; MAX_PULSES];
+        assert_eq!(encode(&nec(0x01, 0x80), &mut pulses), Some(MAX_PULSES));
+        assert_eq!(pulses[..2], [9000, 4500]);
+        // Address bit 0 is a 1, its inverse bit 0 a 0
+        assert_eq!(pulses[2..4], [562, 1686]);
+        assert_eq!(pulses[18..20], [562, 562]);
+        assert_eq!(pulses[MAX_PULSES - 1], 562);
+
+        // A corrupted command is rejected
+        pulses[35] = 1686;
+        assert_eq!(decode(&pulses), None);
+    }
+
+    #[test]
+    fn rc5_frames() {
+        round_trip(rc5(0x00, 0x00, false));
+        round_trip(rc5(0x05, 0x35, true));
+        round_trip(rc5(0x1f, 0x3f, false));
+        round_trip(rc5(0x14, 0x7f, true));
+
+        let mut pulses = [0; MAX_PULSES];
+        assert_eq!(encode(&rc5(0x20, 0, false), &mut pulses), None);
+        assert_eq!(encode(&rc5(0, 0x80, false), &mut pulses), None);
+    }
+
+    #[test]
+    fn rc5_layout() {
+        // Address 0, command 1, no toggle: 1 1 0 00000 000001
+        let mut pulses = [0; MAX_PULSES];
+        let count = encode(&rc5(0, 1, false), &mut pulses).unwrap();
+        assert_eq!(
+            pulses[..count],
+            [
+                889, 889, 1778, 889, 889, 889, 889, 889, 889, 889, 889, 889, 889, 889, 889, 889,
+                889, 889, 889, 889, 889, 889, 889, 1778, 889
+            ]
+        );
+        assert!(decode(&[889, 889, 3000]).is_none());
+    }
+}
@@ -0,0 +1,128 @@
+//! Provides userspace with access to the error journal (see
+//! `kernel::journal`).
+//!
+//! Userspace Interface
+//! -------------------
+//!
+//! ### `allow` System Call
+//!
+//! * `0`: buffer of at least 16 bytes which receives a record with command
+//!   `2`.
+//!
+//! ### `command` System Call
+//!
+//! * `0`: check whether the driver exists
+//! * `1`: returns the number of records in the journal
+//! * `2`: copies record `arg1` into the allowed buffer, the oldest record
+//!   being record `0`. The record has the same layout as in
+//!   `capsules::flash_journal`: sequence number, timestamp in milliseconds
+//!   since boot, event, `0xffff` and argument, all little-endian.
+//! * `3`: removes all records from the journal
+//!
+//! Command `2` returns `EINVAL` for a record that does not exist, `ESIZE` if
+//! the buffer is too small and `EBUSY` while the journal is being written to
+//! storage.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let journal_driver = static_init!(
+//!     capsules::journal::JournalDriver<'static>,
+//!     capsules::journal::JournalDriver::new(journal, board_kernel.create_grant(&grant_cap))
+//! );
+//! ```
+
+use kernel::journal::Journal;
+use kernel::{AppId, AppSlice, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Journal as usize;
+
+#[derive(Default)]
+pub struct App {
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct JournalDriver<'a> {
+    journal: &'a dyn Journal,
+    apps: Grant<App>,
+}
+
+impl<'a> JournalDriver<'a> {
+    pub fn new(journal: &'a dyn Journal, grant: Grant<App>) -> JournalDriver<'a> {
+        JournalDriver {
+            journal: journal,
+            apps: grant,
+        }
+    }
+
+    fn copy_record(&self, index: usize, appid: AppId) -> ReturnCode {
+        if index >= self.journal.len() {
+            return ReturnCode::EINVAL;
+        }
+        let record = match self.journal.get(index) {
+            None => return ReturnCode::EBUSY,
+            Some(record) => record,
+        };
+
+        self.apps
+            .enter(appid, |app, _| {
+                app.buffer.as_mut().map_or(ReturnCode::ENOMEM, |buffer| {
+                    if buffer.len() < 16 {
+                        return ReturnCode::ESIZE;
+                    }
+                    let buffer = buffer.as_mut();
+                    buffer[0..4].copy_from_slice(&record.sequence.to_le_bytes());
+                    buffer[4..8].copy_from_slice(&record.timestamp_ms.to_le_bytes());
+                    buffer[8..10].copy_from_slice(&record.event.to_le_bytes());
+                    buffer[10..12].copy_from_slice(&[0xff, 0xff]);
+                    buffer[12..16].copy_from_slice(&record.arg.to_le_bytes());
+                    ReturnCode::SUCCESS
+                })
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+}
+
+impl Driver for JournalDriver<'_> {
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            // Buffer for records
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, arg1: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            // check whether the driver exists
+            0 => ReturnCode::SUCCESS,
+
+            // number of records
+            1 => ReturnCode::SuccessWithValue {
+                value: self.journal.len(),
+            },
+
+            // read a record
+            2 => self.copy_record(arg1, appid),
+
+            // clear the journal
+            3 => self.journal.clear(),
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
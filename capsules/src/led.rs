@@ -57,11 +57,11 @@ pub const DRIVER_NUM: usize = driver::NUM::Led as usize;
 
 /// Holds the array of GPIO pins attached to the LEDs and implements a `Driver`
 /// interface to control them.
-pub struct LED<'a, P: gpio::Pin> {
+pub struct LED<'a, P: gpio::Pin + ?Sized> {
     pins_init: &'a [(&'a P, gpio::ActivationMode)],
 }
 
-impl<'a, P: gpio::Pin> LED<'a, P> {
+impl<'a, P: gpio::Pin + ?Sized> LED<'a, P> {
     pub fn new(pins_init: &'a [(&'a P, gpio::ActivationMode)]) -> Self {
         // Make all pins output and off
         for &(pin, mode) in pins_init.as_ref().iter() {
@@ -75,7 +75,7 @@ impl<'a, P: gpio::Pin> LED<'a, P> {
     }
 }
 
-impl<P: gpio::Pin> Driver for LED<'_, P> {
+impl<P: gpio::Pin + ?Sized> Driver for LED<'_, P> {
     /// Control the LEDs.
     ///
     /// ### `command_num`
@@ -8,31 +8,50 @@ pub mod test;
 pub mod net;
 
 pub mod adc;
+pub mod adc_monitor;
+pub mod aes;
 pub mod aes_ccm;
+pub mod aes_cmac;
 pub mod alarm;
 pub mod ambient_light;
 pub mod analog_comparator;
 pub mod analog_sensor;
 pub mod app_flash_driver;
+pub mod battery;
 pub mod ble_advertising_driver;
+pub mod boot_mode;
+pub mod bq24075;
+pub mod bus_capture;
 pub mod button;
 pub mod buzzer_driver;
 pub mod console;
+pub mod counter_store;
 pub mod crc;
+pub mod crypto_registry;
 pub mod dac;
 pub mod debug_process_restart;
+pub mod digest_chain;
 pub mod driver;
+pub mod ds18b20;
+pub mod entropy_pool;
+pub mod flash_digest;
+pub mod flash_journal;
 pub mod fm25cl;
 pub mod fxos8700cq;
 pub mod gpio;
 pub mod gpio_async;
 pub mod hd44780;
+pub mod hkdf;
 pub mod hmac;
+pub mod hmac_challenge;
+pub mod host_rpc;
 pub mod humidity;
 pub mod i2c_master;
 pub mod i2c_master_slave_driver;
 pub mod ieee802154;
+pub mod ir_remote;
 pub mod isl29035;
+pub mod journal;
 pub mod l3gd20;
 pub mod led;
 pub mod log;
@@ -40,14 +59,21 @@ pub mod low_level_debug;
 pub mod lps25hb;
 pub mod lsm303dlhc;
 pub mod ltc294x;
+pub mod max17048;
 pub mod max17205;
 pub mod mcp230xx;
+pub mod measurement;
+pub mod metrics;
 pub mod mx25r6435f;
 pub mod ninedof;
+pub mod nonce;
+pub mod nonvolatile_region;
 pub mod nonvolatile_storage_driver;
 pub mod nonvolatile_to_pages;
 pub mod nrf51822_serialization;
+pub mod one_wire;
 pub mod panic_button;
+pub mod pbkdf2;
 pub mod pca9544a;
 pub mod process_console;
 pub mod rf233;
@@ -55,17 +81,26 @@ pub mod rf233_const;
 pub mod rng;
 pub mod sdcard;
 pub mod segger_rtt;
+pub mod sha256;
+pub mod sha512;
 pub mod si7021;
+pub mod software_aes;
+pub mod software_ghash;
 pub mod spi;
+pub mod system_events;
 pub mod temperature;
 pub mod tmp006;
 pub mod tsl2561;
+pub mod uptime;
 pub mod usb;
+pub mod virtual_aes_block;
 pub mod virtual_alarm;
 pub mod virtual_digest;
 pub mod virtual_flash;
+pub mod virtual_gpio_async;
 pub mod virtual_hmac;
 pub mod virtual_i2c;
 pub mod virtual_pwm;
+pub mod virtual_rng;
 pub mod virtual_spi;
 pub mod virtual_uart;
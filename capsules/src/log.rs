@@ -76,6 +76,7 @@ use kernel::common::dynamic_deferred_call::{
 };
 use kernel::hil::flash::{self, Flash};
 use kernel::hil::log::{LogRead, LogReadClient, LogWrite, LogWriteClient};
+use kernel::system_events;
 use kernel::ReturnCode;
 
 /// Globally declare entry ID type.
@@ -89,6 +90,10 @@ pub const ENTRY_HEADER_SIZE: usize = size_of::<usize>();
 /// Byte used to pad the end of a page.
 const PAD_BYTE: u8 = 0xFF;
 
+/// Percentage of a non-circular log in use at which processes are notified
+/// with a `StorageNearlyFull` system event.
+const NEARLY_FULL_PERCENT: usize = 90;
+
 /// Log state keeps track of any in-progress asynchronous operations.
 #[derive(Clone, Copy, PartialEq)]
 enum State {
@@ -429,9 +434,16 @@ impl<'a, F: Flash + 'static> Log<'a, F> {
         }
 
         // Increment append offset by number of bytes appended.
+        let prev_percent = append_entry_id * 100 / self.volume.len();
         let append_entry_id = append_entry_id + length + ENTRY_HEADER_SIZE;
         self.append_entry_id.set(append_entry_id);
 
+        // Warn once when a non-circular log is about to run out of space.
+        let percent = append_entry_id * 100 / self.volume.len();
+        if !self.circular && prev_percent < NEARLY_FULL_PERCENT && percent >= NEARLY_FULL_PERCENT {
+            system_events::notify(system_events::Event::StorageNearlyFull, percent);
+        }
+
         // Replace pagebuffer and callback client.
         self.pagebuffer.replace(pagebuffer);
         self.buffer.replace(buffer);
@@ -3,6 +3,7 @@
 
 mod fmt;
 
+use crate::driver;
 use core::cell::Cell;
 use kernel::hil::uart::{Transmit, TransmitClient};
 use kernel::{AppId, Grant, ReturnCode};
@@ -10,7 +11,7 @@ use kernel::{AppId, Grant, ReturnCode};
 // LowLevelDebug requires a &mut [u8] buffer of length at least BUF_LEN.
 pub use fmt::BUF_LEN;
 
-pub const DRIVER_NUM: usize = 0x8;
+pub const DRIVER_NUM: usize = driver::NUM::LowLevelDebug as usize;
 
 pub struct LowLevelDebug<'u, U: Transmit<'u>> {
     buffer: Cell<Option<&'static mut [u8]>>,
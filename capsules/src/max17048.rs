@@ -0,0 +1,137 @@
+//! Driver for the Maxim MAX17048 fuel gauge.
+//!
+//! <https://www.maximintegrated.com/en/products/power/battery-management/MAX17048.html>
+//!
+//! > The MAX17048/MAX17049 ICs are tiny, micropower current fuel gauges for
+//! > lithium-ion (Li+) batteries in handheld and portable equipment. The
+//! > MAX17048 operates with a single lithium cell and the MAX17049 with two
+//! > lithium cells in series.
+//!
+//! The gauge runs the ModelGauge algorithm on its own, so this driver only
+//! reads the `VCELL` and `SOC` registers. It implements
+//! `hil::power::BatteryGauge` and is typically used with the `battery`
+//! capsule.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let max17048_i2c = static_init!(
+//!     capsules::virtual_i2c::I2CDevice,
+//!     capsules::virtual_i2c::I2CDevice::new(i2c_mux, capsules::max17048::I2C_ADDRESS));
+//! let max17048 = static_init!(
+//!     capsules::max17048::MAX17048<'static>,
+//!     capsules::max17048::MAX17048::new(max17048_i2c, &mut capsules::max17048::BUFFER));
+//! max17048_i2c.set_client(max17048);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::i2c;
+use kernel::hil::power;
+use kernel::ReturnCode;
+
+/// Fixed I2C address of the MAX17048.
+pub const I2C_ADDRESS: u8 = 0x36;
+
+// Buffer to use for I2C messages
+pub static mut BUFFER: [u8; 2] = [0; 2];
+
+#[allow(dead_code)]
+enum Registers {
+    VCell = 0x02,
+    Soc = 0x04,
+    Mode = 0x06,
+    Version = 0x08,
+    HibRt = 0x0a,
+    Config = 0x0c,
+    VAlrt = 0x14,
+    CRate = 0x16,
+    VResetId = 0x18,
+    Status = 0x1a,
+    Command = 0xfe,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    ReadSoc,
+    ReadVCell,
+}
+
+pub struct MAX17048<'a> {
+    i2c: &'a dyn i2c::I2CDevice,
+    state: Cell<State>,
+    buffer: TakeCell<'static, [u8]>,
+    client: OptionalCell<&'static dyn power::BatteryGaugeClient>,
+}
+
+impl<'a> MAX17048<'a> {
+    pub fn new(i2c: &'a dyn i2c::I2CDevice, buffer: &'static mut [u8]) -> MAX17048<'a> {
+        MAX17048 {
+            i2c: i2c,
+            state: Cell::new(State::Idle),
+            buffer: TakeCell::new(buffer),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    fn read_register(&self, register: Registers, next: State) -> ReturnCode {
+        self.buffer.take().map_or(ReturnCode::EBUSY, |buffer| {
+            self.i2c.enable();
+
+            buffer[0] = register as u8;
+            self.i2c.write_read(buffer, 1, 2);
+            self.state.set(next);
+
+            ReturnCode::SUCCESS
+        })
+    }
+}
+
+impl i2c::I2CClient for MAX17048<'_> {
+    fn command_complete(&self, buffer: &'static mut [u8], error: i2c::Error) {
+        let raw = ((buffer[0] as usize) << 8) | (buffer[1] as usize);
+        let state = self.state.get();
+
+        self.buffer.replace(buffer);
+        self.i2c.disable();
+        self.state.set(State::Idle);
+
+        if error != i2c::Error::CommandComplete {
+            if state != State::Idle {
+                self.client
+                    .map(|client| client.reading_failed(ReturnCode::ENOACK));
+            }
+            return;
+        }
+
+        match state {
+            State::ReadSoc => {
+                // 1/256 % per bit
+                let soc = raw * 100 / 256;
+                self.client.map(|client| client.state_of_charge(soc));
+            }
+            State::ReadVCell => {
+                // 78.125 uV per bit
+                let voltage = raw * 5 / 64;
+                self.client.map(|client| client.voltage(voltage));
+            }
+            State::Idle => {}
+        }
+    }
+}
+
+impl power::BatteryGauge for MAX17048<'_> {
+    fn set_client(&self, client: &'static dyn power::BatteryGaugeClient) {
+        self.client.set(client);
+    }
+
+    fn read_state_of_charge(&self) -> ReturnCode {
+        self.read_register(Registers::Soc, State::ReadSoc)
+    }
+
+    fn read_voltage(&self) -> ReturnCode {
+        self.read_register(Registers::VCell, State::ReadVCell)
+    }
+}
@@ -449,13 +449,15 @@ impl hil::i2c::I2CClient for MCP230xx<'_> {
             State::ReadGpioRead(pin_number) => {
                 let pin_value = (buffer[0] >> pin_number) & 0x01;
 
-                self.client.map(|client| {
-                    client.done(pin_value as usize);
-                });
-
+                // Release the buffer first so that the client can issue a new
+                // command from the callback.
                 self.buffer.replace(buffer);
                 self.i2c.disable();
                 self.state.set(State::Idle);
+
+                self.client.map(|client| {
+                    client.done(pin_value as usize);
+                });
             }
             State::EnableInterruptSettings(pin_number) => {
                 // Rather than read the current interrupts and write those
@@ -473,6 +475,10 @@ impl hil::i2c::I2CClient for MCP230xx<'_> {
             State::ReadInterruptValues(bank_number) => {
                 let interrupt_flags = buffer[0];
                 let pins_status = buffer[2];
+                self.buffer.replace(buffer);
+                self.i2c.disable();
+                self.state.set(State::Idle);
+
                 // Check each bit to see if that pin triggered an interrupt.
                 for i in 0..8 {
                     // Calculate the actual pin number based on which bank we
@@ -506,18 +512,15 @@ impl hil::i2c::I2CClient for MCP230xx<'_> {
                         }
                     }
                 }
+            }
+            State::Done => {
                 self.buffer.replace(buffer);
                 self.i2c.disable();
                 self.state.set(State::Idle);
-            }
-            State::Done => {
+
                 self.client.map(|client| {
                     client.done(0);
                 });
-
-                self.buffer.replace(buffer);
-                self.i2c.disable();
-                self.state.set(State::Idle);
             }
             _ => {}
         }
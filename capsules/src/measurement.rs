@@ -0,0 +1,412 @@
+//! Measurement registers, with the semantics of the platform configuration
+//! registers (PCRs) of a TPM, on top of the digest HIL.
+//!
+//! Each register holds a SHA-256 digest and starts as zeros. A register can
+//! only be extended: extending it with some data sets it to
+//! SHA-256(register | data), so its value depends on every measurement
+//! recorded in it and on their order, and no sequence of extensions can
+//! bring it back to an earlier value. The registers are only cleared by a
+//! reset of the board.
+//!
+//! A quote is the digest of a nonce followed by the registers selected by a
+//! mask, in increasing order, which lets a verifier check a set of
+//! registers at once and ensures the answer is fresh. Quotes are not
+//! signed: a capsule that holds an attestation key signs them if the quote
+//! leaves the device.
+//!
+//! The kernel uses the registers through `extend()`, `quote()` and `read()`,
+//! and the completions go to the `Client`. Processes use them through the
+//! syscall driver. Operations run one at a time, whether they come from the
+//! kernel or from a process.
+//!
+//! The data and the nonce are copied into the working buffer given to
+//! `Measurements::new()`, followed by the registers they are hashed with:
+//! to extend, the buffer must hold 32 bytes and the data, and to quote, the
+//! nonce and 32 bytes per register selected.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let measurements = static_init!(
+//!     capsules::measurement::Measurements<'static, lowrisc::hmac::Hmac>,
+//!     capsules::measurement::Measurements::new(
+//!         &ibex::hmac::HMAC,
+//!         static_init!([[u8; 32]; 8], [[0; 32]; 8]),
+//!         static_init!([u8; 288], [0; 288]),
+//!         static_init!([u8; 32], [0; 32]),
+//!         board_kernel.create_grant(&memory_allocation_cap),
+//!     )
+//! );
+//! digest::Digest::set_client(&ibex::hmac::HMAC, measurements);
+//! ```
+
+use crate::driver;
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = driver::NUM::Measurement as usize;
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::leasable_buffer::LeasableBuffer;
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::digest;
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+pub const REGISTER_LEN: usize = 32;
+
+/// The value of a measurement register, a SHA-256 digest.
+pub type Register = [u8; REGISTER_LEN];
+
+pub trait Client {
+    /// The register `index`, passed to `extend()`, was extended, or the
+    /// engine failed and the register is unchanged.
+    fn extend_done(&self, index: usize, result: Result<(), CryptoError>);
+
+    /// The quote started by `quote()` was computed, or the engine failed.
+    fn quote_done(&self, result: Result<&Register, CryptoError>);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    Extend(usize),
+    Quote,
+}
+
+pub struct Measurements<'a, D: digest::Digest<'a, Register>> {
+    digest: &'a D,
+    client: OptionalCell<&'a dyn Client>,
+    state: Cell<State>,
+    registers: TakeCell<'static, [Register]>,
+
+    buffer: TakeCell<'static, [u8]>,
+    /// Number of bytes of the working buffer to hash.
+    len: Cell<usize>,
+    /// Number of bytes of the working buffer given to the engine.
+    offset: Cell<usize>,
+    digest_buffer: TakeCell<'static, Register>,
+
+    apps: Grant<App>,
+    /// The process that started the running operation, if any.
+    appid: OptionalCell<AppId>,
+}
+
+impl<'a, D: digest::Digest<'a, Register>> Measurements<'a, D> {
+    pub fn new(
+        digest: &'a D,
+        registers: &'static mut [Register],
+        buffer: &'static mut [u8],
+        digest_buffer: &'static mut Register,
+        grant: Grant<App>,
+    ) -> Measurements<'a, D> {
+        Measurements {
+            digest,
+            client: OptionalCell::empty(),
+            state: Cell::new(State::Idle),
+            registers: TakeCell::new(registers),
+            buffer: TakeCell::new(buffer),
+            len: Cell::new(0),
+            offset: Cell::new(0),
+            digest_buffer: TakeCell::new(digest_buffer),
+            apps: grant,
+            appid: OptionalCell::empty(),
+        }
+    }
+
+    pub fn set_client(&self, client: &'a dyn Client) {
+        self.client.set(client);
+    }
+
+    /// The number of registers.
+    pub fn count(&self) -> usize {
+        self.registers.map_or(0, |registers| registers.len())
+    }
+
+    /// The value of the register `index`, or `None` if there is no such
+    /// register.
+    pub fn read(&self, index: usize) -> Option<Register> {
+        self.registers
+            .map_or(None, |registers| registers.get(index).copied())
+    }
+
+    /// Extend the register `index` with `data`, which is copied before this
+    /// returns. `extend_done()` is called once the register is updated.
+    /// Returns `EngineBusy` if an operation is in progress, and
+    /// `InvalidArgument` if there is no such register or if `data` does not
+    /// fit in the working buffer.
+    pub fn extend(&self, index: usize, data: &[u8]) -> Result<(), CryptoError> {
+        if self.state.get() != State::Idle {
+            return Err(CryptoError::EngineBusy);
+        }
+        let register = self.read(index).ok_or(CryptoError::InvalidArgument)?;
+        let len = REGISTER_LEN + data.len();
+        self.buffer.map_or(Err(CryptoError::EngineBusy), |buffer| {
+            if len > buffer.len() {
+                return Err(CryptoError::InvalidArgument);
+            }
+            buffer[..REGISTER_LEN].copy_from_slice(&register);
+            buffer[REGISTER_LEN..len].copy_from_slice(data);
+            Ok(())
+        })?;
+        self.start(State::Extend(index), len)
+    }
+
+    /// Compute the quote of the registers whose bit is set in `mask`, with
+    /// `nonce`, which is copied before this returns. `quote_done()` is
+    /// called with the quote.
+    /// Returns `EngineBusy` if an operation is in progress, and
+    /// `InvalidArgument` if `mask` selects no register or a register that
+    /// does not exist, or if the nonce and the registers do not fit in the
+    /// working buffer.
+    pub fn quote(&self, mask: u32, nonce: &[u8]) -> Result<(), CryptoError> {
+        if self.state.get() != State::Idle {
+            return Err(CryptoError::EngineBusy);
+        }
+        let count = self.count();
+        if mask == 0 || (count < 32 && mask >> count != 0) {
+            return Err(CryptoError::InvalidArgument);
+        }
+        let len = nonce.len() + mask.count_ones() as usize * REGISTER_LEN;
+        self.buffer.map_or(Err(CryptoError::EngineBusy), |buffer| {
+            if len > buffer.len() {
+                return Err(CryptoError::InvalidArgument);
+            }
+            buffer[..nonce.len()].copy_from_slice(nonce);
+            let mut offset = nonce.len();
+            self.registers.map(|registers| {
+                for (i, register) in registers.iter().enumerate().take(32) {
+                    if mask & (1 << i) != 0 {
+                        buffer[offset..offset + REGISTER_LEN].copy_from_slice(register);
+                        offset += REGISTER_LEN;
+                    }
+                }
+            });
+            Ok(())
+        })?;
+        self.start(State::Quote, len)
+    }
+
+    /// Hash the first `len` bytes of the working buffer.
+    fn start(&self, state: State, len: usize) -> Result<(), CryptoError> {
+        self.len.set(len);
+        self.offset.set(0);
+        self.state.set(state);
+        if let Err(e) = self.add_next() {
+            self.digest.clear_data();
+            self.state.set(State::Idle);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Give the rest of the working buffer to the engine, which may take
+    /// only part of it.
+    fn add_next(&self) -> Result<(), CryptoError> {
+        let buffer = self.buffer.take().ok_or(CryptoError::EngineBusy)?;
+        let mut lease = LeasableBuffer::new(buffer);
+        lease.slice(self.offset.get()..self.len.get());
+        match self.digest.add_data(lease) {
+            Ok(len) => {
+                self.offset.set(self.offset.get() + len);
+                Ok(())
+            }
+            Err((e, buffer)) => {
+                self.buffer.replace(buffer);
+                Err(e)
+            }
+        }
+    }
+
+    fn finish(&self, result: Result<(), CryptoError>) {
+        self.digest.clear_data();
+        let state = self.state.replace(State::Idle);
+
+        let mut value = [0; REGISTER_LEN];
+        self.digest_buffer
+            .map(|buffer| value.copy_from_slice(buffer));
+        if let (State::Extend(index), Ok(())) = (state, result) {
+            self.registers.map(|registers| registers[index] = value);
+        }
+
+        match self.appid.take() {
+            Some(appid) => {
+                let _ = self.apps.enter(appid, |app, _| {
+                    let code = match result {
+                        Ok(()) => 0,
+                        Err(e) => usize::from(ReturnCode::from(e)),
+                    };
+                    if let (State::Quote, Ok(())) = (state, result) {
+                        app.output.as_mut().map(|output| {
+                            let n = core::cmp::min(output.len(), REGISTER_LEN);
+                            output.as_mut()[..n].copy_from_slice(&value[..n]);
+                        });
+                    }
+                    app.callback.map(|cb| cb.schedule(code, 0, 0));
+                });
+            }
+            None => {
+                self.client.map(|client| match state {
+                    State::Extend(index) => client.extend_done(index, result),
+                    State::Quote => client.quote_done(result.map(|()| &value)),
+                    State::Idle => {}
+                });
+            }
+        }
+    }
+
+    /// Start an operation for a process, with its data or nonce.
+    fn start_app(&self, appid: AppId, command_num: usize, arg: usize) -> ReturnCode {
+        let res = self
+            .apps
+            .enter(appid, |app, _| {
+                let data = app.input.as_ref().map_or(&[][..], |input| input.as_ref());
+                let res = if command_num == 3 {
+                    self.extend(arg, data)
+                } else {
+                    self.quote(arg as u32, data)
+                };
+                res.map_or_else(|e| e.into(), |()| ReturnCode::SUCCESS)
+            })
+            .unwrap_or_else(|err| err.into());
+        if res == ReturnCode::SUCCESS {
+            self.appid.set(appid);
+        }
+        res
+    }
+}
+
+impl<'a, D: digest::Digest<'a, Register>> digest::Client<'a, Register> for Measurements<'a, D> {
+    fn add_data_done(&'a self, result: Result<(), CryptoError>, data: &'static mut [u8]) {
+        self.buffer.replace(data);
+        let res = result.and_then(|()| {
+            if self.offset.get() < self.len.get() {
+                return self.add_next();
+            }
+            let buffer = self.digest_buffer.take().ok_or(CryptoError::EngineBusy)?;
+            self.digest.run(buffer).map_err(|(e, buffer)| {
+                self.digest_buffer.replace(buffer);
+                e
+            })
+        });
+        if let Err(e) = res {
+            self.finish(Err(e));
+        }
+    }
+
+    fn add_readonly_data_done(&'a self, _result: Result<(), CryptoError>, _data: &'static [u8]) {
+        // All the data is copied into the working buffer.
+    }
+
+    fn hash_done(&'a self, result: Result<(), CryptoError>, digest: &'static mut Register) {
+        self.digest_buffer.replace(digest);
+        self.finish(result);
+    }
+}
+
+/// Specify memory regions to be used.
+///
+/// ### `allow_num`
+///
+/// - `0`: The data to extend a register with, or the nonce of a quote. May
+///        be empty or not allowed.
+/// - `1`: The output buffer, of 32 bytes, which receives the value of a
+///        register or a quote.
+impl<'a, D: digest::Digest<'a, Register>> Driver for Measurements<'a, D> {
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        self.apps
+            .enter(appid, |app, _| {
+                match allow_num {
+                    0 => app.input = slice,
+                    1 => app.output = slice,
+                    _ => return ReturnCode::ENOSUPPORT,
+                }
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    /// Subscribe to Measurements events.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: An extension or a quote is done. The callback signature is
+    ///        `fn(result: u32)`.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.callback.insert(callback);
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Return the number of registers.
+    /// - `2`: Copy the register `data1` to the output buffer. Returns
+    ///        `EINVAL` if there is no such register, and `ESIZE` if the
+    ///        output buffer is shorter than a register.
+    /// - `3`: Extend the register `data1` with the data allowed. Returns
+    ///        `EBUSY` if an operation is in progress, and `EINVAL` if there
+    ///        is no such register or the data is too long.
+    /// - `4`: Quote the registers selected by the mask `data1`, with the
+    ///        nonce allowed, into the output buffer. Returns `EBUSY` if an
+    ///        operation is in progress, and `EINVAL` if the mask is invalid
+    ///        or the nonce is too long.
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: self.count(),
+            },
+            2 => {
+                let register = match self.read(data1) {
+                    Some(register) => register,
+                    None => return ReturnCode::EINVAL,
+                };
+                self.apps
+                    .enter(appid, |app, _| match app.output.as_mut() {
+                        Some(output) if output.len() >= REGISTER_LEN => {
+                            output.as_mut()[..REGISTER_LEN].copy_from_slice(&register);
+                            ReturnCode::SUCCESS
+                        }
+                        _ => ReturnCode::ESIZE,
+                    })
+                    .unwrap_or_else(|err| err.into())
+            }
+            3 | 4 => self.start_app(appid, command_num, data1),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+pub struct App {
+    callback: OptionalCell<Callback>,
+    input: Option<AppSlice<Shared, u8>>,
+    output: Option<AppSlice<Shared, u8>>,
+}
+
+impl Default for App {
+    fn default() -> App {
+        App {
+            callback: OptionalCell::empty(),
+            input: None,
+            output: None,
+        }
+    }
+}
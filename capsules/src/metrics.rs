@@ -0,0 +1,147 @@
+//! Provides userspace with read access to the event counters registered with
+//! the kernel (see `kernel::metrics`).
+//!
+//! Userspace Interface
+//! -------------------
+//!
+//! ### `allow` System Call
+//!
+//! * `0`: buffer which receives the name of a counter with command `5`.
+//!
+//! ### `command` System Call
+//!
+//! * `0`: check whether the driver exists
+//! * `1`: returns the number of registered counters
+//! * `2`: returns the number of entries of counter `arg1`
+//! * `3`: returns the value of entry `arg2` of counter `arg1`
+//! * `4`: returns the key of entry `arg2` of counter `arg1` (e.g. the IRQ or
+//!   driver number the entry counts events for)
+//! * `5`: copies the name of counter `arg1` into the allowed buffer and
+//!   returns its length. Names longer than the buffer are truncated.
+//!
+//! Values are returned with `SuccessWithValue`. Commands `2` to `5` return
+//! `EINVAL` for a counter or entry that does not exist.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let metrics = components::metrics::MetricsComponent::new(board_kernel).finalize(());
+//! ```
+
+use core::cmp;
+use kernel::capabilities::ProcessManagementCapability;
+use kernel::introspection::KernelInfo;
+use kernel::{AppId, AppSlice, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Metrics as usize;
+
+#[derive(Default)]
+pub struct App {
+    name_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct Metrics<C: ProcessManagementCapability> {
+    info: KernelInfo,
+    apps: Grant<App>,
+    capability: C,
+}
+
+impl<C: ProcessManagementCapability> Metrics<C> {
+    pub fn new(kernel: &'static kernel::Kernel, grant: Grant<App>, capability: C) -> Metrics<C> {
+        Metrics {
+            info: KernelInfo::new(kernel),
+            apps: grant,
+            capability: capability,
+        }
+    }
+
+    fn value(value: Option<usize>) -> ReturnCode {
+        value.map_or(ReturnCode::EINVAL, |value| ReturnCode::SuccessWithValue {
+            value: value,
+        })
+    }
+
+    fn copy_name(&self, counter: usize, appid: AppId) -> ReturnCode {
+        let name = match self
+            .info
+            .counter_map_or(counter, None, &self.capability, |counter| {
+                Some(counter.name())
+            }) {
+            None => return ReturnCode::EINVAL,
+            Some(name) => name,
+        };
+
+        self.apps
+            .enter(appid, |app, _| {
+                app.name_buffer
+                    .as_mut()
+                    .map_or(ReturnCode::ENOMEM, |buffer| {
+                        let len = cmp::min(name.len(), buffer.len());
+                        buffer.as_mut()[..len].copy_from_slice(&name.as_bytes()[..len]);
+                        ReturnCode::SuccessWithValue { value: len }
+                    })
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+}
+
+impl<C: ProcessManagementCapability> Driver for Metrics<C> {
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            // Buffer for counter names
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.name_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, arg1: usize, arg2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            // check whether the driver exists
+            0 => ReturnCode::SUCCESS,
+
+            // number of counters
+            1 => ReturnCode::SuccessWithValue {
+                value: self.info.number_counters(&self.capability),
+            },
+
+            // number of entries
+            2 => Self::value(
+                self.info
+                    .counter_map_or(arg1, None, &self.capability, |counter| Some(counter.len())),
+            ),
+
+            // entry value
+            3 => Self::value(
+                self.info
+                    .counter_map_or(arg1, None, &self.capability, |counter| {
+                        counter.get_index(arg2).map(|value| value as usize)
+                    }),
+            ),
+
+            // entry key
+            4 => Self::value(
+                self.info
+                    .counter_map_or(arg1, None, &self.capability, |counter| counter.key(arg2)),
+            ),
+
+            // counter name
+            5 => self.copy_name(arg1, appid),
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
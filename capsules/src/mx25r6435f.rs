@@ -44,6 +44,16 @@
 //! mx25r6435f_spi.set_client(mx25r6435f);
 //! mx25r6435f_virtual_alarm.set_client(mx25r6435f);
 //! ```
+//!
+//! Wear Tracking
+//! -------------
+//!
+//! Sector erases can be counted with a `kernel::metrics::Counter` set with
+//! `set_erase_counter()`. The 2048 sectors of the chip are spread evenly over
+//! the entries of the counter, so a counter with 128 entries tracks erases per
+//! 64 kB block. A warning is printed once when an entry reaches 90% of the
+//! sector endurance rating. With more than one sector per entry the warning is
+//! conservative, since the erases of all sectors of the entry are added up.
 
 use core::cell::Cell;
 use core::ops::{Index, IndexMut};
@@ -52,6 +62,7 @@ use kernel::common::cells::TakeCell;
 use kernel::debug;
 use kernel::hil;
 use kernel::hil::time::Frequency;
+use kernel::metrics::Counter;
 use kernel::ReturnCode;
 
 pub static mut TXBUFFER: [u8; PAGE_SIZE as usize + 4] = [0; PAGE_SIZE as usize + 4];
@@ -59,8 +70,17 @@ pub static mut RXBUFFER: [u8; PAGE_SIZE as usize + 4] = [0; PAGE_SIZE as usize +
 
 const SPI_SPEED: u32 = 8000000;
 const SECTOR_SIZE: u32 = 4096;
+const SECTOR_COUNT: u32 = 2048;
 const PAGE_SIZE: u32 = 256;
 
+/// Guaranteed number of erase cycles of a sector.
+pub const ENDURANCE: u32 = 100_000;
+/// Number of erases after which an entry is reported as wearing out.
+const ENDURANCE_WARNING: u32 = ENDURANCE / 10 * 9;
+
+/// Manufacturer, memory type and density returned by RDID.
+pub const ID: [u8; 3] = [0xc2, 0x28, 0x17];
+
 /// This is a wrapper around a u8 array that is sized to a single page for the
 /// MX25R6435F. The page size is 4k because that is the smallest size that can
 /// be erased (even though 256 bytes can be written).
@@ -173,6 +193,8 @@ pub struct MX25R6435F<
     rxbuffer: TakeCell<'static, [u8]>,
     client: OptionalCell<&'a dyn hil::flash::Client<MX25R6435F<'a, S, P, A>>>,
     client_sector: TakeCell<'static, Mx25r6435fSector>,
+    erase_counter: OptionalCell<&'a Counter<'a>>,
+    id: OptionalCell<[u8; 3]>,
 }
 
 impl<
@@ -200,9 +222,28 @@ impl<
             rxbuffer: TakeCell::new(rxbuffer),
             client: OptionalCell::empty(),
             client_sector: TakeCell::empty(),
+            erase_counter: OptionalCell::empty(),
+            id: OptionalCell::empty(),
         }
     }
 
+    /// Count sector erases in `counter`. See the module documentation.
+    pub fn set_erase_counter(&self, counter: &'a Counter<'a>) {
+        self.erase_counter.set(counter);
+    }
+
+    fn count_erase(&self, sector_index: u32) {
+        self.erase_counter.map(|counter| {
+            let entry = sector_index as usize * counter.len() / SECTOR_COUNT as usize;
+            if counter.increment_to(entry, ENDURANCE_WARNING) {
+                debug!(
+                    "MX25R6435F: sector {} has been erased {} times, close to its endurance",
+                    sector_index, ENDURANCE_WARNING
+                );
+            }
+        });
+    }
+
     /// Setup SPI for this chip
     fn configure_spi(&self) {
         self.hold_pin.map(|pin| {
@@ -215,7 +256,13 @@ impl<
         );
     }
 
+    /// Start reading the manufacturer and device ID of the chip. Once it has
+    /// been read, it is returned by `identification()`.
     pub fn read_identification(&self) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.id.clear();
         self.configure_spi();
 
         self.txbuffer
@@ -232,6 +279,12 @@ impl<
             })
     }
 
+    /// The ID read by the last `read_identification()`, to compare with
+    /// `ID`.
+    pub fn identification(&self) -> Option<[u8; 3]> {
+        self.id.map(|id| *id)
+    }
+
     fn enable_write(&self) -> ReturnCode {
         self.write_protect_pin.map(|pin| {
             pin.set();
@@ -330,12 +383,11 @@ impl<
     ) {
         match self.state.get() {
             State::ReadId => {
+                self.state.set(State::Idle);
                 self.txbuffer.replace(write_buffer);
                 read_buffer.map(|read_buffer| {
-                    debug!(
-                        "id {:#x} {:#x} {:#x}",
-                        read_buffer[1], read_buffer[2], read_buffer[3]
-                    );
+                    self.id
+                        .set([read_buffer[1], read_buffer[2], read_buffer[3]]);
                     self.rxbuffer.replace(read_buffer);
                 });
             }
@@ -387,6 +439,7 @@ impl<
                 operation,
             } => {
                 self.state.set(State::EraseSectorErase { operation });
+                self.count_erase(sector_index);
                 write_buffer[0] = Opcodes::SE as u8;
                 write_buffer[1] = ((sector_index * SECTOR_SIZE) >> 16) as u8;
                 write_buffer[2] = ((sector_index * SECTOR_SIZE) >> 8) as u8;
@@ -0,0 +1,389 @@
+//! Issues single use nonces to protect services against replayed requests.
+//!
+//! A service, e.g. a process offering cryptographic operations over IPC or an
+//! over-the-air update protocol, hands a fresh nonce to a client, which sends
+//! it back with its request. The service then validates the nonce, which
+//! succeeds only once, and only for a nonce issued since the last boot.
+//!
+//! A nonce is 16 bytes, all fields little-endian:
+//!
+//! ```text
+//! 0             4             8                           16
+//! +-------------+-------------+---------------------------+
+//! | boot count  | sequence    | random                    |
+//! +-------------+-------------+---------------------------+
+//! ```
+//!
+//! The boot count is kept in nonvolatile storage and incremented at every
+//! boot, and the sequence number counts the nonces issued since, so nonces
+//! increase monotonically and never repeat, even across reboots. The random
+//! part, taken from a pool refilled from the random number generator, makes
+//! them unpredictable.
+//!
+//! Issued nonces are remembered until they are validated. When the table is
+//! full, the oldest nonce is forgotten and fails validation.
+//!
+//! Userspace Interface
+//! -------------------
+//!
+//! ### `allow` System Call
+//!
+//! * `0`: buffer of at least 16 bytes for the nonce of commands `1` and `2`.
+//!
+//! ### `command` System Call
+//!
+//! * `0`: check whether the driver exists
+//! * `1`: issues a nonce into the allowed buffer
+//! * `2`: validates the nonce in the allowed buffer
+//!
+//! Command `1` returns `EBUSY` while the boot count is being updated or the
+//! random pool is empty, and `FAIL` if the boot count could not be updated.
+//! Command `2` returns `EINVAL` for a nonce that was not issued, was already
+//! validated or was forgotten.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let nonce = components::nonce::NonceComponent::new(
+//!     board_kernel,
+//!     mux_rng,
+//!     nonce_storage,
+//!     0x5d000, // Address of the boot count
+//! )
+//! .finalize(());
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::hil::rng::{self, Rng};
+use kernel::{AppId, AppSlice, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Nonce as usize;
+
+/// Size of a nonce in bytes.
+pub const NONCE_LEN: usize = 16;
+
+/// Size of the boot count record in storage.
+pub const RECORD_LEN: usize = 8;
+
+const MAGIC: u32 = 0x4e4f_4e43;
+
+/// Number of issued nonces remembered for validation.
+const OUTSTANDING_LEN: usize = 8;
+
+/// Number of random words kept ready, enough for four nonces.
+const POOL_LEN: usize = 8;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Nonce {
+    pub boot_count: u32,
+    pub sequence: u32,
+    pub random: [u32; 2],
+}
+
+impl Nonce {
+    pub fn to_bytes(&self) -> [u8; NONCE_LEN] {
+        let mut bytes = [0; NONCE_LEN];
+        bytes[0..4].copy_from_slice(&self.boot_count.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.sequence.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.random[0].to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.random[1].to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Nonce> {
+        if bytes.len() < NONCE_LEN {
+            return None;
+        }
+        Some(Nonce {
+            boot_count: word(bytes, 0),
+            sequence: word(bytes, 4),
+            random: [word(bytes, 8), word(bytes, 12)],
+        })
+    }
+
+    /// Compare without returning early, so that the time taken does not tell
+    /// how much of a guess was right.
+    fn matches(&self, other: &Nonce) -> bool {
+        let difference = (self.boot_count ^ other.boot_count)
+            | (self.sequence ^ other.sequence)
+            | (self.random[0] ^ other.random[0])
+            | (self.random[1] ^ other.random[1]);
+        difference == 0
+    }
+}
+
+fn word(buffer: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buffer[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    Restoring,
+    Saving,
+    Ready,
+    Failed,
+}
+
+#[derive(Default)]
+pub struct App {
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct NonceService<'a> {
+    rng: &'a dyn Rng<'a>,
+    storage: &'a dyn NonvolatileStorage<'a>,
+    address: usize,
+    buffer: TakeCell<'a, [u8]>,
+    apps: Grant<App>,
+    state: Cell<State>,
+    boot_count: Cell<u32>,
+    sequence: Cell<u32>,
+    pool: Cell<[u32; POOL_LEN]>,
+    pool_len: Cell<usize>,
+    refilling: Cell<bool>,
+    outstanding: Cell<[Option<Nonce>; OUTSTANDING_LEN]>,
+    /// Slot of the next issued nonce.
+    next: Cell<usize>,
+}
+
+impl<'a> NonceService<'a> {
+    /// `buffer` holds the boot count record and must be at least
+    /// `RECORD_LEN` bytes long.
+    pub fn new(
+        rng: &'a dyn Rng<'a>,
+        storage: &'a dyn NonvolatileStorage<'a>,
+        address: usize,
+        buffer: &'a mut [u8],
+        grant: Grant<App>,
+    ) -> NonceService<'a> {
+        NonceService {
+            rng: rng,
+            storage: storage,
+            address: address,
+            buffer: TakeCell::new(buffer),
+            apps: grant,
+            state: Cell::new(State::Idle),
+            boot_count: Cell::new(0),
+            sequence: Cell::new(0),
+            pool: Cell::new([0; POOL_LEN]),
+            pool_len: Cell::new(0),
+            refilling: Cell::new(false),
+            outstanding: Cell::new([None; OUTSTANDING_LEN]),
+            next: Cell::new(0),
+        }
+    }
+
+    /// Read and increment the boot count, and fill the random pool. Nonces
+    /// are issued once the new boot count has been written back.
+    pub fn restore(&self) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.refill();
+        let res = self.buffer.take().map_or(ReturnCode::ENOMEM, |buffer| {
+            self.storage.read(buffer, self.address, RECORD_LEN)
+        });
+        self.state.set(if res == ReturnCode::SUCCESS {
+            State::Restoring
+        } else {
+            State::Failed
+        });
+        res
+    }
+
+    fn refill(&self) {
+        if !self.refilling.get() && self.pool_len.get() < POOL_LEN {
+            if self.rng.get() == ReturnCode::SUCCESS {
+                self.refilling.set(true);
+            }
+        }
+    }
+
+    /// Issue a new nonce.
+    pub fn issue(&self) -> Result<Nonce, ReturnCode> {
+        match self.state.get() {
+            State::Ready => {}
+            State::Failed => return Err(ReturnCode::FAIL),
+            _ => return Err(ReturnCode::EBUSY),
+        }
+        let pool_len = self.pool_len.get();
+        if pool_len < 2 {
+            self.refill();
+            return Err(ReturnCode::EBUSY);
+        }
+        let sequence = self.sequence.get();
+        if sequence == u32::max_value() {
+            // Never reuse a sequence number within a boot.
+            return Err(ReturnCode::FAIL);
+        }
+
+        let pool = self.pool.get();
+        let nonce = Nonce {
+            boot_count: self.boot_count.get(),
+            sequence: sequence,
+            random: [pool[pool_len - 1], pool[pool_len - 2]],
+        };
+        self.pool_len.set(pool_len - 2);
+        self.sequence.set(sequence + 1);
+
+        let mut outstanding = self.outstanding.get();
+        outstanding[self.next.get()] = Some(nonce);
+        self.outstanding.set(outstanding);
+        self.next.set((self.next.get() + 1) % OUTSTANDING_LEN);
+
+        self.refill();
+        Ok(nonce)
+    }
+
+    /// Check that `nonce` was issued and not validated yet. A nonce is valid
+    /// only once.
+    pub fn validate(&self, nonce: &Nonce) -> ReturnCode {
+        let mut outstanding = self.outstanding.get();
+        let mut found = false;
+        for slot in outstanding.iter_mut() {
+            if slot.map_or(false, |issued| issued.matches(nonce)) {
+                *slot = None;
+                found = true;
+            }
+        }
+        self.outstanding.set(outstanding);
+        if found {
+            ReturnCode::SUCCESS
+        } else {
+            ReturnCode::EINVAL
+        }
+    }
+
+    fn issue_to_app(&self, appid: AppId) -> ReturnCode {
+        self.apps
+            .enter(appid, |app, _| {
+                app.buffer.as_mut().map_or(ReturnCode::ENOMEM, |buffer| {
+                    if buffer.len() < NONCE_LEN {
+                        return ReturnCode::ESIZE;
+                    }
+                    match self.issue() {
+                        Ok(nonce) => {
+                            buffer.as_mut()[..NONCE_LEN].copy_from_slice(&nonce.to_bytes());
+                            ReturnCode::SUCCESS
+                        }
+                        Err(err) => err,
+                    }
+                })
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    fn validate_from_app(&self, appid: AppId) -> ReturnCode {
+        self.apps
+            .enter(appid, |app, _| {
+                app.buffer.as_ref().map_or(ReturnCode::ENOMEM, |buffer| {
+                    Nonce::from_bytes(buffer.as_ref())
+                        .map_or(ReturnCode::ESIZE, |nonce| self.validate(&nonce))
+                })
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+}
+
+impl rng::Client for NonceService<'_> {
+    fn randomness_available(
+        &self,
+        randomness: &mut dyn Iterator<Item = u32>,
+        error: ReturnCode,
+    ) -> rng::Continue {
+        if error != ReturnCode::SUCCESS {
+            self.refilling.set(false);
+            return rng::Continue::Done;
+        }
+        let mut pool = self.pool.get();
+        let mut pool_len = self.pool_len.get();
+        for random in randomness.take(POOL_LEN - pool_len) {
+            pool[pool_len] = random;
+            pool_len += 1;
+        }
+        self.pool.set(pool);
+        self.pool_len.set(pool_len);
+
+        if pool_len < POOL_LEN {
+            rng::Continue::More
+        } else {
+            self.refilling.set(false);
+            rng::Continue::Done
+        }
+    }
+}
+
+impl<'a> NonvolatileStorageClient<'a> for NonceService<'a> {
+    fn read_done(&self, buffer: &'a mut [u8], length: usize) {
+        // Erased or foreign storage counts as no boot yet.
+        let boot_count = if length == RECORD_LEN && word(buffer, 0) == MAGIC {
+            word(buffer, 4)
+        } else {
+            0
+        };
+        if boot_count == u32::max_value() {
+            self.buffer.replace(buffer);
+            self.state.set(State::Failed);
+            return;
+        }
+
+        self.boot_count.set(boot_count + 1);
+        buffer[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        buffer[4..8].copy_from_slice(&(boot_count + 1).to_le_bytes());
+        let res = self.storage.write(buffer, self.address, RECORD_LEN);
+        self.state.set(if res == ReturnCode::SUCCESS {
+            State::Saving
+        } else {
+            State::Failed
+        });
+    }
+
+    fn write_done(&self, buffer: &'a mut [u8], _length: usize) {
+        self.buffer.replace(buffer);
+        self.state.set(State::Ready);
+    }
+}
+
+impl Driver for NonceService<'_> {
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            // Buffer for nonces
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            // check whether the driver exists
+            0 => ReturnCode::SUCCESS,
+
+            // issue a nonce
+            1 => self.issue_to_app(appid),
+
+            // validate a nonce
+            2 => self.validate_from_app(appid),
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Confines a kernel user of nonvolatile storage to a named region.
+//!
+//! Boards share the kernel part of a storage device between several kernel
+//! users, such as the error journal, the nonce service and the counter store.
+//! `NonvolatileRegion` gives each of them addresses relative to the start of
+//! its own region, and rejects accesses outside of it, so that one user
+//! cannot corrupt the data of another.
+//!
+//! The regions are laid out with `nonvolatile_layout!`, which defines a
+//! `(start, length)` constant per region and fails the build if the regions,
+//! listed in increasing order, overlap or do not fit in the storage.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! capsules::nonvolatile_layout! {
+//!     0x60000; // Length of the kernel region
+//!     JOURNAL_REGION = (0x5e000, 0x1000),
+//!     COUNTER_REGION = (0x5f000, 0x1000),
+//! }
+//!
+//! let journal_region = static_init!(
+//!     capsules::nonvolatile_region::NonvolatileRegion<'static>,
+//!     capsules::nonvolatile_region::NonvolatileRegion::new(
+//!         journal_storage,
+//!         "journal",
+//!         JOURNAL_REGION
+//!     )
+//! );
+//! journal_storage.set_client(journal_region);
+//! ```
+
+use kernel::common::cells::OptionalCell;
+use kernel::debug;
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::ReturnCode;
+
+/// Define a `(start, length)` constant for each region, and check at compile
+/// time that the regions are in increasing order, do not overlap, and end
+/// before `$end`.
+#[macro_export]
+macro_rules! nonvolatile_layout {
+    ($end:expr; $($vis:vis $name:ident = ($start:expr, $len:expr)),+ $(,)?) => {
+        $($vis const $name: (usize, usize) = ($start, $len);)+
+        $crate::nonvolatile_layout!(@check $end; $(($start, $len)),+);
+    };
+    (@check $end:expr; ($start:expr, $len:expr)) => {
+        const _: [(); 1] = [(); (($start) + ($len) <= ($end)) as usize];
+    };
+    (@check $end:expr; ($start:expr, $len:expr), ($next_start:expr, $next_len:expr) $(, $rest:tt)*) => {
+        const _: [(); 1] = [(); (($start) + ($len) <= ($next_start)) as usize];
+        $crate::nonvolatile_layout!(@check $end; ($next_start, $next_len) $(, $rest)*);
+    };
+}
+
+pub struct NonvolatileRegion<'a> {
+    storage: &'a dyn NonvolatileStorage<'a>,
+    name: &'static str,
+    start: usize,
+    length: usize,
+    client: OptionalCell<&'a dyn NonvolatileStorageClient<'a>>,
+}
+
+impl<'a> NonvolatileRegion<'a> {
+    /// `region` is the `(start, length)` of the region in `storage`.
+    pub fn new(
+        storage: &'a dyn NonvolatileStorage<'a>,
+        name: &'static str,
+        region: (usize, usize),
+    ) -> NonvolatileRegion<'a> {
+        NonvolatileRegion {
+            storage: storage,
+            name: name,
+            start: region.0,
+            length: region.1,
+            client: OptionalCell::empty(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Translate an access to the region to an address in the storage.
+    fn translate(&self, address: usize, length: usize) -> Option<usize> {
+        match address.checked_add(length) {
+            Some(end) if end <= self.length => Some(self.start + address),
+            _ => {
+                debug!(
+                    "Access to {:#x}+{:#x} outside of region {}",
+                    address, length, self.name
+                );
+                None
+            }
+        }
+    }
+}
+
+impl<'a> NonvolatileStorage<'a> for NonvolatileRegion<'a> {
+    fn set_client(&self, client: &'a dyn NonvolatileStorageClient<'a>) {
+        self.client.set(client);
+    }
+
+    fn read(&self, buffer: &'a mut [u8], address: usize, length: usize) -> ReturnCode {
+        self.translate(address, length)
+            .map_or(ReturnCode::EINVAL, move |address| {
+                self.storage.read(buffer, address, length)
+            })
+    }
+
+    fn write(&self, buffer: &'a mut [u8], address: usize, length: usize) -> ReturnCode {
+        self.translate(address, length)
+            .map_or(ReturnCode::EINVAL, move |address| {
+                self.storage.write(buffer, address, length)
+            })
+    }
+}
+
+impl<'a> NonvolatileStorageClient<'a> for NonvolatileRegion<'a> {
+    fn read_done(&self, buffer: &'a mut [u8], length: usize) {
+        self.client
+            .map(move |client| client.read_done(buffer, length));
+    }
+
+    fn write_done(&self, buffer: &'a mut [u8], length: usize) {
+        self.client
+            .map(move |client| client.write_done(buffer, length));
+    }
+}
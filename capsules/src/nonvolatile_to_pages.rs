@@ -36,6 +36,7 @@ use core::cmp;
 use kernel::common::cells::NumericCellExt;
 use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::hil;
+use kernel::journal;
 use kernel::ReturnCode;
 
 /// This module is either waiting to do something, or handling a read/write.
@@ -252,7 +253,13 @@ impl<F: hil::flash::Flash> hil::flash::Client<F> for NonvolatileToPages<'_, F> {
         }
     }
 
-    fn write_complete(&self, pagebuffer: &'static mut F::Page, _error: hil::flash::Error) {
+    fn write_complete(&self, pagebuffer: &'static mut F::Page, error: hil::flash::Error) {
+        if error != hil::flash::Error::CommandComplete {
+            journal::record(
+                journal::Event::FlashWriteFailure,
+                (self.address.get() / pagebuffer.as_mut().len()) as u32,
+            );
+        }
         // After a write we could be done, need to do another write, or need to
         // do a read.
         self.buffer.take().map(move |buffer| {
@@ -0,0 +1,270 @@
+//! 1-Wire bus master, bit-banged on a GPIO pin.
+//!
+//! The bus needs an external pull-up resistor, typically 4.7k. The pin is
+//! only ever driven low: it is released by making it an input, so any GPIO
+//! pin works, whatever drive modes it supports.
+//!
+//! The time slots of the bus are a few microseconds long, which is shorter
+//! than what alarms can time on most chips. This driver busy-waits on a
+//! `Time` instead, which must count at 1MHz or faster, for example a free
+//! running hardware timer. The bus operations are synchronous: a reset
+//! blocks the kernel for about 1ms, and each byte for about 0.6ms. Kernel
+//! code is only interrupted by the short top halves of interrupt handlers,
+//! which the timings below leave enough margin for.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let one_wire_counter = &nrf5x::timer::TIMER2;
+//! hil::time::Counter::start(one_wire_counter);
+//! let one_wire = static_init!(
+//!     capsules::one_wire::OneWire<'static, nrf5x::gpio::GPIOPin, nrf5x::timer::Timer>,
+//!     capsules::one_wire::OneWire::new(&nrf52840::gpio::PORT[Pin::P1_10], one_wire_counter)
+//! );
+//! one_wire.initialize();
+//! ```
+
+use kernel::hil::gpio;
+use kernel::hil::time::{self, Frequency};
+use kernel::ReturnCode;
+
+/// The unique 64-bit ROM code of a device: the family code, a 48-bit serial
+/// number, and the CRC of the first 7 bytes.
+pub type Rom = [u8; 8];
+
+/// ROM commands, which a master sends after a reset to select devices.
+pub const SEARCH_ROM: u8 = 0xf0;
+pub const READ_ROM: u8 = 0x33;
+pub const MATCH_ROM: u8 = 0x55;
+pub const SKIP_ROM: u8 = 0xcc;
+
+/// The Dallas/Maxim CRC-8 (polynomial x^8 + x^5 + x^4 + 1), which protects
+/// ROM codes and device memories.
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0;
+    for byte in data {
+        let mut byte = *byte;
+        for _ in 0..8 {
+            let mix = (crc ^ byte) & 0x01;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8c;
+            }
+            byte >>= 1;
+        }
+    }
+    crc
+}
+
+/// The progress of a ROM search, which finds one device per call to
+/// `OneWire::search()`.
+#[derive(Default)]
+pub struct Search {
+    rom: Rom,
+    /// The last bit where the devices differed and the search took the 0
+    /// branch, counting from 1, or 0 if there is none.
+    last_discrepancy: u8,
+    done: bool,
+}
+
+impl Search {
+    pub fn new() -> Search {
+        Search::default()
+    }
+}
+
+pub struct OneWire<'a, P: gpio::Pin, T: time::Time> {
+    pin: &'a P,
+    time: &'a T,
+}
+
+impl<'a, P: gpio::Pin, T: time::Time> OneWire<'a, P, T> {
+    pub fn new(pin: &'a P, time: &'a T) -> OneWire<'a, P, T> {
+        OneWire {
+            pin: pin,
+            time: time,
+        }
+    }
+
+    /// Release the bus. Must be called before the first operation.
+    pub fn initialize(&self) {
+        self.release();
+    }
+
+    fn drive_low(&self) {
+        self.pin.clear();
+        self.pin.make_output();
+    }
+
+    fn release(&self) {
+        self.pin.make_input();
+        self.pin.set_floating_state(gpio::FloatingState::PullNone);
+    }
+
+    fn delay_us(&self, us: u32) {
+        let tics = us * (T::Frequency::frequency() / 1_000_000);
+        let start = self.time.now();
+        while self.time.now().wrapping_sub(start) & self.time.max_tics() < tics {}
+    }
+
+    /// Send a reset pulse. Returns whether a device answered with a presence
+    /// pulse.
+    pub fn reset(&self) -> bool {
+        self.drive_low();
+        self.delay_us(480);
+        self.release();
+        self.delay_us(70);
+        let present = !self.pin.read();
+        self.delay_us(410);
+        present
+    }
+
+    fn write_bit(&self, bit: bool) {
+        self.drive_low();
+        if bit {
+            self.delay_us(6);
+            self.release();
+            self.delay_us(64);
+        } else {
+            self.delay_us(60);
+            self.release();
+            self.delay_us(10);
+        }
+    }
+
+    fn read_bit(&self) -> bool {
+        self.drive_low();
+        self.delay_us(6);
+        self.release();
+        self.delay_us(9);
+        let bit = self.pin.read();
+        self.delay_us(55);
+        bit
+    }
+
+    /// Write a byte, least significant bit first.
+    pub fn write_byte(&self, byte: u8) {
+        for i in 0..8 {
+            self.write_bit(byte & (1 << i) != 0);
+        }
+    }
+
+    /// Read a byte, least significant bit first.
+    pub fn read_byte(&self) -> u8 {
+        (0..8).fold(0, |byte, i| byte | ((self.read_bit() as u8) << i))
+    }
+
+    pub fn write(&self, data: &[u8]) {
+        data.iter().for_each(|byte| self.write_byte(*byte));
+    }
+
+    pub fn read(&self, buf: &mut [u8]) {
+        buf.iter_mut().for_each(|byte| *byte = self.read_byte());
+    }
+
+    /// Reset the bus and select the device with the ROM code `rom`, or all
+    /// the devices if `rom` is `None`, which is only useful for commands
+    /// that do not return data or when there is a single device.
+    /// Returns `ENODEVICE` if no device is present.
+    pub fn select(&self, rom: Option<&Rom>) -> ReturnCode {
+        if !self.reset() {
+            return ReturnCode::ENODEVICE;
+        }
+        match rom {
+            Some(rom) => {
+                self.write_byte(MATCH_ROM);
+                self.write(rom);
+            }
+            None => self.write_byte(SKIP_ROM),
+        }
+        ReturnCode::SUCCESS
+    }
+
+    /// Read the ROM code of the only device on the bus.
+    /// Returns `ENODEVICE` if no device is present, and `FAIL` if the CRC is
+    /// wrong, for example because several devices answered.
+    pub fn read_rom(&self) -> Result<Rom, ReturnCode> {
+        if !self.reset() {
+            return Err(ReturnCode::ENODEVICE);
+        }
+        self.write_byte(READ_ROM);
+        let mut rom = [0; 8];
+        self.read(&mut rom);
+        if crc8(&rom[..7]) != rom[7] {
+            return Err(ReturnCode::FAIL);
+        }
+        Ok(rom)
+    }
+
+    /// Find the next device on the bus, in the order of their ROM codes read
+    /// from the least significant bit. Returns `Ok(None)` once all the
+    /// devices have been found. Returns `ENODEVICE` if no device is present,
+    /// and `FAIL` on a bus error, after which the search starts over.
+    pub fn search(&self, search: &mut Search) -> Result<Option<Rom>, ReturnCode> {
+        if search.done {
+            return Ok(None);
+        }
+        if !self.reset() {
+            *search = Search::new();
+            return Err(ReturnCode::ENODEVICE);
+        }
+        self.write_byte(SEARCH_ROM);
+
+        let mut last_zero = 0;
+        for bit in 1..=64 {
+            let byte = (bit as usize - 1) / 8;
+            let mask = 1 << ((bit - 1) % 8);
+
+            // Each device sends its bit, then the complement
+            let id_bit = self.read_bit();
+            let complement = self.read_bit();
+            let direction = if id_bit && complement {
+                // No device took part
+                *search = Search::new();
+                return Err(ReturnCode::FAIL);
+            } else if id_bit != complement {
+                id_bit
+            } else {
+                // The devices differ: take the branch not taken last time
+                let direction = if bit < search.last_discrepancy {
+                    search.rom[byte] & mask != 0
+                } else {
+                    bit == search.last_discrepancy
+                };
+                if !direction {
+                    last_zero = bit;
+                }
+                direction
+            };
+
+            if direction {
+                search.rom[byte] |= mask;
+            } else {
+                search.rom[byte] &= !mask;
+            }
+            // Devices whose bit differs stop taking part
+            self.write_bit(direction);
+        }
+
+        if crc8(&search.rom[..7]) != search.rom[7] {
+            *search = Search::new();
+            return Err(ReturnCode::FAIL);
+        }
+        search.last_discrepancy = last_zero;
+        search.done = last_zero == 0;
+        Ok(Some(search.rom))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::crc8;
+
+    #[test]
+    fn crc() {
+        // From Maxim application note 27
+        assert_eq!(crc8(&[0x02, 0x1c, 0xb8, 0x01, 0x00, 0x00, 0x00]), 0xa2);
+        assert_eq!(crc8(&[0x02, 0x1c, 0xb8, 0x01, 0x00, 0x00, 0x00, 0xa2]), 0);
+    }
+}
@@ -0,0 +1,265 @@
+//! PBKDF2-HMAC-SHA256 password-based key derivation (RFC 8018), on the CPU.
+//!
+//! A derivation runs thousands of HMAC iterations per block of output,
+//! which would keep the kernel busy for a long time if done at once.
+//! `Pbkdf2` runs them in batches of `batch_size` iterations, and waits for
+//! an alarm between two batches so that processes run in the meantime. A
+//! deferred call cannot be used for this: the kernel loop runs pending
+//! deferred calls before any process, so a deferred call that keeps setting
+//! itself again starves the processes.
+//!
+//! The salt is copied into the buffer given to `Pbkdf2::new()`, which must
+//! hold the salt and 4 more bytes.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let pbkdf2_virtual_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let pbkdf2 = static_init!(
+//!     capsules::pbkdf2::Pbkdf2<'static, VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>>,
+//!     capsules::pbkdf2::Pbkdf2::new(
+//!         pbkdf2_virtual_alarm,
+//!         static_init!([u8; 36], [0; 36]),
+//!         100
+//!     )
+//! );
+//! pbkdf2_virtual_alarm.set_client(pbkdf2);
+//! ```
+
+use crate::sha256::{HmacSha256, SHA256_DIGEST_SIZE};
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::time::{self, Frequency};
+
+/// The time between two batches, in milliseconds.
+const PAUSE: u32 = 1;
+
+pub trait Client {
+    /// The derivation started by `derive()` is done. On success, the first
+    /// `len` bytes of `key` hold the derived key.
+    fn derive_done(&self, result: Result<(), CryptoError>, key: &'static mut [u8]);
+}
+
+fn prf(hmac: &HmacSha256, data: &[u8]) -> [u8; SHA256_DIGEST_SIZE] {
+    let mut hmac = *hmac;
+    hmac.update(data);
+    hmac.finish()
+}
+
+/// The state of a derivation.
+#[derive(Clone, Copy)]
+struct Job {
+    /// The HMAC keyed with the password, with no data.
+    prf: HmacSha256,
+    iterations: u32,
+    salt_len: usize,
+    len: usize,
+    /// The index of the block being computed, from 1.
+    block: u32,
+    /// The number of iterations of the block done so far.
+    done: u32,
+    /// The last iteration, U_done.
+    u: [u8; SHA256_DIGEST_SIZE],
+    /// The xor of the iterations done so far.
+    t: [u8; SHA256_DIGEST_SIZE],
+}
+
+impl Job {
+    fn new(password: &[u8], iterations: u32, salt_len: usize, len: usize) -> Job {
+        Job {
+            prf: HmacSha256::new(password),
+            iterations,
+            salt_len,
+            len,
+            block: 1,
+            done: 0,
+            u: [0; SHA256_DIGEST_SIZE],
+            t: [0; SHA256_DIGEST_SIZE],
+        }
+    }
+
+    /// Run up to `budget` iterations, writing the blocks completed to
+    /// `key`. `salt` holds the salt, followed by room for the index of the
+    /// block. Returns whether the key is complete.
+    fn run(&mut self, salt: &mut [u8], key: &mut [u8], budget: u32) -> bool {
+        for _ in 0..budget {
+            if self.done == 0 {
+                // U_1 = PRF(P, S || INT(i))
+                let end = self.salt_len + 4;
+                salt[self.salt_len..end].copy_from_slice(&self.block.to_be_bytes());
+                self.u = prf(&self.prf, &salt[..end]);
+                self.t = self.u;
+            } else {
+                self.u = prf(&self.prf, &self.u);
+                for (t, u) in self.t.iter_mut().zip(self.u.iter()) {
+                    *t ^= *u;
+                }
+            }
+            self.done += 1;
+
+            if self.done == self.iterations {
+                let start = (self.block as usize - 1) * SHA256_DIGEST_SIZE;
+                let n = core::cmp::min(SHA256_DIGEST_SIZE, self.len - start);
+                key[start..start + n].copy_from_slice(&self.t[..n]);
+                if start + n == self.len {
+                    return true;
+                }
+                self.block += 1;
+                self.done = 0;
+            }
+        }
+        false
+    }
+}
+
+pub struct Pbkdf2<'a, A: time::Alarm<'a>> {
+    alarm: &'a A,
+    client: OptionalCell<&'a dyn Client>,
+    batch_size: u32,
+    salt: TakeCell<'static, [u8]>,
+    job: Cell<Job>,
+    /// The key being derived, present while a derivation is in progress.
+    key: TakeCell<'static, [u8]>,
+}
+
+impl<'a, A: time::Alarm<'a>> Pbkdf2<'a, A> {
+    pub fn new(alarm: &'a A, salt: &'static mut [u8], batch_size: u32) -> Pbkdf2<'a, A> {
+        Pbkdf2 {
+            alarm,
+            client: OptionalCell::empty(),
+            batch_size,
+            salt: TakeCell::new(salt),
+            job: Cell::new(Job::new(&[], 0, 0, 0)),
+            key: TakeCell::empty(),
+        }
+    }
+
+    pub fn set_client(&self, client: &'a dyn Client) {
+        self.client.set(client);
+    }
+
+    /// Derive `len` bytes of key into `key` from `password` and `salt`, with
+    /// `iterations` iterations per block. The password and the salt are
+    /// copied before this returns.
+    /// Returns `EngineBusy` if a derivation is in progress, and
+    /// `InvalidArgument` if `iterations` or `len` is 0, if `len` is longer
+    /// than `key`, or if the salt does not fit in the salt buffer.
+    pub fn derive(
+        &self,
+        password: &[u8],
+        salt: &[u8],
+        iterations: u32,
+        key: &'static mut [u8],
+        len: usize,
+    ) -> Result<(), (CryptoError, &'static mut [u8])> {
+        if self.key.is_some() {
+            return Err((CryptoError::EngineBusy, key));
+        }
+        if iterations == 0 || len == 0 || len > key.len() {
+            return Err((CryptoError::InvalidArgument, key));
+        }
+        let copied = self.salt.map_or(false, |buffer| {
+            if salt.len() + 4 > buffer.len() {
+                return false;
+            }
+            buffer[..salt.len()].copy_from_slice(salt);
+            true
+        });
+        if !copied {
+            return Err((CryptoError::InvalidArgument, key));
+        }
+
+        self.job
+            .set(Job::new(password, iterations, salt.len(), len));
+        self.key.replace(key);
+        self.schedule_batch();
+        Ok(())
+    }
+
+    fn schedule_batch(&self) {
+        let interval = PAUSE * <A::Frequency>::frequency() / 1000;
+        self.alarm
+            .set_alarm(self.alarm.now().wrapping_add(interval));
+    }
+}
+
+impl<'a, A: time::Alarm<'a>> time::AlarmClient for Pbkdf2<'a, A> {
+    fn fired(&self) {
+        if self.key.is_none() {
+            return;
+        }
+        let mut job = self.job.get();
+        let complete = self.salt.map_or(false, |salt| {
+            self.key
+                .map_or(false, |key| job.run(salt, key, self.batch_size))
+        });
+        if !complete {
+            self.job.set(job);
+            self.schedule_batch();
+            return;
+        }
+
+        // Wipe the password and the intermediate values
+        self.job.set(Job::new(&[], 0, 0, 0));
+        self.salt.map(|salt| salt.iter_mut().for_each(|b| *b = 0));
+        self.key.take().map(|key| {
+            self.client
+                .map(move |client| client.derive_done(Ok(()), key));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn derive(password: &[u8], salt: &[u8], iterations: u32, key: &mut [u8], batch: u32) {
+        let mut buffer = [0; 64];
+        buffer[..salt.len()].copy_from_slice(salt);
+        let mut job = Job::new(password, iterations, salt.len(), key.len());
+        while !job.run(&mut buffer, key, batch) {}
+    }
+
+    fn decode(hex: &str, out: &mut [u8]) {
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+    }
+
+    #[test]
+    fn one_iteration() {
+        let mut expected = [0; 64];
+        decode(
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+             49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783",
+            &mut expected,
+        );
+        // RFC 7914, section 11
+        let mut key = [0; 64];
+        derive(b"passwd", b"salt", 1, &mut key, 1);
+        assert_eq!(key[..], expected[..]);
+    }
+
+    #[test]
+    fn batches() {
+        let mut expected = [0; 40];
+        decode(
+            "348c89dbcbd32b2f32d814b8116e84cf2b17347ebc1800181c4e2a1fb8dd53e1c635518c7dac47e9",
+            &mut expected,
+        );
+        let password = b"passwordPASSWORDpassword";
+        let salt = b"saltSALTsaltSALTsaltSALTsaltSALTsalt";
+
+        // Batches that do not divide the iterations, or span blocks
+        for batch in [1000, 4096, 5000].iter() {
+            let mut key = [0; 40];
+            derive(password, salt, 4096, &mut key, *batch);
+            assert_eq!(key[..], expected[..]);
+        }
+    }
+}
@@ -9,9 +9,48 @@
 //!  - 'help' prints the available commands and arguments
 //!  - 'status' prints the current system status
 //!  - 'list' lists the current processes with their IDs and running state
+//!  - 'order' lists the processes in the order they were loaded and started,
+//!    with the processes each of them depends on
 //!  - 'stop n' stops the process with name n
 //!  - 'start n' starts the stopped process with name n
 //!  - 'fault n' forces the process with name n into a fault state
+//!  - 'metrics' prints the non-zero entries of the kernel event counters
+//!  - 'journal' prints the records of the error journal, and 'journal clear'
+//!    removes them
+//!  - 'bus' prints the captured SPI and I2C transactions, 'bus on' and
+//!    'bus off' start and stop capturing and 'bus clear' removes them. This
+//!    is only available if the board has set a capture with
+//!    `set_bus_capture()`
+//!  - 'reset' resets the chip, and 'bootloader' resets it into the
+//!    bootloader, so that it can be reflashed over the serial port. These
+//!    are only available if the board has set a `Reset` with `set_reset()`
+//!  - 'features' lists the optional capsules of the board, whether they were
+//!    compiled in, and roughly how much flash each of them takes. This is
+//!    only available if the board has set its features with `set_features()`
+//!  - 'uptime' prints the time since boot, the sources of the low and high
+//!    frequency clocks and whether they are calibrated, and the drift of the
+//!    RTC if the board has synchronized it with an external time reference.
+//!    This is only available if the board has set its `Clocks` with
+//!    `set_clocks()`
+//!  - 'latency n' measures the interrupt latency n times, 100 by default, and
+//!    prints the minimum, average and maximum when done. This is only
+//!    available if the board has set a `LatencyTest` with
+//!    `set_latency_test()`
+//!
+//! ### Locking
+//!
+//! A board can protect the commands that change the state of processes
+//! (`stop`, `start`, `fault`, `journal clear`, `reset` and `bootloader`) by
+//! giving the console an `Authenticator`
+//! with `set_authenticator()`. The console then starts locked and accepts two
+//! more commands:
+//!  - 'unlock' prints a new challenge, and 'unlock r' checks the hex encoded
+//!    response r to that challenge and unlocks the console if it is valid
+//!  - 'lock' locks the console again
+//!
+//! `help`, `status`, `list`, `order`, `metrics`, `journal`, `bus`,
+//! `features`, `uptime` and `latency` are always available, so that the console can be left enabled on deployed devices for
+//! diagnostics.
 //!
 //! ### `list` Command Fields:
 //!
@@ -109,11 +148,14 @@
 use core::cell::Cell;
 use core::cmp;
 use core::str;
+
+use crate::bus_capture::Capture;
 use kernel::capabilities::ProcessManagementCapability;
-use kernel::common::cells::TakeCell;
+use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::debug;
 use kernel::hil::uart;
 use kernel::introspection::KernelInfo;
+use kernel::journal;
 use kernel::Kernel;
 use kernel::ReturnCode;
 
@@ -123,9 +165,110 @@ pub static mut WRITE_BUF: [u8; 4] = [0; 4];
 // Since reads are byte-by-byte, to properly echo what's typed,
 // we can use a very small read buffer.
 pub static mut READ_BUF: [u8; 4] = [0; 4];
-// Commands can be up to 32 bytes long: since commands themselves are 4-5
-// characters, limiting arguments to 25 bytes or so seems fine for now.
-pub static mut COMMAND_BUF: [u8; 32] = [0; 32];
+// Commands can be up to 80 bytes long: commands themselves are 4-6
+// characters, and the longest argument is the hex encoded response to an
+// authentication challenge (64 characters for HMAC-SHA256).
+pub static mut COMMAND_BUF: [u8; 80] = [0; 80];
+
+/// Maximum length in bytes of a response to an authentication challenge.
+const MAX_RESPONSE_LEN: usize = 32;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Verifies that the person at the console is allowed to use the privileged
+/// commands, using a challenge-response protocol.
+pub trait Authenticator<'a> {
+    fn set_client(&self, client: &'a dyn AuthenticatorClient);
+
+    /// Generate a new challenge. The challenge is passed to
+    /// `AuthenticatorClient::challenge_ready`.
+    fn start_challenge(&self) -> ReturnCode;
+
+    /// Check `response` against the last challenge. The result is passed to
+    /// `AuthenticatorClient::verified`. A challenge can only be answered
+    /// once.
+    fn verify(&self, response: &[u8]) -> ReturnCode;
+}
+
+pub trait AuthenticatorClient {
+    /// Called when a new challenge has been generated.
+    fn challenge_ready(&self, challenge: &[u8]);
+
+    /// Called when a response has been checked.
+    fn verified(&self, valid: bool);
+}
+
+/// An optional part of the board, listed by the `features` command. Boards
+/// usually enable it with a Cargo feature, and set `enabled` with `cfg!()`.
+pub struct Feature {
+    pub name: &'static str,
+    pub enabled: bool,
+    /// Approximate flash taken by the feature when it is enabled, in bytes.
+    pub flash_size: usize,
+}
+
+/// Resets the chip for the `reset` and `bootloader` commands.
+pub trait Reset {
+    /// Perform a soft reset. Does not return on success.
+    fn reset(&self) -> ReturnCode;
+
+    /// Reset into the bootloader. Does not return on success, returns
+    /// `ENOSUPPORT` if the board has no bootloader to reset into.
+    fn reset_to_bootloader(&self) -> ReturnCode;
+}
+
+/// The state of a clock, printed by the `uptime` command.
+pub struct ClockStatus {
+    /// The source of the clock, e.g. "crystal" or "RC oscillator".
+    pub source: &'static str,
+    pub running: bool,
+    /// Whether the clock is calibrated, or `None` if its source needs no
+    /// calibration.
+    pub calibrated: Option<bool>,
+}
+
+/// Reports the time since boot and the state of the clocks for the `uptime`
+/// command.
+pub trait Clocks {
+    /// The time since boot, in microseconds.
+    fn uptime_us(&self) -> u64;
+
+    fn low_frequency_clock(&self) -> ClockStatus;
+
+    fn high_frequency_clock(&self) -> ClockStatus;
+
+    /// How much faster the RTC runs than an external time reference, in
+    /// parts per million, or `None` if it was not synchronized with one.
+    fn rtc_drift_ppm(&self) -> Option<i32>;
+}
+
+/// Measures the interrupt latency for the `latency` command.
+pub trait LatencyTest {
+    /// Start measuring the latency `samples` times. The results are printed
+    /// with `debug!()` when all the samples are taken.
+    fn start(&self, samples: usize) -> ReturnCode;
+}
+
+/// Number of samples of the `latency` command without argument.
+const DEFAULT_LATENCY_SAMPLES: usize = 100;
+
+fn print_clock(name: &str, clock: ClockStatus) {
+    let running = if clock.running { "running" } else { "stopped" };
+    match clock.calibrated {
+        None => debug!("{} clock: {}, {}", name, clock.source, running),
+        Some(calibrated) => debug!(
+            "{} clock: {}, {}, {}",
+            name,
+            clock.source,
+            running,
+            if calibrated {
+                "calibrated"
+            } else {
+                "not calibrated"
+            }
+        ),
+    }
+}
 
 pub struct ProcessConsole<'a, C: ProcessManagementCapability> {
     uart: &'a dyn uart::UartData<'a>,
@@ -145,6 +288,26 @@ pub struct ProcessConsole<'a, C: ProcessManagementCapability> {
     execute: Cell<bool>,
     kernel: &'static Kernel,
     capability: C,
+
+    /// When set, privileged commands are only accepted once the console has
+    /// been unlocked.
+    authenticator: OptionalCell<&'a dyn Authenticator<'a>>,
+    unlocked: Cell<bool>,
+
+    /// Recorded bus transactions shown by the `bus` command.
+    bus_capture: OptionalCell<&'a dyn Capture>,
+
+    /// Used by the `reset` and `bootloader` commands.
+    reset: OptionalCell<&'a dyn Reset>,
+
+    /// Listed by the `features` command.
+    features: OptionalCell<&'a [Feature]>,
+
+    /// Used by the `uptime` command.
+    clocks: OptionalCell<&'a dyn Clocks>,
+
+    /// Used by the `latency` command.
+    latency_test: OptionalCell<&'a dyn LatencyTest>,
 }
 
 impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
@@ -168,9 +331,92 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
             execute: Cell::new(false),
             kernel: kernel,
             capability: capability,
+            authenticator: OptionalCell::empty(),
+            unlocked: Cell::new(false),
+            bus_capture: OptionalCell::empty(),
+            reset: OptionalCell::empty(),
+            features: OptionalCell::empty(),
+            clocks: OptionalCell::empty(),
+            latency_test: OptionalCell::empty(),
         }
     }
 
+    /// Require authentication for the commands that control processes. The
+    /// console is locked until a valid `unlock` response is received.
+    pub fn set_authenticator(&self, authenticator: &'a dyn Authenticator<'a>) {
+        self.authenticator.set(authenticator);
+        self.unlocked.set(false);
+    }
+
+    /// Enable the `bus` command, which shows the transactions recorded by
+    /// `capture`.
+    pub fn set_bus_capture(&self, capture: &'a dyn Capture) {
+        self.bus_capture.set(capture);
+    }
+
+    /// Enable the `reset` and `bootloader` commands.
+    pub fn set_reset(&self, reset: &'a dyn Reset) {
+        self.reset.set(reset);
+    }
+
+    /// Enable the `features` command, which lists `features`.
+    pub fn set_features(&self, features: &'a [Feature]) {
+        self.features.set(features);
+    }
+
+    /// Enable the `uptime` command, which reports the state of `clocks`.
+    pub fn set_clocks(&self, clocks: &'a dyn Clocks) {
+        self.clocks.set(clocks);
+    }
+
+    /// Enable the `latency` command, which runs `latency_test`.
+    pub fn set_latency_test(&self, latency_test: &'a dyn LatencyTest) {
+        self.latency_test.set(latency_test);
+    }
+
+    /// Returns true if privileged commands are allowed, printing a hint if
+    /// they are not.
+    fn check_unlocked(&self) -> bool {
+        let allowed = self.authenticator.is_none() || self.unlocked.get();
+        if !allowed {
+            debug!("Console is locked, use 'unlock' first.");
+        }
+        allowed
+    }
+
+    fn print_commands(&self) {
+        if self.authenticator.is_some() {
+            debug!(
+                "Valid commands are: help status uptime latency list order metrics journal bus features stop start fault reset bootloader lock unlock"
+            );
+        } else {
+            debug!(
+                "Valid commands are: help status uptime latency list order metrics journal bus features stop start fault reset bootloader"
+            );
+        }
+    }
+
+    fn unlock(&self, argument: Option<&str>) {
+        self.authenticator.map_or_else(
+            || debug!("Console is not locked."),
+            |authenticator| {
+                let res = match argument {
+                    None => authenticator.start_challenge(),
+                    Some(hex) => {
+                        let mut response = [0u8; MAX_RESPONSE_LEN];
+                        match parse_hex(hex, &mut response) {
+                            Some(len) => authenticator.verify(&response[..len]),
+                            None => ReturnCode::EINVAL,
+                        }
+                    }
+                };
+                if res != ReturnCode::SUCCESS {
+                    debug!("Unlock failed: {:?}", res);
+                }
+            },
+        );
+    }
+
     pub fn start(&self) -> ReturnCode {
         if self.running.get() == false {
             self.rx_buffer.take().map(|buffer| {
@@ -205,8 +451,18 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                         let clean_str = s.trim();
                         if clean_str.starts_with("help") {
                             debug!("Welcome to the process console.");
-                            debug!("Valid commands are: help status list stop start fault");
+                            self.print_commands();
+                        } else if clean_str.starts_with("unlock") {
+                            self.unlock(clean_str.split_whitespace().nth(1));
+                        } else if clean_str.starts_with("lock") {
+                            if self.authenticator.is_some() {
+                                self.unlocked.set(false);
+                                debug!("Console locked.");
+                            }
                         } else if clean_str.starts_with("start") {
+                            if !self.check_unlocked() {
+                                return;
+                            }
                             let argument = clean_str.split_whitespace().nth(1);
                             argument.map(|name| {
                                 self.kernel.process_each_capability(
@@ -221,6 +477,9 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                 );
                             });
                         } else if clean_str.starts_with("stop") {
+                            if !self.check_unlocked() {
+                                return;
+                            }
                             let argument = clean_str.split_whitespace().nth(1);
                             argument.map(|name| {
                                 self.kernel.process_each_capability(
@@ -235,6 +494,9 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                 );
                             });
                         } else if clean_str.starts_with("fault") {
+                            if !self.check_unlocked() {
+                                return;
+                            }
                             let argument = clean_str.split_whitespace().nth(1);
                             argument.map(|name| {
                                 self.kernel.process_each_capability(
@@ -271,6 +533,157 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                         grants_total
                                     );
                                 });
+                        } else if clean_str.starts_with("order") {
+                            let index = Cell::new(0);
+                            self.kernel
+                                .process_each_capability(&self.capability, |proc| {
+                                    debug!(" {:2}  {}", index.get(), proc.get_process_name());
+                                    let mut dependency_index = 0;
+                                    while let Some(dependency) = proc.get_dependency(dependency_index) {
+                                        debug!("       after {}", dependency);
+                                        dependency_index += 1;
+                                    }
+                                    index.set(index.get() + 1);
+                                });
+                        } else if clean_str.starts_with("metrics") {
+                            let info: KernelInfo = KernelInfo::new(self.kernel);
+                            info.counter_each(&self.capability, |counter| {
+                                for index in 0..counter.len() {
+                                    let value = counter.get_index(index).unwrap_or(0);
+                                    if value != 0 {
+                                        debug!(
+                                            "{}[{:#x}]: {}",
+                                            counter.name(),
+                                            counter.key(index).unwrap_or(index),
+                                            value
+                                        );
+                                    }
+                                }
+                            });
+                        } else if clean_str.starts_with("journal") {
+                            match journal::journal() {
+                                None => debug!("No journal."),
+                                Some(journal) => {
+                                    if clean_str.split_whitespace().nth(1) == Some("clear") {
+                                        if !self.check_unlocked() {
+                                            return;
+                                        }
+                                        debug!("Journal cleared: {:?}", journal.clear());
+                                    } else {
+                                        for index in 0..journal.len() {
+                                            match journal.get(index) {
+                                                Some(record) => debug!("{}", record),
+                                                None => {
+                                                    debug!("Journal busy, try again.");
+                                                    break;
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        } else if clean_str.starts_with("bus") {
+                            self.bus_capture.map_or_else(
+                                || debug!("No bus capture."),
+                                |capture| match clean_str.split_whitespace().nth(1) {
+                                    Some("on") => {
+                                        capture.set_enabled(true);
+                                        debug!("Bus capture on.");
+                                    }
+                                    Some("off") => {
+                                        capture.set_enabled(false);
+                                        debug!("Bus capture off.");
+                                    }
+                                    Some("clear") => capture.clear(),
+                                    _ => {
+                                        if !capture.is_enabled() {
+                                            debug!("Bus capture is off, use 'bus on'.");
+                                        }
+                                        for index in 0..capture.len() {
+                                            capture.get(index).map(|transaction| {
+                                                debug!("{}", transaction);
+                                            });
+                                        }
+                                    }
+                                },
+                            );
+                        } else if clean_str.starts_with("features") {
+                            self.features.map_or_else(
+                                || debug!("No feature list."),
+                                |features| {
+                                    let mut total = 0;
+                                    for feature in features.iter() {
+                                        if feature.enabled {
+                                            total += feature.flash_size;
+                                            debug!(
+                                                " {:<20}on   ~{} kB",
+                                                feature.name,
+                                                (feature.flash_size + 512) / 1024
+                                            );
+                                        } else {
+                                            debug!(" {:<20}off", feature.name);
+                                        }
+                                    }
+                                    debug!("Optional features: ~{} kB of flash", (total + 512) / 1024);
+                                },
+                            );
+                        } else if clean_str.starts_with("reset") {
+                            if !self.check_unlocked() {
+                                return;
+                            }
+                            self.reset.map_or_else(
+                                || debug!("Reset not supported."),
+                                |reset| debug!("Reset failed: {:?}", reset.reset()),
+                            );
+                        } else if clean_str.starts_with("bootloader") {
+                            if !self.check_unlocked() {
+                                return;
+                            }
+                            self.reset.map_or_else(
+                                || debug!("Reset not supported."),
+                                |reset| debug!("Reset failed: {:?}", reset.reset_to_bootloader()),
+                            );
+                        } else if clean_str.starts_with("uptime") {
+                            self.clocks.map_or_else(
+                                || debug!("No clock information."),
+                                |clocks| {
+                                    let ms = clocks.uptime_us() / 1000;
+                                    let s = ms / 1000;
+                                    debug!(
+                                        "Uptime: {}d {:02}:{:02}:{:02}.{:03}",
+                                        s / 86400,
+                                        s / 3600 % 24,
+                                        s / 60 % 60,
+                                        s % 60,
+                                        ms % 1000
+                                    );
+                                    print_clock("LF", clocks.low_frequency_clock());
+                                    print_clock("HF", clocks.high_frequency_clock());
+                                    match clocks.rtc_drift_ppm() {
+                                        Some(ppm) => debug!("RTC drift: {:+} ppm", ppm),
+                                        None => debug!("RTC drift: unknown, not synchronized"),
+                                    }
+                                },
+                            );
+                        } else if clean_str.starts_with("latency") {
+                            self.latency_test.map_or_else(
+                                || debug!("No latency test."),
+                                |latency_test| {
+                                    let samples = match clean_str.split_whitespace().nth(1) {
+                                        None => Some(DEFAULT_LATENCY_SAMPLES),
+                                        Some(n) => n.parse::<usize>().ok(),
+                                    };
+                                    match samples {
+                                        Some(samples) => {
+                                            let res = latency_test.start(samples);
+                                            if res != ReturnCode::SUCCESS {
+                                                debug!("Latency test failed: {:?}", res);
+                                            }
+                                        }
+                                        None => debug!("Usage: latency [samples]"),
+                                    }
+                                },
+                            );
                         } else if clean_str.starts_with("status") {
                             let info: KernelInfo = KernelInfo::new(self.kernel);
                             debug!(
@@ -285,8 +698,9 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                 "Timeslice expirations: {}",
                                 info.timeslice_expirations(&self.capability)
                             );
+                            debug!("Timeslice: {}us", self.kernel.timeslice_us());
                         } else {
-                            debug!("Valid commands are: help status list stop start fault");
+                            self.print_commands();
                         }
                     }
                     Err(_e) => debug!("Invalid command: {:?}", command),
@@ -328,6 +742,44 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
     }
 }
 
+/// Decode a hex string into `out`, returning the number of bytes written.
+fn parse_hex(hex: &str, out: &mut [u8]) -> Option<usize> {
+    let hex = hex.as_bytes();
+    if hex.len() % 2 != 0 || hex.len() / 2 > out.len() {
+        return None;
+    }
+    for (i, pair) in hex.chunks(2).enumerate() {
+        let high = (pair[0] as char).to_digit(16)?;
+        let low = (pair[1] as char).to_digit(16)?;
+        out[i] = (high << 4 | low) as u8;
+    }
+    Some(hex.len() / 2)
+}
+
+impl<'a, C: ProcessManagementCapability> AuthenticatorClient for ProcessConsole<'a, C> {
+    fn challenge_ready(&self, challenge: &[u8]) {
+        let mut hex = [0u8; 2 * MAX_RESPONSE_LEN];
+        let len = cmp::min(challenge.len(), MAX_RESPONSE_LEN);
+        for (i, byte) in challenge[..len].iter().enumerate() {
+            hex[2 * i] = HEX_DIGITS[(byte >> 4) as usize];
+            hex[2 * i + 1] = HEX_DIGITS[(byte & 0xf) as usize];
+        }
+        debug!(
+            "Challenge: {}",
+            str::from_utf8(&hex[..2 * len]).unwrap_or("")
+        );
+    }
+
+    fn verified(&self, valid: bool) {
+        self.unlocked.set(valid);
+        if valid {
+            debug!("Console unlocked.");
+        } else {
+            debug!("Invalid response, console locked.");
+        }
+    }
+}
+
 impl<'a, C: ProcessManagementCapability> uart::TransmitClient for ProcessConsole<'a, C> {
     fn transmitted_buffer(&self, buffer: &'static mut [u8], _tx_len: usize, _rcode: ReturnCode) {
         self.tx_buffer.replace(buffer);
@@ -23,6 +23,8 @@ use kernel::hil::entropy;
 use kernel::hil::entropy::{Entropy32, Entropy8};
 use kernel::hil::rng;
 use kernel::hil::rng::{Client, Continue, Random, Rng};
+use kernel::journal;
+use kernel::system_events;
 use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
 
 /// Syscall driver number.
@@ -57,8 +59,15 @@ impl rng::Client for RngDriver<'_> {
     fn randomness_available(
         &self,
         randomness: &mut dyn Iterator<Item = u32>,
-        _error: ReturnCode,
+        error: ReturnCode,
     ) -> rng::Continue {
+        if error != ReturnCode::SUCCESS {
+            journal::record(journal::Event::RngFailure, isize::from(error) as u32);
+            system_events::notify(
+                system_events::Event::RngDegraded,
+                isize::from(error) as usize,
+            );
+        }
         let mut done = true;
         for cntr in self.apps.iter() {
             cntr.enter(|app, _| {
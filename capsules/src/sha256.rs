@@ -0,0 +1,838 @@
+//! Software implementation of SHA-256, SHA-224 and HMAC-SHA-256.
+//!
+//! `Sha256` and `HmacSha256` hash data synchronously on the CPU, for capsules
+//! that need a hash as a building block, such as the mixing of the entropy
+//! pool, whether or not the chip has a hash engine. They hold no buffers and
+//! never call back.
+//!
+//! `SoftwareSha256` implements the `Digest`, `DigestVerify`, `HMACSha256`
+//! and `DigestSaveRestore` HILs on top of them, so that the HMAC and digest
+//! capsules can be used on chips without a hash engine. Each request is
+//! processed at once when it is made, and the client is called from a
+//! deferred call, as it would be from the interrupt of a hardware engine.
+//!
+//! It also computes SHA-224 digests through `Digest<'a, [u8; 28]>`, which
+//! has its own client, once `set_mode_sha224()` is called. The data of a
+//! SHA-224 hash is added through either interface, and its callbacks go to
+//! the SHA-224 client. A SHA-224 hash cannot be saved in a `DigestContext`.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let mut sha = capsules::sha256::Sha256::new();
+//! sha.update(b"abc");
+//! let digest: [u8; 32] = sha.finish();
+//!
+//! let sha256 = static_init!(
+//!     capsules::sha256::SoftwareSha256<'static>,
+//!     capsules::sha256::SoftwareSha256::new(dynamic_deferred_caller)
+//! );
+//! sha256.initialize_callback_handle(
+//!     dynamic_deferred_caller
+//!         .register(sha256)
+//!         .expect("no deferred call slot available for software SHA-256"),
+//! );
+//! digest::Digest::set_client(sha256, mux_hmac);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::dynamic_deferred_call::{
+    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
+};
+use kernel::common::leasable_buffer::{LeasableBuffer, ReadOnlyLeasableBuffer};
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::digest;
+
+pub const SHA256_BLOCK_SIZE: usize = 64;
+pub const SHA256_DIGEST_SIZE: usize = 32;
+pub const SHA224_DIGEST_SIZE: usize = 28;
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const SHA224_INITIAL_STATE: [u32; 8] = [
+    0xc1059ed8, 0x367cd507, 0x3070dd17, 0xf70e5939, 0xffc00b31, 0x68581511, 0x64f98fa7, 0xbefa4fa4,
+];
+
+#[derive(Clone, Copy)]
+pub struct Sha256 {
+    state: [u32; 8],
+    /// Data not yet compressed, less than a block.
+    buffer: [u8; SHA256_BLOCK_SIZE],
+    /// Total length of the data, in bytes.
+    length: u64,
+}
+
+impl Default for Sha256 {
+    fn default() -> Sha256 {
+        Sha256::new()
+    }
+}
+
+impl Sha256 {
+    pub const fn new() -> Sha256 {
+        Sha256::with_state(INITIAL_STATE)
+    }
+
+    /// SHA-224 is SHA-256 with another initial state, truncated to its first
+    /// `SHA224_DIGEST_SIZE` bytes.
+    pub const fn new_sha224() -> Sha256 {
+        Sha256::with_state(SHA224_INITIAL_STATE)
+    }
+
+    const fn with_state(state: [u32; 8]) -> Sha256 {
+        Sha256 {
+            state,
+            buffer: [0; SHA256_BLOCK_SIZE],
+            length: 0,
+        }
+    }
+
+    /// Number of bytes hashed so far.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        let buffered = (self.length % SHA256_BLOCK_SIZE as u64) as usize;
+        self.length += data.len() as u64;
+
+        if buffered > 0 {
+            let n = core::cmp::min(data.len(), SHA256_BLOCK_SIZE - buffered);
+            self.buffer[buffered..buffered + n].copy_from_slice(&data[..n]);
+            data = &data[n..];
+            if buffered + n < SHA256_BLOCK_SIZE {
+                return;
+            }
+            let block = self.buffer;
+            compress(&mut self.state, &block);
+        }
+
+        let mut blocks = data.chunks_exact(SHA256_BLOCK_SIZE);
+        for block in &mut blocks {
+            compress(&mut self.state, block);
+        }
+        let rest = blocks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+    }
+
+    /// Pad the data and return the final state, which is the SHA-256 digest.
+    /// The SHA-224 digest is its first `SHA224_DIGEST_SIZE` bytes.
+    pub fn finish(mut self) -> [u8; SHA256_DIGEST_SIZE] {
+        let bit_length = self.length.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.length % SHA256_BLOCK_SIZE as u64 != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bit_length.to_be_bytes());
+
+        let mut digest = [0; SHA256_DIGEST_SIZE];
+        for (bytes, word) in digest.chunks_mut(4).zip(self.state.iter()) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+    for (i, bytes) in block.chunks(4).enumerate() {
+        w[i] = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h].iter()) {
+        *word = word.wrapping_add(*value);
+    }
+}
+
+/// HMAC-SHA-256, as defined in RFC 2104.
+///
+/// Both hashes are keyed when the HMAC is created: the inner hash has
+/// absorbed the key xor ipad and then hashes the message, and the outer hash
+/// has absorbed the key xor opad and only hashes the inner digest. Copies of
+/// a new `HmacSha256` therefore compute HMACs with the same key without
+/// hashing the key blocks again.
+#[derive(Clone, Copy)]
+pub struct HmacSha256 {
+    inner: Sha256,
+    outer: Sha256,
+    /// The key, hashed if it is longer than a block, padded with zeros to a
+    /// block.
+    key: [u8; SHA256_BLOCK_SIZE],
+}
+
+impl HmacSha256 {
+    pub fn new(key: &[u8]) -> HmacSha256 {
+        let mut padded_key = [0; SHA256_BLOCK_SIZE];
+        if key.len() > SHA256_BLOCK_SIZE {
+            let mut sha = Sha256::new();
+            sha.update(key);
+            padded_key[..SHA256_DIGEST_SIZE].copy_from_slice(&sha.finish());
+        } else {
+            padded_key[..key.len()].copy_from_slice(key);
+        }
+        HmacSha256::with_key(padded_key)
+    }
+
+    fn with_key(key: [u8; SHA256_BLOCK_SIZE]) -> HmacSha256 {
+        let mut inner = Sha256::new();
+        inner.update(&xor_pad(&key, 0x36));
+        HmacSha256::resume(key, inner)
+    }
+
+    /// Continue an HMAC with `key` whose inner hash is `inner`, which must
+    /// have absorbed at least the key block.
+    fn resume(key: [u8; SHA256_BLOCK_SIZE], inner: Sha256) -> HmacSha256 {
+        let mut outer = Sha256::new();
+        outer.update(&xor_pad(&key, 0x5c));
+        HmacSha256 { inner, outer, key }
+    }
+
+    /// Number of bytes of the message hashed so far, not counting the key
+    /// block.
+    pub fn len(&self) -> u64 {
+        self.inner.len() - SHA256_BLOCK_SIZE as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub fn finish(self) -> [u8; SHA256_DIGEST_SIZE] {
+        let mut outer = self.outer;
+        outer.update(&self.inner.finish());
+        outer.finish()
+    }
+}
+
+fn xor_pad(key: &[u8; SHA256_BLOCK_SIZE], pad: u8) -> [u8; SHA256_BLOCK_SIZE] {
+    let mut padded = *key;
+    padded.iter_mut().for_each(|b| *b ^= pad);
+    padded
+}
+
+#[derive(Clone, Copy)]
+enum Hash {
+    Sha256(Sha256),
+    Sha224(Sha256),
+    Hmac(HmacSha256),
+}
+
+impl Hash {
+    /// The same hash, with no data.
+    fn restart(&self) -> Hash {
+        match self {
+            Hash::Sha256(_) => Hash::Sha256(Sha256::new()),
+            Hash::Sha224(_) => Hash::Sha224(Sha256::new_sha224()),
+            Hash::Hmac(hmac) => Hash::Hmac(HmacSha256::with_key(hmac.key)),
+        }
+    }
+}
+
+pub struct SoftwareSha256<'a> {
+    client: OptionalCell<&'a dyn digest::Client<'a, [u8; SHA256_DIGEST_SIZE]>>,
+    verify_client: OptionalCell<&'a dyn digest::ClientVerify<'a, [u8; SHA256_DIGEST_SIZE]>>,
+    sha224_client: OptionalCell<&'a dyn digest::Client<'a, [u8; SHA224_DIGEST_SIZE]>>,
+    deferred_caller: &'a DynamicDeferredCall,
+    handle: OptionalCell<DeferredCallHandle>,
+
+    hash: Cell<Hash>,
+
+    /// The buffers of a finished operation, returned from the deferred call.
+    data: TakeCell<'static, [u8]>,
+    readonly_data: OptionalCell<&'static [u8]>,
+    digest: TakeCell<'static, [u8; SHA256_DIGEST_SIZE]>,
+    sha224_digest: TakeCell<'static, [u8; SHA224_DIGEST_SIZE]>,
+    /// The result of a finished `verify()`, whose buffer is in `digest`.
+    verified: OptionalCell<bool>,
+}
+
+impl<'a> SoftwareSha256<'a> {
+    pub fn new(deferred_caller: &'a DynamicDeferredCall) -> SoftwareSha256<'a> {
+        SoftwareSha256 {
+            client: OptionalCell::empty(),
+            verify_client: OptionalCell::empty(),
+            sha224_client: OptionalCell::empty(),
+            deferred_caller,
+            handle: OptionalCell::empty(),
+            hash: Cell::new(Hash::Sha256(Sha256::new())),
+            data: TakeCell::empty(),
+            readonly_data: OptionalCell::empty(),
+            digest: TakeCell::empty(),
+            sha224_digest: TakeCell::empty(),
+            verified: OptionalCell::empty(),
+        }
+    }
+
+    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
+        self.handle.replace(handle);
+    }
+
+    fn busy(&self) -> bool {
+        self.data.is_some()
+            || self.readonly_data.is_some()
+            || self.digest.is_some()
+            || self.sha224_digest.is_some()
+    }
+
+    fn is_sha224(&self) -> bool {
+        match self.hash.get() {
+            Hash::Sha224(_) => true,
+            _ => false,
+        }
+    }
+
+    fn update(&self, data: &[u8]) {
+        let mut hash = self.hash.get();
+        match hash {
+            Hash::Sha256(ref mut sha) | Hash::Sha224(ref mut sha) => sha.update(data),
+            Hash::Hmac(ref mut hmac) => hmac.update(data),
+        }
+        self.hash.set(hash);
+    }
+
+    /// Finish the hash, and start a new one in the same mode. Returns `None`
+    /// for a SHA-224 hash, whose digest is shorter.
+    fn finish(&self) -> Option<[u8; SHA256_DIGEST_SIZE]> {
+        let hash = self.hash.get();
+        let digest = match hash {
+            Hash::Sha256(sha) => sha.finish(),
+            Hash::Hmac(hmac) => hmac.finish(),
+            Hash::Sha224(_) => return None,
+        };
+        self.hash.set(hash.restart());
+        Some(digest)
+    }
+
+    /// Finish a SHA-224 hash, and start a new one. Returns `None` in the
+    /// other modes.
+    fn finish_sha224(&self) -> Option<[u8; SHA224_DIGEST_SIZE]> {
+        let hash = self.hash.get();
+        let sha = match hash {
+            Hash::Sha224(sha) => sha,
+            _ => return None,
+        };
+        self.hash.set(hash.restart());
+        let mut digest = [0; SHA224_DIGEST_SIZE];
+        digest.copy_from_slice(&sha.finish()[..SHA224_DIGEST_SIZE]);
+        Some(digest)
+    }
+
+    fn schedule_callback(&self) {
+        self.handle.map(|handle| self.deferred_caller.set(*handle));
+    }
+}
+
+impl<'a> digest::Digest<'a, [u8; SHA256_DIGEST_SIZE]> for SoftwareSha256<'a> {
+    fn set_client(&'a self, client: &'a dyn digest::Client<'a, [u8; SHA256_DIGEST_SIZE]>) {
+        self.client.set(client);
+    }
+
+    fn add_data(
+        &self,
+        data: LeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static mut [u8])> {
+        if self.busy() {
+            return Err((CryptoError::EngineBusy, data.take()));
+        }
+
+        let len = data.len();
+        self.update(&data[..]);
+        self.data.replace(data.take());
+        self.schedule_callback();
+        Ok(len)
+    }
+
+    fn add_readonly_data(
+        &self,
+        data: ReadOnlyLeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static [u8])> {
+        if self.busy() {
+            return Err((CryptoError::EngineBusy, data.take()));
+        }
+
+        let len = data.len();
+        self.update(&data[..]);
+        self.readonly_data.set(data.take());
+        self.schedule_callback();
+        Ok(len)
+    }
+
+    fn run(
+        &'a self,
+        digest: &'static mut [u8; SHA256_DIGEST_SIZE],
+    ) -> Result<(), (CryptoError, &'static mut [u8; SHA256_DIGEST_SIZE])> {
+        if self.busy() {
+            return Err((CryptoError::EngineBusy, digest));
+        }
+
+        match self.finish() {
+            Some(result) => *digest = result,
+            None => return Err((CryptoError::InvalidArgument, digest)),
+        }
+        self.digest.replace(digest);
+        self.schedule_callback();
+        Ok(())
+    }
+
+    fn clear_data(&self) {
+        self.hash.set(Hash::Sha256(Sha256::new()));
+    }
+
+    fn cancel(&self) -> digest::Cancelled<[u8; SHA256_DIGEST_SIZE]> {
+        digest::Digest::<[u8; SHA256_DIGEST_SIZE]>::clear_data(self);
+        self.verified.clear();
+        digest::Cancelled {
+            data: self.data.take(),
+            readonly_data: self.readonly_data.take(),
+            digest: self.digest.take(),
+            chain: None,
+        }
+    }
+}
+
+impl<'a> digest::DigestVerify<'a, [u8; SHA256_DIGEST_SIZE]> for SoftwareSha256<'a> {
+    fn set_verify_client(
+        &'a self,
+        client: &'a dyn digest::ClientVerify<'a, [u8; SHA256_DIGEST_SIZE]>,
+    ) {
+        self.verify_client.set(client);
+    }
+
+    fn verify(
+        &'a self,
+        compare: &'static mut [u8; SHA256_DIGEST_SIZE],
+    ) -> Result<(), (CryptoError, &'static mut [u8; SHA256_DIGEST_SIZE])> {
+        if self.busy() {
+            return Err((CryptoError::EngineBusy, compare));
+        }
+
+        let result = match self.finish() {
+            Some(result) => result,
+            None => return Err((CryptoError::InvalidArgument, compare)),
+        };
+        // Look at every byte, whatever the first one that differs
+        let diff = result
+            .iter()
+            .zip(compare.iter())
+            .fold(0, |diff, (a, b)| diff | (a ^ b));
+        self.verified.set(diff == 0);
+        self.digest.replace(compare);
+        self.schedule_callback();
+        Ok(())
+    }
+}
+
+impl digest::HMACSha256 for SoftwareSha256<'_> {
+    fn set_mode_hmacsha256(&self, key: &[u8]) -> Result<(), CryptoError> {
+        if self.busy() {
+            return Err(CryptoError::EngineBusy);
+        }
+        self.hash.set(Hash::Hmac(HmacSha256::new(key)));
+        Ok(())
+    }
+}
+
+impl digest::Sha256 for SoftwareSha256<'_> {
+    fn set_mode_sha256(&self) -> Result<(), CryptoError> {
+        if self.busy() {
+            return Err(CryptoError::EngineBusy);
+        }
+        self.hash.set(Hash::Sha256(Sha256::new()));
+        Ok(())
+    }
+}
+
+impl digest::Sha224 for SoftwareSha256<'_> {
+    fn set_mode_sha224(&self) -> Result<(), CryptoError> {
+        if self.busy() {
+            return Err(CryptoError::EngineBusy);
+        }
+        self.hash.set(Hash::Sha224(Sha256::new_sha224()));
+        Ok(())
+    }
+}
+
+impl<'a> digest::Digest<'a, [u8; SHA224_DIGEST_SIZE]> for SoftwareSha256<'a> {
+    fn set_client(&'a self, client: &'a dyn digest::Client<'a, [u8; SHA224_DIGEST_SIZE]>) {
+        self.sha224_client.set(client);
+    }
+
+    fn add_data(
+        &self,
+        data: LeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static mut [u8])> {
+        digest::Digest::<[u8; SHA256_DIGEST_SIZE]>::add_data(self, data)
+    }
+
+    fn add_readonly_data(
+        &self,
+        data: ReadOnlyLeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static [u8])> {
+        digest::Digest::<[u8; SHA256_DIGEST_SIZE]>::add_readonly_data(self, data)
+    }
+
+    /// Returns `InvalidArgument` unless `set_mode_sha224()` was called.
+    fn run(
+        &'a self,
+        digest: &'static mut [u8; SHA224_DIGEST_SIZE],
+    ) -> Result<(), (CryptoError, &'static mut [u8; SHA224_DIGEST_SIZE])> {
+        if self.busy() {
+            return Err((CryptoError::EngineBusy, digest));
+        }
+
+        match self.finish_sha224() {
+            Some(result) => *digest = result,
+            None => return Err((CryptoError::InvalidArgument, digest)),
+        }
+        self.sha224_digest.replace(digest);
+        self.schedule_callback();
+        Ok(())
+    }
+
+    /// Start a new SHA-224 hash.
+    fn clear_data(&self) {
+        self.hash.set(Hash::Sha224(Sha256::new_sha224()));
+    }
+
+    fn cancel(&self) -> digest::Cancelled<[u8; SHA224_DIGEST_SIZE]> {
+        digest::Digest::<[u8; SHA224_DIGEST_SIZE]>::clear_data(self);
+        digest::Cancelled {
+            data: self.data.take(),
+            readonly_data: self.readonly_data.take(),
+            digest: self.sha224_digest.take(),
+            chain: None,
+        }
+    }
+}
+
+impl digest::DigestSaveRestore for SoftwareSha256<'_> {
+    fn save_context(&self, context: &mut digest::DigestContext) -> Result<(), CryptoError> {
+        if self.busy() {
+            return Err(CryptoError::EngineBusy);
+        }
+
+        let (sha, key) = match self.hash.get() {
+            Hash::Sha256(sha) => (sha, None),
+            Hash::Hmac(hmac) => (hmac.inner, Some(hmac.key)),
+            // It would be restored as a SHA-256 hash
+            Hash::Sha224(_) => return Err(CryptoError::NotSupported),
+        };
+        context.state = sha.state;
+        context.length = sha.length;
+        context.pending = sha.buffer;
+        context.pending_len = (sha.length % SHA256_BLOCK_SIZE as u64) as usize;
+        context.hmac = key.is_some();
+        context.key = key.unwrap_or([0; SHA256_BLOCK_SIZE]);
+        Ok(())
+    }
+
+    fn restore_context(&self, context: &digest::DigestContext) -> Result<(), CryptoError> {
+        if self.busy() {
+            return Err(CryptoError::EngineBusy);
+        }
+        if context.pending_len as u64 != context.length % SHA256_BLOCK_SIZE as u64 {
+            return Err(CryptoError::InvalidArgument);
+        }
+        // The inner hash of an HMAC starts with the key block
+        if context.hmac && context.length < SHA256_BLOCK_SIZE as u64 {
+            return Err(CryptoError::InvalidArgument);
+        }
+
+        let sha = Sha256 {
+            state: context.state,
+            buffer: context.pending,
+            length: context.length,
+        };
+        self.hash.set(if context.hmac {
+            Hash::Hmac(HmacSha256::resume(context.key, sha))
+        } else {
+            Hash::Sha256(sha)
+        });
+        Ok(())
+    }
+}
+
+impl DynamicDeferredCallClient for SoftwareSha256<'_> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        // The mode cannot change while data is pending
+        let sha224 = self.is_sha224();
+        if let Some(data) = self.data.take() {
+            if sha224 {
+                self.sha224_client
+                    .map(move |client| client.add_data_done(Ok(()), data));
+            } else {
+                self.client
+                    .map(move |client| client.add_data_done(Ok(()), data));
+            }
+        }
+        if let Some(data) = self.readonly_data.take() {
+            if sha224 {
+                self.sha224_client
+                    .map(move |client| client.add_readonly_data_done(Ok(()), data));
+            } else {
+                self.client
+                    .map(move |client| client.add_readonly_data_done(Ok(()), data));
+            }
+        }
+        if let Some(digest) = self.sha224_digest.take() {
+            self.sha224_client
+                .map(move |client| client.hash_done(Ok(()), digest));
+        }
+        if let Some(digest) = self.digest.take() {
+            match self.verified.take() {
+                Some(verified) => {
+                    self.verify_client
+                        .map(move |client| client.verification_done(Ok(verified), digest));
+                }
+                None => {
+                    self.client
+                        .map(move |client| client.hash_done(Ok(()), digest));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(hex: &str) -> [u8; SHA256_DIGEST_SIZE] {
+        let mut digest = [0; SHA256_DIGEST_SIZE];
+        for (i, byte) in digest.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        digest
+    }
+
+    fn decode_sha224(hex: &str) -> [u8; SHA224_DIGEST_SIZE] {
+        let mut digest = [0; SHA224_DIGEST_SIZE];
+        for (i, byte) in digest.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        digest
+    }
+
+    // FIPS 180-2, appendix B.1 and B.2
+    #[test]
+    fn fips_180_2() {
+        let mut sha = Sha256::new();
+        sha.update(b"abc");
+        assert_eq!(
+            sha.finish(),
+            decode("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+
+        let message = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        let expected = decode("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+        let mut sha = Sha256::new();
+        sha.update(message);
+        assert_eq!(sha.finish(), expected);
+
+        // The same message, split across block boundaries.
+        let mut sha = Sha256::new();
+        for chunk in message.chunks(5) {
+            sha.update(chunk);
+        }
+        assert_eq!(sha.finish(), expected);
+    }
+
+    // RFC 4231, test cases 1 to 4, 6 and 7
+    #[test]
+    fn rfc_4231() {
+        let mut hmac = HmacSha256::new(&[0x0b; 20]);
+        hmac.update(b"Hi There");
+        assert_eq!(
+            hmac.finish(),
+            decode("b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7")
+        );
+
+        let mut hmac = HmacSha256::new(b"Jefe");
+        hmac.update(b"what do ya want ");
+        hmac.update(b"for nothing?");
+        assert_eq!(
+            hmac.finish(),
+            decode("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+        );
+
+        let mut hmac = HmacSha256::new(&[0xaa; 20]);
+        hmac.update(&[0xdd; 50]);
+        assert_eq!(
+            hmac.finish(),
+            decode("773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe")
+        );
+
+        let key: [u8; 25] = [
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
+            25,
+        ];
+        let mut hmac = HmacSha256::new(&key);
+        hmac.update(&[0xcd; 50]);
+        assert_eq!(
+            hmac.finish(),
+            decode("82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b")
+        );
+
+        // A key longer than a block is hashed first.
+        let mut hmac = HmacSha256::new(&[0xaa; 131]);
+        hmac.update(b"Test Using Larger Than Block-Size Key - Hash Key First");
+        assert_eq!(
+            hmac.finish(),
+            decode("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54")
+        );
+
+        // Data longer than a block, split across block boundaries.
+        let message: &[u8] = b"This is a test using a larger than block-size key and a larger \
+            than block-size data. The key needs to be hashed before being used by the HMAC \
+            algorithm.";
+        let mut hmac = HmacSha256::new(&[0xaa; 131]);
+        for chunk in message.chunks(48) {
+            hmac.update(chunk);
+        }
+        assert_eq!(hmac.len(), message.len() as u64);
+        assert_eq!(
+            hmac.finish(),
+            decode("9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2")
+        );
+    }
+
+    // The length counts the message only, and copies of a keyed HMAC are
+    // independent.
+    #[test]
+    fn hmac_length() {
+        let keyed = HmacSha256::new(b"Jefe");
+        assert!(keyed.is_empty());
+
+        let mut hmac = keyed;
+        hmac.update(b"what do ya want for nothing?");
+        assert_eq!(hmac.len(), 28);
+        assert_eq!(
+            hmac.finish(),
+            decode("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+        );
+
+        let mut hmac = keyed;
+        hmac.update(b"what do ya want ");
+        hmac.update(b"for nothing?");
+        assert_eq!(
+            hmac.finish(),
+            decode("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+        );
+    }
+
+    #[test]
+    fn save_restore() {
+        use kernel::hil::digest::{DigestContext, DigestSaveRestore, HMACSha256};
+
+        let deferred_caller = DynamicDeferredCall::new(&mut []);
+        let sha = SoftwareSha256::new(&deferred_caller);
+        assert_eq!(sha.set_mode_hmacsha256(b"Jefe"), Ok(()));
+        sha.update(b"what do ya want ");
+
+        let mut context = DigestContext::default();
+        assert_eq!(sha.save_context(&mut context), Ok(()));
+        sha.hash.set(Hash::Sha256(Sha256::new()));
+        sha.update(b"another hash in between");
+        assert_eq!(sha.restore_context(&context), Ok(()));
+
+        sha.update(b"for nothing?");
+        assert_eq!(
+            sha.finish(),
+            Some(decode(
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+            ))
+        );
+    }
+
+    // FIPS 180-2, change notice 1, appendix B.1 and B.2
+    #[test]
+    fn sha224() {
+        use kernel::hil::digest::{Sha224, Sha256 as _};
+
+        let mut sha = Sha256::new_sha224();
+        sha.update(b"abc");
+        assert_eq!(
+            sha.finish()[..SHA224_DIGEST_SIZE],
+            decode_sha224("23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7")
+        );
+
+        let deferred_caller = DynamicDeferredCall::new(&mut []);
+        let sha = SoftwareSha256::new(&deferred_caller);
+        assert_eq!(sha.set_mode_sha224(), Ok(()));
+        assert!(sha.finish().is_none());
+        sha.update(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq");
+        assert_eq!(
+            sha.finish_sha224(),
+            Some(decode_sha224(
+                "75388b16512776cc5dba5da1fd890150b0c6455cb4f58b1952522525"
+            ))
+        );
+
+        // The next hash is a SHA-224 hash too, until the mode is changed.
+        assert!(sha.is_sha224());
+        assert_eq!(sha.set_mode_sha256(), Ok(()));
+        assert!(sha.finish_sha224().is_none());
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(
+            Sha256::new().finish(),
+            decode("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+    }
+}
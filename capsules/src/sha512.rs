@@ -0,0 +1,472 @@
+//! Software implementation of SHA-512 and SHA-384.
+//!
+//! `Sha512` hashes data synchronously on the CPU, like `sha256::Sha256`.
+//! `SoftwareSha512` implements the `Digest` and `DigestVerify` HILs on top of
+//! it, for `Sha512Digest` or `Sha384Digest`, so that capsules written against
+//! the HIL can use these digests on chips whose hash engine stops at SHA-256.
+//! It is much slower than a hardware engine.
+//!
+//! Each request is processed at once when it is made, and the client is
+//! called from a deferred call, as it would be from the interrupt of a
+//! hardware engine. The state of a hash does not fit in a `DigestContext`, so
+//! the users of a `MuxDigest` over it take turns.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let sha512 = static_init!(
+//!     capsules::sha512::SoftwareSha512<'static, Sha512Digest>,
+//!     capsules::sha512::SoftwareSha512::new(dynamic_deferred_caller)
+//! );
+//! sha512.initialize_callback_handle(
+//!     dynamic_deferred_caller
+//!         .register(sha512)
+//!         .expect("no deferred call slot available for software SHA-512"),
+//! );
+//! digest::Digest::set_client(sha512, client);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::dynamic_deferred_call::{
+    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
+};
+use kernel::common::leasable_buffer::{LeasableBuffer, ReadOnlyLeasableBuffer};
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::digest::{self, Sha384Digest, Sha512Digest};
+
+pub const SHA512_BLOCK_SIZE: usize = 128;
+pub const SHA512_DIGEST_SIZE: usize = 64;
+pub const SHA384_DIGEST_SIZE: usize = 48;
+
+#[rustfmt::skip]
+const K: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+    0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+    0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30,
+    0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+    0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+#[rustfmt::skip]
+const SHA512_INITIAL_STATE: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+#[rustfmt::skip]
+const SHA384_INITIAL_STATE: [u64; 8] = [
+    0xcbbb9d5dc1059ed8, 0x629a292a367cd507, 0x9159015a3070dd17, 0x152fecd8f70e5939,
+    0x67332667ffc00b31, 0x8eb44a8768581511, 0xdb0c2e0d64f98fa7, 0x47b5481dbefa4fa4,
+];
+
+#[derive(Clone, Copy)]
+pub struct Sha512 {
+    state: [u64; 8],
+    /// Data not yet compressed, less than a block.
+    buffer: [u8; SHA512_BLOCK_SIZE],
+    /// Total length of the data, in bytes.
+    length: u64,
+}
+
+impl Default for Sha512 {
+    fn default() -> Sha512 {
+        Sha512::new()
+    }
+}
+
+impl Sha512 {
+    pub const fn new() -> Sha512 {
+        Sha512::with_state(SHA512_INITIAL_STATE)
+    }
+
+    /// SHA-384 is SHA-512 with another initial state, truncated to its first
+    /// `SHA384_DIGEST_SIZE` bytes.
+    pub const fn new_sha384() -> Sha512 {
+        Sha512::with_state(SHA384_INITIAL_STATE)
+    }
+
+    const fn with_state(state: [u64; 8]) -> Sha512 {
+        Sha512 {
+            state,
+            buffer: [0; SHA512_BLOCK_SIZE],
+            length: 0,
+        }
+    }
+
+    /// Number of bytes hashed so far.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        let buffered = (self.length % SHA512_BLOCK_SIZE as u64) as usize;
+        self.length += data.len() as u64;
+
+        if buffered > 0 {
+            let n = core::cmp::min(data.len(), SHA512_BLOCK_SIZE - buffered);
+            self.buffer[buffered..buffered + n].copy_from_slice(&data[..n]);
+            data = &data[n..];
+            if buffered + n < SHA512_BLOCK_SIZE {
+                return;
+            }
+            let block = self.buffer;
+            compress(&mut self.state, &block);
+        }
+
+        let mut blocks = data.chunks_exact(SHA512_BLOCK_SIZE);
+        for block in &mut blocks {
+            compress(&mut self.state, block);
+        }
+        let rest = blocks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+    }
+
+    /// Pad the data and return the final state, which is the SHA-512 digest.
+    /// The SHA-384 digest is its first `SHA384_DIGEST_SIZE` bytes.
+    pub fn finish(mut self) -> [u8; SHA512_DIGEST_SIZE] {
+        let bit_length = (self.length as u128).wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.length % SHA512_BLOCK_SIZE as u64 != 112 {
+            self.update(&[0]);
+        }
+        self.update(&bit_length.to_be_bytes());
+
+        let mut digest = [0; SHA512_DIGEST_SIZE];
+        for (bytes, word) in digest.chunks_mut(8).zip(self.state.iter()) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+fn compress(state: &mut [u64; 8], block: &[u8]) {
+    let mut w = [0u64; 80];
+    for (i, bytes) in block.chunks(8).enumerate() {
+        let mut word = [0; 8];
+        word.copy_from_slice(bytes);
+        w[i] = u64::from_be_bytes(word);
+    }
+    for i in 16..80 {
+        let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+        let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..80 {
+        let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h].iter()) {
+        *word = word.wrapping_add(*value);
+    }
+}
+
+/// The digests computed with the SHA-512 function, which differ only in
+/// their initial state and in the number of bytes of the final state they
+/// keep.
+pub trait Sha512Variant: digest::DigestType {
+    const INITIAL_STATE: [u64; 8];
+}
+
+impl Sha512Variant for Sha512Digest {
+    const INITIAL_STATE: [u64; 8] = SHA512_INITIAL_STATE;
+}
+
+impl Sha512Variant for Sha384Digest {
+    const INITIAL_STATE: [u64; 8] = SHA384_INITIAL_STATE;
+}
+
+pub struct SoftwareSha512<'a, T: 'static + Sha512Variant> {
+    client: OptionalCell<&'a dyn digest::Client<'a, T>>,
+    verify_client: OptionalCell<&'a dyn digest::ClientVerify<'a, T>>,
+    deferred_caller: &'a DynamicDeferredCall,
+    handle: OptionalCell<DeferredCallHandle>,
+
+    sha: Cell<Sha512>,
+
+    /// The buffers of a finished operation, returned from the deferred call.
+    data: TakeCell<'static, [u8]>,
+    readonly_data: OptionalCell<&'static [u8]>,
+    digest: TakeCell<'static, T>,
+    /// The result of a finished `verify()`, whose buffer is in `digest`.
+    verified: OptionalCell<bool>,
+}
+
+impl<'a, T: 'static + Sha512Variant> SoftwareSha512<'a, T> {
+    pub fn new(deferred_caller: &'a DynamicDeferredCall) -> SoftwareSha512<'a, T> {
+        SoftwareSha512 {
+            client: OptionalCell::empty(),
+            verify_client: OptionalCell::empty(),
+            deferred_caller,
+            handle: OptionalCell::empty(),
+            sha: Cell::new(Sha512::with_state(T::INITIAL_STATE)),
+            data: TakeCell::empty(),
+            readonly_data: OptionalCell::empty(),
+            digest: TakeCell::empty(),
+            verified: OptionalCell::empty(),
+        }
+    }
+
+    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
+        self.handle.replace(handle);
+    }
+
+    fn busy(&self) -> bool {
+        self.data.is_some() || self.readonly_data.is_some() || self.digest.is_some()
+    }
+
+    fn update(&self, data: &[u8]) {
+        let mut sha = self.sha.get();
+        sha.update(data);
+        self.sha.set(sha);
+    }
+
+    /// Finish the hash into `digest`, and start a new one.
+    fn finish(&self, digest: &mut T) {
+        let sha = self.sha.replace(Sha512::with_state(T::INITIAL_STATE));
+        let out = digest.as_mut();
+        let len = out.len();
+        out.copy_from_slice(&sha.finish()[..len]);
+    }
+
+    fn schedule_callback(&self) {
+        self.handle.map(|handle| self.deferred_caller.set(*handle));
+    }
+}
+
+impl<'a, T: 'static + Sha512Variant> digest::Digest<'a, T> for SoftwareSha512<'a, T> {
+    fn set_client(&'a self, client: &'a dyn digest::Client<'a, T>) {
+        self.client.set(client);
+    }
+
+    fn add_data(
+        &self,
+        data: LeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static mut [u8])> {
+        if self.busy() {
+            return Err((CryptoError::EngineBusy, data.take()));
+        }
+
+        let len = data.len();
+        self.update(&data[..]);
+        self.data.replace(data.take());
+        self.schedule_callback();
+        Ok(len)
+    }
+
+    fn add_readonly_data(
+        &self,
+        data: ReadOnlyLeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static [u8])> {
+        if self.busy() {
+            return Err((CryptoError::EngineBusy, data.take()));
+        }
+
+        let len = data.len();
+        self.update(&data[..]);
+        self.readonly_data.set(data.take());
+        self.schedule_callback();
+        Ok(len)
+    }
+
+    fn run(&'a self, digest: &'static mut T) -> Result<(), (CryptoError, &'static mut T)> {
+        if self.busy() {
+            return Err((CryptoError::EngineBusy, digest));
+        }
+
+        self.finish(digest);
+        self.digest.replace(digest);
+        self.schedule_callback();
+        Ok(())
+    }
+
+    fn clear_data(&self) {
+        self.sha.set(Sha512::with_state(T::INITIAL_STATE));
+    }
+
+    fn cancel(&self) -> digest::Cancelled<T> {
+        self.clear_data();
+        self.verified.clear();
+        digest::Cancelled {
+            data: self.data.take(),
+            readonly_data: self.readonly_data.take(),
+            digest: self.digest.take(),
+            chain: None,
+        }
+    }
+}
+
+impl<'a, T: 'static + Sha512Variant> digest::DigestVerify<'a, T> for SoftwareSha512<'a, T> {
+    fn set_verify_client(&'a self, client: &'a dyn digest::ClientVerify<'a, T>) {
+        self.verify_client.set(client);
+    }
+
+    fn verify(&'a self, compare: &'static mut T) -> Result<(), (CryptoError, &'static mut T)> {
+        if self.busy() {
+            return Err((CryptoError::EngineBusy, compare));
+        }
+
+        let mut digest = *compare;
+        self.finish(&mut digest);
+        // Look at every byte, whatever the first one that differs
+        let diff = digest
+            .as_ref()
+            .iter()
+            .zip(compare.as_ref().iter())
+            .fold(0, |diff, (a, b)| diff | (a ^ b));
+        self.verified.set(diff == 0);
+        self.digest.replace(compare);
+        self.schedule_callback();
+        Ok(())
+    }
+}
+
+impl<T: 'static + Sha512Variant> digest::DigestSaveRestore for SoftwareSha512<'_, T> {
+    fn save_context(&self, _context: &mut digest::DigestContext) -> Result<(), CryptoError> {
+        Err(CryptoError::NotSupported)
+    }
+
+    fn restore_context(&self, _context: &digest::DigestContext) -> Result<(), CryptoError> {
+        Err(CryptoError::NotSupported)
+    }
+}
+
+impl<T: 'static + Sha512Variant> DynamicDeferredCallClient for SoftwareSha512<'_, T> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        if let Some(data) = self.data.take() {
+            self.client
+                .map(move |client| client.add_data_done(Ok(()), data));
+        }
+        if let Some(data) = self.readonly_data.take() {
+            self.client
+                .map(move |client| client.add_readonly_data_done(Ok(()), data));
+        }
+        if let Some(digest) = self.digest.take() {
+            match self.verified.take() {
+                Some(verified) => {
+                    self.verify_client
+                        .map(move |client| client.verification_done(Ok(verified), digest));
+                }
+                None => {
+                    self.client
+                        .map(move |client| client.hash_done(Ok(()), digest));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_digest(digest: &[u8], hex: &str) {
+        assert_eq!(2 * digest.len(), hex.len());
+        for (i, byte) in digest.iter().enumerate() {
+            assert_eq!(
+                *byte,
+                u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap()
+            );
+        }
+    }
+
+    // FIPS 180-2, appendix C.1, C.2, D.1 and D.2
+    #[test]
+    fn fips_180_2() {
+        let mut sha = Sha512::new();
+        sha.update(b"abc");
+        assert_digest(
+            &sha.finish(),
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+        );
+
+        let mut sha = Sha512::new_sha384();
+        sha.update(b"abc");
+        assert_digest(
+            &sha.finish()[..SHA384_DIGEST_SIZE],
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded163\
+             1a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7",
+        );
+
+        let message = b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmn\
+                        hijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu";
+        let expected = "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018\
+                        501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909";
+        let mut sha = Sha512::new();
+        sha.update(message);
+        assert_digest(&sha.finish(), expected);
+
+        // The same message, split across block boundaries.
+        let mut sha = Sha512::new();
+        for chunk in message.chunks(7) {
+            sha.update(chunk);
+        }
+        assert_digest(&sha.finish(), expected);
+
+        let mut sha = Sha512::new_sha384();
+        sha.update(message);
+        assert_digest(
+            &sha.finish()[..SHA384_DIGEST_SIZE],
+            "09330c33f71147e83d192fc782cd1b4753111b173b3b05d2\
+             2fa08086e3b0f712fcc7c71a557e2db966c3e9fa91746039",
+        );
+    }
+
+    #[test]
+    fn empty() {
+        assert_digest(
+            &Sha512::new().finish(),
+            "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce\
+             47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+        );
+        assert_digest(
+            &Sha512::new_sha384().finish()[..SHA384_DIGEST_SIZE],
+            "38b060a751ac96384cd9327eb1b1e36a21fdb71114be0743\
+             4c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b",
+        );
+    }
+}
@@ -0,0 +1,604 @@
+//! Software implementation of AES-128.
+//!
+//! `SoftwareAes` implements the `AES128` HIL with its ECB, CBC and CTR modes,
+//! as well as `AES128Block` and `ClearKeys`, on the CPU. Boards whose chip has no AES engine
+//! can use it to provide capsules such as `aes_ccm` or `aes` without a
+//! chip-specific driver, and as a reference to cross-check the answers of
+//! hardware engines. It is much slower than a hardware engine. It uses no
+//! tables and no branches that depend on the key or the data: the S-box is
+//! computed on the bitsliced state, as an inversion in GF(2^8) followed by
+//! the affine transform, so its timing does not leak them. It is not
+//! hardened against power analysis.
+//!
+//! Each request is processed at once when it is made, and the client is
+//! called from a deferred call, as it would be from the interrupt of a
+//! hardware engine.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let aes = static_init!(
+//!     capsules::software_aes::SoftwareAes<'static>,
+//!     capsules::software_aes::SoftwareAes::new(dynamic_deferred_caller)
+//! );
+//! aes.initialize_callback_handle(
+//!     dynamic_deferred_caller
+//!         .register(aes)
+//!         .expect("no deferred call slot available for software AES"),
+//! );
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::dynamic_deferred_call::{
+    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
+};
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::symmetric_encryption::{
+    AES128Block, AES128Ctr, BlockClient, ClearKeys, Client, AES128, AES128CBC, AES128ECB,
+    AES128_BLOCK_SIZE, AES128_KEY_SIZE,
+};
+
+type Block = [u8; AES128_BLOCK_SIZE];
+
+/// The key and the 10 round keys.
+type RoundKeys = [Block; 11];
+
+const RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+/// Multiply by x in GF(2^8).
+fn xtime(a: u8) -> u8 {
+    (a << 1) ^ ((a >> 7) * 0x1b)
+}
+
+/// Multiply in GF(2^8).
+fn gmul(mut a: u8, b: u8) -> u8 {
+    let mut product = 0;
+    for i in 0..8 {
+        product ^= a & ((b >> i) & 1).wrapping_neg();
+        a = xtime(a);
+    }
+    product
+}
+
+/// The 16 bytes of a block, bitsliced: bit `k` of slice `i` is bit `i` of
+/// byte `k`.
+type Slices = [u16; 8];
+
+fn bitslice(block: &Block) -> Slices {
+    let mut slices = [0; 8];
+    for (k, byte) in block.iter().enumerate() {
+        for (i, slice) in slices.iter_mut().enumerate() {
+            *slice |= (((byte >> i) & 1) as u16) << k;
+        }
+    }
+    slices
+}
+
+fn unbitslice(slices: &Slices, block: &mut Block) {
+    for (k, byte) in block.iter_mut().enumerate() {
+        *byte = 0;
+        for (i, slice) in slices.iter().enumerate() {
+            *byte |= (((slice >> k) & 1) as u8) << i;
+        }
+    }
+}
+
+/// Multiply 16 pairs of elements of GF(2^8) at once, modulo the AES
+/// polynomial x^8 + x^4 + x^3 + x + 1.
+fn slice_mul(a: &Slices, b: &Slices) -> Slices {
+    let mut product = [0; 15];
+    for i in 0..8 {
+        for j in 0..8 {
+            product[i + j] ^= a[i] & b[j];
+        }
+    }
+    for k in (8..15).rev() {
+        product[k - 4] ^= product[k];
+        product[k - 5] ^= product[k];
+        product[k - 7] ^= product[k];
+        product[k - 8] ^= product[k];
+    }
+    let mut result = [0; 8];
+    result.copy_from_slice(&product[..8]);
+    result
+}
+
+/// Invert 16 elements of GF(2^8) at once, as x^254, with 0 mapped to 0.
+fn slice_inv(x: &Slices) -> Slices {
+    let x2 = slice_mul(x, x);
+    let x3 = slice_mul(&x2, x);
+    let x6 = slice_mul(&x3, &x3);
+    let x12 = slice_mul(&x6, &x6);
+    let x15 = slice_mul(&x12, &x3);
+    let x30 = slice_mul(&x15, &x15);
+    let x60 = slice_mul(&x30, &x30);
+    let x120 = slice_mul(&x60, &x60);
+    let x240 = slice_mul(&x120, &x120);
+    let x252 = slice_mul(&x240, &x12);
+    slice_mul(&x252, &x2)
+}
+
+/// Apply the S-box to each byte of `block`. The S-box is computed with
+/// logic operations on the bitsliced block rather than looked up in a
+/// table, so that its timing does not depend on the data or the key.
+fn sub_bytes(block: &mut Block) {
+    let x = slice_inv(&bitslice(block));
+    let mut s = [0; 8];
+    for i in 0..8 {
+        s[i] = x[i] ^ x[(i + 4) % 8] ^ x[(i + 5) % 8] ^ x[(i + 6) % 8] ^ x[(i + 7) % 8];
+        s[i] ^= (((0x63 >> i) & 1) as u16).wrapping_neg();
+    }
+    unbitslice(&s, block);
+}
+
+fn inv_sub_bytes(block: &mut Block) {
+    let s = bitslice(block);
+    let mut x = [0; 8];
+    for i in 0..8 {
+        x[i] = s[(i + 2) % 8] ^ s[(i + 5) % 8] ^ s[(i + 7) % 8];
+        x[i] ^= (((0x05 >> i) & 1) as u16).wrapping_neg();
+    }
+    unbitslice(&slice_inv(&x), block);
+}
+
+fn expand_key(key: &[u8; AES128_KEY_SIZE]) -> RoundKeys {
+    let mut round_keys = [[0; AES128_BLOCK_SIZE]; 11];
+    round_keys[0] = *key;
+    for round in 1..11 {
+        let prev = round_keys[round - 1];
+        let mut rotated = [0; AES128_BLOCK_SIZE];
+        rotated[..4].copy_from_slice(&[prev[13], prev[14], prev[15], prev[12]]);
+        sub_bytes(&mut rotated);
+        let mut word = [
+            rotated[0] ^ RCON[round - 1],
+            rotated[1],
+            rotated[2],
+            rotated[3],
+        ];
+        for i in 0..AES128_BLOCK_SIZE {
+            word[i % 4] ^= prev[i];
+            round_keys[round][i] = word[i % 4];
+        }
+    }
+    round_keys
+}
+
+fn add_round_key(state: &mut Block, round_key: &Block) {
+    state
+        .iter_mut()
+        .zip(round_key.iter())
+        .for_each(|(s, k)| *s ^= k);
+}
+
+// The state is stored column by column, so byte `r + 4 * c` is at row `r`
+// and column `c`. Row `r` is rotated left by `r` columns.
+fn shift_rows(state: &mut Block) {
+    let old = *state;
+    for i in 0..AES128_BLOCK_SIZE {
+        state[i] = old[(i + 4 * (i % 4)) % AES128_BLOCK_SIZE];
+    }
+}
+
+fn inv_shift_rows(state: &mut Block) {
+    let old = *state;
+    for i in 0..AES128_BLOCK_SIZE {
+        state[(i + 4 * (i % 4)) % AES128_BLOCK_SIZE] = old[i];
+    }
+}
+
+fn mix_columns(state: &mut Block) {
+    for column in state.chunks_mut(4) {
+        let all = column[0] ^ column[1] ^ column[2] ^ column[3];
+        let first = column[0];
+        for r in 0..4 {
+            let next = if r == 3 { first } else { column[r + 1] };
+            column[r] ^= all ^ xtime(column[r] ^ next);
+        }
+    }
+}
+
+fn inv_mix_columns(state: &mut Block) {
+    for column in state.chunks_mut(4) {
+        let old = [column[0], column[1], column[2], column[3]];
+        for r in 0..4 {
+            column[r] = gmul(old[r], 0x0e)
+                ^ gmul(old[(r + 1) % 4], 0x0b)
+                ^ gmul(old[(r + 2) % 4], 0x0d)
+                ^ gmul(old[(r + 3) % 4], 0x09);
+        }
+    }
+}
+
+fn encrypt_block(round_keys: &RoundKeys, block: &mut Block) {
+    add_round_key(block, &round_keys[0]);
+    for round in 1..11 {
+        sub_bytes(block);
+        shift_rows(block);
+        if round != 10 {
+            mix_columns(block);
+        }
+        add_round_key(block, &round_keys[round]);
+    }
+}
+
+fn decrypt_block(round_keys: &RoundKeys, block: &mut Block) {
+    add_round_key(block, &round_keys[10]);
+    for round in (0..10).rev() {
+        inv_shift_rows(block);
+        inv_sub_bytes(block);
+        add_round_key(block, &round_keys[round]);
+        if round != 0 {
+            inv_mix_columns(block);
+        }
+    }
+}
+
+/// Increment a counter block as a 128-bit big-endian integer.
+fn increment_counter(counter: &mut Block) {
+    for byte in counter.iter_mut().rev() {
+        *byte = byte.wrapping_add(1);
+        if *byte != 0 {
+            break;
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Mode {
+    Ecb,
+    Cbc,
+    Ctr,
+}
+
+pub struct SoftwareAes<'a> {
+    client: OptionalCell<&'a dyn Client<'a>>,
+    block_client: OptionalCell<&'a dyn BlockClient>,
+    deferred_caller: &'a DynamicDeferredCall,
+    handle: OptionalCell<DeferredCallHandle>,
+
+    round_keys: Cell<RoundKeys>,
+    /// The IV or initial counter, loaded by `start_message()`.
+    iv: Cell<Block>,
+    /// The IV or counter of the next block of the message.
+    chain: Cell<Block>,
+    mode: Cell<Mode>,
+    encrypting: Cell<bool>,
+    ctr_no_increment: Cell<bool>,
+
+    /// The buffers of a finished `crypt()`, returned from the deferred call.
+    source: TakeCell<'a, [u8]>,
+    dest: TakeCell<'a, [u8]>,
+    /// The result of a finished `ecb_encrypt_block()`.
+    block: OptionalCell<Block>,
+}
+
+impl<'a> SoftwareAes<'a> {
+    pub fn new(deferred_caller: &'a DynamicDeferredCall) -> SoftwareAes<'a> {
+        SoftwareAes {
+            client: OptionalCell::empty(),
+            block_client: OptionalCell::empty(),
+            deferred_caller,
+            handle: OptionalCell::empty(),
+            round_keys: Cell::new([[0; AES128_BLOCK_SIZE]; 11]),
+            iv: Cell::new([0; AES128_BLOCK_SIZE]),
+            chain: Cell::new([0; AES128_BLOCK_SIZE]),
+            mode: Cell::new(Mode::Ctr),
+            encrypting: Cell::new(true),
+            ctr_no_increment: Cell::new(false),
+            source: TakeCell::empty(),
+            dest: TakeCell::empty(),
+            block: OptionalCell::empty(),
+        }
+    }
+
+    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
+        self.handle.replace(handle);
+    }
+
+    fn busy(&self) -> bool {
+        self.dest.is_some()
+    }
+
+    /// Process `input` into `output`, which have the same length, in the
+    /// current mode, continuing the current message.
+    fn process_block(&self, input: &[u8], output: &mut [u8]) {
+        let round_keys = self.round_keys.get();
+        let mut chain = self.chain.get();
+        let mut block = [0; AES128_BLOCK_SIZE];
+        match self.mode.get() {
+            Mode::Ecb => {
+                block.copy_from_slice(input);
+                if self.encrypting.get() {
+                    encrypt_block(&round_keys, &mut block);
+                } else {
+                    decrypt_block(&round_keys, &mut block);
+                }
+            }
+            Mode::Cbc if self.encrypting.get() => {
+                block.copy_from_slice(input);
+                add_round_key(&mut block, &chain);
+                encrypt_block(&round_keys, &mut block);
+                chain = block;
+            }
+            Mode::Cbc => {
+                block.copy_from_slice(input);
+                decrypt_block(&round_keys, &mut block);
+                add_round_key(&mut block, &chain);
+                chain.copy_from_slice(input);
+            }
+            Mode::Ctr => {
+                block = chain;
+                encrypt_block(&round_keys, &mut block);
+                block
+                    .iter_mut()
+                    .zip(input.iter())
+                    .for_each(|(b, i)| *b ^= i);
+                if !self.ctr_no_increment.get() {
+                    increment_counter(&mut chain);
+                }
+            }
+        }
+        self.chain.set(chain);
+        output.copy_from_slice(&block[..input.len()]);
+    }
+}
+
+impl<'a> AES128<'a> for SoftwareAes<'a> {
+    fn enable(&self) {}
+
+    fn disable(&self) {}
+
+    fn set_client(&'a self, client: &'a dyn Client<'a>) {
+        self.client.set(client);
+    }
+
+    fn set_key(&self, key: &[u8]) -> Result<(), CryptoError> {
+        if key.len() != AES128_KEY_SIZE {
+            return Err(CryptoError::BadKeyLength);
+        }
+        let mut new_key = [0; AES128_KEY_SIZE];
+        new_key.copy_from_slice(key);
+        self.round_keys.set(expand_key(&new_key));
+        Ok(())
+    }
+
+    fn set_iv(&self, iv: &[u8]) -> Result<(), CryptoError> {
+        if iv.len() != AES128_BLOCK_SIZE {
+            return Err(CryptoError::InvalidArgument);
+        }
+        let mut new_iv = [0; AES128_BLOCK_SIZE];
+        new_iv.copy_from_slice(iv);
+        self.iv.set(new_iv);
+        Ok(())
+    }
+
+    fn start_message(&self) {
+        if self.busy() {
+            return;
+        }
+        self.chain.set(self.iv.get());
+    }
+
+    fn crypt(
+        &'a self,
+        source: Option<&'a mut [u8]>,
+        dest: &'a mut [u8],
+        start_index: usize,
+        stop_index: usize,
+    ) -> Option<(CryptoError, Option<&'a mut [u8]>, &'a mut [u8])> {
+        if self.busy() {
+            return Some((CryptoError::EngineBusy, source, dest));
+        }
+        if start_index > stop_index || stop_index > dest.len() {
+            return Some((CryptoError::InvalidArgument, source, dest));
+        }
+        let len = stop_index - start_index;
+        // CTR mode also accepts a partial last block
+        if self.mode.get() != Mode::Ctr && len % AES128_BLOCK_SIZE != 0 {
+            return Some((CryptoError::InvalidArgument, source, dest));
+        }
+        if source.as_ref().map_or(false, |src| src.len() != len) {
+            return Some((CryptoError::InvalidArgument, source, dest));
+        }
+
+        let output = &mut dest[start_index..stop_index];
+        match source {
+            Some(ref input) => {
+                for (input, output) in input
+                    .chunks(AES128_BLOCK_SIZE)
+                    .zip(output.chunks_mut(AES128_BLOCK_SIZE))
+                {
+                    self.process_block(input, output);
+                }
+            }
+            None => {
+                for block in output.chunks_mut(AES128_BLOCK_SIZE) {
+                    let mut input = [0; AES128_BLOCK_SIZE];
+                    input[..block.len()].copy_from_slice(block);
+                    self.process_block(&input[..block.len()], block);
+                }
+            }
+        }
+
+        self.source.put(source);
+        self.dest.replace(dest);
+        self.handle.map(|handle| self.deferred_caller.set(*handle));
+        None
+    }
+}
+
+impl AES128Ctr for SoftwareAes<'_> {
+    fn set_mode_aes128ctr(&self, encrypting: bool) {
+        self.mode.set(Mode::Ctr);
+        self.encrypting.set(encrypting);
+    }
+
+    fn set_ctr_no_increment(&self, no_increment: bool) -> Result<(), CryptoError> {
+        self.ctr_no_increment.set(no_increment);
+        Ok(())
+    }
+}
+
+impl AES128CBC for SoftwareAes<'_> {
+    fn set_mode_aes128cbc(&self, encrypting: bool) {
+        self.mode.set(Mode::Cbc);
+        self.encrypting.set(encrypting);
+    }
+}
+
+impl AES128ECB for SoftwareAes<'_> {
+    fn set_mode_aes128ecb(&self, encrypting: bool) {
+        self.mode.set(Mode::Ecb);
+        self.encrypting.set(encrypting);
+    }
+}
+
+impl<'a> AES128Block<'a> for SoftwareAes<'a> {
+    fn set_block_client(&'a self, client: &'a dyn BlockClient) {
+        self.block_client.set(client);
+    }
+
+    fn ecb_encrypt_block(
+        &self,
+        key: &[u8; AES128_KEY_SIZE],
+        block: &[u8; AES128_BLOCK_SIZE],
+    ) -> Result<(), CryptoError> {
+        if self.block.is_some() {
+            return Err(CryptoError::EngineBusy);
+        }
+        let mut result = *block;
+        encrypt_block(&expand_key(key), &mut result);
+        self.block.set(result);
+        self.handle.map(|handle| self.deferred_caller.set(*handle));
+        Ok(())
+    }
+}
+
+impl ClearKeys for SoftwareAes<'_> {
+    fn clear_keys(&self) -> Result<(), CryptoError> {
+        if self.busy() || self.block.is_some() {
+            return Err(CryptoError::EngineBusy);
+        }
+        self.round_keys.set([[0; AES128_BLOCK_SIZE]; 11]);
+        self.iv.set([0; AES128_BLOCK_SIZE]);
+        self.chain.set([0; AES128_BLOCK_SIZE]);
+        Ok(())
+    }
+}
+
+impl DynamicDeferredCallClient for SoftwareAes<'_> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        if let Some(block) = self.block.take() {
+            self.block_client
+                .map(|client| client.encrypt_block_done(Ok(&block)));
+        }
+        if let Some(dest) = self.dest.take() {
+            let source = self.source.take();
+            self.client
+                .map(move |client| client.crypt_done(source, dest, Ok(())));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(hex: &str) -> Block {
+        let mut block = [0; AES128_BLOCK_SIZE];
+        for (i, byte) in block.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        block
+    }
+
+    #[test]
+    fn sbox() {
+        // Every byte once, as 16 blocks
+        let mut seen = [false; 256];
+        for first in (0..256).step_by(AES128_BLOCK_SIZE) {
+            let mut block = [0; AES128_BLOCK_SIZE];
+            for (i, byte) in block.iter_mut().enumerate() {
+                *byte = (first + i) as u8;
+            }
+            let input = block;
+            sub_bytes(&mut block);
+            block.iter().for_each(|b| seen[*b as usize] = true);
+            inv_sub_bytes(&mut block);
+            assert_eq!(block, input);
+        }
+        assert!(seen.iter().all(|seen| *seen));
+
+        // FIPS-197, figure 7
+        let mut block = [0x00, 0x01, 0x53, 0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        sub_bytes(&mut block);
+        assert_eq!(&block[..4], &[0x63, 0x7c, 0xed, 0x16]);
+    }
+
+    // FIPS-197, appendix C.1
+    #[test]
+    fn fips_197() {
+        let round_keys = expand_key(&decode("000102030405060708090a0b0c0d0e0f"));
+        let mut block = decode("00112233445566778899aabbccddeeff");
+        encrypt_block(&round_keys, &mut block);
+        assert_eq!(block, decode("69c4e0d86a7b0430d8cdb78070b4c55a"));
+        decrypt_block(&round_keys, &mut block);
+        assert_eq!(block, decode("00112233445566778899aabbccddeeff"));
+    }
+
+    // NIST SP 800-38A, F.1.1, F.2.1 and F.5.1, first two blocks
+    #[test]
+    fn sp800_38a() {
+        let key = decode("2b7e151628aed2a6abf7158809cf4f3c");
+        let iv = decode("000102030405060708090a0b0c0d0e0f");
+        let counter = decode("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        let plaintext = [
+            decode("6bc1bee22e409f96e93d7e117393172a"),
+            decode("ae2d8a571e03ac9c9eb76fac45af8e51"),
+        ];
+        let vectors = [
+            (
+                Mode::Ecb,
+                iv,
+                "3ad77bb40d7a3660a89ecaf32466ef97",
+                "f5d3d58503b9699de785895a96fdbaaf",
+            ),
+            (
+                Mode::Cbc,
+                iv,
+                "7649abac8119b246cee98e9b12e9197d",
+                "5086cb9b507219ee95db113a917678b2",
+            ),
+            (
+                Mode::Ctr,
+                counter,
+                "874d6191b620e3261bef6864990db6ce",
+                "9806f66b7970fdff8617187bb9fffdff",
+            ),
+        ];
+        let deferred_caller = DynamicDeferredCall::new(&mut []);
+        let aes = SoftwareAes::new(&deferred_caller);
+        assert_eq!(aes.set_key(&key), Ok(()));
+        for (mode, iv, first, second) in vectors.iter() {
+            let ciphertext = [decode(first), decode(second)];
+            for encrypting in [true, false].iter() {
+                aes.mode.set(*mode);
+                aes.encrypting.set(*encrypting);
+                assert_eq!(aes.set_iv(iv), Ok(()));
+                aes.start_message();
+                let (input, expected) = if *encrypting {
+                    (&plaintext, &ciphertext)
+                } else {
+                    (&ciphertext, &plaintext)
+                };
+                for (input, expected) in input.iter().zip(expected.iter()) {
+                    let mut output = [0; AES128_BLOCK_SIZE];
+                    aes.process_block(input, &mut output);
+                    assert_eq!(&output, expected);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Software implementation of GHASH.
+//!
+//! `SoftwareGhash` implements the `Ghash` HIL on the CPU, for chips without
+//! a GHASH engine. The multiplication in GF(2^128) goes through the 128
+//! bits of the operand with masks rather than branches or tables, so its
+//! timing does not depend on the subkey or the data, at the cost of speed.
+//!
+//! Each request is processed at once when it is made, and the client is
+//! called from a deferred call, as it would be from the interrupt of a
+//! hardware engine.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let ghash = static_init!(
+//!     capsules::software_ghash::SoftwareGhash<'static>,
+//!     capsules::software_ghash::SoftwareGhash::new(dynamic_deferred_caller)
+//! );
+//! ghash.initialize_callback_handle(
+//!     dynamic_deferred_caller
+//!         .register(ghash)
+//!         .expect("no deferred call slot available for software GHASH"),
+//! );
+//! ghash::Ghash::set_client(ghash, client);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::dynamic_deferred_call::{
+    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
+};
+use kernel::common::leasable_buffer::LeasableBuffer;
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::ghash::{Client, Ghash, GHASH_BLOCK_SIZE};
+
+/// The reduction polynomial x^128 + x^7 + x^2 + x + 1, in the bit order of
+/// GCM.
+const R: u128 = 0xe1 << 120;
+
+/// Multiply `x` by `y` in GF(2^128), with blocks read as big-endian
+/// integers, as in NIST SP 800-38D, algorithm 1.
+fn gf_mul(x: u128, y: u128) -> u128 {
+    let mut z = 0;
+    let mut v = y;
+    for i in (0..128).rev() {
+        z ^= v & ((x >> i) & 1).wrapping_neg();
+        v = (v >> 1) ^ (R & (v & 1).wrapping_neg());
+    }
+    z
+}
+
+/// Add `data`, padded with zeros to whole blocks, to the running value `y`.
+fn ghash(subkey: u128, mut y: u128, data: &[u8]) -> u128 {
+    for chunk in data.chunks(GHASH_BLOCK_SIZE) {
+        let mut block = [0; GHASH_BLOCK_SIZE];
+        block[..chunk.len()].copy_from_slice(chunk);
+        y = gf_mul(y ^ u128::from_be_bytes(block), subkey);
+    }
+    y
+}
+
+pub struct SoftwareGhash<'a> {
+    client: OptionalCell<&'a dyn Client>,
+    deferred_caller: &'a DynamicDeferredCall,
+    handle: OptionalCell<DeferredCallHandle>,
+
+    subkey: Cell<u128>,
+    /// The running value of the hash since `init()`.
+    value: Cell<u128>,
+
+    /// The buffers of a finished `update()` or `finalize()`, returned from
+    /// the deferred call.
+    data: TakeCell<'static, [u8]>,
+    hash: TakeCell<'static, [u8; GHASH_BLOCK_SIZE]>,
+}
+
+impl<'a> SoftwareGhash<'a> {
+    pub fn new(deferred_caller: &'a DynamicDeferredCall) -> SoftwareGhash<'a> {
+        SoftwareGhash {
+            client: OptionalCell::empty(),
+            deferred_caller,
+            handle: OptionalCell::empty(),
+            subkey: Cell::new(0),
+            value: Cell::new(0),
+            data: TakeCell::empty(),
+            hash: TakeCell::empty(),
+        }
+    }
+
+    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
+        self.handle.replace(handle);
+    }
+
+    fn busy(&self) -> bool {
+        self.data.is_some() || self.hash.is_some()
+    }
+
+    fn schedule_callback(&self) {
+        self.handle.map(|handle| self.deferred_caller.set(*handle));
+    }
+}
+
+impl<'a> Ghash<'a> for SoftwareGhash<'a> {
+    fn set_client(&'a self, client: &'a dyn Client) {
+        self.client.set(client);
+    }
+
+    fn set_subkey(&self, subkey: &[u8; GHASH_BLOCK_SIZE]) -> Result<(), CryptoError> {
+        if self.busy() {
+            return Err(CryptoError::EngineBusy);
+        }
+        self.subkey.set(u128::from_be_bytes(*subkey));
+        Ok(())
+    }
+
+    fn init(&self) -> Result<(), CryptoError> {
+        if self.busy() {
+            return Err(CryptoError::EngineBusy);
+        }
+        self.value.set(0);
+        Ok(())
+    }
+
+    fn update(
+        &self,
+        data: LeasableBuffer<'static, u8>,
+    ) -> Result<(), (CryptoError, &'static mut [u8])> {
+        if self.busy() {
+            return Err((CryptoError::EngineBusy, data.take()));
+        }
+        self.value
+            .set(ghash(self.subkey.get(), self.value.get(), &data[..]));
+        self.data.replace(data.take());
+        self.schedule_callback();
+        Ok(())
+    }
+
+    fn finalize(
+        &self,
+        hash: &'static mut [u8; GHASH_BLOCK_SIZE],
+    ) -> Result<(), (CryptoError, &'static mut [u8; GHASH_BLOCK_SIZE])> {
+        if self.busy() {
+            return Err((CryptoError::EngineBusy, hash));
+        }
+        hash.copy_from_slice(&self.value.get().to_be_bytes());
+        self.value.set(0);
+        self.hash.replace(hash);
+        self.schedule_callback();
+        Ok(())
+    }
+}
+
+impl DynamicDeferredCallClient for SoftwareGhash<'_> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        if let Some(data) = self.data.take() {
+            self.client
+                .map(move |client| client.update_done(Ok(()), data));
+        }
+        if let Some(hash) = self.hash.take() {
+            self.client
+                .map(move |client| client.finalize_done(Ok(()), hash));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(hex: &str) -> u128 {
+        u128::from_str_radix(hex, 16).unwrap()
+    }
+
+    fn bytes(hex: &str) -> [u8; 64] {
+        let mut bytes = [0; 64];
+        for (i, byte) in bytes.iter_mut().take(hex.len() / 2).enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        bytes
+    }
+
+    // The GCM specification, test case 2
+    #[test]
+    fn single_block() {
+        let subkey = decode("66e94bd4ef8a2c3b884cfa59ca342b2e");
+        let y = ghash(subkey, 0, &bytes("0388dace60b6a392f328c2b971b2fe78")[..16]);
+        let y = ghash(subkey, y, &bytes("00000000000000000000000000000080")[..16]);
+        assert_eq!(y, decode("f38cbb1ad69223dcc3457ae5b6b0f885"));
+    }
+
+    // The GCM specification, test case 4: the additional data and the
+    // ciphertext are each padded to whole blocks
+    #[test]
+    fn padded() {
+        let subkey = decode("b83b533708bf535d0aa6e52980d53b78");
+        let aad = bytes("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let ciphertext = bytes(concat!(
+            "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e",
+            "21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091"
+        ));
+        let y = ghash(subkey, 0, &aad[..20]);
+        let y = ghash(subkey, y, &ciphertext[..60]);
+        let y = ghash(subkey, y, &bytes("00000000000000a000000000000001e0")[..16]);
+        assert_eq!(y, decode("698e57f70e6ecc7fd9463b7260a9ae5f"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Notifies processes of system conditions (see `kernel::system_events`),
+//! such as low memory, a nearly full storage region, a degraded random number
+//! generator, a low battery or a suspended USB bus.
+//!
+//! Userspace Interface
+//! -------------------
+//!
+//! ### `subscribe` System Call
+//!
+//! * `0`: callback for system events. The callback is called with the number
+//!   of the `kernel::system_events::Event` and its argument.
+//!
+//! ### `command` System Call
+//!
+//! * `0`: check whether the driver exists
+//! * `1`: select the events to receive. `arg1` is a bitmask with bit `n` set
+//!   to receive event `n`. All events are received by default.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let system_events = static_init!(
+//!     capsules::system_events::SystemEvents,
+//!     capsules::system_events::SystemEvents::new(board_kernel.create_grant(&grant_cap))
+//! );
+//! kernel::system_events::set_listener(system_events);
+//! ```
+
+use kernel::system_events::{Event, Listener};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::SystemEvents as usize;
+
+pub struct App {
+    callback: Option<Callback>,
+    mask: usize,
+}
+
+impl Default for App {
+    fn default() -> App {
+        App {
+            callback: None,
+            mask: !0,
+        }
+    }
+}
+
+pub struct SystemEvents {
+    apps: Grant<App>,
+}
+
+impl SystemEvents {
+    pub fn new(grant: Grant<App>) -> SystemEvents {
+        SystemEvents { apps: grant }
+    }
+}
+
+impl Listener for SystemEvents {
+    fn notify(&self, event: Event, arg: usize) {
+        let event = event as usize;
+        // Only processes that already have a grant region can be subscribed,
+        // so this never allocates, even when notifying of low memory.
+        self.apps.each(|app| {
+            if app.mask & (1 << event) != 0 {
+                app.callback.map(|mut cb| cb.schedule(event, arg, 0));
+            }
+        });
+    }
+}
+
+impl Driver for SystemEvents {
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, arg1: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            // check whether the driver exists
+            0 => ReturnCode::SUCCESS,
+
+            // select events
+            1 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.mask = arg1;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
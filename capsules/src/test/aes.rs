@@ -4,10 +4,10 @@ use core::cell::Cell;
 use kernel::common::cells::TakeCell;
 use kernel::debug;
 use kernel::hil;
+use kernel::hil::crypto::CryptoError;
 use kernel::hil::symmetric_encryption::{
     AES128Ctr, AES128, AES128CBC, AES128ECB, AES128_BLOCK_SIZE, AES128_KEY_SIZE,
 };
-use kernel::ReturnCode;
 
 pub struct TestAes128Ctr<'a, A: 'a> {
     aes: &'a A,
@@ -70,7 +70,7 @@ impl<'a, A: AES128<'a> + AES128ECB> TestAes128Ecb<'a, A> {
                 key[i] = *b;
             }
 
-            assert!(self.aes.set_key(key) == ReturnCode::SUCCESS);
+            assert!(self.aes.set_key(key).is_ok());
         });
 
         // Copy mode-appropriate source into source buffer
@@ -160,7 +160,7 @@ impl<'a, A: AES128<'a> + AES128Ctr> TestAes128Ctr<'a, A> {
                 key[i] = *b;
             }
 
-            assert!(self.aes.set_key(key) == ReturnCode::SUCCESS);
+            assert!(self.aes.set_key(key).is_ok());
         });
 
         // Copy mode-appropriate IV into IV buffer and configure it in the hardware
@@ -170,7 +170,7 @@ impl<'a, A: AES128<'a> + AES128Ctr> TestAes128Ctr<'a, A> {
                 iv[i] = *b;
             }
 
-            assert!(self.aes.set_iv(iv) == ReturnCode::SUCCESS);
+            assert!(self.aes.set_iv(iv).is_ok());
         });
 
         // Copy mode-appropriate source into source buffer
@@ -231,7 +231,12 @@ impl<'a, A: AES128<'a> + AES128Ctr> TestAes128Ctr<'a, A> {
 }
 
 impl<'a, A: AES128<'a> + AES128Ctr> hil::symmetric_encryption::Client<'a> for TestAes128Ctr<'a, A> {
-    fn crypt_done(&'a self, source: Option<&'a mut [u8]>, dest: &'a mut [u8]) {
+    fn crypt_done(
+        &'a self,
+        source: Option<&'a mut [u8]>,
+        dest: &'a mut [u8],
+        res: Result<(), CryptoError>,
+    ) {
         if self.use_source.get() {
             // Take back the source buffer
             self.source.put(source);
@@ -239,6 +244,9 @@ impl<'a, A: AES128<'a> + AES128Ctr> hil::symmetric_encryption::Client<'a> for Te
 
         // Take back the destination buffer
         self.data.replace(dest);
+        if let Err(e) = res {
+            panic!("crypt_done() failed: {:?}", e);
+        }
 
         let expected = if self.encrypting.get() {
             &CTXT_CTR
@@ -311,7 +319,7 @@ impl<'a, A: AES128<'a> + AES128CBC> TestAes128Cbc<'a, A> {
                 key[i] = *b;
             }
 
-            assert!(self.aes.set_key(key) == ReturnCode::SUCCESS);
+            assert!(self.aes.set_key(key).is_ok());
         });
 
         // Copy mode-appropriate IV into IV buffer and configure it in the hardware
@@ -322,7 +330,7 @@ impl<'a, A: AES128<'a> + AES128CBC> TestAes128Cbc<'a, A> {
                 iv[i] = *b;
             }
 
-            assert!(self.aes.set_iv(iv) == ReturnCode::SUCCESS);
+            assert!(self.aes.set_iv(iv).is_ok());
         });
 
         // Copy mode-appropriate source into source buffer
@@ -383,7 +391,12 @@ impl<'a, A: AES128<'a> + AES128CBC> TestAes128Cbc<'a, A> {
 }
 
 impl<'a, A: AES128<'a> + AES128CBC> hil::symmetric_encryption::Client<'a> for TestAes128Cbc<'a, A> {
-    fn crypt_done(&'a self, source: Option<&'a mut [u8]>, dest: &'a mut [u8]) {
+    fn crypt_done(
+        &'a self,
+        source: Option<&'a mut [u8]>,
+        dest: &'a mut [u8],
+        res: Result<(), CryptoError>,
+    ) {
         if self.use_source.get() {
             // Take back the source buffer
             self.source.put(source);
@@ -391,6 +404,9 @@ impl<'a, A: AES128<'a> + AES128CBC> hil::symmetric_encryption::Client<'a> for Te
 
         // Take back the destination buffer
         self.data.replace(dest);
+        if let Err(e) = res {
+            panic!("crypt_done() failed: {:?}", e);
+        }
 
         let expected = if self.encrypting.get() {
             &CTXT_CBC
@@ -438,7 +454,12 @@ impl<'a, A: AES128<'a> + AES128CBC> hil::symmetric_encryption::Client<'a> for Te
 }
 
 impl<'a, A: AES128<'a> + AES128ECB> hil::symmetric_encryption::Client<'a> for TestAes128Ecb<'a, A> {
-    fn crypt_done(&'a self, source: Option<&'a mut [u8]>, dest: &'a mut [u8]) {
+    fn crypt_done(
+        &'a self,
+        source: Option<&'a mut [u8]>,
+        dest: &'a mut [u8],
+        res: Result<(), CryptoError>,
+    ) {
         if self.use_source.get() {
             // Take back the source buffer
             self.source.put(source);
@@ -446,6 +467,9 @@ impl<'a, A: AES128<'a> + AES128ECB> hil::symmetric_encryption::Client<'a> for Te
 
         // Take back the destination buffer
         self.data.replace(dest);
+        if let Err(e) = res {
+            panic!("crypt_done() failed: {:?}", e);
+        }
 
         let expected = if self.encrypting.get() {
             &CTXT_ECB
@@ -3,8 +3,8 @@
 use core::cell::Cell;
 use kernel::common::cells::TakeCell;
 use kernel::debug;
+use kernel::hil::crypto::CryptoError;
 use kernel::hil::symmetric_encryption::{CCMClient, AES128CCM, AES128_KEY_SIZE, CCM_NONCE_LENGTH};
-use kernel::ReturnCode;
 
 pub struct Test<'a, A: AES128CCM<'a>> {
     aes_ccm: &'a A,
@@ -97,19 +97,15 @@ impl<'a, A: AES128CCM<'a>> Test<'a, A> {
             buf[m_off..m_off + m_len + mic_len].copy_from_slice(c_data);
         }
 
-        if self.aes_ccm.set_key(&KEY) != ReturnCode::SUCCESS
-            || self.aes_ccm.set_nonce(&nonce) != ReturnCode::SUCCESS
-        {
+        if self.aes_ccm.set_key(&KEY).is_err() || self.aes_ccm.set_nonce(&nonce).is_err() {
             panic!("aes_ccm_test failed: cannot set key or nonce.");
         }
 
-        let (res, opt_buf) =
-            self.aes_ccm
-                .crypt(buf, a_off, m_off, m_len, mic_len, confidential, encrypting);
-        if res != ReturnCode::SUCCESS {
-            debug!("Failed to start test.")
-        }
-        if let Some(buf) = opt_buf {
+        let res = self
+            .aes_ccm
+            .crypt(buf, a_off, m_off, m_len, mic_len, confidential, encrypting);
+        if let Err((_, buf)) = res {
+            debug!("Failed to start test.");
             self.buf.replace(buf);
         }
     }
@@ -186,10 +182,10 @@ impl<'a, A: AES128CCM<'a>> Test<'a, A> {
 }
 
 impl<'a, A: AES128CCM<'a>> CCMClient for Test<'a, A> {
-    fn crypt_done(&self, buf: &'static mut [u8], res: ReturnCode, tag_is_valid: bool) {
+    fn crypt_done(&self, buf: &'static mut [u8], res: Result<(), CryptoError>, tag_is_valid: bool) {
         self.buf.replace(buf);
-        if res != ReturnCode::SUCCESS {
-            debug!("aes_ccm_test failed: crypt_done returned {:?}", res);
+        if let Err(e) = res {
+            debug!("aes_ccm_test failed: crypt_done returned {:?}", e);
         } else {
             self.check_test(tag_is_valid);
             if self.next_test() {
@@ -0,0 +1,154 @@
+//! Test the AES-CMAC implementation on top of AES hardware, using the test
+//! vectors from RFC 4493.
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::debug;
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::symmetric_encryption::{CMACClient, AES128CMAC, CMAC_TAG_LENGTH};
+
+pub struct Test<'a, A: AES128CMAC<'a>> {
+    aes_cmac: &'a A,
+
+    buf: TakeCell<'static, [u8]>,
+    current_test: Cell<usize>,
+    verifying: Cell<bool>,
+
+    // (message, tag)
+    tests: [(&'static [u8], &'static [u8; CMAC_TAG_LENGTH]); 4],
+}
+
+impl<'a, A: AES128CMAC<'a>> Test<'a, A> {
+    pub fn new(aes_cmac: &'a A, buf: &'static mut [u8]) -> Test<'a, A> {
+        Test {
+            aes_cmac: aes_cmac,
+            buf: TakeCell::new(buf),
+            current_test: Cell::new(0),
+            verifying: Cell::new(false),
+            tests: [
+                (&MESSAGE[0..0], &TAG_0),
+                (&MESSAGE[0..16], &TAG_16),
+                (&MESSAGE[0..40], &TAG_40),
+                (&MESSAGE[0..64], &TAG_64),
+            ],
+        }
+    }
+
+    pub fn run(&self) {
+        debug!("AES-CMAC tests");
+        if self.aes_cmac.set_key(&KEY).is_err() {
+            panic!("aes_cmac_test failed: cannot set key.");
+        }
+        self.trigger_test();
+    }
+
+    fn next_test(&self) -> bool {
+        if !self.verifying.get() {
+            self.verifying.set(true);
+        } else {
+            self.verifying.set(false);
+            self.current_test.set(self.current_test.get() + 1);
+            if self.current_test.get() >= self.tests.len() {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn trigger_test(&self) {
+        let (message, tag) = self.tests[self.current_test.get()];
+        let m_len = message.len();
+        let verifying = self.verifying.get();
+
+        let buf = match self.buf.take() {
+            None => panic!("aes_cmac_test failed: buffer is not present."),
+            Some(buf) => buf,
+        };
+
+        buf[..m_len].copy_from_slice(message);
+        if verifying {
+            buf[m_len..m_len + CMAC_TAG_LENGTH].copy_from_slice(tag);
+        } else {
+            buf[m_len..m_len + CMAC_TAG_LENGTH]
+                .iter_mut()
+                .for_each(|b| *b = 0);
+        }
+
+        let res = self
+            .aes_cmac
+            .compute(buf, 0, m_len, CMAC_TAG_LENGTH, verifying);
+        if let Err((_, buf)) = res {
+            debug!("Failed to start test.");
+            self.buf.replace(buf);
+        }
+    }
+
+    fn check_test(&self, tag_is_valid: bool) {
+        let (message, tag) = self.tests[self.current_test.get()];
+        let m_len = message.len();
+
+        let tag_matches = self.buf.map_or(false, |buf| {
+            buf[m_len..m_len + CMAC_TAG_LENGTH]
+                .iter()
+                .zip(tag.iter())
+                .all(|(a, b)| *a == *b)
+        });
+
+        if tag_matches && tag_is_valid {
+            debug!(
+                "aes_cmac_test passed: (current_test={}, verifying={})",
+                self.current_test.get(),
+                self.verifying.get()
+            );
+        } else {
+            debug!(
+                "aes_cmac_test failed: tag_matches={}, (current_test={}, verifying={}, tag_is_valid={})",
+                tag_matches,
+                self.current_test.get(),
+                self.verifying.get(),
+                tag_is_valid
+            );
+        }
+    }
+}
+
+impl<'a, A: AES128CMAC<'a>> CMACClient for Test<'a, A> {
+    fn mac_done(&self, buf: &'static mut [u8], res: Result<(), CryptoError>, tag_is_valid: bool) {
+        self.buf.replace(buf);
+        if let Err(e) = res {
+            debug!("aes_cmac_test failed: mac_done returned {:?}", e);
+        } else {
+            self.check_test(tag_is_valid);
+            if self.next_test() {
+                self.trigger_test()
+            }
+        }
+    }
+}
+
+static KEY: [u8; 16] = [
+    0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f, 0x3c,
+];
+
+static MESSAGE: [u8; 64] = [
+    0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17, 0x2a,
+    0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c, 0x9e, 0xb7, 0x6f, 0xac, 0x45, 0xaf, 0x8e, 0x51,
+    0x30, 0xc8, 0x1c, 0x46, 0xa3, 0x5c, 0xe4, 0x11, 0xe5, 0xfb, 0xc1, 0x19, 0x1a, 0x0a, 0x52, 0xef,
+    0xf6, 0x9f, 0x24, 0x45, 0xdf, 0x4f, 0x9b, 0x17, 0xad, 0x2b, 0x41, 0x7b, 0xe6, 0x6c, 0x37, 0x10,
+];
+
+static TAG_0: [u8; 16] = [
+    0xbb, 0x1d, 0x69, 0x29, 0xe9, 0x59, 0x37, 0x28, 0x7f, 0xa3, 0x7d, 0x12, 0x9b, 0x75, 0x67, 0x46,
+];
+
+static TAG_16: [u8; 16] = [
+    0x07, 0x0a, 0x16, 0xb4, 0x6b, 0x4d, 0x41, 0x44, 0xf7, 0x9b, 0xdd, 0x9d, 0xd0, 0x4a, 0x28, 0x7c,
+];
+
+static TAG_40: [u8; 16] = [
+    0xdf, 0xa6, 0x67, 0x47, 0xde, 0x9a, 0xe6, 0x30, 0x30, 0xca, 0x32, 0x61, 0x14, 0x97, 0xc8, 0x27,
+];
+
+static TAG_64: [u8; 16] = [
+    0x51, 0xf0, 0xbe, 0xbf, 0x7e, 0x3b, 0x9d, 0x92, 0xfc, 0x49, 0x74, 0x17, 0x79, 0x36, 0x3c, 0xfe,
+];
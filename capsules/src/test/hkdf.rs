@@ -0,0 +1,86 @@
+//! Test the HKDF capsule on top of an HMAC-SHA256 engine, using test cases 1
+//! and 3 from RFC 5869.
+
+use crate::hkdf::{Client, Hkdf, HASH_LEN};
+use core::cell::Cell;
+use kernel::debug;
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::digest;
+
+pub struct Test<'a, H: digest::Digest<'a, [u8; HASH_LEN]> + digest::HMACSha256> {
+    hkdf: &'a Hkdf<'a, H>,
+    current_test: Cell<usize>,
+
+    // (salt, ikm, info, okm)
+    tests: [(
+        &'static [u8],
+        &'static [u8],
+        &'static [u8],
+        &'static [u8; 42],
+    ); 2],
+}
+
+impl<'a, H: digest::Digest<'a, [u8; HASH_LEN]> + digest::HMACSha256> Test<'a, H> {
+    pub fn new(hkdf: &'a Hkdf<'a, H>) -> Test<'a, H> {
+        Test {
+            hkdf: hkdf,
+            current_test: Cell::new(0),
+            tests: [(&SALT_1, &IKM, &INFO_1, &OKM_1), (&[], &IKM, &[], &OKM_3)],
+        }
+    }
+
+    /// `okm` must be at least 42 bytes long.
+    pub fn run(&self, okm: &'static mut [u8]) {
+        debug!("HKDF tests");
+        self.trigger_test(okm);
+    }
+
+    fn trigger_test(&self, okm: &'static mut [u8]) {
+        let (salt, ikm, info, expected) = self.tests[self.current_test.get()];
+        if let Err((e, _)) = self.hkdf.derive(salt, ikm, info, okm, expected.len()) {
+            debug!("hkdf_test failed: derive returned {:?}", e);
+        }
+    }
+}
+
+impl<'a, H: digest::Digest<'a, [u8; HASH_LEN]> + digest::HMACSha256> Client for Test<'a, H> {
+    fn derive_done(&self, result: Result<(), CryptoError>, okm: &'static mut [u8]) {
+        let current_test = self.current_test.get();
+        let (_, _, _, expected) = self.tests[current_test];
+
+        if let Err(e) = result {
+            debug!("hkdf_test failed: derive_done returned {:?}", e);
+            return;
+        }
+        if okm[..expected.len()] == expected[..] {
+            debug!("hkdf_test passed: (current_test={})", current_test);
+        } else {
+            debug!("hkdf_test failed: (current_test={})", current_test);
+        }
+
+        if current_test + 1 < self.tests.len() {
+            self.current_test.set(current_test + 1);
+            self.trigger_test(okm);
+        }
+    }
+}
+
+static IKM: [u8; 22] = [0x0b; 22];
+
+static SALT_1: [u8; 13] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c,
+];
+
+static INFO_1: [u8; 10] = [0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9];
+
+static OKM_1: [u8; 42] = [
+    0x3c, 0xb2, 0x5f, 0x25, 0xfa, 0xac, 0xd5, 0x7a, 0x90, 0x43, 0x4f, 0x64, 0xd0, 0x36, 0x2f, 0x2a,
+    0x2d, 0x2d, 0x0a, 0x90, 0xcf, 0x1a, 0x5a, 0x4c, 0x5d, 0xb0, 0x2d, 0x56, 0xec, 0xc4, 0xc5, 0xbf,
+    0x34, 0x00, 0x72, 0x08, 0xd5, 0xb8, 0x87, 0x18, 0x58, 0x65,
+];
+
+static OKM_3: [u8; 42] = [
+    0x8d, 0xa4, 0xe7, 0x75, 0xa5, 0x63, 0xc1, 0x8f, 0x71, 0x5f, 0x80, 0x2a, 0x06, 0x3c, 0x5a, 0x31,
+    0xb8, 0xa1, 0x1f, 0x5c, 0x5e, 0xe1, 0x87, 0x9e, 0xc3, 0x45, 0x4e, 0x5f, 0x3c, 0x73, 0x8d, 0x2d,
+    0x9d, 0x20, 0x13, 0x95, 0xfa, 0xa4, 0xb6, 0x1a, 0x96, 0xc8,
+];
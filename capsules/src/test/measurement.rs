@@ -0,0 +1,77 @@
+//! Test the measurement registers on top of a SHA-256 engine: extend the
+//! first register twice with "abc", then quote the first two registers.
+
+use crate::measurement::{Client, Measurements, Register};
+use kernel::debug;
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::digest;
+
+pub struct Test<'a, D: digest::Digest<'a, Register>> {
+    measurements: &'a Measurements<'a, D>,
+}
+
+impl<'a, D: digest::Digest<'a, Register>> Test<'a, D> {
+    pub fn new(measurements: &'a Measurements<'a, D>) -> Test<'a, D> {
+        Test { measurements }
+    }
+
+    /// The registers must all be zeros, and there must be at least two.
+    pub fn run(&self) {
+        debug!("Measurement tests");
+        self.extend();
+    }
+
+    fn extend(&self) {
+        if let Err(e) = self.measurements.extend(0, b"abc") {
+            debug!("measurement_test failed: extend returned {:?}", e);
+        }
+    }
+}
+
+impl<'a, D: digest::Digest<'a, Register>> Client for Test<'a, D> {
+    fn extend_done(&self, index: usize, result: Result<(), CryptoError>) {
+        if let Err(e) = result {
+            debug!("measurement_test failed: extend_done returned {:?}", e);
+            return;
+        }
+        match self.measurements.read(index) {
+            Some(register) if register == EXTEND_1 => {
+                debug!("measurement_test passed: extend 1");
+                self.extend();
+            }
+            Some(register) if register == EXTEND_2 => {
+                debug!("measurement_test passed: extend 2");
+                if let Err(e) = self.measurements.quote(0b11, b"nonce") {
+                    debug!("measurement_test failed: quote returned {:?}", e);
+                }
+            }
+            _ => debug!("measurement_test failed: wrong register value"),
+        }
+    }
+
+    fn quote_done(&self, result: Result<&Register, CryptoError>) {
+        match result {
+            Ok(quote) if *quote == QUOTE => debug!("measurement_test passed: quote"),
+            Ok(_) => debug!("measurement_test failed: wrong quote"),
+            Err(e) => debug!("measurement_test failed: quote_done returned {:?}", e),
+        }
+    }
+}
+
+/// SHA-256(zeros | "abc")
+static EXTEND_1: Register = [
+    0x36, 0x5a, 0xa7, 0xd8, 0xf7, 0xf9, 0x40, 0x2c, 0x4b, 0x94, 0x34, 0x50, 0x2b, 0x4c, 0xc8, 0x9d,
+    0xdb, 0x09, 0xfe, 0x50, 0xd7, 0xcd, 0x95, 0xb4, 0x93, 0xb8, 0x34, 0xc6, 0x2d, 0x5a, 0x53, 0x70,
+];
+
+/// SHA-256(EXTEND_1 | "abc")
+static EXTEND_2: Register = [
+    0x0f, 0x25, 0xde, 0x75, 0x7a, 0x05, 0xfd, 0xcd, 0x69, 0xbe, 0xca, 0xeb, 0x50, 0x67, 0x5b, 0x3d,
+    0x75, 0x2b, 0x78, 0xfd, 0x31, 0x92, 0x9c, 0xdb, 0xc8, 0x35, 0x2b, 0x5d, 0xef, 0xb6, 0x83, 0xa1,
+];
+
+/// SHA-256("nonce" | EXTEND_2 | zeros)
+static QUOTE: Register = [
+    0x6c, 0x0d, 0x94, 0x2c, 0x1d, 0x35, 0xd0, 0xfb, 0x03, 0x4f, 0xe1, 0xa8, 0xbb, 0x23, 0x29, 0xf3,
+    0xce, 0xb2, 0x36, 0xa8, 0x36, 0xdb, 0x1e, 0x20, 0x1b, 0xa1, 0xde, 0x7c, 0xfc, 0xc9, 0x7d, 0x83,
+];
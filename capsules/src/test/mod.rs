@@ -1,6 +1,10 @@
 pub mod aes;
 pub mod aes_ccm;
+pub mod aes_cmac;
 pub mod alarm;
+pub mod digest_chain;
+pub mod hkdf;
+pub mod measurement;
 pub mod rng;
 pub mod udp;
 pub mod virtual_uart;
@@ -0,0 +1,210 @@
+//! Time since boot, on 64 bits, from a wrapping alarm counter.
+//!
+//! Alarm counters wrap quickly, e.g. every 512 seconds for the 24-bit RTC of
+//! the nRF5x chips at 32.768 kHz. `Uptime` adds the time elapsed to a 64-bit
+//! count every time it is read, and sets its alarm to at most half the
+//! period of the counter so that it never misses a wrap.
+//!
+//! `Uptime` also estimates the drift of the counter against an external
+//! time reference, such as a GPS receiver or the clock of a host. Each call
+//! to `synchronize()` gives the reference time, and the drift is measured
+//! from the first synchronization.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let uptime_virtual_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let uptime = static_init!(
+//!     capsules::uptime::Uptime<'static, VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>>,
+//!     capsules::uptime::Uptime::new(uptime_virtual_alarm)
+//! );
+//! uptime_virtual_alarm.set_client(uptime);
+//! uptime.start();
+//! ```
+
+use core::cell::Cell;
+use kernel::hil::time::{self, Frequency};
+
+pub struct Uptime<'a, A: time::Alarm<'a>> {
+    alarm: &'a A,
+    /// The counter value when `tics` was last updated.
+    last: Cell<u32>,
+    /// The tics elapsed since `start()`, up to `last`.
+    tics: Cell<u64>,
+    /// The uptime and the reference time of the first synchronization, in
+    /// microseconds.
+    first_sync: Cell<Option<(u64, u64)>>,
+    /// The drift measured at the last synchronization.
+    drift_ppm: Cell<Option<i32>>,
+}
+
+impl<'a, A: time::Alarm<'a>> Uptime<'a, A> {
+    pub fn new(alarm: &'a A) -> Uptime<'a, A> {
+        Uptime {
+            alarm,
+            last: Cell::new(0),
+            tics: Cell::new(0),
+            first_sync: Cell::new(None),
+            drift_ppm: Cell::new(None),
+        }
+    }
+
+    /// Start counting. The uptime counts from this call, which boards make
+    /// early in their setup.
+    pub fn start(&self) {
+        self.last.set(self.alarm.now());
+        self.tics.set(0);
+        self.schedule();
+    }
+
+    fn schedule(&self) {
+        let max_tics = self.alarm.max_tics();
+        let interval = (max_tics / 2).max(1);
+        self.alarm
+            .set_alarm(self.last.get().wrapping_add(interval) & max_tics);
+    }
+
+    /// Add the tics elapsed since the last update.
+    fn update(&self) -> u64 {
+        let now = self.alarm.now();
+        let elapsed = now.wrapping_sub(self.last.get()) & self.alarm.max_tics();
+        self.last.set(now);
+        let tics = self.tics.get() + elapsed as u64;
+        self.tics.set(tics);
+        tics
+    }
+
+    /// The time since `start()`, in microseconds.
+    pub fn now_us(&self) -> u64 {
+        let tics = self.update();
+        let freq = <A::Frequency>::frequency() as u64;
+        // Split the conversion so that the product cannot overflow
+        tics / freq * 1_000_000 + tics % freq * 1_000_000 / freq
+    }
+
+    /// Record that the reference time is now `reference_us` microseconds,
+    /// counted from any origin, and update the drift estimate.
+    pub fn synchronize(&self, reference_us: u64) {
+        let local_us = self.now_us();
+        match self.first_sync.get() {
+            None => self.first_sync.set(Some((local_us, reference_us))),
+            Some((first_local, first_reference)) => {
+                let local = local_us.wrapping_sub(first_local) as i64;
+                let reference = reference_us.wrapping_sub(first_reference) as i64;
+                if reference > 0 {
+                    let drift = (local - reference) * 1_000_000 / reference;
+                    self.drift_ppm.set(Some(drift as i32));
+                }
+            }
+        }
+    }
+
+    /// How much faster the counter runs than the reference, in parts per
+    /// million, or `None` until it was synchronized twice.
+    pub fn drift_ppm(&self) -> Option<i32> {
+        self.drift_ppm.get()
+    }
+}
+
+impl<'a, A: time::Alarm<'a>> time::AlarmClient for Uptime<'a, A> {
+    fn fired(&self) {
+        self.update();
+        self.schedule();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kernel::hil::time::{Alarm, AlarmClient, Freq32KHz, Time};
+
+    /// A 24-bit counter that only moves when told to.
+    struct FakeAlarm {
+        now: Cell<u32>,
+        alarm: Cell<u32>,
+    }
+
+    impl Time for FakeAlarm {
+        type Frequency = Freq32KHz;
+
+        fn now(&self) -> u32 {
+            self.now.get()
+        }
+
+        fn max_tics(&self) -> u32 {
+            (1 << 24) - 1
+        }
+    }
+
+    impl<'a> Alarm<'a> for FakeAlarm {
+        fn set_alarm(&self, tics: u32) {
+            self.alarm.set(tics);
+        }
+
+        fn get_alarm(&self) -> u32 {
+            self.alarm.get()
+        }
+
+        fn set_client(&'a self, _client: &'a dyn AlarmClient) {}
+
+        fn is_enabled(&self) -> bool {
+            true
+        }
+
+        fn disable(&self) {}
+    }
+
+    impl FakeAlarm {
+        fn advance(&self, tics: u32) {
+            self.now
+                .set(self.now.get().wrapping_add(tics) & self.max_tics());
+        }
+    }
+
+    #[test]
+    fn wraps() {
+        let alarm = FakeAlarm {
+            now: Cell::new(0xfffff0),
+            alarm: Cell::new(0),
+        };
+        let uptime = Uptime::new(&alarm);
+        uptime.start();
+        assert_eq!(alarm.get_alarm(), 0x7fffef);
+
+        // An hour, in steps shorter than the period of the counter
+        for _ in 0..10 {
+            alarm.advance(32768 * 360);
+            uptime.fired();
+        }
+        assert_eq!(uptime.now_us(), 3600 * 1_000_000);
+
+        alarm.advance(1);
+        assert_eq!(uptime.now_us(), 3600 * 1_000_000 + 30);
+    }
+
+    #[test]
+    fn drift() {
+        let alarm = FakeAlarm {
+            now: Cell::new(0),
+            alarm: Cell::new(0),
+        };
+        let uptime = Uptime::new(&alarm);
+        uptime.start();
+
+        uptime.synchronize(5_000_000);
+        assert_eq!(uptime.drift_ppm(), None);
+
+        // 100 s of reference time, which the counter sees as 100.005 s
+        for _ in 0..100 {
+            alarm.advance(32768);
+            uptime.fired();
+        }
+        let local = uptime.now_us();
+        uptime.synchronize(5_000_000 + local - 5000);
+        assert_eq!(uptime.drift_ppm(), Some(50));
+    }
+}
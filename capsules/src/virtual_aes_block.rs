@@ -0,0 +1,194 @@
+//! Virtualize the single-block AES interface, so that several kernel users
+//! can share an engine with `AES128Block`.
+//!
+//! Each user has its own client and can have one block waiting or being
+//! encrypted. The engine encrypts one block of the mux at a time, and the
+//! users with a waiting block take turns, so a user that queues its next
+//! block from its callback does not starve the others. The mux must be the
+//! only block client of the engine.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let mux_aes = static_init!(
+//!     MuxAES128Block<'static, nrf52::aes::AesECB<'static>>,
+//!     MuxAES128Block::new(&nrf52::aes::AESECB)
+//! );
+//! AES128Block::set_block_client(&nrf52::aes::AESECB, mux_aes);
+//!
+//! let virtual_aes = static_init!(
+//!     VirtualAES128Block<'static, nrf52::aes::AesECB<'static>>,
+//!     VirtualAES128Block::new(mux_aes)
+//! );
+//! virtual_aes.setup();
+//! AES128Block::set_block_client(virtual_aes, client);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::common::{List, ListLink, ListNode};
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::symmetric_encryption::{
+    AES128Block, BlockClient, ClearKeys, AES128_BLOCK_SIZE, AES128_KEY_SIZE,
+};
+
+/// A block waiting for the engine, with its key.
+#[derive(Clone, Copy)]
+struct Request {
+    key: [u8; AES128_KEY_SIZE],
+    block: [u8; AES128_BLOCK_SIZE],
+}
+
+pub struct VirtualAES128Block<'a, A: AES128Block<'a>> {
+    mux: &'a MuxAES128Block<'a, A>,
+    next: ListLink<'a, VirtualAES128Block<'a, A>>,
+    client: OptionalCell<&'a dyn BlockClient>,
+    request: Cell<Option<Request>>,
+    id: u32,
+}
+
+impl<'a, A: AES128Block<'a>> ListNode<'a, VirtualAES128Block<'a, A>> for VirtualAES128Block<'a, A> {
+    fn next(&self) -> &'a ListLink<VirtualAES128Block<'a, A>> {
+        &self.next
+    }
+}
+
+impl<'a, A: AES128Block<'a>> VirtualAES128Block<'a, A> {
+    pub fn new(mux_aes: &'a MuxAES128Block<'a, A>) -> VirtualAES128Block<'a, A> {
+        let id = mux_aes.next_id.get();
+        mux_aes.next_id.set(id + 1);
+
+        VirtualAES128Block {
+            mux: mux_aes,
+            next: ListLink::empty(),
+            client: OptionalCell::empty(),
+            request: Cell::new(None),
+            id: id,
+        }
+    }
+
+    /// Must be called right after `static_init!()`.
+    pub fn setup(&'a self) {
+        self.mux.users.push_head(self);
+    }
+
+    /// Drop the waiting block, overwriting its key first.
+    fn drop_request(&self) {
+        self.request.set(Some(Request {
+            key: [0; AES128_KEY_SIZE],
+            block: [0; AES128_BLOCK_SIZE],
+        }));
+        self.request.set(None);
+    }
+}
+
+impl<'a, A: AES128Block<'a>> AES128Block<'a> for VirtualAES128Block<'a, A> {
+    fn set_block_client(&'a self, client: &'a dyn BlockClient) {
+        self.client.set(client);
+    }
+
+    /// Returns `EngineBusy` if a block of this user is already waiting or
+    /// being encrypted.
+    fn ecb_encrypt_block(
+        &self,
+        key: &[u8; AES128_KEY_SIZE],
+        block: &[u8; AES128_BLOCK_SIZE],
+    ) -> Result<(), CryptoError> {
+        if self.request.get().is_some() || self.mux.is_inflight(self.id) {
+            return Err(CryptoError::EngineBusy);
+        }
+        self.request.set(Some(Request {
+            key: *key,
+            block: *block,
+        }));
+        self.mux.run_next();
+        Ok(())
+    }
+}
+
+impl<'a, A: AES128Block<'a>> ClearKeys for VirtualAES128Block<'a, A> {
+    /// Drop the waiting block of this user. The keys of the engine belong
+    /// to its other users, so they are left alone: the engine must not keep
+    /// the key of a single block once it is encrypted.
+    fn clear_keys(&self) -> Result<(), CryptoError> {
+        if self.mux.is_inflight(self.id) {
+            return Err(CryptoError::EngineBusy);
+        }
+        self.drop_request();
+        Ok(())
+    }
+}
+
+pub struct MuxAES128Block<'a, A: AES128Block<'a>> {
+    aes: &'a A,
+    users: List<'a, VirtualAES128Block<'a, A>>,
+    /// The user whose block is being encrypted.
+    inflight: OptionalCell<u32>,
+    /// The last user whose block was encrypted, to take turns.
+    last_id: Cell<u32>,
+    /// Whether a client is being called, during which no block is started
+    /// so that the other users get their turn first.
+    calling: Cell<bool>,
+    next_id: Cell<u32>,
+}
+
+impl<'a, A: AES128Block<'a>> MuxAES128Block<'a, A> {
+    pub const fn new(aes: &'a A) -> MuxAES128Block<'a, A> {
+        MuxAES128Block {
+            aes: aes,
+            users: List::new(),
+            inflight: OptionalCell::empty(),
+            last_id: Cell::new(0),
+            calling: Cell::new(false),
+            next_id: Cell::new(0),
+        }
+    }
+
+    fn is_inflight(&self, id: u32) -> bool {
+        self.inflight.map_or(false, |inflight| *inflight == id)
+    }
+
+    /// Hand the waiting block of the next user to the engine, if it is
+    /// free. The users after the last one served come first.
+    fn run_next(&self) {
+        if self.inflight.is_some() || self.calling.get() {
+            return;
+        }
+        let last_id = self.last_id.get();
+        let next = self
+            .users
+            .iter()
+            .filter(|user| user.request.get().is_some())
+            .min_by_key(|user| user.id.wrapping_sub(last_id).wrapping_sub(1));
+        if let Some(user) = next {
+            if let Some(request) = user.request.get() {
+                // The engine only refuses a block if its queue is full,
+                // which the mux, with one block at a time, never fills.
+                if self
+                    .aes
+                    .ecb_encrypt_block(&request.key, &request.block)
+                    .is_ok()
+                {
+                    user.drop_request();
+                    self.inflight.set(user.id);
+                    self.last_id.set(user.id);
+                }
+            }
+        }
+    }
+}
+
+impl<'a, A: AES128Block<'a>> BlockClient for MuxAES128Block<'a, A> {
+    fn encrypt_block_done(&self, block: Result<&[u8; AES128_BLOCK_SIZE], CryptoError>) {
+        self.inflight.take().map(|id| {
+            self.calling.set(true);
+            self.users
+                .iter()
+                .find(|user| user.id == id)
+                .map(|user| user.client.map(|client| client.encrypt_block_done(block)));
+            self.calling.set(false);
+        });
+        self.run_next();
+    }
+}
@@ -1,5 +1,13 @@
 //! Virtualize the Alarm interface to enable multiple users of an underlying
 //! alarm hardware peripheral.
+//!
+//! ### Coalescing
+//!
+//! Every alarm that fires wakes the chip up. To save power, `MuxAlarm` can be
+//! given a slack with `set_slack()`: alarms may then fire up to that many tics
+//! late, so that alarms which are due at nearly the same time, e.g. several
+//! polling timers, fire together in a single wakeup. Alarms never fire early.
+//! The slack is zero by default.
 
 use core::cell::Cell;
 use kernel::common::cells::OptionalCell;
@@ -85,7 +93,10 @@ impl<'a, A: Alarm<'a>> Alarm<'a> for VirtualMuxAlarm<'a, A> {
             let cur_alarm = self.mux.alarm.get_alarm();
             let now = self.now();
 
-            if cur_alarm.wrapping_sub(now) > when.wrapping_sub(now) {
+            // Only move the underlying alarm if it would fire this alarm too
+            // late.
+            let latest = when.wrapping_sub(now).saturating_add(self.mux.slack.get());
+            if cur_alarm.wrapping_sub(now) > latest {
                 self.mux.prev.set(self.mux.alarm.now());
                 self.mux.alarm.set_alarm(when);
             }
@@ -114,6 +125,8 @@ pub struct MuxAlarm<'a, A: Alarm<'a>> {
     virtual_alarms: List<'a, VirtualMuxAlarm<'a, A>>,
     enabled: Cell<usize>,
     prev: Cell<u32>,
+    /// How late alarms may fire, in tics.
+    slack: Cell<u32>,
     alarm: &'a A,
 }
 
@@ -123,9 +136,34 @@ impl<'a, A: Alarm<'a>> MuxAlarm<'a, A> {
             virtual_alarms: List::new(),
             enabled: Cell::new(0),
             prev: Cell::new(0),
+            slack: Cell::new(0),
             alarm: alarm,
         }
     }
+
+    /// Allow alarms to fire up to `tics` late so that alarms due at nearly
+    /// the same time fire together.
+    pub fn set_slack(&self, tics: u32) {
+        self.slack.set(tics);
+    }
+
+    /// Returns when to set the underlying alarm for the soonest alarm
+    /// `first`: the latest alarm due at most `slack` tics after it, so that
+    /// all these alarms fire together.
+    fn coalesce(&self, first: u32, now: u32) -> u32 {
+        let slack = self.slack.get();
+        if slack == 0 {
+            return first;
+        }
+        let limit = first.wrapping_sub(now).saturating_add(slack);
+        self.virtual_alarms
+            .iter()
+            .filter(|cur| cur.armed.get())
+            .map(|cur| cur.when.get().wrapping_sub(now))
+            .filter(|delay| *delay <= limit)
+            .max()
+            .map_or(first, |delay| now.wrapping_add(delay))
+    }
 }
 
 fn has_expired(alarm: u32, now: u32, prev: u32) -> bool {
@@ -165,8 +203,9 @@ impl<'a, A: Alarm<'a>> time::AlarmClient for MuxAlarm<'a, A> {
         self.prev.set(now);
         // If there is an alarm to fire, set the underlying alarm to it
         if let Some(valrm) = next {
-            self.alarm.set_alarm(valrm.when.get());
-            if has_expired(valrm.when.get(), self.alarm.now(), prev) {
+            let when = self.coalesce(valrm.when.get(), now);
+            self.alarm.set_alarm(when);
+            if has_expired(when, self.alarm.now(), prev) {
                 self.fired();
             }
         } else {
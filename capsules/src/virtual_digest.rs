@@ -1,31 +1,61 @@
 //! Virtualize the Digest interface to enable multiple users of an underlying
 //! Digest hardware peripheral.
+//!
+//! When the engine can save and restore its state, the hashes of several
+//! users are interleaved: a user can add data while another user is in the
+//! middle of a hash, as long as the engine is not busy with one of its
+//! operations. The hash of the other user is saved, and restored the next
+//! time that user calls. Otherwise the users take turns, from the first
+//! call of one user to its `clear_data()`.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let mux_digest = static_init!(
+//!     MuxDigest<'static, lowrisc::hmac::Hmac, [u8; 32]>,
+//!     MuxDigest::new(&ibex::hmac::HMAC)
+//! );
+//! digest::Digest::set_client(&ibex::hmac::HMAC, mux_digest);
+//!
+//! let virtual_digest_user = static_init!(
+//!     VirtualMuxDigest<'static, lowrisc::hmac::Hmac, [u8; 32]>,
+//!     VirtualMuxDigest::new(mux_digest)
+//! );
+//! virtual_digest_user.setup();
+//! ```
 
 use core::cell::Cell;
 use core::marker::PhantomData;
-use kernel::common::cells::OptionalCell;
-use kernel::common::leasable_buffer::LeasableBuffer;
-use kernel::common::{ListLink, ListNode};
+use kernel::common::cells::{MapCell, OptionalCell};
+use kernel::common::leasable_buffer::{LeasableBuffer, ReadOnlyLeasableBuffer};
+use kernel::common::{List, ListLink, ListNode};
+use kernel::hil::crypto::CryptoError;
 use kernel::hil::digest;
 use kernel::hil::digest::DigestType;
-use kernel::ReturnCode;
 
-pub struct VirtualMuxDigest<'a, A: digest::Digest<'a, T>, T: DigestType> {
+pub struct VirtualMuxDigest<'a, A: digest::Digest<'a, T> + digest::DigestSaveRestore, T: DigestType>
+{
     mux: &'a MuxDigest<'a, A, T>,
     next: ListLink<'a, VirtualMuxDigest<'a, A, T>>,
     client: OptionalCell<&'a dyn digest::Client<'a, T>>,
+    verify_client: OptionalCell<&'a dyn digest::ClientVerify<'a, T>>,
+    /// The hash of this user, while it is suspended for another user.
+    context: MapCell<digest::DigestContext>,
     id: u32,
 }
 
-impl<'a, A: digest::Digest<'a, T>, T: DigestType> ListNode<'a, VirtualMuxDigest<'a, A, T>>
-    for VirtualMuxDigest<'a, A, T>
+impl<'a, A: digest::Digest<'a, T> + digest::DigestSaveRestore, T: DigestType>
+    ListNode<'a, VirtualMuxDigest<'a, A, T>> for VirtualMuxDigest<'a, A, T>
 {
     fn next(&self) -> &'a ListLink<VirtualMuxDigest<'a, A, T>> {
         &self.next
     }
 }
 
-impl<'a, A: digest::Digest<'a, T>, T: DigestType> VirtualMuxDigest<'a, A, T> {
+impl<'a, A: digest::Digest<'a, T> + digest::DigestSaveRestore, T: DigestType>
+    VirtualMuxDigest<'a, A, T>
+{
     pub fn new(mux_digest: &'a MuxDigest<'a, A, T>) -> VirtualMuxDigest<'a, A, T> {
         let id = mux_digest.next_id.get();
         mux_digest.next_id.set(id + 1);
@@ -34,12 +64,19 @@ impl<'a, A: digest::Digest<'a, T>, T: DigestType> VirtualMuxDigest<'a, A, T> {
             mux: mux_digest,
             next: ListLink::empty(),
             client: OptionalCell::empty(),
+            verify_client: OptionalCell::empty(),
+            context: MapCell::empty(),
             id: id,
         }
     }
+
+    /// Must be called right after `static_init!()`.
+    pub fn setup(&'a self) {
+        self.mux.users.push_head(self);
+    }
 }
 
-impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::Digest<'a, T>
+impl<'a, A: digest::Digest<'a, T> + digest::DigestSaveRestore, T: DigestType> digest::Digest<'a, T>
     for VirtualMuxDigest<'a, A, T>
 {
     /// Set the client instance which will receive `add_data_done()` and
@@ -54,96 +91,228 @@ impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::Digest<'a, T>
     fn add_data(
         &self,
         data: LeasableBuffer<'static, u8>,
-    ) -> Result<usize, (ReturnCode, &'static mut [u8])> {
-        // Check if any mux is enabled. If it isn't we enable it for us.
-        if self.mux.running.get() == false {
-            self.mux.running.set(true);
-            self.mux.running_id.set(self.id);
-            self.mux.digest.add_data(data)
-        } else if self.mux.running_id.get() == self.id {
-            self.mux.digest.add_data(data)
-        } else {
-            Err((ReturnCode::EBUSY, data.take()))
+    ) -> Result<usize, (CryptoError, &'static mut [u8])> {
+        if let Err(e) = self.mux.acquire(self.id) {
+            return Err((e, data.take()));
+        }
+
+        let result = self.mux.digest.add_data(data);
+        self.mux.busy.set(result.is_ok());
+        result
+    }
+
+    /// Add read-only data to the Digest IP.
+    /// Returns the number of bytes written on success
+    fn add_readonly_data(
+        &self,
+        data: ReadOnlyLeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static [u8])> {
+        if let Err(e) = self.mux.acquire(self.id) {
+            return Err((e, data.take()));
         }
+
+        let result = self.mux.digest.add_readonly_data(data);
+        self.mux.busy.set(result.is_ok());
+        result
     }
 
     /// Request the hardware block to generate a Digest
     /// This doesn't return anything, instead the client needs to have
     /// set a `hash_done` handler.
-    fn run(&'a self, digest: &'static mut T) -> Result<(), (ReturnCode, &'static mut T)> {
-        // Check if any mux is enabled. If it isn't we enable it for us.
-        if self.mux.running.get() == false {
-            self.mux.running.set(true);
-            self.mux.running_id.set(self.id);
-            self.mux.digest.run(digest)
-        } else if self.mux.running_id.get() == self.id {
-            self.mux.digest.run(digest)
-        } else {
-            Err((ReturnCode::EBUSY, digest))
+    fn run(&'a self, digest: &'static mut T) -> Result<(), (CryptoError, &'static mut T)> {
+        if let Err(e) = self.mux.acquire(self.id) {
+            return Err((e, digest));
         }
+
+        let result = self.mux.digest.run(digest);
+        self.mux.busy.set(result.is_ok());
+        result
     }
 
     /// Disable the Digest hardware and clear the keys and any other sensitive
     /// data
     fn clear_data(&self) {
-        if self.mux.running_id.get() == self.id {
+        if self.mux.running.get() && self.mux.running_id.get() == self.id {
             self.mux.running.set(false);
             self.mux.digest.clear_data()
+        } else {
+            // Drop the suspended hash, it may hold a key
+            self.context
+                .map(|context| *context = digest::DigestContext::default());
+            self.context.take();
+        }
+    }
+
+    /// Stop the operations of this user, if the Digest hardware is running
+    /// them, and release the hardware. A suspended hash is dropped.
+    fn cancel(&self) -> digest::Cancelled<T> {
+        if self.mux.running.get() && self.mux.running_id.get() == self.id {
+            self.mux.running.set(false);
+            self.mux.busy.set(false);
+            self.mux.digest.cancel()
+        } else {
+            self.clear_data();
+            digest::Cancelled::default()
         }
     }
 }
 
-impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::Client<'a, T>
-    for VirtualMuxDigest<'a, A, T>
+impl<
+        'a,
+        A: digest::Digest<'a, T> + digest::DigestSaveRestore + digest::DigestVerify<'a, T>,
+        T: DigestType,
+    > digest::DigestVerify<'a, T> for VirtualMuxDigest<'a, A, T>
 {
-    fn add_data_done(&'a self, result: Result<(), ReturnCode>, data: &'static mut [u8]) {
-        self.client
-            .map(move |client| client.add_data_done(result, data));
+    /// Set the client instance which will receive `verification_done()`
+    /// callbacks
+    fn set_verify_client(&'a self, client: &'a dyn digest::ClientVerify<'a, T>) {
+        self.verify_client.set(client);
     }
 
-    fn hash_done(&'a self, result: Result<(), ReturnCode>, digest: &'static mut T) {
-        self.client
-            .map(move |client| client.hash_done(result, digest));
+    /// Request the hardware block to generate a Digest and compare it with
+    /// `compare`. The result is passed to the `verification_done` handler.
+    fn verify(&'a self, compare: &'static mut T) -> Result<(), (CryptoError, &'static mut T)> {
+        if let Err(e) = self.mux.acquire(self.id) {
+            return Err((e, compare));
+        }
+
+        let result = self.mux.digest.verify(compare);
+        self.mux.busy.set(result.is_ok());
+        result
     }
 }
 
-impl<'a, A: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> digest::HMACSha256
-    for VirtualMuxDigest<'a, A, T>
+impl<
+        'a,
+        A: digest::Digest<'a, T> + digest::DigestSaveRestore + digest::HMACSha256,
+        T: DigestType,
+    > digest::HMACSha256 for VirtualMuxDigest<'a, A, T>
 {
-    fn set_mode_hmacsha256(&self, key: &[u8; 32]) -> Result<(), ReturnCode> {
-        // Check if any mux is enabled. If it isn't we enable it for us.
-        if self.mux.running.get() == false {
-            self.mux.running.set(true);
-            self.mux.running_id.set(self.id);
-            self.mux.digest.set_mode_hmacsha256(key)
-        } else if self.mux.running_id.get() == self.id {
-            self.mux.digest.set_mode_hmacsha256(key)
-        } else {
-            Err(ReturnCode::EBUSY)
-        }
+    fn set_mode_hmacsha256(&self, key: &[u8]) -> Result<(), CryptoError> {
+        self.mux.acquire(self.id)?;
+        self.mux.digest.set_mode_hmacsha256(key)
+    }
+}
+
+impl<'a, A: digest::Digest<'a, T> + digest::DigestSaveRestore + digest::Sha256, T: DigestType>
+    digest::Sha256 for VirtualMuxDigest<'a, A, T>
+{
+    fn set_mode_sha256(&self) -> Result<(), CryptoError> {
+        self.mux.acquire(self.id)?;
+        self.mux.digest.set_mode_sha256()
     }
 }
 
 /// Calling a 'set_mode*()' function from a `VirtualMuxDigest` will mark that
 /// `VirtualMuxDigest` as the one that has been enabled and running. Until that
 /// Mux calls `clear_data()` it will be the only `VirtualMuxDigest` that can
-/// interact with the underlying device.
-pub struct MuxDigest<'a, A: digest::Digest<'a, T>, T: DigestType> {
+/// interact with the underlying device, unless the device can suspend its
+/// hash with `save_context()`.
+pub struct MuxDigest<'a, A: digest::Digest<'a, T> + digest::DigestSaveRestore, T: DigestType> {
     digest: &'a A,
+    users: List<'a, VirtualMuxDigest<'a, A, T>>,
     running: Cell<bool>,
     running_id: Cell<u32>,
+    /// Whether an operation of the running user is in progress, so that
+    /// its hash cannot be suspended.
+    busy: Cell<bool>,
     next_id: Cell<u32>,
     phantom: PhantomData<&'a T>,
 }
 
-impl<'a, A: digest::Digest<'a, T>, T: DigestType> MuxDigest<'a, A, T> {
+impl<'a, A: digest::Digest<'a, T> + digest::DigestSaveRestore, T: DigestType> MuxDigest<'a, A, T> {
     pub const fn new(digest: &'a A) -> MuxDigest<'a, A, T> {
         MuxDigest {
             digest: digest,
+            users: List::new(),
             running: Cell::new(false),
             running_id: Cell::new(0),
+            busy: Cell::new(false),
             next_id: Cell::new(0),
             phantom: PhantomData,
         }
     }
+
+    fn find_user(&self, id: u32) -> Option<&'a VirtualMuxDigest<'a, A, T>> {
+        self.users.iter().find(|user| user.id == id)
+    }
+
+    /// Give the device to the user `id`. If another user is running, its
+    /// hash is suspended, which is only possible between two of its
+    /// operations and on devices that can save their state. The hash of
+    /// `id` is resumed if it was suspended.
+    fn acquire(&self, id: u32) -> Result<(), CryptoError> {
+        if self.running.get() {
+            if self.running_id.get() == id {
+                return Ok(());
+            }
+            if self.busy.get() {
+                return Err(CryptoError::EngineBusy);
+            }
+
+            let mut context = digest::DigestContext::default();
+            if self.digest.save_context(&mut context).is_err() {
+                return Err(CryptoError::EngineBusy);
+            }
+            self.find_user(self.running_id.get())
+                .map(|user| user.context.put(context));
+            self.digest.clear_data();
+        }
+
+        self.running.set(true);
+        self.running_id.set(id);
+
+        if let Some(user) = self.find_user(id) {
+            if let Some(context) = user.context.take() {
+                if let Err(e) = self.digest.restore_context(&context) {
+                    // Keep the hash, to try again on the next call
+                    user.context.put(context);
+                    self.running.set(false);
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, A: digest::Digest<'a, T> + digest::DigestSaveRestore, T: DigestType> digest::Client<'a, T>
+    for MuxDigest<'a, A, T>
+{
+    fn add_data_done(&'a self, result: Result<(), CryptoError>, data: &'static mut [u8]) {
+        self.busy.set(false);
+        self.find_user(self.running_id.get()).map(move |user| {
+            user.client
+                .map(move |client| client.add_data_done(result, data))
+        });
+    }
+
+    fn add_readonly_data_done(&'a self, result: Result<(), CryptoError>, data: &'static [u8]) {
+        self.busy.set(false);
+        self.find_user(self.running_id.get()).map(move |user| {
+            user.client
+                .map(move |client| client.add_readonly_data_done(result, data))
+        });
+    }
+
+    fn hash_done(&'a self, result: Result<(), CryptoError>, digest: &'static mut T) {
+        self.busy.set(false);
+        self.find_user(self.running_id.get()).map(move |user| {
+            user.client
+                .map(move |client| client.hash_done(result, digest))
+        });
+    }
+}
+
+impl<'a, A: digest::Digest<'a, T> + digest::DigestSaveRestore, T: DigestType>
+    digest::ClientVerify<'a, T> for MuxDigest<'a, A, T>
+{
+    fn verification_done(&'a self, result: Result<bool, CryptoError>, compare: &'static mut T) {
+        self.busy.set(false);
+        self.find_user(self.running_id.get()).map(move |user| {
+            user.verify_client
+                .map(move |client| client.verification_done(result, compare))
+        });
+    }
 }
@@ -0,0 +1,347 @@
+//! Expose the pins of an asynchronous GPIO port, such as an I2C GPIO extender,
+//! through the synchronous `hil::gpio` pin traits.
+//!
+//! Calls on `hil::gpio` pins complete immediately, while every operation on a
+//! `gpio_async::Port` is split-phase. `MuxGpioAsync` bridges the two by keeping
+//! a shadow copy of the configuration and output value of each
+//! `GpioAsyncPin`. Calls on a pin update the shadow state and return, and the
+//! mux then pushes pending changes to the port one operation at a time.
+//!
+//! Reading a pin returns the last value known to the mux: the output value for
+//! output pins, and for input pins the value sampled when the pin was
+//! configured or when it last interrupted. Interrupt clients are only called
+//! once the new value of the pin has been read, so `read()` in a `fired()`
+//! callback reflects the edge that occurred.
+//!
+//! This allows expander pins to be handed to the `led`, `button` and `gpio`
+//! capsules (and their components) in the same way as native pins.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let mcp_mux = static_init!(
+//!     capsules::virtual_gpio_async::MuxGpioAsync<'static, capsules::mcp230xx::MCP230xx<'static>>,
+//!     capsules::virtual_gpio_async::MuxGpioAsync::new(mcp23017)
+//! );
+//! mcp23017.set_client(mcp_mux);
+//!
+//! let mcp_pin0 = static_init!(
+//!     capsules::virtual_gpio_async::GpioAsyncPin<'static, capsules::mcp230xx::MCP230xx<'static>>,
+//!     capsules::virtual_gpio_async::GpioAsyncPin::new(mcp_mux, 0)
+//! );
+//! mcp_pin0.setup(); // This is important!
+//!
+//! let led = components::led::LedsComponent::new(components::led_component_helper!(
+//!     capsules::virtual_gpio_async::GpioAsyncPin<'static, capsules::mcp230xx::MCP230xx<'static>>,
+//!     (mcp_pin0, kernel::hil::gpio::ActivationMode::ActiveHigh)
+//! ))
+//! .finalize(components::led_component_buf!(
+//!     capsules::virtual_gpio_async::GpioAsyncPin<'static, capsules::mcp230xx::MCP230xx<'static>>
+//! ));
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::common::{List, ListLink, ListNode};
+use kernel::hil::gpio;
+use kernel::hil::gpio_async;
+use kernel::ReturnCode;
+
+/// Port operations a pin can have outstanding, in the order they are issued.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operation {
+    Configure,
+    Output,
+    Interrupt,
+    Read,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Disabled,
+    Input,
+    Output,
+}
+
+pub struct MuxGpioAsync<'a, P: gpio_async::Port> {
+    port: &'a P,
+    pins: List<'a, GpioAsyncPin<'a, P>>,
+    inflight: OptionalCell<(&'a GpioAsyncPin<'a, P>, Operation)>,
+}
+
+impl<'a, P: gpio_async::Port> MuxGpioAsync<'a, P> {
+    pub const fn new(port: &'a P) -> MuxGpioAsync<'a, P> {
+        MuxGpioAsync {
+            port: port,
+            pins: List::new(),
+            inflight: OptionalCell::empty(),
+        }
+    }
+
+    /// Issue the next pending operation, if the port is not already busy.
+    fn do_next_op(&self) {
+        if self.inflight.is_some() {
+            return;
+        }
+
+        for pin in self.pins.iter() {
+            while let Some(op) = pin.next_op() {
+                match self.issue(pin, op) {
+                    ReturnCode::SUCCESS => {
+                        self.inflight.set((pin, op));
+                        return;
+                    }
+                    ReturnCode::EBUSY => {
+                        // The port is busy on its own (e.g. servicing an
+                        // interrupt). Retry on its next callback.
+                        pin.set_pending(op);
+                        return;
+                    }
+                    _ => pin.op_failed(op),
+                }
+            }
+        }
+    }
+
+    fn issue(&self, pin: &GpioAsyncPin<'a, P>, op: Operation) -> ReturnCode {
+        let number = pin.pin;
+        match op {
+            Operation::Configure => match pin.mode.get() {
+                Mode::Disabled => self.port.disable(number),
+                Mode::Input => self.port.make_input(number, pin.floating_state.get()),
+                Mode::Output => self.port.make_output(number),
+            },
+            Operation::Output => {
+                if pin.output.get() {
+                    self.port.set(number)
+                } else {
+                    self.port.clear(number)
+                }
+            }
+            Operation::Interrupt => match pin.interrupt_edge.get() {
+                Some(edge) => self.port.enable_interrupt(number, edge),
+                None => self.port.disable_interrupt(number),
+            },
+            Operation::Read => self.port.read(number),
+        }
+    }
+}
+
+impl<'a, P: gpio_async::Port> gpio_async::Client for MuxGpioAsync<'a, P> {
+    fn fired(&self, pin: usize, _identifier: usize) {
+        if let Some(node) = self.pins.iter().find(|node| node.pin == pin) {
+            node.fire_pending.set(true);
+            node.set_pending(Operation::Read);
+        }
+        self.do_next_op();
+    }
+
+    fn done(&self, value: usize) {
+        self.inflight.take().map(|(pin, op)| {
+            if op == Operation::Read {
+                pin.input.set(value != 0);
+                pin.fire();
+            }
+        });
+        self.do_next_op();
+    }
+}
+
+pub struct GpioAsyncPin<'a, P: gpio_async::Port> {
+    mux: &'a MuxGpioAsync<'a, P>,
+    pin: usize,
+    mode: Cell<Mode>,
+    floating_state: Cell<gpio::FloatingState>,
+    output: Cell<bool>,
+    input: Cell<bool>,
+    interrupt_edge: Cell<Option<gpio::InterruptEdge>>,
+    pending: Cell<[bool; 4]>,
+    fire_pending: Cell<bool>,
+    client: OptionalCell<&'static dyn gpio::Client>,
+    next: ListLink<'a, GpioAsyncPin<'a, P>>,
+}
+
+impl<'a, P: gpio_async::Port> ListNode<'a, GpioAsyncPin<'a, P>> for GpioAsyncPin<'a, P> {
+    fn next(&self) -> &'a ListLink<GpioAsyncPin<'a, P>> {
+        &self.next
+    }
+}
+
+impl<'a, P: gpio_async::Port> GpioAsyncPin<'a, P> {
+    pub const fn new(mux: &'a MuxGpioAsync<'a, P>, pin: usize) -> GpioAsyncPin<'a, P> {
+        GpioAsyncPin {
+            mux: mux,
+            pin: pin,
+            mode: Cell::new(Mode::Disabled),
+            floating_state: Cell::new(gpio::FloatingState::PullNone),
+            output: Cell::new(false),
+            input: Cell::new(false),
+            interrupt_edge: Cell::new(None),
+            pending: Cell::new([false; 4]),
+            fire_pending: Cell::new(false),
+            client: OptionalCell::empty(),
+            next: ListLink::empty(),
+        }
+    }
+
+    /// Register this pin with the mux. Must be called before the pin is used.
+    pub fn setup(&'a self) {
+        self.mux.pins.push_head(self);
+    }
+
+    fn set_pending(&self, op: Operation) {
+        let mut pending = self.pending.get();
+        pending[op as usize] = true;
+        self.pending.set(pending);
+    }
+
+    /// Returns the next operation to send to the port and marks it as no
+    /// longer pending, so that changes made while it is in flight are sent
+    /// again.
+    fn next_op(&self) -> Option<Operation> {
+        let mut pending = self.pending.get();
+        let op = [
+            Operation::Configure,
+            Operation::Output,
+            Operation::Interrupt,
+            Operation::Read,
+        ]
+        .iter()
+        .find(|op| pending[**op as usize])
+        .cloned();
+        if let Some(op) = op {
+            pending[op as usize] = false;
+            self.pending.set(pending);
+        }
+        op
+    }
+
+    fn op_failed(&self, op: Operation) {
+        if op == Operation::Read {
+            // Report the interrupt anyway, with the last known value.
+            self.fire();
+        }
+    }
+
+    fn fire(&self) {
+        if self.fire_pending.replace(false) && self.interrupt_edge.get().is_some() {
+            self.client.map(|client| client.fired());
+        }
+    }
+
+    /// Record that `op` needs to be sent to the port and start it if the
+    /// port is idle.
+    fn request(&self, op: Operation) {
+        self.set_pending(op);
+        self.mux.do_next_op();
+    }
+}
+
+impl<P: gpio_async::Port> gpio::Configure for GpioAsyncPin<'_, P> {
+    fn configuration(&self) -> gpio::Configuration {
+        match self.mode.get() {
+            Mode::Disabled => gpio::Configuration::LowPower,
+            Mode::Input => gpio::Configuration::Input,
+            Mode::Output => gpio::Configuration::Output,
+        }
+    }
+
+    fn make_output(&self) -> gpio::Configuration {
+        self.mode.set(Mode::Output);
+        self.set_pending(Operation::Configure);
+        self.request(Operation::Output);
+        self.configuration()
+    }
+
+    fn disable_output(&self) -> gpio::Configuration {
+        if self.mode.get() == Mode::Output {
+            self.mode.set(Mode::Disabled);
+            self.request(Operation::Configure);
+        }
+        self.configuration()
+    }
+
+    fn make_input(&self) -> gpio::Configuration {
+        self.mode.set(Mode::Input);
+        self.set_pending(Operation::Configure);
+        self.request(Operation::Read);
+        self.configuration()
+    }
+
+    fn disable_input(&self) -> gpio::Configuration {
+        if self.mode.get() == Mode::Input {
+            self.mode.set(Mode::Disabled);
+            self.request(Operation::Configure);
+        }
+        self.configuration()
+    }
+
+    fn deactivate_to_low_power(&self) {
+        self.mode.set(Mode::Disabled);
+        self.request(Operation::Configure);
+    }
+
+    fn set_floating_state(&self, state: gpio::FloatingState) {
+        self.floating_state.set(state);
+        if self.mode.get() == Mode::Input {
+            self.request(Operation::Configure);
+        }
+    }
+
+    fn floating_state(&self) -> gpio::FloatingState {
+        self.floating_state.get()
+    }
+}
+
+impl<P: gpio_async::Port> gpio::Output for GpioAsyncPin<'_, P> {
+    fn set(&self) {
+        self.output.set(true);
+        self.request(Operation::Output);
+    }
+
+    fn clear(&self) {
+        self.output.set(false);
+        self.request(Operation::Output);
+    }
+
+    fn toggle(&self) -> bool {
+        let value = !self.output.get();
+        self.output.set(value);
+        self.request(Operation::Output);
+        value
+    }
+}
+
+impl<P: gpio_async::Port> gpio::Input for GpioAsyncPin<'_, P> {
+    fn read(&self) -> bool {
+        match self.mode.get() {
+            Mode::Output => self.output.get(),
+            _ => self.input.get(),
+        }
+    }
+}
+
+impl<P: gpio_async::Port> gpio::Interrupt for GpioAsyncPin<'_, P> {
+    fn set_client(&self, client: &'static dyn gpio::Client) {
+        self.client.set(client);
+    }
+
+    fn enable_interrupts(&self, mode: gpio::InterruptEdge) {
+        self.interrupt_edge.set(Some(mode));
+        self.request(Operation::Interrupt);
+    }
+
+    fn disable_interrupts(&self) {
+        self.interrupt_edge.set(None);
+        self.fire_pending.set(false);
+        self.request(Operation::Interrupt);
+    }
+
+    fn is_pending(&self) -> bool {
+        self.fire_pending.get()
+    }
+}
+
+impl<P: gpio_async::Port> gpio::Pin for GpioAsyncPin<'_, P> {}
+impl<P: gpio_async::Port> gpio::InterruptPin for GpioAsyncPin<'_, P> {}
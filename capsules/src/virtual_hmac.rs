@@ -1,19 +1,42 @@
 //! Virtualize the HMAC interface to enable multiple users of an underlying
 //! HMAC hardware peripheral.
+//!
+//! The users take turns, from the first call of one user to its
+//! `clear_data()`. The mux is the client of the engine, and passes each
+//! callback to the client of the running user.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let mux_hmac = static_init!(
+//!     MuxHmac<'static, lowrisc::hmac::Hmac, [u8; 32]>,
+//!     MuxHmac::new(&ibex::hmac::HMAC)
+//! );
+//! digest::Digest::set_client(&ibex::hmac::HMAC, mux_hmac);
+//! digest::DigestVerify::set_verify_client(&ibex::hmac::HMAC, mux_hmac);
+//!
+//! let virtual_hmac_user = static_init!(
+//!     VirtualMuxHmac<'static, lowrisc::hmac::Hmac, [u8; 32]>,
+//!     VirtualMuxHmac::new(mux_hmac)
+//! );
+//! virtual_hmac_user.setup();
+//! ```
 
 use core::cell::Cell;
 use core::marker::PhantomData;
 use kernel::common::cells::OptionalCell;
-use kernel::common::leasable_buffer::LeasableBuffer;
-use kernel::common::{ListLink, ListNode};
+use kernel::common::leasable_buffer::{LeasableBuffer, ReadOnlyLeasableBuffer};
+use kernel::common::{List, ListLink, ListNode};
+use kernel::hil::crypto::CryptoError;
 use kernel::hil::digest;
 use kernel::hil::digest::DigestType;
-use kernel::ReturnCode;
 
 pub struct VirtualMuxHmac<'a, A: digest::Digest<'a, T>, T: DigestType> {
     mux: &'a MuxHmac<'a, A, T>,
     next: ListLink<'a, VirtualMuxHmac<'a, A, T>>,
     client: OptionalCell<&'a dyn digest::Client<'a, T>>,
+    verify_client: OptionalCell<&'a dyn digest::ClientVerify<'a, T>>,
     id: u32,
 }
 
@@ -34,9 +57,15 @@ impl<'a, A: digest::Digest<'a, T>, T: DigestType> VirtualMuxHmac<'a, A, T> {
             mux: mux_hmac,
             next: ListLink::empty(),
             client: OptionalCell::empty(),
+            verify_client: OptionalCell::empty(),
             id: id,
         }
     }
+
+    /// Must be called right after `static_init!()`.
+    pub fn setup(&'a self) {
+        self.mux.users.push_head(self);
+    }
 }
 
 impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::Digest<'a, T>
@@ -45,7 +74,7 @@ impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::Digest<'a, T>
     /// Set the client instance which will receive `add_data_done()` and
     /// `hash_done()` callbacks
     fn set_client(&'a self, client: &'a dyn digest::Client<'a, T>) {
-        self.mux.hmac.set_client(client);
+        self.client.set(client);
     }
 
     /// Add data to the hmac IP.
@@ -54,7 +83,7 @@ impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::Digest<'a, T>
     fn add_data(
         &self,
         data: LeasableBuffer<'static, u8>,
-    ) -> Result<usize, (ReturnCode, &'static mut [u8])> {
+    ) -> Result<usize, (CryptoError, &'static mut [u8])> {
         // Check if any mux is enabled. If it isn't we enable it for us.
         if self.mux.running.get() == false {
             self.mux.running.set(true);
@@ -63,14 +92,32 @@ impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::Digest<'a, T>
         } else if self.mux.running_id.get() == self.id {
             self.mux.hmac.add_data(data)
         } else {
-            Err((ReturnCode::EBUSY, data.take()))
+            Err((CryptoError::EngineBusy, data.take()))
+        }
+    }
+
+    /// Add read-only data to the HMAC IP.
+    /// Returns the number of bytes written on success
+    fn add_readonly_data(
+        &self,
+        data: ReadOnlyLeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static [u8])> {
+        // Check if any mux is enabled. If it isn't we enable it for us.
+        if self.mux.running.get() == false {
+            self.mux.running.set(true);
+            self.mux.running_id.set(self.id);
+            self.mux.hmac.add_readonly_data(data)
+        } else if self.mux.running_id.get() == self.id {
+            self.mux.hmac.add_readonly_data(data)
+        } else {
+            Err((CryptoError::EngineBusy, data.take()))
         }
     }
 
     /// Request the hardware block to generate a HMAC
     /// This doesn't return anything, instead the client needs to have
     /// set a `hash_done` handler.
-    fn run(&'a self, digest: &'static mut T) -> Result<(), (ReturnCode, &'static mut T)> {
+    fn run(&'a self, digest: &'static mut T) -> Result<(), (CryptoError, &'static mut T)> {
         // Check if any mux is enabled. If it isn't we enable it for us.
         if self.mux.running.get() == false {
             self.mux.running.set(true);
@@ -79,7 +126,7 @@ impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::Digest<'a, T>
         } else if self.mux.running_id.get() == self.id {
             self.mux.hmac.run(digest)
         } else {
-            Err((ReturnCode::EBUSY, digest))
+            Err((CryptoError::EngineBusy, digest))
         }
     }
 
@@ -91,26 +138,48 @@ impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::Digest<'a, T>
             self.mux.hmac.clear_data()
         }
     }
+
+    /// Stop the operations of this user, if the HMAC hardware is running
+    /// them, and release the hardware
+    fn cancel(&self) -> digest::Cancelled<T> {
+        if self.mux.running.get() && self.mux.running_id.get() == self.id {
+            self.mux.running.set(false);
+            self.mux.hmac.cancel()
+        } else {
+            digest::Cancelled::default()
+        }
+    }
 }
 
-impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::Client<'a, T>
-    for VirtualMuxHmac<'a, A, T>
+impl<'a, A: digest::Digest<'a, T> + digest::DigestVerify<'a, T>, T: DigestType>
+    digest::DigestVerify<'a, T> for VirtualMuxHmac<'a, A, T>
 {
-    fn add_data_done(&'a self, result: Result<(), ReturnCode>, data: &'static mut [u8]) {
-        self.client
-            .map(move |client| client.add_data_done(result, data));
+    /// Set the client instance which will receive `verification_done()`
+    /// callbacks
+    fn set_verify_client(&'a self, client: &'a dyn digest::ClientVerify<'a, T>) {
+        self.verify_client.set(client);
     }
 
-    fn hash_done(&'a self, result: Result<(), ReturnCode>, digest: &'static mut T) {
-        self.client
-            .map(move |client| client.hash_done(result, digest));
+    /// Request the hardware block to generate a HMAC and compare it with
+    /// `compare`. The result is passed to the `verification_done` handler.
+    fn verify(&'a self, compare: &'static mut T) -> Result<(), (CryptoError, &'static mut T)> {
+        // Check if any mux is enabled. If it isn't we enable it for us.
+        if self.mux.running.get() == false {
+            self.mux.running.set(true);
+            self.mux.running_id.set(self.id);
+            self.mux.hmac.verify(compare)
+        } else if self.mux.running_id.get() == self.id {
+            self.mux.hmac.verify(compare)
+        } else {
+            Err((CryptoError::EngineBusy, compare))
+        }
     }
 }
 
 impl<'a, A: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> digest::HMACSha256
     for VirtualMuxHmac<'a, A, T>
 {
-    fn set_mode_hmacsha256(&self, key: &[u8; 32]) -> Result<(), ReturnCode> {
+    fn set_mode_hmacsha256(&self, key: &[u8]) -> Result<(), CryptoError> {
         // Check if any mux is enabled. If it isn't we enable it for us.
         if self.mux.running.get() == false {
             self.mux.running.set(true);
@@ -119,27 +188,85 @@ impl<'a, A: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> digest::H
         } else if self.mux.running_id.get() == self.id {
             self.mux.hmac.set_mode_hmacsha256(key)
         } else {
-            Err(ReturnCode::EBUSY)
+            Err(CryptoError::EngineBusy)
         }
     }
 }
 
 pub struct MuxHmac<'a, A: digest::Digest<'a, T>, T: DigestType> {
     hmac: &'a A,
+    users: List<'a, VirtualMuxHmac<'a, A, T>>,
     running: Cell<bool>,
     running_id: Cell<u32>,
     next_id: Cell<u32>,
     phantom: PhantomData<&'a T>,
 }
 
+impl<'a, A: digest::Digest<'a, T> + digest::Sha256, T: DigestType> digest::Sha256
+    for VirtualMuxHmac<'a, A, T>
+{
+    fn set_mode_sha256(&self) -> Result<(), CryptoError> {
+        if self.mux.running.get() == false {
+            self.mux.running.set(true);
+            self.mux.running_id.set(self.id);
+            self.mux.hmac.set_mode_sha256()
+        } else if self.mux.running_id.get() == self.id {
+            self.mux.hmac.set_mode_sha256()
+        } else {
+            Err(CryptoError::EngineBusy)
+        }
+    }
+}
+
 impl<'a, A: digest::Digest<'a, T>, T: DigestType> MuxHmac<'a, A, T> {
     pub const fn new(hmac: &'a A) -> MuxHmac<'a, A, T> {
         MuxHmac {
             hmac,
+            users: List::new(),
             running: Cell::new(false),
             running_id: Cell::new(0),
             next_id: Cell::new(0),
             phantom: PhantomData,
         }
     }
+
+    /// The user whose operations the engine is running.
+    fn running_user(&self) -> Option<&'a VirtualMuxHmac<'a, A, T>> {
+        let id = self.running_id.get();
+        self.users.iter().find(|user| user.id == id)
+    }
+}
+
+impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::Client<'a, T> for MuxHmac<'a, A, T> {
+    fn add_data_done(&'a self, result: Result<(), CryptoError>, data: &'static mut [u8]) {
+        self.running_user().map(move |user| {
+            user.client
+                .map(move |client| client.add_data_done(result, data))
+        });
+    }
+
+    fn add_readonly_data_done(&'a self, result: Result<(), CryptoError>, data: &'static [u8]) {
+        self.running_user().map(move |user| {
+            user.client
+                .map(move |client| client.add_readonly_data_done(result, data))
+        });
+    }
+
+    fn hash_done(&'a self, result: Result<(), CryptoError>, digest: &'static mut T) {
+        self.running_user().map(move |user| {
+            user.client
+                .map(move |client| client.hash_done(result, digest))
+        });
+    }
+}
+
+impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::ClientVerify<'a, T>
+    for MuxHmac<'a, A, T>
+{
+    fn verification_done(&'a self, result: Result<bool, CryptoError>, compare: &'static mut T) {
+        self.running_user().map(move |user| {
+            user.verify_client
+                .map(move |client| client.verification_done(result, compare))
+        });
+    }
 }
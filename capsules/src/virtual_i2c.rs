@@ -3,6 +3,7 @@
 //! `MuxI2C` provides shared access to a single I2C Master Bus for multiple
 //! users. `I2CDevice` provides access to a specific I2C address.
 
+use crate::bus_capture::{Bus, Capture, Status};
 use core::cell::Cell;
 use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::common::{List, ListLink, ListNode};
@@ -13,10 +14,22 @@ pub struct MuxI2C<'a> {
     devices: List<'a, I2CDevice<'a>>,
     enabled: Cell<usize>,
     inflight: OptionalCell<&'a I2CDevice<'a>>,
+    capture: OptionalCell<&'a dyn Capture>,
+    /// Capture handle of the transaction in flight.
+    capture_handle: OptionalCell<u32>,
 }
 
 impl I2CHwMasterClient for MuxI2C<'_> {
     fn command_complete(&self, buffer: &'static mut [u8], error: Error) {
+        self.capture_handle.take().map(|handle| {
+            self.capture.map(|capture| {
+                let status = match error {
+                    Error::CommandComplete => Status::Complete,
+                    error => Status::I2CError(error),
+                };
+                capture.complete(handle, status);
+            });
+        });
         self.inflight.take().map(move |device| {
             device.command_complete(buffer, error);
         });
@@ -31,9 +44,16 @@ impl<'a> MuxI2C<'a> {
             devices: List::new(),
             enabled: Cell::new(0),
             inflight: OptionalCell::empty(),
+            capture: OptionalCell::empty(),
+            capture_handle: OptionalCell::empty(),
         }
     }
 
+    /// Record the transactions on this bus, see `bus_capture`.
+    pub fn set_capture(&self, capture: &'a dyn Capture) {
+        self.capture.set(capture);
+    }
+
     fn enable(&self) {
         let enabled = self.enabled.get();
         self.enabled.set(enabled + 1);
@@ -58,6 +78,21 @@ impl<'a> MuxI2C<'a> {
                 .find(|node| node.operation.get() != Op::Idle);
             mnode.map(|node| {
                 node.buffer.take().map(|buf| {
+                    self.capture.map(|capture| {
+                        let (write_len, read_len) = match node.operation.get() {
+                            Op::Write(len) => (len, 0),
+                            Op::Read(len) => (0, len),
+                            Op::WriteRead(wlen, rlen) => (wlen, rlen),
+                            Op::Idle => (0, 0),
+                        };
+                        self.capture_handle.insert(capture.start(
+                            Bus::I2C,
+                            node.addr,
+                            buf,
+                            write_len as usize,
+                            read_len as usize,
+                        ));
+                    });
                     match node.operation.get() {
                         Op::Write(len) => self.i2c.write(node.addr, buf, len),
                         Op::Read(len) => self.i2c.read(node.addr, buf, len),
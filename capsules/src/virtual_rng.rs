@@ -0,0 +1,158 @@
+//! Virtualize a random number generator.
+//!
+//! `MuxRngMaster` provides shared access to a single `Rng` from multiple
+//! clients in the kernel, for instance the userspace RNG driver and the nonce
+//! service. Each client uses a `VirtualRngMasterDevice`, which implements
+//! `Rng`. Requests are served one at a time: a device keeps receiving
+//! randomness until its client returns `Continue::Done`, then the next device
+//! with a pending request is served.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let mux_rng = static_init!(
+//!     capsules::virtual_rng::MuxRngMaster<'static>,
+//!     capsules::virtual_rng::MuxRngMaster::new(entropy_to_random)
+//! );
+//! entropy_to_random.set_client(mux_rng);
+//!
+//! let virtual_rng = static_init!(
+//!     capsules::virtual_rng::VirtualRngMasterDevice<'static>,
+//!     capsules::virtual_rng::VirtualRngMasterDevice::new(mux_rng)
+//! );
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::common::{List, ListLink, ListNode};
+use kernel::hil::rng::{Client, Continue, Rng};
+use kernel::ReturnCode;
+
+pub struct MuxRngMaster<'a> {
+    rng: &'a dyn Rng<'a>,
+    devices: List<'a, VirtualRngMasterDevice<'a>>,
+    inflight: OptionalCell<&'a VirtualRngMasterDevice<'a>>,
+}
+
+impl<'a> MuxRngMaster<'a> {
+    pub const fn new(rng: &'a dyn Rng<'a>) -> MuxRngMaster<'a> {
+        MuxRngMaster {
+            rng: rng,
+            devices: List::new(),
+            inflight: OptionalCell::empty(),
+        }
+    }
+
+    fn next_pending(&self) -> Option<&'a VirtualRngMasterDevice<'a>> {
+        self.devices
+            .iter()
+            .find(|node| node.operation.get() == Op::Get)
+    }
+
+    /// Start serving the first device with a pending request, unless a request
+    /// is already in progress.
+    fn do_next_op(&self) -> ReturnCode {
+        if self.inflight.is_some() {
+            return ReturnCode::SUCCESS;
+        }
+        self.next_pending().map_or(ReturnCode::SUCCESS, |node| {
+            let res = self.rng.get();
+            if res == ReturnCode::SUCCESS {
+                self.inflight.set(node);
+            } else {
+                node.operation.set(Op::Idle);
+            }
+            res
+        })
+    }
+
+    fn is_inflight(&self, device: &VirtualRngMasterDevice<'a>) -> bool {
+        self.inflight
+            .map_or(false, |inflight| core::ptr::eq(*inflight, device))
+    }
+}
+
+impl Client for MuxRngMaster<'_> {
+    fn randomness_available(
+        &self,
+        randomness: &mut dyn Iterator<Item = u32>,
+        error: ReturnCode,
+    ) -> Continue {
+        let cont = self.inflight.map_or(Continue::Done, |device| {
+            let cont = device.client.map_or(Continue::Done, |client| {
+                client.randomness_available(randomness, error)
+            });
+            if cont == Continue::Done {
+                device.operation.set(Op::Idle);
+            }
+            cont
+        });
+        if cont == Continue::More {
+            return cont;
+        }
+
+        // Keep the generator running for the next device waiting, if any.
+        self.inflight.clear();
+        self.next_pending().map_or(Continue::Done, |node| {
+            self.inflight.set(node);
+            Continue::More
+        })
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Op {
+    Idle,
+    Get,
+}
+
+pub struct VirtualRngMasterDevice<'a> {
+    mux: &'a MuxRngMaster<'a>,
+    operation: Cell<Op>,
+    next: ListLink<'a, VirtualRngMasterDevice<'a>>,
+    client: OptionalCell<&'a dyn Client>,
+}
+
+impl<'a> VirtualRngMasterDevice<'a> {
+    pub const fn new(mux: &'a MuxRngMaster<'a>) -> VirtualRngMasterDevice<'a> {
+        VirtualRngMasterDevice {
+            mux: mux,
+            operation: Cell::new(Op::Idle),
+            next: ListLink::empty(),
+            client: OptionalCell::empty(),
+        }
+    }
+}
+
+impl<'a> ListNode<'a, VirtualRngMasterDevice<'a>> for VirtualRngMasterDevice<'a> {
+    fn next(&'a self) -> &'a ListLink<'a, VirtualRngMasterDevice<'a>> {
+        &self.next
+    }
+}
+
+impl<'a> Rng<'a> for VirtualRngMasterDevice<'a> {
+    fn get(&self) -> ReturnCode {
+        self.operation.set(Op::Get);
+        self.mux.do_next_op()
+    }
+
+    fn cancel(&self) -> ReturnCode {
+        if !self.mux.is_inflight(self) {
+            self.operation.set(Op::Idle);
+            return ReturnCode::SUCCESS;
+        }
+        let res = self.mux.rng.cancel();
+        if res == ReturnCode::SUCCESS {
+            self.operation.set(Op::Idle);
+            self.mux.inflight.clear();
+            self.mux.do_next_op();
+        }
+        res
+    }
+
+    fn set_client(&'a self, client: &'a dyn Client) {
+        self.mux.devices.push_head(self);
+        self.client.set(client);
+    }
+}
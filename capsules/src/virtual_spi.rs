@@ -1,5 +1,6 @@
 //! Virtualize a SPI master bus to enable multiple users of the SPI bus.
 
+use crate::bus_capture::{Bus, Capture, Status};
 use core::cell::Cell;
 use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::common::{List, ListLink, ListNode};
@@ -12,6 +13,9 @@ pub struct MuxSpiMaster<'a, Spi: hil::spi::SpiMaster> {
     spi: &'a Spi,
     devices: List<'a, VirtualSpiMasterDevice<'a, Spi>>,
     inflight: OptionalCell<&'a VirtualSpiMasterDevice<'a, Spi>>,
+    capture: OptionalCell<&'a dyn Capture>,
+    /// Capture handle of the transaction in flight.
+    capture_handle: OptionalCell<u32>,
 }
 
 impl<Spi: hil::spi::SpiMaster> hil::spi::SpiMasterClient for MuxSpiMaster<'_, Spi> {
@@ -21,6 +25,10 @@ impl<Spi: hil::spi::SpiMaster> hil::spi::SpiMasterClient for MuxSpiMaster<'_, Sp
         read_buffer: Option<&'static mut [u8]>,
         len: usize,
     ) {
+        self.capture_handle.take().map(|handle| {
+            self.capture
+                .map(|capture| capture.complete(handle, Status::Complete));
+        });
         self.inflight.take().map(move |device| {
             self.do_next_op();
             device.read_write_done(write_buffer, read_buffer, len);
@@ -34,9 +42,16 @@ impl<'a, Spi: hil::spi::SpiMaster> MuxSpiMaster<'a, Spi> {
             spi: spi,
             devices: List::new(),
             inflight: OptionalCell::empty(),
+            capture: OptionalCell::empty(),
+            capture_handle: OptionalCell::empty(),
         }
     }
 
+    /// Record the transactions on this bus, see `bus_capture`.
+    pub fn set_capture(&self, capture: &'a dyn Capture) {
+        self.capture.set(capture);
+    }
+
     fn do_next_op(&self) {
         if self.inflight.is_none() {
             let mnode = self
@@ -62,6 +77,21 @@ impl<'a, Spi: hil::spi::SpiMaster> MuxSpiMaster<'a, Spi> {
                         self.inflight.set(node);
                         node.txbuffer.take().map(|txbuffer| {
                             let rxbuffer = node.rxbuffer.take();
+                            self.capture.map(|capture| {
+                                let device = self
+                                    .devices
+                                    .iter()
+                                    .position(|device| core::ptr::eq(device, node))
+                                    .unwrap_or(0);
+                                let read_len = rxbuffer.as_ref().map_or(0, |_| len);
+                                self.capture_handle.insert(capture.start(
+                                    Bus::Spi,
+                                    device as u8,
+                                    txbuffer,
+                                    len,
+                                    read_len,
+                                ));
+                            });
                             self.spi.read_write_bytes(txbuffer, rxbuffer, len);
                         });
                     }
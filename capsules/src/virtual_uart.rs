@@ -89,6 +89,13 @@ impl<'a> uart::ReceiveClient for MuxUart<'a> {
         let mut next_read_len = buffer.len();
         let mut read_pending = false;
 
+        // A line error, such as a break, ends the reads of all clients, so
+        // that they hear about it.
+        let line_error = match error {
+            uart::Error::None | uart::Error::Aborted => false,
+            _ => true,
+        };
+
         // Set a flag that we are in this callback handler. This allows us to
         // note that we can wait until all callbacks are finished before
         // starting a new UART receive.
@@ -134,7 +141,8 @@ impl<'a> uart::ReceiveClient for MuxUart<'a> {
                     // If this finishes the read, signal to the caller,
                     // otherwise update state so next read will fill in
                     // more data.
-                    if remaining == 0 {
+                    if remaining == 0 || (line_error && state == UartDeviceReceiveState::Receiving)
+                    {
                         device.state.set(UartDeviceReceiveState::Idle);
                         device.received_buffer(rxbuf, position, rcode, error);
                         // Need to check if receive was called in callback
@@ -2,14 +2,17 @@
 
 use core::cell::Cell;
 use kernel::common::cells::OptionalCell;
-use kernel::common::leasable_buffer::LeasableBuffer;
+use kernel::common::leasable_buffer::{LeasableBuffer, ReadOnlyLeasableBuffer};
 use kernel::common::registers::{
     register_bitfields, register_structs, ReadOnly, ReadWrite, WriteOnly,
 };
 use kernel::common::StaticRef;
 use kernel::hil;
+use kernel::hil::crypto::CryptoError;
 use kernel::hil::digest;
-use kernel::ReturnCode;
+
+/// Size of the blocks of SHA-256, the longest key the HMAC can use as is.
+const SHA256_BLOCK_SIZE: usize = 64;
 
 register_structs! {
     pub HmacRegisters {
@@ -68,12 +71,15 @@ pub struct Hmac<'a> {
     registers: StaticRef<HmacRegisters>,
 
     client: OptionalCell<&'a dyn hil::digest::Client<'a, [u8; 32]>>,
+    verify_client: OptionalCell<&'a dyn hil::digest::ClientVerify<'a, [u8; 32]>>,
 
     data: Cell<Option<LeasableBuffer<'static, u8>>>,
+    readonly_data: Cell<Option<ReadOnlyLeasableBuffer<'static, u8>>>,
     data_len: Cell<usize>,
     data_index: Cell<usize>,
 
     digest: Cell<Option<&'static mut [u8; 32]>>,
+    compare: Cell<Option<&'static mut [u8; 32]>>,
 }
 
 impl Hmac<'_> {
@@ -81,36 +87,38 @@ impl Hmac<'_> {
         Hmac {
             registers: base,
             client: OptionalCell::empty(),
+            verify_client: OptionalCell::empty(),
             data: Cell::new(None),
+            readonly_data: Cell::new(None),
             data_len: Cell::new(0),
             data_index: Cell::new(0),
             digest: Cell::new(None),
+            compare: Cell::new(None),
         }
     }
 
-    fn data_progress(&self) {
+    /// Write the remaining data in `slice` to the message FIFO.
+    /// Returns `false` if the FIFO filled up before all of the data was
+    /// written, in which case the FIFO empty interrupt has been enabled.
+    fn fill_fifo(&self, slice: &[u8]) -> bool {
         let regs = self.registers;
         let idx = self.data_index.get();
         let len = self.data_len.get();
 
-        let slice = self.data.take().unwrap().take();
-
         if idx < len {
             let data_len = len - idx;
 
             for i in 0..(data_len / 4) {
                 if regs.status.is_set(STATUS::FIFO_FULL) {
-                    self.data.set(Some(LeasableBuffer::new(slice)));
                     // Enable interrupts
                     regs.intr_enable.modify(INTR_ENABLE::FIFO_EMPTY::SET);
-                    return;
+                    return false;
                 }
 
                 if !regs.status.is_set(STATUS::FIFO_EMPTY) {
-                    self.data.set(Some(LeasableBuffer::new(slice)));
                     // Enable interrupts
                     regs.intr_enable.modify(INTR_ENABLE::FIFO_EMPTY::SET);
-                    return;
+                    return false;
                 }
 
                 let data_idx = idx + i * 4;
@@ -135,9 +143,78 @@ impl Hmac<'_> {
             }
         }
 
-        self.client.map(move |client| {
-            client.add_data_done(Ok(()), slice);
-        });
+        true
+    }
+
+    /// Raise the FIFO empty interrupt through the test register, so that
+    /// `data_progress()` runs from `handle_interrupt()`.
+    fn start_fill(&self) {
+        let regs = self.registers;
+
+        regs.intr_enable.modify(INTR_ENABLE::FIFO_EMPTY::SET);
+        regs.intr_test.write(INTR_TEST::FIFO_EMPTY::SET);
+    }
+
+    /// Hash `data` with SHA-256, polling the engine. Used to shorten keys
+    /// longer than a block, before the engine computes the HMAC.
+    fn sha256_blocking(&self, data: &[u8]) -> [u8; 32] {
+        let regs = self.registers;
+
+        regs.cfg
+            .write(CFG::ENDIAN_SWAP::SET + CFG::SHA_EN::SET + CFG::DIGEST_SWAP::SET);
+        regs.cmd.modify(CMD::START::SET);
+
+        for chunk in data.chunks(4) {
+            while regs.status.is_set(STATUS::FIFO_FULL) {}
+            if chunk.len() == 4 {
+                regs.msg_fifo
+                    .set(u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+            } else {
+                for byte in chunk {
+                    while regs.status.is_set(STATUS::FIFO_FULL) {}
+                    regs.msg_fifo.set(*byte as u32);
+                }
+            }
+        }
+
+        regs.cmd.modify(CMD::PROCESS::SET);
+        while !regs.intr_state.is_set(INTR_STATE::HMAC_DONE) {}
+
+        let mut digest = [0; 32];
+        for (i, bytes) in digest.chunks_mut(4).enumerate() {
+            bytes.copy_from_slice(&regs.digest[i].get().to_ne_bytes());
+        }
+        regs.intr_state.modify(INTR_STATE::HMAC_DONE::SET);
+        digest
+    }
+
+    fn data_progress(&self) {
+        let regs = self.registers;
+
+        if let Some(data) = self.data.take() {
+            let slice = data.take();
+
+            if !self.fill_fifo(slice) {
+                self.data.set(Some(LeasableBuffer::new(slice)));
+                return;
+            }
+
+            self.client.map(move |client| {
+                client.add_data_done(Ok(()), slice);
+            });
+        } else if let Some(data) = self.readonly_data.take() {
+            let slice = data.take();
+
+            if !self.fill_fifo(slice) {
+                self.readonly_data
+                    .set(Some(ReadOnlyLeasableBuffer::new(slice)));
+                return;
+            }
+
+            self.client.map(move |client| {
+                client.add_readonly_data_done(Ok(()), slice);
+            });
+        }
 
         // Make sure we don't get any more FIFO empty interrupts
         regs.intr_enable.modify(INTR_ENABLE::FIFO_EMPTY::CLEAR);
@@ -154,6 +231,24 @@ impl Hmac<'_> {
         );
 
         if intrs.is_set(INTR_STATE::HMAC_DONE) {
+            if let Some(compare) = self.compare.take() {
+                // Look at every word, so the time taken doesn't depend on
+                // where the digests differ.
+                let diff = compare
+                    .chunks(4)
+                    .zip(regs.digest.iter())
+                    .fold(0, |acc, (c, d)| {
+                        acc | (u32::from_ne_bytes([c[0], c[1], c[2], c[3]]) ^ d.get())
+                    });
+
+                regs.intr_state.modify(INTR_STATE::HMAC_DONE::SET);
+
+                self.verify_client.map(move |client| {
+                    client.verification_done(Ok(diff == 0), compare);
+                });
+                return;
+            }
+
             self.client.map(|client| {
                 let digest = self.digest.take().unwrap();
 
@@ -180,8 +275,15 @@ impl Hmac<'_> {
         } else if intrs.is_set(INTR_STATE::HMAC_ERR) {
             regs.intr_state.modify(INTR_STATE::HMAC_ERR::SET);
 
+            if let Some(compare) = self.compare.take() {
+                self.verify_client.map(move |client| {
+                    client.verification_done(Err(CryptoError::HardwareFault), compare);
+                });
+                return;
+            }
+
             self.client.map(|client| {
-                client.hash_done(Err(ReturnCode::FAIL), self.digest.take().unwrap());
+                client.hash_done(Err(CryptoError::HardwareFault), self.digest.take().unwrap());
             });
         }
     }
@@ -195,12 +297,12 @@ impl<'a> hil::digest::Digest<'a, [u8; 32]> for Hmac<'a> {
     fn add_data(
         &self,
         data: LeasableBuffer<'static, u8>,
-    ) -> Result<usize, (ReturnCode, &'static mut [u8])> {
+    ) -> Result<usize, (CryptoError, &'static mut [u8])> {
         let regs = self.registers;
 
-        // Ensure the HMAC is setup
+        // Ensure the HMAC is setup, in the mode selected last
         regs.cfg
-            .write(CFG::ENDIAN_SWAP::SET + CFG::SHA_EN::SET + CFG::DIGEST_SWAP::SET);
+            .modify(CFG::ENDIAN_SWAP::SET + CFG::SHA_EN::SET + CFG::DIGEST_SWAP::SET);
 
         regs.cmd.modify(CMD::START::SET);
 
@@ -212,8 +314,36 @@ impl<'a> hil::digest::Digest<'a, [u8; 32]> for Hmac<'a> {
         self.data.set(Some(data));
         self.data_index.set(0);
 
-        // Call the process function, this will start an async fill method
-        self.data_progress();
+        // Start filling the FIFO from the interrupt handler, so the client
+        // is never called back from within this call
+        self.start_fill();
+
+        Ok(self.data_len.get())
+    }
+
+    fn add_readonly_data(
+        &self,
+        data: ReadOnlyLeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static [u8])> {
+        let regs = self.registers;
+
+        // Ensure the HMAC is setup, in the mode selected last
+        regs.cfg
+            .modify(CFG::ENDIAN_SWAP::SET + CFG::SHA_EN::SET + CFG::DIGEST_SWAP::SET);
+
+        regs.cmd.modify(CMD::START::SET);
+
+        // Clear the FIFO empty interrupt
+        regs.intr_state.modify(INTR_STATE::FIFO_EMPTY::SET);
+
+        // Set the length and data index of the data to write
+        self.data_len.set(data.len());
+        self.readonly_data.set(Some(data));
+        self.data_index.set(0);
+
+        // Start filling the FIFO from the interrupt handler, so the client
+        // is never called back from within this call
+        self.start_fill();
 
         Ok(self.data_len.get())
     }
@@ -221,18 +351,19 @@ impl<'a> hil::digest::Digest<'a, [u8; 32]> for Hmac<'a> {
     fn run(
         &'a self,
         digest: &'static mut [u8; 32],
-    ) -> Result<(), (ReturnCode, &'static mut [u8; 32])> {
+    ) -> Result<(), (CryptoError, &'static mut [u8; 32])> {
         let regs = self.registers;
 
         // Enable interrrupts
         regs.intr_enable
             .modify(INTR_ENABLE::HMAC_DONE::SET + INTR_ENABLE::HMAC_ERR::SET);
 
-        // Start the process
-        regs.cmd.modify(CMD::PROCESS::SET);
-
+        // The digest must be in place before the done interrupt can fire
         self.digest.set(Some(digest));
 
+        // Start the process, `hash_done` is called from the interrupt handler
+        regs.cmd.modify(CMD::PROCESS::SET);
+
         Ok(())
     }
 
@@ -241,16 +372,100 @@ impl<'a> hil::digest::Digest<'a, [u8; 32]> for Hmac<'a> {
 
         regs.cmd.modify(CMD::START::CLEAR);
         regs.wipe_secret.set(1 as u32);
+        // Back to plain SHA-256, the key is gone
+        regs.cfg.modify(CFG::HMAC_EN::CLEAR);
+    }
+
+    fn cancel(&self) -> digest::Cancelled<[u8; 32]> {
+        let regs = self.registers;
+
+        // No more callbacks
+        regs.intr_enable.modify(
+            INTR_ENABLE::HMAC_DONE::CLEAR
+                + INTR_ENABLE::FIFO_EMPTY::CLEAR
+                + INTR_ENABLE::HMAC_ERR::CLEAR,
+        );
+        regs.intr_state.modify(
+            INTR_STATE::HMAC_DONE::SET + INTR_STATE::FIFO_EMPTY::SET + INTR_STATE::HMAC_ERR::SET,
+        );
+
+        // Disabling the engine drops the message and the partial digest
+        regs.cfg.set(0);
+        self.clear_data();
+
+        digest::Cancelled {
+            data: self.data.take().map(|data| data.take()),
+            readonly_data: self.readonly_data.take().map(|data| data.take()),
+            digest: self.digest.take().or_else(|| self.compare.take()),
+            chain: None,
+        }
+    }
+}
+
+impl<'a> hil::digest::DigestVerify<'a, [u8; 32]> for Hmac<'a> {
+    fn set_verify_client(&'a self, client: &'a dyn digest::ClientVerify<'a, [u8; 32]>) {
+        self.verify_client.set(client);
+    }
+
+    fn verify(
+        &'a self,
+        compare: &'static mut [u8; 32],
+    ) -> Result<(), (CryptoError, &'static mut [u8; 32])> {
+        let regs = self.registers;
+
+        // Enable interrrupts
+        regs.intr_enable
+            .modify(INTR_ENABLE::HMAC_DONE::SET + INTR_ENABLE::HMAC_ERR::SET);
+
+        // The done interrupt compares the digest with this instead of
+        // copying it out
+        self.compare.set(Some(compare));
+
+        // Start the process, `verification_done` is called from the
+        // interrupt handler
+        regs.cmd.modify(CMD::PROCESS::SET);
+
+        Ok(())
+    }
+}
+
+/// The key registers are write-only, and the digest registers only hold the
+/// final value, so a hash cannot be suspended.
+impl hil::digest::DigestSaveRestore for Hmac<'_> {
+    fn save_context(&self, _context: &mut digest::DigestContext) -> Result<(), CryptoError> {
+        Err(CryptoError::NotSupported)
+    }
+
+    fn restore_context(&self, _context: &digest::DigestContext) -> Result<(), CryptoError> {
+        Err(CryptoError::NotSupported)
     }
 }
 
 impl hil::digest::HMACSha256 for Hmac<'_> {
-    fn set_mode_hmacsha256(&self, key: &[u8; 32]) -> Result<(), ReturnCode> {
+    fn set_mode_hmacsha256(&self, key: &[u8]) -> Result<(), CryptoError> {
         let regs = self.registers;
 
+        // The key registers hold 256 bits, enough for hashed keys and short
+        // keys, which are padded with zeros, but not for keys between 33
+        // bytes and a block.
+        let mut padded_key = [0; 32];
+        if key.len() > SHA256_BLOCK_SIZE {
+            // A digest may be in progress, the engine cannot hash the key.
+            if regs.intr_enable.is_set(INTR_ENABLE::HMAC_DONE) {
+                return Err(CryptoError::EngineBusy);
+            }
+            padded_key = self.sha256_blocking(key);
+        } else if key.len() > padded_key.len() {
+            return Err(CryptoError::BadKeyLength);
+        } else {
+            padded_key[..key.len()].copy_from_slice(key);
+        }
+        let key = &padded_key;
+
         // Ensure the HMAC is setup
-        regs.cfg
-            .write(CFG::ENDIAN_SWAP::SET + CFG::SHA_EN::SET + CFG::DIGEST_SWAP::SET);
+        regs.cfg.write(
+            CFG::ENDIAN_SWAP::SET + CFG::SHA_EN::SET + CFG::HMAC_EN::SET + CFG::DIGEST_SWAP::SET,
+        );
 
         for i in 0..8 {
             let idx = i * 4;
@@ -266,3 +481,14 @@ impl hil::digest::HMACSha256 for Hmac<'_> {
         Ok(())
     }
 }
+
+impl hil::digest::Sha256 for Hmac<'_> {
+    fn set_mode_sha256(&self) -> Result<(), CryptoError> {
+        let regs = self.registers;
+
+        regs.cfg
+            .write(CFG::ENDIAN_SWAP::SET + CFG::SHA_EN::SET + CFG::DIGEST_SWAP::SET);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+//! Accelerated address resolver, nRF52-family
+//!
+//! The AAR peripheral resolves Bluetooth Low Energy resolvable private
+//! addresses: it checks which of up to 16 identity resolving keys (IRKs)
+//! generated an address. This is much faster than computing the address hash
+//! for each key with the ECB peripheral.
+//!
+//! IRKs are given most significant byte first, addresses in the order they
+//! are sent over the air, i.e. least significant byte first.
+//!
+//! The AAR and CCM peripherals share their registers, so only one of them can
+//! be used at a time. Starting an operation while the other is busy returns
+//! `EBUSY`.
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::ReturnCode;
+
+/// Maximum number of IRKs checked by one resolution.
+pub const MAX_IRKS: usize = 16;
+
+pub const IRK_LEN: usize = 16;
+
+pub const ADDRESS_LEN: usize = 6;
+
+kernel::dma_buffer!(
+    static mut IRKS: [u8; MAX_IRKS * IRK_LEN] = [0; MAX_IRKS * IRK_LEN];
+);
+// The AAR reads the address at offset 3, after the header, length and RFU
+// fields of a packet.
+kernel::dma_buffer!(
+    static mut PACKET: [u8; 3 + ADDRESS_LEN] = [0; 3 + ADDRESS_LEN];
+);
+kernel::dma_buffer!(
+    static mut SCRATCH: [u8; 3] = [0; 3];
+);
+
+const AAR_BASE: StaticRef<AarRegisters> =
+    unsafe { StaticRef::new(0x4000F000 as *const AarRegisters) };
+
+#[repr(C)]
+struct AarRegisters {
+    /// Start resolving addresses based on IRKs specified in the IRK data
+    /// structure
+    /// - Address: 0x000 - 0x004
+    task_start: WriteOnly<u32, Task::Register>,
+    _reserved1: u32,
+    /// Stop resolving addresses
+    /// - Address: 0x008 - 0x00c
+    task_stop: WriteOnly<u32, Task::Register>,
+    _reserved2: [u32; 61],
+    /// Address resolution procedure complete
+    /// - Address: 0x100 - 0x104
+    event_end: ReadWrite<u32, Event::Register>,
+    /// Address resolved
+    /// - Address: 0x104 - 0x108
+    event_resolved: ReadWrite<u32, Event::Register>,
+    /// Address not resolved
+    /// - Address: 0x108 - 0x10c
+    event_notresolved: ReadWrite<u32, Event::Register>,
+    _reserved3: [u32; 126],
+    /// Enable interrupt
+    /// - Address: 0x304 - 0x308
+    intenset: ReadWrite<u32, Interrupt::Register>,
+    /// Disable interrupt
+    /// - Address: 0x308 - 0x30c
+    intenclr: ReadWrite<u32, Interrupt::Register>,
+    _reserved4: [u32; 61],
+    /// Resolution status
+    /// - Address: 0x400 - 0x404
+    status: ReadOnly<u32, Status::Register>,
+    _reserved5: [u32; 63],
+    /// Enable AAR
+    /// - Address: 0x500 - 0x504
+    enable: ReadWrite<u32, Enable::Register>,
+    /// Number of IRKs
+    /// - Address: 0x504 - 0x508
+    nirk: ReadWrite<u32, Nirk::Register>,
+    /// Pointer to IRK data structure
+    /// - Address: 0x508 - 0x50c
+    irkptr: ReadWrite<u32>,
+    _reserved6: u32,
+    /// Pointer to the resolvable address
+    /// - Address: 0x510 - 0x514
+    addrptr: ReadWrite<u32>,
+    /// Pointer to data area used for temporary storage
+    /// - Address: 0x514 - 0x518
+    scratchptr: ReadWrite<u32>,
+}
+
+register_bitfields! [u32,
+    Task [
+        ENABLE OFFSET(0) NUMBITS(1)
+    ],
+
+    Event [
+        READY OFFSET(0) NUMBITS(1)
+    ],
+
+    Interrupt [
+        END OFFSET(0) NUMBITS(1),
+        RESOLVED OFFSET(1) NUMBITS(1),
+        NOTRESOLVED OFFSET(2) NUMBITS(1)
+    ],
+
+    Status [
+        /// The IRK that was used last time an address was resolved
+        STATUS OFFSET(0) NUMBITS(4)
+    ],
+
+    /// Shared with the CCM peripheral
+    Enable [
+        ENABLE OFFSET(0) NUMBITS(2) [
+            Disabled = 0,
+            Enabled = 3
+        ]
+    ],
+
+    Nirk [
+        NIRK OFFSET(0) NUMBITS(5)
+    ]
+];
+
+pub trait Client {
+    /// Called when a resolution is done, with the index of the IRK that
+    /// generated the address, or `None` if none did.
+    fn resolve_done(&self, index: Option<usize>);
+}
+
+pub struct Aar<'a> {
+    registers: StaticRef<AarRegisters>,
+    client: OptionalCell<&'a dyn Client>,
+    busy: Cell<bool>,
+}
+
+pub static mut AAR: Aar<'static> = Aar::new();
+
+impl<'a> Aar<'a> {
+    const fn new() -> Aar<'a> {
+        Aar {
+            registers: AAR_BASE,
+            client: OptionalCell::empty(),
+            busy: Cell::new(false),
+        }
+    }
+
+    pub fn set_client(&self, client: &'a dyn Client) {
+        self.client.set(client);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.registers.enable.matches_all(Enable::ENABLE::Enabled)
+    }
+
+    /// Find which of `irks` generated `address`. The client is called with
+    /// the result.
+    pub fn resolve(&self, address: &[u8; ADDRESS_LEN], irks: &[[u8; IRK_LEN]]) -> ReturnCode {
+        let regs = &*self.registers;
+        if self.busy.get() || regs.enable.get() != 0 {
+            return ReturnCode::EBUSY;
+        }
+        if irks.is_empty() || irks.len() > MAX_IRKS {
+            return ReturnCode::EINVAL;
+        }
+        unsafe {
+            for (slot, irk) in IRKS.chunks_mut(IRK_LEN).zip(irks.iter()) {
+                slot.copy_from_slice(irk);
+            }
+            PACKET[3..].copy_from_slice(address);
+        }
+
+        self.busy.set(true);
+        regs.enable.write(Enable::ENABLE::Enabled);
+        regs.nirk.write(Nirk::NIRK.val(irks.len() as u32));
+        unsafe {
+            regs.irkptr.set(IRKS.as_ptr() as u32);
+            regs.addrptr.set(PACKET.as_ptr() as u32);
+            regs.scratchptr.set(SCRATCH.as_ptr() as u32);
+        }
+        regs.event_end.write(Event::READY::CLEAR);
+        regs.event_resolved.write(Event::READY::CLEAR);
+        regs.event_notresolved.write(Event::READY::CLEAR);
+        regs.intenset.write(Interrupt::END::SET);
+        regs.task_start.write(Task::ENABLE::SET);
+        ReturnCode::SUCCESS
+    }
+
+    pub fn handle_interrupt(&self) {
+        let regs = &*self.registers;
+        if regs.event_end.get() == 0 {
+            return;
+        }
+        let index = if regs.event_resolved.get() == 1 {
+            Some(regs.status.read(Status::STATUS) as usize)
+        } else {
+            None
+        };
+        regs.intenclr
+            .write(Interrupt::END::SET + Interrupt::RESOLVED::SET + Interrupt::NOTRESOLVED::SET);
+        regs.enable.write(Enable::ENABLE::Disabled);
+        self.busy.set(false);
+        self.client.map(|client| client.resolve_done(index));
+    }
+}
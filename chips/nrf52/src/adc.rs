@@ -1,5 +1,13 @@
 //! ADC driver for the nRF52. Uses the SAADC peripheral.
+//!
+//! Besides single samples, it can watch a channel with `AdcLimits`: the
+//! SAADC samples it with its internal timer, at about 7.8 kHz, and only
+//! interrupts when a sample crosses a limit of channel 0. A PPI channel
+//! restarts the SAADC each time it has filled its one-sample buffer, so the
+//! CPU is not woken up for each sample.
 
+use crate::ppi;
+use core::cell::Cell;
 use kernel::common::cells::{OptionalCell, VolatileCell};
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
@@ -236,17 +244,37 @@ pub enum AdcChannel {
     VDDHDIV5 = 0xD,
 }
 
+/// Sample rate of the internal timer: 16 MHz / 2047, the lowest it can do.
+const MONITOR_CC: u32 = 2047;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Monitor {
+    Idle,
+    /// Waiting for the SAADC to start, to start the timer.
+    Starting,
+    Monitoring,
+    /// Waiting for the SAADC to stop, to report the crossed limit if any.
+    Stopping(Option<bool>),
+}
+
 const SAADC_BASE: StaticRef<AdcRegisters> =
     unsafe { StaticRef::new(0x40007000 as *const AdcRegisters) };
 
 pub static mut ADC: Adc = Adc::new(SAADC_BASE);
 
 // Buffer to save completed sample to.
-static mut SAMPLE: [u16; 1] = [0; 1];
+kernel::dma_buffer!(
+    static mut SAMPLE: [u16; 1] = [0; 1];
+);
 
 pub struct Adc {
     registers: StaticRef<AdcRegisters>,
     client: OptionalCell<&'static dyn hil::adc::Client>,
+    limit_client: OptionalCell<&'static dyn hil::adc::LimitClient>,
+    monitor: Cell<Monitor>,
+    /// The PPI channel connecting `EVENTS_END` to `TASKS_START` while
+    /// monitoring.
+    ppi_channel: OptionalCell<usize>,
 }
 
 impl Adc {
@@ -255,6 +283,9 @@ impl Adc {
             registers: registers,
             // state: Cell::new(State::Idle),
             client: OptionalCell::empty(),
+            limit_client: OptionalCell::empty(),
+            monitor: Cell::new(Monitor::Idle),
+            ppi_channel: OptionalCell::empty(),
         }
     }
 
@@ -262,9 +293,98 @@ impl Adc {
         self.client.set(client);
     }
 
+    pub fn set_limit_client(&self, client: &'static dyn hil::adc::LimitClient) {
+        self.limit_client.set(client);
+    }
+
+    /// Configure channel 0 to sample `channel` into `SAMPLE`.
+    fn configure(&self, channel: &AdcChannel) {
+        let regs = &*self.registers;
+
+        // Positive goes to the channel passed in, negative not connected.
+        regs.ch[0].pselp.write(PSEL::PSEL.val(*channel as u32));
+        regs.ch[0].pseln.write(PSEL::PSEL::NotConnected);
+
+        // Configure the ADC for a single read.
+        regs.ch[0]
+            .config
+            .write(CONFIG::GAIN::Gain1_4 + CONFIG::REFSEL::VDD1_4 + CONFIG::TACQ::us10);
+
+        // Set max resolution.
+        regs.resolution.write(RESOLUTION::VAL::bit14);
+
+        // Do one measurement.
+        regs.result_maxcnt.write(RESULT_MAXCNT::MAXCNT.val(1));
+        // Where to put the reading.
+        unsafe {
+            regs.result_ptr.set(SAMPLE.as_ptr());
+        }
+    }
+
+    /// Stop monitoring, and report `limit` once the SAADC has stopped.
+    fn stop_monitoring(&self, limit: Option<bool>) {
+        let regs = &*self.registers;
+        self.ppi_channel
+            .take()
+            .map(|channel| unsafe { ppi::PPI.release(channel) });
+        regs.intenclr
+            .write(INTEN::STARTED::SET + INTEN::CH0LIMITH::SET + INTEN::CH0LIMITL::SET);
+        self.monitor.set(Monitor::Stopping(limit));
+        regs.events_stopped.write(EVENT::EVENT::CLEAR);
+        regs.intenset.write(INTEN::STOPPED::SET);
+        regs.tasks_stop.write(TASK::TASK::SET);
+    }
+
+    fn handle_monitor_interrupt(&self) {
+        let regs = &*self.registers;
+
+        match self.monitor.get() {
+            Monitor::Idle => {}
+            Monitor::Starting => {
+                if regs.events_started.is_set(EVENT::EVENT) {
+                    regs.events_started.write(EVENT::EVENT::CLEAR);
+                    // The PPI restarts the SAADC after each sample, which
+                    // needs no handling.
+                    regs.intenclr.write(INTEN::STARTED::SET);
+                    self.monitor.set(Monitor::Monitoring);
+                    // Start the internal timer.
+                    regs.tasks_sample.write(TASK::TASK::SET);
+                }
+            }
+            Monitor::Monitoring => {
+                let above = regs.events_ch[0].limith.is_set(EVENT::EVENT);
+                let below = regs.events_ch[0].limitl.is_set(EVENT::EVENT);
+                if above || below {
+                    regs.events_ch[0].limith.write(EVENT::EVENT::CLEAR);
+                    regs.events_ch[0].limitl.write(EVENT::EVENT::CLEAR);
+                    self.stop_monitoring(Some(above));
+                }
+            }
+            Monitor::Stopping(limit) => {
+                if regs.events_stopped.is_set(EVENT::EVENT) {
+                    regs.events_stopped.write(EVENT::EVENT::CLEAR);
+                    regs.events_started.write(EVENT::EVENT::CLEAR);
+                    regs.events_end.write(EVENT::EVENT::CLEAR);
+                    regs.intenclr.write(INTEN::STOPPED::SET);
+                    regs.samplerate.write(SAMPLERATE::MODE::Task);
+                    regs.enable.write(ENABLE::ENABLE::CLEAR);
+                    self.monitor.set(Monitor::Idle);
+                    limit.map(|above| {
+                        self.limit_client.map(|client| client.limit_reached(above));
+                    });
+                }
+            }
+        }
+    }
+
     pub fn handle_interrupt(&self) {
         let regs = &*self.registers;
 
+        if self.monitor.get() != Monitor::Idle {
+            self.handle_monitor_interrupt();
+            return;
+        }
+
         // Determine what event occurred.
         if regs.events_started.is_set(EVENT::EVENT) {
             regs.events_started.write(EVENT::EVENT::CLEAR);
@@ -294,26 +414,12 @@ impl hil::adc::Adc for Adc {
 
     fn sample(&self, channel: &Self::Channel) -> ReturnCode {
         let regs = &*self.registers;
-
-        // Positive goes to the channel passed in, negative not connected.
-        regs.ch[0].pselp.write(PSEL::PSEL.val(*channel as u32));
-        regs.ch[0].pseln.write(PSEL::PSEL::NotConnected);
-
-        // Configure the ADC for a single read.
-        regs.ch[0]
-            .config
-            .write(CONFIG::GAIN::Gain1_4 + CONFIG::REFSEL::VDD1_4 + CONFIG::TACQ::us10);
-
-        // Set max resolution.
-        regs.resolution.write(RESOLUTION::VAL::bit14);
-
-        // Do one measurement.
-        regs.result_maxcnt.write(RESULT_MAXCNT::MAXCNT.val(1));
-        // Where to put the reading.
-        unsafe {
-            regs.result_ptr.set(SAMPLE.as_ptr());
+        if self.monitor.get() != Monitor::Idle {
+            return ReturnCode::EBUSY;
         }
 
+        self.configure(channel);
+
         // No automatic sampling, will trigger manually.
         regs.samplerate.write(SAMPLERATE::MODE::Task);
 
@@ -335,7 +441,13 @@ impl hil::adc::Adc for Adc {
     }
 
     fn stop_sampling(&self) -> ReturnCode {
-        ReturnCode::FAIL
+        match self.monitor.get() {
+            Monitor::Starting | Monitor::Monitoring => {
+                self.stop_monitoring(None);
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::FAIL,
+        }
     }
 
     fn get_resolution_bits(&self) -> usize {
@@ -346,3 +458,44 @@ impl hil::adc::Adc for Adc {
         Some(3300)
     }
 }
+
+impl hil::adc::AdcLimits for Adc {
+    fn monitor(&self, channel: &Self::Channel, low: u16, high: u16) -> ReturnCode {
+        let regs = &*self.registers;
+        if self.monitor.get() != Monitor::Idle || regs.enable.is_set(ENABLE::ENABLE) {
+            return ReturnCode::EBUSY;
+        }
+        if low > high {
+            return ReturnCode::EINVAL;
+        }
+        let ppi_channel = unsafe {
+            ppi::PPI.connect(
+                "saadc",
+                &regs.events_end as *const _ as usize,
+                &regs.tasks_start as *const _ as usize,
+            )
+        };
+        match ppi_channel {
+            Some(ppi_channel) => self.ppi_channel.set(ppi_channel),
+            None => return ReturnCode::ENOMEM,
+        }
+
+        self.configure(channel);
+        // The limits are compared with the 14-bit result.
+        regs.ch[0]
+            .limit
+            .write(LIMIT::LOW.val(low as u32 >> 2) + LIMIT::HIGH.val(high as u32 >> 2));
+        regs.samplerate
+            .write(SAMPLERATE::MODE::Timers + SAMPLERATE::CC.val(MONITOR_CC));
+        regs.events_started.write(EVENT::EVENT::CLEAR);
+        regs.events_ch[0].limith.write(EVENT::EVENT::CLEAR);
+        regs.events_ch[0].limitl.write(EVENT::EVENT::CLEAR);
+        self.monitor.set(Monitor::Starting);
+        regs.enable.write(ENABLE::ENABLE::SET);
+        regs.inten
+            .write(INTEN::STARTED::SET + INTEN::CH0LIMITH::SET + INTEN::CH0LIMITL::SET);
+        regs.tasks_start.write(TASK::TASK::SET);
+
+        ReturnCode::SUCCESS
+    }
+}
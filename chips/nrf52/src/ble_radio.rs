@@ -38,6 +38,7 @@ use core::convert::TryFrom;
 use kernel::common::cells::OptionalCell;
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
+use kernel::energy;
 use kernel::hil::ble_advertising;
 use kernel::hil::ble_advertising::RadioChannel;
 use kernel::ReturnCode;
@@ -525,14 +526,17 @@ register_bitfields! [u32,
     ]
 ];
 
-static mut PAYLOAD: [u8; nrf5x::constants::RADIO_PAYLOAD_LENGTH] =
-    [0x00; nrf5x::constants::RADIO_PAYLOAD_LENGTH];
+kernel::dma_buffer!(
+    static mut PAYLOAD: [u8; nrf5x::constants::RADIO_PAYLOAD_LENGTH] =
+        [0x00; nrf5x::constants::RADIO_PAYLOAD_LENGTH];
+);
 
 pub struct Radio {
     registers: StaticRef<RadioRegisters>,
     tx_power: Cell<TxPower>,
     rx_client: OptionalCell<&'static dyn ble_advertising::RxClient>,
     tx_client: OptionalCell<&'static dyn ble_advertising::TxClient>,
+    activity: OptionalCell<&'static dyn energy::Activity>,
 }
 
 pub static mut RADIO: Radio = Radio::new();
@@ -544,9 +548,16 @@ impl Radio {
             tx_power: Cell::new(TxPower::ZerodBm),
             rx_client: OptionalCell::empty(),
             tx_client: OptionalCell::empty(),
+            activity: OptionalCell::empty(),
         }
     }
 
+    /// Report when the radio is powered is active to `activity`, for energy
+    /// estimates.
+    pub fn set_activity(&self, activity: &'static dyn energy::Activity) {
+        self.activity.set(activity);
+    }
+
     pub fn is_enabled(&self) -> bool {
         self.registers.mode.matches_all(Mode::MODE::BLE_1MBIT)
     }
@@ -578,11 +589,15 @@ impl Radio {
         // reset and enable power
         regs.power.write(Task::ENABLE::CLEAR);
         regs.power.write(Task::ENABLE::SET);
+        self.activity
+            .map(|activity| activity.set_active(energy::RADIO, true));
     }
 
     fn radio_off(&self) {
         let regs = &*self.registers;
         regs.power.write(Task::ENABLE::CLEAR);
+        self.activity
+            .map(|activity| activity.set_active(energy::RADIO, false));
     }
 
     fn set_tx_power(&self) {
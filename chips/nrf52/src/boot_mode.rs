@@ -0,0 +1,103 @@
+//! Boot mode kept across resets in the POWER retention registers.
+//!
+//! GPREGRET is read by the bootloader: the Nordic SDK bootloaders, e.g. the
+//! open bootloader of the nRF52840 dongle, stay in DFU mode if it holds
+//! `BOOTLOADER_DFU_START`. The other modes are for the kernel and are kept in
+//! GPREGRET2, which is read by `init()`.
+//!
+//! GPREGRET2 also counts consecutive crashes: resets by the watchdog or a CPU
+//! lockup, and resets after a kernel panic, which the panic handler marks with
+//! `record_panic()`. Any other reset clears the count. After
+//! `CRASH_LOOP_THRESHOLD` consecutive crashes the chip boots in safe mode, so
+//! that a crash-looping process can be removed or updated.
+
+use crate::power::POWER;
+use core::cell::Cell;
+use core::cmp;
+use kernel::capabilities::BootModeCapability;
+use kernel::hil::boot_mode::{BootMode, BootModeControl};
+
+/// Value of GPREGRET that makes the bootloader stay in DFU mode.
+pub const BOOTLOADER_DFU_START: u8 = 0xB1;
+
+/// Number of consecutive crashes after which the chip boots in safe mode.
+pub const CRASH_LOOP_THRESHOLD: u8 = 3;
+
+// Layout of GPREGRET2.
+const MODE_MASK: u8 = 0x03;
+const CRASH_COUNT_SHIFT: u8 = 2;
+const CRASH_COUNT_MAX: u8 = 0x1f;
+const PANICKED: u8 = 0x80;
+
+pub struct NrfBootMode {
+    mode: Cell<BootMode>,
+    crash_count: Cell<u8>,
+}
+
+pub static mut BOOT_MODE: NrfBootMode = NrfBootMode::new();
+
+impl NrfBootMode {
+    const fn new() -> NrfBootMode {
+        NrfBootMode {
+            mode: Cell::new(BootMode::Normal),
+            crash_count: Cell::new(0),
+        }
+    }
+
+    /// Read the mode requested before the reset and clear the request, and
+    /// update the crash count.
+    pub fn latch(&self) {
+        let (value, fault) = unsafe { (POWER.get_gpregret2(), POWER.take_reset_by_fault()) };
+        let crash_count = if fault || value & PANICKED != 0 {
+            cmp::min(
+                (value >> CRASH_COUNT_SHIFT) & CRASH_COUNT_MAX,
+                CRASH_COUNT_MAX - 1,
+            ) + 1
+        } else {
+            0
+        };
+        unsafe {
+            POWER.set_gpregret2(crash_count << CRASH_COUNT_SHIFT);
+        }
+        self.crash_count.set(crash_count);
+
+        let mode = if crash_count >= CRASH_LOOP_THRESHOLD {
+            BootMode::SafeMode
+        } else {
+            BootMode::from_usize((value & MODE_MASK) as usize).unwrap_or(BootMode::Normal)
+        };
+        self.mode.set(mode);
+    }
+
+    /// Number of consecutive crashes before this boot.
+    pub fn crash_count(&self) -> u8 {
+        self.crash_count.get()
+    }
+
+    /// Count the next reset as a crash. Called by the panic handler.
+    pub fn record_panic(&self) {
+        unsafe {
+            POWER.set_gpregret2(POWER.get_gpregret2() | PANICKED);
+        }
+    }
+}
+
+impl BootModeControl for NrfBootMode {
+    fn boot_mode(&self) -> BootMode {
+        self.mode.get()
+    }
+
+    fn set_next_boot_mode(&self, mode: BootMode, _capability: &dyn BootModeCapability) {
+        unsafe {
+            if mode == BootMode::Dfu {
+                POWER.set_gpregret(BOOTLOADER_DFU_START);
+            } else {
+                // Cancel a pending request for the bootloader
+                if POWER.get_gpregret() == BOOTLOADER_DFU_START {
+                    POWER.set_gpregret(0);
+                }
+                POWER.set_gpregret2((POWER.get_gpregret2() & !MODE_MASK) | mode as u8);
+            }
+        }
+    }
+}
@@ -0,0 +1,465 @@
+//! AES-CCM peripheral, nRF52-family
+//!
+//! The CCM peripheral encrypts and authenticates packets with AES-CCM as done
+//! for Bluetooth Low Energy links. It implements `AES128CCM` for messages of
+//! that form:
+//!
+//! * the associated data is a single byte, whose bits 2 to 4 are zero (the
+//!   peripheral masks them out, as they are the header bits of a Bluetooth
+//!   packet that are not authenticated),
+//! * the message is 1 to 251 bytes long,
+//! * the MIC is 4 bytes long and the message is encrypted.
+//!
+//! Other messages are rejected with `NotSupported`; they can be handled by
+//! `capsules::aes_ccm` on top of the ECB peripheral instead.
+//!
+//! It also implements `AEAD` for the same messages, with the header byte as
+//! the associated data and a 4 byte tag.
+//!
+//! The 13 byte nonce is split as in Bluetooth: the first 39 bits are the
+//! packet counter, the next bit the direction, and the last 8 bytes the IV.
+//!
+//! The CCM and AAR peripherals share their registers, so only one of them can
+//! be used at a time. Starting a CCM operation while the AAR is busy returns
+//! `EngineBusy`, and the AAR returns `EBUSY` while the CCM is busy.
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::symmetric_encryption::{self, AES128_KEY_SIZE, CCM_NONCE_LENGTH};
+
+/// Longest message, with the extended packet length.
+pub const MAX_MESSAGE_LEN: usize = 251;
+
+/// Length of the MIC appended by the peripheral.
+pub const MIC_LEN: usize = 4;
+
+/// Length of the header, length and RFU fields of a packet.
+const PACKET_HEADER_LEN: usize = 3;
+
+const PACKET_LEN: usize = PACKET_HEADER_LEN + MAX_MESSAGE_LEN + MIC_LEN;
+
+/// Header bits that are not authenticated.
+const HEADER_MASK: u8 = 0x1c;
+
+// Key, packet counter, direction and IV.
+kernel::dma_buffer!(
+    static mut CNF: [u8; 33] = [0; 33];
+);
+kernel::dma_buffer!(
+    static mut INPUT: [u8; PACKET_LEN] = [0; PACKET_LEN];
+);
+kernel::dma_buffer!(
+    static mut OUTPUT: [u8; PACKET_LEN] = [0; PACKET_LEN];
+);
+// The keystream, at least 16 bytes more than the longest message.
+kernel::dma_buffer!(
+    static mut SCRATCH: [u8; 16 + MAX_MESSAGE_LEN] = [0; 16 + MAX_MESSAGE_LEN];
+);
+
+const CNF_KEY: usize = 0;
+const CNF_PKTCTR: usize = 16;
+const CNF_DIRECTION: usize = 24;
+const CNF_IV: usize = 25;
+
+const CCM_BASE: StaticRef<CcmRegisters> =
+    unsafe { StaticRef::new(0x4000F000 as *const CcmRegisters) };
+
+#[repr(C)]
+struct CcmRegisters {
+    /// Start generation of key-stream
+    /// - Address: 0x000 - 0x004
+    task_ksgen: WriteOnly<u32, Task::Register>,
+    /// Start encryption/decryption
+    /// - Address: 0x004 - 0x008
+    task_crypt: WriteOnly<u32, Task::Register>,
+    /// Stop encryption/decryption
+    /// - Address: 0x008 - 0x00c
+    task_stop: WriteOnly<u32, Task::Register>,
+    _reserved1: [u32; 61],
+    /// Key-stream generation complete
+    /// - Address: 0x100 - 0x104
+    event_endksgen: ReadWrite<u32, Event::Register>,
+    /// Encrypt/decrypt complete
+    /// - Address: 0x104 - 0x108
+    event_endcrypt: ReadWrite<u32, Event::Register>,
+    /// Deprecated, CCM error
+    /// - Address: 0x108 - 0x10c
+    event_error: ReadWrite<u32, Event::Register>,
+    _reserved2: [u32; 61],
+    /// Shortcut register
+    /// - Address: 0x200 - 0x204
+    shorts: ReadWrite<u32, Shorts::Register>,
+    _reserved3: [u32; 64],
+    /// Enable interrupt
+    /// - Address: 0x304 - 0x308
+    intenset: ReadWrite<u32, Interrupt::Register>,
+    /// Disable interrupt
+    /// - Address: 0x308 - 0x30c
+    intenclr: ReadWrite<u32, Interrupt::Register>,
+    _reserved4: [u32; 61],
+    /// MIC check result
+    /// - Address: 0x400 - 0x404
+    micstatus: ReadOnly<u32, MicStatus::Register>,
+    _reserved5: [u32; 63],
+    /// Enable
+    /// - Address: 0x500 - 0x504
+    enable: ReadWrite<u32, Enable::Register>,
+    /// Operation mode
+    /// - Address: 0x504 - 0x508
+    mode: ReadWrite<u32, Mode::Register>,
+    /// Pointer to data structure holding AES key and NONCE vector
+    /// - Address: 0x508 - 0x50c
+    cnfptr: ReadWrite<u32>,
+    /// Input pointer
+    /// - Address: 0x50c - 0x510
+    inptr: ReadWrite<u32>,
+    /// Output pointer
+    /// - Address: 0x510 - 0x514
+    outptr: ReadWrite<u32>,
+    /// Pointer to data area used for temporary storage
+    /// - Address: 0x514 - 0x518
+    scratchptr: ReadWrite<u32>,
+}
+
+register_bitfields! [u32,
+    Task [
+        ENABLE OFFSET(0) NUMBITS(1)
+    ],
+
+    Event [
+        READY OFFSET(0) NUMBITS(1)
+    ],
+
+    Shorts [
+        /// Shortcut between ENDKSGEN event and CRYPT task
+        ENDKSGEN_CRYPT OFFSET(0) NUMBITS(1)
+    ],
+
+    Interrupt [
+        ENDKSGEN OFFSET(0) NUMBITS(1),
+        ENDCRYPT OFFSET(1) NUMBITS(1),
+        ERROR OFFSET(2) NUMBITS(1)
+    ],
+
+    MicStatus [
+        MICSTATUS OFFSET(0) NUMBITS(1) [
+            CheckFailed = 0,
+            CheckPassed = 1
+        ]
+    ],
+
+    /// Shared with the AAR peripheral
+    Enable [
+        ENABLE OFFSET(0) NUMBITS(2) [
+            Disabled = 0,
+            Enabled = 2
+        ]
+    ],
+
+    Mode [
+        MODE OFFSET(0) NUMBITS(1) [
+            Encryption = 0,
+            Decryption = 1
+        ],
+        LENGTH OFFSET(24) NUMBITS(1) [
+            Default = 0,
+            Extended = 1
+        ]
+    ]
+];
+
+pub struct Ccm<'a> {
+    registers: StaticRef<CcmRegisters>,
+    client: OptionalCell<&'a dyn symmetric_encryption::CCMClient>,
+    aead_client: OptionalCell<&'a dyn symmetric_encryption::AEADClient>,
+    key: Cell<[u8; AES128_KEY_SIZE]>,
+    nonce: Cell<[u8; CCM_NONCE_LENGTH]>,
+    /// Header byte set with `AEAD::set_aad()`.
+    aad: Cell<u8>,
+    buf: TakeCell<'static, [u8]>,
+    m_off: Cell<usize>,
+    m_len: Cell<usize>,
+    encrypting: Cell<bool>,
+    /// Whether the operation was started through `AEAD` rather than
+    /// `AES128CCM`.
+    aead: Cell<bool>,
+}
+
+pub static mut CCM: Ccm<'static> = Ccm::new();
+
+impl<'a> Ccm<'a> {
+    const fn new() -> Ccm<'a> {
+        Ccm {
+            registers: CCM_BASE,
+            client: OptionalCell::empty(),
+            aead_client: OptionalCell::empty(),
+            key: Cell::new([0; AES128_KEY_SIZE]),
+            nonce: Cell::new([0; CCM_NONCE_LENGTH]),
+            aad: Cell::new(0),
+            buf: TakeCell::empty(),
+            m_off: Cell::new(0),
+            m_len: Cell::new(0),
+            encrypting: Cell::new(false),
+            aead: Cell::new(false),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.registers.enable.matches_all(Enable::ENABLE::Enabled)
+    }
+
+    fn set_key(&self, key: &[u8]) -> Result<(), CryptoError> {
+        if key.len() != AES128_KEY_SIZE {
+            return Err(CryptoError::BadKeyLength);
+        }
+        let mut new_key = [0; AES128_KEY_SIZE];
+        new_key.copy_from_slice(key);
+        self.key.set(new_key);
+        Ok(())
+    }
+
+    fn set_nonce(&self, nonce: &[u8]) -> Result<(), CryptoError> {
+        if nonce.len() != CCM_NONCE_LENGTH {
+            return Err(CryptoError::InvalidArgument);
+        }
+        let mut new_nonce = [0; CCM_NONCE_LENGTH];
+        new_nonce.copy_from_slice(nonce);
+        self.nonce.set(new_nonce);
+        Ok(())
+    }
+
+    /// Encrypt or decrypt the message of `m_len` bytes at `m_off` in `buf`,
+    /// followed by its MIC when decrypting.
+    fn crypt(
+        &self,
+        buf: &'static mut [u8],
+        header: u8,
+        m_off: usize,
+        m_len: usize,
+        encrypting: bool,
+        aead: bool,
+    ) -> Result<(), (CryptoError, &'static mut [u8])> {
+        if self.buf.is_some() || self.registers.enable.get() != 0 {
+            return Err((CryptoError::EngineBusy, buf));
+        }
+        match m_off
+            .checked_add(m_len)
+            .and_then(|end| end.checked_add(MIC_LEN))
+        {
+            Some(end) if end <= buf.len() => {}
+            _ => return Err((CryptoError::InvalidArgument, buf)),
+        }
+        if header & HEADER_MASK != 0 || m_len == 0 || m_len > MAX_MESSAGE_LEN {
+            return Err((CryptoError::NotSupported, buf));
+        }
+
+        let nonce = self.nonce.get();
+        let in_len = if encrypting { m_len } else { m_len + MIC_LEN };
+        unsafe {
+            CNF[CNF_KEY..CNF_PKTCTR].copy_from_slice(&self.key.get());
+            CNF[CNF_PKTCTR..CNF_DIRECTION].copy_from_slice(&[0; 8]);
+            CNF[CNF_PKTCTR..CNF_PKTCTR + 5].copy_from_slice(&nonce[0..5]);
+            CNF[CNF_PKTCTR + 4] &= 0x7f;
+            CNF[CNF_DIRECTION] = nonce[4] >> 7;
+            CNF[CNF_IV..].copy_from_slice(&nonce[5..]);
+
+            INPUT[0] = header;
+            INPUT[1] = in_len as u8;
+            INPUT[2] = 0;
+            INPUT[PACKET_HEADER_LEN..PACKET_HEADER_LEN + in_len]
+                .copy_from_slice(&buf[m_off..m_off + in_len]);
+        }
+
+        self.m_off.set(m_off);
+        self.m_len.set(m_len);
+        self.encrypting.set(encrypting);
+        self.aead.set(aead);
+        self.buf.replace(buf);
+        self.start(encrypting);
+        Ok(())
+    }
+
+    fn start(&self, encrypting: bool) {
+        let regs = &*self.registers;
+        regs.enable.write(Enable::ENABLE::Enabled);
+        regs.mode.write(
+            Mode::LENGTH::Extended
+                + if encrypting {
+                    Mode::MODE::Encryption
+                } else {
+                    Mode::MODE::Decryption
+                },
+        );
+        unsafe {
+            regs.cnfptr.set(CNF.as_ptr() as u32);
+            regs.inptr.set(INPUT.as_ptr() as u32);
+            regs.outptr.set(OUTPUT.as_ptr() as u32);
+            regs.scratchptr.set(SCRATCH.as_ptr() as u32);
+        }
+        regs.event_endksgen.write(Event::READY::CLEAR);
+        regs.event_endcrypt.write(Event::READY::CLEAR);
+        regs.event_error.write(Event::READY::CLEAR);
+        regs.shorts.write(Shorts::ENDKSGEN_CRYPT::SET);
+        regs.intenset
+            .write(Interrupt::ENDCRYPT::SET + Interrupt::ERROR::SET);
+        regs.task_ksgen.write(Task::ENABLE::SET);
+    }
+
+    fn stop(&self) {
+        let regs = &*self.registers;
+        regs.intenclr
+            .write(Interrupt::ENDKSGEN::SET + Interrupt::ENDCRYPT::SET + Interrupt::ERROR::SET);
+        regs.shorts.set(0);
+        regs.enable.write(Enable::ENABLE::Disabled);
+    }
+
+    pub fn handle_interrupt(&self) {
+        let regs = &*self.registers;
+        let error = regs.event_error.get() == 1;
+        if !error && regs.event_endcrypt.get() == 0 {
+            return;
+        }
+        let mic_passed = regs
+            .micstatus
+            .matches_all(MicStatus::MICSTATUS::CheckPassed);
+        self.stop();
+
+        self.buf.take().map(|buf| {
+            let m_off = self.m_off.get();
+            let m_len = self.m_len.get();
+            let res = if error {
+                Err(CryptoError::HardwareFault)
+            } else {
+                // The output has the same form as the input, with the
+                // message encrypted or decrypted and the MIC appended or
+                // removed.
+                let len = if self.encrypting.get() {
+                    m_len + MIC_LEN
+                } else {
+                    m_len
+                };
+                unsafe {
+                    buf[m_off..m_off + len]
+                        .copy_from_slice(&OUTPUT[PACKET_HEADER_LEN..PACKET_HEADER_LEN + len]);
+                }
+                Ok(())
+            };
+            let tag_is_valid = res.is_ok() && (self.encrypting.get() || mic_passed);
+
+            if self.aead.get() {
+                // Never hand out the plaintext of a forged message.
+                let res = if tag_is_valid {
+                    res
+                } else {
+                    buf[m_off..m_off + m_len]
+                        .iter_mut()
+                        .for_each(|byte| *byte = 0);
+                    res.and(Err(CryptoError::AuthenticationFailed))
+                };
+                self.aead_client
+                    .map(move |client| client.crypt_done(buf, res));
+            } else {
+                self.client
+                    .map(move |client| client.crypt_done(buf, res, tag_is_valid));
+            }
+        });
+    }
+}
+
+impl<'a> symmetric_encryption::AES128CCM<'a> for Ccm<'a> {
+    fn set_client(&'a self, client: &'a dyn symmetric_encryption::CCMClient) {
+        self.client.set(client);
+    }
+
+    fn set_key(&self, key: &[u8]) -> Result<(), CryptoError> {
+        Ccm::set_key(self, key)
+    }
+
+    fn set_nonce(&self, nonce: &[u8]) -> Result<(), CryptoError> {
+        Ccm::set_nonce(self, nonce)
+    }
+
+    fn crypt(
+        &self,
+        buf: &'static mut [u8],
+        a_off: usize,
+        m_off: usize,
+        m_len: usize,
+        mic_len: usize,
+        confidential: bool,
+        encrypting: bool,
+    ) -> Result<(), (CryptoError, &'static mut [u8])> {
+        if a_off > m_off || m_off > buf.len() {
+            return Err((CryptoError::InvalidArgument, buf));
+        }
+        if m_off - a_off != 1 || mic_len != MIC_LEN || !confidential {
+            return Err((CryptoError::NotSupported, buf));
+        }
+        let header = buf[a_off];
+        Ccm::crypt(self, buf, header, m_off, m_len, encrypting, false)
+    }
+}
+
+impl<'a> symmetric_encryption::AEAD<'a> for Ccm<'a> {
+    fn set_client(&'a self, client: &'a dyn symmetric_encryption::AEADClient) {
+        self.aead_client.set(client);
+    }
+
+    fn set_key(&self, key: &[u8]) -> Result<(), CryptoError> {
+        Ccm::set_key(self, key)
+    }
+
+    fn set_nonce(&self, nonce: &[u8]) -> Result<(), CryptoError> {
+        Ccm::set_nonce(self, nonce)
+    }
+
+    fn set_aad(&self, aad: &[u8]) -> Result<(), CryptoError> {
+        if aad.len() != 1 || aad[0] & HEADER_MASK != 0 {
+            return Err(CryptoError::NotSupported);
+        }
+        self.aad.set(aad[0]);
+        Ok(())
+    }
+
+    fn encrypt(
+        &self,
+        buf: &'static mut [u8],
+        len: usize,
+        tag_len: usize,
+    ) -> Result<(), (CryptoError, &'static mut [u8])> {
+        if tag_len != MIC_LEN {
+            return Err((CryptoError::NotSupported, buf));
+        }
+        Ccm::crypt(self, buf, self.aad.get(), 0, len, true, true)
+    }
+
+    fn decrypt(
+        &self,
+        buf: &'static mut [u8],
+        len: usize,
+        tag_len: usize,
+    ) -> Result<(), (CryptoError, &'static mut [u8])> {
+        if tag_len != MIC_LEN {
+            return Err((CryptoError::NotSupported, buf));
+        }
+        Ccm::crypt(self, buf, self.aad.get(), 0, len, false, true)
+    }
+}
+
+impl symmetric_encryption::ClearKeys for Ccm<'_> {
+    fn clear_keys(&self) -> Result<(), CryptoError> {
+        if self.buf.is_some() {
+            return Err(CryptoError::EngineBusy);
+        }
+        self.key.set([0; AES128_KEY_SIZE]);
+        self.nonce.set([0; CCM_NONCE_LENGTH]);
+        unsafe {
+            CNF.iter_mut().for_each(|byte| *byte = 0);
+            SCRATCH.iter_mut().for_each(|byte| *byte = 0);
+        }
+        Ok(())
+    }
+}
@@ -3,14 +3,17 @@ use crate::interrupt_service::InterruptService;
 use crate::nvmc;
 use core::fmt::Write;
 use cortexm4::{self, nvic};
+use kernel::common::cells::OptionalCell;
 use kernel::common::deferred_call;
 use kernel::debug;
+use kernel::metrics::Counter;
 
 pub struct NRF52<I: InterruptService> {
     mpu: cortexm4::mpu::MPU,
     userspace_kernel_boundary: cortexm4::syscall::SysCall,
     systick: cortexm4::systick::SysTick,
     interrupt_service: I,
+    interrupt_counter: OptionalCell<&'static Counter<'static>>,
 }
 
 impl<I: InterruptService> NRF52<I> {
@@ -22,8 +25,15 @@ impl<I: InterruptService> NRF52<I> {
             // 64Mhz CPU clock.
             systick: cortexm4::systick::SysTick::new_with_calibration(64000000),
             interrupt_service,
+            interrupt_counter: OptionalCell::empty(),
         }
     }
+
+    /// Count the interrupts serviced for each NVIC line in `counter`, keyed
+    /// by IRQ number.
+    pub fn set_interrupt_counter(&self, counter: &'static Counter<'static>) {
+        self.interrupt_counter.set(counter);
+    }
 }
 
 impl<I: InterruptService> kernel::Chip for NRF52<I> {
@@ -51,6 +61,8 @@ impl<I: InterruptService> kernel::Chip for NRF52<I> {
                         DeferredCallTask::Nvmc => nvmc::NVMC.handle_interrupt(),
                     }
                 } else if let Some(interrupt) = nvic::next_pending() {
+                    self.interrupt_counter
+                        .map(|counter| counter.increment(interrupt as usize));
                     if !self.interrupt_service.service_interrupt(interrupt) {
                         debug!("NvicIdx not supported by Tock: {}", interrupt);
                     }
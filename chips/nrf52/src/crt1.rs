@@ -148,6 +148,10 @@ pub unsafe extern "C" fn init() {
     tock_rt0::init_data(&mut _etext, &mut _srelocate, &mut _erelocate);
     tock_rt0::zero_bss(&mut _szero, &mut _ezero);
 
+    // Read the boot mode requested before the reset, now that the statics
+    // are initialized, and before anything else can reset the chip.
+    crate::boot_mode::BOOT_MODE.latch();
+
     // Explicitly tell the core where Tock's vector table is located. If Tock is the
     // only thing on the chip then this is effectively a no-op. If, however, there is
     // a bootloader present then we want to ensure that the vector table is set
@@ -0,0 +1,22 @@
+//! Helpers for buffers handed to EasyDMA peripherals.
+//!
+//! EasyDMA can only access the Data RAM region. Pointing a peripheral at a
+//! buffer in flash or peripheral memory does not raise an error, the transfer
+//! simply produces garbage (or a bus error on some parts). Static buffers
+//! owned by chip drivers are declared with `kernel::dma_buffer!`, and buffers
+//! received from clients are checked with `is_dma_accessible()` in debug
+//! builds.
+
+/// Start of the Data RAM region on nRF52 chips.
+const DATA_RAM_START: usize = 0x2000_0000;
+
+/// End of the Data RAM region on the largest nRF52 part (256 kB on the
+/// nRF52840).
+const DATA_RAM_END: usize = 0x2004_0000;
+
+/// Returns true if the whole `buf` lies in memory reachable by EasyDMA.
+pub fn is_dma_accessible<T>(buf: &[T]) -> bool {
+    let start = buf.as_ptr() as usize;
+    let end = start + buf.len() * core::mem::size_of::<T>();
+    start >= DATA_RAM_START && end <= DATA_RAM_END
+}
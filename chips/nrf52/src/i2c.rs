@@ -7,12 +7,14 @@
 //! - Author: Andrew Thompson
 //! - Date: Nov 4, 2017
 
+use crate::easydma;
 use kernel::common::cells::OptionalCell;
 use kernel::common::cells::TakeCell;
 use kernel::common::cells::VolatileCell;
 use kernel::common::registers::{register_bitfields, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
 use kernel::hil;
+use kernel::journal;
 use nrf5x::pinmux::Pinmux;
 
 /// Uninitialized `TWIM` instances.
@@ -91,6 +93,7 @@ impl TWIM {
         if self.registers.events_error.is_set(EVENT::EVENT) {
             self.registers.events_error.write(EVENT::EVENT::CLEAR);
             let errorsrc = self.registers.errorsrc.extract();
+            journal::record(journal::Event::DmaError, errorsrc.get());
             self.registers
                 .errorsrc
                 .write(ERRORSRC::ANACK::ErrorDidNotOccur + ERRORSRC::DNACK::ErrorDidNotOccur);
@@ -134,6 +137,7 @@ impl hil::i2c::I2CMaster for TWIM {
     }
 
     fn write_read(&self, addr: u8, data: &'static mut [u8], write_len: u8, read_len: u8) {
+        debug_assert!(easydma::is_dma_accessible(data));
         self.registers
             .address
             .write(ADDRESS::ADDRESS.val((addr >> 1) as u32));
@@ -161,6 +165,7 @@ impl hil::i2c::I2CMaster for TWIM {
     }
 
     fn write(&self, addr: u8, data: &'static mut [u8], len: u8) {
+        debug_assert!(easydma::is_dma_accessible(data));
         self.registers
             .address
             .write(ADDRESS::ADDRESS.val((addr >> 1) as u32));
@@ -182,6 +187,7 @@ impl hil::i2c::I2CMaster for TWIM {
     }
 
     fn read(&self, addr: u8, buffer: &'static mut [u8], len: u8) {
+        debug_assert!(easydma::is_dma_accessible(buffer));
         self.registers
             .address
             .write(ADDRESS::ADDRESS.val((addr >> 1) as u32));
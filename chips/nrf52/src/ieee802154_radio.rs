@@ -6,6 +6,8 @@ use kernel;
 use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
+use kernel::energy;
+use kernel::hil::entropy::{self, Source};
 use kernel::hil::radio::{self, PowerClient};
 use kernel::hil::time::Alarm;
 use kernel::ReturnCode;
@@ -663,6 +665,7 @@ pub struct Radio {
     tx_power: Cell<TxPower>,
     rx_client: OptionalCell<&'static dyn radio::RxClient>,
     tx_client: OptionalCell<&'static dyn radio::TxClient>,
+    entropy: OptionalCell<&'static dyn entropy::Accumulator>,
     tx_buf: TakeCell<'static, [u8]>,
     rx_buf: TakeCell<'static, [u8]>,
     addr: Cell<u16>,
@@ -673,6 +676,7 @@ pub struct Radio {
     random_nonce: Cell<u32>,
     channel: Cell<RadioChannel>,
     transmitting: Cell<bool>,
+    activity: OptionalCell<&'static dyn energy::Activity>,
 }
 
 pub static mut RADIO: Radio = Radio::new();
@@ -684,6 +688,7 @@ impl Radio {
             tx_power: Cell::new(TxPower::ZerodBm),
             rx_client: OptionalCell::empty(),
             tx_client: OptionalCell::empty(),
+            entropy: OptionalCell::empty(),
             tx_buf: TakeCell::empty(),
             rx_buf: TakeCell::empty(),
             addr: Cell::new(0),
@@ -694,9 +699,21 @@ impl Radio {
             random_nonce: Cell::new(0xDEADBEEF),
             channel: Cell::new(RadioChannel::DataChannel26),
             transmitting: Cell::new(false),
+            activity: OptionalCell::empty(),
         }
     }
 
+    /// Report when the radio is powered is active to `activity`, for energy
+    /// estimates.
+    pub fn set_activity(&self, activity: &'static dyn energy::Activity) {
+        self.activity.set(activity);
+    }
+
+    /// Add the signal strength of each received frame to `entropy`.
+    pub fn set_entropy_accumulator(&self, entropy: &'static dyn entropy::Accumulator) {
+        self.entropy.set(entropy);
+    }
+
     pub fn is_enabled(&self) -> bool {
         self.registers
             .mode
@@ -742,11 +759,15 @@ impl Radio {
         // reset and enable power
         regs.power.write(Task::ENABLE::CLEAR);
         regs.power.write(Task::ENABLE::SET);
+        self.activity
+            .map(|activity| activity.set_active(energy::RADIO, true));
     }
 
     fn radio_off(&self) {
         let regs = &*self.registers;
         regs.power.write(Task::ENABLE::CLEAR);
+        self.activity
+            .map(|activity| activity.set_active(energy::RADIO, false));
     }
 
     fn set_tx_power(&self) {
@@ -779,6 +800,10 @@ impl Radio {
                 regs.task_ccastart.write(Task::ENABLE::SET);
             } else {
                 regs.task_start.write(Task::ENABLE::SET);
+                if !self.transmitting.get() {
+                    // Measure the signal strength of the frame to come.
+                    regs.task_rssistart.write(Task::ENABLE::SET);
+                }
             }
         }
 
@@ -855,6 +880,9 @@ impl Radio {
                 | nrf5x::constants::RADIO_STATE_RXIDLE
                 | nrf5x::constants::RADIO_STATE_RXDISABLE
                 | nrf5x::constants::RADIO_STATE_RX => {
+                    let rssi = regs.rssisample.read(RssiSample::RSSISAMPLE);
+                    self.entropy
+                        .map(|entropy| entropy.add_sample(Source::RadioNoise, rssi));
                     self.rx_client.map(|client| {
                         let rbuf = self.rx_buf.take().expect(
                             "RX Buffer produced error when sending received packet to requestor",
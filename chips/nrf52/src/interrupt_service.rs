@@ -1,6 +1,8 @@
+use crate::aar;
 use crate::acomp;
 use crate::adc;
 use crate::ble_radio;
+use crate::ccm;
 use crate::i2c;
 use crate::ieee802154_radio;
 use crate::power;
@@ -70,6 +72,15 @@ impl InterruptService for Nrf52InterruptService {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt {
             peripheral_interrupts::COMP => acomp::ACOMP.handle_interrupt(),
+            peripheral_interrupts::CCM_AAR => {
+                // CCM and AAR share registers and interrupts, only one of
+                // them is enabled at a time.
+                if ccm::CCM.is_enabled() {
+                    ccm::CCM.handle_interrupt();
+                } else if aar::AAR.is_enabled() {
+                    aar::AAR.handle_interrupt();
+                }
+            }
             peripheral_interrupts::ECB => nrf5x::aes::AESECB.handle_interrupt(),
             peripheral_interrupts::GPIOTE => self.gpio_port.handle_interrupt(),
             peripheral_interrupts::POWER_CLOCK => power::POWER.handle_interrupt(),
@@ -92,6 +103,7 @@ impl InterruptService for Nrf52InterruptService {
             peripheral_interrupts::TIMER0 => nrf5x::timer::TIMER0.handle_interrupt(),
             peripheral_interrupts::TIMER1 => nrf5x::timer::ALARM1.handle_interrupt(),
             peripheral_interrupts::TIMER2 => nrf5x::timer::TIMER2.handle_interrupt(),
+            peripheral_interrupts::TIMER3 => nrf5x::timer::TIMER3.handle_interrupt(),
             peripheral_interrupts::UART0 => uart::UARTE0.handle_interrupt(),
             peripheral_interrupts::SPI0_TWI0 => {
                 // SPI0 and TWI0 share interrupts.
@@ -3,17 +3,22 @@
 #![crate_name = "nrf52"]
 #![crate_type = "rlib"]
 
+pub mod aar;
 pub mod acomp;
 pub mod adc;
 pub mod ble_radio;
+pub mod boot_mode;
+pub mod ccm;
 pub mod chip;
 pub mod clock;
 pub mod crt1;
 mod deferred_call_tasks;
+pub mod easydma;
 pub mod ficr;
 pub mod i2c;
 pub mod ieee802154_radio;
 pub mod interrupt_service;
+pub mod latency;
 pub mod nvmc;
 pub mod power;
 pub mod ppi;
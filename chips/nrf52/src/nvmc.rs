@@ -1,6 +1,11 @@
 //! Non-Volatile Memory Controller
 //!
 //! Used in order read and write to internal flash.
+//!
+//! The number of erases of each page can be tracked with a
+//! `kernel::metrics::Counter` keyed by page number (see
+//! `Nvmc::set_erase_counter()`). A warning is printed once when a page reaches
+//! 90% of the flash endurance rating.
 
 use core::cell::Cell;
 use core::ops::{Index, IndexMut};
@@ -10,7 +15,9 @@ use kernel::common::cells::VolatileCell;
 use kernel::common::deferred_call::DeferredCall;
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
 use kernel::common::StaticRef;
+use kernel::debug;
 use kernel::hil;
+use kernel::metrics::Counter;
 use kernel::ReturnCode;
 
 use crate::deferred_call_tasks::DeferredCallTask;
@@ -191,6 +198,11 @@ impl AsMut<[u8]> for NrfPage {
     }
 }
 
+/// Guaranteed number of erase cycles of a flash page.
+pub const ENDURANCE: u32 = 10_000;
+/// Number of erases after which a page is reported as wearing out.
+const ENDURANCE_WARNING: u32 = ENDURANCE / 10 * 9;
+
 /// FlashState is used to track the current state and command of the flash.
 #[derive(Clone, Copy, PartialEq)]
 pub enum FlashState {
@@ -207,6 +219,7 @@ pub struct Nvmc {
     client: OptionalCell<&'static dyn hil::flash::Client<Nvmc>>,
     buffer: TakeCell<'static, NrfPage>,
     state: Cell<FlashState>,
+    erase_counter: OptionalCell<&'static Counter<'static>>,
 }
 
 impl Nvmc {
@@ -216,9 +229,16 @@ impl Nvmc {
             client: OptionalCell::empty(),
             buffer: TakeCell::empty(),
             state: Cell::new(FlashState::Ready),
+            erase_counter: OptionalCell::empty(),
         }
     }
 
+    /// Count page erases in `counter`, keyed by page number. Pages beyond the
+    /// length of the counter are not tracked.
+    pub fn set_erase_counter(&self, counter: &'static Counter<'static>) {
+        self.erase_counter.set(counter);
+    }
+
     /// Configure the NVMC to allow writes to flash.
     pub fn configure_writeable(&self) {
         let regs = &*self.registers;
@@ -286,6 +306,15 @@ impl Nvmc {
         // Make sure that the NVMC is done. The CPU should be blocked while the
         // erase is happening, but it doesn't hurt to check too.
         while !regs.ready.is_set(Ready::READY) {}
+
+        self.erase_counter.map(|counter| {
+            if counter.increment_to(page_number, ENDURANCE_WARNING) {
+                debug!(
+                    "NVMC: page {} has been erased {} times, close to its endurance",
+                    page_number, ENDURANCE_WARNING
+                );
+            }
+        });
     }
 
     fn read_range(
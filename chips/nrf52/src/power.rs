@@ -347,6 +347,39 @@ impl<'a> Power<'a> {
     pub fn is_usb_power_ready(&self) -> bool {
         self.registers.usbregstatus.is_set(UsbRegStatus::OUTPUTRDY)
     }
+
+    /// Write the general purpose retention register, which keeps its value
+    /// across soft resets. Bootloaders read it to decide whether to stay in
+    /// DFU mode.
+    pub fn set_gpregret(&self, value: u8) {
+        self.registers.gpregret.write(Byte::VALUE.val(value as u32));
+    }
+
+    pub fn get_gpregret(&self) -> u8 {
+        self.registers.gpregret.read(Byte::VALUE) as u8
+    }
+
+    /// Whether the last reset was caused by the watchdog or by a CPU lockup.
+    /// The reset reasons are cleared, as they otherwise accumulate across
+    /// resets.
+    pub fn take_reset_by_fault(&self) -> bool {
+        let regs = &*self.registers;
+        let fault =
+            regs.resetreas.is_set(ResetReason::DOG) || regs.resetreas.is_set(ResetReason::LOCKUP);
+        regs.resetreas.set(regs.resetreas.get());
+        fault
+    }
+
+    /// Write the second general purpose retention register.
+    pub fn set_gpregret2(&self, value: u8) {
+        self.registers
+            .gpregret2
+            .write(Byte::VALUE.val(value as u32));
+    }
+
+    pub fn get_gpregret2(&self) -> u8 {
+        self.registers.gpregret2.read(Byte::VALUE) as u8
+    }
 }
 
 pub static mut POWER: Power<'static> = Power::new();
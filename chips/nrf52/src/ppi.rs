@@ -11,6 +11,11 @@
 //! associated with the task. Similarly, a peripheral event is connected to an EEP using
 //! the address of the event register associated with the event.
 //!
+//! Channels 0 to 19 are programmable. Drivers get one with `connect()` while
+//! they need it and give it back with `release()`, so none is hard-coded:
+//! the SAADC uses one while monitoring a channel, and the latency test one
+//! while toggling its pin.
+//!
 //! Pre-programmed Channels
 //! (Channel EEP TEP):
 //!
@@ -33,9 +38,14 @@
 //! * Francine Mäkelä
 //! * Date: May 04, 2018
 
+use kernel::common::cells::OptionalCell;
 use kernel::common::registers::{register_bitfields, FieldValue, ReadWrite};
+use kernel::common::slot_map::SlotMap;
 use kernel::common::StaticRef;
 
+/// Number of programmable channels, 0 to 19.
+const PROGRAMMABLE_CHANNELS: usize = 20;
+
 const PPI_BASE: StaticRef<PpiRegisters> =
     unsafe { StaticRef::new(0x4001F000 as *const PpiRegisters) };
 
@@ -57,52 +67,19 @@ struct PpiRegisters {
     chen: ReadWrite<u32, Channel::Register>,
     chenset: ReadWrite<u32, Channel::Register>,
     chenclr: ReadWrite<u32, Channel::Register>,
-    ch0_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch0_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch1_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch1_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch2_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch2_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch3_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch3_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch4_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch4_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch5_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch5_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch6_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch6_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch7_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch7_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch8_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch8_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch9_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch9_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch10_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch10_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch11_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch11_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch12_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch12_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch13_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch13_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch14_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch14_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch15_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch15_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch16_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch16_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch17_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch17_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch18_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch18_tep: ReadWrite<u32, TaskEndPoint::Register>,
-    ch19_eep: ReadWrite<u32, EventEndPoint::Register>,
-    ch19_tep: ReadWrite<u32, TaskEndPoint::Register>,
+    ch: [ChannelRegisters; 20],
     _reserved2: [u32; 148],
     chg: [ReadWrite<u32, Channel::Register>; 6],
     _reserved3: [u32; 62],
     fork_tep: [ReadWrite<u32, TaskEndPoint::Register>; 32],
 }
 
+#[repr(C)]
+struct ChannelRegisters {
+    eep: ReadWrite<u32, EventEndPoint::Register>,
+    tep: ReadWrite<u32, TaskEndPoint::Register>,
+}
+
 register_bitfields! [u32,
     Control [
         ENABLE OFFSET(0) NUMBITS(1)
@@ -151,17 +128,29 @@ register_bitfields! [u32,
 
 pub struct Ppi {
     registers: StaticRef<PpiRegisters>,
+    /// The driver each programmable channel is connected for, if any.
+    owners: [OptionalCell<&'static str>; PROGRAMMABLE_CHANNELS],
 }
 
+const FREE: OptionalCell<&'static str> = OptionalCell::empty();
+
 pub static mut PPI: Ppi = Ppi::new();
 
 impl Ppi {
     pub const fn new() -> Ppi {
         Ppi {
             registers: PPI_BASE,
+            owners: [
+                FREE, FREE, FREE, FREE, FREE, FREE, FREE, FREE, FREE, FREE, FREE, FREE, FREE, FREE,
+                FREE, FREE, FREE, FREE, FREE, FREE,
+            ],
         }
     }
 
+    fn channels(&self) -> SlotMap<&'static str> {
+        SlotMap::new(&self.owners)
+    }
+
     pub fn enable(&self, channels: FieldValue<u32, Channel::Register>) {
         let regs = &*self.registers;
         regs.chenset.write(channels);
@@ -171,4 +160,41 @@ impl Ppi {
         let regs = &*self.registers;
         regs.chenclr.write(channels);
     }
+
+    /// Connect the event register at address `event` to the task register at
+    /// address `task` through the programmable channel `channel`, between 0
+    /// and 19. The channel still has to be enabled.
+    pub fn configure(&self, channel: usize, event: usize, task: usize) {
+        let regs = &*self.registers;
+        regs.ch[channel]
+            .eep
+            .write(EventEndPoint::ADDRESS.val(event as u32));
+        regs.ch[channel]
+            .tep
+            .write(TaskEndPoint::ADDRESS.val(task as u32));
+    }
+
+    /// Connect `event` to `task` through a free programmable channel, on
+    /// behalf of the driver `owner`, and enable it. Returns the channel, to
+    /// `release()` when done, or `None` if all 20 are in use.
+    pub fn connect(&self, owner: &'static str, event: usize, task: usize) -> Option<usize> {
+        let channel = self.channels().insert(owner)?;
+        self.configure(channel, event, task);
+        let regs = &*self.registers;
+        regs.chenset.set(1 << channel);
+        Some(channel)
+    }
+
+    /// Disable a channel from `connect()` and make it free again.
+    pub fn release(&self, channel: usize) {
+        let regs = &*self.registers;
+        regs.chenclr.set(1 << channel);
+        self.channels().remove(channel);
+    }
+
+    /// The driver `channel` is connected for, if it is programmable and in
+    /// use.
+    pub fn owner(&self, channel: usize) -> Option<&'static str> {
+        self.channels().get(channel)
+    }
 }
@@ -166,12 +166,14 @@ const PWM0_BASE: StaticRef<PwmRegisters> =
 
 pub static mut PWM0: Pwm = Pwm::new(PWM0_BASE);
 
-/// `DUTY_CYCLES` is a static array that must be passed to the PWM hardware.
-/// The nRF52 hardware uses this static array in memory to enable switching
-/// between multiple duty cycles automatically while generating the PWM output.
-/// This isn't ideal from a Rust perspective, but the peripheral hardware must
-/// be passed a pointer.
-static mut DUTY_CYCLES: [u16; 4] = [0; 4];
+kernel::dma_buffer!(
+    /// `DUTY_CYCLES` is a static array that must be passed to the PWM hardware.
+    /// The nRF52 hardware uses this static array in memory to enable switching
+    /// between multiple duty cycles automatically while generating the PWM output.
+    /// This isn't ideal from a Rust perspective, but the peripheral hardware must
+    /// be passed a pointer.
+    static mut DUTY_CYCLES: [u16; 4] = [0; 4];
+);
 
 pub struct Pwm {
     registers: StaticRef<PwmRegisters>,
@@ -29,11 +29,13 @@
 //! * Author: Jay Kickliter
 //! * Date: Sep 10, 2017
 
+use crate::easydma;
 use core::cell::Cell;
 use core::{cmp, ptr};
 use kernel::common::cells::{OptionalCell, TakeCell, VolatileCell};
 use kernel::common::registers::{register_bitfields, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
+use kernel::energy;
 use kernel::hil;
 use kernel::ReturnCode;
 use nrf5x::pinmux::Pinmux;
@@ -248,6 +250,7 @@ pub struct SPIM {
     tx_buf: TakeCell<'static, [u8]>,
     rx_buf: TakeCell<'static, [u8]>,
     transfer_len: Cell<usize>,
+    activity: OptionalCell<&'static dyn energy::Activity>,
 }
 
 impl SPIM {
@@ -261,9 +264,16 @@ impl SPIM {
             tx_buf: TakeCell::empty(),
             rx_buf: TakeCell::empty(),
             transfer_len: Cell::new(0),
+            activity: OptionalCell::empty(),
         }
     }
 
+    /// Report when the SPIM is transferring is active to `activity`, for energy
+    /// estimates.
+    pub fn set_activity(&self, activity: &'static dyn energy::Activity) {
+        self.activity.set(activity);
+    }
+
     #[inline(never)]
     pub fn handle_interrupt(&self) {
         if self.registers.events_end.is_set(EVENT::EVENT) {
@@ -285,6 +295,11 @@ impl SPIM {
             });
 
             self.busy.set(false);
+
+            // The client may have started another transfer
+            let active = self.tx_buf.is_some();
+            self.activity
+                .map(|activity| activity.set_active(energy::SPI, active));
         }
 
         // Although we only configured the chip interrupt on the
@@ -370,6 +385,7 @@ impl hil::spi::SpiMaster for SPIM {
 
         // Setup transmit data registers
         let tx_len: u32 = cmp::min(len, tx_buf.len()) as u32;
+        debug_assert!(easydma::is_dma_accessible(tx_buf));
         self.registers.txd_ptr.set(tx_buf.as_ptr());
         self.registers.txd_maxcnt.write(MAXCNT::MAXCNT.val(tx_len));
         self.tx_buf.replace(tx_buf);
@@ -383,6 +399,7 @@ impl hil::spi::SpiMaster for SPIM {
                 self.rx_buf.put(None);
             }
             Some(buf) => {
+                debug_assert!(easydma::is_dma_accessible(buf));
                 self.registers.rxd_ptr.set(buf.as_mut_ptr());
                 let rx_len: u32 = cmp::min(len, buf.len()) as u32;
                 self.registers.rxd_maxcnt.write(MAXCNT::MAXCNT.val(rx_len));
@@ -393,6 +410,8 @@ impl hil::spi::SpiMaster for SPIM {
 
         // Start the transfer
         self.busy.set(true);
+        self.activity
+            .map(|activity| activity.set_active(energy::SPI, true));
         self.registers.tasks_start.write(TASK::TASK::SET);
         ReturnCode::SUCCESS
     }
@@ -12,13 +12,41 @@ use core::cmp::min;
 use kernel::common::cells::OptionalCell;
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
+use kernel::energy;
 use kernel::hil::uart;
 use kernel::ReturnCode;
 use nrf5x::pinmux;
 
 const UARTE_MAX_BUFFER_SIZE: u32 = 0xff;
 
-static mut BYTE: u8 = 0;
+kernel::dma_buffer!(
+    static mut BYTE: u8 = 0;
+);
+
+// Sent at a lower baud rate to hold the line low for a break
+kernel::dma_buffer!(
+    static mut BREAK: u8 = 0;
+);
+
+/// Supported baud rates, and the values of the BAUDRATE register for them.
+const BAUD_RATES: [(u32, u32); 16] = [
+    (1200, 0x0004F000),
+    (2400, 0x0009D000),
+    (4800, 0x0013B000),
+    (9600, 0x00275000),
+    (14400, 0x003AF000),
+    (19200, 0x004EA000),
+    (28800, 0x0075C000),
+    (38400, 0x009D0000),
+    (57600, 0x00EB0000),
+    (76800, 0x013A9000),
+    (115200, 0x01D60000),
+    (230400, 0x03B00000),
+    (250000, 0x04000000),
+    (460800, 0x07400000),
+    (921600, 0x0F000000),
+    (1000000, 0x10000000),
+];
 
 const UARTE_BASE: StaticRef<UarteRegisters> =
     unsafe { StaticRef::new(0x40002000 as *const UarteRegisters) };
@@ -150,8 +178,11 @@ register_bitfields! [u32,
 
     /// Configuration of parity and flow control
     Config [
-        HWFC OFFSET(0) NUMBITS(1),
-        PARITY OFFSET(1) NUMBITS(3)
+        HWFC OFFSET(0) NUMBITS(1) [],
+        PARITY OFFSET(1) NUMBITS(3) [
+            Excluded = 0,
+            Included = 7
+        ]
     ]
 ];
 
@@ -168,7 +199,12 @@ pub struct Uarte<'a> {
     rx_buffer: kernel::common::cells::TakeCell<'static, [u8]>,
     rx_remaining_bytes: Cell<usize>,
     rx_abort_in_progress: Cell<bool>,
+    /// Line error that stopped the current receive.
+    rx_error: Cell<uart::Error>,
     offset: Cell<usize>,
+    baud_rate: Cell<u32>,
+    tx_break: Cell<bool>,
+    activity: OptionalCell<&'static dyn energy::Activity>,
 }
 
 #[derive(Copy, Clone)]
@@ -193,10 +229,28 @@ impl<'a> Uarte<'a> {
             rx_buffer: kernel::common::cells::TakeCell::empty(),
             rx_remaining_bytes: Cell::new(0),
             rx_abort_in_progress: Cell::new(false),
+            rx_error: Cell::new(uart::Error::None),
             offset: Cell::new(0),
+            baud_rate: Cell::new(0),
+            tx_break: Cell::new(false),
+            activity: OptionalCell::empty(),
         }
     }
 
+    /// Report when the UARTE is transmitting or receiving is active to `activity`, for energy
+    /// estimates.
+    pub fn set_activity(&self, activity: &'static dyn energy::Activity) {
+        self.activity.set(activity);
+    }
+
+    /// The UARTE is active while a transmission or a reception is in
+    /// progress.
+    fn report_activity(&self) {
+        let active = self.tx_buffer.is_some() || self.tx_break.get() || self.rx_buffer.is_some();
+        self.activity
+            .map(|activity| activity.set_active(energy::UART, active));
+    }
+
     /// Configure which pins the UART should use for txd, rxd, cts and rts
     pub fn initialize(
         &self,
@@ -241,25 +295,12 @@ impl<'a> Uarte<'a> {
 
     fn set_baud_rate(&self, baud_rate: u32) {
         let regs = &*self.registers;
-        match baud_rate {
-            1200 => regs.baudrate.set(0x0004F000),
-            2400 => regs.baudrate.set(0x0009D000),
-            4800 => regs.baudrate.set(0x0013B000),
-            9600 => regs.baudrate.set(0x00275000),
-            14400 => regs.baudrate.set(0x003AF000),
-            19200 => regs.baudrate.set(0x004EA000),
-            28800 => regs.baudrate.set(0x0075C000),
-            38400 => regs.baudrate.set(0x009D0000),
-            57600 => regs.baudrate.set(0x00EB0000),
-            76800 => regs.baudrate.set(0x013A9000),
-            115200 => regs.baudrate.set(0x01D60000),
-            230400 => regs.baudrate.set(0x03B00000),
-            250000 => regs.baudrate.set(0x04000000),
-            460800 => regs.baudrate.set(0x07400000),
-            921600 => regs.baudrate.set(0x0F000000),
-            1000000 => regs.baudrate.set(0x10000000),
-            _ => regs.baudrate.set(0x01D60000), //setting default to 115200
-        }
+        let &(baud_rate, value) = BAUD_RATES
+            .iter()
+            .find(|(rate, _)| *rate == baud_rate)
+            .unwrap_or(&(115200, 0x01D60000)); //setting default to 115200
+        regs.baudrate.set(value);
+        self.baud_rate.set(baud_rate);
     }
 
     // Enable UART peripheral, this need to disabled for low power applications
@@ -276,7 +317,8 @@ impl<'a> Uarte<'a> {
 
     fn enable_rx_interrupts(&self) {
         let regs = &*self.registers;
-        regs.intenset.write(Interrupt::ENDRX::SET);
+        regs.intenset
+            .write(Interrupt::ENDRX::SET + Interrupt::ERROR::SET);
     }
 
     fn enable_tx_interrupts(&self) {
@@ -286,7 +328,37 @@ impl<'a> Uarte<'a> {
 
     fn disable_rx_interrupts(&self) {
         let regs = &*self.registers;
-        regs.intenclr.write(Interrupt::ENDRX::SET);
+        regs.intenclr
+            .write(Interrupt::ENDRX::SET + Interrupt::ERROR::SET);
+    }
+
+    /// Record the cause of an ERROR event, and stop the current receive so
+    /// that the client hears about it, with the bytes received until then.
+    fn handle_error(&self) {
+        let regs = &*self.registers;
+        regs.event_error.write(Event::READY::CLEAR);
+
+        let errors = regs.errorsrc.extract();
+        // The bits are cleared by writing 1 to them
+        regs.errorsrc.set(errors.get());
+
+        // A break also causes a framing error, so it is checked first
+        let error = if errors.is_set(ErrorSrc::BREAK) {
+            uart::Error::BreakError
+        } else if errors.is_set(ErrorSrc::FRAMING) {
+            uart::Error::FramingError
+        } else if errors.is_set(ErrorSrc::PARITY) {
+            uart::Error::ParityError
+        } else if errors.is_set(ErrorSrc::OVERRUN) {
+            uart::Error::OverrunError
+        } else {
+            return;
+        };
+
+        if self.rx_buffer.is_some() && self.rx_error.get() == uart::Error::None {
+            self.rx_error.set(error);
+            regs.task_stoprx.write(Task::ENABLE::SET);
+        }
     }
 
     fn disable_tx_interrupts(&self) {
@@ -299,6 +371,17 @@ impl<'a> Uarte<'a> {
     pub fn handle_interrupt(&mut self) {
         let regs = &*self.registers;
 
+        if self.tx_ready() && self.tx_break.get() {
+            self.disable_tx_interrupts();
+            regs.event_endtx.write(Event::READY::CLEAR);
+            self.tx_break.set(false);
+            self.set_baud_rate(self.baud_rate.get());
+
+            self.tx_client.map(|client| {
+                client.transmitted_break(ReturnCode::SUCCESS);
+            });
+        }
+
         if self.tx_ready() {
             self.disable_tx_interrupts();
             let regs = &*self.registers;
@@ -334,6 +417,10 @@ impl<'a> Uarte<'a> {
             }
         }
 
+        if regs.event_error.is_set(Event::READY) {
+            self.handle_error();
+        }
+
         if self.rx_ready() {
             self.disable_rx_interrupts();
 
@@ -357,6 +444,19 @@ impl<'a> Uarte<'a> {
                         );
                     });
                 });
+            } else if self.rx_error.get() != uart::Error::None {
+                // The receive was stopped by a line error
+                let error = self.rx_error.replace(uart::Error::None);
+                self.rx_client.map(|client| {
+                    self.rx_buffer.take().map(|rx_buffer| {
+                        client.received_buffer(
+                            rx_buffer,
+                            self.offset.get() + rx_bytes,
+                            ReturnCode::FAIL,
+                            error,
+                        );
+                    });
+                });
             } else {
                 // In the normal case, we need to either pass call the callback
                 // or do another read to get more bytes.
@@ -393,6 +493,8 @@ impl<'a> Uarte<'a> {
                 }
             }
         }
+
+        self.report_activity();
     }
 
     /// Transmit one byte at the time and the client is responsible for polling
@@ -424,6 +526,7 @@ impl<'a> Uarte<'a> {
     fn set_tx_dma_pointer_to_buffer(&self) {
         let regs = &*self.registers;
         self.tx_buffer.map(|tx_buffer| {
+            debug_assert!(crate::easydma::is_dma_accessible(tx_buffer));
             regs.txd_ptr
                 .set(tx_buffer[self.offset.get()..].as_ptr() as u32);
         });
@@ -432,6 +535,7 @@ impl<'a> Uarte<'a> {
     fn set_rx_dma_pointer_to_buffer(&self) {
         let regs = &*self.registers;
         self.rx_buffer.map(|rx_buffer| {
+            debug_assert!(crate::easydma::is_dma_accessible(rx_buffer));
             regs.rxd_ptr
                 .set(rx_buffer[self.offset.get()..].as_ptr() as u32);
         });
@@ -451,6 +555,7 @@ impl<'a> Uarte<'a> {
         regs.task_starttx.write(Task::ENABLE::SET);
 
         self.enable_tx_interrupts();
+        self.report_activity();
     }
 }
 
@@ -469,7 +574,7 @@ impl<'a> uart::Transmit<'a> for Uarte<'a> {
     ) -> (ReturnCode, Option<&'static mut [u8]>) {
         if tx_len == 0 || tx_len > tx_data.len() {
             (ReturnCode::ESIZE, Some(tx_data))
-        } else if self.tx_buffer.is_some() {
+        } else if self.tx_buffer.is_some() || self.tx_break.get() {
             (ReturnCode::EBUSY, Some(tx_data))
         } else {
             self.setup_buffer_transmit(tx_data, tx_len);
@@ -486,6 +591,45 @@ impl<'a> uart::Transmit<'a> for Uarte<'a> {
     }
 }
 
+/// The UARTE cannot hold TXD low by itself, so a zero byte is sent at less
+/// than half the baud rate: its start bit and eight data bits keep the line
+/// low for more than two frames at the configured rate.
+impl<'a> uart::TransmitBreak for Uarte<'a> {
+    fn transmit_break(&self) -> ReturnCode {
+        let regs = &*self.registers;
+        let baud_rate = self.baud_rate.get();
+        if baud_rate == 0 {
+            return ReturnCode::EOFF;
+        }
+        if self.tx_buffer.is_some() || self.tx_break.get() {
+            return ReturnCode::EBUSY;
+        }
+
+        // 9 bits at `rate` must last 20 bit periods at `baud_rate`
+        let slow = BAUD_RATES
+            .iter()
+            .rev()
+            .find(|(rate, _)| (*rate as u64) * 20 <= (baud_rate as u64) * 9);
+        let &(_, value) = match slow {
+            Some(rate) => rate,
+            None => return ReturnCode::ENOSUPPORT,
+        };
+
+        self.tx_break.set(true);
+        regs.baudrate.set(value);
+        regs.event_endtx.write(Event::READY::CLEAR);
+        unsafe {
+            regs.txd_ptr.set((&BREAK as *const u8) as u32);
+        }
+        regs.txd_maxcnt.write(Counter::COUNTER.val(1));
+        regs.task_starttx.write(Task::ENABLE::SET);
+        self.enable_tx_interrupts();
+        self.report_activity();
+
+        ReturnCode::SUCCESS
+    }
+}
+
 impl<'a> uart::Configure for Uarte<'a> {
     fn configure(&self, params: uart::Parameters) -> ReturnCode {
         // These could probably be implemented, but are currently ignored, so
@@ -493,14 +637,18 @@ impl<'a> uart::Configure for Uarte<'a> {
         if params.stop_bits != uart::StopBits::One {
             return ReturnCode::ENOSUPPORT;
         }
-        if params.parity != uart::Parity::None {
-            return ReturnCode::ENOSUPPORT;
-        }
+        // The UARTE only supports even parity
+        let parity = match params.parity {
+            uart::Parity::None => Config::PARITY::Excluded,
+            uart::Parity::Even => Config::PARITY::Included,
+            uart::Parity::Odd => return ReturnCode::ENOSUPPORT,
+        };
         if params.hw_flow_control != false {
             return ReturnCode::ENOSUPPORT;
         }
 
         self.set_baud_rate(params.baud_rate);
+        self.registers.config.modify(parity);
 
         ReturnCode::SUCCESS
     }
@@ -525,6 +673,10 @@ impl<'a> uart::Receive<'a> for Uarte<'a> {
 
         self.rx_remaining_bytes.set(truncated_length);
         self.offset.set(0);
+        // Forget errors that happened while nothing was being received
+        regs.errorsrc.set(regs.errorsrc.get());
+        regs.event_error.write(Event::READY::CLEAR);
+        self.rx_error.set(uart::Error::None);
         self.rx_buffer.replace(rx_buf);
         self.set_rx_dma_pointer_to_buffer();
 
@@ -536,6 +688,7 @@ impl<'a> uart::Receive<'a> for Uarte<'a> {
         regs.task_startrx.write(Task::ENABLE::SET);
 
         self.enable_rx_interrupts();
+        self.report_activity();
         (ReturnCode::SUCCESS, None)
     }
 
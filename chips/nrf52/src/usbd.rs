@@ -11,6 +11,7 @@ use kernel::common::StaticRef;
 use kernel::debug;
 use kernel::hil;
 use kernel::hil::usb::TransferType;
+use kernel::system_events;
 
 use crate::power;
 
@@ -1453,10 +1454,12 @@ impl<'a> Usbd<'a> {
         if eventcause.is_set(EventCause::SUSPEND) {
             debug_events!("- usbevent: suspend");
             internal_warn!("usbc::suspend not implemented");
+            system_events::notify(system_events::Event::UsbSuspend, 0);
         }
         if eventcause.is_set(EventCause::RESUME) {
             debug_events!("- usbevent: resume");
             internal_warn!("usbc::resume not implemented");
+            system_events::notify(system_events::Event::UsbResume, 0);
         }
         if eventcause.is_set(EventCause::USBWUALLOWED) {
             debug_events!("- usbevent: usbwuallowed");
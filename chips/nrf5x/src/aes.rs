@@ -1,28 +1,69 @@
 //! AES128 driver, nRF5X-family
 //!
-//! Provides a simple driverto encrypt and decrypt
-//! messages using aes128-ctr mode on top of aes128-ecb.
+//! Provides a simple driver to encrypt and decrypt messages using aes128-ctr
+//! mode, and to encrypt messages using aes128-cbc mode, on top of the
+//! aes128-ecb peripheral. The ECB peripheral can only encrypt, so CBC
+//! decryption is not supported and `crypt()` returns `NotSupported` for it.
 //!
 //! Roughly, the module three buffers with the following content:
 //!
 //! * Key
-//! * Initial counter
+//! * Initial counter or IV
 //! * Payload, to be encrypted or decrypted
 //!
 //! ### Key
 //! The key is used for getting a key and configure it in the AES chip
 //!
-//! ### Initial Counter
-//! Counter to be used for aes-ctr and it is entered into AES to generate the
-//! the keystream. After each encryption the initial counter is incremented
+//! ### Initial Counter / IV
+//! In CTR mode the counter is entered into AES to generate the keystream, and
+//! incremented after each block. In CBC mode the IV is XOR:ed with the first
+//! block of plaintext, and then replaced by each block of ciphertext.
+//!
+//! The key and the IV given to `set_key()` and `set_iv()` only take effect at
+//! the next `start_message()`, so they can be set in any order, even while an
+//! operation is in progress. The key and the current counter or IV can be
+//! saved and restored with `AES128SaveRestore`, to interleave several
+//! streams.
+//!
+//! In CTR mode the counter can be kept fixed with `set_ctr_no_increment()`.
+//!
+//! ### Nonce reuse
+//! Encrypting two messages with the same key and counter in CTR mode reveals
+//! the XOR of the plaintexts. With `set_nonce_guard()`, the driver remembers
+//! a fingerprint of the last `NONCE_GUARD_LEN` key and counter pairs used to
+//! encrypt in CTR mode, and `crypt()` returns `NonceReused` for a message
+//! started with one of them. Decryption is not checked, as a message may be
+//! decrypted any number of times.
 //!
 //! ### Payload
-//! Data to be encrypted or decrypted it is XOR:ed with the generated keystream
+//! Data to be encrypted or decrypted. It is processed one block per ECB
+//! operation, either from the source buffer or in place in the destination
+//! buffer.
+//!
+//! ### Single blocks
+//! Kernel code that only needs the block cipher can encrypt single blocks
+//! with `AES128Block::ecb_encrypt_block()`. These requests have their own key
+//! and DMA buffer and are queued. They are served before the next block of a
+//! message, so they wait for at most one block.
+//!
+//! ### Aborted blocks
+//! The peripheral aborts a block when the radio needs the AES hardware. The
+//! block is encrypted again, up to `MAX_RETRIES` times, after which the
+//! message or single block is finished with `HardwareFault`.
+//!
+//! ### Throughput
+//! With `set_counter()`, the driver counts its work in a
+//! `kernel::metrics::Counter` of `COUNTER_LEN` entries, which the process
+//! console shows with the `metrics` command:
+//!
+//! * `0`: bytes of messages processed
+//! * `1`: messages processed
+//! * `2`: single blocks encrypted
+//! * `3`: time spent processing messages, in RTC tics (1/32768 s)
 //!
 //! ### Things to highlight that can be improved:
 //!
 //! * ECB_DATA must be a static mut \[u8\] and can't be located in the struct
-//! * PAYLOAD size is restricted to 128 bytes
 //!
 //! Authors
 //! --------
@@ -31,30 +72,55 @@
 //! * Date: April 21, 2017
 
 use core::cell::Cell;
+use core::cmp;
 use kernel::common::cells::OptionalCell;
 use kernel::common::cells::TakeCell;
 use kernel::common::registers::{register_bitfields, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
-use kernel::hil::symmetric_encryption;
-use kernel::ReturnCode;
+use kernel::energy;
+use kernel::hil::crypto::CryptoError;
+use kernel::hil::symmetric_encryption::{self, AES128_BLOCK_SIZE, AES128_KEY_SIZE};
+use kernel::hil::time::Time;
+use kernel::metrics::Counter;
+use kernel::system_events;
 
 // DMA buffer that the aes chip will mutate during encryption
 // Byte 0-15   - Key
-// Byte 16-32  - Payload
-// Byte 33-47  - Ciphertext
-static mut ECB_DATA: [u8; 48] = [0; 48];
+// Byte 16-31  - Payload
+// Byte 32-47  - Ciphertext
+kernel::dma_buffer!(
+    static mut ECB_DATA: [u8; 48] = [0; 48];
+);
+
+// DMA buffer for single block requests, with the same layout as ECB_DATA.
+kernel::dma_buffer!(
+    static mut BLOCK_DATA: [u8; 48] = [0; 48];
+);
+
+/// Number of entries of the throughput counter.
+pub const COUNTER_LEN: usize = 4;
+
+const COUNTER_BYTES: usize = 0;
+const COUNTER_MESSAGES: usize = 1;
+const COUNTER_BLOCKS: usize = 2;
+const COUNTER_BUSY_TICS: usize = 3;
+
+/// Number of key and counter pairs remembered by the nonce guard.
+pub const NONCE_GUARD_LEN: usize = 16;
+
+/// Number of single block requests that can be queued.
+const BLOCK_QUEUE_LEN: usize = 4;
+
+/// Number of times an aborted block is encrypted again before giving up.
+pub const MAX_RETRIES: usize = 3;
 
-#[allow(dead_code)]
 const KEY_START: usize = 0;
 #[allow(dead_code)]
 const KEY_END: usize = 15;
 const PLAINTEXT_START: usize = 16;
 const PLAINTEXT_END: usize = 32;
-#[allow(dead_code)]
-const CIPHERTEXT_START: usize = 33;
-#[allow(dead_code)]
-const CIPHERTEXT_END: usize = 47;
-const MAX_LENGTH: usize = 128;
+const CIPHERTEXT_START: usize = 32;
+const CIPHERTEXT_END: usize = 48;
 
 const AESECB_BASE: StaticRef<AesEcbRegisters> =
     unsafe { StaticRef::new(0x4000E000 as *const AesEcbRegisters) };
@@ -119,17 +185,64 @@ register_bitfields! [u32,
     ]
 ];
 
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Ctr,
+    CbcEncrypt,
+    CbcDecrypt,
+}
+
+/// What the ECB peripheral is encrypting.
+#[derive(Clone, Copy, PartialEq)]
+enum Running {
+    Idle,
+    Message,
+    Block,
+}
+
+#[derive(Clone, Copy)]
+struct BlockRequest {
+    key: [u8; AES128_KEY_SIZE],
+    block: [u8; AES128_BLOCK_SIZE],
+}
+
 pub struct AesECB<'a> {
     registers: StaticRef<AesEcbRegisters>,
     client: OptionalCell<&'a dyn kernel::hil::symmetric_encryption::Client<'a>>,
-    /// Input either plaintext or ciphertext to be encrypted or decrypted.
+    mode: Cell<Mode>,
+    /// Key and IV, loaded into ECB_DATA by `start_message()`.
+    key: Cell<[u8; AES128_KEY_SIZE]>,
+    iv: Cell<[u8; AES128_BLOCK_SIZE]>,
+    /// Input either plaintext or ciphertext to be encrypted or decrypted, or
+    /// none if the output buffer is encrypted in place.
     input: TakeCell<'a, [u8]>,
     output: TakeCell<'a, [u8]>,
-    /// Keystream to be XOR'ed with the input.
-    keystream: Cell<[u8; MAX_LENGTH]>,
+    /// Index in the output buffer of the block being processed.
     current_idx: Cell<usize>,
     start_idx: Cell<usize>,
     end_idx: Cell<usize>,
+    running: Cell<Running>,
+    /// Times the block being encrypted was aborted.
+    retries: Cell<usize>,
+    /// Whether the next block of the message is waiting for the peripheral.
+    message_pending: Cell<bool>,
+    block_queue: Cell<[Option<BlockRequest>; BLOCK_QUEUE_LEN]>,
+    block_head: Cell<usize>,
+    block_len: Cell<usize>,
+    block_client: OptionalCell<&'a dyn symmetric_encryption::BlockClient>,
+    counter: OptionalCell<&'static Counter<'static>>,
+    /// RTC time at which the message was started.
+    message_start: Cell<u32>,
+    /// Whether CTR mode was set for encryption.
+    ctr_encrypting: Cell<bool>,
+    ctr_no_increment: Cell<bool>,
+    nonce_guard: Cell<bool>,
+    /// Fingerprints of the key and counter pairs used to encrypt.
+    used_nonces: Cell<[Option<u64>; NONCE_GUARD_LEN]>,
+    used_nonces_next: Cell<usize>,
+    /// Whether the current message reuses a key and counter pair.
+    nonce_reused: Cell<bool>,
+    activity: OptionalCell<&'static dyn energy::Activity>,
 }
 
 pub static mut AESECB: AesECB = AesECB::new();
@@ -139,15 +252,90 @@ impl<'a> AesECB<'a> {
         AesECB {
             registers: AESECB_BASE,
             client: OptionalCell::empty(),
+            mode: Cell::new(Mode::Ctr),
+            key: Cell::new([0; AES128_KEY_SIZE]),
+            iv: Cell::new([0; AES128_BLOCK_SIZE]),
             input: TakeCell::empty(),
             output: TakeCell::empty(),
-            keystream: Cell::new([0; MAX_LENGTH]),
             current_idx: Cell::new(0),
             start_idx: Cell::new(0),
             end_idx: Cell::new(0),
+            running: Cell::new(Running::Idle),
+            retries: Cell::new(0),
+            message_pending: Cell::new(false),
+            block_queue: Cell::new([None; BLOCK_QUEUE_LEN]),
+            block_head: Cell::new(0),
+            block_len: Cell::new(0),
+            block_client: OptionalCell::empty(),
+            counter: OptionalCell::empty(),
+            message_start: Cell::new(0),
+            ctr_encrypting: Cell::new(false),
+            ctr_no_increment: Cell::new(false),
+            nonce_guard: Cell::new(false),
+            used_nonces: Cell::new([None; NONCE_GUARD_LEN]),
+            used_nonces_next: Cell::new(0),
+            nonce_reused: Cell::new(false),
+            activity: OptionalCell::empty(),
         }
     }
 
+    /// Refuse to encrypt with a key and counter pair that was already used
+    /// in CTR mode.
+    pub fn set_nonce_guard(&self, enabled: bool) {
+        self.nonce_guard.set(enabled);
+        self.nonce_reused.set(false);
+    }
+
+    /// Check whether the key and counter of a new message were already used,
+    /// and remember them otherwise.
+    fn check_nonce(&self, key: &[u8; AES128_KEY_SIZE], ctr: &[u8; AES128_BLOCK_SIZE]) {
+        let reused = if self.nonce_guard.get()
+            && self.mode.get() == Mode::Ctr
+            && self.ctr_encrypting.get()
+        {
+            // FNV-1a, so that the keys themselves are not kept around.
+            let fingerprint = key
+                .iter()
+                .chain(ctr.iter())
+                .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+                    (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+                });
+            let mut used = self.used_nonces.get();
+            if used.iter().any(|entry| *entry == Some(fingerprint)) {
+                true
+            } else {
+                let next = self.used_nonces_next.get();
+                used[next] = Some(fingerprint);
+                self.used_nonces.set(used);
+                self.used_nonces_next.set((next + 1) % NONCE_GUARD_LEN);
+                false
+            }
+        } else {
+            false
+        };
+        self.nonce_reused.set(reused);
+    }
+
+    /// Count processed bytes, messages and blocks, and the time spent, in
+    /// `counter`, which should have `COUNTER_LEN` entries.
+    pub fn set_counter(&self, counter: &'static Counter<'static>) {
+        self.counter.set(counter);
+    }
+
+    /// Report when the AES engine is encrypting is active to `activity`, for energy
+    /// estimates.
+    pub fn set_activity(&self, activity: &'static dyn energy::Activity) {
+        self.activity.set(activity);
+    }
+
+    fn now(&self) -> u32 {
+        unsafe { crate::rtc::RTC.now() }
+    }
+
+    fn elapsed_since(&self, start: u32) -> u32 {
+        self.now().wrapping_sub(start) & unsafe { crate::rtc::RTC.max_tics() }
+    }
+
     fn set_dma(&self) {
         let regs = &*self.registers;
         unsafe {
@@ -155,12 +343,50 @@ impl<'a> AesECB<'a> {
         }
     }
 
+    fn set_block_dma(&self) {
+        let regs = &*self.registers;
+        unsafe {
+            regs.ecbdataptr.set(BLOCK_DATA.as_ptr() as u32);
+        }
+    }
+
+    /// If the peripheral is idle, start the next queued single block, or
+    /// otherwise the next block of the message.
+    fn run_next(&self) {
+        if self.running.get() != Running::Idle {
+            return;
+        }
+        if self.block_len.get() > 0 {
+            let mut queue = self.block_queue.get();
+            let head = self.block_head.get();
+            let request = queue[head].take();
+            self.block_queue.set(queue);
+            self.block_head.set((head + 1) % BLOCK_QUEUE_LEN);
+            self.block_len.set(self.block_len.get() - 1);
+
+            request.map(|request| {
+                unsafe {
+                    BLOCK_DATA[KEY_START..PLAINTEXT_START].copy_from_slice(&request.key);
+                    BLOCK_DATA[PLAINTEXT_START..PLAINTEXT_END].copy_from_slice(&request.block);
+                }
+                self.running.set(Running::Block);
+                self.set_block_dma();
+                self.start_ecb();
+            });
+        } else if self.message_pending.get() {
+            self.message_pending.set(false);
+            self.running.set(Running::Message);
+            self.set_dma();
+            self.crypt();
+        }
+    }
+
     // FIXME: should this be performed in constant time i.e. skip the break part
     // and always loop 16 times?
     fn update_ctr(&self) {
         for i in (PLAINTEXT_START..PLAINTEXT_END).rev() {
             unsafe {
-                ECB_DATA[i] += 1;
+                ECB_DATA[i] = ECB_DATA[i].wrapping_add(1);
                 if ECB_DATA[i] != 0 {
                     break;
                 }
@@ -168,11 +394,51 @@ impl<'a> AesECB<'a> {
         }
     }
 
+    /// Number of bytes in the block being processed.
+    fn block_len(&self) -> usize {
+        cmp::min(
+            AES128_BLOCK_SIZE,
+            self.end_idx.get().saturating_sub(self.current_idx.get()),
+        )
+    }
+
+    /// Copy the input of the block being processed into `block`.
+    fn input_block(&self, block: &mut [u8; AES128_BLOCK_SIZE]) {
+        let len = self.block_len();
+        let current_idx = self.current_idx.get();
+        let offset = current_idx - self.start_idx.get();
+        let copied = self.input.map(|input| {
+            block[..len].copy_from_slice(&input[offset..offset + len]);
+        });
+        if copied.is_none() {
+            self.output.map(|output| {
+                block[..len].copy_from_slice(&output[current_idx..current_idx + len]);
+            });
+        }
+    }
+
+    /// Prepare ECB_DATA for the block being processed and start encrypting.
     fn crypt(&self) {
+        if self.mode.get() == Mode::CbcEncrypt {
+            let mut block = [0; AES128_BLOCK_SIZE];
+            self.input_block(&mut block);
+            for (i, byte) in block.iter().enumerate() {
+                unsafe {
+                    ECB_DATA[PLAINTEXT_START + i] ^= *byte;
+                }
+            }
+        }
+        self.start_ecb();
+    }
+
+    fn start_ecb(&self) {
         let regs = &*self.registers;
 
         regs.event_endecb.write(Event::READY::CLEAR);
+        regs.event_errorecb.write(Event::READY::CLEAR);
         regs.task_startecb.set(1);
+        self.activity
+            .map(|activity| activity.set_active(energy::CRYPTO, true));
 
         self.enable_interrupts();
     }
@@ -183,60 +449,121 @@ impl<'a> AesECB<'a> {
 
         // disable interrupts
         self.disable_interrupts();
+        // Until the next block is started, if any
+        self.activity
+            .map(|activity| activity.set_active(energy::CRYPTO, false));
+
+        // The block was aborted, e.g. because the radio needed the AES
+        // hardware. ECB_DATA is unchanged, so encrypt the block again, unless
+        // it keeps failing.
+        if regs.event_errorecb.get() == 1 {
+            if self.retries.get() < MAX_RETRIES {
+                self.retries.set(self.retries.get() + 1);
+                self.start_ecb();
+            } else {
+                system_events::notify(system_events::Event::CryptoFault, 0);
+                self.fail();
+            }
+            return;
+        }
+        self.retries.set(0);
 
-        if regs.event_endecb.get() == 1 {
+        if regs.event_endecb.get() == 1 && self.running.get() == Running::Block {
+            let mut encrypted = [0; AES128_BLOCK_SIZE];
+            encrypted.copy_from_slice(unsafe { &BLOCK_DATA[CIPHERTEXT_START..CIPHERTEXT_END] });
+            // The key of a single block is not used again
+            unsafe {
+                BLOCK_DATA.iter_mut().for_each(|byte| *byte = 0);
+            }
+            self.running.set(Running::Idle);
+            self.counter
+                .map(|counter| counter.increment(COUNTER_BLOCKS));
+            self.block_client
+                .map(|client| client.encrypt_block_done(Ok(&encrypted)));
+            self.run_next();
+        } else if regs.event_endecb.get() == 1 {
             let current_idx = self.current_idx.get();
-            let end_idx = self.end_idx.get();
-
-            // Get the number of bytes to be used in the keystream/block
-            let take = match end_idx.checked_sub(current_idx) {
-                Some(v) if v > symmetric_encryption::AES128_BLOCK_SIZE => {
-                    symmetric_encryption::AES128_BLOCK_SIZE
+            let len = self.block_len();
+
+            let mut encrypted = [0; AES128_BLOCK_SIZE];
+            encrypted.copy_from_slice(unsafe { &ECB_DATA[CIPHERTEXT_START..CIPHERTEXT_END] });
+
+            let mut block = [0; AES128_BLOCK_SIZE];
+            match self.mode.get() {
+                Mode::Ctr => {
+                    // XOR the keystream with the input
+                    self.input_block(&mut block);
+                    for (byte, key) in block.iter_mut().zip(encrypted.iter()) {
+                        *byte ^= *key;
+                    }
+                    if len > 0 && !self.ctr_no_increment.get() {
+                        self.update_ctr();
+                    }
                 }
-                Some(v) => v,
-                None => 0,
-            };
-
-            let mut ks = self.keystream.get();
-
-            // Append keystream to the KEYSTREAM array
-            if take > 0 {
-                for i in current_idx..current_idx + take {
-                    ks[i] = unsafe { ECB_DATA[i - current_idx + PLAINTEXT_END] }
+                Mode::CbcEncrypt => {
+                    // The ciphertext is the IV of the next block
+                    block = encrypted;
+                    unsafe {
+                        ECB_DATA[PLAINTEXT_START..PLAINTEXT_END].copy_from_slice(&encrypted);
+                    }
                 }
-                self.current_idx.set(current_idx + take);
-                self.update_ctr();
+                // Rejected by `crypt()`
+                Mode::CbcDecrypt => {}
             }
+            self.output.map(|output| {
+                output[current_idx..current_idx + len].copy_from_slice(&block[..len]);
+            });
+            self.current_idx.set(current_idx + len);
 
+            self.running.set(Running::Idle);
             // More bytes to encrypt!!!
             if self.current_idx.get() < self.end_idx.get() {
-                self.crypt();
+                self.message_pending.set(true);
             }
-            // Entire keystream generated we are done!
-            // XOR keystream the input
-            else if self.input.is_some() && self.output.is_some() {
-                self.input.take().map(|slice| {
-                    self.output.take().map(|buf| {
-                        let start = self.start_idx.get();
-                        let end = self.end_idx.get();
-                        let len = end - start;
-
-                        for ((i, out), inp) in buf.as_mut()[start..end]
-                            .iter_mut()
-                            .enumerate()
-                            .zip(slice.as_ref()[0..len].iter())
-                        {
-                            *out = ks[i] ^ *inp;
-                        }
-
-                        self.client
-                            .map(move |client| client.crypt_done(Some(slice), buf));
-                    });
+            // Entire message processed, we are done!
+            else {
+                self.counter.map(|counter| {
+                    counter.add(
+                        COUNTER_BUSY_TICS,
+                        self.elapsed_since(self.message_start.get()),
+                    );
+                    counter.add(
+                        COUNTER_BYTES,
+                        (self.end_idx.get() - self.start_idx.get()) as u32,
+                    );
+                    counter.increment(COUNTER_MESSAGES);
+                });
+                let input = self.input.take();
+                self.output.take().map(|output| {
+                    self.client
+                        .map(move |client| client.crypt_done(input, output, Ok(())));
                 });
             }
+            self.run_next();
+        }
+    }
 
-            self.keystream.set(ks);
+    /// Give up on the aborted block, finishing its message or single block
+    /// with `HardwareFault`.
+    fn fail(&self) {
+        self.retries.set(0);
+        let running = self.running.get();
+        self.running.set(Running::Idle);
+        if running == Running::Block {
+            unsafe {
+                BLOCK_DATA.iter_mut().for_each(|byte| *byte = 0);
+            }
+            self.block_client
+                .map(|client| client.encrypt_block_done(Err(CryptoError::HardwareFault)));
+        } else if running == Running::Message {
+            let input = self.input.take();
+            self.output.take().map(|output| {
+                self.client.map(move |client| {
+                    client.crypt_done(input, output, Err(CryptoError::HardwareFault))
+                });
+            });
         }
+        self.run_next();
     }
 
     fn enable_interrupts(&self) {
@@ -267,80 +594,193 @@ impl<'a> kernel::hil::symmetric_encryption::AES128<'a> for AesECB<'a> {
         self.client.set(client);
     }
 
-    fn set_key(&self, key: &[u8]) -> ReturnCode {
-        if key.len() != symmetric_encryption::AES128_KEY_SIZE {
-            ReturnCode::EINVAL
+    fn set_key(&self, key: &[u8]) -> Result<(), CryptoError> {
+        if key.len() != AES128_KEY_SIZE {
+            Err(CryptoError::BadKeyLength)
         } else {
-            for (i, c) in key.iter().enumerate() {
-                unsafe {
-                    ECB_DATA[i] = *c;
-                }
-            }
-            ReturnCode::SUCCESS
+            let mut new_key = [0; AES128_KEY_SIZE];
+            new_key.copy_from_slice(key);
+            self.key.set(new_key);
+            Ok(())
         }
     }
 
-    fn set_iv(&self, iv: &[u8]) -> ReturnCode {
-        if iv.len() != symmetric_encryption::AES128_BLOCK_SIZE {
-            ReturnCode::EINVAL
+    fn set_iv(&self, iv: &[u8]) -> Result<(), CryptoError> {
+        if iv.len() != AES128_BLOCK_SIZE {
+            Err(CryptoError::InvalidArgument)
         } else {
-            for (i, c) in iv.iter().enumerate() {
-                unsafe {
-                    ECB_DATA[i + PLAINTEXT_START] = *c;
-                }
-            }
-            ReturnCode::SUCCESS
+            let mut new_iv = [0; AES128_BLOCK_SIZE];
+            new_iv.copy_from_slice(iv);
+            self.iv.set(new_iv);
+            Ok(())
         }
     }
 
-    // not needed by NRF5x
     fn start_message(&self) {
-        ()
+        if self.output.is_some() {
+            return;
+        }
+        self.check_nonce(&self.key.get(), &self.iv.get());
+        unsafe {
+            ECB_DATA[KEY_START..PLAINTEXT_START].copy_from_slice(&self.key.get());
+            ECB_DATA[PLAINTEXT_START..PLAINTEXT_END].copy_from_slice(&self.iv.get());
+        }
     }
 
-    // start_index and stop_index not used!!!
-    // assuming that
     fn crypt(
         &'a self,
         source: Option<&'a mut [u8]>,
         dest: &'a mut [u8],
         start_index: usize,
         stop_index: usize,
-    ) -> Option<(ReturnCode, Option<&'a mut [u8]>, &'a mut [u8])> {
-        match source {
-            None => Some((ReturnCode::EINVAL, source, dest)),
-            Some(src) => {
-                if stop_index - start_index <= MAX_LENGTH {
-                    // replace buffers
-                    self.input.replace(src);
-                    self.output.replace(dest);
-
-                    // configure buffer offsets
-                    self.current_idx.set(0);
-                    self.start_idx.set(start_index);
-                    self.end_idx.set(stop_index);
-
-                    // start crypt
-                    self.crypt();
-                    None
-                } else {
-                    Some((ReturnCode::ESIZE, Some(src), dest))
+    ) -> Option<(CryptoError, Option<&'a mut [u8]>, &'a mut [u8])> {
+        if self.output.is_some() {
+            return Some((CryptoError::EngineBusy, source, dest));
+        }
+        if self.nonce_reused.get() {
+            return Some((CryptoError::NonceReused, source, dest));
+        }
+        if start_index > stop_index || stop_index > dest.len() {
+            return Some((CryptoError::InvalidArgument, source, dest));
+        }
+        let len = stop_index - start_index;
+        if source.as_ref().map_or(false, |src| src.len() < len) {
+            return Some((CryptoError::InvalidArgument, source, dest));
+        }
+        match self.mode.get() {
+            // CTR mode also accepts a partial last block
+            Mode::Ctr => {}
+            Mode::CbcEncrypt => {
+                if len % AES128_BLOCK_SIZE != 0 {
+                    return Some((CryptoError::InvalidArgument, source, dest));
                 }
             }
+            Mode::CbcDecrypt => return Some((CryptoError::NotSupported, source, dest)),
         }
+
+        // replace buffers
+        self.input.put(source);
+        self.output.replace(dest);
+
+        // configure buffer offsets
+        self.current_idx.set(start_index);
+        self.start_idx.set(start_index);
+        self.end_idx.set(stop_index);
+
+        self.message_start.set(self.now());
+
+        // start crypt, after the single blocks already queued
+        self.message_pending.set(true);
+        self.run_next();
+        None
+    }
+}
+
+impl<'a> symmetric_encryption::AES128Block<'a> for AesECB<'a> {
+    fn set_block_client(&'a self, client: &'a dyn symmetric_encryption::BlockClient) {
+        self.block_client.set(client);
+    }
+
+    fn ecb_encrypt_block(
+        &self,
+        key: &[u8; AES128_KEY_SIZE],
+        block: &[u8; AES128_BLOCK_SIZE],
+    ) -> Result<(), CryptoError> {
+        let len = self.block_len.get();
+        if len == BLOCK_QUEUE_LEN {
+            return Err(CryptoError::EngineBusy);
+        }
+        let mut queue = self.block_queue.get();
+        queue[(self.block_head.get() + len) % BLOCK_QUEUE_LEN] = Some(BlockRequest {
+            key: *key,
+            block: *block,
+        });
+        self.block_queue.set(queue);
+        self.block_len.set(len + 1);
+        self.run_next();
+        Ok(())
     }
 }
 
 impl kernel::hil::symmetric_encryption::AES128Ctr for AesECB<'_> {
-    // not needed by NRF5x (the configuration is the same for encryption and decryption)
-    fn set_mode_aes128ctr(&self, _encrypting: bool) {
-        ()
+    // The configuration is the same for encryption and decryption, only the
+    // nonce guard tells them apart.
+    fn set_mode_aes128ctr(&self, encrypting: bool) {
+        self.mode.set(Mode::Ctr);
+        self.ctr_encrypting.set(encrypting);
+    }
+
+    fn set_ctr_no_increment(&self, no_increment: bool) -> Result<(), CryptoError> {
+        self.ctr_no_increment.set(no_increment);
+        Ok(())
+    }
+}
+
+impl kernel::hil::symmetric_encryption::AES128SaveRestore for AesECB<'_> {
+    // The counter or IV is kept in ECB_DATA and updated after every block, so
+    // it always holds the value for the next block.
+    fn save_context(
+        &self,
+        context: &mut symmetric_encryption::AES128Context,
+    ) -> Result<(), CryptoError> {
+        if self.output.is_some() {
+            return Err(CryptoError::EngineBusy);
+        }
+        unsafe {
+            context
+                .key
+                .copy_from_slice(&ECB_DATA[KEY_START..PLAINTEXT_START]);
+            context
+                .iv
+                .copy_from_slice(&ECB_DATA[PLAINTEXT_START..PLAINTEXT_END]);
+        }
+        Ok(())
+    }
+
+    fn restore_context(
+        &self,
+        context: &symmetric_encryption::AES128Context,
+    ) -> Result<(), CryptoError> {
+        if self.output.is_some() {
+            return Err(CryptoError::EngineBusy);
+        }
+        unsafe {
+            ECB_DATA[KEY_START..PLAINTEXT_START].copy_from_slice(&context.key);
+            ECB_DATA[PLAINTEXT_START..PLAINTEXT_END].copy_from_slice(&context.iv);
+        }
+        Ok(())
+    }
+}
+
+impl symmetric_encryption::ClearKeys for AesECB<'_> {
+    // ECB_DATA and BLOCK_DATA also hold the last keystream or ciphertext
+    // block, so they are wiped completely. The fingerprints of the nonce
+    // guard are kept: they do not reveal the keys, and the guard has to
+    // outlive the keys, which the clients set again for every message.
+    fn clear_keys(&self) -> Result<(), CryptoError> {
+        if self.running.get() != Running::Idle || self.output.is_some() || self.block_len.get() > 0
+        {
+            return Err(CryptoError::EngineBusy);
+        }
+        self.key.set([0; AES128_KEY_SIZE]);
+        self.iv.set([0; AES128_BLOCK_SIZE]);
+        unsafe {
+            ECB_DATA.iter_mut().for_each(|byte| *byte = 0);
+            BLOCK_DATA.iter_mut().for_each(|byte| *byte = 0);
+        }
+        Ok(())
     }
 }
 
 impl kernel::hil::symmetric_encryption::AES128CBC for AesECB<'_> {
-    fn set_mode_aes128cbc(&self, _encrypting: bool) {
-        ()
+    // Decryption needs the inverse cipher, which the ECB peripheral does not
+    // have, so `crypt()` rejects it.
+    fn set_mode_aes128cbc(&self, encrypting: bool) {
+        self.mode.set(if encrypting {
+            Mode::CbcEncrypt
+        } else {
+            Mode::CbcDecrypt
+        });
     }
 }
 //TODO: replace this placeholder with a proper implementation of the AES system
@@ -349,13 +789,13 @@ impl<'a> kernel::hil::symmetric_encryption::AES128CCM<'a> for AesECB<'a> {
     fn set_client(&'a self, _client: &'a dyn kernel::hil::symmetric_encryption::CCMClient) {}
 
     /// Set the key to be used for CCM encryption
-    fn set_key(&self, _key: &[u8]) -> ReturnCode {
-        ReturnCode::SUCCESS
+    fn set_key(&self, _key: &[u8]) -> Result<(), CryptoError> {
+        Ok(())
     }
 
     /// Set the nonce (length NONCE_LENGTH) to be used for CCM encryption
-    fn set_nonce(&self, _nonce: &[u8]) -> ReturnCode {
-        ReturnCode::SUCCESS
+    fn set_nonce(&self, _nonce: &[u8]) -> Result<(), CryptoError> {
+        Ok(())
     }
 
     /// Try to begin the encryption/decryption process
@@ -368,7 +808,7 @@ impl<'a> kernel::hil::symmetric_encryption::AES128CCM<'a> for AesECB<'a> {
         _mic_len: usize,
         _confidential: bool,
         _encrypting: bool,
-    ) -> (ReturnCode, Option<&'static mut [u8]>) {
-        (ReturnCode::SUCCESS, None)
+    ) -> Result<(), (CryptoError, &'static mut [u8])> {
+        Ok(())
     }
 }
@@ -12,6 +12,7 @@ use kernel::common::registers::{register_bitfields, ReadWrite};
 use kernel::common::StaticRef;
 use kernel::debug;
 use kernel::hil;
+use kernel::ReturnCode;
 
 #[cfg(feature = "nrf51")]
 const NUM_GPIOTE: usize = 4;
@@ -401,6 +402,29 @@ impl hil::gpio::Configure for GPIOPin {
         }
     }
 
+    fn set_drive_mode(&self, mode: hil::gpio::DriveMode) -> ReturnCode {
+        let gpio_regs = &*self.gpio_registers;
+        let drive = match mode {
+            hil::gpio::DriveMode::PushPull => PinConfig::DRIVE::S0S1,
+            hil::gpio::DriveMode::OpenDrain => PinConfig::DRIVE::S0D1,
+            hil::gpio::DriveMode::HighDrive => PinConfig::DRIVE::H0H1,
+        };
+        gpio_regs.pin_cnf[self.pin as usize].modify(drive);
+        ReturnCode::SUCCESS
+    }
+
+    fn drive_mode(&self) -> hil::gpio::DriveMode {
+        let gpio_regs = &*self.gpio_registers;
+        match gpio_regs.pin_cnf[self.pin as usize].read_as_enum(PinConfig::DRIVE) {
+            Some(PinConfig::DRIVE::Value::S0D1) | Some(PinConfig::DRIVE::Value::H0D1) => {
+                hil::gpio::DriveMode::OpenDrain
+            }
+            Some(PinConfig::DRIVE::Value::H0H1) => hil::gpio::DriveMode::HighDrive,
+            // The other modes are only set by chip-specific code
+            _ => hil::gpio::DriveMode::PushPull,
+        }
+    }
+
     fn make_output(&self) -> hil::gpio::Configuration {
         let gpio_regs = &*self.gpio_registers;
         gpio_regs.pin_cnf[self.pin as usize].modify(PinConfig::DIR::Output);
@@ -521,6 +545,41 @@ impl hil::gpio::Interrupt for GPIOPin {
 impl hil::gpio::InterruptPin for GPIOPin {}
 
 impl GPIOPin {
+    /// Drive the pin from a GPIOTE channel whose OUT task toggles it, so
+    /// that an event can toggle the pin through the PPI without the CPU.
+    /// Returns the address of the OUT task, or `None` if no channel is
+    /// free. The pin can no longer be set as a regular output until
+    /// `disable_toggle_task()` is called.
+    pub fn enable_toggle_task(&self) -> Option<usize> {
+        let channel = self.allocate_channel().ok()?;
+        let regs = &*self.gpiote_registers;
+        let pin: u32 = (GPIO_PER_PORT as u32 * self.port as u32) + self.pin as u32;
+        regs.config[channel].write(
+            Config::MODE::Task
+                + Config::PSEL.val(pin)
+                + Config::POLARITY::Toggle
+                + Config::OUTINIT::Low,
+        );
+        Some(&regs.task_out[channel] as *const _ as usize)
+    }
+
+    /// Toggle the pin from the CPU, through its OUT task.
+    pub fn trigger_toggle_task(&self) {
+        if let Ok(channel) = self.find_channel(self.pin) {
+            let regs = &*self.gpiote_registers;
+            regs.task_out[channel].write(TasksOut::TASK::Enable);
+        }
+    }
+
+    /// Release the GPIOTE channel of `enable_toggle_task()`.
+    pub fn disable_toggle_task(&self) {
+        if let Ok(channel) = self.find_channel(self.pin) {
+            let regs = &*self.gpiote_registers;
+            regs.config[channel]
+                .write(Config::MODE::CLEAR + Config::PSEL::CLEAR + Config::POLARITY::CLEAR);
+        }
+    }
+
     /// Allocate a GPIOTE channel
     /// If the channel couldn't be allocated return error instead
     fn allocate_channel(&self) -> Result<usize, ()> {
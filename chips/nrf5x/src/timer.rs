@@ -8,7 +8,8 @@
 //!
 //! This implementation provides a full-fledged Timer interface to
 //! timers 0 and 2, and exposes Timer1 as an HIL Alarm, for a Tock
-//! timer system. It may be that the Tock timer system should be ultimately
+//! timer system. Timer2 can also run as a 1MHz HIL Counter, for drivers
+//! that need to time short intervals precisely. It may be that the Tock timer system should be ultimately
 //! placed on top of the RTC (from the low frequency clock). It's currently
 //! implemented this way as a demonstration that it can be and because
 //! the full RTC/clock interface hasn't been finalized yet.
@@ -22,16 +23,21 @@
 //! * Philip Levis <pal@cs.stanford.edu>
 //! * Date: August 18, 2016
 
+use core::cell::Cell;
 use kernel::common::cells::OptionalCell;
 use kernel::common::registers::{self, register_bitfields, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
 use kernel::hil;
+use kernel::ReturnCode;
 
-const INSTANCES: [StaticRef<TimerRegisters>; 3] = unsafe {
+// TIMER3 and TIMER4 only exist on the nRF52
+const INSTANCES: [StaticRef<TimerRegisters>; 5] = unsafe {
     [
         StaticRef::new(0x40008000 as *const TimerRegisters),
         StaticRef::new(0x40009000 as *const TimerRegisters),
         StaticRef::new(0x4000A000 as *const TimerRegisters),
+        StaticRef::new(0x4001A000 as *const TimerRegisters),
+        StaticRef::new(0x4001B000 as *const TimerRegisters),
     ]
 };
 
@@ -205,6 +211,8 @@ pub enum BitmodeValue {
 pub static mut TIMER0: TimerAlarm = TimerAlarm::new(0);
 pub static mut ALARM1: TimerAlarm = TimerAlarm::new(1);
 pub static mut TIMER2: Timer = Timer::new(2);
+/// nRF52 only.
+pub static mut TIMER3: Timer = Timer::new(3);
 
 pub trait CompareClient {
     /// Passes a bitmask of which of the 4 compares/captures fired (0x0-0xf).
@@ -214,13 +222,20 @@ pub trait CompareClient {
 pub struct Timer {
     registers: StaticRef<TimerRegisters>,
     client: OptionalCell<&'static dyn CompareClient>,
+    running: Cell<bool>,
 }
 
+// When running as a Counter, CC3 is used to capture the value
+const COUNTER_CAPTURE: usize = 3;
+// 16MHz / 2^4
+const COUNTER_PRESCALER: u32 = 4;
+
 impl Timer {
     pub const fn new(instance: usize) -> Timer {
         Timer {
             registers: INSTANCES[instance],
             client: OptionalCell::empty(),
+            running: Cell::new(false),
         }
     }
 
@@ -256,6 +271,65 @@ impl Timer {
             client.compare(val as u8);
         });
     }
+
+    /// Start counting at 16 MHz / 2^`prescaler`, on 32 bits.
+    pub fn start_with_prescaler(&self, prescaler: u32) {
+        // Timer mode
+        self.registers.mode.set(0);
+        self.registers.bitmode.write(Bitmode::BITMODE::Bit32);
+        self.registers.prescaler.set(prescaler);
+        self.registers.tasks_start.write(Task::ENABLE::SET);
+        self.running.set(true);
+    }
+
+    /// Returns the current value of the timer, captured in `cc`.
+    pub fn capture(&self, cc: usize) -> u32 {
+        self.registers.tasks_capture[cc].write(Task::ENABLE::SET);
+        self.registers.cc[cc].get()
+    }
+
+    /// Generate the compare event `cc` when the timer reaches `value`, and
+    /// call the client back then.
+    pub fn set_compare(&self, cc: usize, value: u32) {
+        self.registers.events_compare[cc].write(Event::READY::CLEAR);
+        self.registers.cc[cc].write(CC::CC.val(value));
+        self.registers.intenset.set(1 << (16 + cc));
+    }
+
+    /// The address of the compare event `cc`, to connect it to a task
+    /// through the PPI.
+    pub fn compare_event_address(&self, cc: usize) -> usize {
+        &self.registers.events_compare[cc] as *const _ as usize
+    }
+}
+
+impl hil::time::Time for Timer {
+    type Frequency = hil::time::Freq1MHz;
+
+    fn now(&self) -> u32 {
+        self.capture(COUNTER_CAPTURE)
+    }
+
+    fn max_tics(&self) -> u32 {
+        core::u32::MAX
+    }
+}
+
+impl hil::time::Counter for Timer {
+    fn start(&self) -> ReturnCode {
+        self.start_with_prescaler(COUNTER_PRESCALER);
+        ReturnCode::SUCCESS
+    }
+
+    fn stop(&self) -> ReturnCode {
+        self.registers.tasks_stop.write(Task::ENABLE::SET);
+        self.running.set(false);
+        ReturnCode::SUCCESS
+    }
+
+    fn is_running(&self) -> bool {
+        self.running.get()
+    }
 }
 
 pub struct TimerAlarm<'a> {
@@ -16,8 +16,8 @@ use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOn
 use kernel::common::StaticRef;
 use kernel::debug;
 use kernel::hil;
+use kernel::hil::crypto::CryptoError;
 use kernel::hil::symmetric_encryption::{AES128_BLOCK_SIZE, AES128_KEY_SIZE};
-use kernel::ReturnCode;
 
 #[allow(dead_code)]
 #[derive(Copy, Clone)]
@@ -396,7 +396,7 @@ impl<'a> Aes<'a> {
 
                 // Alert the client of the completion
                 self.client.map(|client| {
-                    client.crypt_done(self.source.take(), self.dest.take().unwrap());
+                    client.crypt_done(self.source.take(), self.dest.take().unwrap(), Ok(()));
                 });
             }
         }
@@ -420,10 +420,10 @@ impl<'a> hil::symmetric_encryption::AES128<'a> for Aes<'a> {
         self.client.set(client);
     }
 
-    fn set_key(&self, key: &[u8]) -> ReturnCode {
+    fn set_key(&self, key: &[u8]) -> Result<(), CryptoError> {
         let regs: &AesRegisters = &*self.registers;
         if key.len() != AES128_KEY_SIZE {
-            return ReturnCode::EINVAL;
+            return Err(CryptoError::BadKeyLength);
         }
 
         for i in 0..4 {
@@ -440,13 +440,13 @@ impl<'a> hil::symmetric_encryption::AES128<'a> for Aes<'a> {
             }
         }
 
-        ReturnCode::SUCCESS
+        Ok(())
     }
 
-    fn set_iv(&self, iv: &[u8]) -> ReturnCode {
+    fn set_iv(&self, iv: &[u8]) -> Result<(), CryptoError> {
         let regs: &AesRegisters = &*self.registers;
         if iv.len() != AES128_BLOCK_SIZE {
-            return ReturnCode::EINVAL;
+            return Err(CryptoError::InvalidArgument);
         }
 
         // Set the initial value from the array.
@@ -464,7 +464,7 @@ impl<'a> hil::symmetric_encryption::AES128<'a> for Aes<'a> {
             }
         }
 
-        ReturnCode::SUCCESS
+        Ok(())
     }
 
     fn start_message(&self) {
@@ -482,9 +482,9 @@ impl<'a> hil::symmetric_encryption::AES128<'a> for Aes<'a> {
         dest: &'a mut [u8],
         start_index: usize,
         stop_index: usize,
-    ) -> Option<(ReturnCode, Option<&'a mut [u8]>, &'a mut [u8])> {
+    ) -> Option<(CryptoError, Option<&'a mut [u8]>, &'a mut [u8])> {
         if self.busy() {
-            Some((ReturnCode::EBUSY, source, dest))
+            Some((CryptoError::EngineBusy, source, dest))
         } else {
             self.source.put(source);
             self.dest.replace(dest);
@@ -493,7 +493,7 @@ impl<'a> hil::symmetric_encryption::AES128<'a> for Aes<'a> {
                 None
             } else {
                 Some((
-                    ReturnCode::EINVAL,
+                    CryptoError::InvalidArgument,
                     self.source.take(),
                     self.dest.take().unwrap(),
                 ))
@@ -514,4 +514,24 @@ impl hil::symmetric_encryption::AES128CBC for Aes<'_> {
     }
 }
 
+impl hil::symmetric_encryption::ClearKeys for Aes<'_> {
+    // The key registers are write-only, so they are overwritten with a zero
+    // key.
+    fn clear_keys(&self) -> Result<(), CryptoError> {
+        if self.busy() {
+            return Err(CryptoError::EngineBusy);
+        }
+        let regs: &AesRegisters = &*self.registers;
+        regs.key0.set(0);
+        regs.key1.set(0);
+        regs.key2.set(0);
+        regs.key3.set(0);
+        regs.initvect0.set(0);
+        regs.initvect1.set(0);
+        regs.initvect2.set(0);
+        regs.initvect3.set(0);
+        Ok(())
+    }
+}
+
 pub static mut AES: Aes<'static> = Aes::new();
@@ -58,6 +58,11 @@ pub unsafe trait MainLoopCapability {}
 /// memory, for example by creating grants.
 pub unsafe trait MemoryAllocationCapability {}
 
+/// The `BootModeCapability` allows the holder to select the mode of the next
+/// boot, for example to keep the chip in its bootloader or to start the
+/// kernel without processes.
+pub unsafe trait BootModeCapability {}
+
 /// The `UdpDriverCapability` capability allows the holder to use
 /// two functions only allowed by the UDP driver.
 /// The first `driver_send_to()` function in udp_send.rs, which does
@@ -0,0 +1,220 @@
+//! Fixed-capacity FIFO queue with interior mutability.
+//!
+//! `BoundedQueue` stores its elements in a caller-provided slice of
+//! `OptionalCell`s, so it needs no allocation and can be shared by `&`
+//! reference like the rest of a capsule's state. Unlike `RingBuffer`, every
+//! slot of the backing storage is usable.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! # use kernel::common::bounded_queue::BoundedQueue;
+//! # use kernel::common::cells::OptionalCell;
+//!
+//! let slots = [OptionalCell::empty(), OptionalCell::empty()];
+//! let queue = BoundedQueue::new(&slots);
+//!
+//! assert!(queue.enqueue(1));
+//! assert!(queue.enqueue(2));
+//! assert!(!queue.enqueue(3));
+//!
+//! assert_eq!(queue.dequeue(), Some(1));
+//! assert_eq!(queue.dequeue(), Some(2));
+//! assert_eq!(queue.dequeue(), None);
+//! ```
+
+use core::cell::Cell;
+
+use crate::common::cells::OptionalCell;
+
+pub struct BoundedQueue<'a, T: Copy> {
+    slots: &'a [OptionalCell<T>],
+    head: Cell<usize>,
+    len: Cell<usize>,
+}
+
+impl<'a, T: Copy> BoundedQueue<'a, T> {
+    /// Create a queue using `slots` as backing storage. The capacity of the
+    /// queue is `slots.len()`. Any values already stored in `slots` are
+    /// discarded.
+    pub fn new(slots: &'a [OptionalCell<T>]) -> BoundedQueue<'a, T> {
+        for slot in slots.iter() {
+            slot.clear();
+        }
+        BoundedQueue {
+            slots: slots,
+            head: Cell::new(0),
+            len: Cell::new(0),
+        }
+    }
+
+    /// Returns the maximum number of elements the queue can hold.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns how many elements are in the queue.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Returns true if there are no elements in the queue.
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    /// Returns true if the queue is full.
+    pub fn is_full(&self) -> bool {
+        self.len.get() == self.slots.len()
+    }
+
+    /// Add an element to the back of the queue. Returns whether the element
+    /// was added, i.e. `false` if the queue was full.
+    pub fn enqueue(&self, val: T) -> bool {
+        if self.is_full() {
+            false
+        } else {
+            let tail = (self.head.get() + self.len.get()) % self.slots.len();
+            self.slots[tail].set(val);
+            self.len.set(self.len.get() + 1);
+            true
+        }
+    }
+
+    /// Remove the element at the front of the queue.
+    pub fn dequeue(&self) -> Option<T> {
+        if self.is_empty() {
+            None
+        } else {
+            let head = self.head.get();
+            self.head.set((head + 1) % self.slots.len());
+            self.len.set(self.len.get() - 1);
+            self.slots[head].take()
+        }
+    }
+
+    /// Returns the element at the front of the queue without removing it.
+    pub fn peek(&self) -> Option<T> {
+        if self.is_empty() {
+            None
+        } else {
+            self.slots[self.head.get()].map(|val| *val)
+        }
+    }
+
+    /// Remove all elements from the queue.
+    pub fn empty(&self) {
+        for slot in self.slots.iter() {
+            slot.clear();
+        }
+        self.head.set(0);
+        self.len.set(0);
+    }
+
+    /// Retains only the elements that satisfy the predicate, preserving their
+    /// order.
+    pub fn retain<F>(&self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let cap = self.slots.len();
+        let head = self.head.get();
+        let mut kept = 0;
+
+        for i in 0..self.len.get() {
+            let src = (head + i) % cap;
+            if let Some(val) = self.slots[src].take() {
+                if f(&val) {
+                    self.slots[(head + kept) % cap].set(val);
+                    kept += 1;
+                }
+            }
+        }
+
+        self.len.set(kept);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BoundedQueue;
+    use crate::common::cells::OptionalCell;
+
+    #[test]
+    fn test_enqueue_dequeue() {
+        let slots = [OptionalCell::empty(), OptionalCell::empty()];
+        let queue = BoundedQueue::new(&slots);
+
+        for i in 0..10 {
+            assert!(queue.enqueue(i));
+            assert_eq!(queue.len(), 1);
+            assert_eq!(queue.peek(), Some(i));
+            assert_eq!(queue.dequeue(), Some(i));
+            assert!(queue.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_full_capacity() {
+        let slots = [
+            OptionalCell::empty(),
+            OptionalCell::empty(),
+            OptionalCell::empty(),
+        ];
+        let queue = BoundedQueue::new(&slots);
+        assert_eq!(queue.capacity(), 3);
+
+        // Offset the head so that the queue wraps around.
+        assert!(queue.enqueue(0));
+        assert_eq!(queue.dequeue(), Some(0));
+
+        for i in 1..4 {
+            assert!(queue.enqueue(i));
+        }
+        assert!(queue.is_full());
+        assert!(!queue.enqueue(4));
+
+        for i in 1..4 {
+            assert_eq!(queue.dequeue(), Some(i));
+        }
+        assert_eq!(queue.dequeue(), None);
+    }
+
+    #[test]
+    fn test_retain() {
+        let slots = [
+            OptionalCell::empty(),
+            OptionalCell::empty(),
+            OptionalCell::empty(),
+            OptionalCell::empty(),
+        ];
+        let queue = BoundedQueue::new(&slots);
+
+        assert!(queue.enqueue(0));
+        assert!(queue.enqueue(0));
+        queue.dequeue();
+        queue.dequeue();
+        for i in 1..5 {
+            assert!(queue.enqueue(i));
+        }
+
+        queue.retain(|x| x % 2 == 0);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.dequeue(), Some(2));
+        assert_eq!(queue.dequeue(), Some(4));
+        assert_eq!(queue.dequeue(), None);
+    }
+
+    #[test]
+    fn test_empty() {
+        let slots = [OptionalCell::empty(), OptionalCell::empty()];
+        let queue = BoundedQueue::new(&slots);
+
+        assert!(queue.enqueue(1));
+        queue.empty();
+        assert!(queue.is_empty());
+        assert_eq!(queue.peek(), None);
+        assert!(slots[0].is_none());
+    }
+}
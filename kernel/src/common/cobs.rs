@@ -0,0 +1,120 @@
+//! Consistent Overhead Byte Stuffing (COBS).
+//!
+//! COBS removes the zero bytes from a frame, at a cost of at most one byte
+//! per 254 bytes, so that a zero byte can delimit frames on a byte stream
+//! such as a UART. A receiver that starts listening in the middle of a frame
+//! resynchronizes at the next zero byte.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//! use kernel::common::cobs;
+//!
+//! let mut frame = [0; cobs::max_encoded_len(3)];
+//! let len = cobs::encode([0x11, 0x00, 0x22].iter().cloned(), &mut frame);
+//! assert_eq!(&frame[..len], &[0x02, 0x11, 0x02, 0x22, 0x00]);
+//! ```
+
+/// Number of bytes of the longest encoding of `len` bytes, including the
+/// final zero byte.
+pub const fn max_encoded_len(len: usize) -> usize {
+    len + len / 254 + 2
+}
+
+/// Number of bytes that can always be encoded in a buffer of `encoded_len`
+/// bytes.
+pub fn max_payload_len(encoded_len: usize) -> usize {
+    encoded_len
+        .saturating_sub(2)
+        .saturating_sub(encoded_len.saturating_sub(1) / 255)
+}
+
+/// Encode the bytes of `src` into `dst`, followed by a zero byte, and return
+/// the number of bytes written.
+///
+/// Panics if `dst` is shorter than `max_encoded_len()` of the number of
+/// bytes in `src`.
+pub fn encode<I: Iterator<Item = u8>>(src: I, dst: &mut [u8]) -> usize {
+    // Each block starts with a code byte, the distance to the next zero,
+    // which is only known at the end of the block.
+    let mut code_index = 0;
+    let mut index = 1;
+    let mut code = 1;
+
+    for byte in src {
+        if byte != 0 {
+            dst[index] = byte;
+            index += 1;
+            code += 1;
+        }
+        if byte == 0 || code == 0xff {
+            dst[code_index] = code;
+            code_index = index;
+            index += 1;
+            code = 1;
+        }
+    }
+
+    dst[code_index] = code;
+    dst[index] = 0;
+    index + 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_encodes(src: &[u8], expected: &[u8]) {
+        let mut dst = [0xaa; 600];
+        let len = encode(src.iter().cloned(), &mut dst);
+        assert_eq!(&dst[..len], expected);
+        assert!(len <= max_encoded_len(src.len()));
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_encodes(&[], &[0x01, 0x00]);
+        assert_encodes(&[0x00], &[0x01, 0x01, 0x00]);
+        assert_encodes(&[0x00, 0x00], &[0x01, 0x01, 0x01, 0x00]);
+        assert_encodes(
+            &[0x11, 0x22, 0x00, 0x33],
+            &[0x03, 0x11, 0x22, 0x02, 0x33, 0x00],
+        );
+        assert_encodes(
+            &[0x11, 0x00, 0x00, 0x00],
+            &[0x02, 0x11, 0x01, 0x01, 0x01, 0x00],
+        );
+    }
+
+    #[test]
+    fn test_encode_long_blocks() {
+        let mut src = [0; 300];
+        for (i, byte) in src.iter_mut().enumerate() {
+            *byte = (i % 255 + 1) as u8;
+        }
+
+        let mut expected = [0; 303];
+        expected[0] = 0xff;
+        expected[1..255].copy_from_slice(&src[..254]);
+        expected[255] = 47;
+        expected[256..302].copy_from_slice(&src[254..]);
+        assert_encodes(&src, &expected);
+
+        // A block of 254 bytes at the end is followed by an empty block
+        let mut expected = [0; 257];
+        expected[0] = 0xff;
+        expected[1..255].copy_from_slice(&src[..254]);
+        expected[255] = 0x01;
+        assert_encodes(&src[..254], &expected);
+    }
+
+    #[test]
+    fn test_max_payload_len() {
+        for encoded_len in 0..1200 {
+            let len = max_payload_len(encoded_len);
+            assert!(max_encoded_len(len) <= encoded_len || len == 0);
+            assert!(max_encoded_len(len + 1) > encoded_len);
+        }
+    }
+}
@@ -32,6 +32,13 @@
 //! assert_eq!((buffer[0], buffer[1]), ('a', 'b'));
 //!
 //!  ```
+//!
+//! `ReadOnlyLeasableBuffer` offers the same slicing over a shared `&[T]`. It is
+//! used to pass source data that the lower layer only needs to read, such as
+//! data stored in flash, without requiring a mutable static buffer. Only the
+//! digest HIL takes it for now: the AES HIL hands the source buffer back to
+//! its client as mutable, and the flash HIL writes whole pages of its own
+//! buffer type.
 
 use core::ops::{Bound, Range, RangeBounds};
 use core::ops::{Index, IndexMut};
@@ -87,25 +94,35 @@ impl<'a, T> LeasableBuffer<'a, T> {
     /// buffer, but wishes to send a 250 byte packet, the upper layer should slice the
     /// LeasableBuffer down to its first 250 bytes before passing it down.
     pub fn slice<R: RangeBounds<usize>>(&mut self, range: R) {
-        let start = match range.start_bound() {
-            Bound::Included(s) => *s,
-            Bound::Excluded(s) => *s + 1,
-            Bound::Unbounded => 0,
-        };
-
-        let end = match range.end_bound() {
-            Bound::Included(e) => *e + 1,
-            Bound::Excluded(e) => *e,
-            Bound::Unbounded => self.internal.len(),
-        };
-
-        let new_start = self.active_range.start + start;
-        let new_end = new_start + (end - start);
-
-        self.active_range = Range {
-            start: new_start,
-            end: new_end,
-        };
+        self.active_range = narrow_range(&self.active_range, self.internal.len(), range);
+    }
+}
+
+/// Compute the new active range when slicing `range` out of `active_range`,
+/// where `len` is the length of the underlying buffer.
+fn narrow_range<R: RangeBounds<usize>>(
+    active_range: &Range<usize>,
+    len: usize,
+    range: R,
+) -> Range<usize> {
+    let start = match range.start_bound() {
+        Bound::Included(s) => *s,
+        Bound::Excluded(s) => *s + 1,
+        Bound::Unbounded => 0,
+    };
+
+    let end = match range.end_bound() {
+        Bound::Included(e) => *e + 1,
+        Bound::Excluded(e) => *e,
+        Bound::Unbounded => len,
+    };
+
+    let new_start = active_range.start + start;
+    let new_end = new_start + (end - start);
+
+    Range {
+        start: new_start,
+        end: new_end,
     }
 }
 
@@ -128,3 +145,67 @@ where
         &mut self.internal[self.active_range.clone()][idx]
     }
 }
+
+/// Read-only counterpart of `LeasableBuffer`, which can be used to pass a
+/// section of a larger shared buffer but still get the entire buffer back in a
+/// callback
+pub struct ReadOnlyLeasableBuffer<'a, T> {
+    internal: &'a [T],
+    active_range: Range<usize>,
+}
+
+impl<'a, T> ReadOnlyLeasableBuffer<'a, T> {
+    /// Create a leasable buffer from a passed reference to a raw buffer
+    pub fn new(buffer: &'a [T]) -> Self {
+        let len = buffer.len();
+        ReadOnlyLeasableBuffer {
+            internal: buffer,
+            active_range: 0..len,
+        }
+    }
+
+    /// Retrieve the raw buffer used to create the ReadOnlyLeasableBuffer.
+    /// Consumes the ReadOnlyLeasableBuffer.
+    pub fn take(self) -> &'a [T] {
+        self.internal
+    }
+
+    /// Resets the ReadOnlyLeasableBuffer to its full size, making the entire
+    /// buffer accessible again.
+    pub fn reset(&mut self) {
+        self.active_range = 0..self.internal.len();
+    }
+
+    fn active_slice(&self) -> &[T] {
+        &self.internal[self.active_range.clone()]
+    }
+
+    /// Returns the length of the currently accessible portion of the
+    /// ReadOnlyLeasableBuffer
+    pub fn len(&self) -> usize {
+        self.active_slice().len()
+    }
+
+    /// Returns a pointer to the currently accessible portion of the
+    /// ReadOnlyLeasableBuffer
+    pub fn as_ptr(&self) -> *const T {
+        self.active_slice().as_ptr()
+    }
+
+    /// Reduces the range of the ReadOnlyLeasableBuffer that is accessible.
+    /// See `LeasableBuffer::slice()`.
+    pub fn slice<R: RangeBounds<usize>>(&mut self, range: R) {
+        self.active_range = narrow_range(&self.active_range, self.internal.len(), range);
+    }
+}
+
+impl<'a, T, I> Index<I> for ReadOnlyLeasableBuffer<'a, T>
+where
+    I: SliceIndex<[T]>,
+{
+    type Output = <I as SliceIndex<[T]>>::Output;
+
+    fn index(&self, idx: I) -> &Self::Output {
+        &self.internal[self.active_range.clone()][idx]
+    }
+}
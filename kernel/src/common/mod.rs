@@ -15,6 +15,8 @@ pub mod registers {
     pub use tock_registers::{register_bitfields, register_structs};
 }
 
+pub mod bounded_queue;
+pub mod cobs;
 pub mod deferred_call;
 pub mod dynamic_deferred_call;
 pub mod leasable_buffer;
@@ -23,6 +25,7 @@ pub mod math;
 pub mod peripherals;
 pub mod queue;
 pub mod ring_buffer;
+pub mod slot_map;
 pub mod utils;
 
 mod static_ref;
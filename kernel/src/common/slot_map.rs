@@ -0,0 +1,182 @@
+//! Fixed-capacity table of values addressed by slot index.
+//!
+//! `SlotMap` hands out the lowest free index of a caller-provided slice of
+//! `OptionalCell`s when a value is inserted. The index can then be used as a
+//! handle to look the value up or release the slot again. This is useful for
+//! allocating a small pool of hardware resources (e.g. channels) or for
+//! tracking outstanding requests without an allocator.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! # use kernel::common::slot_map::SlotMap;
+//! # use kernel::common::cells::OptionalCell;
+//!
+//! let slots = [OptionalCell::empty(), OptionalCell::empty()];
+//! let map = SlotMap::new(&slots);
+//!
+//! let a = map.insert('a').unwrap();
+//! let b = map.insert('b').unwrap();
+//! assert_eq!(map.insert('c'), None);
+//!
+//! assert_eq!(map.remove(a), Some('a'));
+//! assert_eq!(map.get(b), Some('b'));
+//! assert_eq!(map.insert('c'), Some(a));
+//! ```
+
+use crate::common::cells::OptionalCell;
+
+pub struct SlotMap<'a, T: Copy> {
+    slots: &'a [OptionalCell<T>],
+}
+
+impl<'a, T: Copy> SlotMap<'a, T> {
+    /// Create a map using `slots` as backing storage. Slots which already
+    /// contain a value are considered in use.
+    pub const fn new(slots: &'a [OptionalCell<T>]) -> SlotMap<'a, T> {
+        SlotMap { slots: slots }
+    }
+
+    /// Returns the number of slots in the map.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the number of slots currently in use.
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    /// Returns true if no slot is in use.
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(|slot| slot.is_none())
+    }
+
+    /// Returns true if every slot is in use.
+    pub fn is_full(&self) -> bool {
+        self.slots.iter().all(|slot| slot.is_some())
+    }
+
+    /// Store `val` in the lowest free slot and return its index, or `None` if
+    /// every slot is in use.
+    pub fn insert(&self, val: T) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| slot.is_none())
+            .map(|index| {
+                self.slots[index].set(val);
+                index
+            })
+    }
+
+    /// Returns a copy of the value in slot `index`, if any.
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.slots.get(index).and_then(|slot| slot.map(|val| *val))
+    }
+
+    /// Overwrite the value in slot `index`, returning the previous value.
+    /// Returns `None` without storing anything if `index` is out of range.
+    pub fn replace(&self, index: usize, val: T) -> Option<T> {
+        self.slots.get(index).and_then(|slot| slot.replace(val))
+    }
+
+    /// Free slot `index`, returning the value it held.
+    pub fn remove(&self, index: usize) -> Option<T> {
+        self.slots.get(index).and_then(|slot| slot.take())
+    }
+
+    /// Returns the index of the first used slot whose value satisfies the
+    /// predicate.
+    pub fn find<F>(&self, mut f: F) -> Option<usize>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.slots
+            .iter()
+            .position(|slot| slot.map_or(false, |val| f(val)))
+    }
+
+    /// Call a closure on the index and value of every used slot.
+    pub fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(usize, T),
+    {
+        for (index, slot) in self.slots.iter().enumerate() {
+            slot.map(|val| f(index, *val));
+        }
+    }
+
+    /// Free every slot.
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SlotMap;
+    use crate::common::cells::OptionalCell;
+
+    #[test]
+    fn test_insert_remove() {
+        let slots = [
+            OptionalCell::empty(),
+            OptionalCell::empty(),
+            OptionalCell::empty(),
+        ];
+        let map = SlotMap::new(&slots);
+        assert!(map.is_empty());
+
+        assert_eq!(map.insert(10), Some(0));
+        assert_eq!(map.insert(11), Some(1));
+        assert_eq!(map.insert(12), Some(2));
+        assert!(map.is_full());
+        assert_eq!(map.insert(13), None);
+
+        assert_eq!(map.remove(1), Some(11));
+        assert_eq!(map.remove(1), None);
+        assert_eq!(map.len(), 2);
+
+        // The lowest free slot is reused first.
+        assert_eq!(map.insert(14), Some(1));
+        assert_eq!(map.get(1), Some(14));
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let slots = [OptionalCell::empty()];
+        let map = SlotMap::new(&slots);
+
+        assert_eq!(map.get(5), None);
+        assert_eq!(map.remove(5), None);
+        assert_eq!(map.replace(5, 1), None);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_find_for_each() {
+        let slots = [
+            OptionalCell::empty(),
+            OptionalCell::empty(),
+            OptionalCell::empty(),
+        ];
+        let map = SlotMap::new(&slots);
+        map.insert(3);
+        map.insert(6);
+        map.insert(9);
+        map.remove(0);
+
+        assert_eq!(map.find(|v| *v == 9), Some(2));
+        assert_eq!(map.find(|v| *v == 3), None);
+
+        let mut sum = 0;
+        map.for_each(|index, val| sum += index * val);
+        assert_eq!(sum, 6 + 18);
+
+        map.clear();
+        assert!(map.is_empty());
+    }
+}
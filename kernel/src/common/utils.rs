@@ -70,6 +70,27 @@ macro_rules! storage_volume {
     };
 }
 
+/// Allocates a static buffer that DMA engines restricted to data RAM can use.
+///
+/// Some DMA engines, such as Nordic's EasyDMA, can only read from and write to
+/// data RAM and fail silently when handed a pointer into flash. Buffers
+/// declared with this macro are placed in a section called `.dma_buffers`,
+/// which the linker script kernel_layout.ld keeps in RAM and brackets with
+/// the `_sdma_buffers` and `_edma_buffers` symbols.
+///
+/// The section is zeroed at boot like `.bss`, so the initializer must be all
+/// zeros.
+///
+/// `dma_buffer!(static mut SAMPLE: [u16; 1] = [0; 1]);`
+#[macro_export]
+macro_rules! dma_buffer {
+    ($(#[$attr:meta])* $vis:vis static mut $N:ident: $T:ty = $init:expr;) => {
+        $(#[$attr])*
+        #[link_section = ".dma_buffers"]
+        $vis static mut $N: $T = $init;
+    };
+}
+
 /// Create an object with the given capability.
 ///
 /// ```ignore
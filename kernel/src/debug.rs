@@ -51,11 +51,13 @@
 
 use core::cell::Cell;
 use core::fmt::{write, Arguments, Result, Write};
+use core::iter;
 use core::panic::PanicInfo;
 use core::str;
 
 use crate::common::cells::NumericCellExt;
 use crate::common::cells::{MapCell, TakeCell};
+use crate::common::cobs;
 use crate::common::queue::Queue;
 use crate::common::ring_buffer::RingBuffer;
 use crate::hil;
@@ -349,8 +351,14 @@ pub struct DebugWriter {
     internal_buffer: TakeCell<'static, RingBuffer<'static, u8>>,
     // Number of debug!() calls.
     count: Cell<usize>,
+    // Whether the output is sent in COBS frames on `DEBUG_CHANNEL`.
+    framing: Cell<bool>,
 }
 
+/// Channel of the kernel debug output when the console output is framed, see
+/// `DebugWriter::set_framing()`. Processes use the channels from 1.
+pub const DEBUG_CHANNEL: u8 = 0;
+
 /// Static variable that holds the kernel's reference to the debug tool. This is
 /// needed so the debug!() macros have a reference to the object to use.
 static mut DEBUG_WRITER: Option<&'static mut DebugWriterWrapper> = None;
@@ -385,9 +393,17 @@ impl DebugWriter {
             output_buffer: TakeCell::new(out_buffer),
             internal_buffer: TakeCell::new(internal_buffer),
             count: Cell::new(0), // how many debug! calls
+            framing: Cell::new(false),
         }
     }
 
+    /// Send the debug output in COBS frames, each starting with
+    /// `DEBUG_CHANNEL`, so that a host can separate it from the output of
+    /// processes when they share a UART. The console must be framed too.
+    pub fn set_framing(&self, framing: bool) {
+        self.framing.set(framing);
+    }
+
     fn increment_count(&self) {
         self.count.increment();
     }
@@ -405,14 +421,22 @@ impl DebugWriter {
             if let Some(out_buffer) = self.output_buffer.take() {
                 let mut count = 0;
 
-                for dst in out_buffer.iter_mut() {
-                    match ring_buffer.dequeue() {
-                        Some(src) => {
-                            *dst = src;
-                            count += 1;
-                        }
-                        None => {
-                            break;
+                if self.framing.get() {
+                    if ring_buffer.has_elements() {
+                        let payload_len = cobs::max_payload_len(out_buffer.len()).saturating_sub(1);
+                        let payload = iter::from_fn(|| ring_buffer.dequeue()).take(payload_len);
+                        count = cobs::encode(iter::once(DEBUG_CHANNEL).chain(payload), out_buffer);
+                    }
+                } else {
+                    for dst in out_buffer.iter_mut() {
+                        match ring_buffer.dequeue() {
+                            Some(src) => {
+                                *dst = src;
+                                count += 1;
+                            }
+                            None => {
+                                break;
+                            }
                         }
                     }
                 }
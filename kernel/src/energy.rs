@@ -0,0 +1,230 @@
+//! Coarse estimates of the energy used by each subsystem.
+//!
+//! The kernel and the chip drivers report when a subsystem, such as the CPU,
+//! the radio or a UART, becomes active or idle to an `Activity`. The
+//! `EnergyMeter` accumulates the time each subsystem spends active and
+//! multiplies it by the current the board gives for that subsystem. This
+//! attributes battery drain to subsystems without external instrumentation,
+//! but it is only as good as the currents of the board: it ignores sleep
+//! currents, clock sources and the actual load of each peripheral.
+//!
+//! The estimates are kept in two `metrics::Counter`s of `SUBSYSTEMS`
+//! entries, keyed by subsystem, so that once registered with the kernel they
+//! can be read from the process console and the metrics driver:
+//!
+//! * the active time of each subsystem, in milliseconds,
+//! * the energy used by each subsystem, in millijoules.
+//!
+//! The meter accumulates time whenever a subsystem changes state, and the
+//! CPU changes state every time the chip sleeps or wakes up. The kernel must
+//! therefore wake up at least once per period of the timer used by the meter
+//! (512 seconds for the 24-bit RTC of the nRF5x chips), for example for a
+//! periodic alarm.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust,ignore
+//! let energy_time = components::counter_component_helper!("energy_ms", kernel::energy::SUBSYSTEMS);
+//! let energy = components::counter_component_helper!("energy_mj", kernel::energy::SUBSYSTEMS);
+//! board_kernel.register_counter(energy_time);
+//! board_kernel.register_counter(energy);
+//! let energy_meter = static_init!(
+//!     kernel::energy::EnergyMeter<'static, nrf52::rtc::Rtc>,
+//!     kernel::energy::EnergyMeter::new(&nrf52::rtc::RTC, 3000, CURRENTS_UA, energy_time, energy)
+//! );
+//! energy_meter.start();
+//! board_kernel.set_activity(energy_meter);
+//! ```
+
+use core::cell::Cell;
+
+use crate::hil::time::{Frequency, Time};
+use crate::metrics::Counter;
+
+/// The processor, reported by the kernel when the chip sleeps and wakes up.
+pub const CPU: usize = 0;
+/// The radio.
+pub const RADIO: usize = 1;
+/// Cryptographic accelerators, such as the AES engine or the CryptoCell.
+pub const CRYPTO: usize = 2;
+/// UARTs.
+pub const UART: usize = 3;
+/// SPI masters.
+pub const SPI: usize = 4;
+/// The number of subsystems, and of entries of the counters.
+pub const SUBSYSTEMS: usize = 5;
+
+/// Receives the changes of activity of the subsystems.
+pub trait Activity {
+    /// Record that `subsystem` is now active or idle. Reporting the state a
+    /// subsystem is already in has no effect.
+    fn set_active(&self, subsystem: usize, active: bool);
+}
+
+pub struct EnergyMeter<'a, T: Time> {
+    time: &'a T,
+    /// Supply voltage, in millivolts.
+    voltage_mv: u32,
+    /// Current drawn by each subsystem when active, in microamps.
+    currents_ua: [u32; SUBSYSTEMS],
+    /// Bit `i` is set when subsystem `i` is active.
+    active: Cell<u32>,
+    /// The time at which the active times were last updated.
+    last: Cell<u32>,
+    /// Active time of each subsystem, in tics of `time`.
+    tics: [Cell<u64>; SUBSYSTEMS],
+    active_ms: &'a Counter<'a>,
+    energy_mj: &'a Counter<'a>,
+}
+
+impl<'a, T: Time> EnergyMeter<'a, T> {
+    pub fn new(
+        time: &'a T,
+        voltage_mv: u32,
+        currents_ua: [u32; SUBSYSTEMS],
+        active_ms: &'a Counter<'a>,
+        energy_mj: &'a Counter<'a>,
+    ) -> EnergyMeter<'a, T> {
+        EnergyMeter {
+            time: time,
+            voltage_mv: voltage_mv,
+            currents_ua: currents_ua,
+            active: Cell::new(0),
+            last: Cell::new(0),
+            tics: Default::default(),
+            active_ms: active_ms,
+            energy_mj: energy_mj,
+        }
+    }
+
+    /// Start measuring, with the CPU active.
+    pub fn start(&self) {
+        self.last.set(self.time.now());
+        self.active.set(1 << CPU);
+    }
+
+    /// Add the time elapsed since the last update to the subsystems that are
+    /// active, and update their counters.
+    pub fn update(&self) {
+        let now = self.time.now();
+        let elapsed = now.wrapping_sub(self.last.get()) & self.time.max_tics();
+        self.last.set(now);
+        if elapsed == 0 {
+            return;
+        }
+        for (subsystem, tics) in self.tics.iter().enumerate() {
+            if self.active.get() & (1 << subsystem) != 0 {
+                tics.set(tics.get() + elapsed as u64);
+                self.publish(subsystem);
+            }
+        }
+    }
+
+    /// Returns the time `subsystem` was active, in milliseconds.
+    pub fn active_ms(&self, subsystem: usize) -> u64 {
+        let tics = self.tics.get(subsystem).map_or(0, |tics| tics.get());
+        let freq = T::Frequency::frequency() as u64;
+        // Split the conversion so that the product cannot overflow
+        tics / freq * 1000 + tics % freq * 1000 / freq
+    }
+
+    /// Returns the energy used by `subsystem`, in microjoules.
+    pub fn energy_uj(&self, subsystem: usize) -> u64 {
+        let current_ua = self.currents_ua.get(subsystem).map_or(0, |c| *c) as u64;
+        // µA × mV × ms is a picojoule
+        self.active_ms(subsystem) * current_ua * self.voltage_mv as u64 / 1_000_000
+    }
+
+    fn publish(&self, subsystem: usize) {
+        let saturate = |value: u64| value.min(u32::max_value() as u64) as u32;
+        self.active_ms
+            .set(subsystem, saturate(self.active_ms(subsystem)));
+        self.energy_mj
+            .set(subsystem, saturate(self.energy_uj(subsystem) / 1000));
+    }
+}
+
+impl<'a, T: Time> Activity for EnergyMeter<'a, T> {
+    fn set_active(&self, subsystem: usize, active: bool) {
+        if subsystem >= SUBSYSTEMS {
+            return;
+        }
+        self.update();
+        let bit = 1 << subsystem;
+        if active {
+            self.active.set(self.active.get() | bit);
+        } else {
+            self.active.set(self.active.get() & !bit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hil::time::Freq32KHz;
+
+    struct FakeTime {
+        now: Cell<u32>,
+    }
+
+    impl Time for FakeTime {
+        type Frequency = Freq32KHz;
+
+        fn now(&self) -> u32 {
+            self.now.get()
+        }
+
+        fn max_tics(&self) -> u32 {
+            (1 << 24) - 1
+        }
+    }
+
+    impl FakeTime {
+        fn advance(&self, tics: u32) {
+            self.now
+                .set(self.now.get().wrapping_add(tics) & self.max_tics());
+        }
+    }
+
+    #[test]
+    fn test_energy() {
+        let time = FakeTime {
+            now: Cell::new(0xffff00),
+        };
+        let active_counts: [Cell<u32>; SUBSYSTEMS] = Default::default();
+        let energy_counts: [Cell<u32>; SUBSYSTEMS] = Default::default();
+        let active = Counter::new("energy_ms", &active_counts);
+        let energy = Counter::new("energy_mj", &energy_counts);
+        // 3 mA for the CPU and 10 mA for the radio, at 3 V
+        let mut currents = [0; SUBSYSTEMS];
+        currents[CPU] = 3000;
+        currents[RADIO] = 10_000;
+        let meter = EnergyMeter::new(&time, 3000, currents, &active, &energy);
+        meter.start();
+
+        // The radio is on for 2 s, the CPU sleeps after the first second and
+        // across the wrap of the counter
+        meter.set_active(RADIO, true);
+        time.advance(32768);
+        meter.set_active(CPU, false);
+        time.advance(32768);
+        meter.set_active(RADIO, false);
+        time.advance(32768 * 5);
+        meter.set_active(CPU, true);
+
+        assert_eq!(active.get(CPU), Some(1000));
+        assert_eq!(active.get(RADIO), Some(2000));
+        assert_eq!(energy.get(CPU), Some(9));
+        assert_eq!(energy.get(RADIO), Some(60));
+        assert_eq!(active.get(UART), Some(0));
+
+        // Active times are only added up to the last update
+        time.advance(16384);
+        assert_eq!(active.get(CPU), Some(1000));
+        meter.update();
+        assert_eq!(active.get(CPU), Some(1500));
+        assert_eq!(meter.energy_uj(CPU), 13500);
+    }
+}
@@ -6,8 +6,10 @@ use core::ops::{Deref, DerefMut};
 use core::ptr::{write, NonNull};
 
 use crate::callback::AppId;
+use crate::journal;
 use crate::process::{Error, ProcessType};
 use crate::sched::Kernel;
+use crate::system_events;
 
 /// Region of process memory reserved for the kernel.
 pub struct Grant<T: Default> {
@@ -223,7 +225,18 @@ impl<T: Default> Grant<T> {
                             // Note: This allocation is intentionally never
                             // freed.  A grant region is valid once allocated
                             // for the lifetime of the process.
-                            let new_region = allocator.alloc_unowned(T::default())?;
+                            let new_region =
+                                allocator.alloc_unowned(T::default()).map_err(|err| {
+                                    journal::record(
+                                        journal::Event::GrantExhausted,
+                                        self.grant_num as u32,
+                                    );
+                                    system_events::notify(
+                                        system_events::Event::LowMemory,
+                                        self.grant_num,
+                                    );
+                                    err
+                                })?;
 
                             // Update the grant pointer in the process. Again,
                             // since the process struct does not know about the
@@ -45,6 +45,23 @@ pub trait Client {
     fn sample_ready(&self, sample: u16);
 }
 
+/// Interface for ADCs that compare their samples against limits in hardware,
+/// so that a signal can be watched without handling every sample.
+pub trait AdcLimits: Adc {
+    /// Sample `channel` continuously until a sample is below `low` or above
+    /// `high`, then stop and call the limit client. The limits are
+    /// left-justified like the samples. `stop_sampling()` stops without a
+    /// callback.
+    fn monitor(&self, channel: &Self::Channel, low: u16, high: u16) -> ReturnCode;
+}
+
+/// Trait for handling callbacks from `AdcLimits::monitor()`.
+pub trait LimitClient {
+    /// Called when a sample crossed a limit: the high limit if `above` is
+    /// true, the low limit otherwise.
+    fn limit_reached(&self, above: bool);
+}
+
 // *** Interfaces for high-speed, buffered ADC sampling ***
 
 /// Interface for continuously sampling at a given frequency on a channel.
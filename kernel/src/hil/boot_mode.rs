@@ -0,0 +1,44 @@
+//! Interface for passing a boot mode across a reset.
+//!
+//! Chips keep the requested mode in a register or memory that survives a
+//! soft reset. The mode is read once, early at boot, and cleared, so that a
+//! request only applies to the next boot.
+//!
+//! Selecting a mode requires the `BootModeCapability`, so that a board
+//! decides which capsules, and through them which processes, may do it.
+
+use crate::capabilities::BootModeCapability;
+
+/// The modes a chip can be asked to boot in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BootMode {
+    Normal = 0,
+    /// Stay in the bootloader to receive a firmware update.
+    Dfu = 1,
+    /// Boot the kernel without starting any process.
+    SafeMode = 2,
+    /// Boot for a factory test. Processes can read the mode to decide to run
+    /// their tests.
+    FactoryTest = 3,
+}
+
+impl BootMode {
+    pub fn from_usize(value: usize) -> Option<BootMode> {
+        match value {
+            0 => Some(BootMode::Normal),
+            1 => Some(BootMode::Dfu),
+            2 => Some(BootMode::SafeMode),
+            3 => Some(BootMode::FactoryTest),
+            _ => None,
+        }
+    }
+}
+
+pub trait BootModeControl {
+    /// The mode the chip booted in.
+    fn boot_mode(&self) -> BootMode;
+
+    /// Select the mode of the next boot. It takes effect after the next soft
+    /// reset, which the caller has to trigger.
+    fn set_next_boot_mode(&self, mode: BootMode, capability: &dyn BootModeCapability);
+}
@@ -0,0 +1,42 @@
+//! Errors shared by the cryptographic interfaces: digests, AES and AEAD.
+
+use crate::returncode::ReturnCode;
+
+/// Why a cryptographic operation could not be started or did not complete.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CryptoError {
+    /// The engine is in the middle of another operation. Try again once it
+    /// has called back.
+    EngineBusy,
+    /// The engine does not support keys of this length.
+    BadKeyLength,
+    /// An argument other than the key is invalid, e.g. the length of a nonce
+    /// or an IV, or offsets that are outside of the buffer.
+    InvalidArgument,
+    /// The engine does not support this mode, algorithm or length.
+    NotSupported,
+    /// The nonce, IV or counter was already used with this key, which would
+    /// void the confidentiality of both messages.
+    NonceReused,
+    /// The message does not match its authentication tag.
+    AuthenticationFailed,
+    /// The engine reported an error, or is not in a state where it can run.
+    HardwareFault,
+    /// The operation was stopped before it completed.
+    Cancelled,
+}
+
+impl From<CryptoError> for ReturnCode {
+    fn from(err: CryptoError) -> ReturnCode {
+        match err {
+            CryptoError::EngineBusy => ReturnCode::EBUSY,
+            CryptoError::BadKeyLength => ReturnCode::ESIZE,
+            CryptoError::InvalidArgument => ReturnCode::EINVAL,
+            CryptoError::NotSupported => ReturnCode::ENOSUPPORT,
+            CryptoError::NonceReused => ReturnCode::EALREADY,
+            CryptoError::AuthenticationFailed => ReturnCode::FAIL,
+            CryptoError::HardwareFault => ReturnCode::FAIL,
+            CryptoError::Cancelled => ReturnCode::ECANCEL,
+        }
+    }
+}
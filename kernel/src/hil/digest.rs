@@ -1,13 +1,94 @@
 //! Interface for Digest
 
-use crate::common::leasable_buffer::LeasableBuffer;
-use crate::returncode::ReturnCode;
+use crate::common::leasable_buffer::{LeasableBuffer, ReadOnlyLeasableBuffer};
+use crate::hil::crypto::CryptoError;
 
 /// The 'types' of digests, this should define the output size of the digest
 /// operations.
 pub trait DigestType: Eq + Copy + Clone + Sized + AsRef<[u8]> + AsMut<[u8]> {}
 
+/// SHA-256
 impl DigestType for [u8; 32] {}
+/// SHA-224
+impl DigestType for [u8; 28] {}
+
+// Arrays longer than 32 bytes do not implement `Eq` or `AsRef`, so the
+// longer digests are wrapped.
+macro_rules! wide_digest {
+    ($(#[$attr:meta])* $name:ident, $len:expr) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy)]
+        pub struct $name(pub [u8; $len]);
+
+        impl Default for $name {
+            fn default() -> $name {
+                $name([0; $len])
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &$name) -> bool {
+                self.0[..] == other.0[..]
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl AsMut<[u8]> for $name {
+            fn as_mut(&mut self) -> &mut [u8] {
+                &mut self.0
+            }
+        }
+
+        impl DigestType for $name {}
+    };
+}
+
+wide_digest!(
+    /// SHA-512
+    Sha512Digest,
+    64
+);
+wide_digest!(
+    /// SHA-384
+    Sha384Digest,
+    48
+);
+
+/// Buffers whose data is added to a digest in order by
+/// `DigestChain::add_data_chain()`, for example the header, payload and
+/// trailer of a packet. Empty slots are skipped.
+pub type DataChain = [Option<LeasableBuffer<'static, u8>>];
+
+/// The buffers of the operations stopped by `Digest::cancel()`, which will
+/// not be returned through callbacks.
+pub struct Cancelled<T: 'static> {
+    /// The buffer passed to `add_data()`.
+    pub data: Option<&'static mut [u8]>,
+    /// The buffer passed to `add_readonly_data()`.
+    pub readonly_data: Option<&'static [u8]>,
+    /// The buffer passed to `run()`, or to `DigestVerify::verify()`.
+    pub digest: Option<&'static mut T>,
+    /// The chain passed to `DigestChain::add_data_chain()`.
+    pub chain: Option<&'static mut DataChain>,
+}
+
+impl<T: 'static> Default for Cancelled<T> {
+    fn default() -> Cancelled<T> {
+        Cancelled {
+            data: None,
+            readonly_data: None,
+            digest: None,
+            chain: None,
+        }
+    }
+}
 
 /// Implement this trait and use `set_client()` in order to receive callbacks.
 pub trait Client<'a, T: DigestType> {
@@ -15,12 +96,46 @@ pub trait Client<'a, T: DigestType> {
     /// engine.
     /// On error or success `data` will contain a reference to the original
     /// data supplied to `add_data()`.
-    fn add_data_done(&'a self, result: Result<(), ReturnCode>, data: &'static mut [u8]);
+    fn add_data_done(&'a self, result: Result<(), CryptoError>, data: &'static mut [u8]);
+
+    /// This callback is called when the data passed to `add_readonly_data()`
+    /// has been added to the digest engine.
+    /// On error or success `data` will contain a reference to the original
+    /// data supplied to `add_readonly_data()`.
+    fn add_readonly_data_done(&'a self, result: Result<(), CryptoError>, data: &'static [u8]);
 
     /// This callback is called when a digest is computed.
     /// On error or success `digest` will contain a reference to the original
     /// data supplied to `run()`.
-    fn hash_done(&'a self, result: Result<(), ReturnCode>, digest: &'static mut T);
+    fn hash_done(&'a self, result: Result<(), CryptoError>, digest: &'static mut T);
+}
+
+/// Implement this trait and use `set_chain_client()` in order to receive
+/// the result of `add_data_chain()`.
+pub trait ClientChain<'a> {
+    /// This callback is called when the data of every buffer of the chain
+    /// has been added to the digest engine, or when adding one of them
+    /// failed, in which case the data of the following buffers was not
+    /// added.
+    /// On error or success `chain` will contain a reference to the chain
+    /// supplied to `add_data_chain()`, where each slot holds the whole
+    /// buffer it was leased from again.
+    fn add_data_chain_done(
+        &'a self,
+        result: Result<(), CryptoError>,
+        chain: &'static mut DataChain,
+    );
+}
+
+/// Implement this trait and use `set_verify_client()` in order to receive
+/// the result of `verify()`.
+pub trait ClientVerify<'a, T: DigestType> {
+    /// This callback is called when the digest computed by `verify()` has
+    /// been compared with the expected value.
+    /// `result` is `Ok(true)` if they match and `Ok(false)` if they don't.
+    /// On error or success `compare` will contain a reference to the
+    /// unmodified data supplied to `verify()`.
+    fn verification_done(&'a self, result: Result<bool, CryptoError>, compare: &'static mut T);
 }
 
 /// Computes a digest (cryptographic hash) over data
@@ -41,7 +156,15 @@ pub trait Digest<'a, T: DigestType> {
     fn add_data(
         &self,
         data: LeasableBuffer<'static, u8>,
-    ) -> Result<usize, (ReturnCode, &'static mut [u8])>;
+    ) -> Result<usize, (CryptoError, &'static mut [u8])>;
+
+    /// Add data that the digest engine only needs to read, for example data
+    /// stored in flash. This behaves like `add_data()`, but completion is
+    /// signalled with the `add_readonly_data_done()` callback.
+    fn add_readonly_data(
+        &self,
+        data: ReadOnlyLeasableBuffer<'static, u8>,
+    ) -> Result<usize, (CryptoError, &'static [u8])>;
 
     /// Request the hardware block to generate a Digest and stores the returned
     /// digest in the memory location specified.
@@ -55,18 +178,127 @@ pub trait Digest<'a, T: DigestType> {
     /// implementation should try to use a default option. In the case where
     /// there is only one digest supported this should be used. If there is no
     /// suitable or obvious default option, the implementation can return an
-    /// error with error code ENOSUPPORT.
-    fn run(&'a self, digest: &'static mut T) -> Result<(), (ReturnCode, &'static mut T)>;
+    /// error with `NotSupported`.
+    ///
+    /// Implementations must not call `hash_done()` (or any other client
+    /// callback) from within `run()`, `add_data()` or `add_readonly_data()`;
+    /// completion is signalled later, from an interrupt or deferred call.
+    fn run(&'a self, digest: &'static mut T) -> Result<(), (CryptoError, &'static mut T)>;
 
     /// Clear the keys and any other sensitive data.
     /// This won't clear the buffers provided to this API, that is up to the
     /// user to clear.
     fn clear_data(&self);
+
+    /// Stop the operations in progress, reset the engine and clear the keys
+    /// and any other sensitive data, as `clear_data()` does. The buffers of
+    /// the stopped operations are returned, and no callback will be made for
+    /// them. This lets a client recover its buffers when the owner of a
+    /// hash, such as a process, goes away in the middle of it.
+    fn cancel(&self) -> Cancelled<T>;
+}
+
+/// Adds the data of several buffers to a digest with a single request, so
+/// that a client can hash data that is split across buffers without
+/// concatenating it into one.
+pub trait DigestChain<'a> {
+    /// Set the client instance which will receive `add_data_chain_done()`
+    /// callbacks.
+    fn set_chain_client(&'a self, client: &'a dyn ClientChain<'a>);
+
+    /// Add the data of the buffers of `chain` in order, as successive calls
+    /// to `Digest::add_data()` would. Completion is signalled with the
+    /// `add_data_chain_done()` callback, under the same rules as
+    /// `add_data_done()`.
+    /// A chain without data returns `InvalidArgument`.
+    /// On error the return value will contain a return code and the original data
+    fn add_data_chain(
+        &self,
+        chain: &'static mut DataChain,
+    ) -> Result<(), (CryptoError, &'static mut DataChain)>;
+}
+
+/// Computes a digest and compares it with an expected value, without ever
+/// handing the digest to the client. The comparison takes the same time
+/// whatever the position of the first differing byte, so a client that
+/// reports the result to an untrusted party, such as a userspace app
+/// checking a MAC, does not leak how much of a forged value was correct.
+pub trait DigestVerify<'a, T: DigestType> {
+    /// Set the client instance which will receive `verification_done()`
+    /// callbacks.
+    fn set_verify_client(&'a self, client: &'a dyn ClientVerify<'a, T>);
+
+    /// Compute the digest of the data added, like `Digest::run()`, and
+    /// compare it with `compare`. The result is signalled with the
+    /// `verification_done()` callback, under the same rules as
+    /// `hash_done()`.
+    /// On error the return value will contain a return code and the original data
+    fn verify(&'a self, compare: &'static mut T) -> Result<(), (CryptoError, &'static mut T)>;
+}
+
+/// The intermediate state of a hash or HMAC: the chaining value, the number
+/// of bytes hashed so far, the bytes of the incomplete block and, for an
+/// HMAC, the key padded with zeros to a block. The sizes are those of
+/// SHA-256, which are large enough for the smaller digests.
+#[derive(Clone, Copy)]
+pub struct DigestContext {
+    pub state: [u32; 8],
+    pub length: u64,
+    pub pending: [u8; 64],
+    pub pending_len: usize,
+    /// Whether the hash is an HMAC with `key`, which may be all zeros.
+    pub hmac: bool,
+    pub key: [u8; 64],
+}
+
+impl Default for DigestContext {
+    fn default() -> DigestContext {
+        DigestContext {
+            state: [0; 8],
+            length: 0,
+            pending: [0; 64],
+            pending_len: 0,
+            hmac: false,
+            key: [0; 64],
+        }
+    }
+}
+
+/// Suspend and resume hashes, so that the data of several clients can be
+/// interleaved on a single digest engine.
+pub trait DigestSaveRestore {
+    /// Copy the state of the current hash into `context`.
+    /// Returns `EngineBusy` if an operation is in progress, and
+    /// `NotSupported` if the engine cannot read its state back.
+    fn save_context(&self, context: &mut DigestContext) -> Result<(), CryptoError>;
+
+    /// Load a hash saved with `save_context()`. The next call to
+    /// `Digest::add_data()` or `Digest::run()` continues the hash where it
+    /// was suspended, in the same mode.
+    /// Returns `EngineBusy` if an operation is in progress, and
+    /// `NotSupported` if the engine cannot load a state.
+    fn restore_context(&self, context: &DigestContext) -> Result<(), CryptoError>;
 }
 
 pub trait HMACSha256 {
     /// Call before `Digest::run()` to perform HMACSha256
     ///
-    /// The key used for the HMAC is passed to this function.
-    fn set_mode_hmacsha256(&self, key: &[u8; 32]) -> Result<(), ReturnCode>;
+    /// The key used for the HMAC is passed to this function. It can have
+    /// any length: as in RFC 2104, the implementation hashes keys longer
+    /// than the 64-byte block of SHA-256, and pads shorter keys with zeros.
+    /// Implementations return `BadKeyLength` for key lengths their engine
+    /// cannot use.
+    fn set_mode_hmacsha256(&self, key: &[u8]) -> Result<(), CryptoError>;
+}
+
+pub trait Sha256 {
+    /// Call before `Digest::add_data()` to compute a plain SHA-256 hash,
+    /// whatever mode the engine was left in.
+    fn set_mode_sha256(&self) -> Result<(), CryptoError>;
+}
+
+pub trait Sha224 {
+    /// Call before `Digest::add_data()` to compute a SHA-224 hash, whose
+    /// 28-byte digest is produced by `Digest<'a, [u8; 28]>::run()`.
+    fn set_mode_sha224(&self) -> Result<(), CryptoError>;
 }
@@ -215,3 +215,31 @@ pub trait Client8 {
         error: ReturnCode,
     ) -> Continue;
 }
+
+/// The sources of the samples given to an
+/// [Accumulator](trait.Accumulator.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Source {
+    /// A true random number generator.
+    Trng = 0,
+    /// The received signal strength measured by a radio.
+    RadioNoise = 1,
+    /// The least significant bits of ADC samples.
+    AdcNoise = 2,
+    /// The time at which the samples of the other sources arrive.
+    TimerJitter = 3,
+}
+
+/// Accumulates samples of sources of unknown or varying quality, such as
+/// radio or ADC noise, and mixes them with a cryptographic hash function
+/// into entropy.
+///
+/// Samples carry less than 1 bit of entropy per bit, and a source may
+/// degrade to none at all; implementations must stay secure as long as one
+/// of the sources works.
+pub trait Accumulator {
+    /// Add a sample from `source`. Implementations must not call back any
+    /// client from within this call, so that drivers can add samples from
+    /// their interrupt handlers.
+    fn add_sample(&self, source: Source, sample: u32);
+}
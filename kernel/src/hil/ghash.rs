@@ -0,0 +1,67 @@
+//! Interface for GHASH, the keyed universal hash of GCM.
+//!
+//! GHASH multiplies each 16-byte block of the data, added to the running
+//! value, by the subkey H in GF(2^128). Besides computing GCM tags, it is a
+//! fast universal hash for protocols that only need one, for example to
+//! authenticate data with a one-time subkey.
+//!
+//! The data of each `update()` is padded with zeros to a whole number of
+//! blocks, as GCM pads the additional data and the ciphertext. A message
+//! split over several updates therefore gives the same hash as in one
+//! update only if all the updates but the last are a multiple of
+//! `GHASH_BLOCK_SIZE` long.
+//!
+//! A hash is computed with:
+//!
+//! 1. `set_subkey()`, once per subkey,
+//! 2. `init()`, which clears the running value,
+//! 3. `update()` for each piece of the data, waiting for `update_done()`
+//!    before the next one,
+//! 4. `finalize()`, which returns the hash in `finalize_done()` and clears
+//!    the running value for the next hash.
+
+use crate::common::leasable_buffer::LeasableBuffer;
+use crate::hil::crypto::CryptoError;
+
+/// The size of a GHASH block, of the subkey and of the hash.
+pub const GHASH_BLOCK_SIZE: usize = 16;
+
+pub trait Client {
+    /// The data passed to `update()` has been hashed, or not if `result` is
+    /// an error.
+    fn update_done(&self, result: Result<(), CryptoError>, data: &'static mut [u8]);
+
+    /// The hash of the data since `init()` is in `hash`.
+    fn finalize_done(
+        &self,
+        result: Result<(), CryptoError>,
+        hash: &'static mut [u8; GHASH_BLOCK_SIZE],
+    );
+}
+
+pub trait Ghash<'a> {
+    /// Set the client to be used for callbacks.
+    fn set_client(&'a self, client: &'a dyn Client);
+
+    /// Set the subkey H. Returns `EngineBusy` during an update or a
+    /// finalize.
+    fn set_subkey(&self, subkey: &[u8; GHASH_BLOCK_SIZE]) -> Result<(), CryptoError>;
+
+    /// Start a new hash with the current subkey. Returns `EngineBusy` during
+    /// an update or a finalize.
+    fn init(&self) -> Result<(), CryptoError>;
+
+    /// Hash the active slice of `data`, padded with zeros to a whole number
+    /// of blocks. On success, `update_done()` is called with the buffer.
+    fn update(
+        &self,
+        data: LeasableBuffer<'static, u8>,
+    ) -> Result<(), (CryptoError, &'static mut [u8])>;
+
+    /// Write the hash of the data since `init()` into `hash`. On success,
+    /// `finalize_done()` is called with the buffer.
+    fn finalize(
+        &self,
+        hash: &'static mut [u8; GHASH_BLOCK_SIZE],
+    ) -> Result<(), (CryptoError, &'static mut [u8; GHASH_BLOCK_SIZE])>;
+}
@@ -11,8 +11,21 @@ pub enum FloatingState {
     PullNone,
 }
 
+/// Enum for configuring how an output pin drives its signal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DriveMode {
+    /// Drive the pin both high and low, with the standard strength.
+    PushPull,
+    /// Drive the pin low, and leave it floating when it is set. Used for
+    /// wired-AND buses, such as I2C or 1-Wire, with an external pull-up.
+    OpenDrain,
+    /// Drive the pin both high and low, with more current than
+    /// `PushPull`. Used for loads such as LEDs.
+    HighDrive,
+}
+
 /// Enum for selecting which edge to trigger interrupts on.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum InterruptEdge {
     RisingEdge,
     FallingEdge,
@@ -101,6 +114,20 @@ pub trait Configure {
     /// Return the current floating state of the pin.
     fn floating_state(&self) -> FloatingState;
 
+    /// Set how the pin drives its signal when it is an output. Returns
+    /// `ENOSUPPORT` if the pin cannot be driven in `mode`. All pins
+    /// support `DriveMode::PushPull`, which is also their default.
+    fn set_drive_mode(&self, mode: DriveMode) -> ReturnCode {
+        match mode {
+            DriveMode::PushPull => ReturnCode::SUCCESS,
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+    /// Return how the pin drives its signal when it is an output.
+    fn drive_mode(&self) -> DriveMode {
+        DriveMode::PushPull
+    }
+
     /// Return whether the pin is an input (reading from
     /// the Input trait will return valid results). Returns
     /// true if the pin is in Configuration::Input or
@@ -254,13 +281,13 @@ pub trait ClientWithValue {
 /// Standard implementation of InterruptWithValue: handles an
 /// `gpio::Client::fired` and passes it up as a
 /// `gpio::ClientWithValue::fired`.
-pub struct InterruptValueWrapper<'a, IP: InterruptPin> {
+pub struct InterruptValueWrapper<'a, IP: InterruptPin + ?Sized> {
     value: Cell<u32>,
     client: OptionalCell<&'static dyn ClientWithValue>,
     source: &'a IP,
 }
 
-impl<'a, IP: InterruptPin> InterruptValueWrapper<'a, IP> {
+impl<'a, IP: InterruptPin + ?Sized> InterruptValueWrapper<'a, IP> {
     pub fn new(pin: &'a IP) -> Self {
         Self {
             value: Cell::new(0),
@@ -275,7 +302,7 @@ impl<'a, IP: InterruptPin> InterruptValueWrapper<'a, IP> {
     }
 }
 
-impl<IP: InterruptPin> InterruptWithValue for InterruptValueWrapper<'_, IP> {
+impl<IP: InterruptPin + ?Sized> InterruptWithValue for InterruptValueWrapper<'_, IP> {
     fn set_value(&self, value: u32) {
         self.value.set(value);
     }
@@ -302,13 +329,13 @@ impl<IP: InterruptPin> InterruptWithValue for InterruptValueWrapper<'_, IP> {
     }
 }
 
-impl<IP: InterruptPin> Input for InterruptValueWrapper<'_, IP> {
+impl<IP: InterruptPin + ?Sized> Input for InterruptValueWrapper<'_, IP> {
     fn read(&self) -> bool {
         self.source.read()
     }
 }
 
-impl<IP: InterruptPin> Configure for InterruptValueWrapper<'_, IP> {
+impl<IP: InterruptPin + ?Sized> Configure for InterruptValueWrapper<'_, IP> {
     fn configuration(&self) -> Configuration {
         self.source.configuration()
     }
@@ -341,6 +368,14 @@ impl<IP: InterruptPin> Configure for InterruptValueWrapper<'_, IP> {
         self.source.floating_state()
     }
 
+    fn set_drive_mode(&self, mode: DriveMode) -> ReturnCode {
+        self.source.set_drive_mode(mode)
+    }
+
+    fn drive_mode(&self) -> DriveMode {
+        self.source.drive_mode()
+    }
+
     fn is_input(&self) -> bool {
         self.source.is_input()
     }
@@ -350,7 +385,7 @@ impl<IP: InterruptPin> Configure for InterruptValueWrapper<'_, IP> {
     }
 }
 
-impl<IP: InterruptPin> Output for InterruptValueWrapper<'_, IP> {
+impl<IP: InterruptPin + ?Sized> Output for InterruptValueWrapper<'_, IP> {
     fn set(&self) {
         self.source.set();
     }
@@ -364,10 +399,10 @@ impl<IP: InterruptPin> Output for InterruptValueWrapper<'_, IP> {
     }
 }
 
-impl<IP: InterruptPin> InterruptValuePin for InterruptValueWrapper<'_, IP> {}
-impl<IP: InterruptPin> Pin for InterruptValueWrapper<'_, IP> {}
+impl<IP: InterruptPin + ?Sized> InterruptValuePin for InterruptValueWrapper<'_, IP> {}
+impl<IP: InterruptPin + ?Sized> Pin for InterruptValueWrapper<'_, IP> {}
 
-impl<IP: InterruptPin> Client for InterruptValueWrapper<'_, IP> {
+impl<IP: InterruptPin + ?Sized> Client for InterruptValueWrapper<'_, IP> {
     fn fired(&self) {
         self.client.map(|c| c.fired(self.value()));
     }
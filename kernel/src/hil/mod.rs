@@ -3,18 +3,22 @@
 pub mod adc;
 pub mod analog_comparator;
 pub mod ble_advertising;
+pub mod boot_mode;
 pub mod crc;
+pub mod crypto;
 pub mod dac;
 pub mod digest;
 pub mod eic;
 pub mod entropy;
 pub mod flash;
+pub mod ghash;
 pub mod gpio;
 pub mod gpio_async;
 pub mod i2c;
 pub mod led;
 pub mod log;
 pub mod nonvolatile_storage;
+pub mod power;
 pub mod pwm;
 pub mod radio;
 pub mod rng;
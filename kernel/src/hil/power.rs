@@ -0,0 +1,52 @@
+//! Interfaces for battery fuel gauges and chargers.
+
+use crate::returncode::ReturnCode;
+
+/// Charging state of a battery, as reported by a charger.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChargingStatus {
+    /// No external power is present, the system runs from the battery.
+    Discharging = 0,
+    /// External power is present and the battery is being charged.
+    Charging = 1,
+    /// External power is present but the battery is not being charged,
+    /// typically because charging has completed.
+    Charged = 2,
+}
+
+/// A basic interface for a battery fuel gauge.
+pub trait BatteryGauge {
+    fn set_client(&self, client: &'static dyn BatteryGaugeClient);
+
+    /// Start a state-of-charge reading. The result is passed to
+    /// `BatteryGaugeClient::state_of_charge`.
+    fn read_state_of_charge(&self) -> ReturnCode;
+
+    /// Start a battery voltage reading. The result is passed to
+    /// `BatteryGaugeClient::voltage`.
+    fn read_voltage(&self) -> ReturnCode;
+}
+
+/// Client for receiving fuel gauge readings.
+pub trait BatteryGaugeClient {
+    /// Called when a state-of-charge reading has completed.
+    ///
+    /// - `soc`: the remaining battery capacity in hundredths of percent.
+    fn state_of_charge(&self, soc: usize);
+
+    /// Called when a voltage reading has completed.
+    ///
+    /// - `voltage`: the battery voltage in millivolts.
+    fn voltage(&self, voltage: usize);
+
+    /// Called instead of the above when a reading could not be completed,
+    /// e.g. because the gauge did not answer on its bus.
+    fn reading_failed(&self, error: ReturnCode);
+}
+
+/// A basic interface for a battery charger which reports its status
+/// synchronously, e.g. through status pins.
+pub trait Charger {
+    /// Return the current charging status.
+    fn charging_status(&self) -> ChargingStatus;
+}
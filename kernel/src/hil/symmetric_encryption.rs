@@ -2,12 +2,19 @@
 //!
 //! see boards/imix/src/aes_test.rs for example usage
 
-use crate::returncode::ReturnCode;
+use crate::hil::crypto::CryptoError;
 
 /// Implement this trait and use `set_client()` in order to receive callbacks from an `AES128`
 /// instance.
 pub trait Client<'a> {
-    fn crypt_done(&'a self, source: Option<&'a mut [u8]>, dest: &'a mut [u8]);
+    /// `res` is `Err(HardwareFault)` if the engine failed in the middle of
+    /// the message, in which case `dest` only holds part of the result.
+    fn crypt_done(
+        &'a self,
+        source: Option<&'a mut [u8]>,
+        dest: &'a mut [u8],
+        res: Result<(), CryptoError>,
+    );
 }
 
 /// The number of bytes used for AES block operations.  Keys and IVs must have this length,
@@ -27,12 +34,12 @@ pub trait AES128<'a> {
     fn set_client(&'a self, client: &'a dyn Client<'a>);
 
     /// Set the encryption key.
-    /// Returns `EINVAL` if length is not `AES128_KEY_SIZE`
-    fn set_key(&self, key: &[u8]) -> ReturnCode;
+    /// Returns `BadKeyLength` if length is not `AES128_KEY_SIZE`
+    fn set_key(&self, key: &[u8]) -> Result<(), CryptoError>;
 
     /// Set the IV (or initial counter).
-    /// Returns `EINVAL` if length is not `AES128_BLOCK_SIZE`
-    fn set_iv(&self, iv: &[u8]) -> ReturnCode;
+    /// Returns `InvalidArgument` if length is not `AES128_BLOCK_SIZE`
+    fn set_iv(&self, iv: &[u8]) -> Result<(), CryptoError>;
 
     /// Begin a new message (with the configured IV) when `crypt()` is
     /// next called.  Multiple calls to `crypt()` may be made between
@@ -61,15 +68,15 @@ pub trait AES128<'a> {
     /// The indices `start_index` and `stop_index` must be valid
     /// offsets in the destination buffer, and the length
     /// `stop_index - start_index` must be a multiple of
-    /// `AES128_BLOCK_SIZE`.  Otherwise, `Some(EINVAL, ...)` will be
-    /// returned.
+    /// `AES128_BLOCK_SIZE`.  Otherwise, `Some(InvalidArgument, ...)` will
+    /// be returned.
     ///
     /// If the source buffer is not `None`, its length must be
-    /// `stop_index - start_index`.  Otherwise, `Some(EINVAL, ...)`
+    /// `stop_index - start_index`.  Otherwise, `Some(InvalidArgument, ...)`
     /// will be returned.
     ///
     /// If an encryption operation is already in progress,
-    /// `Some(EBUSY, ...)` will be returned.
+    /// `Some(EngineBusy, ...)` will be returned.
     ///
     /// For correct operation, the methods `set_key` and `set_iv` must have
     /// previously been called to set the buffers containing the
@@ -83,12 +90,19 @@ pub trait AES128<'a> {
         dest: &'a mut [u8],
         start_index: usize,
         stop_index: usize,
-    ) -> Option<(ReturnCode, Option<&'a mut [u8]>, &'a mut [u8])>;
+    ) -> Option<(CryptoError, Option<&'a mut [u8]>, &'a mut [u8])>;
 }
 
 pub trait AES128Ctr {
     /// Call before `AES128::crypt()` to perform AES128Ctr
     fn set_mode_aes128ctr(&self, encrypting: bool);
+
+    /// Keep the counter block fixed instead of incrementing it after each
+    /// block, for protocols that use the same counter block for a whole
+    /// message. Returns `NotSupported` if the engine cannot do this.
+    fn set_ctr_no_increment(&self, _no_increment: bool) -> Result<(), CryptoError> {
+        Err(CryptoError::NotSupported)
+    }
 }
 
 pub trait AES128CBC {
@@ -101,14 +115,74 @@ pub trait AES128ECB {
     fn set_mode_aes128ecb(&self, encrypting: bool);
 }
 
+/// The state of a CTR or CBC session: the key, and the counter or IV to use
+/// for the next block.
+#[derive(Clone, Copy, Default)]
+pub struct AES128Context {
+    pub key: [u8; AES128_KEY_SIZE],
+    pub iv: [u8; AES128_BLOCK_SIZE],
+}
+
+/// Suspend and resume sessions, so that long-lived streams of several clients
+/// can be interleaved on a single AES engine.
+pub trait AES128SaveRestore {
+    /// Copy the key and the counter or IV of the current session into
+    /// `context`.
+    /// Returns `EngineBusy` if an encryption operation is in progress.
+    fn save_context(&self, context: &mut AES128Context) -> Result<(), CryptoError>;
+
+    /// Load a session saved with `save_context()`. The next call to
+    /// `AES128::crypt()` continues the session where it was suspended. The
+    /// mode must be set again with a method `set_mode_*()`.
+    /// Returns `EngineBusy` if an encryption operation is in progress.
+    fn restore_context(&self, context: &AES128Context) -> Result<(), CryptoError>;
+}
+
+/// Wipe key material held by an engine, e.g. when a session ends or the
+/// process that owned the key dies.
+pub trait ClearKeys {
+    /// Overwrite the keys, and the state derived from them such as keystream
+    /// or counters, with zeros. The key has to be set again before the next
+    /// operation.
+    /// Returns `EngineBusy` if an operation is in progress, as it uses the
+    /// key.
+    fn clear_keys(&self) -> Result<(), CryptoError>;
+}
+
+pub trait BlockClient {
+    /// Called when a block passed to `AES128Block::ecb_encrypt_block()` has
+    /// been encrypted, with the ciphertext, or with `HardwareFault` if the
+    /// engine could not encrypt it.
+    fn encrypt_block_done(&self, block: Result<&[u8; AES128_BLOCK_SIZE], CryptoError>);
+}
+
+/// Raw single-block AES-128 encryption, for kernel code that only needs the
+/// block cipher, e.g. CMAC subkey generation or a DRBG. It does not use the
+/// session of `AES128`, so it can be used in the middle of a CTR or CBC
+/// message without saving and restoring it.
+pub trait AES128Block<'a> {
+    /// Set the client instance which will receive `encrypt_block_done()`
+    /// callbacks. This is independent of the `AES128` client.
+    fn set_block_client(&'a self, client: &'a dyn BlockClient);
+
+    /// Queue the encryption of `block` with `key`. The client is called once
+    /// it has been encrypted.
+    /// Returns `EngineBusy` if the queue is full.
+    fn ecb_encrypt_block(
+        &self,
+        key: &[u8; AES128_KEY_SIZE],
+        block: &[u8; AES128_BLOCK_SIZE],
+    ) -> Result<(), CryptoError>;
+}
+
 pub trait CCMClient {
-    /// `res` is SUCCESS if the encryption/decryption process succeeded. This
+    /// `res` is `Ok` if the encryption/decryption process succeeded. This
     /// does not mean that the message has been verified in the case of
     /// decryption.
-    /// If we are encrypting: `tag_is_valid` is `true` iff `res` is SUCCESS.
-    /// If we are decrypting: `tag_is_valid` is `true` iff `res` is SUCCESS and the
+    /// If we are encrypting: `tag_is_valid` is `true` iff `res` is `Ok`.
+    /// If we are decrypting: `tag_is_valid` is `true` iff `res` is `Ok` and the
     /// message authentication tag is valid.
-    fn crypt_done(&self, buf: &'static mut [u8], res: ReturnCode, tag_is_valid: bool);
+    fn crypt_done(&self, buf: &'static mut [u8], res: Result<(), CryptoError>, tag_is_valid: bool);
 }
 
 pub const CCM_NONCE_LENGTH: usize = 13;
@@ -118,12 +192,13 @@ pub trait AES128CCM<'a> {
     fn set_client(&'a self, client: &'a dyn CCMClient);
 
     /// Set the key to be used for CCM encryption
-    fn set_key(&self, key: &[u8]) -> ReturnCode;
+    fn set_key(&self, key: &[u8]) -> Result<(), CryptoError>;
 
     /// Set the nonce (length NONCE_LENGTH) to be used for CCM encryption
-    fn set_nonce(&self, nonce: &[u8]) -> ReturnCode;
+    fn set_nonce(&self, nonce: &[u8]) -> Result<(), CryptoError>;
 
     /// Try to begin the encryption/decryption process
+    /// On error the return value will contain the error and `buf`.
     fn crypt(
         &self,
         buf: &'static mut [u8],
@@ -133,5 +208,86 @@ pub trait AES128CCM<'a> {
         mic_len: usize,
         confidential: bool,
         encrypting: bool,
-    ) -> (ReturnCode, Option<&'static mut [u8]>);
+    ) -> Result<(), (CryptoError, &'static mut [u8])>;
+}
+
+pub trait AEADClient {
+    /// Called when `AEAD::encrypt()` or `AEAD::decrypt()` is done. When
+    /// decrypting, `res` is `AuthenticationFailed` if the tag does not
+    /// match, and the message in `buf` has then been overwritten with zeros.
+    fn crypt_done(&self, buf: &'static mut [u8], res: Result<(), CryptoError>);
+}
+
+/// Authenticated encryption with associated data, e.g. AES-CCM, AES-GCM or
+/// ChaCha20-Poly1305.
+///
+/// The key, nonce and associated data apply to the following operations
+/// until they are set again. Engines that only handle some lengths return
+/// `BadKeyLength` or `NotSupported` for the others. `encrypt()` and
+/// `decrypt()` return the buffer with the error if they could not start.
+pub trait AEAD<'a> {
+    fn set_client(&'a self, client: &'a dyn AEADClient);
+
+    fn set_key(&self, key: &[u8]) -> Result<(), CryptoError>;
+
+    fn set_nonce(&self, nonce: &[u8]) -> Result<(), CryptoError>;
+
+    /// Set the data that is authenticated, but not encrypted, with the
+    /// message.
+    fn set_aad(&self, aad: &[u8]) -> Result<(), CryptoError>;
+
+    /// Encrypt the first `len` bytes of `buf` in place and write the tag of
+    /// `tag_len` bytes right after them.
+    fn encrypt(
+        &self,
+        buf: &'static mut [u8],
+        len: usize,
+        tag_len: usize,
+    ) -> Result<(), (CryptoError, &'static mut [u8])>;
+
+    /// Decrypt the first `len` bytes of `buf` in place, and check them
+    /// against the tag of `tag_len` bytes that follows them.
+    fn decrypt(
+        &self,
+        buf: &'static mut [u8],
+        len: usize,
+        tag_len: usize,
+    ) -> Result<(), (CryptoError, &'static mut [u8])>;
+}
+
+pub trait CMACClient {
+    /// `res` is `Ok` if the tag was computed.
+    /// If we are generating: `tag_is_valid` is `true` iff `res` is `Ok`.
+    /// If we are verifying: `tag_is_valid` is `true` iff `res` is `Ok` and
+    /// the computed tag matches the one in the buffer.
+    fn mac_done(&self, buf: &'static mut [u8], res: Result<(), CryptoError>, tag_is_valid: bool);
+}
+
+/// The maximum length of an AES-CMAC tag.
+pub const CMAC_TAG_LENGTH: usize = 16;
+
+pub trait AES128CMAC<'a> {
+    /// Set the client instance which will receive `mac_done()` callbacks
+    fn set_client(&'a self, client: &'a dyn CMACClient);
+
+    /// Set the key to be used for CMAC.
+    /// Returns `BadKeyLength` unless the key is `AES128_KEY_SIZE` bytes long.
+    fn set_key(&self, key: &[u8]) -> Result<(), CryptoError>;
+
+    /// Try to begin computing the tag over `buf[m_off..m_off + m_len]`.
+    ///
+    /// If `verify` is false, the first `tag_len` bytes of the tag are written
+    /// to `buf[m_off + m_len..m_off + m_len + tag_len]`. Otherwise the tag is
+    /// compared against the bytes at that location and the result is reported
+    /// in `mac_done()`. `m_len` may be zero. `tag_len` must be between 1 and
+    /// `CMAC_TAG_LENGTH`. On error the return value will contain the error
+    /// and `buf`.
+    fn compute(
+        &self,
+        buf: &'static mut [u8],
+        m_off: usize,
+        m_len: usize,
+        tag_len: usize,
+        verify: bool,
+    ) -> Result<(), (CryptoError, &'static mut [u8])>;
 }
@@ -48,6 +48,10 @@ pub enum Error {
     /// Overrun error during receive
     OverrunError,
 
+    /// Break condition during receive: the line was held low for longer
+    /// than a frame
+    BreakError,
+
     /// Repeat call of transmit or receive before initial command complete
     RepeatCallError,
 
@@ -149,6 +153,21 @@ pub trait Transmit<'a> {
     fn transmit_abort(&self) -> ReturnCode;
 }
 
+/// Trait for UARTs that can send a break, which some bootloaders and wake-up
+/// protocols use to get the attention of the other end.
+pub trait TransmitBreak {
+    /// Hold the TX line low for at least two frames. On completion,
+    /// `transmitted_break` will be called on the `TransmitClient`.
+    /// Returns SUCCESS, or
+    ///  - EOFF: The UART has not been configured.
+    ///  - EBUSY: the UART is already transmitting and has not made a
+    ///           transmission callback yet.
+    ///  - ENOSUPPORT: The UART cannot send a break at the configured baud
+    ///                rate.
+    /// If the `ReturnCode` is not SUCCESS, no callback will be made.
+    fn transmit_break(&self) -> ReturnCode;
+}
+
 pub trait Receive<'a> {
     /// Set the receive client, which will he called when reads complete.
     fn set_receive_client(&self, client: &'a dyn ReceiveClient);
@@ -221,6 +240,14 @@ pub trait TransmitClient {
     ///   - FAIL if the transmission failed in some way.
     fn transmitted_word(&self, _rval: ReturnCode) {}
 
+    /// A call to `TransmitBreak::transmit_break` completed. A call to
+    /// `transmit_word`, `transmit_buffer` or `transmit_break` made within
+    /// this callback SHOULD NOT return EBUSY.
+    ///
+    /// `rval` is SUCCESS if the break was sent, or
+    ///   - FAIL if the transmission failed in some way.
+    fn transmitted_break(&self, _rval: ReturnCode) {}
+
     /// A call to `Transmit::transmit_buffer` completed. The `ReturnCode`
     /// indicates whether the buffer was successfully transmitted. A call
     /// to `transmit_word` or `transmit_buffer` made within this callback
@@ -275,7 +302,8 @@ pub trait ReceiveClient {
     ///   - ESIZE if the buffer could only be partially received. `rx_len`
     ///     contains how many words were transmitted.
     ///   - FAIL if reception failed in some way: `error` may contain further
-    ///     information.
+    ///     information, such as a parity error or a break. `rx_len` contains
+    ///     how many words were received before the error.
     fn received_buffer(
         &self,
         rx_buffer: &'static mut [u8],
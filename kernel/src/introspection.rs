@@ -15,6 +15,7 @@ use core::cell::Cell;
 use crate::callback::AppId;
 use crate::capabilities::ProcessManagementCapability;
 use crate::common::cells::NumericCellExt;
+use crate::metrics::Counter;
 use crate::process;
 use crate::sched::Kernel;
 
@@ -159,4 +160,34 @@ impl KernelInfo {
         });
         count.get()
     }
+
+    /// Run a closure on every event counter registered with the kernel.
+    pub fn counter_each<F>(&self, _capability: &dyn ProcessManagementCapability, closure: F)
+    where
+        F: FnMut(&Counter),
+    {
+        self.kernel.counter_each(closure);
+    }
+
+    /// Returns the number of event counters registered with the kernel.
+    pub fn number_counters(&self, _capability: &dyn ProcessManagementCapability) -> usize {
+        let count: Cell<usize> = Cell::new(0);
+        self.kernel.counter_each(|_| count.increment());
+        count.get()
+    }
+
+    /// Run a closure on the registered event counter at `index`, or return
+    /// `default` if there is no such counter.
+    pub fn counter_map_or<F, R>(
+        &self,
+        index: usize,
+        default: R,
+        _capability: &dyn ProcessManagementCapability,
+        closure: F,
+    ) -> R
+    where
+        F: FnOnce(&Counter) -> R,
+    {
+        self.kernel.counter_at(index).map_or(default, closure)
+    }
 }
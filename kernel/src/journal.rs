@@ -0,0 +1,103 @@
+//! Journal of errors for analysing failures in the field.
+//!
+//! Error paths in the kernel, chips and capsules call `journal::record()` with
+//! an `Event` and an event specific argument. If the board has set a journal
+//! with `set_journal()`, for example `capsules::flash_journal::FlashJournal`,
+//! the event is appended to it with a timestamp. Otherwise the event is
+//! dropped, so recording an event is always cheap and never fails.
+//!
+//! The journal can be read and cleared from the process console and from
+//! userspace.
+
+use core::fmt;
+
+use crate::returncode::ReturnCode;
+
+/// The kinds of events that are recorded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// The system booted. The argument is zero.
+    Boot = 0,
+    /// A grant region could not be allocated in the memory of a process. The
+    /// argument is the number of the grant.
+    GrantExhausted = 1,
+    /// A DMA transfer of a peripheral failed. The argument is the peripheral
+    /// specific error source.
+    DmaError = 2,
+    /// A random number generator reported an error. The argument is the
+    /// `ReturnCode`.
+    RngFailure = 3,
+    /// A flash write failed. The argument is the page number.
+    FlashWriteFailure = 4,
+}
+
+impl Event {
+    /// Convert the value stored in a `Record` back into an `Event`.
+    pub fn from_raw(event: u16) -> Option<Event> {
+        match event {
+            0 => Some(Event::Boot),
+            1 => Some(Event::GrantExhausted),
+            2 => Some(Event::DmaError),
+            3 => Some(Event::RngFailure),
+            4 => Some(Event::FlashWriteFailure),
+            _ => None,
+        }
+    }
+}
+
+/// An entry of the journal.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Record {
+    /// Number of the record, increasing across reboots.
+    pub sequence: u32,
+    /// Milliseconds since the last boot.
+    pub timestamp_ms: u32,
+    /// The `Event`, as its numeric value.
+    pub event: u16,
+    pub arg: u32,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:5} {:10}ms ", self.sequence, self.timestamp_ms)?;
+        match Event::from_raw(self.event) {
+            Some(event) => write!(f, "{:?}", event)?,
+            None => write!(f, "Unknown({})", self.event)?,
+        }
+        write!(f, " {:#x}", self.arg)
+    }
+}
+
+pub trait Journal {
+    /// Append an event to the journal.
+    fn append(&self, event: Event, arg: u32);
+
+    /// Returns the number of records in the journal.
+    fn len(&self) -> usize;
+
+    /// Returns the record at `index`, the oldest record being at index `0`.
+    /// Returns `None` if there is no such record or the journal is busy.
+    fn get(&self, index: usize) -> Option<Record>;
+
+    /// Remove all records from the journal.
+    fn clear(&self) -> ReturnCode;
+}
+
+static mut JOURNAL: Option<&'static dyn Journal> = None;
+
+/// Function used by board main.rs to set the journal.
+pub unsafe fn set_journal(journal: &'static dyn Journal) {
+    JOURNAL = Some(journal);
+}
+
+/// Returns the journal set by the board, if any.
+pub fn journal() -> Option<&'static dyn Journal> {
+    unsafe { JOURNAL }
+}
+
+/// Record an event in the journal, if the board has set one.
+pub fn record(event: Event, arg: u32) {
+    if let Some(journal) = journal() {
+        journal.append(event, arg);
+    }
+}
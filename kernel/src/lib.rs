@@ -20,10 +20,14 @@ pub mod capabilities;
 pub mod common;
 pub mod component;
 pub mod debug;
+pub mod energy;
 pub mod hil;
 pub mod introspection;
 pub mod ipc;
+pub mod journal;
+pub mod metrics;
 pub mod syscall;
+pub mod system_events;
 
 mod callback;
 mod config;
@@ -0,0 +1,186 @@
+//! Event counters for monitoring a running system.
+//!
+//! A `Counter` is a named set of event counts, for example the number of
+//! interrupts per IRQ line or the number of erases per flash page. Counters
+//! are created by the board and handed to the kernel, chip or capsule that
+//! produces the events. Counters registered with the kernel with
+//! `Kernel::register_counter()` can be read through
+//! `introspection::KernelInfo`, and from there by the process console and the
+//! metrics syscall driver.
+//!
+//! Each entry of a counter is identified by a key. By default the key of an
+//! entry is its index, but a counter can be given an explicit list of keys
+//! when the events are identified by sparse values such as driver numbers.
+//!
+//! Counts saturate at `u32::max_value()` rather than wrapping.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! # use core::cell::Cell;
+//! # use kernel::metrics::Counter;
+//! let counts = [Cell::new(0), Cell::new(0)];
+//! let erases = Counter::new("flash_erase", &counts);
+//!
+//! erases.increment(1);
+//! erases.increment(1);
+//! assert_eq!(erases.get(1), Some(2));
+//! assert_eq!(erases.get(2), None);
+//! ```
+
+use core::cell::Cell;
+
+use crate::common::{ListLink, ListNode};
+
+pub struct Counter<'a> {
+    name: &'static str,
+    counts: &'a [Cell<u32>],
+    keys: Option<&'a [usize]>,
+    next: ListLink<'a, Counter<'a>>,
+}
+
+impl<'a> ListNode<'a, Counter<'a>> for Counter<'a> {
+    fn next(&self) -> &'a ListLink<Counter<'a>> {
+        &self.next
+    }
+}
+
+impl<'a> Counter<'a> {
+    /// Create a counter with one entry per element of `counts`, keyed by
+    /// index.
+    pub const fn new(name: &'static str, counts: &'a [Cell<u32>]) -> Counter<'a> {
+        Counter {
+            name: name,
+            counts: counts,
+            keys: None,
+            next: ListLink::empty(),
+        }
+    }
+
+    /// Create a counter where entry `i` is identified by `keys[i]`.
+    /// `counts` and `keys` should have the same length.
+    pub const fn new_with_keys(
+        name: &'static str,
+        counts: &'a [Cell<u32>],
+        keys: &'a [usize],
+    ) -> Counter<'a> {
+        Counter {
+            name: name,
+            counts: counts,
+            keys: Some(keys),
+            next: ListLink::empty(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the number of entries of the counter.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Returns the key of the entry at `index`.
+    pub fn key(&self, index: usize) -> Option<usize> {
+        if index >= self.counts.len() {
+            return None;
+        }
+        self.keys
+            .map_or(Some(index), |keys| keys.get(index).map(|key| *key))
+    }
+
+    fn index_of(&self, key: usize) -> Option<usize> {
+        self.keys.map_or(Some(key), |keys| {
+            keys.iter().position(|candidate| *candidate == key)
+        })
+    }
+
+    /// Record one event for `key`. Events for unknown keys are ignored.
+    pub fn increment(&self, key: usize) {
+        self.add(key, 1);
+    }
+
+    /// Record `n` events for `key`. Events for unknown keys are ignored.
+    pub fn add(&self, key: usize, n: u32) {
+        if let Some(count) = self.index_of(key).and_then(|index| self.counts.get(index)) {
+            count.set(count.get().saturating_add(n));
+        }
+    }
+
+    /// Record one event for `key` and return whether its count has just
+    /// reached `threshold`, e.g. to warn once when a flash page nears its
+    /// endurance rating.
+    pub fn increment_to(&self, key: usize, threshold: u32) -> bool {
+        self.increment(key);
+        self.get(key) == Some(threshold)
+    }
+
+    /// Returns the count for `key`.
+    pub fn get(&self, key: usize) -> Option<u32> {
+        self.index_of(key)
+            .and_then(|index| self.counts.get(index))
+            .map(|count| count.get())
+    }
+
+    /// Returns the count of the entry at `index`.
+    pub fn get_index(&self, index: usize) -> Option<u32> {
+        self.counts.get(index).map(|count| count.get())
+    }
+
+    /// Set the count for `key`, e.g. to restore a persisted value.
+    pub fn set(&self, key: usize, value: u32) {
+        if let Some(count) = self.index_of(key).and_then(|index| self.counts.get(index)) {
+            count.set(value);
+        }
+    }
+
+    /// Reset every entry to zero.
+    pub fn reset(&self) {
+        for count in self.counts.iter() {
+            count.set(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Counter;
+    use core::cell::Cell;
+
+    #[test]
+    fn test_keys() {
+        let counts = [Cell::new(0), Cell::new(0), Cell::new(0)];
+        let keys = [0x0, 0x1, 0x40001];
+        let counter = Counter::new_with_keys("syscalls", &counts, &keys);
+
+        counter.increment(0x40001);
+        counter.add(0x1, 3);
+        counter.increment(0x2);
+
+        assert_eq!(counter.get(0x40001), Some(1));
+        assert_eq!(counter.get(0x1), Some(3));
+        assert_eq!(counter.get(0x2), None);
+        assert_eq!(counter.key(2), Some(0x40001));
+        assert_eq!(counter.get_index(2), Some(1));
+        assert_eq!(counter.key(3), None);
+    }
+
+    #[test]
+    fn test_saturate() {
+        let counts = [Cell::new(0)];
+        let counter = Counter::new("events", &counts);
+
+        assert!(!counter.increment_to(0, 2));
+        assert!(counter.increment_to(0, 2));
+        assert!(!counter.increment_to(0, 2));
+
+        counter.set(0, u32::max_value() - 1);
+        counter.add(0, 5);
+        assert_eq!(counter.get(0), Some(u32::max_value()));
+
+        counter.reset();
+        assert_eq!(counter.get(0), Some(0));
+    }
+}
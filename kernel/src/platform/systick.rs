@@ -15,9 +15,13 @@ pub trait SysTick {
     ///
     /// Callers can assume at least a 24-bit wide clock. Specific timing is
     /// dependent on the driving clock. In practice, increments of 10ms are most
-    /// accurate and values up to 400ms are valid.
+    /// accurate and values up to `max_us()` are valid.
     fn set_timer(&self, us: u32);
 
+    /// Returns the longest interval in microseconds that `set_timer` can
+    /// count, e.g. about 262ms for a 24-bit timer at 64 MHz.
+    fn max_us(&self) -> u32;
+
     /// Returns if there is at least `us` microseconds left
     fn greater_than(&self, us: u32) -> bool;
 
@@ -47,6 +51,10 @@ impl SysTick for () {
 
     fn set_timer(&self, _: u32) {}
 
+    fn max_us(&self) -> u32 {
+        u32::max_value()
+    }
+
     fn enable(&self, _: bool) {}
 
     fn overflowed(&self) -> bool {
@@ -56,6 +56,22 @@ pub enum ProcessLoadError {
         expected_address: u32,
     },
 
+    /// A process depends on a process that is not in flash or is disabled.
+    /// The process is not loaded, but all processes that do not depend on it
+    /// are.
+    MissingDependency {
+        process_name: &'static str,
+        dependency_name: &'static str,
+    },
+
+    /// A process depends on a process that could not be loaded itself, for
+    /// example because the dependencies form a cycle. The process is not
+    /// loaded.
+    UnresolvedDependency {
+        process_name: &'static str,
+        dependency_name: &'static str,
+    },
+
     /// Process loading error due (likely) to a bug in the kernel. If you get
     /// this error please open a bug report.
     InternalError,
@@ -109,6 +125,24 @@ impl fmt::Debug for ProcessLoadError {
                 actual_address, expected_address
             ),
 
+            ProcessLoadError::MissingDependency {
+                process_name,
+                dependency_name,
+            } => write!(
+                f,
+                "App {} not loaded: depends on {}, which is missing or disabled",
+                process_name, dependency_name
+            ),
+
+            ProcessLoadError::UnresolvedDependency {
+                process_name,
+                dependency_name,
+            } => write!(
+                f,
+                "App {} not loaded: depends on {}, which could not be loaded",
+                process_name, dependency_name
+            ),
+
             ProcessLoadError::InternalError => write!(f, "Error in kernel. Likely a bug."),
         }
     }
@@ -124,6 +158,13 @@ impl fmt::Debug for ProcessLoadError {
 /// number of processes are created, with process structures placed in the
 /// provided array. How process faults are handled by the kernel is also
 /// selected.
+///
+/// Processes are loaded in the order they appear in flash, except that a
+/// process which declares dependencies in its TBF header is loaded after the
+/// processes it depends on. Since processes are scheduled in the order of the
+/// array, this also means that they start after them. A process whose
+/// dependencies cannot be loaded is skipped and reported with an error once
+/// all other processes are loaded.
 pub fn load_processes<C: Chip>(
     kernel: &'static Kernel,
     chip: &'static C,
@@ -133,9 +174,9 @@ pub fn load_processes<C: Chip>(
     fault_response: FaultResponse,
     _capability: &dyn ProcessManagementCapability,
 ) -> Result<(), ProcessLoadError> {
-    let mut remaining_flash = app_flash;
     let mut app_memory_ptr = app_memory.as_mut_ptr();
     let mut app_memory_size = app_memory.len();
+    let mut num_procs = 0;
 
     if config::CONFIG.debug_load_processes {
         debug!(
@@ -146,58 +187,57 @@ pub fn load_processes<C: Chip>(
         );
     }
 
-    for i in 0..procs.len() {
-        unsafe {
-            // Get the first eight bytes of flash to check if there is another
-            // app.
-            let test_header_slice = match remaining_flash.get(0..8) {
-                Some(s) => s,
-                None => {
-                    // Not enough flash to test for another app. This just means
-                    // we are at the end of flash, and there are no more apps to
-                    // load.
-                    return Ok(());
-                }
-            };
-
-            // Pass the first eight bytes to tbfheader to parse out the length
-            // of the tbf header and app. We then use those values to see if we
-            // have enough flash remaining to parse the remainder of the header.
-            let (version, header_length, app_length) = match tbfheader::parse_tbf_header_lengths(
-                test_header_slice
-                    .try_into()
-                    .or(Err(ProcessLoadError::InternalError))?,
-            ) {
-                Ok((v, hl, al)) => (v, hl, al),
-                Err(_tbferr) => {
-                    // Since Tock apps use a linked list, it is very possible
-                    // the header we started to parse is intentionally invalid
-                    // to signal the end of apps. This is ok and just means we
-                    // have finished loading apps.
-                    return Ok(());
-                }
+    // Each pass over flash loads the processes whose dependencies have all
+    // been loaded by a previous pass, so the number of passes is bounded by
+    // the depth of the dependency graph.
+    let mut first_pass = true;
+    let mut progress = true;
+    while progress && num_procs < procs.len() {
+        progress = false;
+        let mut remaining_flash = app_flash;
+
+        while num_procs < procs.len() {
+            let (entry_flash, header_length, version, tbf_header) = match next_app(remaining_flash)?
+            {
+                Some(app) => app,
+                None => break,
             };
 
-            // Now we can get a slice which only encompasses the app. At this
-            // point, since the version number in the beginning of the header is
-            // valid, we consider further parsing errors to be actual errors and
-            // report them to the caller.
-            let app_flash = remaining_flash
-                .get(0..app_length as usize)
+            // Advance in flash before seeing if this app should be loaded now.
+            remaining_flash = remaining_flash
+                .get(entry_flash.len()..)
                 .ok_or(ProcessLoadError::NotEnoughFlash)?;
 
+            if !tbf_header.is_app() || !tbf_header.enabled() {
+                // `Process::create()` checks and skips these, which only
+                // needs to happen once.
+                if !first_pass {
+                    continue;
+                }
+            } else if is_loaded(&procs[..num_procs], |proc| {
+                proc.flash_start() == entry_flash.as_ptr()
+            }) || !tbf_header.dependencies().all(|dependency| {
+                is_loaded(&procs[..num_procs], |proc| {
+                    proc.get_process_name() == dependency
+                })
+            }) {
+                continue;
+            }
+
             // Try to create a process object from that app slice.
-            let (process, memory_offset) = Process::create(
-                kernel,
-                chip,
-                app_flash,
-                header_length as usize,
-                version,
-                app_memory_ptr,
-                app_memory_size,
-                fault_response,
-                i,
-            )?;
+            let (process, memory_offset) = unsafe {
+                Process::create(
+                    kernel,
+                    chip,
+                    entry_flash,
+                    header_length as usize,
+                    version,
+                    app_memory_ptr,
+                    app_memory_size,
+                    fault_response,
+                    num_procs,
+                )?
+            };
 
             // Check to see if actually got a valid process to execute. If we
             // didn't and we didn't get a loading error (aka we got to this
@@ -206,30 +246,140 @@ pub fn load_processes<C: Chip>(
                 if config::CONFIG.debug_load_processes {
                     debug!(
                         "Loaded process[{}] from flash=[{:#010X}:{:#010X}] into sram=[{:#010X}:{:#010X}] = {:?}",
-                        i,
-                        app_flash.as_ptr() as usize,
-                        app_flash.as_ptr() as usize + app_flash.len(),
+                        num_procs,
+                        entry_flash.as_ptr() as usize,
+                        entry_flash.as_ptr() as usize + entry_flash.len(),
                         app_memory_ptr as usize,
                         app_memory_ptr as usize + memory_offset,
                         process.map(|p| p.get_process_name())
                     );
                 }
-                procs[i] = process;
+                procs[num_procs] = process;
+                num_procs += 1;
+                progress = true;
             }
 
-            // Advance in our buffers before seeing if there is an additional
-            // process to load.
-            remaining_flash = remaining_flash
-                .get(app_flash.len()..)
-                .ok_or(ProcessLoadError::NotEnoughFlash)?;
-            app_memory_ptr = app_memory_ptr.add(memory_offset);
+            app_memory_ptr = unsafe { app_memory_ptr.add(memory_offset) };
             app_memory_size -= memory_offset;
         }
+        first_pass = false;
+    }
+
+    if num_procs == procs.len() {
+        return Ok(());
+    }
+
+    // Every enabled app that is still not loaded is waiting for a dependency.
+    // Report the first one.
+    let mut remaining_flash = app_flash;
+    while let Some((entry_flash, _, _, tbf_header)) = next_app(remaining_flash)? {
+        remaining_flash = remaining_flash
+            .get(entry_flash.len()..)
+            .ok_or(ProcessLoadError::NotEnoughFlash)?;
+
+        if !tbf_header.is_app() || !tbf_header.enabled() {
+            continue;
+        }
+        for dependency_name in tbf_header.dependencies() {
+            if is_loaded(&procs[..num_procs], |proc| {
+                proc.get_process_name() == dependency_name
+            }) {
+                continue;
+            }
+            let process_name = tbf_header.get_package_name().unwrap_or("");
+            return Err(if find_enabled_app(app_flash, dependency_name)? {
+                ProcessLoadError::UnresolvedDependency {
+                    process_name,
+                    dependency_name,
+                }
+            } else {
+                ProcessLoadError::MissingDependency {
+                    process_name,
+                    dependency_name,
+                }
+            });
+        }
     }
 
     Ok(())
 }
 
+/// Get the next entry of the app linked list at the start of `flash`, with its
+/// header length, TBF version and parsed header. Returns `None` at the end of
+/// the list.
+fn next_app(
+    flash: &'static [u8],
+) -> Result<Option<(&'static [u8], u16, u16, tbfheader::TbfHeader)>, ProcessLoadError> {
+    // Get the first eight bytes of flash to check if there is another app.
+    let test_header_slice = match flash.get(0..8) {
+        Some(s) => s,
+        None => {
+            // Not enough flash to test for another app. This just means we are
+            // at the end of flash, and there are no more apps to load.
+            return Ok(None);
+        }
+    };
+
+    // Pass the first eight bytes to tbfheader to parse out the length of the
+    // tbf header and app. We then use those values to see if we have enough
+    // flash remaining to parse the remainder of the header.
+    let (version, header_length, app_length) = match tbfheader::parse_tbf_header_lengths(
+        test_header_slice
+            .try_into()
+            .or(Err(ProcessLoadError::InternalError))?,
+    ) {
+        Ok((v, hl, al)) => (v, hl, al),
+        Err(_tbferr) => {
+            // Since Tock apps use a linked list, it is very possible the
+            // header we started to parse is intentionally invalid to signal
+            // the end of apps. This is ok and just means we have finished
+            // loading apps.
+            return Ok(None);
+        }
+    };
+
+    // Now we can get a slice which only encompasses the app. At this point,
+    // since the version number in the beginning of the header is valid, we
+    // consider further parsing errors to be actual errors and report them to
+    // the caller.
+    let app_flash = flash
+        .get(0..app_length as usize)
+        .ok_or(ProcessLoadError::NotEnoughFlash)?;
+    let header_flash = app_flash
+        .get(0..header_length as usize)
+        .ok_or(ProcessLoadError::NotEnoughFlash)?;
+    let tbf_header = tbfheader::parse_tbf_header(header_flash, version)?;
+
+    Ok(Some((app_flash, header_length, version, tbf_header)))
+}
+
+/// Whether an enabled app named `name` is in the app linked list in `flash`.
+fn find_enabled_app(flash: &'static [u8], name: &str) -> Result<bool, ProcessLoadError> {
+    let mut remaining_flash = flash;
+    while let Some((app_flash, _, _, tbf_header)) = next_app(remaining_flash)? {
+        if tbf_header.is_app()
+            && tbf_header.enabled()
+            && tbf_header.get_package_name() == Some(name)
+        {
+            return Ok(true);
+        }
+        remaining_flash = remaining_flash
+            .get(app_flash.len()..)
+            .ok_or(ProcessLoadError::NotEnoughFlash)?;
+    }
+    Ok(false)
+}
+
+/// Whether one of the loaded processes in `procs` matches `predicate`.
+fn is_loaded<F>(procs: &[Option<&'static dyn ProcessType>], predicate: F) -> bool
+where
+    F: Fn(&dyn ProcessType) -> bool,
+{
+    procs
+        .iter()
+        .any(|proc| proc.map_or(false, |proc| predicate(proc)))
+}
+
 /// This trait is implemented by process structs.
 pub trait ProcessType {
     /// Returns the process's identifier
@@ -290,6 +440,10 @@ pub trait ProcessType {
     /// Get the name of the process. Used for IPC.
     fn get_process_name(&self) -> &'static str;
 
+    /// Get the name of the `index`th process this process depends on, as
+    /// declared in its TBF header.
+    fn get_dependency(&self, index: usize) -> Option<&'static str>;
+
     // memop operations
 
     /// Change the location of the program break and reallocate the MPU region
@@ -321,6 +475,10 @@ pub trait ProcessType {
     /// process.
     fn flash_end(&self) -> *const u8;
 
+    /// The flash region allocated for this process: its TBF header, code and
+    /// padding. This is what is hashed to measure the process.
+    fn flash_image(&self) -> &'static [u8];
+
     /// The lowest address of the grant region for the process.
     fn kernel_memory_break(&self) -> *const u8;
 
@@ -941,6 +1099,10 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         unsafe { self.flash.as_ptr().add(self.flash.len()) }
     }
 
+    fn flash_image(&self) -> &'static [u8] {
+        self.flash
+    }
+
     fn kernel_memory_break(&self) -> *const u8 {
         self.kernel_memory_break.get()
     }
@@ -1173,6 +1335,10 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         self.process_name
     }
 
+    fn get_dependency(&self, index: usize) -> Option<&'static str> {
+        self.header.dependencies().nth(index)
+    }
+
     unsafe fn set_syscall_return_value(&self, return_value: isize) {
         self.stored_state.map(|stored_state| {
             self.chip
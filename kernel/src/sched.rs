@@ -1,17 +1,21 @@
 //! Tock core scheduler.
 
 use core::cell::Cell;
+use core::cmp;
 use core::ptr::NonNull;
 
 use crate::callback::{AppId, Callback, CallbackId};
 use crate::capabilities;
-use crate::common::cells::NumericCellExt;
+use crate::common::cells::{NumericCellExt, OptionalCell};
 use crate::common::dynamic_deferred_call::DynamicDeferredCall;
+use crate::common::List;
 use crate::config;
 use crate::debug;
+use crate::energy;
 use crate::grant::Grant;
 use crate::ipc;
 use crate::memop;
+use crate::metrics::Counter;
 use crate::platform::mpu::MPU;
 use crate::platform::systick::SysTick;
 use crate::platform::{Chip, Platform};
@@ -19,7 +23,8 @@ use crate::process::{self, Task};
 use crate::returncode::ReturnCode;
 use crate::syscall::{ContextSwitchReason, Syscall};
 
-/// The time a process is permitted to run before being pre-empted
+/// The time a process is permitted to run before being pre-empted, unless
+/// the board sets another one with `Kernel::set_timeslice_us()`.
 const KERNEL_TICK_DURATION_US: u32 = 10000;
 /// Skip re-scheduling a process if its quanta is nearly exhausted
 const MIN_QUANTA_THRESHOLD_US: u32 = 500;
@@ -47,9 +52,27 @@ pub struct Kernel {
     /// created and the data structures for grants have already been
     /// established.
     grants_finalized: Cell<bool>,
+
+    /// Event counters registered by the board.
+    counters: List<'static, Counter<'static>>,
+
+    /// Counter for the number of system calls made to each driver.
+    syscall_counter: OptionalCell<&'static Counter<'static>>,
+
+    /// The time a process is permitted to run before being pre-empted.
+    timeslice_us: Cell<u32>,
+
+    /// Counter for how much of their timeslice processes use.
+    timeslice_counter: OptionalCell<&'static Counter<'static>>,
+
+    /// Receives the CPU activity, for energy estimates.
+    activity: OptionalCell<&'static dyn energy::Activity>,
 }
 
 impl Kernel {
+    /// Number of entries of the counter set with `set_timeslice_counter()`.
+    pub const TIMESLICE_COUNTER_LEN: usize = 5;
+
     pub fn new(processes: &'static [Option<&'static dyn process::ProcessType>]) -> Kernel {
         Kernel {
             work: Cell::new(0),
@@ -57,9 +80,75 @@ impl Kernel {
             process_identifier_max: Cell::new(0),
             grant_counter: Cell::new(0),
             grants_finalized: Cell::new(false),
+            counters: List::new(),
+            syscall_counter: OptionalCell::empty(),
+            timeslice_us: Cell::new(KERNEL_TICK_DURATION_US),
+            timeslice_counter: OptionalCell::empty(),
+            activity: OptionalCell::empty(),
         }
     }
 
+    /// Set the time a process is permitted to run before being pre-empted.
+    /// Longer timeslices favour compute-heavy processes, shorter ones the
+    /// responsiveness of the others. The timeslice is at least 1 ms. The
+    /// scheduler shortens it to the longest period of the chip's SysTick,
+    /// `SysTick::max_us()`, if it is longer.
+    pub fn set_timeslice_us(&self, us: u32) {
+        self.timeslice_us
+            .set(cmp::max(us, 2 * MIN_QUANTA_THRESHOLD_US));
+    }
+
+    /// Returns the time a process is permitted to run before being
+    /// pre-empted.
+    pub fn timeslice_us(&self) -> u32 {
+        self.timeslice_us.get()
+    }
+
+    /// Register an event counter so that it is visible through introspection.
+    pub fn register_counter(&self, counter: &'static Counter<'static>) {
+        self.counters.push_tail(counter);
+    }
+
+    /// Count the `subscribe`, `command` and `allow` system calls made to each
+    /// driver in `counter`, keyed by driver number. The counter is also
+    /// registered.
+    pub fn set_syscall_counter(&self, counter: &'static Counter<'static>) {
+        self.syscall_counter.set(counter);
+        self.register_counter(counter);
+    }
+
+    /// Count how much of its timeslice a process used each time it ran in
+    /// `counter`, which needs `TIMESLICE_COUNTER_LEN` entries: entries `0` to
+    /// `3` count the processes that gave up the processor within the first to
+    /// last quarter of their timeslice, and entry `4` the processes that
+    /// exhausted it. The counter is also registered.
+    pub fn set_timeslice_counter(&self, counter: &'static Counter<'static>) {
+        self.timeslice_counter.set(counter);
+        self.register_counter(counter);
+    }
+
+    /// Report to `activity` when the CPU sleeps and wakes up, so that the
+    /// time it spends active is part of the energy estimates. Chip drivers
+    /// report the activity of the peripherals to the same `Activity`.
+    pub fn set_activity(&self, activity: &'static dyn energy::Activity) {
+        self.activity.set(activity);
+    }
+
+    /// Run a closure on every registered counter.
+    pub(crate) fn counter_each<F>(&self, mut closure: F)
+    where
+        F: FnMut(&Counter),
+    {
+        for counter in self.counters.iter() {
+            closure(counter);
+        }
+    }
+
+    /// Returns the registered counter at `index`, in registration order.
+    pub(crate) fn counter_at(&self, index: usize) -> Option<&'static Counter<'static>> {
+        self.counters.iter().nth(index)
+    }
+
     /// Something was scheduled for a process, so there is more work to do.
     pub(crate) fn increment_work(&self) {
         self.work.increment();
@@ -313,7 +402,11 @@ impl Kernel {
                         && !DynamicDeferredCall::global_instance_calls_pending().unwrap_or(false)
                         && self.processes_blocked()
                     {
+                        self.activity
+                            .map(|activity| activity.set_active(energy::CPU, false));
                         chip.sleep();
+                        self.activity
+                            .map(|activity| activity.set_active(energy::CPU, true));
                     }
                 });
             };
@@ -328,10 +421,15 @@ impl Kernel {
         ipc: Option<&crate::ipc::IPC>,
     ) {
         let systick = chip.systick();
+        let timeslice_us = cmp::min(self.timeslice_us.get(), systick.max_us());
         systick.reset();
-        systick.set_timer(KERNEL_TICK_DURATION_US);
+        systick.set_timer(timeslice_us);
         systick.enable(false);
 
+        // Whether the process ran, and whether it exhausted its timeslice.
+        let mut ran = false;
+        let mut expired = false;
+
         loop {
             if chip.has_pending_interrupts()
                 || DynamicDeferredCall::global_instance_calls_pending().unwrap_or(false)
@@ -341,6 +439,7 @@ impl Kernel {
 
             if systick.overflowed() || !systick.greater_than(MIN_QUANTA_THRESHOLD_US) {
                 process.debug_timeslice_expired();
+                expired = true;
                 break;
             }
 
@@ -352,6 +451,7 @@ impl Kernel {
                     process.setup_mpu();
                     chip.mpu().enable_mpu();
                     systick.enable(true);
+                    ran = true;
                     let context_switch_reason = process.switch_to();
                     systick.enable(false);
                     chip.mpu().disable_mpu();
@@ -387,6 +487,16 @@ impl Kernel {
                                 }
                             }
 
+                            match syscall {
+                                Syscall::SUBSCRIBE { driver_number, .. }
+                                | Syscall::COMMAND { driver_number, .. }
+                                | Syscall::ALLOW { driver_number, .. } => {
+                                    self.syscall_counter
+                                        .map(|counter| counter.increment(driver_number));
+                                }
+                                _ => {}
+                            }
+
                             // Handle each of the syscalls.
                             match syscall {
                                 Syscall::MEMOP { operand, arg0 } => {
@@ -599,6 +709,22 @@ impl Kernel {
                 }
             }
         }
+        if ran {
+            self.timeslice_counter.map(|counter| {
+                let quarter = if expired {
+                    4
+                } else if systick.greater_than(timeslice_us / 4 * 3) {
+                    0
+                } else if systick.greater_than(timeslice_us / 2) {
+                    1
+                } else if systick.greater_than(timeslice_us / 4) {
+                    2
+                } else {
+                    3
+                };
+                counter.increment(quarter);
+            });
+        }
         systick.reset();
     }
 }
@@ -0,0 +1,50 @@
+//! Notifications of system conditions for processes.
+//!
+//! Code that detects a condition processes may want to react to, such as low
+//! memory or a battery running low, calls `system_events::notify()` with an
+//! `Event` and an event specific argument. If the board has set a listener
+//! with `set_listener()`, for example `capsules::system_events::SystemEvents`,
+//! the event is passed on to it, so that processes can subscribe to all these
+//! conditions in one place instead of polling the drivers involved.
+//! Otherwise the event is dropped.
+
+/// The kinds of events that are notified.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// A grant region could not be allocated in the memory of a process. The
+    /// argument is the number of the grant.
+    LowMemory = 0,
+    /// A nonvolatile storage region is nearly full. The argument is the
+    /// percentage in use.
+    StorageNearlyFull = 1,
+    /// A random number generator reported an error. The argument is the
+    /// `ReturnCode`.
+    RngDegraded = 2,
+    /// A cryptographic accelerator reported an error.
+    CryptoFault = 3,
+    /// The battery is nearly empty. The argument is the state of charge in
+    /// hundredths of percent.
+    BatteryLow = 4,
+    /// The USB host suspended the bus.
+    UsbSuspend = 5,
+    /// The USB host resumed the bus.
+    UsbResume = 6,
+}
+
+pub trait Listener {
+    fn notify(&self, event: Event, arg: usize);
+}
+
+static mut LISTENER: Option<&'static dyn Listener> = None;
+
+/// Function used by board main.rs to set the listener.
+pub unsafe fn set_listener(listener: &'static dyn Listener) {
+    LISTENER = Some(listener);
+}
+
+/// Notify the listener of an event, if the board has set one.
+pub fn notify(event: Event, arg: usize) {
+    if let Some(listener) = unsafe { LISTENER } {
+        listener.notify(event, arg);
+    }
+}
@@ -41,6 +41,10 @@ pub enum TbfParseError {
     /// UTF-8 string.
     BadProcessName,
 
+    /// The list of dependencies in the TBF header could not be successfully
+    /// parsed as a UTF-8 string.
+    BadDependencies,
+
     /// Internal kernel error. This is a bug inside of this library. Likely this
     /// means that for some reason a slice was not sized properly for parsing a
     /// certain type, which is something completely controlled by this library.
@@ -73,6 +77,7 @@ impl fmt::Debug for TbfParseError {
             ),
             TbfParseError::BadTlvEntry(tipe) => write!(f, "TLV entry type {} is invalid", tipe),
             TbfParseError::BadProcessName => write!(f, "Process name not UTF-8"),
+            TbfParseError::BadDependencies => write!(f, "Process dependencies not UTF-8"),
             TbfParseError::InternalError => write!(f, "Internal kernel error. This is a bug."),
         }
     }
@@ -97,6 +102,7 @@ pub(crate) enum TbfHeaderTypes {
     TbfHeaderWriteableFlashRegions = 2,
     TbfHeaderPackageName = 3,
     TbfHeaderFixedAddresses = 5,
+    TbfHeaderDependencies = 6,
 
     /// Some field in the header that we do not understand. Since the TLV format
     /// specifies the length of each section, if we get a field we do not
@@ -201,6 +207,7 @@ impl core::convert::TryFrom<u16> for TbfHeaderTypes {
             2 => Ok(TbfHeaderTypes::TbfHeaderWriteableFlashRegions),
             3 => Ok(TbfHeaderTypes::TbfHeaderPackageName),
             5 => Ok(TbfHeaderTypes::TbfHeaderFixedAddresses),
+            6 => Ok(TbfHeaderTypes::TbfHeaderDependencies),
             _ => Ok(TbfHeaderTypes::Unknown),
         }
     }
@@ -300,6 +307,9 @@ pub(crate) struct TbfHeaderV2 {
     package_name: Option<&'static str>,
     writeable_regions: Option<[Option<TbfHeaderV2WriteableFlashRegion>; 4]>,
     fixed_addresses: Option<TbfHeaderV2FixedAddresses>,
+    /// Package names of the processes that must be loaded before this one,
+    /// separated by NUL characters.
+    dependencies: Option<&'static str>,
 }
 
 /// Type that represents the fields of the Tock Binary Format header.
@@ -375,6 +385,15 @@ impl TbfHeader {
         }
     }
 
+    /// Get the package names of the processes that this app depends on.
+    pub(crate) fn dependencies(&self) -> impl Iterator<Item = &'static str> {
+        let dependencies = match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd.dependencies.unwrap_or(""),
+            _ => "",
+        };
+        dependencies.split('\0').filter(|name| !name.is_empty())
+    }
+
     /// Get the number of flash regions this app has specified in its header.
     pub(crate) fn number_writeable_flash_regions(&self) -> usize {
         match *self {
@@ -541,6 +560,7 @@ pub(crate) fn parse_tbf_header(
                     Default::default();
                 let mut app_name_str = "";
                 let mut fixed_address_pointer: Option<TbfHeaderV2FixedAddresses> = None;
+                let mut dependencies_str: Option<&'static str> = None;
 
                 // Iterate the remainder of the header looking for TLV entries.
                 while remaining.len() > 0 {
@@ -621,6 +641,18 @@ pub(crate) fn parse_tbf_header(
                             }
                         }
 
+                        TbfHeaderTypes::TbfHeaderDependencies => {
+                            let dependencies_buf = remaining
+                                .get(0..tlv_header.length as usize)
+                                .ok_or(TbfParseError::NotEnoughFlash)?;
+
+                            str::from_utf8(dependencies_buf)
+                                .map(|dependencies| {
+                                    dependencies_str = Some(dependencies);
+                                })
+                                .or(Err(TbfParseError::BadDependencies))?;
+                        }
+
                         _ => {}
                     }
 
@@ -638,6 +670,7 @@ pub(crate) fn parse_tbf_header(
                     package_name: Some(app_name_str),
                     writeable_regions: Some(wfr_pointer),
                     fixed_addresses: fixed_address_pointer,
+                    dependencies: dependencies_str,
                 };
 
                 Ok(TbfHeader::TbfHeaderV2(tbf_header))
@@ -0,0 +1,509 @@
+//! Bounded, canonical CBOR decoder.
+
+use crate::{major, simple, Error, MAX_DEPTH};
+use core::cmp::Ordering;
+
+/// A decoded item. Arrays, maps and tags are returned as their header, and
+/// their content by the following calls.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Value<'a> {
+    Unsigned(u64),
+    /// The integer -1 - n.
+    Negative(u64),
+    Bytes(&'a [u8]),
+    Text(&'a str),
+    /// An array of this many items.
+    Array(usize),
+    /// A map of this many keys and values.
+    Map(usize),
+    /// A tag applying to the next item.
+    Tag(u64),
+    Bool(bool),
+    Null,
+    Undefined,
+}
+
+/// An array, map or tag being decoded.
+#[derive(Clone, Copy, Default)]
+struct Level {
+    /// Offset of its header.
+    start: usize,
+    /// Items left, counting keys and values of maps separately.
+    remaining: u64,
+    map: bool,
+    /// Offsets of the encoding of the last key of a map.
+    last_key: Option<(usize, usize)>,
+}
+
+/// Reads CBOR items from a buffer.
+///
+/// The decoder keeps track of the arrays, maps and tags it is in, so it
+/// checks the order of map keys and the nesting depth, and can skip whole
+/// items. After an error, the position in the input is unspecified, and the
+/// decoder should not be used any further.
+pub struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    levels: [Level; MAX_DEPTH],
+    depth: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(data: &'a [u8]) -> Decoder<'a> {
+        Decoder {
+            data,
+            pos: 0,
+            levels: [Level::default(); MAX_DEPTH],
+            depth: 0,
+        }
+    }
+
+    /// Number of bytes read.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Check that the items are complete and that there is no input left.
+    pub fn finish(&self) -> Result<(), Error> {
+        if self.depth > 0 {
+            Err(Error::UnexpectedEnd)
+        } else if self.pos < self.data.len() {
+            Err(Error::TrailingData)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Read the next item, or the header of the next array, map or tag.
+    pub fn value(&mut self) -> Result<Value<'a>, Error> {
+        let start = self.pos;
+        let initial = self.read(1)?[0];
+        let major = initial >> 5;
+        let info = initial & 0x1f;
+
+        if major == major::SIMPLE {
+            let value = match info {
+                simple::FALSE => Value::Bool(false),
+                simple::TRUE => Value::Bool(true),
+                simple::NULL => Value::Null,
+                simple::UNDEFINED => Value::Undefined,
+                28..=30 => return Err(Error::Malformed),
+                _ => return Err(Error::Unsupported),
+            };
+            self.complete(start)?;
+            return Ok(value);
+        }
+
+        let argument = self.argument(info)?;
+        let value = match major {
+            major::UNSIGNED => Value::Unsigned(argument),
+            major::NEGATIVE => Value::Negative(argument),
+            major::BYTES => Value::Bytes(self.read_length(argument)?),
+            major::TEXT => {
+                let text = self.read_length(argument)?;
+                Value::Text(core::str::from_utf8(text).map_err(|_| Error::Malformed)?)
+            }
+            major::ARRAY => {
+                self.enter(start, argument, false)?;
+                return Ok(Value::Array(argument as usize));
+            }
+            major::MAP => {
+                self.enter(start, argument, true)?;
+                return Ok(Value::Map(argument as usize));
+            }
+            _ => {
+                self.enter(start, 1, false)?;
+                return Ok(Value::Tag(argument));
+            }
+        };
+        self.complete(start)?;
+        Ok(value)
+    }
+
+    /// Skip the next item, with the content of arrays, maps and tags.
+    pub fn skip(&mut self) -> Result<(), Error> {
+        let depth = self.depth;
+        loop {
+            self.value()?;
+            if self.depth <= depth {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn unsigned(&mut self) -> Result<u64, Error> {
+        match self.value()? {
+            Value::Unsigned(value) => Ok(value),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    pub fn int(&mut self) -> Result<i64, Error> {
+        match self.value()? {
+            Value::Unsigned(value) if value <= i64::max_value() as u64 => Ok(value as i64),
+            Value::Negative(value) if value <= i64::max_value() as u64 => Ok(!(value as i64)),
+            Value::Unsigned(_) | Value::Negative(_) => Err(Error::Overflow),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], Error> {
+        match self.value()? {
+            Value::Bytes(value) => Ok(value),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    pub fn text(&mut self) -> Result<&'a str, Error> {
+        match self.value()? {
+            Value::Text(value) => Ok(value),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    /// Read the header of an array, and return its number of items.
+    pub fn array(&mut self) -> Result<usize, Error> {
+        match self.value()? {
+            Value::Array(items) => Ok(items),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    /// Read the header of a map, and return its number of entries.
+    pub fn map(&mut self) -> Result<usize, Error> {
+        match self.value()? {
+            Value::Map(entries) => Ok(entries),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    /// Read a tag, which applies to the next item.
+    pub fn tag(&mut self) -> Result<u64, Error> {
+        match self.value()? {
+            Value::Tag(tag) => Ok(tag),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    pub fn bool(&mut self) -> Result<bool, Error> {
+        match self.value()? {
+            Value::Bool(value) => Ok(value),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    pub fn null(&mut self) -> Result<(), Error> {
+        match self.value()? {
+            Value::Null => Ok(()),
+            _ => Err(Error::TypeMismatch),
+        }
+    }
+
+    fn read(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.data.len() - self.pos < len {
+            return Err(Error::UnexpectedEnd);
+        }
+        let data = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(data)
+    }
+
+    fn read_length(&mut self, len: u64) -> Result<&'a [u8], Error> {
+        if len > (self.data.len() - self.pos) as u64 {
+            return Err(Error::UnexpectedEnd);
+        }
+        self.read(len as usize)
+    }
+
+    /// Read the argument of the initial byte, which must be in its shortest
+    /// encoding.
+    fn argument(&mut self, info: u8) -> Result<u64, Error> {
+        let (size, min) = match info {
+            0..=23 => return Ok(info as u64),
+            24 => (1, 24),
+            25 => (2, u8::max_value() as u64 + 1),
+            26 => (4, u16::max_value() as u64 + 1),
+            27 => (8, u32::max_value() as u64 + 1),
+            31 => return Err(Error::Unsupported),
+            _ => return Err(Error::Malformed),
+        };
+        let mut bytes = [0; 8];
+        bytes[8 - size..].copy_from_slice(self.read(size)?);
+        let argument = u64::from_be_bytes(bytes);
+        if argument < min {
+            return Err(Error::NotCanonical);
+        }
+        Ok(argument)
+    }
+
+    /// Start an array, map or tag with `items` items.
+    fn enter(&mut self, start: usize, items: u64, map: bool) -> Result<(), Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::TooDeep);
+        }
+        let remaining = if map {
+            items.checked_mul(2).ok_or(Error::UnexpectedEnd)?
+        } else {
+            items
+        };
+        // Every item takes at least a byte.
+        if remaining > (self.data.len() - self.pos) as u64 {
+            return Err(Error::UnexpectedEnd);
+        }
+        if remaining == 0 {
+            return self.complete(start);
+        }
+
+        self.levels[self.depth] = Level {
+            start,
+            remaining,
+            map,
+            last_key: None,
+        };
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Account for the item from `start` to the current position, and for
+    /// the arrays, maps and tags it completes.
+    fn complete(&mut self, mut start: usize) -> Result<(), Error> {
+        while self.depth > 0 {
+            let level = &mut self.levels[self.depth - 1];
+            if level.map && level.remaining % 2 == 0 {
+                let key = (start, self.pos);
+                if let Some(last_key) = level.last_key {
+                    if compare_keys(self.data, last_key, key) != Ordering::Less {
+                        return Err(Error::NotCanonical);
+                    }
+                }
+                level.last_key = Some(key);
+            }
+
+            level.remaining -= 1;
+            if level.remaining > 0 {
+                return Ok(());
+            }
+            start = level.start;
+            self.depth -= 1;
+        }
+        Ok(())
+    }
+}
+
+/// Canonical order of map keys: shorter encodings first, then by bytes.
+fn compare_keys(data: &[u8], a: (usize, usize), b: (usize, usize)) -> Ordering {
+    (a.1 - a.0)
+        .cmp(&(b.1 - b.0))
+        .then_with(|| data[a.0..a.1].cmp(&data[b.0..b.1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_one(data: &[u8]) -> Result<Value, Error> {
+        let mut decoder = Decoder::new(data);
+        let value = decoder.value()?;
+        decoder.finish()?;
+        Ok(value)
+    }
+
+    // RFC 7049, appendix A
+    #[test]
+    fn rfc_7049_scalars() {
+        assert_eq!(decode_one(&[0x00]), Ok(Value::Unsigned(0)));
+        assert_eq!(decode_one(&[0x17]), Ok(Value::Unsigned(23)));
+        assert_eq!(decode_one(&[0x18, 0x18]), Ok(Value::Unsigned(24)));
+        assert_eq!(decode_one(&[0x19, 0x03, 0xe8]), Ok(Value::Unsigned(1000)));
+        assert_eq!(
+            decode_one(&[0x1b, 0x00, 0x00, 0x00, 0xe8, 0xd4, 0xa5, 0x10, 0x00]),
+            Ok(Value::Unsigned(1_000_000_000_000))
+        );
+        assert_eq!(decode_one(&[0x20]), Ok(Value::Negative(0)));
+        assert_eq!(decode_one(&[0x39, 0x03, 0xe7]), Ok(Value::Negative(999)));
+        assert_eq!(decode_one(&[0xf4]), Ok(Value::Bool(false)));
+        assert_eq!(decode_one(&[0xf5]), Ok(Value::Bool(true)));
+        assert_eq!(decode_one(&[0xf6]), Ok(Value::Null));
+        assert_eq!(decode_one(&[0xf7]), Ok(Value::Undefined));
+        assert_eq!(
+            decode_one(&[0x44, 0x01, 0x02, 0x03, 0x04]),
+            Ok(Value::Bytes(&[1, 2, 3, 4]))
+        );
+        assert_eq!(decode_one(&[0x60]), Ok(Value::Text("")));
+        assert_eq!(
+            decode_one(&[0x64, 0x49, 0x45, 0x54, 0x46]),
+            Ok(Value::Text("IETF"))
+        );
+        assert_eq!(
+            decode_one(&[0x63, 0xe6, 0xb0, 0xb4]),
+            Ok(Value::Text("\u{6c34}"))
+        );
+    }
+
+    #[test]
+    fn int() {
+        assert_eq!(Decoder::new(&[0x29]).int(), Ok(-10));
+        assert_eq!(
+            Decoder::new(&[0x3b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).int(),
+            Ok(i64::min_value())
+        );
+        assert_eq!(
+            Decoder::new(&[0x3b, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]).int(),
+            Err(Error::Overflow)
+        );
+        assert_eq!(
+            Decoder::new(&[0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).int(),
+            Err(Error::Overflow)
+        );
+        assert_eq!(Decoder::new(&[0x60]).int(), Err(Error::TypeMismatch));
+    }
+
+    // RFC 7049, appendix A: {"a": 1, "b": [2, 3]}
+    #[test]
+    fn nested() {
+        let data = [0xa2, 0x61, 0x61, 0x01, 0x61, 0x62, 0x82, 0x02, 0x03];
+        let mut decoder = Decoder::new(&data);
+        assert_eq!(decoder.map(), Ok(2));
+        assert_eq!(decoder.text(), Ok("a"));
+        assert_eq!(decoder.unsigned(), Ok(1));
+        assert_eq!(decoder.text(), Ok("b"));
+        assert_eq!(decoder.finish(), Err(Error::UnexpectedEnd));
+        assert_eq!(decoder.array(), Ok(2));
+        assert_eq!(decoder.unsigned(), Ok(2));
+        assert_eq!(decoder.unsigned(), Ok(3));
+        assert_eq!(decoder.finish(), Ok(()));
+
+        let mut decoder = Decoder::new(&data);
+        assert_eq!(decoder.skip(), Ok(()));
+        assert_eq!(decoder.position(), data.len());
+
+        let mut decoder = Decoder::new(&data);
+        assert_eq!(decoder.map(), Ok(2));
+        assert_eq!(decoder.skip(), Ok(()));
+        assert_eq!(decoder.skip(), Ok(()));
+        assert_eq!(decoder.text(), Ok("b"));
+        assert_eq!(decoder.skip(), Ok(()));
+        assert_eq!(decoder.finish(), Ok(()));
+    }
+
+    #[test]
+    fn tag() {
+        let data = [0xc1, 0x1a, 0x51, 0x4b, 0x67, 0xb0];
+        let mut decoder = Decoder::new(&data);
+        assert_eq!(decoder.tag(), Ok(1));
+        assert_eq!(decoder.unsigned(), Ok(1_363_896_240));
+        assert_eq!(decoder.finish(), Ok(()));
+    }
+
+    #[test]
+    fn not_canonical() {
+        assert_eq!(decode_one(&[0x18, 0x17]), Err(Error::NotCanonical));
+        assert_eq!(decode_one(&[0x19, 0x00, 0xff]), Err(Error::NotCanonical));
+        assert_eq!(
+            decode_one(&[0x1a, 0x00, 0x00, 0xff, 0xff]),
+            Err(Error::NotCanonical)
+        );
+        assert_eq!(
+            decode_one(&[0x1b, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff]),
+            Err(Error::NotCanonical)
+        );
+        assert_eq!(decode_one(&[0x58, 0x00]), Err(Error::NotCanonical));
+    }
+
+    #[test]
+    fn map_key_order() {
+        // {1: 0, 2: 0, -1: 0, "a": 0}, in canonical order.
+        let data = [0xa4, 0x01, 0x00, 0x02, 0x00, 0x20, 0x00, 0x61, 0x61, 0x00];
+        let mut decoder = Decoder::new(&data);
+        assert_eq!(decoder.skip(), Ok(()));
+        assert_eq!(decoder.finish(), Ok(()));
+
+        // {2: 0, 1: 0}
+        let data = [0xa2, 0x02, 0x00, 0x01, 0x00];
+        assert_eq!(Decoder::new(&data).skip(), Err(Error::NotCanonical));
+        // {1: 0, 1: 0}
+        let data = [0xa2, 0x01, 0x00, 0x01, 0x00];
+        assert_eq!(Decoder::new(&data).skip(), Err(Error::NotCanonical));
+        // {24: 0, 1: 0}, longer encoding first.
+        let data = [0xa2, 0x18, 0x18, 0x00, 0x01, 0x00];
+        assert_eq!(Decoder::new(&data).skip(), Err(Error::NotCanonical));
+        // {[1]: 0, [0]: 0}, composite keys.
+        let data = [0xa2, 0x81, 0x01, 0x00, 0x81, 0x00, 0x00];
+        assert_eq!(Decoder::new(&data).skip(), Err(Error::NotCanonical));
+    }
+
+    #[test]
+    fn bounds() {
+        // Five levels of arrays.
+        let data = [0x81, 0x81, 0x81, 0x81, 0x81, 0x00];
+        assert_eq!(Decoder::new(&data).skip(), Err(Error::TooDeep));
+        // Four levels, the innermost one empty.
+        let data = [0x81, 0x81, 0x81, 0x80];
+        let mut decoder = Decoder::new(&data);
+        assert_eq!(decoder.skip(), Ok(()));
+        assert_eq!(decoder.finish(), Ok(()));
+
+        // Lengths beyond the input.
+        assert_eq!(decode_one(&[0x44, 0x01]), Err(Error::UnexpectedEnd));
+        assert_eq!(
+            decode_one(&[0x5b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            Err(Error::UnexpectedEnd)
+        );
+        assert_eq!(
+            decode_one(&[0x9a, 0x00, 0x01, 0x00, 0x00, 0x00]),
+            Err(Error::UnexpectedEnd)
+        );
+        assert_eq!(
+            decode_one(&[0xbb, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            Err(Error::UnexpectedEnd)
+        );
+        assert_eq!(decode_one(&[0x19, 0x01]), Err(Error::UnexpectedEnd));
+        assert_eq!(decode_one(&[]), Err(Error::UnexpectedEnd));
+        assert_eq!(decode_one(&[0x00, 0x00]), Err(Error::TrailingData));
+    }
+
+    #[test]
+    fn unsupported() {
+        // Indefinite-length byte string and array.
+        assert_eq!(
+            decode_one(&[0x5f, 0x41, 0x00, 0xff]),
+            Err(Error::Unsupported)
+        );
+        assert_eq!(decode_one(&[0x9f, 0xff]), Err(Error::Unsupported));
+        // Half-precision float and simple value 16.
+        assert_eq!(decode_one(&[0xf9, 0x3c, 0x00]), Err(Error::Unsupported));
+        assert_eq!(decode_one(&[0xf0]), Err(Error::Unsupported));
+        // Reserved additional information.
+        assert_eq!(decode_one(&[0x1c]), Err(Error::Malformed));
+        // Invalid UTF-8.
+        assert_eq!(decode_one(&[0x61, 0xff]), Err(Error::Malformed));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buf = [0; 64];
+        let mut encoder = crate::Encoder::new(&mut buf);
+        encoder.map(3).unwrap();
+        encoder.unsigned(1).unwrap();
+        encoder.bytes(&[0xca, 0xfe]).unwrap();
+        encoder.unsigned(2).unwrap();
+        encoder.array(2).unwrap();
+        encoder.text("es256").unwrap();
+        encoder.int(-7).unwrap();
+        encoder.unsigned(3).unwrap();
+        encoder.bool(true).unwrap();
+        let len = encoder.len();
+
+        let mut decoder = Decoder::new(&buf[..len]);
+        assert_eq!(decoder.map(), Ok(3));
+        assert_eq!(decoder.unsigned(), Ok(1));
+        assert_eq!(decoder.bytes(), Ok(&[0xca, 0xfe][..]));
+        assert_eq!(decoder.unsigned(), Ok(2));
+        assert_eq!(decoder.array(), Ok(2));
+        assert_eq!(decoder.text(), Ok("es256"));
+        assert_eq!(decoder.int(), Ok(-7));
+        assert_eq!(decoder.unsigned(), Ok(3));
+        assert_eq!(decoder.bool(), Ok(true));
+        assert_eq!(decoder.finish(), Ok(()));
+    }
+}
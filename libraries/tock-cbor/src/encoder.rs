@@ -0,0 +1,200 @@
+//! Canonical CBOR encoder.
+
+use crate::{major, simple, Error};
+
+/// Writes CBOR items into a buffer.
+///
+/// Each call writes one item, or the header of an array, map or tag, which
+/// the following calls fill. An item that does not fit is not written at
+/// all, and `BufferFull` is returned.
+pub struct Encoder<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Encoder<'a> {
+        Encoder { buf, len: 0 }
+    }
+
+    /// Number of bytes written.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn unsigned(&mut self, value: u64) -> Result<(), Error> {
+        self.header(major::UNSIGNED, value, 0)
+    }
+
+    pub fn int(&mut self, value: i64) -> Result<(), Error> {
+        if value < 0 {
+            // -1 - value, which cannot overflow.
+            self.header(major::NEGATIVE, !value as u64, 0)
+        } else {
+            self.header(major::UNSIGNED, value as u64, 0)
+        }
+    }
+
+    pub fn bytes(&mut self, value: &[u8]) -> Result<(), Error> {
+        self.header(major::BYTES, value.len() as u64, value.len())?;
+        self.append(value);
+        Ok(())
+    }
+
+    pub fn text(&mut self, value: &str) -> Result<(), Error> {
+        self.header(major::TEXT, value.len() as u64, value.len())?;
+        self.append(value.as_bytes());
+        Ok(())
+    }
+
+    /// Start an array of `items` items.
+    pub fn array(&mut self, items: usize) -> Result<(), Error> {
+        self.header(major::ARRAY, items as u64, 0)
+    }
+
+    /// Start a map of `entries` keys and values. The keys must be written
+    /// in canonical order for the decoder to accept the map.
+    pub fn map(&mut self, entries: usize) -> Result<(), Error> {
+        self.header(major::MAP, entries as u64, 0)
+    }
+
+    /// Start an item tagged with `tag`.
+    pub fn tag(&mut self, tag: u64) -> Result<(), Error> {
+        self.header(major::TAG, tag, 0)
+    }
+
+    pub fn bool(&mut self, value: bool) -> Result<(), Error> {
+        self.simple(if value { simple::TRUE } else { simple::FALSE })
+    }
+
+    pub fn null(&mut self) -> Result<(), Error> {
+        self.simple(simple::NULL)
+    }
+
+    fn simple(&mut self, value: u8) -> Result<(), Error> {
+        self.header(major::SIMPLE, value as u64, 0)
+    }
+
+    /// Write the shortest header for `value`, if there is room for it and
+    /// for `payload` more bytes.
+    fn header(&mut self, major: u8, value: u64, payload: usize) -> Result<(), Error> {
+        let (info, size) = if value < 24 {
+            (value as u8, 0)
+        } else if value <= u8::max_value() as u64 {
+            (24, 1)
+        } else if value <= u16::max_value() as u64 {
+            (25, 2)
+        } else if value <= u32::max_value() as u64 {
+            (26, 4)
+        } else {
+            (27, 8)
+        };
+        let needed = 1 + size + payload;
+        if self.buf.len() - self.len < needed {
+            return Err(Error::BufferFull);
+        }
+
+        self.append(&[major << 5 | info]);
+        self.append(&value.to_be_bytes()[8 - size..]);
+        Ok(())
+    }
+
+    fn append(&mut self, data: &[u8]) {
+        self.buf[self.len..self.len + data.len()].copy_from_slice(data);
+        self.len += data.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(f: impl FnOnce(&mut Encoder) -> Result<(), Error>) -> ([u8; 32], usize) {
+        let mut buf = [0; 32];
+        let mut encoder = Encoder::new(&mut buf);
+        f(&mut encoder).unwrap();
+        let len = encoder.len();
+        (buf, len)
+    }
+
+    fn assert_encodes(expected: &[u8], f: impl FnOnce(&mut Encoder) -> Result<(), Error>) {
+        let (buf, len) = encode(f);
+        assert_eq!(&buf[..len], expected);
+    }
+
+    // RFC 7049, appendix A
+    #[test]
+    fn rfc_7049_integers() {
+        assert_encodes(&[0x00], |e| e.unsigned(0));
+        assert_encodes(&[0x17], |e| e.unsigned(23));
+        assert_encodes(&[0x18, 0x18], |e| e.unsigned(24));
+        assert_encodes(&[0x18, 0x64], |e| e.unsigned(100));
+        assert_encodes(&[0x19, 0x03, 0xe8], |e| e.unsigned(1000));
+        assert_encodes(&[0x1a, 0x00, 0x0f, 0x42, 0x40], |e| e.unsigned(1_000_000));
+        assert_encodes(
+            &[0x1b, 0x00, 0x00, 0x00, 0xe8, 0xd4, 0xa5, 0x10, 0x00],
+            |e| e.unsigned(1_000_000_000_000),
+        );
+        assert_encodes(
+            &[0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            |e| e.unsigned(u64::max_value()),
+        );
+        assert_encodes(&[0x20], |e| e.int(-1));
+        assert_encodes(&[0x29], |e| e.int(-10));
+        assert_encodes(&[0x38, 0x63], |e| e.int(-100));
+        assert_encodes(&[0x39, 0x03, 0xe7], |e| e.int(-1000));
+        assert_encodes(
+            &[0x3b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            |e| e.int(i64::min_value()),
+        );
+    }
+
+    // RFC 7049, appendix A
+    #[test]
+    fn rfc_7049_others() {
+        assert_encodes(&[0xf4], |e| e.bool(false));
+        assert_encodes(&[0xf5], |e| e.bool(true));
+        assert_encodes(&[0xf6], |e| e.null());
+        assert_encodes(&[0x40], |e| e.bytes(&[]));
+        assert_encodes(&[0x44, 0x01, 0x02, 0x03, 0x04], |e| e.bytes(&[1, 2, 3, 4]));
+        assert_encodes(&[0x62, 0x22, 0x5c], |e| e.text("\"\\"));
+        assert_encodes(&[0x63, 0xe6, 0xb0, 0xb4], |e| e.text("\u{6c34}"));
+        assert_encodes(&[0x83, 0x01, 0x82, 0x02, 0x03, 0x82, 0x04, 0x05], |e| {
+            e.array(3)?;
+            e.unsigned(1)?;
+            e.array(2)?;
+            e.unsigned(2)?;
+            e.unsigned(3)?;
+            e.array(2)?;
+            e.unsigned(4)?;
+            e.unsigned(5)
+        });
+        assert_encodes(&[0xa2, 0x01, 0x02, 0x03, 0x04], |e| {
+            e.map(2)?;
+            e.unsigned(1)?;
+            e.unsigned(2)?;
+            e.unsigned(3)?;
+            e.unsigned(4)
+        });
+        assert_encodes(&[0xc1, 0x1a, 0x51, 0x4b, 0x67, 0xb0], |e| {
+            e.tag(1)?;
+            e.unsigned(1_363_896_240)
+        });
+    }
+
+    #[test]
+    fn buffer_full() {
+        let mut buf = [0; 4];
+        let mut encoder = Encoder::new(&mut buf);
+        encoder.unsigned(1000).unwrap();
+        assert_eq!(encoder.bytes(&[1]), Err(Error::BufferFull));
+        assert_eq!(encoder.unsigned(1000), Err(Error::BufferFull));
+        encoder.unsigned(1).unwrap();
+        assert_eq!(encoder.len(), 4);
+        assert_eq!(encoder.null(), Err(Error::BufferFull));
+    }
+}
@@ -0,0 +1,67 @@
+//! Bounded CBOR encoder and decoder for CTAP2.
+//!
+//! CTAP2 messages are encoded in a canonical subset of CBOR (RFC 7049): the
+//! shortest encodings of integers and lengths, definite lengths only, map
+//! keys sorted by the length and then the bytes of their encoding, and at
+//! most four levels of nesting. `Decoder` only accepts that subset, and
+//! `Encoder` produces it, except for the order of map keys, which is up to
+//! the caller.
+//!
+//! Neither allocates: the decoder borrows the input, and returns byte and
+//! text strings as slices of it, and the encoder writes into a borrowed
+//! buffer.
+
+#![no_std]
+
+mod decoder;
+mod encoder;
+
+pub use crate::decoder::{Decoder, Value};
+pub use crate::encoder::Encoder;
+
+/// Maximum nesting of arrays, maps and tags.
+pub const MAX_DEPTH: usize = 4;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The input ends in the middle of an item, or a length is larger than
+    /// the remaining input.
+    UnexpectedEnd,
+    /// The input is not well-formed CBOR.
+    Malformed,
+    /// The input is well-formed, but not in the canonical encoding.
+    NotCanonical,
+    /// Indefinite lengths, floats and simple values other than booleans,
+    /// null and undefined are not used by CTAP2.
+    Unsupported,
+    /// Items are nested deeper than `MAX_DEPTH` levels.
+    TooDeep,
+    /// The item is not of the requested type.
+    TypeMismatch,
+    /// The integer does not fit in the requested type.
+    Overflow,
+    /// There is input left after the last item.
+    TrailingData,
+    /// The output buffer is too small.
+    BufferFull,
+}
+
+/// Major types, in the 3 high bits of the initial byte of an item.
+mod major {
+    pub const UNSIGNED: u8 = 0;
+    pub const NEGATIVE: u8 = 1;
+    pub const BYTES: u8 = 2;
+    pub const TEXT: u8 = 3;
+    pub const ARRAY: u8 = 4;
+    pub const MAP: u8 = 5;
+    pub const TAG: u8 = 6;
+    pub const SIMPLE: u8 = 7;
+}
+
+/// Simple values, in the 5 low bits of the initial byte of major type 7.
+mod simple {
+    pub const FALSE: u8 = 20;
+    pub const TRUE: u8 = 21;
+    pub const NULL: u8 = 22;
+    pub const UNDEFINED: u8 = 23;
+}
@@ -0,0 +1,94 @@
+#!/usr/bin/env python3
+"""Call the functions of the host RPC capsule (capsules/src/host_rpc.rs).
+
+Examples:
+
+    host_rpc.py /dev/ttyACM0 ping 0102
+    host_rpc.py /dev/ttyACM0 kat
+    host_rpc.py /dev/ttyACM0 metrics irq
+    host_rpc.py /dev/ttyACM0 hash 0 0 4096
+    host_rpc.py /dev/ttyACM0 pin 0 toggle
+
+Requires pyserial.
+"""
+
+import argparse
+import struct
+import sys
+import time
+
+import serial
+
+PING = 0x00
+KAT = 0x01
+METRICS = 0x02
+HASH = 0x03
+PIN = 0x04
+PIN_OPS = {'clear': 0, 'set': 1, 'toggle': 2, 'read': 3}
+# States of the known-answer tests of the hash engine
+ENGINE_PASSED = 3
+ENGINE_FAILED = 4
+
+
+class RpcError(Exception):
+    pass
+
+
+def call(port, function, args=b''):
+    """Send one request and return the result of the response."""
+    request = bytes([function]) + args
+    port.write(struct.pack('<H', len(request)) + request)
+    header = port.read(2)
+    if len(header) != 2:
+        raise RpcError('timeout')
+    (length,) = struct.unpack('<H', header)
+    body = port.read(length)
+    if len(body) != length or length == 0:
+        raise RpcError('short response')
+    status = struct.unpack('<b', body[:1])[0]
+    if status < 0:
+        raise RpcError('status {}'.format(status))
+    return body[1:]
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument('port')
+    parser.add_argument('--baud', type=int, default=115200)
+    parser.add_argument('function', choices=['ping', 'kat', 'metrics', 'hash', 'pin'])
+    parser.add_argument('args', nargs='*')
+    args = parser.parse_args()
+
+    with serial.Serial(args.port, args.baud, timeout=2) as port:
+        if args.function == 'ping':
+            print(call(port, PING, bytes.fromhex(''.join(args.args))).hex())
+        elif args.function == 'kat':
+            result = call(port, KAT)
+            for name, failed in zip(['SHA-256', 'HMAC-SHA256'], result):
+                print('{}: {}'.format(name, 'FAILED' if failed else 'passed'))
+            if len(result) > 2:
+                # The first call starts the tests of the engine
+                for _ in range(10):
+                    if result[2] in (ENGINE_PASSED, ENGINE_FAILED):
+                        break
+                    time.sleep(0.1)
+                    result = call(port, KAT)
+                state = {ENGINE_PASSED: 'passed', ENGINE_FAILED: 'FAILED'}
+                print('hash engine: {}'.format(state.get(result[2], 'no answer')))
+        elif args.function == 'metrics':
+            result = call(port, METRICS, args.args[0].encode())
+            for key, value in struct.iter_unpack('<II', result):
+                print('{}: {}'.format(key, value))
+        elif args.function == 'hash':
+            region, offset, length = (int(a, 0) for a in args.args)
+            print(call(port, HASH, struct.pack('<BII', region, offset, length)).hex())
+        elif args.function == 'pin':
+            pin, op = int(args.args[0]), PIN_OPS[args.args[1]]
+            print(call(port, PIN, bytes([pin, op]))[0])
+
+
+if __name__ == '__main__':
+    try:
+        main()
+    except RpcError as e:
+        sys.exit('error: {}'.format(e))